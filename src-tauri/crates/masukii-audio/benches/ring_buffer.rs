@@ -0,0 +1,54 @@
+//! Ring buffer throughput, producer and consumer on separate threads as in
+//! playback. `cargo bench -p masukii-audio`; no harness, so it runs on
+//! stable.
+
+use std::sync::Arc;
+use std::thread;
+use std::time::Instant;
+
+use masukii_audio::ring_buffer::RingBuffer;
+
+/// About 10 minutes of 44.1 kHz stereo.
+const SAMPLES: usize = 44_100 * 2 * 600;
+
+fn main() {
+    for (capacity, chunk) in [(1 << 14, 512), (1 << 16, 4096), (1 << 18, 4096)] {
+        let ring = Arc::new(RingBuffer::new(capacity));
+        let start = Instant::now();
+
+        let producer = {
+            let ring = ring.clone();
+            thread::spawn(move || {
+                let data = vec![0.25f32; chunk];
+                let mut written = 0;
+                while written < SAMPLES {
+                    let n = ring.write(&data[..chunk.min(SAMPLES - written)]);
+                    if n == 0 {
+                        thread::yield_now();
+                    }
+                    written += n;
+                }
+            })
+        };
+
+        let mut out = vec![0.0f32; chunk];
+        let mut read = 0;
+        while read < SAMPLES {
+            let n = ring.read(&mut out);
+            if n == 0 {
+                thread::yield_now();
+            }
+            read += n;
+        }
+        producer.join().unwrap();
+
+        let secs = start.elapsed().as_secs_f64();
+        println!(
+            "capacity {:>6}, chunks of {:>4}: {:>7.1} M samples/s ({:.0}x realtime)",
+            capacity,
+            chunk,
+            SAMPLES as f64 / secs / 1e6,
+            SAMPLES as f64 / secs / (44_100.0 * 2.0),
+        );
+    }
+}
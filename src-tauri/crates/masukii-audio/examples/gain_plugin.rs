@@ -0,0 +1,102 @@
+//! A DSP plugin in Rust: gain and a channel balance. Build it with
+//! `cargo build -p masukii-audio --example gain_plugin` and copy the library
+//! from `target/debug/examples` into the app's plugins folder.
+
+use std::ffi::c_void;
+
+use masukii_audio::dsp_plugin::{RawParam, RawPlugin, ABI_VERSION};
+
+struct Gain {
+    channels: usize,
+    gain_db: f32,
+    balance: f32,
+}
+
+/// Raw pointers aren't `Sync`, but these only point at string literals.
+struct Descriptor<T>(T);
+unsafe impl<T> Sync for Descriptor<T> {}
+
+static PARAMS: Descriptor<[RawParam; 2]> = Descriptor([
+    RawParam {
+        id: c"gain".as_ptr(),
+        name: c"Gain".as_ptr(),
+        unit: c"dB".as_ptr(),
+        min: -24.0,
+        max: 12.0,
+        default_value: 0.0,
+        step: 0.0,
+    },
+    RawParam {
+        id: c"balance".as_ptr(),
+        name: c"Balance".as_ptr(),
+        unit: std::ptr::null(),
+        min: -1.0,
+        max: 1.0,
+        default_value: 0.0,
+        step: 0.0,
+    },
+]);
+
+static PLUGIN: Descriptor<RawPlugin> = Descriptor(RawPlugin {
+    abi_version: ABI_VERSION,
+    id: c"example.gain".as_ptr(),
+    name: c"Gain".as_ptr(),
+    version: c"1.0.0".as_ptr(),
+    param_count: 2,
+    params: PARAMS.0.as_ptr(),
+    create: Some(create),
+    destroy: Some(destroy),
+    set_param: Some(set_param),
+    process: Some(process),
+    reset: None,
+});
+
+#[no_mangle]
+pub extern "C" fn masukii_dsp_plugin() -> *const RawPlugin {
+    &PLUGIN.0
+}
+
+unsafe extern "C" fn create(_sample_rate: u32, channels: u32) -> *mut c_void {
+    if channels == 0 {
+        return std::ptr::null_mut();
+    }
+    let gain = Gain {
+        channels: channels as usize,
+        gain_db: 0.0,
+        balance: 0.0,
+    };
+    Box::into_raw(Box::new(gain)) as *mut c_void
+}
+
+unsafe extern "C" fn destroy(instance: *mut c_void) {
+    drop(Box::from_raw(instance as *mut Gain));
+}
+
+unsafe extern "C" fn set_param(instance: *mut c_void, index: u32, value: f32) {
+    let gain = &mut *(instance as *mut Gain);
+    match index {
+        0 => gain.gain_db = value,
+        _ => gain.balance = value,
+    }
+}
+
+unsafe extern "C" fn process(instance: *mut c_void, samples: *mut f32, frames: usize) -> i32 {
+    let gain = &*(instance as *const Gain);
+    let samples = std::slice::from_raw_parts_mut(samples, frames * gain.channels);
+    let level = 10f32.powf(gain.gain_db / 20.0);
+    // Balance only turns the other side down, and only for stereo
+    let (left, right) = match gain.channels {
+        2 => (
+            level * (1.0 - gain.balance.max(0.0)),
+            level * (1.0 + gain.balance.min(0.0)),
+        ),
+        _ => (level, level),
+    };
+    for frame in samples.chunks_exact_mut(gain.channels) {
+        frame[0] *= left;
+        for s in frame[1..].iter_mut() {
+            *s *= right;
+        }
+    }
+    0
+}
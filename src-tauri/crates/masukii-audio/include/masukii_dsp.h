@@ -0,0 +1,70 @@
+/*
+ * Masukii DSP plugin interface, version 1.
+ *
+ * A plugin is a shared library in the app's plugins folder exporting
+ * masukii_dsp_plugin(). It returns a descriptor that stays valid as long as
+ * the library is loaded. Instances run on the decoder thread, one per
+ * track, on interleaved 32-bit float samples at the output's rate and
+ * channel count.
+ *
+ * process() returns 0, or nonzero on failure. A failing instance, or one
+ * that outputs NaN, infinities or samples beyond +-4.0, or takes longer
+ * than real time repeatedly, is bypassed for the rest of the track.
+ * Functions must not throw or unwind.
+ */
+
+#ifndef MASUKII_DSP_H
+#define MASUKII_DSP_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+#define MASUKII_DSP_ABI_VERSION 1
+
+typedef struct MasukiiDspParam {
+    /* Stable key, for saved settings */
+    const char *id;
+    const char *name;
+    /* e.g. "dB", "Hz", "%"; may be NULL */
+    const char *unit;
+    float min;
+    float max;
+    float default_value;
+    /* 0 for continuous; 1 over 0-1 for a switch */
+    float step;
+} MasukiiDspParam;
+
+typedef struct MasukiiDspPlugin {
+    uint32_t abi_version;
+    const char *id;
+    const char *name;
+    const char *version;
+    uint32_t param_count;
+    const MasukiiDspParam *params;
+    /* An instance for this format, or NULL if it isn't supported */
+    void *(*create)(uint32_t sample_rate, uint32_t channels);
+    void (*destroy)(void *instance);
+    void (*set_param)(void *instance, uint32_t index, float value);
+    /* Process `frames` interleaved frames in place */
+    int32_t (*process)(void *instance, float *samples, size_t frames);
+    /* Clear filter history after a seek; may be NULL */
+    void (*reset)(void *instance);
+} MasukiiDspPlugin;
+
+#if defined(_WIN32)
+#define MASUKII_DSP_EXPORT __declspec(dllexport)
+#else
+#define MASUKII_DSP_EXPORT __attribute__((visibility("default")))
+#endif
+
+MASUKII_DSP_EXPORT const MasukiiDspPlugin *masukii_dsp_plugin(void);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* MASUKII_DSP_H */
@@ -0,0 +1,141 @@
+//! Bit-activity meter: which bits of the output samples are in use, like a
+//! DAW bit meter.
+//!
+//! The output callback feeds the meter the samples it hands the device
+//! (after volume, like [`crate::levels`]), except during pause, resume and
+//! underrun fades, which would always fill in the low bits. Each sample is
+//! read as a 32-bit signed PCM word and the bits of its magnitude are OR-ed
+//! together per channel, lock-free. A 16-bit file played bit-perfectly only
+//! ever sets the top 16 bits; a 24-bit file that sets just as few is 16-bit
+//! audio padded out; bits that never toggle below some point mean truncation
+//! upstream. Any volume change or ReplayGain fills in the low bits, and
+//! usually leaves fractions below the 32nd bit, which are flagged separately.
+//!
+//! Two sets of bits are kept: since the previous read, for a live display,
+//! and since the track started, which settles on the track's real depth.
+
+use serde::Serialize;
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+
+use crate::levels::MAX_METER_CHANNELS;
+
+/// Word length the samples are measured against.
+pub const WORD_BITS: u32 = 32;
+
+/// 1.0 as a 32-bit PCM word.
+const FULL_SCALE: f64 = (1u64 << (WORD_BITS - 1)) as f64;
+
+#[derive(Clone, Serialize)]
+pub struct ChannelBits {
+    /// Bits set since the previous read, most significant first
+    /// ([`WORD_BITS`] entries).
+    pub active: Vec<bool>,
+    /// Bits set since the track started, most significant first.
+    pub track_active: Vec<bool>,
+    /// Bits in use since the track started, counting down from the most
+    /// significant to the lowest active one: 16 for 16-bit audio played
+    /// untouched. 0 while silent.
+    pub effective_bits: u32,
+    /// Some sample since the track started was finer than a 32-bit word
+    /// can hold: the float output was processed.
+    pub below_word: bool,
+}
+
+#[derive(Clone, Serialize)]
+pub struct BitActivity {
+    /// One entry per output channel; empty when nothing has played.
+    pub channels: Vec<ChannelBits>,
+}
+
+pub struct BitMeter {
+    channels: AtomicU32,
+    /// Magnitude bits per channel since the last read, and since the track
+    /// started.
+    recent: [AtomicU32; MAX_METER_CHANNELS],
+    track: [AtomicU32; MAX_METER_CHANNELS],
+    below_word: [AtomicBool; MAX_METER_CHANNELS],
+}
+
+impl Default for BitMeter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl BitMeter {
+    pub fn new() -> Self {
+        Self {
+            channels: AtomicU32::new(0),
+            recent: std::array::from_fn(|_| AtomicU32::new(0)),
+            track: std::array::from_fn(|_| AtomicU32::new(0)),
+            below_word: std::array::from_fn(|_| AtomicBool::new(false)),
+        }
+    }
+
+    /// Start metering a new track with `channels` channels.
+    pub fn start_track(&self, channels: usize) {
+        self.channels
+            .store(channels.min(MAX_METER_CHANNELS) as u32, Ordering::Relaxed);
+        for c in 0..MAX_METER_CHANNELS {
+            self.recent[c].store(0, Ordering::Relaxed);
+            self.track[c].store(0, Ordering::Relaxed);
+            self.below_word[c].store(false, Ordering::Relaxed);
+        }
+    }
+
+    /// Add a block of interleaved output samples. Called from the audio
+    /// callback: no locks, no allocations.
+    #[inline]
+    pub fn feed(&self, samples: &[f32], channels: usize) {
+        let metered = channels.min(MAX_METER_CHANNELS);
+        if metered == 0 {
+            return;
+        }
+        let mut bits = [0u32; MAX_METER_CHANNELS];
+        let mut below_word = [false; MAX_METER_CHANNELS];
+        for frame in samples.chunks_exact(channels) {
+            for (c, &s) in frame[..metered].iter().enumerate() {
+                if !s.is_finite() {
+                    continue;
+                }
+                // Exact: an f32 fits in an f64 and the scale is a power of two
+                let word = f64::from(s) * FULL_SCALE;
+                below_word[c] |= word.fract() != 0.0;
+                bits[c] |= word.abs().min(u32::MAX as f64) as u32;
+            }
+        }
+        for c in 0..metered {
+            self.recent[c].fetch_or(bits[c], Ordering::Relaxed);
+            self.track[c].fetch_or(bits[c], Ordering::Relaxed);
+            if below_word[c] {
+                self.below_word[c].store(true, Ordering::Relaxed);
+            }
+        }
+    }
+
+    /// Bits in use per channel; clears the since-last-read bits.
+    pub fn activity(&self) -> BitActivity {
+        let channels = self.channels.load(Ordering::Relaxed) as usize;
+        let channels = (0..channels)
+            .map(|c| {
+                let recent = self.recent[c].swap(0, Ordering::Relaxed);
+                let track = self.track[c].load(Ordering::Relaxed);
+                ChannelBits {
+                    active: msb_first(recent),
+                    track_active: msb_first(track),
+                    effective_bits: if track == 0 {
+                        0
+                    } else {
+                        WORD_BITS - track.trailing_zeros()
+                    },
+                    below_word: self.below_word[c].load(Ordering::Relaxed),
+                }
+            })
+            .collect();
+        BitActivity { channels }
+    }
+}
+
+fn msb_first(bits: u32) -> Vec<bool> {
+    (0..WORD_BITS).rev().map(|i| bits & (1 << i) != 0).collect()
+}
@@ -0,0 +1,141 @@
+//! Clipping counter: samples that reach or pass full scale, and samples the
+//! hard limiter has to catch, counted per track.
+//!
+//! The output callback feeds the counter the samples it is about to play
+//! with the volume it applies, so a hot master (over full scale with volume
+//! at 1.0, passed through untouched in bit-perfect mode) and too much
+//! ReplayGain boost (caught by the limiter at ±0.99) both show. Fades are
+//! left out, like the bit meter. Counting is lock-free; when the next track
+//! starts, the finished track's counts go into a per-track report.
+
+use parking_lot::Mutex;
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::engine::HARD_LIMIT_CEILING;
+
+/// Tracks kept in the report; older ones are dropped.
+const MAX_TRACKS: usize = 200;
+
+#[derive(Clone, Serialize)]
+pub struct TrackClipping {
+    pub file: String,
+    /// Unix time in milliseconds.
+    pub started_at_ms: u64,
+    /// Samples counted, over all channels.
+    pub samples: u64,
+    /// Samples at or over full scale (±1.0) after volume.
+    pub clipped_samples: u64,
+    /// Samples over the limiter's ceiling, which it cut down. Always 0 in
+    /// bit-perfect mode, where the limiter is bypassed.
+    pub limited_samples: u64,
+    /// Still playing (or the last track played).
+    pub current: bool,
+}
+
+struct Track {
+    file: String,
+    started_at_ms: u64,
+}
+
+pub struct ClipCounter {
+    samples: AtomicU64,
+    clipped: AtomicU64,
+    limited: AtomicU64,
+    track: Mutex<Option<Track>>,
+    finished: Mutex<VecDeque<TrackClipping>>,
+}
+
+impl Default for ClipCounter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ClipCounter {
+    pub fn new() -> Self {
+        Self {
+            samples: AtomicU64::new(0),
+            clipped: AtomicU64::new(0),
+            limited: AtomicU64::new(0),
+            track: Mutex::new(None),
+            finished: Mutex::new(VecDeque::new()),
+        }
+    }
+
+    /// Start counting for `file`, filing the previous track's counts.
+    /// Called from the engine thread.
+    pub fn start_track(&self, file: &str) {
+        let mut track = self.track.lock();
+        if let Some(done) = self.counts(track.as_ref(), false) {
+            let mut finished = self.finished.lock();
+            if finished.len() == MAX_TRACKS {
+                finished.pop_front();
+            }
+            finished.push_back(done);
+        }
+        self.samples.store(0, Ordering::Relaxed);
+        self.clipped.store(0, Ordering::Relaxed);
+        self.limited.store(0, Ordering::Relaxed);
+        *track = Some(Track {
+            file: file.to_string(),
+            started_at_ms: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_millis() as u64)
+                .unwrap_or(0),
+        });
+    }
+
+    /// Count a block of samples about to be played at `gain`, through the
+    /// limiter when `limiting`. Called from the audio callback: no locks,
+    /// no allocations.
+    #[inline]
+    pub fn feed(&self, samples: &[f32], gain: f32, limiting: bool) {
+        let mut clipped = 0u64;
+        let mut limited = 0u64;
+        for &s in samples {
+            let level = (s * gain).abs();
+            clipped += u64::from(level >= 1.0);
+            limited += u64::from(limiting && level > HARD_LIMIT_CEILING);
+        }
+        self.samples
+            .fetch_add(samples.len() as u64, Ordering::Relaxed);
+        if clipped > 0 {
+            self.clipped.fetch_add(clipped, Ordering::Relaxed);
+        }
+        if limited > 0 {
+            self.limited.fetch_add(limited, Ordering::Relaxed);
+        }
+    }
+
+    /// Counts for the current track so far: (clipped, limited).
+    pub fn current(&self) -> (u64, u64) {
+        (
+            self.clipped.load(Ordering::Relaxed),
+            self.limited.load(Ordering::Relaxed),
+        )
+    }
+
+    /// Every track played this session (up to the last [`MAX_TRACKS`]),
+    /// oldest first, ending with the current one.
+    pub fn report(&self) -> Vec<TrackClipping> {
+        let track = self.track.lock();
+        let mut report: Vec<TrackClipping> = self.finished.lock().iter().cloned().collect();
+        report.extend(self.counts(track.as_ref(), true));
+        report
+    }
+
+    fn counts(&self, track: Option<&Track>, current: bool) -> Option<TrackClipping> {
+        let track = track?;
+        Some(TrackClipping {
+            file: track.file.clone(),
+            started_at_ms: track.started_at_ms,
+            samples: self.samples.load(Ordering::Relaxed),
+            clipped_samples: self.clipped.load(Ordering::Relaxed),
+            limited_samples: self.limited.load(Ordering::Relaxed),
+            current,
+        })
+    }
+}
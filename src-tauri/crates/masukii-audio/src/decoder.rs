@@ -1,13 +1,16 @@
 use std::fs::File;
 use std::path::Path;
-use symphonia::core::audio::{AudioBufferRef, SampleBuffer, SignalSpec};
+use symphonia::core::audio::{SampleBuffer, SignalSpec};
 use symphonia::core::codecs::{DecoderOptions, CODEC_TYPE_NULL};
 use symphonia::core::errors::Error as SymphoniaError;
 use symphonia::core::formats::{FormatOptions, FormatReader, SeekMode, SeekTo};
-use symphonia::core::io::MediaSourceStream;
+use symphonia::core::io::{MediaSource, MediaSourceStream};
 use symphonia::core::meta::MetadataOptions;
 use symphonia::core::probe::Hint;
-use symphonia::core::units::Time;
+use symphonia::core::units::{Time, TimeBase};
+
+use crate::http_source::{self, HttpSource};
+use crate::paths::{self, OPEN_TIMEOUT};
 
 pub struct AudioDecoder {
     format: Box<dyn FormatReader>,
@@ -16,17 +19,42 @@ pub struct AudioDecoder {
     pub spec: SignalSpec,
     pub duration_secs: f64,
     bit_depth: Option<u8>,
+    /// Symphonia's short codec name, e.g. `flac`.
+    codec_name: Option<&'static str>,
+    time_base: Option<TimeBase>,
+    /// Frames to drop from the next decoded packets after a seek. Symphonia seeks
+    /// land on a packet boundary at or before the target; trimming makes seeks
+    /// (and cue-sheet track starts) sample-accurate.
+    skip_frames: usize,
 }
 
 impl AudioDecoder {
+    /// Open a file, or stream an `http(s)://` URL.
     pub fn open(path: &str) -> Result<Self, String> {
-        let file = File::open(path).map_err(|e| format!("Failed to open file: {}", e))?;
-        let mss = MediaSourceStream::new(Box::new(file), Default::default());
-
         let mut hint = Hint::new();
-        if let Some(ext) = Path::new(path).extension().and_then(|e| e.to_str()) {
-            hint.with_extension(ext);
-        }
+        let source: Box<dyn MediaSource> = if http_source::is_url(path) {
+            let source = HttpSource::open(path)?;
+            if let Some(ext) = http_source::url_extension(path) {
+                hint.with_extension(ext);
+            }
+            if let Some(mime) = source.content_type() {
+                hint.mime_type(mime);
+            }
+            Box::new(source)
+        } else {
+            // A file on a share that stopped answering must not hang the caller
+            let file = paths::with_timeout(OPEN_TIMEOUT, {
+                let path = path.to_string();
+                move || File::open(path)
+            })
+            .map_err(|e| format!("File not reachable: {}", e))?
+            .map_err(|e| format!("Failed to open file: {}", e))?;
+            if let Some(ext) = Path::new(path).extension().and_then(|e| e.to_str()) {
+                hint.with_extension(ext);
+            }
+            Box::new(file)
+        };
+        let mss = MediaSourceStream::new(source, Default::default());
 
         let meta_opts = MetadataOptions::default();
         let fmt_opts = FormatOptions {
@@ -55,10 +83,10 @@ impl AudioDecoder {
 
         let spec = SignalSpec::new(
             track.codec_params.sample_rate.unwrap_or(44100),
-            track
-                .codec_params
-                .channels
-                .unwrap_or(symphonia::core::audio::Channels::FRONT_LEFT | symphonia::core::audio::Channels::FRONT_RIGHT),
+            track.codec_params.channels.unwrap_or(
+                symphonia::core::audio::Channels::FRONT_LEFT
+                    | symphonia::core::audio::Channels::FRONT_RIGHT,
+            ),
         );
 
         let duration_secs = if let Some(n_frames) = track.codec_params.n_frames {
@@ -69,6 +97,10 @@ impl AudioDecoder {
         };
 
         let bit_depth = track.codec_params.bits_per_sample.map(|b| b as u8);
+        let codec_name = symphonia::default::get_codecs()
+            .get_codec(track.codec_params.codec)
+            .map(|c| c.short_name);
+        let time_base = track.codec_params.time_base;
 
         Ok(Self {
             format,
@@ -77,6 +109,9 @@ impl AudioDecoder {
             spec,
             duration_secs,
             bit_depth,
+            codec_name,
+            time_base,
+            skip_frames: 0,
         })
     }
 
@@ -92,6 +127,10 @@ impl AudioDecoder {
         self.bit_depth
     }
 
+    pub fn codec_name(&self) -> Option<&'static str> {
+        self.codec_name
+    }
+
     /// Decode the next packet, returning interleaved f32 samples.
     pub fn next_samples(&mut self) -> Result<Vec<f32>, DecodeStatus> {
         loop {
@@ -120,20 +159,41 @@ impl AudioDecoder {
             let mut sample_buf = SampleBuffer::<f32>::new(num_frames as u64, spec);
             sample_buf.copy_interleaved_ref(decoded);
 
-            return Ok(sample_buf.samples().to_vec());
+            let mut samples = sample_buf.samples().to_vec();
+            if self.skip_frames > 0 {
+                let skip = self.skip_frames.min(num_frames);
+                samples.drain(..skip * spec.channels.count());
+                self.skip_frames -= skip;
+                if samples.is_empty() {
+                    continue;
+                }
+            }
+
+            return Ok(samples);
         }
     }
 
-    /// Seek to a position in seconds.
+    /// Seek to a position in seconds (sample-accurate).
     pub fn seek(&mut self, position_secs: f64) -> Result<(), String> {
+        let position_secs = position_secs.max(0.0);
         let seek_to = SeekTo::Time {
-            time: Time::new(position_secs as u64, (position_secs.fract() * 1_000_000_000.0) as u32),
+            time: Time::new(position_secs as u64, position_secs.fract()),
             track_id: Some(self.track_id),
         };
-        self.format
+        let seeked = self
+            .format
             .seek(SeekMode::Accurate, seek_to)
             .map_err(|e| format!("Seek failed: {}", e))?;
         self.decoder.reset();
+
+        let lead_in = seeked.required_ts.saturating_sub(seeked.actual_ts);
+        self.skip_frames = match self.time_base {
+            Some(tb) => {
+                let t = tb.calc_time(lead_in);
+                ((t.seconds as f64 + t.frac) * self.spec.rate as f64).round() as usize
+            }
+            None => lead_in as usize,
+        };
         Ok(())
     }
 }
@@ -0,0 +1,232 @@
+//! Rolling history of the engine's diagnostics, for graphs: buffer fill,
+//! output latency, audio callback load, decoder CPU and dropouts, one point a
+//! second over the last five minutes of playback.
+//!
+//! Callback load is the time the output callback takes as a share of the
+//! audio it produces; near 100% the callback can't keep up and the device
+//! starves. Decoder CPU is the decoder thread's CPU time as a share of one
+//! core. The callback and the decoder add their timings to atomics; the
+//! engine thread's idle tick folds them into a point each second, and the
+//! latest point's figures also go into [`crate::engine::AudioDiagnostics`].
+
+use parking_lot::Mutex;
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicU32, AtomicU64, Ordering};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use crate::resource_usage;
+
+/// Time between points...
+const SAMPLE_INTERVAL: Duration = Duration::from_secs(1);
+
+/// ...and points kept: five minutes.
+const HISTORY_LEN: usize = 300;
+
+#[derive(Clone, Serialize)]
+pub struct DiagnosticsSample {
+    /// Unix time in milliseconds.
+    pub timestamp_ms: u64,
+    /// Lowest ring buffer fill (0–100) during the interval.
+    pub buffer_fill_pct: f32,
+    /// Audio buffered ahead of the output at the end of the interval.
+    pub latency_ms: f64,
+    /// Output callback time as a share of the audio it produced (0–100):
+    /// the interval's average, and its slowest callback.
+    pub callback_load_pct: f32,
+    pub callback_peak_pct: f32,
+    /// Decoder thread CPU time as a share of one core (0–100); `None` where
+    /// the OS gives no per-thread reading.
+    pub decoder_cpu_pct: Option<f32>,
+    /// Dropouts during the interval.
+    pub dropouts: u64,
+}
+
+/// Output callback execution times over the last interval, in microseconds.
+#[derive(Clone, Copy)]
+pub struct CallbackTimes {
+    pub min_us: f64,
+    pub avg_us: f64,
+    pub max_us: f64,
+}
+
+/// Decoder and callback figures of the latest point.
+#[derive(Clone, Copy)]
+pub struct ThreadUsage {
+    pub decoder_cpu_pct: Option<f32>,
+    pub callback_times: Option<CallbackTimes>,
+}
+
+/// Callback figures folded from the atomics.
+struct CallbackStats {
+    load_pct: f32,
+    peak_pct: f32,
+    times: Option<CallbackTimes>,
+}
+
+struct History {
+    samples: VecDeque<DiagnosticsSample>,
+    /// `None` until the first point of this playback.
+    usage: Option<ThreadUsage>,
+    last_sample: Option<Instant>,
+    min_fill: Option<f32>,
+    last_dropout_count: u64,
+}
+
+pub struct DiagnosticsHistory {
+    /// Callback time and audio time since the last point, in nanoseconds.
+    busy_ns: AtomicU64,
+    audio_ns: AtomicU64,
+    /// Highest single-callback load since the last point, as f32 bits.
+    peak_load: AtomicU32,
+    /// Callbacks since the last point, and the quickest and slowest.
+    callbacks: AtomicU64,
+    min_busy_ns: AtomicU64,
+    max_busy_ns: AtomicU64,
+    /// Decoder thread CPU time since the last point, in nanoseconds.
+    decoder_cpu_ns: AtomicU64,
+    history: Mutex<History>,
+}
+
+impl Default for DiagnosticsHistory {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl DiagnosticsHistory {
+    pub fn new() -> Self {
+        Self {
+            busy_ns: AtomicU64::new(0),
+            audio_ns: AtomicU64::new(0),
+            peak_load: AtomicU32::new(0),
+            callbacks: AtomicU64::new(0),
+            min_busy_ns: AtomicU64::new(u64::MAX),
+            max_busy_ns: AtomicU64::new(0),
+            decoder_cpu_ns: AtomicU64::new(0),
+            history: Mutex::new(History {
+                samples: VecDeque::new(),
+                usage: None,
+                last_sample: None,
+                min_fill: None,
+                last_dropout_count: 0,
+            }),
+        }
+    }
+
+    /// Record one output callback: how long it took and how much audio it
+    /// produced. Called from the audio callback: no locks, no allocations.
+    #[inline]
+    pub fn record_callback(&self, busy: Duration, audio_ns: u64) {
+        if audio_ns == 0 {
+            return;
+        }
+        let busy_ns = busy.as_nanos() as u64;
+        self.busy_ns.fetch_add(busy_ns, Ordering::Relaxed);
+        self.audio_ns.fetch_add(audio_ns, Ordering::Relaxed);
+        // Non-negative floats order like their bit patterns
+        let load = busy_ns as f32 / audio_ns as f32 * 100.0;
+        self.peak_load.fetch_max(load.to_bits(), Ordering::Relaxed);
+        self.callbacks.fetch_add(1, Ordering::Relaxed);
+        self.min_busy_ns.fetch_min(busy_ns, Ordering::Relaxed);
+        self.max_busy_ns.fetch_max(busy_ns, Ordering::Relaxed);
+    }
+
+    /// Add CPU time the decoder thread used. Called from the decoder thread.
+    pub fn add_decoder_cpu(&self, cpu: Duration) {
+        self.decoder_cpu_ns
+            .fetch_add(cpu.as_nanos() as u64, Ordering::Relaxed);
+    }
+
+    /// Called from the engine thread on every idle tick; adds a point once
+    /// per [`SAMPLE_INTERVAL`] while playing.
+    pub fn tick(&self, playing: bool, fill_pct: f32, latency_ms: f64, dropout_count: u64) {
+        let mut history = self.history.lock();
+        if !playing {
+            history.last_sample = None;
+            history.min_fill = None;
+            history.usage = None;
+            return;
+        }
+        history.min_fill = Some(history.min_fill.map_or(fill_pct, |m| m.min(fill_pct)));
+        let Some(last) = history.last_sample else {
+            // Start of playback: begin the first interval now
+            history.last_sample = Some(Instant::now());
+            history.last_dropout_count = dropout_count;
+            self.take_callback_stats();
+            self.decoder_cpu_ns.store(0, Ordering::Relaxed);
+            return;
+        };
+        let elapsed = last.elapsed();
+        if elapsed < SAMPLE_INTERVAL {
+            return;
+        }
+
+        let callback = self.take_callback_stats();
+        let decoder_cpu_ns = self.decoder_cpu_ns.swap(0, Ordering::Relaxed);
+        let decoder_cpu_pct = resource_usage::THREAD_CPU_SUPPORTED
+            .then(|| decoder_cpu_ns as f32 / elapsed.as_nanos() as f32 * 100.0);
+        history.usage = Some(ThreadUsage {
+            decoder_cpu_pct,
+            callback_times: callback.times,
+        });
+        // The engine's count restarts with each track
+        let dropouts = dropout_count.saturating_sub(history.last_dropout_count);
+        history.last_dropout_count = dropout_count;
+        history.last_sample = Some(Instant::now());
+        let buffer_fill_pct = history.min_fill.take().unwrap_or(fill_pct);
+        if history.samples.len() == HISTORY_LEN {
+            history.samples.pop_front();
+        }
+        history.samples.push_back(DiagnosticsSample {
+            timestamp_ms: unix_ms(),
+            buffer_fill_pct,
+            latency_ms,
+            callback_load_pct: callback.load_pct,
+            callback_peak_pct: callback.peak_pct,
+            decoder_cpu_pct,
+            dropouts,
+        });
+    }
+
+    /// Points over the last five minutes of playback, oldest first.
+    pub fn samples(&self) -> Vec<DiagnosticsSample> {
+        self.history.lock().samples.iter().cloned().collect()
+    }
+
+    /// Decoder CPU and callback times over the last second; `None` when not
+    /// playing or in the first second.
+    pub fn usage(&self) -> Option<ThreadUsage> {
+        self.history.lock().usage
+    }
+
+    /// Callback load and times since the last call.
+    fn take_callback_stats(&self) -> CallbackStats {
+        let busy = self.busy_ns.swap(0, Ordering::Relaxed);
+        let audio = self.audio_ns.swap(0, Ordering::Relaxed);
+        let peak = f32::from_bits(self.peak_load.swap(0, Ordering::Relaxed));
+        let callbacks = self.callbacks.swap(0, Ordering::Relaxed);
+        let min = self.min_busy_ns.swap(u64::MAX, Ordering::Relaxed);
+        let max = self.max_busy_ns.swap(0, Ordering::Relaxed);
+        CallbackStats {
+            load_pct: if audio > 0 {
+                busy as f32 / audio as f32 * 100.0
+            } else {
+                0.0
+            },
+            peak_pct: peak,
+            times: (callbacks > 0).then(|| CallbackTimes {
+                min_us: min as f64 / 1000.0,
+                avg_us: busy as f64 / callbacks as f64 / 1000.0,
+                max_us: max as f64 / 1000.0,
+            }),
+        }
+    }
+}
+
+fn unix_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
@@ -0,0 +1,167 @@
+//! Dropout log, for tracking down flaky USB DACs and network shares.
+//!
+//! The output callback reports each underrun through a bounded channel
+//! (lock-free, no allocation). The engine thread picks them up on its idle
+//! tick, stamps them with the time, file and position, and keeps a rolling
+//! history of the ring buffer's fill level so the lead-up to each dropout can
+//! be seen: a slow drain points at a slow source, a sudden one at the device.
+
+use crossbeam_channel::{bounded, Receiver, Sender};
+use parking_lot::Mutex;
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// Underruns the callback can report between two engine ticks; more are
+/// still counted in `dropout_count` but not logged.
+const PENDING_CAPACITY: usize = 64;
+
+/// Dropouts kept; older ones are dropped.
+const MAX_EVENTS: usize = 500;
+
+/// Buffer fill is sampled this often while playing...
+const HISTORY_INTERVAL: Duration = Duration::from_millis(100);
+
+/// ...and kept for a minute.
+const HISTORY_LEN: usize = 600;
+
+/// An underrun as the callback saw it.
+struct Underrun {
+    /// Samples the device asked for.
+    requested: usize,
+    /// Samples the buffer had.
+    delivered: usize,
+}
+
+#[derive(Clone, Serialize)]
+pub struct DropoutEvent {
+    /// Unix time in milliseconds.
+    pub timestamp_ms: u64,
+    pub file: Option<String>,
+    /// Playback position when it happened.
+    pub position_ms: u64,
+    /// Ring buffer fill (0–100) at the last history sample before it.
+    pub buffer_fill_pct: f32,
+    /// Samples the device asked for, and how many it got.
+    pub requested_samples: usize,
+    pub delivered_samples: usize,
+}
+
+#[derive(Clone, Serialize)]
+pub struct BufferFillSample {
+    /// Unix time in milliseconds.
+    pub timestamp_ms: u64,
+    pub fill_pct: f32,
+}
+
+#[derive(Clone, Serialize)]
+pub struct DropoutLog {
+    /// Most recent last.
+    pub events: Vec<DropoutEvent>,
+    /// Buffer fill over the last minute of playback, oldest first.
+    pub buffer_history: Vec<BufferFillSample>,
+}
+
+struct Log {
+    events: VecDeque<DropoutEvent>,
+    history: VecDeque<BufferFillSample>,
+    last_sample: Option<Instant>,
+}
+
+pub struct DropoutRecorder {
+    tx: Sender<Underrun>,
+    rx: Receiver<Underrun>,
+    log: Mutex<Log>,
+}
+
+impl Default for DropoutRecorder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl DropoutRecorder {
+    pub fn new() -> Self {
+        let (tx, rx) = bounded(PENDING_CAPACITY);
+        Self {
+            tx,
+            rx,
+            log: Mutex::new(Log {
+                events: VecDeque::new(),
+                history: VecDeque::new(),
+                last_sample: None,
+            }),
+        }
+    }
+
+    /// Report an underrun. Called from the audio callback: never blocks.
+    #[inline]
+    pub fn underrun(&self, requested: usize, delivered: usize) {
+        let _ = self.tx.try_send(Underrun {
+            requested,
+            delivered,
+        });
+    }
+
+    /// Sample the buffer fill and log pending underruns. Called from the
+    /// engine thread; `context` gives the playing file and position and is
+    /// only asked for when there is something to log.
+    pub fn tick(
+        &self,
+        playing: bool,
+        fill_pct: f32,
+        context: impl FnOnce() -> (Option<String>, u64),
+    ) {
+        let mut log = self.log.lock();
+        let now = unix_ms();
+        // Taken before this tick's sample, which may already show a refill
+        let fill_before = log.history.back().map_or(fill_pct, |s| s.fill_pct);
+        let sample_due = log
+            .last_sample
+            .is_none_or(|t| t.elapsed() >= HISTORY_INTERVAL);
+        if playing && sample_due {
+            log.last_sample = Some(Instant::now());
+            if log.history.len() == HISTORY_LEN {
+                log.history.pop_front();
+            }
+            log.history.push_back(BufferFillSample {
+                timestamp_ms: now,
+                fill_pct,
+            });
+        }
+
+        let mut pending = self.rx.try_iter().peekable();
+        if pending.peek().is_none() {
+            return;
+        }
+        let (file, position_ms) = context();
+        for underrun in pending {
+            if log.events.len() == MAX_EVENTS {
+                log.events.pop_front();
+            }
+            log.events.push_back(DropoutEvent {
+                timestamp_ms: now,
+                file: file.clone(),
+                position_ms,
+                buffer_fill_pct: fill_before,
+                requested_samples: underrun.requested,
+                delivered_samples: underrun.delivered,
+            });
+        }
+    }
+
+    pub fn log(&self) -> DropoutLog {
+        let log = self.log.lock();
+        DropoutLog {
+            events: log.events.iter().cloned().collect(),
+            buffer_history: log.history.iter().cloned().collect(),
+        }
+    }
+}
+
+fn unix_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
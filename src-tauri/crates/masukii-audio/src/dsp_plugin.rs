@@ -0,0 +1,490 @@
+//! DSP plugins: processors in shared libraries, loaded from a plugins
+//! folder and run in the decoder thread after ReplayGain and format
+//! conversion, in the order of the chain.
+//!
+//! The interface is a C ABI, so plugins can be written in anything that
+//! produces a `.dll`, `.so` or `.dylib`; `include/masukii_dsp.h` has it for
+//! C. A plugin exports `masukii_dsp_plugin`, returning a [`RawPlugin`]
+//! that lives as long as the library: its id, name and version, its
+//! parameters, and functions to create an instance for a sample rate and
+//! channel count, set a parameter, process interleaved `f32` samples in
+//! place, reset after a seek and destroy it.
+//!
+//! A plugin runs in-process, so a crash in it takes the app with it, and it
+//! must not unwind across the boundary. Short of that, a misbehaving one is
+//! contained: a processor that reports an error, outputs NaN or wildly
+//! loud samples, or can't keep up with real time is bypassed for the rest of
+//! the track with the samples it was given, and the failure is reported.
+
+use libloading::Library;
+use serde::Serialize;
+use std::ffi::{c_char, c_void, CStr};
+use std::path::{Path, PathBuf};
+use std::ptr::NonNull;
+use std::sync::Arc;
+use std::time::Instant;
+
+/// Version of the interface this host implements; plugins built for
+/// another one aren't loaded.
+pub const ABI_VERSION: u32 = 1;
+
+/// Name of the function a plugin exports.
+pub const ENTRY_SYMBOL: &str = "masukii_dsp_plugin";
+
+/// Most parameters a plugin may declare.
+const MAX_PARAMS: u32 = 64;
+
+/// Output beyond this (about +12 dBFS) is taken as a broken processor
+/// rather than a loud one.
+const MAX_OUTPUT_LEVEL: f32 = 4.0;
+
+/// A processor taking longer than the audio it processed this many times
+/// in a row can't keep up and is bypassed.
+const MAX_SLOW_BLOCKS: u32 = 8;
+
+// ─── ABI ───
+
+#[repr(C)]
+pub struct RawParam {
+    /// Stable key, for saved settings.
+    pub id: *const c_char,
+    pub name: *const c_char,
+    /// e.g. `dB`, `Hz`, `%`; may be null.
+    pub unit: *const c_char,
+    pub min: f32,
+    pub max: f32,
+    pub default_value: f32,
+    /// 0 for continuous; 1 over 0–1 for a switch.
+    pub step: f32,
+}
+
+#[repr(C)]
+pub struct RawPlugin {
+    pub abi_version: u32,
+    pub id: *const c_char,
+    pub name: *const c_char,
+    pub version: *const c_char,
+    pub param_count: u32,
+    pub params: *const RawParam,
+    /// An instance for this sample rate and channel count, or null.
+    pub create: Option<unsafe extern "C" fn(sample_rate: u32, channels: u32) -> *mut c_void>,
+    pub destroy: Option<unsafe extern "C" fn(instance: *mut c_void)>,
+    pub set_param: Option<unsafe extern "C" fn(instance: *mut c_void, index: u32, value: f32)>,
+    /// Process `frames` interleaved frames in place; nonzero on failure.
+    pub process: Option<
+        unsafe extern "C" fn(instance: *mut c_void, samples: *mut f32, frames: usize) -> i32,
+    >,
+    /// Clear state carried between blocks (filter history), after a seek.
+    pub reset: Option<unsafe extern "C" fn(instance: *mut c_void)>,
+}
+
+pub type EntryFn = unsafe extern "C" fn() -> *const RawPlugin;
+
+// ─── Plugins ───
+
+#[derive(Clone, Serialize)]
+pub struct ParamSchema {
+    pub id: String,
+    pub name: String,
+    pub unit: Option<String>,
+    pub min: f32,
+    pub max: f32,
+    pub default_value: f32,
+    pub step: f32,
+}
+
+impl ParamSchema {
+    /// `value` in range, or the default for NaN.
+    pub fn clamp(&self, value: f32) -> f32 {
+        if value.is_nan() {
+            self.default_value
+        } else {
+            value.clamp(self.min, self.max)
+        }
+    }
+}
+
+#[derive(Clone, Serialize)]
+pub struct PluginInfo {
+    pub id: String,
+    pub name: String,
+    pub version: String,
+    /// Library it came from; `None` when linked in.
+    pub path: Option<String>,
+    pub params: Vec<ParamSchema>,
+}
+
+/// A validated plugin, and the library keeping it loaded.
+pub struct DspPlugin {
+    info: PluginInfo,
+    create: unsafe extern "C" fn(u32, u32) -> *mut c_void,
+    destroy: unsafe extern "C" fn(*mut c_void),
+    set_param: unsafe extern "C" fn(*mut c_void, u32, f32),
+    process: unsafe extern "C" fn(*mut c_void, *mut f32, usize) -> i32,
+    reset: Option<unsafe extern "C" fn(*mut c_void)>,
+    _library: Option<Library>,
+}
+
+// The functions only touch the instance they're given.
+unsafe impl Send for DspPlugin {}
+unsafe impl Sync for DspPlugin {}
+
+impl DspPlugin {
+    /// Load the plugin in the library at `path`.
+    pub fn load(path: &Path) -> Result<Self, String> {
+        // SAFETY: loading runs the library's initializers; plugins in the
+        // plugins folder are trusted to that extent.
+        let library = unsafe { Library::new(path) }.map_err(|e| e.to_string())?;
+        let raw = unsafe {
+            let entry = library
+                .get::<EntryFn>(ENTRY_SYMBOL.as_bytes())
+                .map_err(|_| format!("No {} function", ENTRY_SYMBOL))?;
+            entry()
+        };
+        // SAFETY: the descriptor lives as long as the library, which is
+        // kept with it.
+        let mut plugin = unsafe { Self::from_raw(raw)? };
+        plugin.info.path = Some(path.to_string_lossy().to_string());
+        plugin._library = Some(library);
+        Ok(plugin)
+    }
+
+    /// A plugin from its descriptor, for one linked into the app.
+    ///
+    /// # Safety
+    ///
+    /// `raw` must be null or point to a descriptor whose strings, parameters
+    /// and functions stay valid for as long as the plugin is used.
+    pub unsafe fn from_raw(raw: *const RawPlugin) -> Result<Self, String> {
+        let raw = raw.as_ref().ok_or("The plugin returned no descriptor")?;
+        if raw.abi_version != ABI_VERSION {
+            return Err(format!(
+                "Built for plugin interface {}, not {}",
+                raw.abi_version, ABI_VERSION
+            ));
+        }
+        let id = string(raw.id).ok_or("The plugin has no id")?;
+        let name = string(raw.name).unwrap_or_else(|| id.clone());
+        let version = string(raw.version).unwrap_or_default();
+        let (Some(create), Some(destroy), Some(set_param), Some(process)) =
+            (raw.create, raw.destroy, raw.set_param, raw.process)
+        else {
+            return Err(format!("{} is missing a required function", id));
+        };
+        if raw.param_count > MAX_PARAMS || (raw.param_count > 0 && raw.params.is_null()) {
+            return Err(format!("{} has an invalid parameter list", id));
+        }
+
+        let mut params: Vec<ParamSchema> = Vec::new();
+        for index in 0..raw.param_count as usize {
+            let param = &*raw.params.add(index);
+            let param_id =
+                string(param.id).ok_or_else(|| format!("{}: parameter {} has no id", id, index))?;
+            let valid = [param.min, param.max, param.default_value, param.step]
+                .iter()
+                .all(|v| v.is_finite())
+                && param.min <= param.max
+                && (param.min..=param.max).contains(&param.default_value)
+                && param.step >= 0.0;
+            if !valid {
+                return Err(format!(
+                    "{}: parameter {} has an invalid range",
+                    id, param_id
+                ));
+            }
+            if params.iter().any(|p| p.id == param_id) {
+                return Err(format!("{}: parameter {} is declared twice", id, param_id));
+            }
+            params.push(ParamSchema {
+                name: string(param.name).unwrap_or_else(|| param_id.clone()),
+                id: param_id,
+                unit: string(param.unit),
+                min: param.min,
+                max: param.max,
+                default_value: param.default_value,
+                step: param.step,
+            });
+        }
+
+        Ok(Self {
+            info: PluginInfo {
+                id,
+                name,
+                version,
+                path: None,
+                params,
+            },
+            create,
+            destroy,
+            set_param,
+            process,
+            reset: raw.reset,
+            _library: None,
+        })
+    }
+
+    pub fn info(&self) -> &PluginInfo {
+        &self.info
+    }
+
+    pub fn id(&self) -> &str {
+        &self.info.id
+    }
+
+    pub fn name(&self) -> &str {
+        &self.info.name
+    }
+
+    pub fn params(&self) -> &[ParamSchema] {
+        &self.info.params
+    }
+
+    /// Each parameter at its default.
+    pub fn default_params(&self) -> Vec<f32> {
+        self.info.params.iter().map(|p| p.default_value).collect()
+    }
+}
+
+fn string(ptr: *const c_char) -> Option<String> {
+    if ptr.is_null() {
+        return None;
+    }
+    // SAFETY: non-null strings in a descriptor are NUL-terminated.
+    let s = unsafe { CStr::from_ptr(ptr) }
+        .to_string_lossy()
+        .trim()
+        .to_string();
+    (!s.is_empty()).then_some(s)
+}
+
+// ─── Discovery ───
+
+#[derive(Clone, Serialize)]
+pub struct PluginLoadError {
+    pub path: String,
+    pub error: String,
+}
+
+#[derive(Default)]
+pub struct Discovery {
+    pub plugins: Vec<Arc<DspPlugin>>,
+    pub failed: Vec<PluginLoadError>,
+}
+
+/// Load every plugin library directly in `dir`, in file name order. A
+/// second plugin with an id already loaded is skipped.
+pub fn discover(dir: &Path) -> Discovery {
+    let mut discovery = Discovery::default();
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return discovery;
+    };
+    let mut paths: Vec<PathBuf> = entries
+        .filter_map(|e| e.ok().map(|e| e.path()))
+        .filter(|p| p.is_file() && is_library(p))
+        .collect();
+    paths.sort();
+
+    for path in paths {
+        let loaded = DspPlugin::load(&path).and_then(|plugin| {
+            match discovery.plugins.iter().any(|p| p.id() == plugin.id()) {
+                true => Err(format!("Another plugin already has the id {}", plugin.id())),
+                false => Ok(plugin),
+            }
+        });
+        match loaded {
+            Ok(plugin) => {
+                log::info!("Loaded DSP plugin {} from {}", plugin.id(), path.display());
+                discovery.plugins.push(Arc::new(plugin));
+            }
+            Err(error) => discovery.failed.push(PluginLoadError {
+                path: path.to_string_lossy().to_string(),
+                error,
+            }),
+        }
+    }
+    discovery
+}
+
+fn is_library(path: &Path) -> bool {
+    let extension = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(str::to_ascii_lowercase);
+    extension.as_deref() == Some(std::env::consts::DLL_EXTENSION)
+}
+
+// ─── Chain ───
+
+/// A plugin in the chain and its parameter values, in schema order.
+#[derive(Clone)]
+pub struct ChainSlot {
+    pub plugin: Arc<DspPlugin>,
+    pub params: Vec<f32>,
+}
+
+struct Instance {
+    plugin: Arc<DspPlugin>,
+    handle: NonNull<c_void>,
+    params: Vec<f32>,
+    /// Bypassed after a failure.
+    failed: bool,
+    slow_blocks: u32,
+}
+
+impl Instance {
+    fn new(slot: &ChainSlot, sample_rate: u32, channels: usize) -> Result<Self, String> {
+        let plugin = slot.plugin.clone();
+        // SAFETY: the functions were checked at load and the plugin is kept
+        // alive by the Arc.
+        let handle = unsafe { (plugin.create)(sample_rate, channels as u32) };
+        let handle = NonNull::new(handle).ok_or_else(|| {
+            format!(
+                "{} doesn't support {} Hz, {} channels",
+                plugin.name(),
+                sample_rate,
+                channels
+            )
+        })?;
+        let mut instance = Self {
+            plugin,
+            handle,
+            params: Vec::new(),
+            failed: false,
+            slow_blocks: 0,
+        };
+        instance.set_params(&slot.params);
+        Ok(instance)
+    }
+
+    /// Pass on the values that changed.
+    fn set_params(&mut self, values: &[f32]) {
+        let values: Vec<f32> = self
+            .plugin
+            .params()
+            .iter()
+            .enumerate()
+            .map(|(i, s)| s.clamp(values.get(i).copied().unwrap_or(s.default_value)))
+            .collect();
+        for (index, &value) in values.iter().enumerate() {
+            if self.params.get(index) != Some(&value) {
+                // SAFETY: index is within the declared parameters.
+                unsafe { (self.plugin.set_param)(self.handle.as_ptr(), index as u32, value) };
+            }
+        }
+        self.params = values;
+    }
+}
+
+impl Drop for Instance {
+    fn drop(&mut self) {
+        // SAFETY: created by this plugin and not used after this.
+        unsafe { (self.plugin.destroy)(self.handle.as_ptr()) };
+    }
+}
+
+/// The chain as the decoder thread runs it: an instance of each plugin for
+/// the current format.
+pub struct DspChain {
+    sample_rate: u32,
+    channels: usize,
+    instances: Vec<Instance>,
+    /// Input to the running processor, restored if it fails.
+    backup: Vec<f32>,
+}
+
+impl DspChain {
+    pub fn new(sample_rate: u32, channels: usize) -> Self {
+        Self {
+            sample_rate,
+            channels: channels.max(1),
+            instances: Vec::new(),
+            backup: Vec::new(),
+        }
+    }
+
+    /// Switch to `slots`, keeping the state of instances whose plugin is
+    /// still at the same place and updating their parameters. Returns the
+    /// plugins that couldn't be created.
+    pub fn update(&mut self, slots: &[ChainSlot]) -> Vec<String> {
+        let mut old: Vec<Option<Instance>> = std::mem::take(&mut self.instances)
+            .into_iter()
+            .map(Some)
+            .collect();
+        let mut errors = Vec::new();
+        for (index, slot) in slots.iter().enumerate() {
+            let reusable = old
+                .get_mut(index)
+                .and_then(|i| i.take_if(|i| Arc::ptr_eq(&i.plugin, &slot.plugin) && !i.failed));
+            if let Some(mut instance) = reusable {
+                instance.set_params(&slot.params);
+                self.instances.push(instance);
+                continue;
+            }
+            match Instance::new(slot, self.sample_rate, self.channels) {
+                Ok(instance) => self.instances.push(instance),
+                Err(e) => errors.push(e),
+            }
+        }
+        errors
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.instances.is_empty()
+    }
+
+    /// Clear each processor's history, after a seek.
+    pub fn reset(&mut self) {
+        for instance in self.instances.iter_mut().filter(|i| !i.failed) {
+            if let Some(reset) = instance.plugin.reset {
+                // SAFETY: a live instance of this plugin.
+                unsafe { reset(instance.handle.as_ptr()) };
+            }
+        }
+    }
+
+    /// Run `samples` through the chain. Each processor that fails is
+    /// bypassed from then on, with the reason passed to `on_failure`.
+    pub fn process(&mut self, samples: &mut [f32], mut on_failure: impl FnMut(String)) {
+        let frames = samples.len() / self.channels;
+        if frames == 0 {
+            return;
+        }
+        let samples = &mut samples[..frames * self.channels];
+        let budget_secs = frames as f64 / f64::from(self.sample_rate.max(1));
+        for instance in self.instances.iter_mut().filter(|i| !i.failed) {
+            self.backup.clear();
+            self.backup.extend_from_slice(samples);
+
+            let started = Instant::now();
+            // SAFETY: a live instance, given exactly `frames` frames of its
+            // channel count.
+            let status = unsafe {
+                (instance.plugin.process)(instance.handle.as_ptr(), samples.as_mut_ptr(), frames)
+            };
+            let slow = started.elapsed().as_secs_f64() > budget_secs;
+            instance.slow_blocks = if slow { instance.slow_blocks + 1 } else { 0 };
+
+            let failure = if status != 0 {
+                Some(format!("reported error {}", status))
+            } else if samples
+                .iter()
+                .any(|s| !s.is_finite() || s.abs() > MAX_OUTPUT_LEVEL)
+            {
+                Some("output invalid samples".to_string())
+            } else if instance.slow_blocks >= MAX_SLOW_BLOCKS {
+                Some("can't keep up with playback".to_string())
+            } else {
+                None
+            };
+            if let Some(reason) = failure {
+                samples.copy_from_slice(&self.backup);
+                instance.failed = true;
+                on_failure(format!(
+                    "DSP plugin {} {}; bypassing it",
+                    instance.plugin.name(),
+                    reason
+                ));
+            }
+        }
+    }
+}
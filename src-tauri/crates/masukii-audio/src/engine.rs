@@ -0,0 +1,1662 @@
+use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+use cpal::{SampleRate, StreamConfig};
+use crossbeam_channel::{bounded, Receiver, Sender};
+use parking_lot::Mutex;
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::bit_meter::{BitActivity, BitMeter};
+use crate::clipping::{ClipCounter, TrackClipping};
+use crate::decoder::{AudioDecoder, DecodeStatus};
+use crate::diagnostics_history::{DiagnosticsHistory, DiagnosticsSample};
+use crate::dropouts::{DropoutLog, DropoutRecorder};
+use crate::dsp_plugin::{ChainSlot, DspChain};
+use crate::http_source;
+use crate::levels::{AudioLevels, LevelMeter};
+use crate::loudness_meter::{self, TAP_SIZE};
+use crate::output::{self, FormatConverter, OutputFormat};
+use crate::replaygain::ReplayGainState;
+use crate::resource_usage;
+use crate::ring_buffer::RingBuffer;
+
+// ─── Safety Constants ───
+
+/// Fade ramp in samples. 256 samples @ 44.1kHz ≈ 5.8ms. Eliminates all pops.
+const FADE_RAMP_SAMPLES: usize = 256;
+
+/// Hard limiter ceiling. Applied ONLY when volume < 1.0 or ReplayGain is active.
+/// In bit-perfect mode (vol=1.0, RG=off), NO limiting is applied.
+pub const HARD_LIMIT_CEILING: f32 = 0.99;
+
+/// Ring buffer size. Power of 2 for lock-free masking.
+/// 131072 samples ≈ 1.5s at 44.1kHz stereo, ~0.34s at 192kHz stereo.
+/// Balance between latency and buffer safety. Mobile outputs run at 48kHz
+/// or less in stereo, so there it's larger: ≈ 5.5s, for [`BufferPolicy`]s
+/// that decode in bursts.
+const RING_BUFFER_SIZE: usize = if cfg!(any(target_os = "android", target_os = "ios")) {
+    1 << 19
+} else {
+    131072
+};
+
+/// Most of the ring buffer the decoder fills, leaving room for the chunk
+/// written after the last check.
+const RING_FILL_MAX: usize = RING_BUFFER_SIZE / 4 * 3;
+
+/// Longest the decoder sleeps while the buffer drains, so seeks and stops
+/// are still picked up quickly.
+const MAX_BACKPRESSURE_SLEEP_MS: u64 = 50;
+
+/// Errors kept for [`AudioEngine::errors`]; later ones are dropped until
+/// they're read.
+const ERROR_QUEUE_LEN: usize = 32;
+
+/// How long the output stream stays open, silent, once nothing is playing,
+/// so the next track can reuse it.
+const STREAM_IDLE_CLOSE: Duration = Duration::from_secs(10);
+
+// ─── Commands ───
+
+pub enum AudioCommand {
+    /// Play `path` starting at `start_secs`. When `end_secs` is set, playback
+    /// stops there instead of at EOF — used for cue-sheet virtual tracks that
+    /// share one image file. Position/duration are reported relative to the segment.
+    Play {
+        path: String,
+        start_secs: f64,
+        end_secs: Option<f64>,
+    },
+    Pause,
+    Resume,
+    Stop,
+    Seek(f64),
+    SetVolume(f32),
+    /// Pause, resume and stop fade length in ms. Never shorter than
+    /// `FADE_RAMP_SAMPLES`, so 0 still avoids pops.
+    SetFadeLength(u32),
+    SetReplayGain(ReplayGainMode),
+    /// Extra gain for files with ReplayGain tags, in dB.
+    SetReplayGainPreamp(f32),
+    SetClippingPrevention(bool),
+    SetBufferPolicy(BufferPolicy),
+    /// Plugins to run after ReplayGain, in order; empty for none.
+    SetDspChain(Vec<ChainSlot>),
+    Shutdown,
+}
+
+impl AudioCommand {
+    /// Play a whole file from the beginning.
+    pub fn play(path: String) -> Self {
+        AudioCommand::Play {
+            path,
+            start_secs: 0.0,
+            end_secs: None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, serde::Serialize, serde::Deserialize)]
+pub enum ReplayGainMode {
+    Off,
+    Track,
+    Album,
+}
+
+/// How far ahead of the output audio is decoded and streams downloaded.
+#[derive(Clone, Copy, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct BufferPolicy {
+    /// Decoded audio kept ahead of the output, in ms, as far as the ring
+    /// buffer allows.
+    pub ahead_ms: u32,
+    /// Once the buffer is full, decoding waits until it's down to this, in
+    /// ms. Below `ahead_ms` the decoder works in bursts and sleeps in
+    /// between, which wakes the CPU less often.
+    pub refill_ms: u32,
+    /// Bytes of an HTTP stream downloaded ahead of the decoder; 0 downloads
+    /// only as it's decoded.
+    pub prefetch_bytes: usize,
+}
+
+impl Default for BufferPolicy {
+    fn default() -> Self {
+        Self {
+            ahead_ms: 1000,
+            refill_ms: 1000,
+            prefetch_bytes: 0,
+        }
+    }
+}
+
+// ─── Errors ───
+
+/// A playback failure the user should hear about, beyond the log.
+#[derive(Clone, Debug, serde::Serialize)]
+pub struct EngineError {
+    pub kind: EngineErrorKind,
+    pub message: String,
+    /// File or URL it happened on.
+    pub path: Option<String>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EngineErrorKind {
+    /// The file couldn't be opened or has no playable track.
+    Open,
+    /// A seek failed; playback carries on from wherever the decoder is.
+    Seek,
+    /// Decoding failed mid-track; playback stops there.
+    Decode,
+    /// No output device, or it couldn't be opened or went away.
+    Output,
+    /// ReplayGain is on but the file's tags couldn't be read, so it plays
+    /// without gain.
+    ReplayGainTags,
+    /// A DSP plugin couldn't start or failed and is bypassed.
+    Plugin,
+}
+
+/// Log an error and queue it for [`AudioEngine::errors`].
+fn report(errors: &Sender<EngineError>, kind: EngineErrorKind, path: &str, message: String) {
+    log::error!("{}", message);
+    let _ = errors.try_send(EngineError {
+        kind,
+        message,
+        path: Some(path.to_string()),
+    });
+}
+
+// ─── Playback State ───
+
+#[derive(Clone, serde::Serialize)]
+pub struct PlaybackState {
+    pub is_playing: bool,
+    pub is_paused: bool,
+    pub position_secs: f64,
+    pub duration_secs: f64,
+    pub sample_rate: u32,
+    pub bit_depth: Option<u8>,
+    pub channels: u32,
+    pub current_file: Option<String>,
+    /// Part of the file being played, in seconds: the whole file unless it
+    /// is a cue-sheet track.
+    pub start_secs: f64,
+    pub end_secs: Option<f64>,
+    /// True if the OS is resampling: the device runs at another rate than the
+    /// file's (or, where the OS doesn't say, doesn't support the file's rate).
+    pub resampled: bool,
+    /// Format the OS actually runs the device at for this stream.
+    pub device_format: Option<DeviceFormat>,
+    /// Codec of the current file, e.g. `flac`.
+    pub codec: Option<String>,
+    /// Device the current file is playing on.
+    pub output_device: Option<String>,
+}
+
+impl Default for PlaybackState {
+    fn default() -> Self {
+        Self {
+            is_playing: false,
+            is_paused: false,
+            position_secs: 0.0,
+            duration_secs: 0.0,
+            sample_rate: 0,
+            bit_depth: None,
+            channels: 0,
+            current_file: None,
+            start_secs: 0.0,
+            end_secs: None,
+            resampled: false,
+            device_format: None,
+            codec: None,
+            output_device: None,
+        }
+    }
+}
+
+// ─── Signal Processing Settings ───
+
+/// The processing the output currently applies, so it can be reproduced
+/// offline, as the app's DSP null test does.
+#[derive(Clone, Copy, serde::Serialize)]
+pub struct DspSettings {
+    pub volume: f32,
+    pub replaygain_mode: ReplayGainMode,
+    pub replaygain_preamp_db: f32,
+    pub clipping_prevention: bool,
+    /// DSP plugins in the chain. Only playback runs them.
+    pub plugins: usize,
+}
+
+impl DspSettings {
+    /// Same rule as the output callback: no processing at all at volume 1.0
+    /// with ReplayGain off and no plugins, otherwise volume and the hard
+    /// limiter.
+    pub fn is_bit_perfect(&self) -> bool {
+        (self.volume - 1.0).abs() < f32::EPSILON
+            && self.replaygain_mode == ReplayGainMode::Off
+            && self.plugins == 0
+    }
+
+    /// Whether a stream plays bit-perfect: no processing, and nothing
+    /// resampled on the way to the DAC, by the engine or the OS.
+    pub fn is_bit_perfect_on(&self, resampled: bool) -> bool {
+        !resampled && self.is_bit_perfect()
+    }
+}
+
+// ─── Audio Diagnostics (Latency Analyzer) ───
+
+#[derive(Clone, serde::Serialize)]
+pub struct AudioDiagnostics {
+    /// Ring buffer capacity in samples.
+    pub buffer_capacity: usize,
+    /// Ring buffer currently filled (samples).
+    pub buffer_filled: usize,
+    /// Buffer fill percentage (0–100).
+    pub buffer_fill_pct: f32,
+    /// Estimated output latency in milliseconds.
+    pub latency_ms: f64,
+    /// Total number of buffer underruns (dropouts) since playback started.
+    pub dropout_count: u64,
+    /// Current sample rate being output.
+    pub output_sample_rate: u32,
+    /// Number of output channels.
+    pub output_channels: u32,
+    /// True when signal path is fully bit-perfect (vol=1.0, RG=off, no resample).
+    pub is_bit_perfect: bool,
+    /// Always true for MVP — cpal uses WASAPI Shared mode.
+    pub shared_mode: bool,
+    /// Decoder thread CPU time over the last second, as a share of one core
+    /// (0–100). `None` when not playing or where the OS gives no reading.
+    pub decoder_cpu_pct: Option<f32>,
+    /// Output callback execution time per buffer over the last second, in
+    /// microseconds. `None` when not playing.
+    pub callback_min_us: Option<f64>,
+    pub callback_avg_us: Option<f64>,
+    pub callback_max_us: Option<f64>,
+    /// Resident memory (working set) of the whole app, in bytes.
+    pub resident_memory_bytes: Option<u64>,
+    /// Samples of the current track at or over full scale after volume,
+    /// and samples the hard limiter cut down. Fades aren't counted.
+    pub clipped_samples: u64,
+    pub limited_samples: u64,
+}
+
+// ─── Fade State Machine ───
+// Uses equal-power (cosine) curves for professional-grade transitions.
+
+#[derive(Clone, Copy, PartialEq)]
+enum FadeState {
+    Playing,
+    FadingOut,
+    Silent,
+    FadingIn,
+}
+
+/// The output stream, kept open across tracks: some DACs click or mute for
+/// a moment when a stream is opened, and opening one adds to start latency.
+/// The next track reuses it when it plays on the same device at the same
+/// format; otherwise it's rebuilt.
+struct OpenStream {
+    /// Playing until dropped.
+    _stream: cpal::Stream,
+    device: Option<String>,
+    /// File format it was opened for, and what that negotiated to.
+    source: (u32, usize),
+    output: OutputFormat,
+    /// What the device runs at with the stream open.
+    negotiated: DeviceFormat,
+    /// Whether the engine or the OS resamples what it carries.
+    resampled: bool,
+    /// Set by the error callback, e.g. when the device goes away.
+    failed: Arc<AtomicBool>,
+}
+
+impl OpenStream {
+    fn fits(&self, device: Option<&str>, sample_rate: u32, channels: usize) -> bool {
+        !self.failed.load(Ordering::Relaxed)
+            && device.is_some()
+            && self.device.as_deref() == device
+            && self.source == (sample_rate, channels)
+    }
+}
+
+// ─── Audio Engine ───
+
+pub struct AudioEngine {
+    cmd_tx: Sender<AudioCommand>,
+    errors: Receiver<EngineError>,
+    state: Arc<Mutex<PlaybackState>>,
+    position_ms: Arc<AtomicU64>,
+    duration_ms: Arc<AtomicU64>,
+    is_playing: Arc<AtomicBool>,
+    is_paused: Arc<AtomicBool>,
+    ring_buffer: Arc<RingBuffer>,
+    dropout_count: Arc<AtomicU64>,
+    current_sample_rate: Arc<AtomicU32>,
+    current_channels: Arc<AtomicU32>,
+    /// True when the signal path is bit-perfect (vol=1.0, RG=off).
+    is_bit_perfect: Arc<AtomicBool>,
+    /// Levels of the samples sent to the device.
+    levels: Arc<LevelMeter>,
+    /// Bits in use in the samples sent to the device.
+    bit_meter: Arc<BitMeter>,
+    /// Samples over full scale or limited, per track.
+    clipping: Arc<ClipCounter>,
+    /// Copy of the played samples (before volume) for the loudness meter.
+    loudness_tap: Arc<RingBuffer>,
+    /// Another copy for the recorder.
+    record_tap: Arc<RingBuffer>,
+    /// And one for the Snapcast output.
+    snapcast_tap: Arc<RingBuffer>,
+    /// Lock-free volume (atomic f32 via bit cast)
+    volume: Arc<AtomicU32>,
+    rg_state: Arc<Mutex<ReplayGainState>>,
+    /// DSP plugins the decoder thread runs.
+    dsp_chain: Arc<Mutex<Vec<ChainSlot>>>,
+    /// Each underrun, and the buffer fill leading up to them.
+    dropouts: Arc<DropoutRecorder>,
+    /// Diagnostics over the last few minutes, for graphs.
+    history: Arc<DiagnosticsHistory>,
+}
+
+impl Default for AudioEngine {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl AudioEngine {
+    pub fn new() -> Self {
+        let (cmd_tx, cmd_rx) = bounded::<AudioCommand>(64);
+        let (error_tx, errors) = bounded::<EngineError>(ERROR_QUEUE_LEN);
+        let state = Arc::new(Mutex::new(PlaybackState::default()));
+        let position_ms = Arc::new(AtomicU64::new(0));
+        let duration_ms = Arc::new(AtomicU64::new(0));
+        let is_playing = Arc::new(AtomicBool::new(false));
+        let is_paused = Arc::new(AtomicBool::new(false));
+        let ring_buffer = Arc::new(RingBuffer::new(RING_BUFFER_SIZE));
+        let dropout_count = Arc::new(AtomicU64::new(0));
+        let current_sample_rate = Arc::new(AtomicU32::new(0));
+        let current_channels = Arc::new(AtomicU32::new(0));
+        let is_bit_perfect = Arc::new(AtomicBool::new(true));
+        let levels = Arc::new(LevelMeter::new());
+        let bit_meter = Arc::new(BitMeter::new());
+        let clipping = Arc::new(ClipCounter::new());
+        let loudness_tap = Arc::new(RingBuffer::new(TAP_SIZE));
+        let record_tap = Arc::new(RingBuffer::new(TAP_SIZE));
+        let snapcast_tap = Arc::new(RingBuffer::new(TAP_SIZE));
+        let volume = Arc::new(AtomicU32::new(f32_to_atomic(1.0)));
+        let rg_state = Arc::new(Mutex::new(ReplayGainState::new()));
+        let dsp_chain = Arc::new(Mutex::new(Vec::new()));
+        let dropouts = Arc::new(DropoutRecorder::new());
+        let history = Arc::new(DiagnosticsHistory::new());
+
+        let shared = ThreadShared {
+            state: state.clone(),
+            position_ms: position_ms.clone(),
+            duration_ms: duration_ms.clone(),
+            is_playing: is_playing.clone(),
+            is_paused: is_paused.clone(),
+            ring_buffer: ring_buffer.clone(),
+            dropout_count: dropout_count.clone(),
+            current_sample_rate: current_sample_rate.clone(),
+            current_channels: current_channels.clone(),
+            is_bit_perfect: is_bit_perfect.clone(),
+            levels: levels.clone(),
+            bit_meter: bit_meter.clone(),
+            clipping: clipping.clone(),
+            loudness_tap: loudness_tap.clone(),
+            record_tap: record_tap.clone(),
+            snapcast_tap: snapcast_tap.clone(),
+            volume: volume.clone(),
+            rg_state: rg_state.clone(),
+            dsp_chain: dsp_chain.clone(),
+            dropouts: dropouts.clone(),
+            history: history.clone(),
+        };
+
+        thread::Builder::new()
+            .name("audio-engine".into())
+            .spawn(move || audio_thread(cmd_rx, error_tx, shared))
+            .expect("Failed to spawn audio thread");
+
+        Self {
+            cmd_tx,
+            errors,
+            state,
+            position_ms,
+            duration_ms,
+            is_playing,
+            is_paused,
+            ring_buffer,
+            dropout_count,
+            current_sample_rate,
+            current_channels,
+            is_bit_perfect,
+            levels,
+            bit_meter,
+            clipping,
+            loudness_tap,
+            record_tap,
+            snapcast_tap,
+            volume,
+            rg_state,
+            dsp_chain,
+            dropouts,
+            history,
+        }
+    }
+
+    pub fn send_command(&self, cmd: AudioCommand) {
+        let _ = self.cmd_tx.send(cmd);
+    }
+
+    /// Playback failures as they happen: files that won't open or decode,
+    /// output device errors, unreadable ReplayGain tags. Each error goes to
+    /// one receiver, so take this once.
+    pub fn errors(&self) -> Receiver<EngineError> {
+        self.errors.clone()
+    }
+
+    /// Stop playback and wait until the output stream is closed, so a
+    /// diagnostic can use the device.
+    pub fn stop_and_wait(&self, timeout: Duration) -> Result<(), String> {
+        self.send_command(AudioCommand::Stop);
+        let requested = Instant::now();
+        while self.state.lock().current_file.is_some() {
+            if requested.elapsed() > timeout {
+                return Err("Playback didn't stop".to_string());
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        Ok(())
+    }
+
+    pub fn get_state(&self) -> PlaybackState {
+        let mut s = self.state.lock().clone();
+        s.position_secs = self.position_ms.load(Ordering::Relaxed) as f64 / 1000.0;
+        s.duration_secs = self.duration_ms.load(Ordering::Relaxed) as f64 / 1000.0;
+        s.is_playing = self.is_playing.load(Ordering::Relaxed);
+        s.is_paused = self.is_paused.load(Ordering::Relaxed);
+        s
+    }
+
+    pub fn get_position_ms(&self) -> u64 {
+        self.position_ms.load(Ordering::Relaxed)
+    }
+
+    pub fn get_duration_ms(&self) -> u64 {
+        self.duration_ms.load(Ordering::Relaxed)
+    }
+
+    /// Position of the sample being heard: the decoder position minus what
+    /// is still buffered ahead of the output.
+    pub fn get_audible_position_ms(&self) -> u64 {
+        self.position_ms
+            .load(Ordering::Relaxed)
+            .saturating_sub(self.output_latency_ms().round() as u64)
+    }
+
+    /// Returns live audio diagnostics for the latency analyzer UI.
+    pub fn get_diagnostics(&self) -> AudioDiagnostics {
+        let filled = self.ring_buffer.available_read();
+        let capacity = RING_BUFFER_SIZE;
+        let sr = self.current_sample_rate.load(Ordering::Relaxed);
+        let ch = self.current_channels.load(Ordering::Relaxed).max(1);
+        let usage = self.history.usage();
+        let callback_times = usage.and_then(|u| u.callback_times);
+        let (clipped_samples, limited_samples) = self.clipping.current();
+
+        AudioDiagnostics {
+            buffer_capacity: capacity,
+            buffer_filled: filled,
+            buffer_fill_pct: (filled as f32 / capacity as f32) * 100.0,
+            latency_ms: self.output_latency_ms(),
+            dropout_count: self.dropout_count.load(Ordering::Relaxed),
+            output_sample_rate: sr,
+            output_channels: ch,
+            is_bit_perfect: self.is_bit_perfect.load(Ordering::Relaxed),
+            shared_mode: true, // cpal always uses WASAPI Shared — MVP limitation
+            decoder_cpu_pct: usage.and_then(|u| u.decoder_cpu_pct),
+            callback_min_us: callback_times.map(|t| t.min_us),
+            callback_avg_us: callback_times.map(|t| t.avg_us),
+            callback_max_us: callback_times.map(|t| t.max_us),
+            resident_memory_bytes: resource_usage::resident_memory_bytes(),
+            clipped_samples,
+            limited_samples,
+        }
+    }
+
+    /// Per-channel peak and RMS levels of what is playing.
+    pub fn get_levels(&self) -> AudioLevels {
+        self.levels.levels()
+    }
+
+    /// Bits in use in the output, since the last call and since the track
+    /// started.
+    pub fn get_bit_activity(&self) -> BitActivity {
+        self.bit_meter.activity()
+    }
+
+    /// Clipped and limited sample counts for each track played this session.
+    pub fn get_clipping_report(&self) -> Vec<TrackClipping> {
+        self.clipping.report()
+    }
+
+    pub fn dsp_settings(&self) -> DspSettings {
+        let rg = self.rg_state.lock();
+        DspSettings {
+            volume: atomic_to_f32(self.volume.load(Ordering::Relaxed)),
+            replaygain_mode: rg.get_mode(),
+            replaygain_preamp_db: rg.preamp_db(),
+            clipping_prevention: rg.clipping_prevention(),
+            plugins: self.dsp_chain.lock().len(),
+        }
+    }
+
+    /// Names of the DSP plugins in the chain, in order.
+    pub fn dsp_chain(&self) -> Vec<String> {
+        let chain = self.dsp_chain.lock();
+        chain.iter().map(|s| s.plugin.name().to_string()).collect()
+    }
+
+    /// Gain ReplayGain applies to the current file, in dB; `None` when it is
+    /// off or the file has no gain tag for the mode.
+    pub fn replaygain_gain_db(&self) -> Option<f32> {
+        let rg = self.rg_state.lock();
+        rg.has_gain().then(|| rg.gain_db())
+    }
+
+    /// Logged underruns with the buffer fill history.
+    pub fn get_dropout_log(&self) -> DropoutLog {
+        self.dropouts.log()
+    }
+
+    /// One point a second of buffer fill, latency, callback load and
+    /// dropouts over the last five minutes of playback.
+    pub fn get_diagnostics_history(&self) -> Vec<DiagnosticsSample> {
+        self.history.samples()
+    }
+
+    /// Samples played, before volume, for [`loudness_meter::spawn_meter`].
+    pub fn loudness_tap(&self) -> Arc<RingBuffer> {
+        self.loudness_tap.clone()
+    }
+
+    /// The same samples again, for recording the output.
+    pub fn record_tap(&self) -> Arc<RingBuffer> {
+        self.record_tap.clone()
+    }
+
+    /// And again, for streaming it to Snapcast.
+    pub fn snapcast_tap(&self) -> Arc<RingBuffer> {
+        self.snapcast_tap.clone()
+    }
+
+    /// Sample rate and channels of what's played, and so of the taps: the
+    /// file's, unless the output needed another format (mobile).
+    pub fn output_format(&self) -> (u32, u32) {
+        (
+            self.current_sample_rate.load(Ordering::Relaxed),
+            self.current_channels.load(Ordering::Relaxed),
+        )
+    }
+
+    /// Audio buffered between the decoder and the output, in milliseconds.
+    fn output_latency_ms(&self) -> f64 {
+        buffered_ms(
+            self.ring_buffer.available_read(),
+            self.current_sample_rate.load(Ordering::Relaxed),
+            self.current_channels.load(Ordering::Relaxed),
+        )
+    }
+}
+
+/// Duration of `filled` buffered samples, in milliseconds.
+fn buffered_ms(filled: usize, sr: u32, ch: u32) -> f64 {
+    if sr > 0 {
+        (filled as f64 / ch.max(1) as f64) / sr as f64 * 1000.0
+    } else {
+        0.0
+    }
+}
+
+// ─── Atomic f32 helpers (lock-free volume) ───
+
+#[inline]
+fn f32_to_atomic(v: f32) -> u32 {
+    v.to_bits()
+}
+#[inline]
+fn atomic_to_f32(b: u32) -> f32 {
+    f32::from_bits(b)
+}
+
+// ─── Equal-power fade curve ───
+// Cosine curve eliminates the perceived 3dB dip of linear fades.
+// Standard in professional audio (Pro Tools, Ableton, foobar2000).
+
+#[inline]
+fn equal_power_gain(progress: f32) -> f32 {
+    // progress: 0.0 = silent, 1.0 = full volume
+    // sin(progress * π/2) gives equal-power curve
+    (progress * std::f32::consts::FRAC_PI_2).sin()
+}
+
+/// Frames in a fade of `ms` at `sample_rate`.
+fn fade_frames(ms: u32, sample_rate: u32) -> usize {
+    ((u64::from(ms) * u64::from(sample_rate) / 1000) as usize).max(FADE_RAMP_SAMPLES)
+}
+
+// ─── Audio Thread ───
+
+/// The engine's state the audio thread updates and reads.
+struct ThreadShared {
+    state: Arc<Mutex<PlaybackState>>,
+    position_ms: Arc<AtomicU64>,
+    duration_ms: Arc<AtomicU64>,
+    is_playing: Arc<AtomicBool>,
+    is_paused: Arc<AtomicBool>,
+    ring_buffer: Arc<RingBuffer>,
+    dropout_count: Arc<AtomicU64>,
+    current_sample_rate: Arc<AtomicU32>,
+    current_channels: Arc<AtomicU32>,
+    is_bit_perfect: Arc<AtomicBool>,
+    levels: Arc<LevelMeter>,
+    bit_meter: Arc<BitMeter>,
+    clipping: Arc<ClipCounter>,
+    loudness_tap: Arc<RingBuffer>,
+    record_tap: Arc<RingBuffer>,
+    snapcast_tap: Arc<RingBuffer>,
+    volume: Arc<AtomicU32>,
+    // ReplayGain state — applied in the decoder thread, not the callback
+    rg_state: Arc<Mutex<ReplayGainState>>,
+    // DSP plugins — also run in the decoder thread, which picks up changes
+    // by the generation
+    dsp_chain: Arc<Mutex<Vec<ChainSlot>>>,
+    dropouts: Arc<DropoutRecorder>,
+    history: Arc<DiagnosticsHistory>,
+}
+
+fn audio_thread(cmd_rx: Receiver<AudioCommand>, errors: Sender<EngineError>, shared: ThreadShared) {
+    let ThreadShared {
+        state,
+        position_ms,
+        duration_ms,
+        is_playing,
+        is_paused,
+        ring_buffer,
+        dropout_count,
+        current_sample_rate,
+        current_channels,
+        is_bit_perfect,
+        levels,
+        bit_meter,
+        clipping,
+        loudness_tap,
+        record_tap,
+        snapcast_tap,
+        volume,
+        rg_state,
+        dsp_chain,
+        dropouts,
+        history,
+    } = shared;
+    let host = cpal::default_host();
+    let mut current_stream: Option<OpenStream> = None;
+
+    // Silences the open stream between tracks (engine thread writes,
+    // callback reads), and since when
+    let hold = Arc::new(AtomicBool::new(false));
+    let mut held_since: Option<Instant> = None;
+
+    // Bit-perfect flag — shared with callback for zero-processing passthrough
+    let bit_perfect_cb = Arc::new(AtomicBool::new(true));
+
+    // Fade request flags (atomic — callback reads, engine thread writes)
+    let fade_req_pause = Arc::new(AtomicBool::new(false));
+    let fade_req_resume = Arc::new(AtomicBool::new(false));
+    let fade_req_stop = Arc::new(AtomicBool::new(false));
+    let fade_ms = Arc::new(AtomicU32::new(0));
+
+    // Buffering targets (engine thread writes, decoder reads)
+    let ahead_ms = Arc::new(AtomicU32::new(BufferPolicy::default().ahead_ms));
+    let refill_ms = Arc::new(AtomicU32::new(BufferPolicy::default().refill_ms));
+    let chain_generation = Arc::new(AtomicU64::new(0));
+
+    // Decoder thread control
+    let decoder_running = Arc::new(AtomicBool::new(false));
+    let decoder_paused = Arc::new(AtomicBool::new(false));
+    let seek_request_ms = Arc::new(AtomicU64::new(u64::MAX));
+
+    /// Recalculate whether the signal path is bit-perfect.
+    /// Bit-perfect = volume is exactly 1.0 AND ReplayGain is OFF (gain_linear ≈ 1.0)
+    /// AND no DSP plugins AND the stream isn't resampled.
+    fn update_bit_perfect(
+        volume: &AtomicU32,
+        rg_state: &Mutex<ReplayGainState>,
+        dsp_chain: &Mutex<Vec<ChainSlot>>,
+        resampled: bool,
+        is_bit_perfect: &AtomicBool,
+        bit_perfect_cb: &AtomicBool,
+    ) {
+        let rg = rg_state.lock();
+        let settings = DspSettings {
+            volume: atomic_to_f32(volume.load(Ordering::Relaxed)),
+            replaygain_mode: rg.get_mode(),
+            replaygain_preamp_db: rg.preamp_db(),
+            clipping_prevention: rg.clipping_prevention(),
+            plugins: dsp_chain.lock().len(),
+        };
+        let bp = settings.is_bit_perfect_on(resampled);
+        is_bit_perfect.store(bp, Ordering::SeqCst);
+        bit_perfect_cb.store(bp, Ordering::SeqCst);
+    }
+
+    loop {
+        match cmd_rx.recv_timeout(Duration::from_millis(16)) {
+            Ok(AudioCommand::Play {
+                path,
+                start_secs,
+                end_secs,
+            }) => {
+                // Stop current playback; the stream goes silent, kept for
+                // this track if the format matches
+                hold.store(true, Ordering::SeqCst);
+                held_since = Some(Instant::now());
+                decoder_running.store(false, Ordering::SeqCst);
+                thread::sleep(Duration::from_millis(50));
+
+                // Open file
+                let mut decoder = match AudioDecoder::open(&path) {
+                    Ok(d) => d,
+                    Err(e) => {
+                        let message = format!("Failed to open: {}", e);
+                        report(&errors, EngineErrorKind::Open, &path, message);
+                        continue;
+                    }
+                };
+
+                let sr = decoder.sample_rate();
+                let ch = decoder.channels();
+                let bit_depth = decoder.bit_depth();
+                // Segment bounds (whole file unless playing a cue-sheet track)
+                let start_secs = start_secs.max(0.0);
+                let dur = match end_secs {
+                    Some(end) => end - start_secs,
+                    None => decoder.duration_secs - start_secs,
+                }
+                .max(0.0);
+                if start_secs > 0.0 {
+                    if let Err(e) = decoder.seek(start_secs) {
+                        let message = format!("Failed to seek to segment start: {}", e);
+                        report(&errors, EngineErrorKind::Seek, &path, message);
+                    }
+                }
+
+                // Read ReplayGain tags from file (streams have none to read)
+                {
+                    let mut rg = rg_state.lock();
+                    let loaded = if http_source::is_url(&path) {
+                        rg.clear();
+                        Ok(())
+                    } else {
+                        rg.load_from_file(&path)
+                    };
+                    if let Err(e) = loaded {
+                        if rg.get_mode() != ReplayGainMode::Off {
+                            let message = format!("Failed to read ReplayGain tags: {}", e);
+                            report(&errors, EngineErrorKind::ReplayGainTags, &path, message);
+                        }
+                    }
+                }
+
+                // ── Output format (A2) ──
+                // The file's format on desktop; the device's on mobile, converted
+                // by the decoder thread.
+                let Some(device) = host.default_output_device() else {
+                    let message = "No output device".to_string();
+                    report(&errors, EngineErrorKind::Output, &path, message);
+                    continue;
+                };
+                let device_name = device.name().ok();
+                let reused = match current_stream.take() {
+                    Some(open) if open.fits(device_name.as_deref(), sr, ch) => Some(open),
+                    // Closed before negotiating, as some devices can't be
+                    // queried while open
+                    _ => None,
+                };
+                let output = match &reused {
+                    Some(open) => open.output.clone(),
+                    None => output::negotiate(&device, sr, ch),
+                };
+                let (actual_sr, out_ch) = (output.sample_rate, output.channels);
+                let mut converter = if actual_sr != sr || out_ch != ch {
+                    match FormatConverter::new(sr, ch, actual_sr, out_ch) {
+                        Ok(c) => Some(c),
+                        Err(e) => {
+                            report(&errors, EngineErrorKind::Output, &path, e);
+                            continue;
+                        }
+                    }
+                } else {
+                    None
+                };
+                let resampled = output.os_resampled || actual_sr != sr;
+
+                // Update state
+                {
+                    let mut s = state.lock();
+                    s.is_playing = true;
+                    s.is_paused = false;
+                    s.duration_secs = dur;
+                    s.position_secs = 0.0;
+                    s.sample_rate = sr;
+                    s.bit_depth = bit_depth;
+                    s.channels = ch as u32;
+                    s.current_file = Some(path.clone());
+                    s.start_secs = start_secs;
+                    s.end_secs = end_secs;
+                    s.resampled = resampled;
+                    s.device_format = None;
+                    s.codec = decoder.codec_name().map(str::to_string);
+                    s.output_device = device_name.clone();
+                }
+                is_playing.store(true, Ordering::SeqCst);
+                is_paused.store(false, Ordering::SeqCst);
+                duration_ms.store((dur * 1000.0) as u64, Ordering::SeqCst);
+                position_ms.store(0, Ordering::SeqCst);
+                // What the ring buffer and output carry
+                current_sample_rate.store(actual_sr, Ordering::SeqCst);
+                current_channels.store(out_ch as u32, Ordering::SeqCst);
+                dropout_count.store(0, Ordering::SeqCst);
+                levels.set_channels(out_ch);
+                bit_meter.start_track(out_ch);
+                clipping.start_track(&path);
+
+                // Update bit-perfect status; if resampled, it's never truly
+                // bit-perfect at the DAC level
+                update_bit_perfect(
+                    &volume,
+                    &rg_state,
+                    &dsp_chain,
+                    resampled,
+                    &is_bit_perfect,
+                    &bit_perfect_cb,
+                );
+
+                // Reset ring buffer and flags
+                ring_buffer.clear();
+                fade_req_pause.store(false, Ordering::SeqCst);
+                fade_req_resume.store(false, Ordering::SeqCst);
+                fade_req_stop.store(false, Ordering::SeqCst);
+                decoder_paused.store(false, Ordering::SeqCst);
+                seek_request_ms.store(u64::MAX, Ordering::SeqCst);
+
+                // ── Spawn decoder thread ──
+                // Pure signal path: decode → (optional ReplayGain) → (optional
+                // DSP plugins) → ring buffer — bit-perfect when both are off.
+                let ring_c = ring_buffer.clone();
+                let running = decoder_running.clone();
+                let paused_d = decoder_paused.clone();
+                let pos_ms = position_ms.clone();
+                let rg_c = rg_state.clone();
+                let seek_r = seek_request_ms.clone();
+                let history_d = history.clone();
+                let errors_d = errors.clone();
+                let path_d = path.clone();
+                let ahead_d = ahead_ms.clone();
+                let refill_d = refill_ms.clone();
+                let chain_d = dsp_chain.clone();
+                let chain_gen_d = chain_generation.clone();
+                let samples_per_sec = actual_sr as usize * out_ch;
+                let fill_for =
+                    move |ms: u32| (samples_per_sec * ms as usize / 1000).min(RING_FILL_MAX);
+                running.store(true, Ordering::SeqCst);
+
+                thread::Builder::new()
+                    .name("decoder".into())
+                    .spawn(move || {
+                        // Counted in absolute file frames; position is reported relative to the segment start
+                        let mut samples_decoded: u64 = (start_secs * sr as f64) as u64;
+                        let end_frame = end_secs.map(|e| (e * sr as f64) as u64);
+                        let mut cpu_seen = resource_usage::thread_cpu_time();
+                        // Filled up to `ahead_ms` and waiting for the refill mark
+                        let mut topped_up = false;
+                        // Instances of the plugins, for the output's format
+                        let mut chain = DspChain::new(actual_sr, out_ch);
+                        let mut chain_seen = None;
+                        let plugin_failed = |message: String| {
+                            report(&errors_d, EngineErrorKind::Plugin, &path_d, message)
+                        };
+
+                        while running.load(Ordering::SeqCst) {
+                            // CPU time for diagnostics
+                            if let Some(cpu) = resource_usage::thread_cpu_time() {
+                                let since = cpu_seen.unwrap_or_default();
+                                history_d.add_decoder_cpu(cpu.saturating_sub(since));
+                                cpu_seen = Some(cpu);
+                            }
+
+                            // Check seek request (relative to the segment start)
+                            let seek_val = seek_r.load(Ordering::SeqCst);
+                            if seek_val != u64::MAX {
+                                let secs = start_secs + seek_val as f64 / 1000.0;
+                                seek_r.store(u64::MAX, Ordering::SeqCst);
+                                ring_c.clear();
+                                if let Some(converter) = converter.as_mut() {
+                                    converter.reset();
+                                }
+                                chain.reset();
+                                if let Err(e) = decoder.seek(secs) {
+                                    let message = format!("Seek failed: {}", e);
+                                    report(&errors_d, EngineErrorKind::Seek, &path_d, message);
+                                }
+                                samples_decoded = (secs * sr as f64) as u64;
+                                continue;
+                            }
+
+                            // Pause check
+                            if paused_d.load(Ordering::Relaxed) {
+                                thread::sleep(Duration::from_millis(10));
+                                continue;
+                            }
+
+                            // Backpressure — don't flood buffer (`ahead_ms`, then wait
+                            // for it to drain to `refill_ms`)
+                            let filled = ring_c.available_read();
+                            let refill = fill_for(refill_d.load(Ordering::Relaxed));
+                            if filled > fill_for(ahead_d.load(Ordering::Relaxed)) {
+                                topped_up = true;
+                            } else if filled <= refill {
+                                topped_up = false;
+                            }
+                            if topped_up {
+                                let drain_ms = (filled.saturating_sub(refill) * 1000
+                                    / samples_per_sec.max(1))
+                                    as u64;
+                                thread::sleep(Duration::from_millis(
+                                    drain_ms.clamp(5, MAX_BACKPRESSURE_SLEEP_MS),
+                                ));
+                                continue;
+                            }
+
+                            // Chain changed: new instances, keeping those still in place
+                            let generation = chain_gen_d.load(Ordering::Acquire);
+                            if chain_seen != Some(generation) {
+                                let slots = chain_d.lock().clone();
+                                chain.update(&slots).into_iter().for_each(plugin_failed);
+                                chain_seen = Some(generation);
+                            }
+
+                            // Decode
+                            match decoder.next_samples() {
+                                Ok(mut samples) => {
+                                    // Stop at the segment end (cue-sheet virtual tracks)
+                                    let mut segment_done = false;
+                                    if let Some(end) = end_frame {
+                                        let remaining =
+                                            end.saturating_sub(samples_decoded) as usize;
+                                        if samples.len() / ch >= remaining {
+                                            samples.truncate(remaining * ch);
+                                            segment_done = true;
+                                        }
+                                    }
+
+                                    let frames = samples.len() / ch;
+                                    samples_decoded += frames as u64;
+                                    let pos = samples_decoded as f64 / sr as f64 - start_secs;
+                                    pos_ms.store((pos.max(0.0) * 1000.0) as u64, Ordering::Relaxed);
+
+                                    // Apply ReplayGain if enabled (the ONLY processing in the path)
+                                    {
+                                        let rg = rg_c.lock();
+                                        rg.apply(&mut samples);
+                                    }
+
+                                    // To the output's format (mobile)
+                                    if let Some(converter) = converter.as_mut() {
+                                        samples = converter.process(&samples);
+                                    }
+
+                                    chain.process(&mut samples, plugin_failed);
+
+                                    // Write to lock-free ring buffer
+                                    ring_c.write(&samples);
+
+                                    if segment_done {
+                                        if let Some(converter) = converter.as_mut() {
+                                            let mut tail = converter.finish();
+                                            chain.process(&mut tail, plugin_failed);
+                                            ring_c.write(&tail);
+                                        }
+                                        drain_and_finish(&running, &ring_c);
+                                        break;
+                                    }
+                                }
+                                Err(DecodeStatus::EndOfStream) => {
+                                    if let Some(converter) = converter.as_mut() {
+                                        let mut tail = converter.finish();
+                                        chain.process(&mut tail, plugin_failed);
+                                        ring_c.write(&tail);
+                                    }
+                                    drain_and_finish(&running, &ring_c);
+                                    break;
+                                }
+                                Err(DecodeStatus::Error(e)) => {
+                                    let message = format!("Decode error: {}", e);
+                                    report(&errors_d, EngineErrorKind::Decode, &path_d, message);
+                                    running.store(false, Ordering::SeqCst);
+                                    break;
+                                }
+                            }
+                        }
+                    })
+                    .expect("Failed to spawn decoder thread");
+
+                // ── Create cpal output stream ──
+                let config = StreamConfig {
+                    channels: out_ch as u16,
+                    sample_rate: SampleRate(actual_sr),
+                    buffer_size: output.buffer_size,
+                };
+
+                let ring_cb = ring_buffer.clone();
+                let vol_cb = volume.clone();
+                let bp_cb = bit_perfect_cb.clone();
+                let pause_cb = fade_req_pause.clone();
+                let resume_cb = fade_req_resume.clone();
+                let stop_cb = fade_req_stop.clone();
+                let fade_ms_cb = fade_ms.clone();
+                let drop_cb = dropout_count.clone();
+                let levels_cb = levels.clone();
+                let bits_cb = bit_meter.clone();
+                let clip_cb = clipping.clone();
+                let tap_cb = loudness_tap.clone();
+                let rec_cb = record_tap.clone();
+                let snap_cb = snapcast_tap.clone();
+                let dropouts_cb = dropouts.clone();
+                let history_cb = history.clone();
+                let hold_cb = hold.clone();
+                let errors_cb = errors.clone();
+                let state_cb = state.clone();
+                let failed = Arc::new(AtomicBool::new(false));
+                let failed_cb = failed.clone();
+
+                // ── AUDIO CALLBACK ──
+                // Rules: NO locks, NO allocs, NO blocking.
+                // Only atomics + lock-free ring buffer.
+                //
+                // AUDIOPHILE SIGNAL PATH:
+                //   Bit-perfect mode (vol=1.0, RG=off): raw samples → output (ZERO processing)
+                //   Normal mode: samples × volume → hard limiter → output
+                //
+                // Equal-power cosine fades on all transitions (no pops, no perceived dips).
+                let stream = match reused {
+                    Some(open) => Ok(open),
+                    None => device
+                        .build_output_stream(
+                            &config,
+                            {
+                                let mut fade = FadeState::Playing;
+                                let mut fade_len: usize = FADE_RAMP_SAMPLES;
+                                let mut fade_ctr: usize = FADE_RAMP_SAMPLES;
+                                let mut held = false;
+                                let ch_count = out_ch;
+                                let ns_per_frame = 1e9 / f64::from(actual_sr.max(1));
+
+                                move |data: &mut [f32], _: &cpal::OutputCallbackInfo| {
+                                    let callback_started = Instant::now();
+
+                                    // Check fade requests (atomic swap — one-shot triggers)
+                                    let requested_len =
+                                        fade_frames(fade_ms_cb.load(Ordering::Relaxed), actual_sr);
+                                    if stop_cb.swap(false, Ordering::Relaxed) {
+                                        fade = FadeState::FadingOut;
+                                        fade_len = requested_len;
+                                        fade_ctr = fade_len;
+                                    }
+                                    if pause_cb.swap(false, Ordering::Relaxed)
+                                        && (fade == FadeState::Playing
+                                            || fade == FadeState::FadingIn)
+                                    {
+                                        fade = FadeState::FadingOut;
+                                        fade_len = requested_len;
+                                        fade_ctr = fade_len;
+                                    }
+                                    if resume_cb.swap(false, Ordering::Relaxed)
+                                        && (fade == FadeState::Silent
+                                            || fade == FadeState::FadingOut)
+                                    {
+                                        fade = FadeState::FadingIn;
+                                        fade_len = requested_len;
+                                        fade_ctr = 0;
+                                    }
+
+                                    // Held between tracks; the next one starts at
+                                    // full level, as on a new stream
+                                    let holding = hold_cb.load(Ordering::Relaxed);
+                                    if holding {
+                                        fade = FadeState::Silent;
+                                    } else if held {
+                                        fade = FadeState::Playing;
+                                    }
+                                    held = holding;
+
+                                    let vol = atomic_to_f32(vol_cb.load(Ordering::Relaxed));
+                                    let bit_perfect = bp_cb.load(Ordering::Relaxed);
+
+                                    match fade {
+                                        FadeState::Silent => {
+                                            for s in data.iter_mut() {
+                                                *s = 0.0;
+                                            }
+                                        }
+
+                                        FadeState::Playing => {
+                                            let read = ring_cb.read(data);
+                                            loudness_meter::feed_tap(
+                                                &tap_cb,
+                                                &data[..read],
+                                                ch_count,
+                                            );
+                                            loudness_meter::feed_tap(
+                                                &rec_cb,
+                                                &data[..read],
+                                                ch_count,
+                                            );
+                                            loudness_meter::feed_tap(
+                                                &snap_cb,
+                                                &data[..read],
+                                                ch_count,
+                                            );
+                                            let gain = if bit_perfect { 1.0 } else { vol };
+                                            clip_cb.feed(&data[..read], gain, !bit_perfect);
+
+                                            if bit_perfect {
+                                                // ── BIT-PERFECT PASSTHROUGH ──
+                                                // Vol=1.0 and RG=off: NO multiply, NO clamp.
+                                                // Every sample passes through untouched.
+                                                // This is the foobar2000/Qobuz gold standard.
+                                                // (samples already in data from ring_cb.read)
+                                            } else {
+                                                // Normal mode: apply volume + hard limiter
+                                                for s in data[..read].iter_mut() {
+                                                    *s = hard_limit(*s * vol);
+                                                }
+                                            }
+                                            bits_cb.feed(&data[..read], ch_count);
+
+                                            // Buffer underrun — fade out gracefully + count dropout
+                                            if read < data.len() {
+                                                if read > 0 {
+                                                    drop_cb.fetch_add(1, Ordering::Relaxed);
+                                                    dropouts_cb.underrun(data.len(), read);
+                                                }
+                                                // Fade out the tail of what we did get
+                                                let ramp = read.min(FADE_RAMP_SAMPLES);
+                                                for i in 0..ramp {
+                                                    let idx = read - ramp + i;
+                                                    let progress = 1.0 - (i as f32 / ramp as f32);
+                                                    let g = equal_power_gain(progress);
+                                                    data[idx] *= g;
+                                                }
+                                                // Zero-fill the rest
+                                                for s in data[read..].iter_mut() {
+                                                    *s = 0.0;
+                                                }
+                                            }
+                                        }
+
+                                        FadeState::FadingOut => {
+                                            let read = ring_cb.read(data);
+                                            loudness_meter::feed_tap(
+                                                &tap_cb,
+                                                &data[..read],
+                                                ch_count,
+                                            );
+                                            loudness_meter::feed_tap(
+                                                &rec_cb,
+                                                &data[..read],
+                                                ch_count,
+                                            );
+                                            loudness_meter::feed_tap(
+                                                &snap_cb,
+                                                &data[..read],
+                                                ch_count,
+                                            );
+                                            for frame_start in (0..read).step_by(ch_count.max(1)) {
+                                                if fade_ctr == 0 {
+                                                    // Fade complete — zero remaining
+                                                    for c in 0..ch_count {
+                                                        if frame_start + c < read {
+                                                            data[frame_start + c] = 0.0;
+                                                        }
+                                                    }
+                                                } else {
+                                                    let progress =
+                                                        fade_ctr as f32 / fade_len as f32;
+                                                    let g = equal_power_gain(progress);
+                                                    for c in 0..ch_count {
+                                                        if frame_start + c < read {
+                                                            let s = &mut data[frame_start + c];
+                                                            *s = if bit_perfect {
+                                                                *s * g
+                                                            } else {
+                                                                hard_limit(*s * vol * g)
+                                                            };
+                                                        }
+                                                    }
+                                                    fade_ctr = fade_ctr.saturating_sub(1);
+                                                }
+                                            }
+                                            for s in data[read..].iter_mut() {
+                                                *s = 0.0;
+                                            }
+                                            if fade_ctr == 0 {
+                                                fade = FadeState::Silent;
+                                            }
+                                        }
+
+                                        FadeState::FadingIn => {
+                                            let read = ring_cb.read(data);
+                                            loudness_meter::feed_tap(
+                                                &tap_cb,
+                                                &data[..read],
+                                                ch_count,
+                                            );
+                                            loudness_meter::feed_tap(
+                                                &rec_cb,
+                                                &data[..read],
+                                                ch_count,
+                                            );
+                                            loudness_meter::feed_tap(
+                                                &snap_cb,
+                                                &data[..read],
+                                                ch_count,
+                                            );
+
+                                            for frame_start in (0..read).step_by(ch_count.max(1)) {
+                                                let progress = if fade_ctr >= fade_len {
+                                                    1.0
+                                                } else {
+                                                    fade_ctr as f32 / fade_len as f32
+                                                };
+                                                let g = equal_power_gain(progress);
+                                                for c in 0..ch_count {
+                                                    if frame_start + c < read {
+                                                        let s = &mut data[frame_start + c];
+                                                        *s = if bit_perfect && progress >= 1.0 {
+                                                            *s // Full volume, bit-perfect
+                                                        } else if bit_perfect {
+                                                            *s * g // Fading in, apply gain only
+                                                        } else {
+                                                            hard_limit(*s * vol * g)
+                                                        };
+                                                    }
+                                                }
+                                                fade_ctr = fade_ctr.saturating_add(1).min(fade_len);
+                                            }
+                                            for s in data[read..].iter_mut() {
+                                                *s = 0.0;
+                                            }
+                                            if fade_ctr >= fade_len {
+                                                fade = FadeState::Playing;
+                                            }
+                                        }
+                                    }
+
+                                    // Meter what actually goes out
+                                    levels_cb.feed(data, ch_count);
+
+                                    let frames = data.len() / ch_count.max(1);
+                                    history_cb.record_callback(
+                                        callback_started.elapsed(),
+                                        (frames as f64 * ns_per_frame) as u64,
+                                    );
+                                }
+                            },
+                            move |err| {
+                                failed_cb.store(true, Ordering::Relaxed);
+                                let path = state_cb.lock().current_file.clone().unwrap_or_default();
+                                let message = format!("Stream error: {}", err);
+                                report(&errors_cb, EngineErrorKind::Output, &path, message);
+                            },
+                            None,
+                        )
+                        .map_err(|e| format!("Failed to build output stream: {}", e))
+                        .and_then(|stream| {
+                            stream
+                                .play()
+                                .map_err(|e| format!("Failed to start stream: {}", e))?;
+                            Ok(stream)
+                        })
+                        .map(|stream| OpenStream {
+                            _stream: stream,
+                            device: device_name,
+                            source: (sr, ch),
+                            // What the OS settled on, now the stream is open
+                            negotiated: negotiated_format(
+                                &device,
+                                DeviceFormat {
+                                    sample_rate: actual_sr,
+                                    channels: out_ch as u16,
+                                    sample_format: cpal::SampleFormat::F32.to_string(),
+                                    bits_per_sample: 32,
+                                },
+                            ),
+                            output,
+                            resampled,
+                            failed,
+                        }),
+                };
+                let mut open = match stream {
+                    Ok(open) => open,
+                    Err(message) => {
+                        report(&errors, EngineErrorKind::Output, &path, message);
+                        decoder_running.store(false, Ordering::SeqCst);
+                        ring_buffer.clear();
+                        is_playing.store(false, Ordering::SeqCst);
+                        *state.lock() = PlaybackState::default();
+                        continue;
+                    }
+                };
+                // What the OS settled on; a device running at another rate
+                // than the stream carries resamples it, whatever the
+                // supported ranges said
+                let format = open.negotiated.clone();
+                let os_resampled = format.sample_rate != actual_sr;
+                if os_resampled {
+                    log::warn!(
+                        "Device runs at {}Hz, OS resamples {}Hz (not bit-perfect).",
+                        format.sample_rate,
+                        actual_sr
+                    );
+                }
+                // Kept with the stream, so volume or DSP changes don't
+                // report a resampled stream as bit-perfect
+                open.resampled = resampled || os_resampled;
+                update_bit_perfect(
+                    &volume,
+                    &rg_state,
+                    &dsp_chain,
+                    open.resampled,
+                    &is_bit_perfect,
+                    &bit_perfect_cb,
+                );
+                {
+                    let mut s = state.lock();
+                    s.resampled = open.resampled;
+                    s.device_format = Some(format);
+                }
+                hold.store(false, Ordering::SeqCst);
+                held_since = None;
+                current_stream = Some(open);
+            }
+
+            Ok(AudioCommand::Pause) => {
+                fade_req_pause.store(true, Ordering::SeqCst);
+                decoder_paused.store(true, Ordering::SeqCst);
+                is_paused.store(true, Ordering::SeqCst);
+                is_playing.store(false, Ordering::SeqCst);
+                state.lock().is_paused = true;
+                state.lock().is_playing = false;
+            }
+
+            Ok(AudioCommand::Resume) => {
+                decoder_paused.store(false, Ordering::SeqCst);
+                fade_req_resume.store(true, Ordering::SeqCst);
+                is_paused.store(false, Ordering::SeqCst);
+                is_playing.store(true, Ordering::SeqCst);
+                state.lock().is_paused = false;
+                state.lock().is_playing = true;
+            }
+
+            Ok(AudioCommand::Stop) => {
+                fade_req_stop.store(true, Ordering::SeqCst);
+                // A6 fix: use actual sample rate, not hardcoded 44100
+                let sr = current_sample_rate.load(Ordering::Relaxed).max(1);
+                let frames = fade_frames(fade_ms.load(Ordering::Relaxed), sr) as u64;
+                thread::sleep(Duration::from_millis(frames * 1000 / u64::from(sr) + 5));
+                decoder_running.store(false, Ordering::SeqCst);
+                current_stream = None;
+                ring_buffer.clear();
+                is_playing.store(false, Ordering::SeqCst);
+                is_paused.store(false, Ordering::SeqCst);
+                position_ms.store(0, Ordering::SeqCst);
+                *state.lock() = PlaybackState::default();
+            }
+
+            Ok(AudioCommand::Seek(secs)) => {
+                let ms = (secs * 1000.0) as u64;
+                seek_request_ms.store(ms, Ordering::SeqCst);
+                position_ms.store(ms, Ordering::SeqCst);
+            }
+
+            Ok(AudioCommand::SetVolume(v)) => {
+                volume.store(f32_to_atomic(v.clamp(0.0, 1.0)), Ordering::Relaxed);
+                update_bit_perfect(
+                    &volume,
+                    &rg_state,
+                    &dsp_chain,
+                    current_stream.as_ref().is_some_and(|open| open.resampled),
+                    &is_bit_perfect,
+                    &bit_perfect_cb,
+                );
+            }
+
+            Ok(AudioCommand::SetFadeLength(ms)) => {
+                fade_ms.store(ms, Ordering::Relaxed);
+            }
+
+            Ok(AudioCommand::SetReplayGain(mode)) => {
+                rg_state.lock().set_mode(mode);
+                update_bit_perfect(
+                    &volume,
+                    &rg_state,
+                    &dsp_chain,
+                    current_stream.as_ref().is_some_and(|open| open.resampled),
+                    &is_bit_perfect,
+                    &bit_perfect_cb,
+                );
+            }
+
+            Ok(AudioCommand::SetReplayGainPreamp(db)) => {
+                rg_state.lock().set_preamp(db);
+            }
+
+            Ok(AudioCommand::SetClippingPrevention(on)) => {
+                rg_state.lock().set_clipping_prevention(on);
+                update_bit_perfect(
+                    &volume,
+                    &rg_state,
+                    &dsp_chain,
+                    current_stream.as_ref().is_some_and(|open| open.resampled),
+                    &is_bit_perfect,
+                    &bit_perfect_cb,
+                );
+            }
+
+            Ok(AudioCommand::SetBufferPolicy(policy)) => {
+                ahead_ms.store(policy.ahead_ms, Ordering::Relaxed);
+                refill_ms.store(policy.refill_ms.min(policy.ahead_ms), Ordering::Relaxed);
+                http_source::set_prefetch(policy.prefetch_bytes);
+            }
+
+            Ok(AudioCommand::SetDspChain(slots)) => {
+                *dsp_chain.lock() = slots;
+                chain_generation.fetch_add(1, Ordering::Release);
+                update_bit_perfect(
+                    &volume,
+                    &rg_state,
+                    &dsp_chain,
+                    current_stream.as_ref().is_some_and(|open| open.resampled),
+                    &is_bit_perfect,
+                    &bit_perfect_cb,
+                );
+            }
+
+            Ok(AudioCommand::Shutdown) => {
+                fade_req_stop.store(true, Ordering::SeqCst);
+                thread::sleep(Duration::from_millis(15));
+                decoder_running.store(false, Ordering::SeqCst);
+                drop(current_stream.take());
+                break;
+            }
+
+            Err(crossbeam_channel::RecvTimeoutError::Timeout) => {
+                // Auto-detect end of track
+                if !decoder_running.load(Ordering::Relaxed)
+                    && is_playing.load(Ordering::Relaxed)
+                    && ring_buffer.available_read() == 0
+                {
+                    is_playing.store(false, Ordering::SeqCst);
+                    is_paused.store(false, Ordering::SeqCst);
+                    // Kept open, silent, for the next track in the queue
+                    hold.store(true, Ordering::SeqCst);
+                    held_since = Some(Instant::now());
+                    let mut s = state.lock();
+                    s.is_playing = false;
+                    s.is_paused = false;
+                }
+                if held_since.is_some_and(|since| since.elapsed() > STREAM_IDLE_CLOSE) {
+                    current_stream = None;
+                    held_since = None;
+                }
+
+                // The buffer is empty at an underrun, so the decoder
+                // position is the one being played
+                dropouts.tick(
+                    is_playing.load(Ordering::Relaxed),
+                    ring_buffer.available_read() as f32 / RING_BUFFER_SIZE as f32 * 100.0,
+                    || {
+                        (
+                            state.lock().current_file.clone(),
+                            position_ms.load(Ordering::Relaxed),
+                        )
+                    },
+                );
+                let filled = ring_buffer.available_read();
+                history.tick(
+                    is_playing.load(Ordering::Relaxed),
+                    filled as f32 / RING_BUFFER_SIZE as f32 * 100.0,
+                    buffered_ms(
+                        filled,
+                        current_sample_rate.load(Ordering::Relaxed),
+                        current_channels.load(Ordering::Relaxed),
+                    ),
+                    dropout_count.load(Ordering::Relaxed),
+                );
+            }
+            Err(crossbeam_channel::RecvTimeoutError::Disconnected) => break,
+        }
+    }
+}
+
+/// Wait for the ring buffer to drain before signaling the decoder is done,
+/// so end-of-track detection doesn't cut off the last buffered audio.
+fn drain_and_finish(running: &AtomicBool, ring: &RingBuffer) {
+    while running.load(Ordering::SeqCst) {
+        if ring.available_read() == 0 {
+            break;
+        }
+        thread::sleep(Duration::from_millis(50));
+    }
+    running.store(false, Ordering::SeqCst);
+}
+
+// ─── Audio Safety ───
+
+/// Hard limiter — ONLY used when NOT in bit-perfect mode.
+/// Catches NaN, Inf, and any samples exceeding ±0.99.
+#[inline(always)]
+pub fn hard_limit(s: f32) -> f32 {
+    if s.is_finite() {
+        s.clamp(-HARD_LIMIT_CEILING, HARD_LIMIT_CEILING)
+    } else {
+        0.0
+    }
+}
+
+#[inline]
+pub fn db_to_linear(db: f32) -> f32 {
+    10.0_f32.powf(db / 20.0)
+}
+
+// ─── Device Enumeration ───
+
+pub fn get_output_devices() -> Vec<AudioDeviceInfo> {
+    let host = cpal::default_host();
+    let mut devices = Vec::new();
+    if let Ok(out) = host.output_devices() {
+        for dev in out {
+            if let Ok(name) = dev.name() {
+                let is_default = host
+                    .default_output_device()
+                    .map(|d| d.name().ok() == Some(name.clone()))
+                    .unwrap_or(false);
+                devices.push(AudioDeviceInfo { name, is_default });
+            }
+        }
+    }
+    devices
+}
+
+pub fn get_input_devices() -> Vec<AudioDeviceInfo> {
+    let host = cpal::default_host();
+    let default_name = host.default_input_device().and_then(|d| d.name().ok());
+    let mut devices = Vec::new();
+    if let Ok(inputs) = host.input_devices() {
+        for dev in inputs {
+            if let Ok(name) = dev.name() {
+                let is_default = default_name.as_ref() == Some(&name);
+                devices.push(AudioDeviceInfo { name, is_default });
+            }
+        }
+    }
+    devices
+}
+
+#[derive(Clone, serde::Serialize)]
+pub struct AudioDeviceInfo {
+    pub name: String,
+    pub is_default: bool,
+}
+
+/// A device's running format as the OS reports it: the shared-mode mix
+/// format on WASAPI, the current stream format on CoreAudio. cpal doesn't
+/// expose the WASAPI speaker mask, only the channel count.
+#[derive(Clone, serde::Serialize)]
+pub struct DeviceFormat {
+    pub sample_rate: u32,
+    pub channels: u16,
+    /// cpal's name for the sample type, e.g. `f32`.
+    pub sample_format: String,
+    pub bits_per_sample: u32,
+}
+
+/// The format `device` runs at with a stream of format `stream` open on it.
+/// WASAPI (the shared-mode mix format) and CoreAudio (the current stream
+/// format) report it, and it differs from the stream's when the OS
+/// resamples. Other hosts open the device at the stream's format or fail.
+fn negotiated_format(device: &cpal::Device, stream: DeviceFormat) -> DeviceFormat {
+    if !cfg!(any(windows, target_os = "macos")) {
+        return stream;
+    }
+    match device.default_output_config() {
+        Ok(config) => DeviceFormat {
+            sample_rate: config.sample_rate().0,
+            channels: config.channels(),
+            sample_format: config.sample_format().to_string(),
+            bits_per_sample: config.sample_format().sample_size() as u32 * 8,
+        },
+        Err(e) => {
+            log::warn!("Failed to query the device format: {}", e);
+            stream
+        }
+    }
+}
@@ -0,0 +1,328 @@
+//! Shared HTTP client for online lookups.
+//!
+//! Requests are blocking, so they are made from background threads or
+//! `spawn_blocking`. Every request names the app in its User-Agent, which
+//! the MusicBrainz family of services asks for, and gives up after
+//! [`TIMEOUT`]. Streams ([`get_stream`]) have no overall deadline, only on
+//! connecting and on each read.
+
+use parking_lot::Mutex;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::io::Read;
+use std::sync::OnceLock;
+use std::time::{Duration, Instant};
+
+pub const USER_AGENT: &str = concat!(
+    "masukii/",
+    env!("CARGO_PKG_VERSION"),
+    " ( https://github.com/maskey73/Lossless-Lab )"
+);
+
+const TIMEOUT: Duration = Duration::from_secs(15);
+
+fn agent() -> &'static ureq::Agent {
+    static AGENT: OnceLock<ureq::Agent> = OnceLock::new();
+    AGENT.get_or_init(|| {
+        ureq::AgentBuilder::new()
+            .timeout(TIMEOUT)
+            .user_agent(USER_AGENT)
+            .build()
+    })
+}
+
+fn stream_agent() -> &'static ureq::Agent {
+    static AGENT: OnceLock<ureq::Agent> = OnceLock::new();
+    AGENT.get_or_init(|| {
+        ureq::AgentBuilder::new()
+            .timeout_connect(TIMEOUT)
+            .timeout_read(TIMEOUT)
+            .user_agent(USER_AGENT)
+            .build()
+    })
+}
+
+/// Spaces out requests to a service that limits how often it may be called.
+pub struct RateLimit {
+    interval: Duration,
+    last: Mutex<Option<Instant>>,
+}
+
+impl RateLimit {
+    pub const fn new(interval: Duration) -> Self {
+        Self {
+            interval,
+            last: Mutex::new(None),
+        }
+    }
+
+    /// Block until the next request may be made.
+    pub fn wait(&self) {
+        let mut last = self.last.lock();
+        if let Some(previous) = *last {
+            let due = previous + self.interval;
+            let now = Instant::now();
+            if due > now {
+                std::thread::sleep(due - now);
+            }
+        }
+        *last = Some(Instant::now());
+    }
+}
+
+/// Largest download accepted, so a bad link can't fill memory.
+const MAX_DOWNLOAD_BYTES: u64 = 32 * 1024 * 1024;
+
+/// GET a URL with query parameters and parse the JSON response.
+pub fn get_json<T: DeserializeOwned>(url: &str, query: &[(&str, &str)]) -> Result<T, String> {
+    let mut request = agent().get(url);
+    for (name, value) in query {
+        request = request.query(name, value);
+    }
+    request
+        .call()
+        .map_err(request_error)?
+        .into_json()
+        .map_err(|e| format!("Invalid response: {}", e))
+}
+
+/// Like [`get_json`], with `None` for a 404.
+pub fn get_json_if_found<T: DeserializeOwned>(
+    url: &str,
+    query: &[(&str, &str)],
+) -> Result<Option<T>, String> {
+    let mut request = agent().get(url);
+    for (name, value) in query {
+        request = request.query(name, value);
+    }
+    match request.call() {
+        Ok(response) => response
+            .into_json()
+            .map(Some)
+            .map_err(|e| format!("Invalid response: {}", e)),
+        Err(ureq::Error::Status(404, _)) => Ok(None),
+        Err(e) => Err(request_error(e)),
+    }
+}
+
+/// Like [`get_json`], with extra request headers (e.g. `Authorization`).
+pub fn get_json_with_headers<T: DeserializeOwned>(
+    url: &str,
+    headers: &[(&str, &str)],
+) -> Result<T, String> {
+    let mut request = agent().get(url);
+    for (name, value) in headers {
+        request = request.set(name, value);
+    }
+    request
+        .call()
+        .map_err(request_error)?
+        .into_json()
+        .map_err(|e| format!("Invalid response: {}", e))
+}
+
+/// A failed request, with the status the server answered with, if it did,
+/// so callers can tell a refusal from being offline.
+pub struct RequestError {
+    pub status: Option<u16>,
+    pub message: String,
+}
+
+impl From<ureq::Error> for RequestError {
+    fn from(e: ureq::Error) -> Self {
+        let status = match &e {
+            ureq::Error::Status(code, _) => Some(*code),
+            ureq::Error::Transport(_) => None,
+        };
+        Self {
+            status,
+            message: request_error(e),
+        }
+    }
+}
+
+/// POST a JSON body with extra request headers and parse the JSON response.
+pub fn post_json<B: Serialize, T: DeserializeOwned>(
+    url: &str,
+    headers: &[(&str, &str)],
+    body: &B,
+) -> Result<T, RequestError> {
+    let mut request = agent().post(url);
+    for (name, value) in headers {
+        request = request.set(name, value);
+    }
+    request
+        .send_json(body)?
+        .into_json()
+        .map_err(|e| RequestError {
+            status: None,
+            message: format!("Invalid response: {}", e),
+        })
+}
+
+/// POST a JSON body with extra request headers, for endpoints that answer
+/// with no content.
+pub fn send_json<B: Serialize>(
+    url: &str,
+    headers: &[(&str, &str)],
+    body: &B,
+) -> Result<(), String> {
+    let mut request = agent().post(url);
+    for (name, value) in headers {
+        request = request.set(name, value);
+    }
+    request.send_json(body).map_err(request_error)?;
+    Ok(())
+}
+
+/// Download a file into memory.
+pub fn get_bytes(url: &str) -> Result<Vec<u8>, String> {
+    let response = agent().get(url).call().map_err(request_error)?;
+    let mut bytes = Vec::new();
+    response
+        .into_reader()
+        .take(MAX_DOWNLOAD_BYTES + 1)
+        .read_to_end(&mut bytes)
+        .map_err(|e| format!("Download failed: {}", e))?;
+    if bytes.len() as u64 > MAX_DOWNLOAD_BYTES {
+        return Err(format!(
+            "Download is larger than {} MB",
+            MAX_DOWNLOAD_BYTES / 1024 / 1024
+        ));
+    }
+    Ok(bytes)
+}
+
+/// Like [`get_bytes`], with `None` for a 404.
+pub fn get_bytes_if_found(url: &str) -> Result<Option<Vec<u8>>, String> {
+    match get_bytes(url) {
+        Ok(bytes) => Ok(Some(bytes)),
+        Err(e) if e.starts_with("Request failed with status 404") => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+/// GET a URL and return the response body as text.
+pub fn get_text(url: &str) -> Result<String, String> {
+    agent()
+        .get(url)
+        .call()
+        .map_err(request_error)?
+        .into_string()
+        .map_err(|e| format!("Invalid response: {}", e))
+}
+
+/// POST a text body (e.g. a SOAP envelope) with extra request headers and
+/// return the response body as text.
+pub fn post_text(url: &str, headers: &[(&str, &str)], body: &str) -> Result<String, String> {
+    let mut request = agent().post(url);
+    for (name, value) in headers {
+        request = request.set(name, value);
+    }
+    request
+        .send_string(body)
+        .map_err(request_error)?
+        .into_string()
+        .map_err(|e| format!("Invalid response: {}", e))
+}
+
+/// A response body to read as it arrives.
+pub struct HttpStream {
+    pub reader: Box<dyn Read + Send + Sync>,
+    /// Length of the whole resource, when the server says.
+    pub length: Option<u64>,
+    /// The server takes `Range` requests, so the stream can be reopened at
+    /// another offset.
+    pub ranges: bool,
+    pub content_type: Option<String>,
+    /// Bytes of audio between the ICY metadata blocks an internet radio
+    /// station interleaves into the stream (see [`crate::icy`]).
+    pub icy_metaint: Option<usize>,
+}
+
+/// GET a URL as a stream starting `offset` bytes in. From the start,
+/// internet radio stations are asked for their now-playing titles.
+pub fn get_stream(url: &str, offset: u64) -> Result<HttpStream, String> {
+    let mut request = stream_agent().get(url);
+    if offset > 0 {
+        request = request.set("Range", &format!("bytes={}-", offset));
+    } else {
+        request = request.set("Icy-MetaData", "1");
+    }
+    let response = request.call().map_err(request_error)?;
+    let partial = response.status() == 206;
+    if offset > 0 && !partial {
+        return Err("Server ignored the range request".to_string());
+    }
+    let length = if partial {
+        // "bytes 1000-1999/2000"
+        response
+            .header("Content-Range")
+            .and_then(|r| r.rsplit('/').next())
+            .and_then(|total| total.trim().parse().ok())
+    } else {
+        response
+            .header("Content-Length")
+            .and_then(|l| l.trim().parse().ok())
+    };
+    let ranges = partial
+        || response
+            .header("Accept-Ranges")
+            .is_some_and(|r| r.trim().eq_ignore_ascii_case("bytes"));
+    let content_type = response
+        .header("Content-Type")
+        .map(|t| t.split(';').next().unwrap_or(t).trim().to_string());
+    let icy_metaint = response
+        .header("icy-metaint")
+        .and_then(|i| i.trim().parse().ok())
+        .filter(|&i| i > 0);
+    Ok(HttpStream {
+        reader: response.into_reader(),
+        length,
+        ranges,
+        content_type,
+        icy_metaint,
+    })
+}
+
+/// `url` with `query` appended, percent-encoded, for URLs handed on rather
+/// than requested here (e.g. stream URLs for the queue).
+pub fn url_with_query(url: &str, query: &[(&str, &str)]) -> Result<String, String> {
+    let mut request = agent().get(url);
+    for (name, value) in query {
+        request = request.query(name, value);
+    }
+    request
+        .request_url()
+        .map(|u| u.as_url().to_string())
+        .map_err(|e| format!("Invalid URL {}: {}", url, e))
+}
+
+/// Decoded value of a URL's query parameter.
+pub fn query_value(url: &str, name: &str) -> Option<String> {
+    let url = agent().get(url).request_url().ok()?;
+    url.query_pairs()
+        .into_iter()
+        .find(|(n, _)| *n == name)
+        .map(|(_, v)| v.to_string())
+}
+
+/// POST a form and parse the JSON response.
+pub fn post_form_json<T: DeserializeOwned>(url: &str, form: &[(&str, &str)]) -> Result<T, String> {
+    agent()
+        .post(url)
+        .send_form(form)
+        .map_err(request_error)?
+        .into_json()
+        .map_err(|e| format!("Invalid response: {}", e))
+}
+
+fn request_error(e: ureq::Error) -> String {
+    match e {
+        ureq::Error::Status(code, response) => {
+            let body = response.into_string().unwrap_or_default();
+            format!("Request failed with status {}: {}", code, body.trim())
+        }
+        ureq::Error::Transport(e) => format!("Request failed: {}", e),
+    }
+}
@@ -0,0 +1,238 @@
+//! Audio streamed over HTTP, for playing straight from media servers.
+//!
+//! The response body is decoded as it arrives. Seeks (symphonia makes them
+//! to skip tags, find the stream's end and jump to a position) read ahead
+//! when the target is just past the current offset and otherwise reopen
+//! the request there with a `Range` header. A server that doesn't take
+//! ranges gives an unseekable source, which plays from the start through,
+//! as do internet radio streams, whose ICY metadata is stripped out.
+//!
+//! With a prefetch set ([`set_prefetch`]), a thread downloads up to that
+//! much ahead of the decoder, so a slow patch of network doesn't reach the
+//! output and the radio can idle between bursts.
+
+use parking_lot::{Condvar, Mutex};
+use std::collections::VecDeque;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread;
+use symphonia::core::io::MediaSource;
+
+use crate::http;
+use crate::icy::IcyReader;
+
+/// Forward seeks up to this far are read through rather than reopened.
+const READ_AHEAD_BYTES: u64 = 256 * 1024;
+
+/// Bytes downloaded ahead of the decoder; 0 for none.
+static PREFETCH_BYTES: AtomicUsize = AtomicUsize::new(0);
+
+/// Size of each read the prefetch thread makes.
+const PREFETCH_CHUNK: usize = 16 * 1024;
+
+/// How far ahead of the decoder to download streams. Takes effect on open
+/// streams too.
+pub fn set_prefetch(bytes: usize) {
+    PREFETCH_BYTES.store(bytes, Ordering::Relaxed);
+}
+
+/// `path` is an `http://` or `https://` URL rather than a file.
+pub fn is_url(path: &str) -> bool {
+    let lower = path.get(..8).unwrap_or(path).to_ascii_lowercase();
+    lower.starts_with("http://") || lower.starts_with("https://")
+}
+
+/// File extension of a URL's path, ignoring the query, e.g. `flac`.
+pub fn url_extension(url: &str) -> Option<&str> {
+    let path = url.split(['?', '#']).next()?;
+    let name = path.rsplit('/').next()?;
+    let (_, extension) = name.rsplit_once('.')?;
+    (!extension.is_empty()).then_some(extension)
+}
+
+pub struct HttpSource {
+    url: String,
+    reader: Box<dyn Read + Send + Sync>,
+    position: u64,
+    length: Option<u64>,
+    seekable: bool,
+    content_type: Option<String>,
+}
+
+impl HttpSource {
+    pub fn open(url: &str) -> Result<Self, String> {
+        let stream = http::get_stream(url, 0)?;
+        let reader = prefetch(stream.reader);
+        let reader = match stream.icy_metaint {
+            Some(metaint) => Box::new(IcyReader::new(reader, url, metaint)),
+            None => reader,
+        };
+        Ok(Self {
+            url: url.to_string(),
+            reader,
+            position: 0,
+            length: stream.length,
+            seekable: stream.ranges && stream.length.is_some(),
+            content_type: stream.content_type,
+        })
+    }
+
+    /// MIME type the server gave, e.g. `audio/flac`.
+    pub fn content_type(&self) -> Option<&str> {
+        self.content_type.as_deref()
+    }
+}
+
+impl Read for HttpSource {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = self.reader.read(buf)?;
+        self.position += read as u64;
+        Ok(read)
+    }
+}
+
+impl Seek for HttpSource {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let target = match pos {
+            SeekFrom::Start(offset) => Some(offset),
+            SeekFrom::Current(delta) => self.position.checked_add_signed(delta),
+            SeekFrom::End(delta) => self.length.and_then(|l| l.checked_add_signed(delta)),
+        }
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "Invalid seek"))?;
+        if target == self.position {
+            return Ok(target);
+        }
+        if !self.seekable {
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "Stream isn't seekable",
+            ));
+        }
+
+        if target > self.position && target - self.position <= READ_AHEAD_BYTES {
+            let skip = target - self.position;
+            let skipped = io::copy(&mut (&mut self.reader).take(skip), &mut io::sink())?;
+            self.position += skipped;
+            if skipped == skip {
+                return Ok(target);
+            }
+        }
+        if self.length.is_some_and(|l| target >= l) {
+            // Nothing left to read; a range request there would be refused
+            self.reader = Box::new(io::empty());
+        } else {
+            let stream = http::get_stream(&self.url, target).map_err(io::Error::other)?;
+            self.reader = prefetch(stream.reader);
+        }
+        self.position = target;
+        Ok(target)
+    }
+}
+
+impl MediaSource for HttpSource {
+    fn is_seekable(&self) -> bool {
+        self.seekable
+    }
+
+    fn byte_len(&self) -> Option<u64> {
+        self.length
+    }
+}
+
+/// Download `reader` ahead on a thread, if a prefetch is set.
+fn prefetch(reader: Box<dyn Read + Send + Sync>) -> Box<dyn Read + Send + Sync> {
+    if PREFETCH_BYTES.load(Ordering::Relaxed) == 0 {
+        return reader;
+    }
+    let shared = Arc::new(Prefetch::default());
+    let fetcher = shared.clone();
+    thread::Builder::new()
+        .name("stream-prefetch".into())
+        .spawn(move || fetcher.run(reader))
+        .expect("Failed to spawn prefetch thread");
+    Box::new(Prefetched(shared))
+}
+
+#[derive(Default)]
+struct Prefetch {
+    fetched: Mutex<Fetched>,
+    changed: Condvar,
+}
+
+#[derive(Default)]
+struct Fetched {
+    data: VecDeque<u8>,
+    /// The response ended or failed.
+    done: bool,
+    error: Option<io::Error>,
+    /// The reader was dropped.
+    closed: bool,
+}
+
+impl Prefetch {
+    fn run(&self, mut reader: Box<dyn Read + Send + Sync>) {
+        let mut chunk = vec![0; PREFETCH_CHUNK];
+        loop {
+            {
+                let mut fetched = self.fetched.lock();
+                // At least a chunk, so lowering the prefetch to 0 on an open
+                // stream doesn't stall it
+                while !fetched.closed
+                    && fetched.data.len()
+                        >= PREFETCH_BYTES.load(Ordering::Relaxed).max(PREFETCH_CHUNK)
+                {
+                    self.changed.wait(&mut fetched);
+                }
+                if fetched.closed {
+                    return;
+                }
+            }
+            let result = reader.read(&mut chunk);
+            let mut fetched = self.fetched.lock();
+            match result {
+                Ok(0) => fetched.done = true,
+                Ok(read) => fetched.data.extend(&chunk[..read]),
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => {
+                    fetched.error = Some(e);
+                    fetched.done = true;
+                }
+            }
+            self.changed.notify_all();
+            if fetched.done {
+                return;
+            }
+        }
+    }
+}
+
+/// The reading end of a [`Prefetch`].
+struct Prefetched(Arc<Prefetch>);
+
+impl Read for Prefetched {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let mut fetched = self.0.fetched.lock();
+        loop {
+            if !fetched.data.is_empty() {
+                let read = fetched.data.read(buf)?;
+                self.0.changed.notify_all();
+                return Ok(read);
+            }
+            if let Some(e) = fetched.error.take() {
+                return Err(e);
+            }
+            if fetched.done {
+                return Ok(0);
+            }
+            self.0.changed.wait(&mut fetched);
+        }
+    }
+}
+
+impl Drop for Prefetched {
+    fn drop(&mut self) {
+        self.0.fetched.lock().closed = true;
+        self.0.changed.notify_all();
+    }
+}
@@ -0,0 +1,96 @@
+//! ICY metadata from internet radio (SHOUTcast, Icecast).
+//!
+//! Asked with `Icy-MetaData: 1`, a station sends a metadata block after
+//! every `icy-metaint` bytes of audio: one length byte (in 16-byte units)
+//! then `StreamTitle='Artist - Title';...`, padded with zeros. An empty block
+//! means nothing changed. [`IcyReader`] strips the blocks out so the decoder
+//! sees plain audio, and keeps the latest title per stream URL for
+//! [`stream_title`].
+
+use parking_lot::Mutex;
+use std::collections::HashMap;
+use std::io::{self, Read};
+use std::sync::OnceLock;
+
+fn titles() -> &'static Mutex<HashMap<String, String>> {
+    static TITLES: OnceLock<Mutex<HashMap<String, String>>> = OnceLock::new();
+    TITLES.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+/// The title the station streaming `url` last announced, while it plays.
+pub fn stream_title(url: &str) -> Option<String> {
+    titles().lock().get(url).cloned()
+}
+
+pub struct IcyReader<R> {
+    inner: R,
+    url: String,
+    metaint: usize,
+    /// Audio bytes left before the next metadata block.
+    until_metadata: usize,
+}
+
+impl<R: Read> IcyReader<R> {
+    pub fn new(inner: R, url: &str, metaint: usize) -> Self {
+        Self {
+            inner,
+            url: url.to_string(),
+            metaint,
+            until_metadata: metaint,
+        }
+    }
+
+    /// Read one metadata block. `false` at the end of the stream.
+    fn read_metadata(&mut self) -> io::Result<bool> {
+        let mut length = [0u8; 1];
+        if self.inner.read(&mut length)? == 0 {
+            return Ok(false);
+        }
+        let mut block = vec![0u8; length[0] as usize * 16];
+        self.inner.read_exact(&mut block)?;
+        if let Some(title) = parse_title(&block) {
+            titles().lock().insert(self.url.clone(), title);
+        }
+        Ok(true)
+    }
+}
+
+impl<R: Read> Read for IcyReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        if self.until_metadata == 0 {
+            if !self.read_metadata()? {
+                return Ok(0);
+            }
+            self.until_metadata = self.metaint;
+        }
+        let wanted = buf.len().min(self.until_metadata);
+        let read = self.inner.read(&mut buf[..wanted])?;
+        self.until_metadata -= read;
+        Ok(read)
+    }
+}
+
+impl<R> Drop for IcyReader<R> {
+    fn drop(&mut self) {
+        titles().lock().remove(&self.url);
+    }
+}
+
+/// `StreamTitle` from a metadata block. Stations send UTF-8 or Latin-1.
+fn parse_title(block: &[u8]) -> Option<String> {
+    let end = block.iter().position(|&b| b == 0).unwrap_or(block.len());
+    let block = &block[..end];
+    let text = match std::str::from_utf8(block) {
+        Ok(text) => text.to_string(),
+        Err(_) => block.iter().map(|&b| b as char).collect(),
+    };
+    let start = text.find("StreamTitle='")? + "StreamTitle='".len();
+    let rest = &text[start..];
+    // The title may itself contain quotes; the field ends at "';"
+    let title = rest.find("';").map_or(rest, |end| &rest[..end]);
+    let title = title.trim_end_matches('\'').trim();
+    (!title.is_empty()).then(|| title.to_string())
+}
@@ -0,0 +1,182 @@
+//! Per-channel peak and RMS level meters.
+//!
+//! The output callback feeds the meter the samples it hands the device,
+//! after volume and fades, so the meters show what is actually played. The
+//! callback side is lock-free: each block's peak and sum of squares go into
+//! atomics. Ballistics are applied when levels are read: peaks rise
+//! instantly and fall at the IEC 60268-10 type I rate, RMS is averaged with
+//! a VU-style 300 ms time constant, and a peak-hold marker stays up for two
+//! seconds before falling.
+
+use parking_lot::Mutex;
+use serde::Serialize;
+use std::sync::atomic::{AtomicU32, AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+use crate::engine::db_to_linear;
+
+/// Channels metered; any beyond are ignored.
+pub const MAX_METER_CHANNELS: usize = 8;
+
+/// Peak fall-back rate: 20 dB in 1.7 s.
+const PEAK_DECAY_DB_PER_SEC: f32 = 20.0 / 1.7;
+
+/// RMS integration time constant.
+const RMS_TIME_CONSTANT_SECS: f64 = 0.3;
+
+/// How long the peak-hold marker stays before falling.
+const PEAK_HOLD: Duration = Duration::from_secs(2);
+
+/// Levels are reported down to this many dBFS; silence reads as this.
+const FLOOR_DB: f32 = -96.0;
+
+#[derive(Clone, Serialize)]
+pub struct ChannelLevel {
+    /// Peak level in dBFS, with fall-back.
+    pub peak_db: f32,
+    /// RMS level in dBFS.
+    pub rms_db: f32,
+    /// Highest recent peak in dBFS.
+    pub peak_hold_db: f32,
+    /// A sample reached full scale within the hold time.
+    pub clipped: bool,
+}
+
+#[derive(Clone, Serialize)]
+pub struct AudioLevels {
+    /// One entry per output channel; empty when nothing has played.
+    pub channels: Vec<ChannelLevel>,
+}
+
+/// Meter state after ballistics, kept between reads.
+struct Ballistics {
+    last_read: Instant,
+    peak: [f32; MAX_METER_CHANNELS],
+    mean_square: [f64; MAX_METER_CHANNELS],
+    hold: [f32; MAX_METER_CHANNELS],
+    hold_since: [Instant; MAX_METER_CHANNELS],
+}
+
+pub struct LevelMeter {
+    channels: AtomicU32,
+    /// Largest absolute sample per channel since the last read, as f32 bits.
+    /// Bit patterns of non-negative floats order like the floats, so
+    /// `fetch_max` works on them.
+    block_peak: [AtomicU32; MAX_METER_CHANNELS],
+    /// Sum of squares per channel since the last read, as f64 bits.
+    block_sum_sq: [AtomicU64; MAX_METER_CHANNELS],
+    block_frames: AtomicU64,
+    ballistics: Mutex<Ballistics>,
+}
+
+impl Default for LevelMeter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl LevelMeter {
+    pub fn new() -> Self {
+        let now = Instant::now();
+        Self {
+            channels: AtomicU32::new(0),
+            block_peak: std::array::from_fn(|_| AtomicU32::new(0)),
+            block_sum_sq: std::array::from_fn(|_| AtomicU64::new(0)),
+            block_frames: AtomicU64::new(0),
+            ballistics: Mutex::new(Ballistics {
+                last_read: now,
+                peak: [0.0; MAX_METER_CHANNELS],
+                mean_square: [0.0; MAX_METER_CHANNELS],
+                hold: [0.0; MAX_METER_CHANNELS],
+                hold_since: [now; MAX_METER_CHANNELS],
+            }),
+        }
+    }
+
+    /// Start metering a stream with `channels` channels.
+    pub fn set_channels(&self, channels: usize) {
+        self.channels
+            .store(channels.min(MAX_METER_CHANNELS) as u32, Ordering::Relaxed);
+    }
+
+    /// Add a block of interleaved output samples. Called from the audio
+    /// callback: no locks, no allocations.
+    #[inline]
+    pub fn feed(&self, samples: &[f32], channels: usize) {
+        let metered = channels.min(MAX_METER_CHANNELS);
+        if metered == 0 {
+            return;
+        }
+        let mut peak = [0.0f32; MAX_METER_CHANNELS];
+        let mut sum_sq = [0.0f64; MAX_METER_CHANNELS];
+        for frame in samples.chunks_exact(channels) {
+            for (c, &s) in frame[..metered].iter().enumerate() {
+                if s.is_finite() {
+                    peak[c] = peak[c].max(s.abs());
+                    sum_sq[c] += f64::from(s) * f64::from(s);
+                }
+            }
+        }
+        for c in 0..metered {
+            self.block_peak[c].fetch_max(peak[c].to_bits(), Ordering::Relaxed);
+            // The callback is the only writer, so load + store doesn't race
+            // another add; a read in between at worst drops one block
+            let sum = f64::from_bits(self.block_sum_sq[c].load(Ordering::Relaxed));
+            self.block_sum_sq[c].store((sum + sum_sq[c]).to_bits(), Ordering::Relaxed);
+        }
+        self.block_frames
+            .fetch_add((samples.len() / channels) as u64, Ordering::Relaxed);
+    }
+
+    /// Current levels, with ballistics applied over the time since the
+    /// previous read.
+    pub fn levels(&self) -> AudioLevels {
+        let channels = self.channels.load(Ordering::Relaxed) as usize;
+        let frames = self.block_frames.swap(0, Ordering::Relaxed);
+        let mut b = self.ballistics.lock();
+        let now = Instant::now();
+        let dt = now.duration_since(b.last_read).as_secs_f32();
+        b.last_read = now;
+
+        let fall = db_to_linear(-PEAK_DECAY_DB_PER_SEC * dt);
+        let rms_weight = 1.0 - (-f64::from(dt) / RMS_TIME_CONSTANT_SECS).exp();
+
+        let mut levels = Vec::with_capacity(channels);
+        for c in 0..channels {
+            let block_peak = f32::from_bits(self.block_peak[c].swap(0, Ordering::Relaxed));
+            let block_sum_sq = f64::from_bits(self.block_sum_sq[c].swap(0, Ordering::Relaxed));
+
+            b.peak[c] = block_peak.max(b.peak[c] * fall);
+
+            let block_mean_square = if frames > 0 {
+                block_sum_sq / frames as f64
+            } else {
+                0.0
+            };
+            b.mean_square[c] += rms_weight * (block_mean_square - b.mean_square[c]);
+
+            if b.peak[c] >= b.hold[c] {
+                b.hold[c] = b.peak[c];
+                b.hold_since[c] = now;
+            } else if now.duration_since(b.hold_since[c]) >= PEAK_HOLD {
+                b.hold[c] = (b.hold[c] * fall).max(b.peak[c]);
+            }
+
+            levels.push(ChannelLevel {
+                peak_db: to_db(b.peak[c]),
+                rms_db: to_db(b.mean_square[c].sqrt() as f32),
+                peak_hold_db: to_db(b.hold[c]),
+                clipped: b.hold[c] >= 1.0,
+            });
+        }
+        AudioLevels { channels: levels }
+    }
+}
+
+fn to_db(linear: f32) -> f32 {
+    if linear > 0.0 {
+        (20.0 * linear.log10()).max(FLOOR_DB)
+    } else {
+        FLOOR_DB
+    }
+}
@@ -0,0 +1,29 @@
+//! Masukii's playback engine, without the app around it: decoding (local
+//! files and HTTP streams), the lock-free ring buffer between the decoder
+//! and output threads, bit-perfect output through cpal with volume,
+//! ReplayGain and clipping prevention, and the meters and diagnostics fed
+//! from the output callback.
+//!
+//! Everything here is independent of Tauri, so the desktop app, the
+//! `masukii-cli` binary, integration tests and other frontends share it.
+//! Start with [`engine::AudioEngine`]: commands go in with
+//! [`engine::AudioEngine::send_command`] and the state comes back from
+//! [`engine::AudioEngine::get_state`].
+
+pub mod bit_meter;
+pub mod clipping;
+pub mod decoder;
+pub mod diagnostics_history;
+pub mod dropouts;
+pub mod dsp_plugin;
+pub mod engine;
+pub mod http;
+pub mod http_source;
+pub mod icy;
+pub mod levels;
+pub mod loudness_meter;
+pub mod output;
+pub mod paths;
+pub mod replaygain;
+pub mod resource_usage;
+pub mod ring_buffer;
@@ -0,0 +1,105 @@
+//! Live EBU R128 loudness meter.
+//!
+//! The output callback copies the samples it plays into a tap (a lock-free
+//! ring buffer) before the volume control, so the meter shows the loudness
+//! of the recording (after ReplayGain, when on) in step with what is heard,
+//! not of the listening level. A meter thread feeds the tap to the `ebur128`
+//! crate and reports momentary (400 ms), short-term (3 s) and integrated
+//! loudness ten times a second while playing. Integrated loudness starts
+//! over with each file.
+
+use ebur128::{EbuR128, Mode};
+use serde::Serialize;
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use crate::engine::AudioEngine;
+use crate::ring_buffer::RingBuffer;
+
+/// Tap size in samples: comfortably over one report interval of 192 kHz
+/// 8-channel audio.
+pub const TAP_SIZE: usize = 1 << 19;
+
+/// How often readings are reported; the momentary window's update rate in
+/// EBU Tech 3341.
+const REPORT_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Loudness readings in LUFS; `None` while too little has been measured or
+/// during silence.
+#[derive(Clone, Serialize)]
+pub struct LoudnessReading {
+    pub momentary_lufs: Option<f64>,
+    pub short_term_lufs: Option<f64>,
+    pub integrated_lufs: Option<f64>,
+}
+
+/// Start the meter thread. `on_reading` is called from it every
+/// [`REPORT_INTERVAL`] while something is playing.
+pub fn spawn_meter(
+    engine: Arc<AudioEngine>,
+    mut on_reading: impl FnMut(&LoudnessReading) + Send + 'static,
+) {
+    thread::Builder::new()
+        .name("loudness-meter".into())
+        .spawn(move || {
+            let tap = engine.loudness_tap();
+            let mut scratch = vec![0.0f32; TAP_SIZE];
+            // (file, sample rate, channels) the meter was started for
+            let mut metering: Option<(String, u32, u32)> = None;
+            let mut meter: Option<EbuR128> = None;
+
+            loop {
+                thread::sleep(REPORT_INTERVAL);
+
+                let state = engine.get_state();
+                let (rate, channels) = engine.output_format();
+                let format = state
+                    .current_file
+                    .filter(|_| rate > 0 && channels > 0)
+                    .map(|file| (file, rate, channels));
+                if format != metering {
+                    drain(&tap, &mut scratch);
+                    meter = format.as_ref().and_then(|(_, rate, channels)| {
+                        EbuR128::new(*channels, *rate, Mode::M | Mode::S | Mode::I)
+                            .map_err(|e| log::warn!("Loudness meter unavailable: {}", e))
+                            .ok()
+                    });
+                    metering = format;
+                }
+                let Some(meter) = meter.as_mut() else {
+                    drain(&tap, &mut scratch);
+                    continue;
+                };
+
+                let n = tap.read(&mut scratch);
+                if let Err(e) = meter.add_frames_f32(&scratch[..n]) {
+                    log::warn!("Loudness measurement failed: {}", e);
+                }
+                if !state.is_playing {
+                    continue;
+                }
+                on_reading(&LoudnessReading {
+                    momentary_lufs: meter.loudness_momentary().ok().filter(|l| l.is_finite()),
+                    short_term_lufs: meter.loudness_shortterm().ok().filter(|l| l.is_finite()),
+                    integrated_lufs: meter.loudness_global().ok().filter(|l| l.is_finite()),
+                });
+            }
+        })
+        .expect("Failed to spawn loudness meter thread");
+}
+
+/// Copy a block of played samples into the tap. Called from the audio
+/// callback; whole frames only, and the block is dropped when the tap is
+/// full so channels never go out of step.
+#[inline]
+pub fn feed_tap(tap: &RingBuffer, samples: &[f32], channels: usize) {
+    let samples = &samples[..samples.len() - samples.len() % channels.max(1)];
+    if !samples.is_empty() && tap.available_write() >= samples.len() {
+        tap.write(samples);
+    }
+}
+
+fn drain(tap: &RingBuffer, scratch: &mut [f32]) {
+    while tap.read(scratch) > 0 {}
+}
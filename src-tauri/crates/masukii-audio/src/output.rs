@@ -0,0 +1,195 @@
+//! Output format negotiation.
+//!
+//! On desktop the stream opens at the file's own format: WASAPI and
+//! CoreAudio take it or convert it themselves, so the path stays
+//! bit-perfect wherever the device allows. Mobile outputs don't work that
+//! way. iOS (RemoteIO) only runs at the audio session's rate, and Android's
+//! AAudio plays reliably at the device's native rate, in mono or stereo.
+//! There the stream opens at the device's default rate, with a buffer of
+//! about [`MOBILE_BUFFER_MS`] rather than the low-latency default that
+//! underruns when the app is in the background. The decoder thread then
+//! converts to that format with a [`FormatConverter`].
+
+use cpal::traits::DeviceTrait;
+use cpal::{BufferSize, SupportedBufferSize};
+use rubato::{FftFixedInOut, Resampler};
+
+/// Output buffer to ask for on mobile.
+pub const MOBILE_BUFFER_MS: u32 = 40;
+
+/// Most channels mobile outputs are opened with.
+const MOBILE_MAX_CHANNELS: usize = 2;
+
+/// Frames the resampler takes at a time.
+const CHUNK_FRAMES: usize = 1024;
+
+#[derive(Clone)]
+pub struct OutputFormat {
+    pub sample_rate: u32,
+    pub channels: usize,
+    pub buffer_size: BufferSize,
+    /// The device doesn't list the file's rate, so the OS will resample.
+    pub os_resampled: bool,
+}
+
+/// The format to open `device` at for audio of `sample_rate` and `channels`.
+pub fn negotiate(device: &cpal::Device, sample_rate: u32, channels: usize) -> OutputFormat {
+    if cfg!(any(target_os = "android", target_os = "ios")) {
+        if let Some(format) = mobile_format(device, channels) {
+            return format;
+        }
+    }
+
+    // Check if the output device actually supports the file's sample rate.
+    let os_resampled = match device.supported_output_configs() {
+        Ok(configs) => !configs.into_iter().any(|range| {
+            sample_rate >= range.min_sample_rate().0
+                && sample_rate <= range.max_sample_rate().0
+                && range.channels() as usize >= channels
+        }),
+        Err(_) => false, // Can't query — hope for the best
+    };
+    if os_resampled {
+        log::warn!(
+            "Device doesn't natively support {}Hz. OS will resample (not bit-perfect).",
+            sample_rate
+        );
+    }
+    OutputFormat {
+        // Still request it — let cpal/WASAPI handle the conversion
+        sample_rate,
+        channels,
+        buffer_size: BufferSize::Default,
+        os_resampled,
+    }
+}
+
+fn mobile_format(device: &cpal::Device, channels: usize) -> Option<OutputFormat> {
+    let default = device.default_output_config().ok()?;
+    let sample_rate = default.sample_rate().0;
+    let channels = channels
+        .min(MOBILE_MAX_CHANNELS)
+        .min(usize::from(default.channels()))
+        .max(1);
+    let frames = sample_rate * MOBILE_BUFFER_MS / 1000;
+    let buffer_size = match default.buffer_size() {
+        SupportedBufferSize::Range { min, max } => BufferSize::Fixed(frames.clamp(*min, *max)),
+        SupportedBufferSize::Unknown => BufferSize::Default,
+    };
+    Some(OutputFormat {
+        sample_rate,
+        channels,
+        buffer_size,
+        os_resampled: false,
+    })
+}
+
+/// Decoded audio to the output's rate and channel count. Mono goes to both
+/// sides; beyond what the output has, only the front channels are kept.
+pub struct FormatConverter {
+    in_channels: usize,
+    out_channels: usize,
+    /// None when the rates already match.
+    resampler: Option<FftFixedInOut<f32>>,
+    /// Each output channel, waiting to fill the resampler's next chunk.
+    pending: Vec<Vec<f32>>,
+}
+
+impl FormatConverter {
+    pub fn new(
+        in_rate: u32,
+        in_channels: usize,
+        out_rate: u32,
+        out_channels: usize,
+    ) -> Result<Self, String> {
+        let resampler = (in_rate != out_rate)
+            .then(|| {
+                FftFixedInOut::new(
+                    in_rate as usize,
+                    out_rate as usize,
+                    CHUNK_FRAMES,
+                    out_channels,
+                )
+            })
+            .transpose()
+            .map_err(|e| format!("Failed to create resampler: {}", e))?;
+        Ok(Self {
+            in_channels: in_channels.max(1),
+            out_channels: out_channels.max(1),
+            resampler,
+            pending: vec![Vec::new(); out_channels.max(1)],
+        })
+    }
+
+    pub fn process(&mut self, samples: &[f32]) -> Vec<f32> {
+        let mut out = Vec::new();
+        for frame in samples.chunks_exact(self.in_channels) {
+            for c in 0..self.out_channels {
+                let s = match frame.get(c) {
+                    Some(&s) => s,
+                    None if self.in_channels == 1 => frame[0],
+                    None => 0.0,
+                };
+                if self.resampler.is_some() {
+                    self.pending[c].push(s);
+                } else {
+                    out.push(s);
+                }
+            }
+        }
+        self.resample(&mut out);
+        out
+    }
+
+    /// What's left waiting for a full chunk, padded with silence. Call at
+    /// the end of a track.
+    pub fn finish(&mut self) -> Vec<f32> {
+        let mut out = Vec::new();
+        let Some(resampler) = self.resampler.as_ref() else {
+            return out;
+        };
+        if self.pending[0].is_empty() {
+            return out;
+        }
+        let chunk = resampler.input_frames_next();
+        for channel in &mut self.pending {
+            channel.resize(chunk, 0.0);
+        }
+        self.resample(&mut out);
+        out
+    }
+
+    /// Drop what's waiting, after a seek.
+    pub fn reset(&mut self) {
+        for channel in &mut self.pending {
+            channel.clear();
+        }
+        if let Some(resampler) = self.resampler.as_mut() {
+            resampler.reset();
+        }
+    }
+
+    fn resample(&mut self, out: &mut Vec<f32>) {
+        let Some(resampler) = self.resampler.as_mut() else {
+            return;
+        };
+        while self.pending[0].len() >= resampler.input_frames_next() {
+            let take = resampler.input_frames_next();
+            let input: Vec<&[f32]> = self.pending.iter().map(|c| &c[..take]).collect();
+            let resampled = match resampler.process(&input, None) {
+                Ok(resampled) => resampled,
+                Err(e) => {
+                    log::warn!("Resampling for the output failed: {}", e);
+                    self.pending.iter_mut().for_each(Vec::clear);
+                    break;
+                }
+            };
+            for i in 0..resampled[0].len() {
+                out.extend(resampled.iter().map(|channel| channel[i]));
+            }
+            for channel in &mut self.pending {
+                channel.drain(..take);
+            }
+        }
+    }
+}
@@ -0,0 +1,84 @@
+//! Path handling for Windows long paths and network shares.
+//!
+//! The standard library already adds the `\\?\` prefix (`\\?\UNC\` for
+//! `\\server\share` paths) when opening files, so paths over 260
+//! characters work in every `std::fs` call. What it doesn't do is turn
+//! verbatim paths, as returned by `canonicalize` or pasted by users, back
+//! into the plain form the library stores; [`plain_path`] does, so prefix
+//! matches against library roots keep working.
+//!
+//! A slow or unreachable share can block a filesystem call for a minute or
+//! more. [`with_timeout`] runs such a call on a helper thread and gives up
+//! after a deadline; the thread finishes (and is dropped) whenever the OS
+//! returns.
+
+use std::path::Path;
+use std::sync::mpsc;
+use std::time::{Duration, Instant};
+
+/// How long a library root may take to answer before it counts as offline.
+pub const ROOT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How long opening an audio file may take.
+pub const OPEN_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// `path` without a `\\?\` or `\\?\UNC\` verbatim prefix.
+pub fn plain_path(path: &str) -> String {
+    if let Some(rest) = path.strip_prefix(r"\\?\UNC\") {
+        format!(r"\\{}", rest)
+    } else if let Some(rest) = path.strip_prefix(r"\\?\") {
+        rest.to_string()
+    } else {
+        path.to_string()
+    }
+}
+
+/// Run `f` on a helper thread, failing if it takes longer than `timeout`.
+pub fn with_timeout<T: Send + 'static>(
+    timeout: Duration,
+    f: impl FnOnce() -> T + Send + 'static,
+) -> Result<T, String> {
+    let (tx, rx) = mpsc::channel();
+    std::thread::Builder::new()
+        .name("fs-timeout".to_string())
+        .spawn(move || {
+            let _ = tx.send(f());
+        })
+        .map_err(|e| format!("Failed to start filesystem check: {}", e))?;
+    rx.recv_timeout(timeout)
+        .map_err(|_| format!("Timed out after {} s", timeout.as_secs()))
+}
+
+/// Whether `path` is a reachable folder, giving up after `timeout`.
+pub fn is_dir_within(path: &str, timeout: Duration) -> bool {
+    let path = path.to_string();
+    with_timeout(timeout, move || Path::new(&path).is_dir()).unwrap_or(false)
+}
+
+/// [`is_dir_within`] for several folders at once, sharing one deadline.
+pub fn dirs_reachable(paths: &[String], timeout: Duration) -> Vec<bool> {
+    let deadline = Instant::now() + timeout;
+    let checks: Vec<_> = paths
+        .iter()
+        .map(|path| {
+            let (tx, rx) = mpsc::channel();
+            let path = path.clone();
+            let spawned = std::thread::Builder::new()
+                .name("fs-timeout".to_string())
+                .spawn(move || {
+                    let _ = tx.send(Path::new(&path).is_dir());
+                });
+            spawned.ok().map(|_| rx)
+        })
+        .collect();
+    checks
+        .into_iter()
+        .map(|rx| {
+            rx.and_then(|rx| {
+                rx.recv_timeout(deadline.saturating_duration_since(Instant::now()))
+                    .ok()
+            })
+            .unwrap_or(false)
+        })
+        .collect()
+}
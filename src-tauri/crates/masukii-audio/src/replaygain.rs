@@ -0,0 +1,328 @@
+/// ReplayGain implementation.
+///
+/// Reads R128 / ReplayGain tags from audio files and applies gain adjustment
+/// in the decoder thread. When mode is Off, the signal path is 100% untouched
+/// (bit-perfect). Clipping prevention optionally limits gain to prevent
+/// the adjusted signal from exceeding 0 dBFS.
+use crate::engine::{db_to_linear, ReplayGainMode};
+use lofty::prelude::*;
+use lofty::probe::Probe;
+
+/// R128 gain tags are stored in 1/256 dB steps.
+pub const R128_SCALE: f32 = 256.0;
+
+/// R128 gains are relative to -23 LUFS, ReplayGain 2.0 to -18 LUFS.
+pub const R128_TO_REPLAYGAIN_DB: f32 = 5.0;
+
+/// Comment description / freeform name iTunes stores Sound Check under.
+const ITUNNORM: &str = "iTunNORM";
+
+/// Per-track ReplayGain values read from metadata tags.
+#[derive(Clone, Default, serde::Serialize, serde::Deserialize)]
+pub struct ReplayGainInfo {
+    /// Track gain in dB (e.g. -7.5).
+    pub track_gain_db: Option<f32>,
+    /// Track peak as linear value (e.g. 0.98).
+    pub track_peak: Option<f32>,
+    /// Album gain in dB.
+    pub album_gain_db: Option<f32>,
+    /// Album peak as linear value.
+    pub album_peak: Option<f32>,
+}
+
+pub struct ReplayGainState {
+    mode: ReplayGainMode,
+    clipping_prevention: bool,
+    /// Added to tagged files' gain, in dB.
+    preamp_db: f32,
+    info: ReplayGainInfo,
+    /// Cached linear gain to apply. Recalculated when mode/info changes.
+    gain_linear: f32,
+}
+
+impl Default for ReplayGainState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ReplayGainState {
+    pub fn new() -> Self {
+        Self {
+            mode: ReplayGainMode::Off,
+            clipping_prevention: true,
+            preamp_db: 0.0,
+            info: ReplayGainInfo::default(),
+            gain_linear: 1.0,
+        }
+    }
+
+    pub fn set_mode(&mut self, mode: ReplayGainMode) {
+        self.mode = mode;
+        self.recalculate_gain();
+    }
+
+    pub fn set_clipping_prevention(&mut self, on: bool) {
+        self.clipping_prevention = on;
+        self.recalculate_gain();
+    }
+
+    pub fn set_preamp(&mut self, db: f32) {
+        self.preamp_db = db;
+        self.recalculate_gain();
+    }
+
+    pub fn get_info(&self) -> &ReplayGainInfo {
+        &self.info
+    }
+
+    pub fn get_mode(&self) -> ReplayGainMode {
+        self.mode
+    }
+
+    pub fn clipping_prevention(&self) -> bool {
+        self.clipping_prevention
+    }
+
+    pub fn preamp_db(&self) -> f32 {
+        self.preamp_db
+    }
+
+    /// Gain applied to the loaded file, in dB.
+    pub fn gain_db(&self) -> f32 {
+        20.0 * self.gain_linear.log10()
+    }
+
+    /// Whether the loaded file has a gain tag for the current mode.
+    pub fn has_gain(&self) -> bool {
+        match self.mode {
+            ReplayGainMode::Off => false,
+            ReplayGainMode::Track => self.info.track_gain_db.is_some(),
+            ReplayGainMode::Album => self
+                .info
+                .album_gain_db
+                .or(self.info.track_gain_db)
+                .is_some(),
+        }
+    }
+
+    /// Read ReplayGain tags from an audio file. If they can't be read, the
+    /// file plays without gain.
+    pub fn load_from_file(&mut self, path: &str) -> Result<(), String> {
+        let tags = read_replaygain_tags(path);
+        self.info = match &tags {
+            Ok(info) => info.clone(),
+            Err(_) => ReplayGainInfo::default(),
+        };
+        self.recalculate_gain();
+        tags.map(|_| ())
+    }
+
+    /// Forget the last file's tags, for a source that has none.
+    pub fn clear(&mut self) {
+        self.info = ReplayGainInfo::default();
+        self.recalculate_gain();
+    }
+
+    fn recalculate_gain(&mut self) {
+        let gain_db = match self.mode {
+            ReplayGainMode::Off => {
+                self.gain_linear = 1.0;
+                return;
+            }
+            ReplayGainMode::Track => self.info.track_gain_db,
+            ReplayGainMode::Album => {
+                // Fall back to track gain if album gain missing
+                self.info.album_gain_db.or(self.info.track_gain_db)
+            }
+        };
+
+        let Some(db) = gain_db else {
+            // No gain tag found — passthrough
+            self.gain_linear = 1.0;
+            return;
+        };
+
+        let mut gain = db_to_linear(db + self.preamp_db);
+
+        // Clipping prevention: limit gain so (gain * peak) <= 1.0
+        if self.clipping_prevention {
+            let peak = match self.mode {
+                ReplayGainMode::Track => self.info.track_peak,
+                ReplayGainMode::Album => self.info.album_peak.or(self.info.track_peak),
+                ReplayGainMode::Off => None,
+            };
+
+            if let Some(peak) = peak {
+                if peak > 0.0 {
+                    let max_gain = 1.0 / peak;
+                    if gain > max_gain {
+                        gain = max_gain;
+                    }
+                }
+            }
+        }
+
+        self.gain_linear = gain;
+    }
+
+    /// Apply ReplayGain to a buffer of interleaved samples.
+    /// When mode is Off, this is a no-op (bit-perfect passthrough).
+    #[inline]
+    pub fn apply(&self, samples: &mut [f32]) {
+        // Fast path: if gain is exactly 1.0, don't touch the data at all.
+        // This ensures bit-perfect playback when ReplayGain is off or no tags found.
+        if (self.gain_linear - 1.0).abs() < f32::EPSILON {
+            return;
+        }
+
+        let g = self.gain_linear;
+        for s in samples.iter_mut() {
+            *s *= g;
+        }
+    }
+}
+
+/// Parse ReplayGain tags from an audio file using lofty. Files without any
+/// fall back to iTunes Sound Check.
+fn read_replaygain_tags(path: &str) -> Result<ReplayGainInfo, String> {
+    let tagged = Probe::open(path)
+        .map_err(|e| format!("{}", e))?
+        .read()
+        .map_err(|e| format!("{}", e))?;
+
+    Ok(match tagged.primary_tag().or_else(|| tagged.first_tag()) {
+        Some(tag) => {
+            let info = replaygain_from_tag(tag);
+            if info.track_gain_db.is_none() && info.album_gain_db.is_none() {
+                sound_check_from_tag(tag).unwrap_or(info)
+            } else {
+                info
+            }
+        }
+        None => ReplayGainInfo::default(),
+    })
+}
+
+/// ReplayGain values stored in a tag.
+pub fn replaygain_from_tag(tag: &lofty::tag::Tag) -> ReplayGainInfo {
+    // Standard ReplayGain tags (Vorbis Comments / ID3v2 TXXX / APE / MP4),
+    // falling back to R128 gains (Opus)
+    let track_gain = find_tag_value(
+        tag,
+        ItemKey::ReplayGainTrackGain,
+        &["REPLAYGAIN_TRACK_GAIN", "replaygain_track_gain"],
+    );
+    let track_peak = find_tag_value(
+        tag,
+        ItemKey::ReplayGainTrackPeak,
+        &["REPLAYGAIN_TRACK_PEAK", "replaygain_track_peak"],
+    );
+    let album_gain = find_tag_value(
+        tag,
+        ItemKey::ReplayGainAlbumGain,
+        &["REPLAYGAIN_ALBUM_GAIN", "replaygain_album_gain"],
+    );
+    let album_peak = find_tag_value(
+        tag,
+        ItemKey::ReplayGainAlbumPeak,
+        &["REPLAYGAIN_ALBUM_PEAK", "replaygain_album_peak"],
+    );
+
+    ReplayGainInfo {
+        track_gain_db: parse_gain_value(&track_gain).or_else(|| r128_gain(tag, "R128_TRACK_GAIN")),
+        track_peak: parse_peak_value(&track_peak),
+        album_gain_db: parse_gain_value(&album_gain).or_else(|| r128_gain(tag, "R128_ALBUM_GAIN")),
+        album_peak: parse_peak_value(&album_peak),
+    }
+}
+
+/// Track gain and peak from the iTunes Sound Check value (`iTunNORM`) that
+/// iTunes writes instead of ReplayGain: a COMM frame in MP3s, a freeform
+/// atom in MP4s.
+pub fn sound_check_from_tag(tag: &lofty::tag::Tag) -> Option<ReplayGainInfo> {
+    let value = tag
+        .items()
+        .find(|item| match item.key() {
+            ItemKey::Comment => item.description().eq_ignore_ascii_case(ITUNNORM),
+            // "----:com.apple.iTunes:iTunNORM", or a bare Vorbis/APE field
+            ItemKey::Unknown(key) => key
+                .rsplit(':')
+                .next()
+                .is_some_and(|k| k.eq_ignore_ascii_case(ITUNNORM)),
+            _ => false,
+        })?
+        .value()
+        .text()?;
+    let (gain_db, peak) = parse_sound_check(value)?;
+    Some(ReplayGainInfo {
+        track_gain_db: Some(gain_db),
+        track_peak: peak,
+        album_gain_db: None,
+        album_peak: None,
+    })
+}
+
+/// Ten hex words. The first two are the left and right channel loudness as
+/// the power, in thousandths, of a reference level, so the gain is
+/// -10·log10(max / 1000) dB; words 7 and 8 are the channel peaks as 16-bit
+/// sample values.
+fn parse_sound_check(value: &str) -> Option<(f32, Option<f32>)> {
+    let words = value
+        .split_whitespace()
+        .map(|w| u32::from_str_radix(w, 16).ok())
+        .collect::<Option<Vec<u32>>>()?;
+    let level = *words.get(..2)?.iter().max()?;
+    if level == 0 {
+        return None;
+    }
+    let gain_db = -10.0 * (level as f32 / 1000.0).log10();
+    let peak = words
+        .get(6..8)
+        .and_then(|peaks| peaks.iter().max().copied())
+        .filter(|&p| p > 0)
+        .map(|p| p as f32 / 32768.0);
+    Some((gain_db, peak))
+}
+
+fn find_tag_value(tag: &lofty::tag::Tag, standard: ItemKey, keys: &[&str]) -> Option<String> {
+    if let Some(item) = tag.get_string(&standard) {
+        return Some(item.to_string());
+    }
+    for key in keys {
+        // Try as ItemKey::Unknown (custom tags)
+        if let Some(item) = tag.get_string(&ItemKey::Unknown(key.to_string())) {
+            return Some(item.to_string());
+        }
+    }
+    None
+}
+
+/// R128 gain tag (Q7.8 fixed point, relative to -23 LUFS) as a ReplayGain
+/// gain in dB (relative to -18 LUFS).
+fn r128_gain(tag: &lofty::tag::Tag, key: &str) -> Option<f32> {
+    let value: i32 = tag
+        .get_string(&ItemKey::Unknown(key.to_string()))?
+        .trim()
+        .parse()
+        .ok()?;
+    Some(value as f32 / R128_SCALE + R128_TO_REPLAYGAIN_DB)
+}
+
+/// Parse a gain value like "-7.5 dB" → -7.5
+fn parse_gain_value(s: &Option<String>) -> Option<f32> {
+    s.as_ref().and_then(|v| {
+        v.trim()
+            .trim_end_matches(" dB")
+            .trim_end_matches(" db")
+            .trim_end_matches("dB")
+            .trim()
+            .parse::<f32>()
+            .ok()
+    })
+}
+
+/// Parse a peak value like "0.988" → 0.988
+fn parse_peak_value(s: &Option<String>) -> Option<f32> {
+    s.as_ref().and_then(|v| v.trim().parse::<f32>().ok())
+}
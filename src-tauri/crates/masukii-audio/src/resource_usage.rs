@@ -0,0 +1,89 @@
+//! CPU time and memory readings from the OS, for diagnostics.
+//!
+//! Thread CPU time is what the scheduler actually ran the calling thread
+//! for, so time spent waiting for the disk or preempted by other programs
+//! doesn't count. Resident memory is the process's working set. Both are
+//! `None` on platforms without a reading.
+
+use std::time::Duration;
+
+/// Whether [`thread_cpu_time`] works on this platform.
+pub const THREAD_CPU_SUPPORTED: bool = cfg!(any(unix, windows));
+
+/// CPU time (user and kernel) the calling thread has used so far.
+#[cfg(unix)]
+pub fn thread_cpu_time() -> Option<Duration> {
+    let mut ts = libc::timespec {
+        tv_sec: 0,
+        tv_nsec: 0,
+    };
+    // SAFETY: `ts` is a valid timespec for the duration of the call
+    let rc = unsafe { libc::clock_gettime(libc::CLOCK_THREAD_CPUTIME_ID, &mut ts) };
+    (rc == 0).then(|| Duration::new(ts.tv_sec as u64, ts.tv_nsec as u32))
+}
+
+/// CPU time (user and kernel) the calling thread has used so far.
+#[cfg(windows)]
+pub fn thread_cpu_time() -> Option<Duration> {
+    use windows_sys::Win32::Foundation::FILETIME;
+    use windows_sys::Win32::System::Threading::{GetCurrentThread, GetThreadTimes};
+
+    let zero = FILETIME {
+        dwLowDateTime: 0,
+        dwHighDateTime: 0,
+    };
+    let (mut created, mut exited, mut kernel, mut user) = (zero, zero, zero, zero);
+    // SAFETY: the pseudo-handle is always valid for the calling thread and
+    // the FILETIMEs outlive the call
+    let ok = unsafe {
+        GetThreadTimes(
+            GetCurrentThread(),
+            &mut created,
+            &mut exited,
+            &mut kernel,
+            &mut user,
+        )
+    };
+    // FILETIMEs count 100 ns intervals
+    let ticks = |t: FILETIME| (u64::from(t.dwHighDateTime) << 32) | u64::from(t.dwLowDateTime);
+    (ok != 0).then(|| Duration::from_nanos((ticks(kernel) + ticks(user)) * 100))
+}
+
+#[cfg(not(any(unix, windows)))]
+pub fn thread_cpu_time() -> Option<Duration> {
+    None
+}
+
+/// Resident memory (working set) of the whole process, in bytes.
+#[cfg(target_os = "linux")]
+pub fn resident_memory_bytes() -> Option<u64> {
+    // Second field of statm: resident pages
+    let statm = std::fs::read_to_string("/proc/self/statm").ok()?;
+    let pages: u64 = statm.split_whitespace().nth(1)?.parse().ok()?;
+    // SAFETY: sysconf has no preconditions
+    let page_size = unsafe { libc::sysconf(libc::_SC_PAGESIZE) };
+    (page_size > 0).then(|| pages * page_size as u64)
+}
+
+/// Resident memory (working set) of the whole process, in bytes.
+#[cfg(windows)]
+pub fn resident_memory_bytes() -> Option<u64> {
+    use windows_sys::Win32::System::ProcessStatus::{
+        K32GetProcessMemoryInfo, PROCESS_MEMORY_COUNTERS,
+    };
+    use windows_sys::Win32::System::Threading::GetCurrentProcess;
+
+    let size = std::mem::size_of::<PROCESS_MEMORY_COUNTERS>() as u32;
+    // SAFETY: PROCESS_MEMORY_COUNTERS is plain data, valid when zeroed
+    let mut counters: PROCESS_MEMORY_COUNTERS = unsafe { std::mem::zeroed() };
+    counters.cb = size;
+    // SAFETY: the pseudo-handle is always valid for this process and
+    // `counters` is `size` bytes
+    let ok = unsafe { K32GetProcessMemoryInfo(GetCurrentProcess(), &mut counters, size) };
+    (ok != 0).then_some(counters.WorkingSetSize as u64)
+}
+
+#[cfg(not(any(target_os = "linux", windows)))]
+pub fn resident_memory_bytes() -> Option<u64> {
+    None
+}
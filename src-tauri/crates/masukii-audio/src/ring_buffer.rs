@@ -8,7 +8,6 @@
 ///
 /// Design based on the same principles used by foobar2000, JACK, and
 /// professional audio software.
-
 use std::sync::atomic::{AtomicUsize, Ordering};
 
 pub struct RingBuffer {
@@ -27,7 +26,10 @@ pub struct RingBuffer {
 impl RingBuffer {
     pub fn new(capacity: usize) -> Self {
         // Ensure capacity is a power of 2
-        assert!(capacity.is_power_of_two(), "Ring buffer capacity must be power of 2");
+        assert!(
+            capacity.is_power_of_two(),
+            "Ring buffer capacity must be power of 2"
+        );
 
         Self {
             buffer: vec![0.0; capacity].into_boxed_slice(),
@@ -61,15 +63,16 @@ impl RingBuffer {
         //   3. The consumer only reads up to read_pos..write_pos
         //   4. The ordering ensures the consumer sees the data after we publish write_pos
         let buf_ptr = self.buffer.as_ptr() as *mut f32;
-        for i in 0..to_write {
+        for (i, &sample) in data[..to_write].iter().enumerate() {
             let idx = (write + i) & self.mask;
             unsafe {
-                buf_ptr.add(idx).write(data[i]);
+                buf_ptr.add(idx).write(sample);
             }
         }
 
         // Publish the new write position (Release ensures data is visible before pointer update)
-        self.write_pos.store(write.wrapping_add(to_write), Ordering::Release);
+        self.write_pos
+            .store(write.wrapping_add(to_write), Ordering::Release);
 
         to_write
     }
@@ -90,13 +93,14 @@ impl RingBuffer {
 
         // Read samples — safe because only ONE thread reads
         let buf_ptr = self.buffer.as_ptr();
-        for i in 0..to_read {
+        for (i, out) in output[..to_read].iter_mut().enumerate() {
             let idx = (read + i) & self.mask;
-            output[i] = unsafe { buf_ptr.add(idx).read() };
+            *out = unsafe { buf_ptr.add(idx).read() };
         }
 
         // Publish the new read position
-        self.read_pos.store(read.wrapping_add(to_read), Ordering::Release);
+        self.read_pos
+            .store(read.wrapping_add(to_read), Ordering::Release);
 
         to_read
     }
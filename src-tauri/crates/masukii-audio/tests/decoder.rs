@@ -0,0 +1,86 @@
+use std::path::PathBuf;
+
+use masukii_audio::decoder::{AudioDecoder, DecodeStatus};
+
+const SAMPLE_RATE: u32 = 44_100;
+const CHANNELS: u16 = 2;
+
+/// A 16-bit stereo WAV of `frames` frames: a ramp on the left, its negation
+/// on the right.
+fn write_wav(name: &str, frames: u32) -> PathBuf {
+    let data_len = frames * u32::from(CHANNELS) * 2;
+    let mut wav = Vec::with_capacity(44 + data_len as usize);
+    wav.extend_from_slice(b"RIFF");
+    wav.extend_from_slice(&(36 + data_len).to_le_bytes());
+    wav.extend_from_slice(b"WAVEfmt ");
+    wav.extend_from_slice(&16u32.to_le_bytes());
+    wav.extend_from_slice(&1u16.to_le_bytes());
+    wav.extend_from_slice(&CHANNELS.to_le_bytes());
+    wav.extend_from_slice(&SAMPLE_RATE.to_le_bytes());
+    wav.extend_from_slice(&(SAMPLE_RATE * u32::from(CHANNELS) * 2).to_le_bytes());
+    wav.extend_from_slice(&(CHANNELS * 2).to_le_bytes());
+    wav.extend_from_slice(&16u16.to_le_bytes());
+    wav.extend_from_slice(b"data");
+    wav.extend_from_slice(&data_len.to_le_bytes());
+    for frame in 0..frames {
+        let sample = (frame % 1000) as i16 * 16;
+        wav.extend_from_slice(&sample.to_le_bytes());
+        wav.extend_from_slice(&(-sample).to_le_bytes());
+    }
+
+    let path =
+        std::env::temp_dir().join(format!("masukii-audio-{}-{}.wav", name, std::process::id()));
+    std::fs::write(&path, wav).unwrap();
+    path
+}
+
+fn decode_all(decoder: &mut AudioDecoder) -> Vec<f32> {
+    let mut samples = Vec::new();
+    loop {
+        match decoder.next_samples() {
+            Ok(chunk) => samples.extend(chunk),
+            Err(DecodeStatus::EndOfStream) => return samples,
+            Err(DecodeStatus::Error(e)) => panic!("decode failed: {}", e),
+        }
+    }
+}
+
+#[test]
+fn decodes_pcm_wav_exactly() {
+    let path = write_wav("exact", SAMPLE_RATE);
+    let mut decoder = AudioDecoder::open(path.to_str().unwrap()).unwrap();
+    assert_eq!(decoder.sample_rate(), SAMPLE_RATE);
+    assert_eq!(decoder.channels(), 2);
+    assert_eq!(decoder.bit_depth(), Some(16));
+    assert!((decoder.duration_secs - 1.0).abs() < 1e-6);
+
+    let samples = decode_all(&mut decoder);
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(samples.len(), SAMPLE_RATE as usize * 2);
+    for (frame, pair) in samples.chunks(2).enumerate() {
+        let expected = f32::from((frame % 1000) as i16 * 16) / 32768.0;
+        assert_eq!(pair[0], expected);
+        assert_eq!(pair[1], -expected);
+    }
+}
+
+#[test]
+fn seeks_to_a_position() {
+    let path = write_wav("seek", SAMPLE_RATE * 2);
+    let mut decoder = AudioDecoder::open(path.to_str().unwrap()).unwrap();
+    decoder.seek(1.5).unwrap();
+    let remaining = decode_all(&mut decoder).len() / 2;
+    std::fs::remove_file(&path).unwrap();
+    // Seeks land on a packet boundary at or before the position
+    let expected = SAMPLE_RATE as usize / 2;
+    assert!(
+        remaining >= expected && remaining < expected + 4096,
+        "{} frames left",
+        remaining
+    );
+}
+
+#[test]
+fn reports_missing_files() {
+    assert!(AudioDecoder::open("/nonexistent/masukii-audio.flac").is_err());
+}
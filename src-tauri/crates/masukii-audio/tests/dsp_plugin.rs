@@ -0,0 +1,179 @@
+use std::ffi::c_void;
+use std::sync::Arc;
+
+use masukii_audio::dsp_plugin::{self, ChainSlot, DspChain, DspPlugin, RawParam, RawPlugin};
+
+// A gain plugin linked into the test, with switches to misbehave.
+
+const GAIN: u32 = 0;
+const FAIL: u32 = 1;
+const NAN: u32 = 2;
+
+unsafe extern "C" fn create(_sample_rate: u32, channels: u32) -> *mut c_void {
+    if channels == 0 {
+        return std::ptr::null_mut();
+    }
+    Box::into_raw(Box::new([1.0f32, 0.0, 0.0])) as *mut c_void
+}
+
+unsafe extern "C" fn destroy(instance: *mut c_void) {
+    drop(Box::from_raw(instance as *mut [f32; 3]));
+}
+
+unsafe extern "C" fn set_param(instance: *mut c_void, index: u32, value: f32) {
+    (*(instance as *mut [f32; 3]))[index as usize] = value;
+}
+
+unsafe extern "C" fn process(instance: *mut c_void, samples: *mut f32, frames: usize) -> i32 {
+    let params = &*(instance as *const [f32; 3]);
+    let samples = std::slice::from_raw_parts_mut(samples, frames * 2);
+    for s in samples.iter_mut() {
+        *s *= params[GAIN as usize];
+    }
+    if params[NAN as usize] > 0.5 {
+        samples[0] = f32::NAN;
+    }
+    if params[FAIL as usize] > 0.5 {
+        return 7;
+    }
+    0
+}
+
+fn descriptor(abi_version: u32) -> *const RawPlugin {
+    let params = Box::leak(Box::new([
+        RawParam {
+            id: c"gain".as_ptr(),
+            name: c"Gain".as_ptr(),
+            unit: std::ptr::null(),
+            min: 0.0,
+            max: 2.0,
+            default_value: 1.0,
+            step: 0.0,
+        },
+        RawParam {
+            id: c"fail".as_ptr(),
+            name: c"Fail".as_ptr(),
+            unit: std::ptr::null(),
+            min: 0.0,
+            max: 1.0,
+            default_value: 0.0,
+            step: 1.0,
+        },
+        RawParam {
+            id: c"nan".as_ptr(),
+            name: c"NaN".as_ptr(),
+            unit: std::ptr::null(),
+            min: 0.0,
+            max: 1.0,
+            default_value: 0.0,
+            step: 1.0,
+        },
+    ]));
+    Box::leak(Box::new(RawPlugin {
+        abi_version,
+        id: c"test.gain".as_ptr(),
+        name: c"Test gain".as_ptr(),
+        version: c"1.0".as_ptr(),
+        param_count: params.len() as u32,
+        params: params.as_ptr(),
+        create: Some(create),
+        destroy: Some(destroy),
+        set_param: Some(set_param),
+        process: Some(process),
+        reset: None,
+    }))
+}
+
+fn plugin() -> Arc<DspPlugin> {
+    Arc::new(unsafe { DspPlugin::from_raw(descriptor(dsp_plugin::ABI_VERSION)) }.unwrap())
+}
+
+fn chain(plugin: &Arc<DspPlugin>, params: Vec<f32>) -> DspChain {
+    let mut chain = DspChain::new(48000, 2);
+    let errors = chain.update(&[ChainSlot {
+        plugin: plugin.clone(),
+        params,
+    }]);
+    assert!(errors.is_empty());
+    chain
+}
+
+#[test]
+fn plugin_schema_is_read_and_checked() {
+    let plugin = plugin();
+    assert_eq!(plugin.id(), "test.gain");
+    assert_eq!(plugin.name(), "Test gain");
+    let ids: Vec<&str> = plugin.params().iter().map(|p| p.id.as_str()).collect();
+    assert_eq!(ids, ["gain", "fail", "nan"]);
+    assert_eq!(plugin.params()[0].clamp(5.0), 2.0);
+    assert_eq!(plugin.params()[0].clamp(f32::NAN), 1.0);
+
+    let wrong_abi = unsafe { DspPlugin::from_raw(descriptor(dsp_plugin::ABI_VERSION + 1)) };
+    assert!(wrong_abi.is_err());
+    assert!(unsafe { DspPlugin::from_raw(std::ptr::null()) }.is_err());
+}
+
+#[test]
+fn chain_processes_and_updates_params() {
+    let plugin = plugin();
+    let mut chain = chain(&plugin, vec![0.5, 0.0, 0.0]);
+
+    let mut samples = vec![0.5f32; 256];
+    chain.process(&mut samples, |e| panic!("{}", e));
+    assert!(samples.iter().all(|&s| s == 0.25));
+
+    // Out of range: clamped to 2.0
+    chain.update(&[ChainSlot {
+        plugin: plugin.clone(),
+        params: vec![3.0, 0.0, 0.0],
+    }]);
+    let mut samples = vec![0.25f32; 256];
+    chain.process(&mut samples, |e| panic!("{}", e));
+    assert!(samples.iter().all(|&s| s == 0.5));
+}
+
+#[test]
+fn failing_plugin_is_bypassed() {
+    let plugin = plugin();
+    let mut chain = chain(&plugin, vec![0.5, 1.0, 0.0]);
+
+    let mut failures = Vec::new();
+    let mut samples = vec![0.5f32; 256];
+    chain.process(&mut samples, |e| failures.push(e));
+    assert_eq!(failures.len(), 1);
+    assert!(samples.iter().all(|&s| s == 0.5));
+
+    // Stays bypassed without reporting again
+    let mut samples = vec![0.5f32; 256];
+    chain.process(&mut samples, |e| failures.push(e));
+    assert_eq!(failures.len(), 1);
+    assert!(samples.iter().all(|&s| s == 0.5));
+}
+
+#[test]
+fn invalid_output_is_discarded() {
+    let plugin = plugin();
+    let mut chain = chain(&plugin, vec![1.0, 0.0, 1.0]);
+
+    let mut failures = Vec::new();
+    let mut samples = vec![0.5f32; 256];
+    chain.process(&mut samples, |e| failures.push(e));
+    assert_eq!(failures.len(), 1);
+    assert!(samples.iter().all(|&s| s == 0.5));
+}
+
+#[test]
+fn discovery_reports_broken_libraries() {
+    let dir = std::env::temp_dir().join(format!("masukii-plugins-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let broken = dir.join(format!("broken.{}", std::env::consts::DLL_EXTENSION));
+    std::fs::write(&broken, b"not a library").unwrap();
+    std::fs::write(dir.join("readme.txt"), b"ignored").unwrap();
+
+    let discovery = dsp_plugin::discover(&dir);
+    assert!(discovery.plugins.is_empty());
+    assert_eq!(discovery.failed.len(), 1);
+    assert_eq!(discovery.failed[0].path, broken.to_string_lossy());
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
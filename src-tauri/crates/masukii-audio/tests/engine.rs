@@ -0,0 +1,25 @@
+use masukii_audio::engine::{DspSettings, ReplayGainMode};
+
+fn settings(volume: f32) -> DspSettings {
+    DspSettings {
+        volume,
+        replaygain_mode: ReplayGainMode::Off,
+        replaygain_preamp_db: 0.0,
+        clipping_prevention: true,
+        plugins: 0,
+    }
+}
+
+#[test]
+fn full_volume_without_processing_is_bit_perfect() {
+    assert!(settings(1.0).is_bit_perfect_on(false));
+    assert!(!settings(0.5).is_bit_perfect_on(false));
+}
+
+#[test]
+fn volume_changes_keep_a_resampled_stream_not_bit_perfect() {
+    // Lowered and then restored, as SetVolume does, on a stream the OS
+    // resamples
+    assert!(!settings(0.5).is_bit_perfect_on(true));
+    assert!(!settings(1.0).is_bit_perfect_on(true));
+}
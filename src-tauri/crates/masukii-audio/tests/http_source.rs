@@ -0,0 +1,53 @@
+use std::io::{Read, Write};
+use std::net::TcpListener;
+use std::thread;
+
+use masukii_audio::http_source::{self, HttpSource};
+
+/// Serve `body` once over HTTP on a local port and return its URL.
+fn serve(body: Vec<u8>) -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}/track.flac", listener.local_addr().unwrap());
+    thread::spawn(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+        let mut request = Vec::new();
+        let mut byte = [0; 1];
+        while !request.ends_with(b"\r\n\r\n") && stream.read(&mut byte).unwrap() == 1 {
+            request.push(byte[0]);
+        }
+        let head = format!(
+            "HTTP/1.1 200 OK\r\nContent-Type: audio/flac\r\nContent-Length: {}\r\n\r\n",
+            body.len()
+        );
+        stream.write_all(head.as_bytes()).unwrap();
+        let _ = stream.write_all(&body);
+    });
+    url
+}
+
+fn body(len: usize) -> Vec<u8> {
+    (0..len).map(|i| (i % 251) as u8).collect()
+}
+
+#[test]
+fn prefetched_stream_reads_through() {
+    let expected = body(300_000);
+    http_source::set_prefetch(64 * 1024);
+    let mut source = HttpSource::open(&serve(expected.clone())).unwrap();
+
+    assert_eq!(source.content_type(), Some("audio/flac"));
+    let mut read = Vec::new();
+    source.read_to_end(&mut read).unwrap();
+    assert_eq!(read, expected);
+}
+
+#[test]
+fn dropping_a_prefetched_stream_early_is_fine() {
+    http_source::set_prefetch(16 * 1024);
+    let mut source = HttpSource::open(&serve(body(200_000))).unwrap();
+
+    let mut start = [0; 100];
+    source.read_exact(&mut start).unwrap();
+    assert_eq!(start.to_vec(), body(100));
+    drop(source);
+}
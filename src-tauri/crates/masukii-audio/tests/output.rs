@@ -0,0 +1,51 @@
+use masukii_audio::output::FormatConverter;
+
+#[test]
+fn passes_through_at_the_same_format() {
+    let mut converter = FormatConverter::new(44100, 2, 44100, 2).unwrap();
+    let samples = [0.1, 0.2, 0.3, 0.4];
+    assert_eq!(converter.process(&samples), samples);
+    assert!(converter.finish().is_empty());
+}
+
+#[test]
+fn puts_mono_on_both_sides() {
+    let mut converter = FormatConverter::new(48000, 1, 48000, 2).unwrap();
+    assert_eq!(converter.process(&[0.5, -0.5]), [0.5, 0.5, -0.5, -0.5]);
+}
+
+#[test]
+fn keeps_the_front_pair_of_surround() {
+    let mut converter = FormatConverter::new(48000, 6, 48000, 2).unwrap();
+    let frame = [0.1, 0.2, 0.3, 0.4, 0.5, 0.6];
+    assert_eq!(converter.process(&frame), [0.1, 0.2]);
+}
+
+#[test]
+fn resamples_to_the_output_rate() {
+    let mut converter = FormatConverter::new(44100, 2, 48000, 2).unwrap();
+    let seconds = 2;
+    let frames = 44100 * seconds;
+    let samples: Vec<f32> = (0..frames)
+        .flat_map(|i| {
+            let s = (i as f32 * 440.0 * std::f32::consts::TAU / 44100.0).sin() * 0.5;
+            [s, s]
+        })
+        .collect();
+
+    let mut out = converter.process(&samples);
+    out.extend(converter.finish());
+    let out_frames = out.len() / 2;
+    // Whole chunks, so up to one chunk of padding at the end
+    assert!(out_frames >= 48000 * seconds);
+    assert!(out_frames < 48000 * seconds + 2048);
+    assert!(out.iter().all(|s| s.abs() <= 0.6));
+}
+
+#[test]
+fn reset_drops_what_was_waiting() {
+    let mut converter = FormatConverter::new(44100, 1, 48000, 1).unwrap();
+    converter.process(&[0.25; 100]);
+    converter.reset();
+    assert!(converter.finish().is_empty());
+}
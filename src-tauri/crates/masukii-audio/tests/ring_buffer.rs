@@ -0,0 +1,78 @@
+use std::sync::Arc;
+use std::thread;
+
+use masukii_audio::ring_buffer::RingBuffer;
+
+#[test]
+fn reads_back_what_was_written() {
+    let ring = RingBuffer::new(8);
+    assert_eq!(ring.write(&[1.0, 2.0, 3.0]), 3);
+    assert_eq!(ring.available_read(), 3);
+
+    let mut out = [0.0; 4];
+    assert_eq!(ring.read(&mut out), 3);
+    assert_eq!(&out[..3], &[1.0, 2.0, 3.0]);
+    assert_eq!(ring.available_read(), 0);
+}
+
+#[test]
+fn keeps_one_slot_free_when_full() {
+    let ring = RingBuffer::new(8);
+    assert_eq!(ring.write(&[0.5; 10]), 7);
+    assert_eq!(ring.available_write(), 0);
+    assert_eq!(ring.write(&[0.5]), 0);
+}
+
+#[test]
+fn wraps_around_the_end() {
+    let ring = RingBuffer::new(8);
+    let mut out = [0.0; 8];
+    for round in 0..10 {
+        let samples: Vec<f32> = (0..5).map(|i| (round * 5 + i) as f32).collect();
+        assert_eq!(ring.write(&samples), 5);
+        assert_eq!(ring.read(&mut out[..5]), 5);
+        assert_eq!(&out[..5], &samples[..]);
+    }
+}
+
+#[test]
+fn clear_empties_the_buffer() {
+    let ring = RingBuffer::new(8);
+    ring.write(&[1.0; 4]);
+    ring.clear();
+    assert_eq!(ring.available_read(), 0);
+    assert_eq!(ring.available_write(), 7);
+}
+
+#[test]
+fn passes_samples_between_threads_in_order() {
+    const COUNT: usize = 100_000;
+    let ring = Arc::new(RingBuffer::new(1024));
+
+    let producer = {
+        let ring = ring.clone();
+        thread::spawn(move || {
+            let mut next = 0;
+            while next < COUNT {
+                let end = (next + 300).min(COUNT);
+                let chunk: Vec<f32> = (next..end).map(|i| i as f32).collect();
+                next += ring.write(&chunk);
+                thread::yield_now();
+            }
+        })
+    };
+
+    let mut expected = 0;
+    let mut out = [0.0; 256];
+    while expected < COUNT {
+        let read = ring.read(&mut out);
+        for &sample in &out[..read] {
+            assert_eq!(sample, expected as f32);
+            expected += 1;
+        }
+        if read == 0 {
+            thread::yield_now();
+        }
+    }
+    producer.join().unwrap();
+}
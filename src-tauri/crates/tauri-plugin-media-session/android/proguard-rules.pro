@@ -0,0 +1 @@
+-keep class com.masukii.mediasession.** { *; }
@@ -0,0 +1,297 @@
+package com.masukii.mediasession
+
+import android.Manifest
+import android.app.Activity
+import android.app.PendingIntent
+import android.content.BroadcastReceiver
+import android.content.Context
+import android.content.Intent
+import android.content.IntentFilter
+import android.content.pm.PackageManager
+import android.graphics.BitmapFactory
+import android.media.AudioManager
+import android.net.ConnectivityManager
+import android.os.Build
+import android.os.PowerManager
+import android.support.v4.media.MediaMetadataCompat
+import android.support.v4.media.session.MediaSessionCompat
+import android.support.v4.media.session.PlaybackStateCompat
+import android.webkit.WebView
+import androidx.core.app.ActivityCompat
+import androidx.core.app.NotificationChannelCompat
+import androidx.core.app.NotificationCompat
+import androidx.core.app.NotificationManagerCompat
+import androidx.core.content.ContextCompat
+import androidx.core.net.ConnectivityManagerCompat
+import androidx.media.AudioAttributesCompat
+import androidx.media.AudioFocusRequestCompat
+import androidx.media.AudioManagerCompat
+import app.tauri.annotation.Command
+import app.tauri.annotation.InvokeArg
+import app.tauri.annotation.TauriPlugin
+import app.tauri.plugin.Channel
+import app.tauri.plugin.Invoke
+import app.tauri.plugin.JSObject
+import app.tauri.plugin.Plugin
+
+private const val CHANNEL_ID = "playback"
+private const val NOTIFICATION_ID = 1
+private const val ACTION_BUTTON = "com.masukii.mediasession.BUTTON"
+private const val EXTRA_ACTION = "action"
+
+@InvokeArg
+class EventHandlerArgs {
+    lateinit var handler: Channel
+}
+
+@InvokeArg
+class MetadataArgs {
+    var title: String? = null
+    var artist: String? = null
+    var album: String? = null
+    // A local image file
+    var artworkPath: String? = null
+    var durationMs: Long = 0
+}
+
+@InvokeArg
+class PlaybackArgs {
+    // "playing", "paused" or "stopped"
+    var status: String = "stopped"
+    var positionMs: Long = 0
+}
+
+// The session, its notification and audio focus. Transport buttons, focus
+// changes and "becoming noisy" go to the handler as { action, ... }.
+@TauriPlugin
+class MediaSessionPlugin(private val activity: Activity) : Plugin(activity) {
+    private val audioManager = activity.getSystemService(Context.AUDIO_SERVICE) as AudioManager
+    private val powerManager = activity.getSystemService(Context.POWER_SERVICE) as PowerManager
+    private val connectivity =
+        activity.getSystemService(Context.CONNECTIVITY_SERVICE) as ConnectivityManager
+    private var session: MediaSessionCompat? = null
+    private var handler: Channel? = null
+    private var metadata: MetadataArgs = MetadataArgs()
+    private var status = "stopped"
+    private var focusRequest: AudioFocusRequestCompat? = null
+    private var askedForNotifications = false
+
+    private val receiver = object : BroadcastReceiver() {
+        override fun onReceive(context: Context, intent: Intent) {
+            when (intent.action) {
+                AudioManager.ACTION_AUDIO_BECOMING_NOISY -> send("becoming_noisy")
+                ACTION_BUTTON -> intent.getStringExtra(EXTRA_ACTION)?.let { send(it) }
+            }
+        }
+    }
+
+    override fun load(webView: WebView) {
+        super.load(webView)
+        val session = MediaSessionCompat(activity, "Masukii")
+        session.setCallback(object : MediaSessionCompat.Callback() {
+            override fun onPlay() = send("play")
+            override fun onPause() = send("pause")
+            override fun onStop() = send("stop")
+            override fun onSkipToNext() = send("next")
+            override fun onSkipToPrevious() = send("previous")
+            override fun onSeekTo(pos: Long) {
+                send(JSObject().put("action", "seek_to").put("position_ms", pos))
+            }
+        })
+        session.isActive = true
+        this.session = session
+
+        NotificationManagerCompat.from(activity).createNotificationChannel(
+            NotificationChannelCompat.Builder(CHANNEL_ID, NotificationManagerCompat.IMPORTANCE_LOW)
+                .setName("Playback")
+                .setShowBadge(false)
+                .build()
+        )
+        val filter = IntentFilter().apply {
+            addAction(AudioManager.ACTION_AUDIO_BECOMING_NOISY)
+            addAction(ACTION_BUTTON)
+        }
+        ContextCompat.registerReceiver(activity, receiver, filter, ContextCompat.RECEIVER_NOT_EXPORTED)
+    }
+
+    override fun onDestroy() {
+        activity.unregisterReceiver(receiver)
+        abandonFocus()
+        NotificationManagerCompat.from(activity).cancel(NOTIFICATION_ID)
+        session?.release()
+        super.onDestroy()
+    }
+
+    @Command
+    fun setEventHandler(invoke: Invoke) {
+        handler = invoke.parseArgs(EventHandlerArgs::class.java).handler
+        invoke.resolve()
+    }
+
+    @Command
+    fun setMetadata(invoke: Invoke) {
+        val args = invoke.parseArgs(MetadataArgs::class.java)
+        metadata = args
+        val builder = MediaMetadataCompat.Builder()
+            .putString(MediaMetadataCompat.METADATA_KEY_TITLE, args.title)
+            .putString(MediaMetadataCompat.METADATA_KEY_ARTIST, args.artist)
+            .putString(MediaMetadataCompat.METADATA_KEY_ALBUM, args.album)
+            .putLong(MediaMetadataCompat.METADATA_KEY_DURATION, args.durationMs)
+        args.artworkPath?.let { BitmapFactory.decodeFile(it) }?.let {
+            builder.putBitmap(MediaMetadataCompat.METADATA_KEY_ALBUM_ART, it)
+        }
+        session?.setMetadata(builder.build())
+        updateNotification()
+        invoke.resolve()
+    }
+
+    @Command
+    fun setPlayback(invoke: Invoke) {
+        val args = invoke.parseArgs(PlaybackArgs::class.java)
+        val state = when (args.status) {
+            "playing" -> PlaybackStateCompat.STATE_PLAYING
+            "paused" -> PlaybackStateCompat.STATE_PAUSED
+            else -> PlaybackStateCompat.STATE_STOPPED
+        }
+        val actions = PlaybackStateCompat.ACTION_PLAY or
+            PlaybackStateCompat.ACTION_PAUSE or
+            PlaybackStateCompat.ACTION_PLAY_PAUSE or
+            PlaybackStateCompat.ACTION_STOP or
+            PlaybackStateCompat.ACTION_SKIP_TO_NEXT or
+            PlaybackStateCompat.ACTION_SKIP_TO_PREVIOUS or
+            PlaybackStateCompat.ACTION_SEEK_TO
+        session?.setPlaybackState(
+            PlaybackStateCompat.Builder()
+                .setActions(actions)
+                .setState(state, args.positionMs, if (args.status == "playing") 1f else 0f)
+                .build()
+        )
+        when (args.status) {
+            "playing" -> requestFocus()
+            "stopped" -> abandonFocus()
+        }
+        status = args.status
+        updateNotification()
+        invoke.resolve()
+    }
+
+    @Command
+    fun getConditions(invoke: Invoke) {
+        invoke.resolve(
+            JSObject()
+                .put("powerSaving", powerManager.isPowerSaveMode)
+                .put("metered", ConnectivityManagerCompat.isActiveNetworkMetered(connectivity))
+        )
+    }
+
+    private fun send(action: String) = send(JSObject().put("action", action))
+
+    private fun send(event: JSObject) {
+        handler?.send(event)
+    }
+
+    private fun requestFocus() {
+        if (focusRequest != null) {
+            return
+        }
+        val request = AudioFocusRequestCompat.Builder(AudioManagerCompat.AUDIOFOCUS_GAIN)
+            .setAudioAttributes(
+                AudioAttributesCompat.Builder()
+                    .setUsage(AudioAttributesCompat.USAGE_MEDIA)
+                    .setContentType(AudioAttributesCompat.CONTENT_TYPE_MUSIC)
+                    .build()
+            )
+            // Ducking is done by the player, so it can restore its own volume
+            .setWillPauseWhenDucked(false)
+            .setOnAudioFocusChangeListener { change ->
+                when (change) {
+                    AudioManager.AUDIOFOCUS_GAIN -> send("focus_gained")
+                    AudioManager.AUDIOFOCUS_LOSS -> {
+                        focusRequest = null
+                        send("focus_lost")
+                    }
+                    AudioManager.AUDIOFOCUS_LOSS_TRANSIENT -> send("focus_lost_transient")
+                    AudioManager.AUDIOFOCUS_LOSS_TRANSIENT_CAN_DUCK -> send("duck")
+                }
+            }
+            .build()
+        if (AudioManagerCompat.requestAudioFocus(audioManager, request) ==
+            AudioManager.AUDIOFOCUS_REQUEST_GRANTED
+        ) {
+            focusRequest = request
+        }
+    }
+
+    private fun abandonFocus() {
+        focusRequest?.let { AudioManagerCompat.abandonAudioFocusRequest(audioManager, it) }
+        focusRequest = null
+    }
+
+    private fun updateNotification() {
+        val notifications = NotificationManagerCompat.from(activity)
+        val session = session ?: return
+        if (status == "stopped") {
+            notifications.cancel(NOTIFICATION_ID)
+            return
+        }
+        if (Build.VERSION.SDK_INT >= Build.VERSION_CODES.TIRAMISU &&
+            ContextCompat.checkSelfPermission(activity, Manifest.permission.POST_NOTIFICATIONS) !=
+            PackageManager.PERMISSION_GRANTED
+        ) {
+            if (!askedForNotifications) {
+                askedForNotifications = true
+                ActivityCompat.requestPermissions(
+                    activity,
+                    arrayOf(Manifest.permission.POST_NOTIFICATIONS),
+                    0
+                )
+            }
+            return
+        }
+
+        val playing = status == "playing"
+        val launch = activity.packageManager.getLaunchIntentForPackage(activity.packageName)
+        val notification = NotificationCompat.Builder(activity, CHANNEL_ID)
+            .setSmallIcon(activity.applicationInfo.icon)
+            .setContentTitle(metadata.title)
+            .setContentText(metadata.artist)
+            .setSubText(metadata.album)
+            .setLargeIcon(metadata.artworkPath?.let { BitmapFactory.decodeFile(it) })
+            .setContentIntent(
+                launch?.let {
+                    PendingIntent.getActivity(activity, 0, it, PendingIntent.FLAG_IMMUTABLE)
+                }
+            )
+            .setVisibility(NotificationCompat.VISIBILITY_PUBLIC)
+            .setOnlyAlertOnce(true)
+            .setOngoing(playing)
+            .addAction(button(android.R.drawable.ic_media_previous, "Previous", "previous", 1))
+            .addAction(
+                if (playing) {
+                    button(android.R.drawable.ic_media_pause, "Pause", "pause", 2)
+                } else {
+                    button(android.R.drawable.ic_media_play, "Play", "play", 2)
+                }
+            )
+            .addAction(button(android.R.drawable.ic_media_next, "Next", "next", 3))
+            .setStyle(
+                androidx.media.app.NotificationCompat.MediaStyle()
+                    .setMediaSession(session.sessionToken)
+                    .setShowActionsInCompactView(0, 1, 2)
+            )
+            .build()
+        notifications.notify(NOTIFICATION_ID, notification)
+    }
+
+    private fun button(icon: Int, title: String, action: String, requestCode: Int) =
+        NotificationCompat.Action(
+            icon,
+            title,
+            PendingIntent.getBroadcast(
+                activity,
+                requestCode,
+                Intent(ACTION_BUTTON).setPackage(activity.packageName).putExtra(EXTRA_ACTION, action),
+                PendingIntent.FLAG_IMMUTABLE or PendingIntent.FLAG_UPDATE_CURRENT
+            )
+        )
+}
@@ -0,0 +1,9 @@
+// Called from Rust only; nothing is exposed to the webview.
+const COMMANDS: &[&str] = &[];
+
+fn main() {
+    tauri_plugin::Builder::new(COMMANDS)
+        .android_path("android")
+        .ios_path("ios")
+        .build();
+}
@@ -0,0 +1,200 @@
+import AVFoundation
+import MediaPlayer
+import Network
+import SwiftRs
+import Tauri
+import UIKit
+
+class EventHandlerArgs: Decodable {
+    let handler: Channel
+}
+
+class MetadataArgs: Decodable {
+    let title: String?
+    let artist: String?
+    let album: String?
+    // A local image file
+    let artworkPath: String?
+    let durationMs: Double
+}
+
+class PlaybackArgs: Decodable {
+    // "playing", "paused" or "stopped"
+    let status: String
+    let positionMs: Double
+}
+
+// The audio session (background playback, interruptions, route changes),
+// Now Playing info and the remote commands. Events go to the handler in
+// the same shape as Android's: interruptions as focus changes, the
+// headphones going away as "becoming_noisy".
+class MediaSessionPlugin: Plugin {
+    private var handler: Channel?
+    private var info: [String: Any] = [:]
+    private var status = "stopped"
+    private let network = NWPathMonitor()
+
+    override init() {
+        super.init()
+        network.start(queue: DispatchQueue(label: "media-session.network"))
+        let session = AVAudioSession.sharedInstance()
+        do {
+            // Keeps playing with the screen locked or the app in the background
+            // (with the "audio" background mode) and ignores the silent switch
+            try session.setCategory(.playback, mode: .default)
+        } catch {
+            Logger.error("Failed to set the audio session category: \(error)")
+        }
+        let center = NotificationCenter.default
+        center.addObserver(
+            self, selector: #selector(interrupted(_:)),
+            name: AVAudioSession.interruptionNotification, object: session)
+        center.addObserver(
+            self, selector: #selector(routeChanged(_:)),
+            name: AVAudioSession.routeChangeNotification, object: session)
+        setUpRemoteCommands()
+    }
+
+    @objc public func setEventHandler(_ invoke: Invoke) throws {
+        handler = try invoke.parseArgs(EventHandlerArgs.self).handler
+        invoke.resolve()
+    }
+
+    @objc public func setMetadata(_ invoke: Invoke) throws {
+        let args = try invoke.parseArgs(MetadataArgs.self)
+        var info: [String: Any] = [
+            MPMediaItemPropertyPlaybackDuration: args.durationMs / 1000
+        ]
+        info[MPMediaItemPropertyTitle] = args.title
+        info[MPMediaItemPropertyArtist] = args.artist
+        info[MPMediaItemPropertyAlbumTitle] = args.album
+        if let path = args.artworkPath, let image = UIImage(contentsOfFile: path) {
+            info[MPMediaItemPropertyArtwork] = MPMediaItemArtwork(boundsSize: image.size) { _ in
+                image
+            }
+        }
+        self.info = info
+        MPNowPlayingInfoCenter.default().nowPlayingInfo = info
+        invoke.resolve()
+    }
+
+    @objc public func setPlayback(_ invoke: Invoke) throws {
+        let args = try invoke.parseArgs(PlaybackArgs.self)
+        let session = AVAudioSession.sharedInstance()
+        if args.status == "playing" && status != "playing" {
+            do {
+                try session.setActive(true)
+            } catch {
+                Logger.error("Failed to activate the audio session: \(error)")
+            }
+        } else if args.status == "stopped" && status != "stopped" {
+            try? session.setActive(false, options: .notifyOthersOnDeactivation)
+        }
+        status = args.status
+
+        let center = MPNowPlayingInfoCenter.default()
+        if status == "stopped" {
+            center.nowPlayingInfo = nil
+        } else {
+            info[MPNowPlayingInfoPropertyElapsedPlaybackTime] = args.positionMs / 1000
+            info[MPNowPlayingInfoPropertyPlaybackRate] = status == "playing" ? 1.0 : 0.0
+            center.nowPlayingInfo = info
+        }
+        if #available(iOS 13.0, *) {
+            center.playbackState =
+                status == "playing" ? .playing : status == "paused" ? .paused : .stopped
+        }
+        invoke.resolve()
+    }
+
+    @objc public func getConditions(_ invoke: Invoke) {
+        let path = network.currentPath
+        invoke.resolve([
+            "powerSaving": ProcessInfo.processInfo.isLowPowerModeEnabled,
+            "metered": path.isExpensive || path.isConstrained,
+        ])
+    }
+
+    private func send(_ action: String) {
+        handler?.send(["action": action])
+    }
+
+    private func setUpRemoteCommands() {
+        let commands = MPRemoteCommandCenter.shared()
+        commands.playCommand.addTarget { [weak self] _ in
+            self?.send("play")
+            return .success
+        }
+        commands.pauseCommand.addTarget { [weak self] _ in
+            self?.send("pause")
+            return .success
+        }
+        commands.togglePlayPauseCommand.addTarget { [weak self] _ in
+            guard let self = self else { return .commandFailed }
+            self.send(self.status == "playing" ? "pause" : "play")
+            return .success
+        }
+        commands.stopCommand.addTarget { [weak self] _ in
+            self?.send("stop")
+            return .success
+        }
+        commands.nextTrackCommand.addTarget { [weak self] _ in
+            self?.send("next")
+            return .success
+        }
+        commands.previousTrackCommand.addTarget { [weak self] _ in
+            self?.send("previous")
+            return .success
+        }
+        commands.changePlaybackPositionCommand.addTarget { [weak self] event in
+            guard let event = event as? MPChangePlaybackPositionCommandEvent else {
+                return .commandFailed
+            }
+            self?.handler?.send([
+                "action": "seek_to",
+                "position_ms": Int(event.positionTime * 1000),
+            ])
+            return .success
+        }
+    }
+
+    // Calls, Siri, alarms: pause, and resume afterwards if iOS says to
+    @objc private func interrupted(_ notification: Notification) {
+        guard let raw = notification.userInfo?[AVAudioSessionInterruptionTypeKey] as? UInt,
+            let type = AVAudioSession.InterruptionType(rawValue: raw)
+        else {
+            return
+        }
+        switch type {
+        case .began:
+            send("focus_lost_transient")
+        case .ended:
+            let options = (notification.userInfo?[AVAudioSessionInterruptionOptionKey] as? UInt)
+                .map { AVAudioSession.InterruptionOptions(rawValue: $0) } ?? []
+            if options.contains(.shouldResume) {
+                try? AVAudioSession.sharedInstance().setActive(true)
+                send("focus_gained")
+            } else {
+                send("focus_lost")
+            }
+        @unknown default:
+            break
+        }
+    }
+
+    // Headphones unplugged or a Bluetooth device gone: pause rather than
+    // carry on through the speaker
+    @objc private func routeChanged(_ notification: Notification) {
+        guard let raw = notification.userInfo?[AVAudioSessionRouteChangeReasonKey] as? UInt,
+            AVAudioSession.RouteChangeReason(rawValue: raw) == .oldDeviceUnavailable
+        else {
+            return
+        }
+        send("becoming_noisy")
+    }
+}
+
+@_cdecl("init_plugin_media_session")
+func initPlugin() -> Plugin {
+    return MediaSessionPlugin()
+}
@@ -0,0 +1,171 @@
+//! Media controls for Masukii on mobile: transport controls on the lock
+//! screen, in the media notification and from Bluetooth and wired headsets,
+//! plus audio focus and "becoming noisy" (headphones unplugged).
+//!
+//! On Android this is a `MediaSession` with a media notification. On iOS
+//! it's the `AVAudioSession` in the playback category (so audio carries on
+//! in the background), Now Playing info and the remote commands;
+//! interruptions (calls, Siri) come back as [`SessionEvent::FocusLostTransient`]
+//! and [`SessionEvent::FocusGained`] or [`SessionEvent::FocusLost`], and
+//! a route change that loses the output as [`SessionEvent::BecomingNoisy`].
+//!
+//! [`MediaSession::conditions`] tells whether battery saver (Low Power
+//! Mode) is on and the network is metered, for sizing buffers.
+//!
+//! The app pushes what's playing with [`MediaSession::set_metadata`] and
+//! [`MediaSession::set_playback`]. Focus is requested while playing and
+//! given up on stop. Button presses and focus changes come back as
+//! [`SessionEvent`]s; the plugin doesn't touch playback itself, so ducking
+//! and pausing are up to the handler. Everything here is for Rust; nothing
+//! is exposed to the webview.
+
+#![cfg(any(target_os = "android", target_os = "ios"))]
+
+use serde::{Deserialize, Serialize};
+use tauri::ipc::{Channel, InvokeResponseBody};
+use tauri::plugin::{Builder, PluginHandle, TauriPlugin};
+use tauri::{Manager, Runtime};
+
+#[cfg(target_os = "android")]
+const PLUGIN_PACKAGE: &str = "com.masukii.mediasession";
+
+#[cfg(target_os = "ios")]
+tauri::ios_plugin_binding!(init_plugin_media_session);
+
+#[derive(Serialize, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct NowPlaying {
+    pub title: Option<String>,
+    pub artist: Option<String>,
+    pub album: Option<String>,
+    /// A local image file for the lock screen and notification.
+    pub artwork_path: Option<String>,
+    pub duration_ms: u64,
+}
+
+#[derive(Serialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Status {
+    Playing,
+    Paused,
+    Stopped,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Playback {
+    status: Status,
+    position_ms: u64,
+}
+
+#[derive(Deserialize, Clone, Copy, Debug)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum SessionEvent {
+    Play,
+    Pause,
+    Stop,
+    Next,
+    Previous,
+    SeekTo {
+        position_ms: u64,
+    },
+    /// Another app took audio focus for good, or an interruption ended
+    /// without the OS saying to resume.
+    FocusLost,
+    /// A call or an assistant took focus for a while.
+    FocusLostTransient,
+    /// Something short (a navigation prompt) plays over us.
+    Duck,
+    /// Focus is back after a transient loss or ducking.
+    FocusGained,
+    /// The output is about to move to the speaker.
+    BecomingNoisy,
+}
+
+/// What the device is running on, as far as playback cares.
+#[derive(Deserialize, Clone, Copy, Default, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Conditions {
+    /// Battery saver on Android, Low Power Mode on iOS.
+    pub power_saving: bool,
+    /// The active network is metered (cellular, a hotspot) or, on iOS, in
+    /// Low Data Mode.
+    pub metered: bool,
+}
+
+#[derive(Serialize)]
+struct EventHandler {
+    handler: Channel<serde_json::Value>,
+}
+
+pub struct MediaSession<R: Runtime>(PluginHandle<R>);
+
+impl<R: Runtime> MediaSession<R> {
+    /// Receive button presses and focus changes. Replaces any earlier
+    /// handler.
+    pub fn set_event_handler(
+        &self,
+        on_event: impl Fn(SessionEvent) + Send + Sync + 'static,
+    ) -> Result<(), String> {
+        let handler = Channel::new(move |body: InvokeResponseBody| {
+            match body.deserialize::<SessionEvent>() {
+                Ok(event) => on_event(event),
+                Err(e) => log::warn!("Unknown media session event: {}", e),
+            }
+            Ok(())
+        });
+        self.run("setEventHandler", EventHandler { handler })
+    }
+
+    pub fn set_metadata(&self, now_playing: &NowPlaying) -> Result<(), String> {
+        self.run("setMetadata", now_playing)
+    }
+
+    /// Playing status and position; the notification and lock screen run
+    /// the clock from here while playing.
+    pub fn set_playback(&self, status: Status, position_ms: u64) -> Result<(), String> {
+        self.run(
+            "setPlayback",
+            Playback {
+                status,
+                position_ms,
+            },
+        )
+    }
+
+    pub fn conditions(&self) -> Result<Conditions, String> {
+        self.0
+            .run_mobile_plugin("getConditions", ())
+            .map_err(|e| format!("Media session getConditions failed: {}", e))
+    }
+
+    fn run(&self, command: &str, payload: impl Serialize) -> Result<(), String> {
+        self.0
+            .run_mobile_plugin::<()>(command, payload)
+            .map_err(|e| format!("Media session {} failed: {}", command, e))
+    }
+}
+
+/// Access to the [`MediaSession`] from the app.
+pub trait MediaSessionExt<R: Runtime> {
+    fn media_session(&self) -> &MediaSession<R>;
+}
+
+impl<R: Runtime, T: Manager<R>> MediaSessionExt<R> for T {
+    fn media_session(&self) -> &MediaSession<R> {
+        self.state::<MediaSession<R>>().inner()
+    }
+}
+
+pub fn init<R: Runtime>() -> TauriPlugin<R> {
+    Builder::new("media-session")
+        .setup(|app, api| {
+            #[cfg(target_os = "android")]
+            let handle = api.register_android_plugin(PLUGIN_PACKAGE, "MediaSessionPlugin")?;
+            #[cfg(target_os = "ios")]
+            let handle = api.register_ios_plugin(init_plugin_media_session)?;
+            app.manage(MediaSession(handle));
+            Ok(())
+        })
+        .build()
+}
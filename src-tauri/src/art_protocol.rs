@@ -0,0 +1,152 @@
+//! `art://`: album art served straight to the webview, so grids of albums
+//! don't push megabytes of base64 through IPC.
+//!
+//! - `art://localhost/album/<id>`: the album's thumbnail from the cache,
+//!   generated on first request
+//! - `art://localhost/track?path=<file>`: a file's cover at full size, its
+//!   embedded front cover or a folder image next to it
+//!
+//! Windows and Android webviews only allow custom schemes as
+//! `http://art.localhost/...`; the UI builds URLs with `convertFileSrc`,
+//! which picks the right form. Responses carry an `ETag` from the cover
+//! track's path and mtime, so a cached image is only sent again when the
+//! art changes. Albums and files without art are a 404.
+
+use std::path::PathBuf;
+use std::time::UNIX_EPOCH;
+use tauri::http::{header, Request, Response, StatusCode};
+use tauri::{AppHandle, Manager, UriSchemeResponder, Url};
+
+use crate::commands::AppState;
+use crate::library::artwork::{self, CoverSource};
+use crate::metadata::cue;
+
+pub const SCHEME: &str = "art";
+
+/// Answer a request on a worker thread; decoding and scaling art can take
+/// a while.
+pub fn handle(app: &AppHandle, request: Request<Vec<u8>>, responder: UriSchemeResponder) {
+    let app = app.clone();
+    tauri::async_runtime::spawn_blocking(move || {
+        let response = respond(&app, &request).unwrap_or_else(|(status, message)| {
+            if status == StatusCode::INTERNAL_SERVER_ERROR {
+                log::warn!("{}: {}", request.uri(), message);
+            }
+            Response::builder()
+                .status(status)
+                .header(header::CONTENT_TYPE, "text/plain")
+                .body(message.into_bytes())
+                .unwrap_or_default()
+        });
+        responder.respond(response);
+    });
+}
+
+type Failure = (StatusCode, String);
+
+/// The image a URL asks for.
+enum Art {
+    /// An album's thumbnail.
+    Album { dir: PathBuf, source: CoverSource },
+    /// A file's cover, and its mtime.
+    Track { path: String, modified_at: i64 },
+}
+
+impl Art {
+    fn from_url(app: &AppHandle, url: &Url) -> Result<Self, Failure> {
+        let segments: Vec<&str> = url.path_segments().map(|s| s.collect()).unwrap_or_default();
+        match segments.as_slice() {
+            ["album", id] => {
+                let id: i64 = id
+                    .parse()
+                    .map_err(|_| (StatusCode::BAD_REQUEST, format!("Invalid album id {}", id)))?;
+                let state = app.state::<AppState>();
+                let library = state.library.lock();
+                let source = library.cover_source(id).map_err(internal)?;
+                Ok(Art::Album {
+                    dir: library.thumbnail_dir().to_path_buf(),
+                    source: source.ok_or_else(not_found)?,
+                })
+            }
+            ["track"] => {
+                let path = url
+                    .query_pairs()
+                    .find(|(key, _)| key == "path")
+                    .map(|(_, value)| value.into_owned())
+                    .ok_or((StatusCode::BAD_REQUEST, "Missing path".to_string()))?;
+                let file = cue::audio_file(&path).map_err(|e| (StatusCode::NOT_FOUND, e))?;
+                let modified_at = std::fs::metadata(file)
+                    .and_then(|m| m.modified())
+                    .map_err(|_| not_found())?
+                    .duration_since(UNIX_EPOCH)
+                    .map(|d| d.as_secs() as i64)
+                    .unwrap_or(0);
+                Ok(Art::Track { path, modified_at })
+            }
+            _ => Err(not_found()),
+        }
+    }
+
+    /// Changes whenever the image may have.
+    fn etag(&self) -> String {
+        let key = match self {
+            Art::Album { source, .. } => artwork::cache_key(&source.path, source.modified_at),
+            Art::Track { path, modified_at } => artwork::cache_key(path, *modified_at),
+        };
+        format!("\"{}\"", key)
+    }
+
+    fn load(&self) -> Result<Option<Vec<u8>>, String> {
+        match self {
+            Art::Album { dir, source } => {
+                match artwork::ensure_thumbnail(dir, &source.path, source.modified_at)? {
+                    Some(path) => std::fs::read(path)
+                        .map(Some)
+                        .map_err(|e| format!("Failed to read thumbnail: {}", e)),
+                    None => Ok(None),
+                }
+            }
+            Art::Track { path, .. } => Ok(artwork::find_cover_image(path)),
+        }
+    }
+}
+
+fn respond(app: &AppHandle, request: &Request<Vec<u8>>) -> Result<Response<Vec<u8>>, Failure> {
+    let url = Url::parse(&request.uri().to_string())
+        .map_err(|e| (StatusCode::BAD_REQUEST, format!("Invalid URL: {}", e)))?;
+    let art = Art::from_url(app, &url)?;
+
+    let etag = art.etag();
+    let cached = request
+        .headers()
+        .get(header::IF_NONE_MATCH)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.split(',').any(|tag| tag.trim() == etag));
+    let response = Response::builder()
+        .header(header::ETAG, &etag)
+        .header(header::CACHE_CONTROL, "no-cache");
+    if cached {
+        return response
+            .status(StatusCode::NOT_MODIFIED)
+            .body(Vec::new())
+            .map_err(|e| internal(e.to_string()));
+    }
+
+    let bytes = art.load().map_err(internal)?.ok_or_else(not_found)?;
+    let mime = match image::guess_format(&bytes) {
+        Ok(image::ImageFormat::Png) => "image/png",
+        _ => "image/jpeg",
+    };
+    response
+        .header(header::CONTENT_TYPE, mime)
+        .body(bytes)
+        .map_err(|e| internal(e.to_string()))
+}
+
+fn not_found() -> Failure {
+    (StatusCode::NOT_FOUND, "No artwork".to_string())
+}
+
+fn internal(message: String) -> Failure {
+    (StatusCode::INTERNAL_SERVER_ERROR, message)
+}
@@ -13,6 +13,7 @@ use std::collections::HashMap;
 use std::path::PathBuf;
 
 use super::engine::ReplayGainMode;
+use crate::errors::{self, ErrorCode};
 
 #[derive(Clone, Serialize, Deserialize)]
 pub struct DeviceProfile {
@@ -49,11 +50,18 @@ pub struct DeviceProfileStore {
 }
 
 impl DeviceProfileStore {
-    /// Load profiles from disk. Returns empty store if file doesn't exist.
+    /// Load profiles from disk. Returns empty store if file doesn't exist
+    /// or is unreadable.
     pub fn load(app_data_dir: &PathBuf) -> Self {
         let path = app_data_dir.join("device_profiles.json");
         if let Ok(data) = std::fs::read_to_string(&path) {
-            serde_json::from_str(&data).unwrap_or_default()
+            serde_json::from_str(&data).unwrap_or_else(|e| {
+                errors::report(
+                    ErrorCode::ConfigLoad,
+                    format!("Ignoring invalid {}: {}", path.display(), e),
+                );
+                Self::default()
+            })
         } else {
             Self::default()
         }
@@ -0,0 +1,93 @@
+//! Diagnostic report for bug reports: one JSON file with the app version and
+//! OS, the audio devices and the formats the default output supports, the
+//! output settings and device profiles, playback state, diagnostics with
+//! their history, the dropout log and recent log records.
+
+use cpal::traits::{DeviceTrait, HostTrait};
+use serde::Serialize;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use super::device_profiles::DeviceProfileStore;
+use super::diagnostics_history::DiagnosticsSample;
+use super::dropouts::DropoutLog;
+use super::engine::{
+    self, AudioDeviceInfo, AudioDiagnostics, AudioEngine, DspSettings, PlaybackState,
+};
+use crate::logging::{self, LogEntry};
+
+#[derive(Serialize)]
+pub struct DiagnosticReport {
+    /// Unix time in milliseconds.
+    pub generated_at_ms: u64,
+    pub app_version: &'static str,
+    pub os: &'static str,
+    pub arch: &'static str,
+    pub audio_host: String,
+    pub output_devices: Vec<AudioDeviceInfo>,
+    /// Configurations the default output device accepts, e.g.
+    /// `2 ch, 44100–192000 Hz, f32`.
+    pub default_output_formats: Vec<String>,
+    pub input_devices: Vec<AudioDeviceInfo>,
+    pub dsp: DspSettings,
+    pub device_profiles: DeviceProfileStore,
+    pub playback: PlaybackState,
+    pub diagnostics: AudioDiagnostics,
+    pub diagnostics_history: Vec<DiagnosticsSample>,
+    pub dropouts: DropoutLog,
+    /// Records at the log level or above, oldest first.
+    pub logs: Vec<LogEntry>,
+}
+
+/// Gather the report. Device enumeration can take a moment on some drivers,
+/// so call it off the UI thread.
+pub fn build(engine: &AudioEngine, device_profiles: DeviceProfileStore) -> DiagnosticReport {
+    let host = cpal::default_host();
+    DiagnosticReport {
+        generated_at_ms: SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis() as u64)
+            .unwrap_or(0),
+        app_version: env!("CARGO_PKG_VERSION"),
+        os: std::env::consts::OS,
+        arch: std::env::consts::ARCH,
+        audio_host: format!("{:?}", host.id()),
+        output_devices: engine::get_output_devices(),
+        default_output_formats: default_output_formats(&host),
+        input_devices: engine::get_input_devices(),
+        dsp: engine.dsp_settings(),
+        device_profiles,
+        playback: engine.get_state(),
+        diagnostics: engine.get_diagnostics(),
+        diagnostics_history: engine.get_diagnostics_history(),
+        dropouts: engine.get_dropout_log(),
+        logs: logging::recent(),
+    }
+}
+
+/// Write the report to `path` as pretty-printed JSON.
+pub fn export(report: &DiagnosticReport, path: &Path) -> Result<(), String> {
+    let json =
+        serde_json::to_string_pretty(report).map_err(|e| format!("Serialize failed: {}", e))?;
+    std::fs::write(path, json).map_err(|e| format!("Write failed: {}", e))
+}
+
+fn default_output_formats(host: &cpal::Host) -> Vec<String> {
+    let Some(configs) = host
+        .default_output_device()
+        .and_then(|d| d.supported_output_configs().ok())
+    else {
+        return Vec::new();
+    };
+    configs
+        .map(|c| {
+            format!(
+                "{} ch, {}–{} Hz, {}",
+                c.channels(),
+                c.min_sample_rate().0,
+                c.max_sample_rate().0,
+                c.sample_format()
+            )
+        })
+        .collect()
+}
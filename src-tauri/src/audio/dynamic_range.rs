@@ -0,0 +1,90 @@
+//! Dynamic range (DR) measurement, TT-DR / DR14 style.
+//!
+//! The track is split into 3-second blocks. For each channel, DR is the
+//! ratio in dB between the second-highest block peak and the RMS of the
+//! loudest 20% of blocks (block RMS scaled by √2, so a full-scale sine
+//! reads 0 dB). The track value is the average over channels. Players show
+//! it rounded ("DR12"); the unrounded value is kept so album averages match
+//! the offline meters.
+
+use super::decoder::{AudioDecoder, DecodeStatus};
+
+/// Block length in seconds.
+const BLOCK_SECS: u32 = 3;
+
+/// Share of the loudest blocks used for the RMS.
+const LOUDEST_FRACTION: f64 = 0.2;
+
+/// Running peak and sum of squares of one channel's current block.
+#[derive(Clone, Copy, Default)]
+struct Block {
+    peak: f64,
+    sum_squares: f64,
+}
+
+/// Decode a file and measure its dynamic range in dB.
+pub fn analyze_file(path: &str) -> Result<f64, String> {
+    let mut decoder = AudioDecoder::open(path)?;
+    let channels = decoder.channels().max(1);
+    let block_frames = (decoder.sample_rate() * BLOCK_SECS) as usize;
+
+    let mut blocks: Vec<Vec<(f64, f64)>> = vec![Vec::new(); channels];
+    let mut current = vec![Block::default(); channels];
+    let mut frames = 0;
+    loop {
+        let samples = match decoder.next_samples() {
+            Ok(s) => s,
+            Err(DecodeStatus::EndOfStream) => break,
+            Err(DecodeStatus::Error(e)) => return Err(format!("Decode failed: {}", e)),
+        };
+        for frame in samples.chunks_exact(channels) {
+            for (block, &s) in current.iter_mut().zip(frame) {
+                let s = f64::from(s);
+                block.peak = block.peak.max(s.abs());
+                block.sum_squares += s * s;
+            }
+            frames += 1;
+            if frames == block_frames {
+                push_blocks(&mut blocks, &mut current, frames);
+                frames = 0;
+            }
+        }
+    }
+    if frames > 0 {
+        push_blocks(&mut blocks, &mut current, frames);
+    }
+
+    let mut total = 0.0;
+    for channel in &mut blocks {
+        total += channel_dr(channel)?;
+    }
+    Ok(total / channels as f64)
+}
+
+/// Close the current block of every channel as (peak, RMS).
+fn push_blocks(blocks: &mut [Vec<(f64, f64)>], current: &mut [Block], frames: usize) {
+    for (channel, block) in blocks.iter_mut().zip(current.iter_mut()) {
+        let rms = (2.0 * block.sum_squares / frames as f64).sqrt();
+        channel.push((block.peak, rms));
+        *block = Block::default();
+    }
+}
+
+fn channel_dr(blocks: &mut [(f64, f64)]) -> Result<f64, String> {
+    if blocks.is_empty() {
+        return Err("No audio to measure".to_string());
+    }
+
+    // Second-highest peak, so a single click doesn't decide the value
+    blocks.sort_by(|a, b| b.0.total_cmp(&a.0));
+    let peak = blocks[1.min(blocks.len() - 1)].0;
+
+    blocks.sort_by(|a, b| b.1.total_cmp(&a.1));
+    let loudest = ((blocks.len() as f64 * LOUDEST_FRACTION) as usize).max(1);
+    let rms = (blocks[..loudest].iter().map(|b| b.1 * b.1).sum::<f64>() / loudest as f64).sqrt();
+
+    if rms <= 0.0 || peak <= 0.0 {
+        return Err("Track is silent".to_string());
+    }
+    Ok(20.0 * (peak / rms).log10())
+}
@@ -0,0 +1,64 @@
+//! Chromaprint audio fingerprints, as used by AcoustID.
+//!
+//! Like `fpcalc`, only the first two minutes are fingerprinted, with the
+//! default algorithm. The result is compressed and base64-encoded
+//! (URL-safe, no padding), which is the form the AcoustID API takes.
+
+use base64::Engine;
+use rusty_chromaprint::{Configuration, FingerprintCompressor, Fingerprinter};
+
+use super::decoder::{AudioDecoder, DecodeStatus};
+use crate::metadata::reader;
+
+/// Length of audio fingerprinted, from the start of the track.
+const FINGERPRINT_SECS: u32 = 120;
+
+pub struct Fingerprint {
+    /// Compressed, base64-encoded fingerprint.
+    pub fingerprint: String,
+    /// Length of the whole track, which AcoustID matches on too.
+    pub duration_secs: f64,
+}
+
+pub fn fingerprint_file(path: &str) -> Result<Fingerprint, String> {
+    let mut decoder = AudioDecoder::open(path)?;
+    let channels = decoder.channels().max(1);
+    let config = Configuration::default();
+    let mut printer = Fingerprinter::new(&config);
+    printer
+        .start(decoder.sample_rate(), channels as u32)
+        .map_err(|e| format!("Failed to start fingerprint: {}", e))?;
+
+    let wanted = (decoder.sample_rate() * FINGERPRINT_SECS) as usize * channels;
+    let mut consumed = 0;
+    while consumed < wanted {
+        let samples = match decoder.next_samples() {
+            Ok(s) => s,
+            Err(DecodeStatus::EndOfStream) => break,
+            Err(DecodeStatus::Error(e)) => return Err(format!("Decode failed: {}", e)),
+        };
+        let take = samples.len().min(wanted - consumed);
+        let pcm: Vec<i16> = samples[..take]
+            .iter()
+            .map(|&s| (s.clamp(-1.0, 1.0) * f32::from(i16::MAX)) as i16)
+            .collect();
+        printer.consume(&pcm);
+        consumed += take;
+    }
+    printer.finish();
+    if printer.fingerprint().is_empty() {
+        return Err("Track is too short to fingerprint".to_string());
+    }
+
+    let compressed = FingerprintCompressor::from(&config).compress(printer.fingerprint());
+    // Not every format states its length up front
+    let duration_secs = if decoder.duration_secs > 0.0 {
+        decoder.duration_secs
+    } else {
+        reader::read_metadata(path)?.duration_secs
+    };
+    Ok(Fingerprint {
+        fingerprint: base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(compressed),
+        duration_secs,
+    })
+}
@@ -0,0 +1,356 @@
+//! FLAC encoder for the recorder and the CD ripper.
+//!
+//! A small encoder: fixed blocks of [`BLOCK_SIZE`] frames, each channel
+//! coded on its own with the best of the fixed predictors (orders 0–4) and
+//! a single Rice partition, or verbatim when prediction doesn't help. Files
+//! come out somewhat larger than the reference encoder's, which is fine for
+//! recordings and rips that can be re-encoded later. The STREAMINFO block's
+//! sample count is filled in by [`FlacWriter::finish`]; its MD5 is left
+//! unset, which the format allows.
+
+use std::fs::File;
+use std::io::{BufWriter, Seek, SeekFrom, Write};
+use std::path::Path;
+
+/// Frames per FLAC frame (block size code 12).
+const BLOCK_SIZE: usize = 4096;
+
+/// Highest Rice parameter of the 4-bit partition coding method.
+const MAX_RICE_PARAMETER: u32 = 14;
+
+/// Offset of the total sample count's top byte in the file.
+const TOTAL_SAMPLES_OFFSET: u64 = 4 + 4 + 13;
+
+pub struct FlacWriter {
+    out: BufWriter<File>,
+    channels: usize,
+    bits: u8,
+    /// Interleaved frames waiting to fill a block.
+    pending: Vec<i32>,
+    frame_number: u64,
+    total_frames: u64,
+    bytes_written: u64,
+}
+
+impl FlacWriter {
+    pub fn create(path: &Path, sample_rate: u32, channels: u16, bits: u8) -> Result<Self, String> {
+        if !(1..=8).contains(&channels) {
+            return Err(format!("FLAC can't hold {} channels", channels));
+        }
+        let file = File::create(path).map_err(|e| format!("Failed to create file: {}", e))?;
+        let mut out = BufWriter::new(file);
+
+        let mut header = BitWriter::default();
+        header.bytes(b"fLaC");
+        // Last metadata block, STREAMINFO, 34 bytes
+        header.bits(1, 1);
+        header.bits(0, 7);
+        header.bits(34, 24);
+        header.bits(BLOCK_SIZE as u64, 16);
+        header.bits(BLOCK_SIZE as u64, 16);
+        // Frame sizes unknown
+        header.bits(0, 24);
+        header.bits(0, 24);
+        header.bits(u64::from(sample_rate), 20);
+        header.bits(u64::from(channels) - 1, 3);
+        header.bits(u64::from(bits) - 1, 5);
+        // Total samples, filled in at the end, then the MD5
+        header.bits(0, 36);
+        header.bytes(&[0; 16]);
+        let header = header.into_bytes();
+        out.write_all(&header)
+            .map_err(|e| format!("Write failed: {}", e))?;
+
+        Ok(Self {
+            out,
+            channels: usize::from(channels),
+            bits,
+            pending: Vec::with_capacity(BLOCK_SIZE * usize::from(channels)),
+            frame_number: 0,
+            total_frames: 0,
+            bytes_written: header.len() as u64,
+        })
+    }
+
+    /// Bytes written to the file so far.
+    pub fn bytes_written(&self) -> u64 {
+        self.bytes_written
+    }
+
+    /// Append interleaved samples.
+    pub fn write(&mut self, mut samples: &[i32]) -> Result<(), String> {
+        let block = BLOCK_SIZE * self.channels;
+        while !samples.is_empty() {
+            let take = (block - self.pending.len()).min(samples.len());
+            self.pending.extend_from_slice(&samples[..take]);
+            samples = &samples[take..];
+            if self.pending.len() == block {
+                self.write_frame()?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Encode what's left, fill in the sample count and close the file.
+    pub fn finish(mut self) -> Result<(), String> {
+        self.pending
+            .truncate(self.pending.len() - self.pending.len() % self.channels);
+        if !self.pending.is_empty() {
+            self.write_frame()?;
+        }
+        // 4 bits of sample size share the byte with the count's top 4
+        let bits = self.bits;
+        let total = self.total_frames;
+        let write_total = |out: &mut BufWriter<File>| -> std::io::Result<()> {
+            let mut bytes = [0u8; 5];
+            bytes[0] = (((bits - 1) & 0x0f) << 4) | ((total >> 32) as u8 & 0x0f);
+            bytes[1..].copy_from_slice(&(total as u32).to_be_bytes());
+            out.seek(SeekFrom::Start(TOTAL_SAMPLES_OFFSET))?;
+            out.write_all(&bytes)?;
+            out.flush()
+        };
+        write_total(&mut self.out).map_err(|e| format!("Write failed: {}", e))
+    }
+
+    fn write_frame(&mut self) -> Result<(), String> {
+        let frames = self.pending.len() / self.channels;
+        let mut frame = BitWriter::default();
+        frame.bits(0xfff8, 16);
+        // Block size: 4096, or given after the frame number
+        let size_code = if frames == BLOCK_SIZE { 12 } else { 7 };
+        frame.bits(size_code, 4);
+        // Sample rate from STREAMINFO
+        frame.bits(0, 4);
+        frame.bits(self.channels as u64 - 1, 4);
+        frame.bits(sample_size_code(self.bits), 3);
+        frame.bits(0, 1);
+        frame.utf8_number(self.frame_number);
+        if size_code == 7 {
+            frame.bits(frames as u64 - 1, 16);
+        }
+        let crc = crc8(frame.as_bytes());
+        frame.bits(u64::from(crc), 8);
+
+        let mut channel = Vec::with_capacity(frames);
+        for c in 0..self.channels {
+            channel.clear();
+            channel.extend(self.pending.iter().skip(c).step_by(self.channels));
+            write_subframe(&mut frame, &channel, self.bits);
+        }
+        frame.align();
+        let crc = crc16(frame.as_bytes());
+        frame.bits(u64::from(crc), 16);
+
+        let bytes = frame.into_bytes();
+        self.out
+            .write_all(&bytes)
+            .map_err(|e| format!("Write failed: {}", e))?;
+        self.bytes_written += bytes.len() as u64;
+        self.frame_number += 1;
+        self.total_frames += frames as u64;
+        self.pending.clear();
+        Ok(())
+    }
+}
+
+fn sample_size_code(bits: u8) -> u64 {
+    match bits {
+        8 => 1,
+        12 => 2,
+        16 => 4,
+        20 => 5,
+        24 => 6,
+        32 => 7,
+        // From STREAMINFO
+        _ => 0,
+    }
+}
+
+/// One channel of a frame: the fixed predictor that codes it smallest, or
+/// the samples verbatim.
+fn write_subframe(out: &mut BitWriter, samples: &[i32], bits: u8) {
+    let bits = u32::from(bits);
+    let verbatim_bits = samples.len() as u64 * u64::from(bits);
+    let mut best: Option<(usize, u32, u64, Vec<u64>)> = None;
+    for order in 0..=4.min(samples.len()) {
+        let residuals = fixed_residuals(samples, order);
+        let (parameter, size) = rice_parameter(&residuals);
+        let size = size + order as u64 * u64::from(bits) + 6;
+        if best.as_ref().is_none_or(|b| size < b.2) {
+            best = Some((order, parameter, size, residuals));
+        }
+    }
+
+    // Zero padding bit, type, no wasted bits
+    match best.filter(|b| b.2 < verbatim_bits) {
+        Some((order, parameter, _, residuals)) => {
+            out.bits(0, 1);
+            out.bits(0b001000 | order as u64, 6);
+            out.bits(0, 1);
+            for &s in &samples[..order] {
+                out.signed(s, bits);
+            }
+            // Rice, partition order 0
+            out.bits(0, 2);
+            out.bits(0, 4);
+            out.bits(u64::from(parameter), 4);
+            for u in residuals {
+                out.rice(u, parameter);
+            }
+        }
+        None => {
+            out.bits(0, 1);
+            out.bits(0b000001, 6);
+            out.bits(0, 1);
+            for &s in samples {
+                out.signed(s, bits);
+            }
+        }
+    }
+}
+
+/// Residuals of the fixed predictor of `order`, zigzag-folded to unsigned.
+fn fixed_residuals(samples: &[i32], order: usize) -> Vec<u64> {
+    let s = |i: usize| i64::from(samples[i]);
+    (order..samples.len())
+        .map(|i| {
+            let prediction = match order {
+                0 => 0,
+                1 => s(i - 1),
+                2 => 2 * s(i - 1) - s(i - 2),
+                3 => 3 * s(i - 1) - 3 * s(i - 2) + s(i - 3),
+                _ => 4 * s(i - 1) - 6 * s(i - 2) + 4 * s(i - 3) - s(i - 4),
+            };
+            let r = s(i) - prediction;
+            ((r << 1) ^ (r >> 63)) as u64
+        })
+        .collect()
+}
+
+/// The Rice parameter that codes `residuals` smallest, and their size in
+/// bits with it.
+fn rice_parameter(residuals: &[u64]) -> (u32, u64) {
+    (0..=MAX_RICE_PARAMETER)
+        .map(|k| {
+            let size = residuals.iter().map(|u| (u >> k) + 1 + u64::from(k)).sum();
+            (k, size)
+        })
+        .min_by_key(|&(_, size)| size)
+        .unwrap_or((0, 0))
+}
+
+#[derive(Default)]
+struct BitWriter {
+    bytes: Vec<u8>,
+    /// Bits not yet making a whole byte, in the low `pending` bits.
+    accumulator: u64,
+    pending: u32,
+}
+
+impl BitWriter {
+    /// The low `count` bits of `value`, most significant first.
+    fn bits(&mut self, value: u64, mut count: u32) {
+        while count > 0 {
+            let take = count.min(32);
+            count -= take;
+            let chunk = (value >> count) & ((1 << take) - 1);
+            self.accumulator = (self.accumulator << take) | chunk;
+            self.pending += take;
+            while self.pending >= 8 {
+                self.pending -= 8;
+                self.bytes.push((self.accumulator >> self.pending) as u8);
+            }
+            self.accumulator &= (1 << self.pending) - 1;
+        }
+    }
+
+    fn bytes(&mut self, bytes: &[u8]) {
+        for &b in bytes {
+            self.bits(u64::from(b), 8);
+        }
+    }
+
+    fn signed(&mut self, value: i32, count: u32) {
+        self.bits(value as u64 & ((1 << count) - 1), count);
+    }
+
+    /// `value >> parameter` in unary, then the low bits.
+    fn rice(&mut self, value: u64, parameter: u32) {
+        let mut zeros = value >> parameter;
+        while zeros > 0 {
+            let run = zeros.min(32);
+            self.bits(0, run as u32);
+            zeros -= run;
+        }
+        self.bits(1, 1);
+        self.bits(value, parameter);
+    }
+
+    /// A frame number as FLAC codes it, like UTF-8.
+    fn utf8_number(&mut self, n: u64) {
+        if n < 0x80 {
+            self.bits(n, 8);
+            return;
+        }
+        let continuation = match n {
+            0..=0x7ff => 1,
+            0x800..=0xffff => 2,
+            0x1_0000..=0x1f_ffff => 3,
+            0x20_0000..=0x3ff_ffff => 4,
+            _ => 5,
+        };
+        // As many leading ones as bytes in all
+        let marker = (0xff << (7 - continuation)) & 0xff;
+        self.bits(marker | (n >> (6 * continuation)), 8);
+        for i in (0..continuation).rev() {
+            self.bits(0x80 | ((n >> (6 * i)) & 0x3f), 8);
+        }
+    }
+
+    /// Pad to a whole byte with zeros.
+    fn align(&mut self) {
+        if self.pending > 0 {
+            self.bits(0, 8 - self.pending);
+        }
+    }
+
+    /// Whole bytes written so far.
+    fn as_bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    fn into_bytes(mut self) -> Vec<u8> {
+        self.align();
+        self.bytes
+    }
+}
+
+fn crc8(bytes: &[u8]) -> u8 {
+    let mut crc = 0u8;
+    for &b in bytes {
+        crc ^= b;
+        for _ in 0..8 {
+            crc = if crc & 0x80 != 0 {
+                (crc << 1) ^ 0x07
+            } else {
+                crc << 1
+            };
+        }
+    }
+    crc
+}
+
+fn crc16(bytes: &[u8]) -> u16 {
+    let mut crc = 0u16;
+    for &b in bytes {
+        crc ^= u16::from(b) << 8;
+        for _ in 0..8 {
+            crc = if crc & 0x8000 != 0 {
+                (crc << 1) ^ 0x8005
+            } else {
+                crc << 1
+            };
+        }
+    }
+    crc
+}
@@ -0,0 +1,128 @@
+//! File integrity check: decode a whole file strictly and report damage.
+//!
+//! Unlike playback, which skips a corrupt packet and carries on, every
+//! decode error counts here. Formats with an embedded checksum (FLAC's
+//! STREAMINFO MD5) are verified against the decoded audio, and a stream
+//! that ends well short of its declared length is reported as truncated.
+
+use std::fs::File;
+use std::path::Path;
+use symphonia::core::codecs::{DecoderOptions, CODEC_TYPE_NULL};
+use symphonia::core::errors::Error as SymphoniaError;
+use symphonia::core::formats::FormatOptions;
+use symphonia::core::io::MediaSourceStream;
+use symphonia::core::meta::MetadataOptions;
+use symphonia::core::probe::Hint;
+
+use crate::paths::{self, OPEN_TIMEOUT};
+
+/// Outcome of checking a file that could be opened.
+pub struct IntegrityReport {
+    /// What's wrong with the file, `None` if it decoded cleanly.
+    pub error: Option<String>,
+    /// The file's embedded checksum was verified.
+    pub checksum_verified: bool,
+}
+
+/// Decode a file from start to end. An `Err` means it couldn't be opened
+/// or probed at all, which for a library file is damage too.
+pub fn check_file(path: &str) -> Result<IntegrityReport, String> {
+    // A file on a share that stopped answering must not hang the caller
+    let file = paths::with_timeout(OPEN_TIMEOUT, {
+        let path = path.to_string();
+        move || File::open(path)
+    })
+    .map_err(|e| format!("File not reachable: {}", e))?
+    .map_err(|e| format!("Failed to open file: {}", e))?;
+    let mss = MediaSourceStream::new(Box::new(file), Default::default());
+    let mut hint = Hint::new();
+    if let Some(ext) = Path::new(path).extension().and_then(|e| e.to_str()) {
+        hint.with_extension(ext);
+    }
+    let fmt_opts = FormatOptions {
+        enable_gapless: true,
+        ..Default::default()
+    };
+    let mut format = symphonia::default::get_probe()
+        .format(&hint, mss, &fmt_opts, &MetadataOptions::default())
+        .map_err(|e| format!("Failed to probe format: {}", e))?
+        .format;
+    let track = format
+        .tracks()
+        .iter()
+        .find(|t| t.codec_params.codec != CODEC_TYPE_NULL)
+        .ok_or("No audio tracks found")?;
+    let track_id = track.id;
+    let params = track.codec_params.clone();
+    let mut decoder = symphonia::default::get_codecs()
+        .make(&params, &DecoderOptions { verify: true })
+        .map_err(|e| format!("Failed to create decoder: {}", e))?;
+
+    let mut frames: u64 = 0;
+    let mut bad_packets = 0;
+    let mut first_error: Option<String> = None;
+    let mut note = |error: String, ts: u64| {
+        bad_packets += 1;
+        if first_error.is_none() {
+            let at = params
+                .time_base
+                .map(|tb| tb.calc_time(ts))
+                .map(|t| format!(" at {}:{:02}", t.seconds / 60, t.seconds % 60))
+                .unwrap_or_default();
+            first_error = Some(format!("{}{}", error, at));
+        }
+    };
+
+    loop {
+        let packet = match format.next_packet() {
+            Ok(p) => p,
+            Err(SymphoniaError::IoError(ref e))
+                if e.kind() == std::io::ErrorKind::UnexpectedEof =>
+            {
+                break
+            }
+            Err(e) => {
+                note(format!("Unreadable stream: {}", e), frames);
+                break;
+            }
+        };
+        if packet.track_id() != track_id {
+            continue;
+        }
+        match decoder.decode(&packet) {
+            Ok(decoded) => frames += decoded.frames() as u64,
+            Err(SymphoniaError::DecodeError(e)) => {
+                note(format!("Corrupt frame ({})", e), packet.ts())
+            }
+            Err(SymphoniaError::ResetRequired) => decoder.reset(),
+            Err(e) => {
+                note(format!("Decode failed: {}", e), packet.ts());
+                break;
+            }
+        }
+    }
+
+    let verify_ok = decoder.finalize().verify_ok;
+    let error = if let Some(first) = first_error {
+        Some(if bad_packets > 1 {
+            format!("{} errors, first: {}", bad_packets, first)
+        } else {
+            first
+        })
+    } else if verify_ok == Some(false) {
+        Some("Checksum mismatch: decoded audio differs from the stored MD5".to_string())
+    } else {
+        match (params.n_frames, params.sample_rate) {
+            // More than a second missing; encoder padding never gets close
+            (Some(expected), Some(rate)) if frames + u64::from(rate) < expected => Some(format!(
+                "Truncated: {} of {} frames decoded",
+                frames, expected
+            )),
+            _ => None,
+        }
+    };
+    Ok(IntegrityReport {
+        error,
+        checksum_verified: verify_ok == Some(true),
+    })
+}
@@ -0,0 +1,349 @@
+//! Round-trip latency measurement.
+//!
+//! Plays short logarithmic sweeps on the default output device while
+//! recording an input device — a loopback cable from the DAC to an audio
+//! interface, or a microphone by the speakers — and finds each sweep in the
+//! recording by cross-correlation. The delay runs from the moment the output
+//! callback hands a sweep to the driver to the moment it arrives in the
+//! input buffer, so it covers everything the buffer-derived estimate leaves
+//! out: driver and device buffers on both sides, the converters, and the
+//! cable or air in between.
+
+use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+use cpal::SampleFormat;
+use realfft::RealFftPlanner;
+use serde::Serialize;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use super::engine::AudioEngine;
+use super::ring_buffer::RingBuffer;
+
+/// Sweeps played; the result is their median.
+const SWEEPS: usize = 3;
+
+/// Sweep length and level (-12 dBFS: audible through a mic without being
+/// harsh).
+const SWEEP_SECS: f64 = 0.1;
+const SWEEP_LEVEL: f32 = 0.25;
+
+/// Sweep frequency range; the top is kept under Nyquist.
+const SWEEP_FREQS_HZ: (f64, f64) = (200.0, 10_000.0);
+
+/// Silence before the first sweep, while both streams settle.
+const PRE_ROLL_SECS: f64 = 0.5;
+
+/// Time between sweep starts; also the longest latency that can be measured.
+const SWEEP_SPACING_SECS: f64 = 1.0;
+
+/// Recording buffer in mono samples (~5 s at 192 kHz), emptied every
+/// [`POLL_INTERVAL`].
+const CAPTURE_BUFFER_SIZE: usize = 1 << 20;
+
+/// Input callbacks whose timing is recorded; enough for the whole
+/// measurement with buffers down to ~0.3 ms.
+const MAX_INPUT_CALLBACKS: usize = 16384;
+
+/// Mono samples mixed per ring buffer write in the input callback.
+const MIX_CHUNK: usize = 1024;
+
+/// A correlation peak must stand this far above the correlation's RMS to
+/// count as a sweep rather than noise.
+const MIN_PEAK_RATIO: f32 = 10.0;
+
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+const ENGINE_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Clone, Serialize)]
+pub struct LatencyMeasurement {
+    pub output_device: String,
+    pub input_device: String,
+    pub output_sample_rate: u32,
+    pub input_sample_rate: u32,
+    /// Median round trip of the sweeps found, in milliseconds.
+    pub round_trip_ms: f64,
+    /// Each sweep's round trip; `None` where it wasn't found in the
+    /// recording.
+    pub sweeps_ms: Vec<Option<f64>>,
+}
+
+/// Measure the round trip from the default output device to `input_device`
+/// (by name; the default input device when `None`). Stops playback first.
+pub fn measure_round_trip(
+    engine: &AudioEngine,
+    input_device: Option<&str>,
+) -> Result<LatencyMeasurement, String> {
+    let host = cpal::default_host();
+    let output = host
+        .default_output_device()
+        .ok_or_else(|| "No output device".to_string())?;
+    let input = match input_device {
+        Some(name) => host
+            .input_devices()
+            .map_err(|e| format!("Failed to list input devices: {}", e))?
+            .find(|d| d.name().ok().as_deref() == Some(name))
+            .ok_or_else(|| format!("Input device not found: {}", name))?,
+        None => host
+            .default_input_device()
+            .ok_or_else(|| "No input device".to_string())?,
+    };
+    let output_config = output
+        .default_output_config()
+        .map_err(|e| format!("Failed to query the output format: {}", e))?;
+    let input_config = input
+        .default_input_config()
+        .map_err(|e| format!("Failed to query the input format: {}", e))?;
+    for format in [output_config.sample_format(), input_config.sample_format()] {
+        if format != SampleFormat::F32 {
+            return Err(format!("Unsupported sample format: {:?}", format));
+        }
+    }
+    let out_rate = output_config.sample_rate().0;
+    let out_channels = usize::from(output_config.channels()).max(1);
+    let in_rate = input_config.sample_rate().0;
+    let in_channels = usize::from(input_config.channels()).max(1);
+
+    engine.stop_and_wait(ENGINE_TIMEOUT)?;
+
+    // All times are nanoseconds since `base`
+    let base = Instant::now();
+    let sweep_out = sweep(out_rate);
+    let starts: [u64; SWEEPS] = std::array::from_fn(|i| {
+        ((PRE_ROLL_SECS + i as f64 * SWEEP_SPACING_SECS) * f64::from(out_rate)) as u64
+    });
+    let written_ns: Arc<[AtomicU64; SWEEPS]> = Arc::new(std::array::from_fn(|_| AtomicU64::new(0)));
+
+    let ring = Arc::new(RingBuffer::new(CAPTURE_BUFFER_SIZE));
+    let overflowed = Arc::new(AtomicBool::new(false));
+    // (input frames received so far, time) after each input callback
+    let stamps: Arc<Vec<(AtomicU64, AtomicU64)>> = Arc::new(
+        (0..MAX_INPUT_CALLBACKS)
+            .map(|_| (AtomicU64::new(0), AtomicU64::new(0)))
+            .collect(),
+    );
+    let callbacks = Arc::new(AtomicUsize::new(0));
+
+    let input_stream = input
+        .build_input_stream(
+            &input_config.config(),
+            {
+                let ring = ring.clone();
+                let overflowed = overflowed.clone();
+                let stamps = stamps.clone();
+                let callbacks = callbacks.clone();
+                let mut mono = vec![0.0f32; MIX_CHUNK];
+                let mut frames: u64 = 0;
+                move |data: &[f32], _: &cpal::InputCallbackInfo| {
+                    let now = base.elapsed().as_nanos() as u64;
+                    for chunk in data.chunks(MIX_CHUNK * in_channels) {
+                        let n = chunk.len() / in_channels;
+                        for (m, frame) in mono.iter_mut().zip(chunk.chunks_exact(in_channels)) {
+                            *m = frame.iter().sum::<f32>() / in_channels as f32;
+                        }
+                        if ring.write(&mono[..n]) < n {
+                            overflowed.store(true, Ordering::Relaxed);
+                        }
+                    }
+                    frames += (data.len() / in_channels) as u64;
+                    let i = callbacks.fetch_add(1, Ordering::Relaxed);
+                    if let Some((end, at)) = stamps.get(i) {
+                        end.store(frames, Ordering::Relaxed);
+                        at.store(now, Ordering::Release);
+                    }
+                }
+            },
+            |err| log::error!("Latency test input error: {}", err),
+            None,
+        )
+        .map_err(|e| format!("Failed to open the input device: {}", e))?;
+
+    let output_stream = output
+        .build_output_stream(
+            &output_config.config(),
+            {
+                let written_ns = written_ns.clone();
+                let mut frame: u64 = 0;
+                move |data: &mut [f32], _: &cpal::OutputCallbackInfo| {
+                    let now = base.elapsed().as_nanos() as u64;
+                    let frames = (data.len() / out_channels) as u64;
+                    for (i, out) in data.chunks_mut(out_channels).enumerate() {
+                        let f = frame + i as u64;
+                        let s = starts
+                            .iter()
+                            .find_map(|&start| {
+                                f.checked_sub(start)
+                                    .and_then(|offset| sweep_out.get(offset as usize))
+                            })
+                            .copied()
+                            .unwrap_or(0.0);
+                        out.fill(s);
+                    }
+                    for (start, written) in starts.iter().zip(written_ns.iter()) {
+                        if (frame..frame + frames).contains(start) {
+                            let offset_ns = (start - frame) * 1_000_000_000 / u64::from(out_rate);
+                            written.store(now + offset_ns, Ordering::Relaxed);
+                        }
+                    }
+                    frame += frames;
+                }
+            },
+            |err| log::error!("Latency test output error: {}", err),
+            None,
+        )
+        .map_err(|e| format!("Failed to open the output device: {}", e))?;
+
+    input_stream
+        .play()
+        .map_err(|e| format!("Failed to start recording: {}", e))?;
+    output_stream
+        .play()
+        .map_err(|e| format!("Failed to start playback: {}", e))?;
+
+    let length = Duration::from_secs_f64(PRE_ROLL_SECS + SWEEPS as f64 * SWEEP_SPACING_SECS);
+    let mut captured: Vec<f32> = Vec::new();
+    let mut scratch = vec![0.0f32; CAPTURE_BUFFER_SIZE];
+    while base.elapsed() < length {
+        thread::sleep(POLL_INTERVAL);
+        let n = ring.read(&mut scratch);
+        captured.extend_from_slice(&scratch[..n]);
+    }
+    drop(output_stream);
+    drop(input_stream);
+    let n = ring.read(&mut scratch);
+    captured.extend_from_slice(&scratch[..n]);
+
+    if overflowed.load(Ordering::Relaxed) {
+        return Err("Recording fell behind; try again".to_string());
+    }
+    let recorded = callbacks.load(Ordering::Relaxed).min(MAX_INPUT_CALLBACKS);
+    let stamps: Vec<(u64, u64)> = stamps[..recorded]
+        .iter()
+        .map(|(end, at)| (end.load(Ordering::Relaxed), at.load(Ordering::Acquire)))
+        .collect();
+
+    let sweep_in = sweep(in_rate);
+    let ns_per_frame = 1e9 / f64::from(in_rate);
+    let window = (SWEEP_SPACING_SECS * f64::from(in_rate)) as usize + sweep_in.len();
+    let mut sweeps_ms = Vec::with_capacity(SWEEPS);
+    for written in written_ns.iter() {
+        let written = written.load(Ordering::Relaxed);
+        if written == 0 {
+            return Err("The output device stopped before the test finished".to_string());
+        }
+        // Search from the input frame recorded when the sweep was handed over
+        let Some(from) = frame_at(&stamps, written, ns_per_frame) else {
+            sweeps_ms.push(None);
+            continue;
+        };
+        let end = (from + window).min(captured.len());
+        let found = captured
+            .get(from..end)
+            .and_then(|recording| find_sweep(recording, &sweep_in));
+        sweeps_ms.push(found.and_then(|lag| {
+            let arrived = time_of(&stamps, (from + lag) as u64, ns_per_frame)?;
+            Some((arrived - written as f64) / 1e6)
+        }));
+    }
+
+    let mut found: Vec<f64> = sweeps_ms.iter().flatten().copied().collect();
+    if found.is_empty() {
+        return Err(
+            "The test sweep wasn't heard on the input; check the cable or microphone level"
+                .to_string(),
+        );
+    }
+    found.sort_by(f64::total_cmp);
+
+    Ok(LatencyMeasurement {
+        output_device: output.name().unwrap_or_default(),
+        input_device: input.name().unwrap_or_default(),
+        output_sample_rate: out_rate,
+        input_sample_rate: in_rate,
+        round_trip_ms: found[found.len() / 2],
+        sweeps_ms,
+    })
+}
+
+/// Logarithmic sweep with short fades at both ends.
+fn sweep(sample_rate: u32) -> Vec<f32> {
+    let rate = f64::from(sample_rate);
+    let len = (SWEEP_SECS * rate) as usize;
+    let (f0, f1) = (SWEEP_FREQS_HZ.0, SWEEP_FREQS_HZ.1.min(rate * 0.45));
+    let k = (f1 / f0).ln();
+    let fade = len / 10;
+    (0..len)
+        .map(|i| {
+            let t = i as f64 / rate;
+            let phase = 2.0 * std::f64::consts::PI * f0 * SWEEP_SECS / k
+                * ((t / SWEEP_SECS * k).exp() - 1.0);
+            let edge = i.min(len - 1 - i);
+            let gain = if edge < fade {
+                edge as f64 / fade as f64
+            } else {
+                1.0
+            };
+            (phase.sin() * gain) as f32 * SWEEP_LEVEL
+        })
+        .collect()
+}
+
+/// Offset of `reference` in `recording`, by cross-correlation, if the peak
+/// stands out from the noise.
+fn find_sweep(recording: &[f32], reference: &[f32]) -> Option<usize> {
+    if recording.len() < reference.len() {
+        return None;
+    }
+    let size = (recording.len() + reference.len()).next_power_of_two();
+    let mut planner = RealFftPlanner::<f32>::new();
+    let forward = planner.plan_fft_forward(size);
+    let inverse = planner.plan_fft_inverse(size);
+
+    let spectrum = |signal: &[f32]| {
+        let mut input = forward.make_input_vec();
+        input[..signal.len()].copy_from_slice(signal);
+        let mut output = forward.make_output_vec();
+        forward.process(&mut input, &mut output).ok()?;
+        Some(output)
+    };
+    let recorded = spectrum(recording)?;
+    let expected = spectrum(reference)?;
+    let mut product: Vec<_> = recorded
+        .iter()
+        .zip(&expected)
+        .map(|(r, e)| r * e.conj())
+        .collect();
+    // Real signals: DC and Nyquist have no imaginary part
+    if let [first, .., last] = product.as_mut_slice() {
+        first.im = 0.0;
+        last.im = 0.0;
+    }
+    let mut correlation = inverse.make_output_vec();
+    inverse.process(&mut product, &mut correlation).ok()?;
+
+    // Polarity may be inverted along the way, so peaks count either way
+    let lags = &correlation[..recording.len() - reference.len() + 1];
+    let (lag, peak) = lags
+        .iter()
+        .map(|c| c.abs())
+        .enumerate()
+        .max_by(|a, b| a.1.total_cmp(&b.1))?;
+    let rms = (lags.iter().map(|c| c * c).sum::<f32>() / lags.len() as f32).sqrt();
+    (peak > rms * MIN_PEAK_RATIO).then_some(lag)
+}
+
+/// The input frame being recorded at `time_ns`.
+fn frame_at(stamps: &[(u64, u64)], time_ns: u64, ns_per_frame: f64) -> Option<usize> {
+    let (end, at) = stamps.iter().find(|(_, at)| *at >= time_ns)?;
+    let frames_since = (at - time_ns) as f64 / ns_per_frame;
+    Some((*end as f64 - frames_since).max(0.0) as usize)
+}
+
+/// When input frame `frame` was recorded, from the callback that delivered
+/// it: the callback time less the frames that came after it.
+fn time_of(stamps: &[(u64, u64)], frame: u64, ns_per_frame: f64) -> Option<f64> {
+    let (end, at) = stamps.iter().find(|(end, _)| *end > frame)?;
+    Some(*at as f64 - (end - frame) as f64 * ns_per_frame)
+}
@@ -0,0 +1,76 @@
+//! EBU R128 loudness analysis for ReplayGain.
+//!
+//! Tracks are decoded and measured with the `ebur128` crate (integrated
+//! loudness and sample peak). Gains follow ReplayGain 2.0: relative to a
+//! -18 LUFS reference. Album loudness is the gated loudness of all of the
+//! album's tracks together, not an average of their values.
+
+use super::decoder::{AudioDecoder, DecodeStatus};
+use ebur128::{EbuR128, Mode};
+
+/// ReplayGain 2.0 reference loudness.
+pub const REPLAYGAIN_REFERENCE_LUFS: f64 = -18.0;
+
+/// Loudness state of one analysed track.
+pub struct TrackLoudness {
+    state: EbuR128,
+    channels: u32,
+}
+
+impl TrackLoudness {
+    /// Integrated loudness in LUFS.
+    pub fn loudness_lufs(&self) -> Result<f64, String> {
+        self.state
+            .loudness_global()
+            .map_err(|e| format!("Loudness measurement failed: {}", e))
+    }
+
+    /// Highest absolute sample value over all channels (1.0 = full scale).
+    pub fn peak(&self) -> Result<f64, String> {
+        let mut peak: f64 = 0.0;
+        for channel in 0..self.channels {
+            let p = self
+                .state
+                .sample_peak(channel)
+                .map_err(|e| format!("Peak measurement failed: {}", e))?;
+            peak = peak.max(p);
+        }
+        Ok(peak)
+    }
+}
+
+/// Decode a file and measure its loudness.
+pub fn analyze_file(path: &str) -> Result<TrackLoudness, String> {
+    let mut decoder = AudioDecoder::open(path)?;
+    let channels = decoder.channels() as u32;
+    let mut state = EbuR128::new(
+        channels,
+        decoder.sample_rate(),
+        Mode::I | Mode::SAMPLE_PEAK | Mode::HISTOGRAM,
+    )
+    .map_err(|e| format!("Failed to start loudness analysis: {}", e))?;
+
+    loop {
+        match decoder.next_samples() {
+            Ok(samples) => state
+                .add_frames_f32(&samples)
+                .map_err(|e| format!("Loudness measurement failed: {}", e))?,
+            Err(DecodeStatus::EndOfStream) => break,
+            Err(DecodeStatus::Error(e)) => return Err(format!("Decode failed: {}", e)),
+        }
+    }
+    Ok(TrackLoudness { state, channels })
+}
+
+/// Loudness of several tracks played as one album, in LUFS.
+pub fn album_loudness_lufs<'a>(
+    tracks: impl Iterator<Item = &'a TrackLoudness>,
+) -> Result<f64, String> {
+    EbuR128::loudness_global_multiple(tracks.map(|t| &t.state))
+        .map_err(|e| format!("Loudness measurement failed: {}", e))
+}
+
+/// ReplayGain 2.0 gain in dB for a loudness in LUFS.
+pub fn gain_db(loudness_lufs: f64) -> f64 {
+    REPLAYGAIN_REFERENCE_LUFS - loudness_lufs
+}
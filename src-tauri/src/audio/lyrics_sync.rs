@@ -0,0 +1,92 @@
+//! Synced lyrics follower.
+//!
+//! A tracker thread watches the engine and reports whenever the line of the
+//! playing file's synced lyrics changes, going by the audible (latency
+//! compensated) position rather than the decoder's, so highlighted lines
+//! match what is heard. Lyrics are loaded once per file, or re-checked now
+//! and then while it has none.
+
+use serde::Serialize;
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use super::engine::AudioEngine;
+use crate::metadata::lyrics::{self, LyricLine};
+
+/// How often the tracker samples the playback position.
+const SAMPLE_INTERVAL: Duration = Duration::from_millis(50);
+
+/// How often a playing file without synced lyrics is checked again, so an
+/// `.lrc` saved meanwhile (e.g. fetched online) is picked up.
+const RETRY_INTERVAL: Duration = Duration::from_secs(5);
+
+/// The current line of the playing file's synced lyrics.
+#[derive(Clone, Serialize)]
+pub struct LyricsLineEvent {
+    pub path: String,
+    /// Index into the file's synced lyrics, `None` before the first line.
+    pub index: Option<usize>,
+    /// Start of the line, in milliseconds.
+    pub time_ms: Option<u64>,
+    pub text: Option<String>,
+}
+
+/// Start the tracker. `on_line` is called from its thread on every change of
+/// line, including jumps from seeking.
+pub fn spawn_tracker(
+    engine: Arc<AudioEngine>,
+    mut on_line: impl FnMut(&LyricsLineEvent) + Send + 'static,
+) {
+    thread::Builder::new()
+        .name("lyrics-tracker".into())
+        .spawn(move || {
+            // (path, its synced lyrics, when read) for the current file
+            let mut loaded: Option<(String, Option<Vec<LyricLine>>, Instant)> = None;
+            // Line last reported; the outer `None` means nothing reported yet
+            let mut reported: Option<Option<usize>> = None;
+
+            loop {
+                thread::sleep(SAMPLE_INTERVAL);
+
+                let Some(file) = engine.get_state().current_file else {
+                    loaded = None;
+                    continue;
+                };
+                let stale = match &loaded {
+                    Some((path, lines, read_at)) => {
+                        *path != file || (lines.is_none() && read_at.elapsed() >= RETRY_INTERVAL)
+                    }
+                    None => true,
+                };
+                if stale {
+                    let lines = lyrics::read_synced_lyrics(&file).unwrap_or_else(|e| {
+                        log::warn!("Lyrics for {} unreadable: {}", file, e);
+                        None
+                    });
+                    loaded = Some((file.clone(), lines, Instant::now()));
+                    reported = None;
+                }
+                let Some((_, Some(lines), _)) = &loaded else {
+                    continue;
+                };
+
+                let position = engine.get_audible_position_ms();
+                let index = lines
+                    .partition_point(|l| l.time_ms <= position)
+                    .checked_sub(1);
+                if reported == Some(index) {
+                    continue;
+                }
+                reported = Some(index);
+                let line = index.map(|i| &lines[i]);
+                on_line(&LyricsLineEvent {
+                    path: file,
+                    index,
+                    time_ms: line.map(|l| l.time_ms),
+                    text: line.map(|l| l.text.clone()),
+                });
+            }
+        })
+        .expect("Failed to spawn lyrics tracker thread");
+}
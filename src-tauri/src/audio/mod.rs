@@ -1,6 +1,26 @@
-pub mod decoder;
+//! Audio: the playback engine and its meters come from the `masukii-audio`
+//! crate and are re-exported here; the rest builds on them for the app
+//! (analysis, recording, streaming, diagnostics).
+
+pub use masukii_audio::{
+    bit_meter, clipping, decoder, diagnostics_history, dropouts, dsp_plugin, engine, http_source,
+    icy, levels, loudness_meter, replaygain, resource_usage, ring_buffer,
+};
+
 pub mod device_profiles;
-pub mod engine;
+pub mod diagnostic_report;
+pub mod dynamic_range;
+pub mod fingerprint;
+pub mod flac_writer;
+pub mod integrity;
+pub mod latency;
+pub mod loudness;
+pub mod lyrics_sync;
 pub mod null_test;
-pub mod replaygain;
-pub mod ring_buffer;
+pub mod recorder;
+pub mod signal_path;
+pub mod snapcast;
+pub mod spectral;
+pub mod spectrogram;
+pub mod true_peak;
+pub mod wav_writer;
@@ -14,12 +14,49 @@
 ///   - Volume is 1.0
 ///   - No DSP is active
 ///
-/// The test decodes the file twice independently and compares samples,
+/// The basic test decodes the file twice independently and compares samples,
 /// confirming that symphonia's decoder produces consistent output and
 /// the ring buffer doesn't corrupt data.
+///
+/// The loopback test checks delivery through the OS: it plays the file,
+/// records the default output device with WASAPI loopback (what the Windows
+/// mixer hands the driver), lines the recording up with the decoded source
+/// and nulls the two. Anything the OS does to the signal — sample rate or
+/// channel conversion, system effects, other apps' sounds — shows up as a
+/// difference.
 
 use super::decoder::{AudioDecoder, DecodeStatus};
+use super::engine::{self, AudioCommand, AudioEngine, ReplayGainMode};
+use super::replaygain::ReplayGainState;
+use super::ring_buffer::RingBuffer;
+use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+use cpal::SampleFormat;
 use serde::Serialize;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Default and longest stretch the loopback test plays, in seconds.
+pub const LOOPBACK_DEFAULT_SECS: f64 = 10.0;
+const LOOPBACK_MAX_SECS: f64 = 120.0;
+
+/// Loopback capture buffer in samples (~5 s of 192 kHz stereo). It is
+/// emptied every [`POLL_INTERVAL`].
+const CAPTURE_BUFFER_SIZE: usize = 1 << 21;
+
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// How long playback may take to stop or start.
+const ENGINE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Extra playback captured past the compared stretch, in milliseconds.
+const CAPTURE_MARGIN_MS: u64 = 500;
+
+/// How far either side of the first sound the capture is searched for the
+/// source, and how many frames are compared at each offset.
+const ALIGN_SEARCH_FRAMES: usize = 4096;
+const ALIGN_BLOCK_FRAMES: usize = 8192;
 
 #[derive(Clone, Serialize)]
 pub struct NullTestResult {
@@ -33,6 +70,9 @@ pub struct NullTestResult {
     pub max_diff: f64,
     /// RMS of all differences (lower = better, 0.0 = perfect).
     pub rms_diff: f64,
+    /// `max_diff` and `rms_diff` in dBFS; `None` when nothing differs.
+    pub max_diff_dbfs: Option<f64>,
+    pub rms_diff_dbfs: Option<f64>,
     /// Human-readable summary.
     pub summary: String,
 }
@@ -68,33 +108,15 @@ pub fn run_null_test(path: &str) -> Result<NullTestResult, String> {
 
     // Compare
     let len = samples_a.len().min(samples_b.len());
-    let mut diff_count: u64 = 0;
-    let mut max_diff: f64 = 0.0;
-    let mut sum_sq: f64 = 0.0;
-
-    for i in 0..len {
-        let diff = (samples_a[i] as f64) - (samples_b[i] as f64);
-        if diff.abs() > 0.0 {
-            diff_count += 1;
-            let abs_diff = diff.abs();
-            if abs_diff > max_diff {
-                max_diff = abs_diff;
-            }
-            sum_sq += diff * diff;
-        }
-    }
+    let mut diff = Diff::default();
+    diff.add(&samples_a, &samples_b);
+    let (mut diff_count, max_diff, rms_diff) = (diff.count, diff.max, diff.rms());
 
     // Check length mismatch
     if samples_a.len() != samples_b.len() {
         diff_count += (samples_a.len() as i64 - samples_b.len() as i64).unsigned_abs();
     }
 
-    let rms_diff = if len > 0 {
-        (sum_sq / len as f64).sqrt()
-    } else {
-        0.0
-    };
-
     let passed = diff_count == 0 && samples_a.len() == samples_b.len();
 
     let summary = if passed {
@@ -115,6 +137,324 @@ pub fn run_null_test(path: &str) -> Result<NullTestResult, String> {
         diff_samples: diff_count,
         max_diff,
         rms_diff,
+        max_diff_dbfs: dbfs(max_diff),
+        rms_diff_dbfs: dbfs(rms_diff),
         summary,
     })
 }
+
+/// Play the first `seconds` of a file through the engine, record the
+/// default output device with WASAPI loopback, and null the recording
+/// against the decoded file.
+///
+/// Whatever was playing is stopped, and playback is stopped again at the
+/// end. Needs volume 100% and ReplayGain off; other apps should be quiet.
+pub fn run_loopback_null_test(
+    engine: &AudioEngine,
+    path: &str,
+    seconds: f64,
+) -> Result<NullTestResult, String> {
+    if !engine.get_diagnostics().is_bit_perfect {
+        return Err("Set the volume to 100% and ReplayGain off first".to_string());
+    }
+    let mut decoder = AudioDecoder::open(path)?;
+    let sample_rate = decoder.sample_rate();
+    let channels = decoder.channels().max(1);
+    // Stay clear of the end of the track, which the engine ramps down
+    let seconds = seconds
+        .clamp(1.0, LOOPBACK_MAX_SECS)
+        .min(decoder.duration_secs - 1.0);
+    if seconds < 1.0 {
+        return Err("Track is too short for a loopback test".to_string());
+    }
+
+    let device = cpal::default_host()
+        .default_output_device()
+        .ok_or_else(|| "No output device".to_string())?;
+    let mix = device
+        .default_output_config()
+        .map_err(|e| format!("Failed to query the output format: {}", e))?;
+    if mix.sample_rate().0 != sample_rate || usize::from(mix.channels()) != channels {
+        return Ok(failed(format!(
+            "NOT BIT-PERFECT: the system mixes at {} Hz / {} ch, so this {} Hz / {} ch file is converted before it reaches the device.",
+            mix.sample_rate().0,
+            mix.channels(),
+            sample_rate,
+            channels
+        )));
+    }
+    if mix.sample_format() != SampleFormat::F32 {
+        return Err(format!("Unsupported mix format: {:?}", mix.sample_format()));
+    }
+
+    // Decode up front so nothing holds up emptying the capture buffer
+    let wanted = (seconds * f64::from(sample_rate)) as usize * channels;
+    let mut source: Vec<f32> = Vec::with_capacity(wanted);
+    while source.len() < wanted {
+        match decoder.next_samples() {
+            Ok(buf) => source.extend_from_slice(&buf),
+            Err(DecodeStatus::EndOfStream) => break,
+            Err(DecodeStatus::Error(e)) => return Err(format!("Decode failed: {}", e)),
+        }
+    }
+    source.truncate(wanted);
+
+    engine.stop_and_wait(ENGINE_TIMEOUT)?;
+
+    // Loopback capture: an input stream on an output device
+    let ring = Arc::new(RingBuffer::new(CAPTURE_BUFFER_SIZE));
+    let overflowed = Arc::new(AtomicBool::new(false));
+    let stream = device
+        .build_input_stream(
+            &mix.config(),
+            {
+                let ring = ring.clone();
+                let overflowed = overflowed.clone();
+                move |data: &[f32], _: &cpal::InputCallbackInfo| {
+                    if ring.write(data) < data.len() {
+                        overflowed.store(true, Ordering::Relaxed);
+                    }
+                }
+            },
+            |err| log::error!("Loopback capture error: {}", err),
+            None,
+        )
+        .map_err(|e| format!("Loopback capture isn't available for this output: {}", e))?;
+    stream
+        .play()
+        .map_err(|e| format!("Failed to start loopback capture: {}", e))?;
+
+    engine.send_command(AudioCommand::play(path.to_string()));
+    let play_requested = Instant::now();
+    let target_ms = (seconds * 1000.0) as u64 + CAPTURE_MARGIN_MS;
+    let mut captured: Vec<f32> = Vec::new();
+    let mut scratch = vec![0.0f32; CAPTURE_BUFFER_SIZE];
+    let mut started = false;
+    loop {
+        thread::sleep(POLL_INTERVAL);
+        let n = ring.read(&mut scratch);
+        captured.extend_from_slice(&scratch[..n]);
+
+        let state = engine.get_state();
+        if !started {
+            started = state.current_file.as_deref() == Some(path) && state.is_playing;
+            if !started && play_requested.elapsed() > ENGINE_TIMEOUT {
+                return Err("Playback didn't start".to_string());
+            }
+            continue;
+        }
+        if !state.is_playing || engine.get_audible_position_ms() >= target_ms {
+            break;
+        }
+    }
+    drop(stream);
+    let n = ring.read(&mut scratch);
+    captured.extend_from_slice(&scratch[..n]);
+    let dropouts = engine.get_diagnostics().dropout_count;
+    engine.send_command(AudioCommand::Stop);
+
+    if overflowed.load(Ordering::Relaxed) {
+        return Err("Loopback capture fell behind; try again".to_string());
+    }
+    let Some(offset) = align(&source, &captured, channels) else {
+        return Ok(failed(
+            "NO SIGNAL: nothing from the file was captured from the output.".to_string(),
+        ));
+    };
+
+    // Frames of the source to compare, and where they start in the capture
+    let start = offset.min(0).unsigned_abs();
+    let captured_start = (start as isize + offset) as usize;
+    let frames = (source.len() / channels)
+        .saturating_sub(start)
+        .min((captured.len() / channels).saturating_sub(captured_start));
+    let expected = &source[start * channels..(start + frames) * channels];
+    let received = &captured[captured_start * channels..(captured_start + frames) * channels];
+    let mut diff = Diff::default();
+    diff.add(expected, received);
+    let (diff_count, max_diff, rms_diff) = (diff.count, diff.max, diff.rms());
+
+    let passed = diff_count == 0 && frames > 0;
+    let compared_secs = frames as f64 / f64::from(sample_rate);
+    let mut summary = if passed {
+        format!(
+            "BIT-PERFECT: {:.1} s captured from the output matches the file sample for sample.",
+            compared_secs
+        )
+    } else {
+        format!(
+            "DIFFERENCES FOUND: {}/{} samples captured from the output differ over {:.1} s. Max diff: {:.2e}, RMS: {:.2e}",
+            diff_count,
+            expected.len(),
+            compared_secs,
+            max_diff,
+            rms_diff
+        )
+    };
+    if dropouts > 0 {
+        summary.push_str(&format!(" {} dropout(s) during playback.", dropouts));
+    }
+
+    Ok(NullTestResult {
+        passed,
+        total_samples: expected.len() as u64,
+        diff_samples: diff_count,
+        max_diff,
+        rms_diff,
+        max_diff_dbfs: dbfs(max_diff),
+        rms_diff_dbfs: dbfs(rms_diff),
+        summary,
+    })
+}
+
+/// Run a file through the output's current processing — ReplayGain, volume
+/// and the hard limiter, as playback applies them — and null the result
+/// against the plain decode, to show how far the settings move the signal.
+/// DSP plugins only run in playback and aren't included.
+pub fn run_dsp_null_test(engine: &AudioEngine, path: &str) -> Result<NullTestResult, String> {
+    let settings = engine.dsp_settings();
+    let mut replaygain = ReplayGainState::new();
+    replaygain.set_mode(settings.replaygain_mode);
+    replaygain.set_preamp(settings.replaygain_preamp_db);
+    replaygain.set_clipping_prevention(settings.clipping_prevention);
+    // Unreadable tags mean no gain, as in playback
+    let _ = replaygain.load_from_file(path);
+    let bit_perfect = settings.is_bit_perfect();
+
+    let mut decoder = AudioDecoder::open(path)?;
+    let mut diff = Diff::default();
+    let mut limited: u64 = 0;
+    loop {
+        let source = match decoder.next_samples() {
+            Ok(buf) => buf,
+            Err(DecodeStatus::EndOfStream) => break,
+            Err(DecodeStatus::Error(e)) => return Err(format!("Decode failed: {}", e)),
+        };
+        let mut processed = source.clone();
+        replaygain.apply(&mut processed);
+        if !bit_perfect {
+            for s in processed.iter_mut() {
+                let scaled = *s * settings.volume;
+                *s = engine::hard_limit(scaled);
+                if *s != scaled {
+                    limited += 1;
+                }
+            }
+        }
+        diff.add(&source, &processed);
+    }
+
+    let chain = format!(
+        "ReplayGain {} ({:+.2} dB), volume {:.0}%",
+        match settings.replaygain_mode {
+            ReplayGainMode::Off => "off",
+            ReplayGainMode::Track => "track",
+            ReplayGainMode::Album => "album",
+        },
+        replaygain.gain_db(),
+        settings.volume * 100.0
+    );
+    let passed = diff.count == 0;
+    let summary = match (dbfs(diff.max), dbfs(diff.rms())) {
+        (Some(max_db), Some(rms_db)) => format!(
+            "DSP ACTIVE: {}. {}/{} samples changed; max deviation {:.1} dBFS, RMS {:.1} dBFS; {} sample(s) hit the limiter.",
+            chain, diff.count, diff.samples, max_db, rms_db, limited
+        ),
+        _ => format!(
+            "BIT-PERFECT: {} leaves all {} samples untouched.",
+            chain, diff.samples
+        ),
+    };
+
+    Ok(NullTestResult {
+        passed,
+        total_samples: diff.samples,
+        diff_samples: diff.count,
+        max_diff: diff.max,
+        rms_diff: diff.rms(),
+        max_diff_dbfs: dbfs(diff.max),
+        rms_diff_dbfs: dbfs(diff.rms()),
+        summary,
+    })
+}
+
+/// Running comparison of two signals.
+#[derive(Default)]
+struct Diff {
+    /// Samples compared.
+    samples: u64,
+    /// Samples that differed.
+    count: u64,
+    /// Largest absolute difference.
+    max: f64,
+    sum_sq: f64,
+}
+
+impl Diff {
+    /// Compare the samples both slices have.
+    fn add(&mut self, a: &[f32], b: &[f32]) {
+        for (&x, &y) in a.iter().zip(b) {
+            let diff = (x as f64) - (y as f64);
+            if diff.abs() > 0.0 {
+                self.count += 1;
+                self.max = self.max.max(diff.abs());
+                self.sum_sq += diff * diff;
+            }
+        }
+        self.samples += a.len().min(b.len()) as u64;
+    }
+
+    /// RMS of all differences.
+    fn rms(&self) -> f64 {
+        if self.samples > 0 {
+            (self.sum_sq / self.samples as f64).sqrt()
+        } else {
+            0.0
+        }
+    }
+}
+
+/// Where the source starts in the capture, in frames (capture frame =
+/// source frame + offset). The capture opens with silence until playback
+/// reaches the device, so the first sound in each gives a rough offset;
+/// the exact one is where a block of the source matches best.
+fn align(source: &[f32], captured: &[f32], channels: usize) -> Option<isize> {
+    let first_sound = |s: &[f32]| s.iter().position(|&x| x != 0.0).map(|i| i / channels);
+    let source_first = first_sound(source)?;
+    let captured_first = first_sound(captured)?;
+
+    let block = ALIGN_BLOCK_FRAMES.min(source.len() / channels - source_first);
+    let reference = &source[source_first * channels..(source_first + block) * channels];
+    let candidates =
+        captured_first.saturating_sub(ALIGN_SEARCH_FRAMES)..=captured_first + ALIGN_SEARCH_FRAMES;
+    candidates
+        .filter(|&at| (at + block) * channels <= captured.len())
+        .map(|at| {
+            let error: f64 = reference
+                .iter()
+                .zip(&captured[at * channels..])
+                .map(|(&a, &b)| (f64::from(a) - f64::from(b)).powi(2))
+                .sum();
+            (at, error)
+        })
+        .min_by(|a, b| a.1.total_cmp(&b.1))
+        .map(|(at, _)| at as isize - source_first as isize)
+}
+
+fn failed(summary: String) -> NullTestResult {
+    NullTestResult {
+        passed: false,
+        total_samples: 0,
+        diff_samples: 0,
+        max_diff: 0.0,
+        rms_diff: 0.0,
+        max_diff_dbfs: None,
+        rms_diff_dbfs: None,
+        summary,
+    }
+}
+
+/// An amplitude difference in dBFS; `None` for no difference.
+fn dbfs(diff: f64) -> Option<f64> {
+    (diff > 0.0).then(|| 20.0 * diff.log10())
+}
@@ -0,0 +1,389 @@
+//! Record what's playing to a lossless file.
+//!
+//! The output callback copies the samples it plays, after ReplayGain and
+//! before the volume control, into a tap the recorder drains to a WAV or
+//! FLAC file: a radio show or a render through the processing, at the
+//! recording's level rather than the listening level. Pauses and the gaps
+//! between tracks aren't recorded.
+//!
+//! With splitting on, each track goes to its own file, and so does each
+//! title a radio station announces; otherwise one file runs until the
+//! recording is stopped or the sample rate or channel count changes. Files
+//! are named after the track (`Artist - Title`) or the station's title,
+//! with ` (2)` and so on added rather than overwriting. Splits follow the
+//! player's state, so a file may start or end up to a tenth of a second
+//! off, and a station's titles are often a little ahead of its audio.
+
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use super::engine::{AudioEngine, PlaybackState};
+use super::flac_writer::FlacWriter;
+use super::http_source;
+use super::icy;
+use super::loudness_meter::TAP_SIZE;
+use super::wav_writer::WavWriter;
+use crate::errors::{self, ErrorCode};
+use crate::library::organize;
+use crate::metadata::reader;
+
+/// How often the tap is drained.
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RecordFormat {
+    Wav,
+    Flac,
+}
+
+impl RecordFormat {
+    fn extension(self) -> &'static str {
+        match self {
+            RecordFormat::Wav => "wav",
+            RecordFormat::Flac => "flac",
+        }
+    }
+}
+
+#[derive(Clone, Deserialize)]
+pub struct RecordOptions {
+    /// Folder the files are saved in.
+    pub folder: String,
+    pub format: RecordFormat,
+    /// 16 or 24.
+    #[serde(default = "default_bit_depth")]
+    pub bit_depth: u8,
+    /// A new file for every track and every radio title.
+    #[serde(default)]
+    pub split_on_track_change: bool,
+}
+
+fn default_bit_depth() -> u8 {
+    24
+}
+
+#[derive(Clone, Serialize)]
+pub struct RecordedFile {
+    pub path: String,
+    pub duration_secs: f64,
+    pub size_bytes: u64,
+}
+
+#[derive(Clone, Default, Serialize)]
+pub struct RecordingStatus {
+    pub recording: bool,
+    /// File being written, once something has played.
+    pub current_file: Option<String>,
+    pub current_secs: f64,
+    /// Files finished since recording started, oldest first.
+    pub saved: Vec<RecordedFile>,
+    /// Why recording stopped on its own, e.g. the disk is full.
+    pub error: Option<String>,
+}
+
+struct Running {
+    stop: Arc<AtomicBool>,
+    thread: JoinHandle<()>,
+}
+
+pub struct Recorder {
+    engine: Arc<AudioEngine>,
+    running: Mutex<Option<Running>>,
+    status: Arc<Mutex<RecordingStatus>>,
+}
+
+impl Recorder {
+    pub fn new(engine: Arc<AudioEngine>) -> Self {
+        Self {
+            engine,
+            running: Mutex::new(None),
+            status: Arc::new(Mutex::new(RecordingStatus::default())),
+        }
+    }
+
+    pub fn status(&self) -> RecordingStatus {
+        self.status.lock().clone()
+    }
+
+    /// Start recording, stopping a recording already running. `on_saved` is
+    /// called from the recorder thread for every file it finishes.
+    pub fn start(
+        &self,
+        options: RecordOptions,
+        on_saved: impl FnMut(&RecordedFile) + Send + 'static,
+    ) -> Result<(), String> {
+        if !matches!(options.bit_depth, 16 | 24) {
+            return Err("Bit depth must be 16 or 24".to_string());
+        }
+        std::fs::create_dir_all(&options.folder)
+            .map_err(|e| format!("Failed to create folder: {}", e))?;
+        self.stop();
+        *self.status.lock() = RecordingStatus {
+            recording: true,
+            ..Default::default()
+        };
+
+        let stop = Arc::new(AtomicBool::new(false));
+        let session = Session {
+            engine: self.engine.clone(),
+            options,
+            status: self.status.clone(),
+            file: None,
+        };
+        let stopped = stop.clone();
+        let thread = thread::Builder::new()
+            .name("recorder".into())
+            .spawn(move || session.run(&stopped, on_saved))
+            .expect("Failed to spawn recorder thread");
+        *self.running.lock() = Some(Running { stop, thread });
+        Ok(())
+    }
+
+    /// Stop recording, finishing the file being written.
+    pub fn stop(&self) -> RecordingStatus {
+        if let Some(running) = self.running.lock().take() {
+            running.stop.store(true, Ordering::Relaxed);
+            let _ = running.thread.join();
+        }
+        self.status()
+    }
+}
+
+enum Writer {
+    Wav(WavWriter),
+    Flac(FlacWriter),
+}
+
+struct OpenFile {
+    writer: Writer,
+    path: PathBuf,
+    /// What's being recorded: a new one starts a new file when splitting.
+    source: Source,
+    sample_rate: u32,
+    channels: usize,
+    frames: u64,
+}
+
+impl OpenFile {
+    fn size_bytes(&self) -> u64 {
+        match &self.writer {
+            Writer::Wav(w) => w.data_bytes() + 44,
+            Writer::Flac(w) => w.bytes_written(),
+        }
+    }
+}
+
+/// The track (file and segment) or radio title playing.
+#[derive(Clone, PartialEq)]
+struct Source {
+    file: String,
+    start_secs: u64,
+    title: Option<String>,
+}
+
+impl Source {
+    fn of(state: &PlaybackState) -> Option<Self> {
+        let file = state.current_file.clone()?;
+        let title = http_source::is_url(&file)
+            .then(|| icy::stream_title(&file))
+            .flatten();
+        Some(Self {
+            file,
+            start_secs: state.start_secs.to_bits(),
+            title,
+        })
+    }
+}
+
+struct Session {
+    engine: Arc<AudioEngine>,
+    options: RecordOptions,
+    status: Arc<Mutex<RecordingStatus>>,
+    file: Option<OpenFile>,
+}
+
+impl Session {
+    fn run(mut self, stop: &AtomicBool, mut on_saved: impl FnMut(&RecordedFile)) {
+        let tap = self.engine.record_tap();
+        let mut scratch = vec![0.0f32; TAP_SIZE];
+        // What played before recording started
+        while tap.read(&mut scratch) > 0 {}
+
+        let result = loop {
+            let stopping = stop.load(Ordering::Relaxed);
+            if !stopping {
+                thread::sleep(POLL_INTERVAL);
+            }
+            let mut state = self.engine.get_state();
+            // The tap carries the output's format
+            (state.sample_rate, state.channels) = self.engine.output_format();
+            let source = Source::of(&state);
+
+            // What was heard since the last look belongs to the file open
+            // then, or to what's playing now if none is
+            let n = tap.read(&mut scratch);
+            if let Err(e) = self.write(&scratch[..n], &state, source.as_ref()) {
+                break Err(e);
+            }
+            if stopping {
+                break Ok(());
+            }
+
+            // Stopped in between tracks changes nothing
+            let playing = source.as_ref().filter(|_| state.sample_rate > 0);
+            let changed = self.file.as_ref().zip(playing).is_some_and(|(f, source)| {
+                f.sample_rate != state.sample_rate
+                    || f.channels != state.channels as usize
+                    || (self.options.split_on_track_change && f.source != *source)
+            });
+            if changed {
+                match self.close() {
+                    Ok(Some(saved)) => on_saved(&saved),
+                    Ok(None) => {}
+                    Err(e) => break Err(e),
+                }
+            }
+        };
+
+        let closed = self.close();
+        let mut status = self.status.lock();
+        status.recording = false;
+        status.current_file = None;
+        status.current_secs = 0.0;
+        drop(status);
+        match result.and(closed) {
+            Ok(Some(saved)) => on_saved(&saved),
+            Ok(None) => {}
+            Err(e) => {
+                errors::report(ErrorCode::Recording, format!("Recording failed: {}", e));
+                self.status.lock().error = Some(e);
+            }
+        }
+    }
+
+    fn write(
+        &mut self,
+        samples: &[f32],
+        state: &PlaybackState,
+        source: Option<&Source>,
+    ) -> Result<(), String> {
+        if samples.is_empty() {
+            return Ok(());
+        }
+        if self.file.is_none() {
+            let (Some(source), true) = (source, state.channels > 0) else {
+                return Ok(());
+            };
+            self.file = Some(self.open(state, source.clone())?);
+        }
+        let Some(file) = self.file.as_mut() else {
+            return Ok(());
+        };
+
+        let scale = f64::from(1u32 << (self.options.bit_depth - 1));
+        let samples: Vec<i32> = samples
+            .iter()
+            .map(|&s| (f64::from(s) * scale).round().clamp(-scale, scale - 1.0) as i32)
+            .collect();
+        match &mut file.writer {
+            Writer::Wav(w) => w.write(&samples)?,
+            Writer::Flac(w) => w.write(&samples)?,
+        }
+        file.frames += (samples.len() / file.channels.max(1)) as u64;
+
+        let mut status = self.status.lock();
+        status.current_file = Some(file.path.to_string_lossy().to_string());
+        status.current_secs = file.frames as f64 / f64::from(file.sample_rate.max(1));
+        Ok(())
+    }
+
+    fn open(&self, state: &PlaybackState, source: Source) -> Result<OpenFile, String> {
+        let folder = Path::new(&self.options.folder);
+        let path = unused_path(folder, &file_name(state, &source), self.options.format);
+        let (rate, channels, bits) = (
+            state.sample_rate,
+            state.channels as u16,
+            self.options.bit_depth,
+        );
+        let writer = match self.options.format {
+            RecordFormat::Wav => Writer::Wav(WavWriter::create(&path, rate, channels, bits)?),
+            RecordFormat::Flac => Writer::Flac(FlacWriter::create(&path, rate, channels, bits)?),
+        };
+        log::info!("Recording to {}", path.display());
+        Ok(OpenFile {
+            writer,
+            path,
+            source,
+            sample_rate: rate,
+            channels: usize::from(channels),
+            frames: 0,
+        })
+    }
+
+    /// Finish the open file, if any.
+    fn close(&mut self) -> Result<Option<RecordedFile>, String> {
+        let Some(file) = self.file.take() else {
+            return Ok(None);
+        };
+        let size_bytes = file.size_bytes();
+        match file.writer {
+            Writer::Wav(w) => w.finish()?,
+            Writer::Flac(w) => w.finish()?,
+        }
+        let saved = RecordedFile {
+            path: file.path.to_string_lossy().to_string(),
+            duration_secs: file.frames as f64 / f64::from(file.sample_rate.max(1)),
+            size_bytes,
+        };
+        let mut status = self.status.lock();
+        status.saved.push(saved.clone());
+        status.current_file = None;
+        status.current_secs = 0.0;
+        Ok(Some(saved))
+    }
+}
+
+/// `Artist - Title` of a track, the title a station announced, or the
+/// file's name.
+fn file_name(state: &PlaybackState, source: &Source) -> String {
+    if let Some(title) = &source.title {
+        return title.clone();
+    }
+    let whole_file = state.start_secs == 0.0 && state.end_secs.is_none();
+    let tagged = (whole_file && !http_source::is_url(&source.file))
+        .then(|| reader::read_metadata(&source.file).ok())
+        .flatten()
+        .and_then(|m| match (m.artist, m.title) {
+            (Some(artist), Some(title)) => Some(format!("{} - {}", artist, title)),
+            (None, Some(title)) => Some(title),
+            _ => None,
+        });
+    tagged.unwrap_or_else(|| {
+        let name = source.file.trim_end_matches('/');
+        let name = name.rsplit(['/', '\\']).next().unwrap_or(name);
+        name.rsplit_once('.')
+            .map_or(name, |(stem, _)| stem)
+            .to_string()
+    })
+}
+
+/// `folder/name.ext`, or `name (2).ext` and so on if that's taken.
+fn unused_path(folder: &Path, name: &str, format: RecordFormat) -> PathBuf {
+    let name = Some(organize::sanitize(name))
+        .filter(|n| !n.is_empty())
+        .unwrap_or_else(|| "Recording".to_string());
+    let ext = format.extension();
+    let mut path = folder.join(format!("{}.{}", name, ext));
+    let mut n = 2;
+    while path.exists() {
+        path = folder.join(format!("{} ({}).{}", name, n, ext));
+        n += 1;
+    }
+    path
+}
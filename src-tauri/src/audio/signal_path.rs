@@ -0,0 +1,184 @@
+//! Signal-path report, like foobar2000's playback information: each stage
+//! the current file passes through on its way to the device, read from the
+//! engine's live state, and whether it changes the samples.
+//!
+//! The chain is: decoder → ReplayGain → DSP plugins (decoder thread) →
+//! volume → hard limiter (output callback) → OS audio API → device. At
+//! volume 1.0 with ReplayGain off and no plugins the callback passes samples
+//! through untouched and volume and limiter are bypassed; the OS can still
+//! resample or remix when the device runs at another rate or channel count.
+
+use serde::Serialize;
+
+use super::engine::{AudioEngine, ReplayGainMode};
+
+#[derive(Clone, Serialize)]
+pub struct SignalPathStage {
+    /// `decoder`, `replaygain`, `plugins`, `volume`, `limiter`, `output` or
+    /// `device`.
+    pub stage: &'static str,
+    /// What it does right now, e.g. `volume -6.0 dB`.
+    pub summary: String,
+    /// The stage changes (or may change) the samples.
+    pub processing: bool,
+}
+
+#[derive(Clone, Serialize)]
+pub struct SignalPath {
+    /// Source first.
+    pub stages: Vec<SignalPathStage>,
+    /// The summaries joined with arrows, for display or copying.
+    pub description: String,
+    /// No stage touches the samples.
+    pub bit_perfect: bool,
+}
+
+/// The current file's signal path; `None` when nothing is loaded.
+pub fn describe(engine: &AudioEngine) -> Option<SignalPath> {
+    let state = engine.get_state();
+    state.current_file.as_ref()?;
+    let dsp = engine.dsp_settings();
+    let bypassed = dsp.is_bit_perfect();
+
+    let mut stages = Vec::new();
+    let mut stage = |stage, summary: String, processing| {
+        stages.push(SignalPathStage {
+            stage,
+            summary,
+            processing,
+        })
+    };
+
+    let codec = state
+        .codec
+        .as_deref()
+        .unwrap_or("unknown codec")
+        .to_uppercase();
+    let depth = state
+        .bit_depth
+        .map(|b| format!(" {}-bit", b))
+        .unwrap_or_default();
+    stage(
+        "decoder",
+        format!(
+            "{}{} {} {}",
+            codec,
+            depth,
+            rate(state.sample_rate),
+            channels(state.channels)
+        ),
+        false,
+    );
+
+    let rg_gain = engine.replaygain_gain_db();
+    let rg_summary = match (dsp.replaygain_mode, rg_gain) {
+        (ReplayGainMode::Off, _) => "ReplayGain off".to_string(),
+        (mode, gain) => {
+            let mode = if mode == ReplayGainMode::Album {
+                "album"
+            } else {
+                "track"
+            };
+            let gain = gain.map_or("no tag".to_string(), db);
+            let clip = if dsp.clipping_prevention {
+                ", clipping prevention"
+            } else {
+                ""
+            };
+            format!("ReplayGain {} {}{}", mode, gain, clip)
+        }
+    };
+    stage(
+        "replaygain",
+        rg_summary,
+        rg_gain.is_some_and(|g| g.abs() > 0.005),
+    );
+
+    let plugins = engine.dsp_chain();
+    if !plugins.is_empty() {
+        stage("plugins", format!("DSP {}", plugins.join(", ")), true);
+    }
+
+    let volume_db = 20.0 * dsp.volume.max(1e-6).log10();
+    if bypassed {
+        stage("volume", "volume 0 dB (bypassed)".to_string(), false);
+        stage("limiter", "limiter bypassed".to_string(), false);
+    } else {
+        stage(
+            "volume",
+            format!("volume {}", db(volume_db)),
+            (dsp.volume - 1.0).abs() >= f32::EPSILON,
+        );
+        stage("limiter", "hard limiter at ±0.99".to_string(), true);
+    }
+
+    let host = format!("{:?}", cpal::default_host().id());
+    let mode = if cfg!(windows) { " shared" } else { "" };
+    let remixed = state
+        .device_format
+        .as_ref()
+        .is_some_and(|f| u32::from(f.channels) != state.channels);
+    let mut conversion = String::new();
+    if state.resampled {
+        conversion.push_str(", resampled by the OS");
+    }
+    if remixed {
+        conversion.push_str(", channels remixed by the OS");
+    }
+    let format = match &state.device_format {
+        Some(f) => format!(
+            "{} {}-bit {} {}",
+            rate(f.sample_rate),
+            f.bits_per_sample,
+            f.sample_format,
+            channels(u32::from(f.channels))
+        ),
+        None => format!("{} 32-bit float", rate(state.sample_rate)),
+    };
+    stage(
+        "output",
+        format!("{}{} {}{}", host, mode, format, conversion),
+        state.resampled || remixed,
+    );
+    stage(
+        "device",
+        state
+            .output_device
+            .unwrap_or_else(|| "unknown device".to_string()),
+        false,
+    );
+
+    let description = stages
+        .iter()
+        .map(|s| s.summary.as_str())
+        .collect::<Vec<_>>()
+        .join(" → ");
+    let bit_perfect = !stages.iter().any(|s| s.processing);
+    Some(SignalPath {
+        stages,
+        description,
+        bit_perfect,
+    })
+}
+
+/// `44.1 kHz`, `48 kHz`.
+fn rate(hz: u32) -> String {
+    format!("{} kHz", f64::from(hz) / 1000.0)
+}
+
+fn channels(count: u32) -> String {
+    match count {
+        1 => "mono".to_string(),
+        2 => "stereo".to_string(),
+        n => format!("{} ch", n),
+    }
+}
+
+/// `+3.0 dB`, `-6.2 dB`, `0 dB`.
+fn db(value: f32) -> String {
+    if value.abs() < 0.05 {
+        "0 dB".to_string()
+    } else {
+        format!("{:+.1} dB", value)
+    }
+}
@@ -0,0 +1,410 @@
+//! Snapcast output: what's playing, streamed to a Snapcast server so rooms
+//! with a `snapclient` play it in step with each other.
+//!
+//! The server reads raw PCM from a source set up in its `snapserver.conf`:
+//! a TCP source the player connects to
+//! (`source = tcp://0.0.0.0:4953?name=Masukii&mode=server`), or the
+//! named pipe it reads by default (`pipe:///tmp/snapfifo`). The stream has
+//! to be in the server's sample format, 16-bit stereo at 48 kHz unless its
+//! `sampleformat` says otherwise, so tracks at other rates are resampled on
+//! the way and anything beyond stereo is cut to the front pair.
+//!
+//! Local playback carries on as before. The samples are taken after
+//! ReplayGain and before the volume control, like the recorder's; nothing
+//! is sent while paused, which the server fills with silence. The rooms are
+//! the server's buffer (a second by default) behind this machine; for this
+//! room to be in step too, play it through a local `snapclient` and turn
+//! the player down. While the server can't be reached the player tries
+//! again every few seconds.
+
+use parking_lot::Mutex;
+use rubato::{FftFixedInOut, Resampler};
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+use std::net::{TcpStream, ToSocketAddrs};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use super::engine::AudioEngine;
+use super::loudness_meter::TAP_SIZE;
+
+const SETTINGS_FILE: &str = "snapcast.json";
+
+pub const DEFAULT_PORT: u16 = 4953;
+pub const DEFAULT_PIPE: &str = "/tmp/snapfifo";
+pub const DEFAULT_SAMPLE_RATE: u32 = 48000;
+
+/// How often the tap is drained to the server.
+const POLL_INTERVAL: Duration = Duration::from_millis(20);
+const RETRY_INTERVAL: Duration = Duration::from_secs(3);
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(3);
+/// A server this far behind is given up on and reconnected to.
+const WRITE_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Frames the resampler takes at a time.
+const CHUNK_FRAMES: usize = 1024;
+
+#[derive(Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SnapcastTransport {
+    /// Connect to a `tcp://…?mode=server` source.
+    #[default]
+    Tcp,
+    /// Write to the server's named pipe, on the same machine.
+    Pipe,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SnapcastSettings {
+    pub enabled: bool,
+    pub transport: SnapcastTransport,
+    pub host: String,
+    pub port: u16,
+    pub pipe_path: String,
+    /// The server's `sampleformat` rate.
+    pub sample_rate: u32,
+}
+
+impl Default for SnapcastSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            transport: SnapcastTransport::Tcp,
+            host: "127.0.0.1".to_string(),
+            port: DEFAULT_PORT,
+            pipe_path: DEFAULT_PIPE.to_string(),
+            sample_rate: DEFAULT_SAMPLE_RATE,
+        }
+    }
+}
+
+#[derive(Clone, Serialize)]
+pub struct SnapcastStatus {
+    pub settings: SnapcastSettings,
+    /// Sending to the server right now.
+    pub connected: bool,
+    /// Why the last attempt to connect or send failed.
+    pub error: Option<String>,
+}
+
+#[derive(Default)]
+struct Link {
+    connected: bool,
+    error: Option<String>,
+}
+
+struct Running {
+    stop: Arc<AtomicBool>,
+    thread: JoinHandle<()>,
+}
+
+pub struct SnapcastOutput {
+    engine: Arc<AudioEngine>,
+    app_data_dir: PathBuf,
+    settings: Mutex<SnapcastSettings>,
+    running: Mutex<Option<Running>>,
+    link: Arc<Mutex<Link>>,
+}
+
+impl SnapcastOutput {
+    /// Load the settings. Call [`SnapcastOutput::start`] to begin streaming
+    /// if enabled.
+    pub fn load(app_data_dir: &Path, engine: Arc<AudioEngine>) -> Self {
+        let settings = std::fs::read_to_string(app_data_dir.join(SETTINGS_FILE))
+            .ok()
+            .and_then(|data| serde_json::from_str(&data).ok())
+            .unwrap_or_default();
+        Self {
+            engine,
+            app_data_dir: app_data_dir.to_path_buf(),
+            settings: Mutex::new(settings),
+            running: Mutex::new(None),
+            link: Arc::new(Mutex::new(Link::default())),
+        }
+    }
+
+    pub fn status(&self) -> SnapcastStatus {
+        let link = self.link.lock();
+        SnapcastStatus {
+            settings: self.settings.lock().clone(),
+            connected: link.connected,
+            error: link.error.clone(),
+        }
+    }
+
+    /// Save new settings and restart the stream with them.
+    pub fn configure(&self, settings: SnapcastSettings) -> Result<SnapcastStatus, String> {
+        if !(8000..=384_000).contains(&settings.sample_rate) {
+            return Err("Sample rate must be between 8000 and 384000 Hz".to_string());
+        }
+        match settings.transport {
+            SnapcastTransport::Tcp if settings.host.trim().is_empty() => {
+                return Err("Enter the Snapcast server's address".to_string());
+            }
+            SnapcastTransport::Tcp if settings.port == 0 => {
+                return Err("Port must be between 1 and 65535".to_string());
+            }
+            SnapcastTransport::Pipe if cfg!(not(unix)) => {
+                return Err("Snapcast pipes are only available on Linux and macOS".to_string());
+            }
+            SnapcastTransport::Pipe if settings.pipe_path.trim().is_empty() => {
+                return Err("Enter the path of the server's pipe".to_string());
+            }
+            _ => {}
+        }
+        self.save(&settings)?;
+        *self.settings.lock() = settings;
+        self.start();
+        Ok(self.status())
+    }
+
+    /// (Re)start streaming as the settings say: stop if running, and start
+    /// again when enabled.
+    pub fn start(&self) {
+        let mut running = self.running.lock();
+        if let Some(previous) = running.take() {
+            previous.stop.store(true, Ordering::Relaxed);
+            let _ = previous.thread.join();
+        }
+        *self.link.lock() = Link::default();
+
+        let settings = self.settings.lock().clone();
+        if !settings.enabled {
+            return;
+        }
+        let stop = Arc::new(AtomicBool::new(false));
+        let (engine, link, stopped) = (self.engine.clone(), self.link.clone(), stop.clone());
+        let thread = thread::Builder::new()
+            .name("snapcast".into())
+            .spawn(move || stream(&engine, &settings, &stopped, &link))
+            .expect("Failed to spawn Snapcast thread");
+        *running = Some(Running { stop, thread });
+    }
+
+    fn save(&self, settings: &SnapcastSettings) -> Result<(), String> {
+        std::fs::create_dir_all(&self.app_data_dir)
+            .map_err(|e| format!("Failed to create dir: {}", e))?;
+        let json = serde_json::to_string_pretty(settings)
+            .map_err(|e| format!("Serialize failed: {}", e))?;
+        std::fs::write(self.app_data_dir.join(SETTINGS_FILE), json)
+            .map_err(|e| format!("Write failed: {}", e))
+    }
+}
+
+fn stream(
+    engine: &AudioEngine,
+    settings: &SnapcastSettings,
+    stop: &AtomicBool,
+    link: &Mutex<Link>,
+) {
+    let tap = engine.snapcast_tap();
+    let mut scratch = vec![0.0f32; TAP_SIZE];
+    // What played before streaming started
+    while tap.read(&mut scratch) > 0 {}
+
+    let mut sink: Option<Box<dyn Write>> = None;
+    let mut retry_at = Instant::now();
+    let mut converter: Option<PcmConverter> = None;
+    while !stop.load(Ordering::Relaxed) {
+        thread::sleep(POLL_INTERVAL);
+        // Drained even while disconnected, so a reconnect starts from now
+        let n = tap.read(&mut scratch);
+
+        if sink.is_none() && Instant::now() >= retry_at {
+            match connect(settings) {
+                Ok(opened) => {
+                    log::info!("Streaming to Snapcast ({})", describe(settings));
+                    sink = Some(opened);
+                    *link.lock() = Link {
+                        connected: true,
+                        error: None,
+                    };
+                }
+                Err(e) => {
+                    retry_at = Instant::now() + RETRY_INTERVAL;
+                    let mut link = link.lock();
+                    if link.error.as_ref() != Some(&e) {
+                        log::warn!("{}", e);
+                    }
+                    link.error = Some(e);
+                }
+            }
+        }
+        let Some(out) = sink.as_mut() else {
+            continue;
+        };
+        if n == 0 {
+            continue;
+        }
+
+        let (rate, channels) = engine.output_format();
+        let channels = channels as usize;
+        if rate == 0 || channels == 0 {
+            continue;
+        }
+        if !converter.as_ref().is_some_and(|c| c.takes(rate, channels)) {
+            converter = match PcmConverter::new(rate, channels, settings.sample_rate) {
+                Ok(c) => Some(c),
+                Err(e) => {
+                    log::warn!("{}", e);
+                    None
+                }
+            };
+        }
+        let Some(converter) = converter.as_mut() else {
+            continue;
+        };
+        let bytes = converter.process(&scratch[..n]);
+        if let Err(e) = out.write_all(&bytes) {
+            let message = format!("Snapcast server stopped reading: {}", e);
+            log::warn!("{}", message);
+            sink = None;
+            retry_at = Instant::now() + RETRY_INTERVAL;
+            *link.lock() = Link {
+                connected: false,
+                error: Some(message),
+            };
+        }
+    }
+    link.lock().connected = false;
+}
+
+fn describe(settings: &SnapcastSettings) -> String {
+    match settings.transport {
+        SnapcastTransport::Tcp => format!("{}:{}", settings.host, settings.port),
+        SnapcastTransport::Pipe => settings.pipe_path.clone(),
+    }
+}
+
+fn connect(settings: &SnapcastSettings) -> Result<Box<dyn Write>, String> {
+    match settings.transport {
+        SnapcastTransport::Tcp => {
+            let target = describe(settings);
+            let address = (settings.host.trim(), settings.port)
+                .to_socket_addrs()
+                .map_err(|e| format!("Failed to resolve {}: {}", target, e))?
+                .next()
+                .ok_or_else(|| format!("Failed to resolve {}", target))?;
+            let stream = TcpStream::connect_timeout(&address, CONNECT_TIMEOUT)
+                .map_err(|e| format!("Failed to connect to Snapcast at {}: {}", target, e))?;
+            let _ = stream.set_nodelay(true);
+            stream
+                .set_write_timeout(Some(WRITE_TIMEOUT))
+                .map_err(|e| format!("Failed to connect to Snapcast at {}: {}", target, e))?;
+            Ok(Box::new(stream))
+        }
+        SnapcastTransport::Pipe => open_pipe(&settings.pipe_path),
+    }
+}
+
+#[cfg(unix)]
+fn open_pipe(path: &str) -> Result<Box<dyn Write>, String> {
+    use std::os::unix::fs::{FileTypeExt, OpenOptionsExt};
+    use std::os::unix::io::AsRawFd;
+
+    let is_pipe = std::fs::metadata(path).is_ok_and(|m| m.file_type().is_fifo());
+    if !is_pipe {
+        return Err(format!("{} isn't a pipe; is snapserver running?", path));
+    }
+    // Non-blocking, so a pipe nobody reads fails rather than hanging...
+    let file = std::fs::OpenOptions::new()
+        .write(true)
+        .custom_flags(libc::O_NONBLOCK)
+        .open(path)
+        .map_err(|e| format!("Failed to open {}: {}", path, e))?;
+    // ...then blocking, so writes wait for the server to catch up
+    let fd = file.as_raw_fd();
+    unsafe {
+        let flags = libc::fcntl(fd, libc::F_GETFL);
+        libc::fcntl(fd, libc::F_SETFL, flags & !libc::O_NONBLOCK);
+    }
+    Ok(Box::new(file))
+}
+
+#[cfg(not(unix))]
+fn open_pipe(_path: &str) -> Result<Box<dyn Write>, String> {
+    Err("Snapcast pipes are only available on Linux and macOS".to_string())
+}
+
+/// Played samples to the server's format: 16-bit little-endian stereo at
+/// its rate.
+struct PcmConverter {
+    rate: u32,
+    channels: usize,
+    /// None when the rates already match.
+    resampler: Option<FftFixedInOut<f32>>,
+    /// Left and right waiting to fill the resampler's next chunk.
+    pending: [Vec<f32>; 2],
+}
+
+impl PcmConverter {
+    fn new(rate: u32, channels: usize, output_rate: u32) -> Result<Self, String> {
+        let resampler = (rate != output_rate)
+            .then(|| FftFixedInOut::new(rate as usize, output_rate as usize, CHUNK_FRAMES, 2))
+            .transpose()
+            .map_err(|e| format!("Failed to create resampler: {}", e))?;
+        Ok(Self {
+            rate,
+            channels,
+            resampler,
+            pending: [Vec::new(), Vec::new()],
+        })
+    }
+
+    /// Whether this converts audio of `rate` and `channels`.
+    fn takes(&self, rate: u32, channels: usize) -> bool {
+        self.rate == rate && self.channels == channels
+    }
+
+    fn process(&mut self, samples: &[f32]) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        let Some(resampler) = self.resampler.as_mut() else {
+            for frame in samples.chunks_exact(self.channels) {
+                let (left, right) = stereo(frame);
+                push_frame(&mut bytes, left, right);
+            }
+            return bytes;
+        };
+
+        for frame in samples.chunks_exact(self.channels) {
+            let (left, right) = stereo(frame);
+            self.pending[0].push(left);
+            self.pending[1].push(right);
+        }
+        let [left, right] = &mut self.pending;
+        while left.len() >= resampler.input_frames_next() {
+            let take = resampler.input_frames_next();
+            let resampled = match resampler.process(&[&left[..take], &right[..take]], None) {
+                Ok(resampled) => resampled,
+                Err(e) => {
+                    log::warn!("Resampling for Snapcast failed: {}", e);
+                    left.clear();
+                    right.clear();
+                    break;
+                }
+            };
+            for (&l, &r) in resampled[0].iter().zip(&resampled[1]) {
+                push_frame(&mut bytes, l, r);
+            }
+            left.drain(..take);
+            right.drain(..take);
+        }
+        bytes
+    }
+}
+
+/// The front pair of a frame, or a mono frame on both sides.
+fn stereo(frame: &[f32]) -> (f32, f32) {
+    (frame[0], frame.get(1).copied().unwrap_or(frame[0]))
+}
+
+fn push_frame(bytes: &mut Vec<u8>, left: f32, right: f32) {
+    for s in [left, right] {
+        let s = (f64::from(s) * 32768.0).round().clamp(-32768.0, 32767.0) as i16;
+        bytes.extend_from_slice(&s.to_le_bytes());
+    }
+}
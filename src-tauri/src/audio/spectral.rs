@@ -0,0 +1,187 @@
+//! Spectral analysis for spotting lossy transcodes ("fake FLAC").
+//!
+//! MP3, AAC and Vorbis encoders low-pass the signal, typically somewhere
+//! between 16 and 20 kHz, and at lower bitrates drop whole high bands in
+//! some frames. Decoding and re-encoding to a lossless format keeps both
+//! marks: a steep cliff in the average spectrum well below Nyquist, and
+//! "holes", bands that go silent in one window while their neighbours
+//! don't. Each gives a score and the confidence combines them. It's a
+//! heuristic: genuinely band-limited recordings (old tapes, some
+//! electronic music) can score too.
+
+use super::decoder::{AudioDecoder, DecodeStatus};
+use realfft::RealFftPlanner;
+
+/// FFT window length in frames.
+const FFT_SIZE: usize = 4096;
+
+/// Highest frequency considered, so hi-res files are judged on the range a
+/// lossy encoder would have kept.
+const MAX_FREQ_HZ: f32 = 22_050.0;
+
+/// Band whose level stands for the music's level.
+const REFERENCE_BAND_HZ: (f32, f32) = (2_000.0, 8_000.0);
+
+/// Windows with a reference level below this (dBFS per bin) are skipped;
+/// silence says nothing about the source.
+const SILENCE_DB: f32 = -100.0;
+
+/// Level below the reference level that counts as no content.
+const CUTOFF_DROP_DB: f32 = 60.0;
+
+/// Cutoffs above this share of the considered range are normal roll-off.
+const NATURAL_CUTOFF_RATIO: f32 = 0.93;
+
+/// Width on each side of the cutoff over which its steepness is measured.
+const CLIFF_WIDTH_HZ: f32 = 1_000.0;
+
+/// Cliff steepness (dB) where the cutoff score starts, and where it's full.
+const CLIFF_RANGE_DB: (f32, f32) = (10.0, 40.0);
+
+/// Width of the bands checked for holes, and the lowest band start.
+const HOLE_BAND_HZ: f32 = 500.0;
+const HOLE_MIN_FREQ_HZ: f32 = 8_000.0;
+
+/// How far below both neighbours a band must be to count as a hole.
+const HOLE_DEPTH_DB: f32 = 30.0;
+
+/// Share of hole bands that gives a full hole score.
+const FULL_HOLE_RATIO: f32 = 0.05;
+
+/// Result of analysing one file.
+pub struct SpectralReport {
+    /// Highest frequency with content, in Hz.
+    pub cutoff_hz: u32,
+    /// Level drop across the cutoff, in dB.
+    pub cliff_db: f32,
+    /// Share of checked bands that were holes.
+    pub hole_ratio: f32,
+    /// Likelihood of a lossy source, 0.0–1.0.
+    pub confidence: f32,
+}
+
+/// Decode a file and analyse its spectrum.
+pub fn analyze_file(path: &str) -> Result<SpectralReport, String> {
+    let mut decoder = AudioDecoder::open(path)?;
+    let channels = decoder.channels().max(1);
+    let sample_rate = decoder.sample_rate() as f32;
+    let bin_hz = sample_rate / FFT_SIZE as f32;
+    let usable_hz = (sample_rate / 2.0).min(MAX_FREQ_HZ);
+    let bins = (usable_hz / bin_hz) as usize;
+    let band_bins = ((HOLE_BAND_HZ / bin_hz) as usize).max(1);
+
+    let fft = RealFftPlanner::<f32>::new().plan_fft_forward(FFT_SIZE);
+    let mut input = fft.make_input_vec();
+    let mut output = fft.make_output_vec();
+    let window: Vec<f32> = (0..FFT_SIZE)
+        .map(|i| 0.5 - 0.5 * (2.0 * std::f32::consts::PI * i as f32 / FFT_SIZE as f32).cos())
+        .collect();
+    // Power of a full-scale sine's bin is 1.0
+    let scale = 4.0 / window.iter().sum::<f32>().powi(2);
+    let reference =
+        (REFERENCE_BAND_HZ.0 / bin_hz) as usize..(REFERENCE_BAND_HZ.1 / bin_hz) as usize;
+    if reference.end > bins {
+        return Err("Sample rate too low to analyse".to_string());
+    }
+
+    let mut power = vec![0.0f64; bins];
+    let mut band_levels: Vec<Vec<f32>> = Vec::new();
+    let mut mono = Vec::with_capacity(FFT_SIZE);
+    loop {
+        let samples = match decoder.next_samples() {
+            Ok(s) => s,
+            Err(DecodeStatus::EndOfStream) => break,
+            Err(DecodeStatus::Error(e)) => return Err(format!("Decode failed: {}", e)),
+        };
+        for frame in samples.chunks_exact(channels) {
+            mono.push(frame.iter().sum::<f32>() / channels as f32);
+            if mono.len() < FFT_SIZE {
+                continue;
+            }
+            for ((x, s), w) in input.iter_mut().zip(&mono).zip(&window) {
+                *x = s * w;
+            }
+            mono.clear();
+            fft.process(&mut input, &mut output)
+                .map_err(|e| format!("Spectral analysis failed: {}", e))?;
+
+            let window_power: Vec<f32> = output[..bins]
+                .iter()
+                .map(|c| c.norm_sqr() * scale)
+                .collect();
+            if db(mean(&window_power[reference.clone()])) < SILENCE_DB {
+                continue;
+            }
+            for (total, p) in power.iter_mut().zip(&window_power) {
+                *total += f64::from(*p);
+            }
+            band_levels.push(
+                window_power
+                    .chunks_exact(band_bins)
+                    .map(|b| db(mean(b)))
+                    .collect(),
+            );
+        }
+    }
+    if band_levels.is_empty() {
+        return Err("Track is silent".to_string());
+    }
+
+    let windows = band_levels.len() as f64;
+    let spectrum: Vec<f32> = power.iter().map(|p| db((p / windows) as f32)).collect();
+    let reference_db = mean(&spectrum[reference]);
+    let cutoff = spectrum
+        .iter()
+        .rposition(|&level| level > reference_db - CUTOFF_DROP_DB)
+        .unwrap_or(0);
+    let cutoff_hz = cutoff as f32 * bin_hz;
+
+    let width = ((CLIFF_WIDTH_HZ / bin_hz) as usize).max(1);
+    let cliff_db = if cutoff + 1 < bins {
+        let below = &spectrum[cutoff.saturating_sub(width)..=cutoff];
+        let above = &spectrum[cutoff + 1..(cutoff + 1 + width).min(bins)];
+        mean(below) - mean(above)
+    } else {
+        0.0
+    };
+    let cutoff_score = if cutoff_hz < usable_hz * NATURAL_CUTOFF_RATIO {
+        ((cliff_db - CLIFF_RANGE_DB.0) / (CLIFF_RANGE_DB.1 - CLIFF_RANGE_DB.0)).clamp(0.0, 1.0)
+    } else {
+        0.0
+    };
+
+    // Only bands whose neighbours lie below the cutoff can be holes
+    let first_band = (HOLE_MIN_FREQ_HZ / (band_bins as f32 * bin_hz)) as usize;
+    let last_band = (cutoff / band_bins).saturating_sub(1);
+    let (mut holes, mut checked) = (0usize, 0usize);
+    for levels in &band_levels {
+        for band in first_band.max(1)..last_band.min(levels.len().saturating_sub(1)) {
+            checked += 1;
+            let neighbours = levels[band - 1].min(levels[band + 1]);
+            if levels[band] < neighbours - HOLE_DEPTH_DB {
+                holes += 1;
+            }
+        }
+    }
+    let hole_ratio = if checked > 0 {
+        holes as f32 / checked as f32
+    } else {
+        0.0
+    };
+    let hole_score = (hole_ratio / FULL_HOLE_RATIO).min(1.0);
+
+    Ok(SpectralReport {
+        cutoff_hz: cutoff_hz.round() as u32,
+        cliff_db,
+        hole_ratio,
+        confidence: 1.0 - (1.0 - cutoff_score) * (1.0 - hole_score),
+    })
+}
+
+fn mean(values: &[f32]) -> f32 {
+    values.iter().sum::<f32>() / values.len().max(1) as f32
+}
+
+fn db(power: f32) -> f32 {
+    10.0 * power.max(1e-20).log10()
+}
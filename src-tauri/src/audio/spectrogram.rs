@@ -0,0 +1,321 @@
+//! Full-file spectrogram images, for inspecting masters and checking by eye
+//! that a lossless file wasn't made from a lossy one ([`super::spectral`]
+//! is the automatic check).
+//!
+//! The channels are mixed to mono and analysed in Hann-windowed FFT windows
+//! at 50% overlap, or closer for files too short to fill the image. Each
+//! image column averages the power of the windows in its stretch of time,
+//! each row the bins in its frequency band. Levels from the floor up to
+//! 0 dBFS run from black through blue, red and yellow to white. Images are
+//! PNGs named after the file's path and mtime and the options, so a
+//! changed file or setting renders afresh.
+
+use image::{ImageFormat, Rgb, RgbImage};
+use realfft::RealFftPlanner;
+use serde::{Deserialize, Serialize};
+use std::io::Cursor;
+use std::ops::Range;
+use std::path::Path;
+use std::time::UNIX_EPOCH;
+
+use super::decoder::{AudioDecoder, DecodeStatus};
+use crate::library::artwork;
+
+/// Subfolder of the app data directory holding the images.
+pub const SPECTROGRAM_DIR: &str = "spectrograms";
+
+/// Bottom of a logarithmic frequency axis.
+const LOG_MIN_FREQ_HZ: f32 = 20.0;
+
+/// Colours from the floor (first) to 0 dBFS (last), evenly spaced.
+const PALETTE: &[[u8; 3]] = &[
+    [0, 0, 0],
+    [20, 0, 90],
+    [120, 0, 150],
+    [220, 30, 70],
+    [255, 150, 0],
+    [255, 235, 90],
+    [255, 255, 255],
+];
+
+#[derive(Clone, Deserialize)]
+#[serde(default)]
+pub struct SpectrogramOptions {
+    /// Image size in pixels: time runs left to right, frequency bottom to
+    /// top. 64–4096 wide, 64–2048 high.
+    pub width: u32,
+    pub height: u32,
+    /// FFT length in frames, a power of two from 256 to 32768. Longer
+    /// resolves frequency finer and time coarser.
+    pub fft_size: usize,
+    /// Level drawn black, in dBFS.
+    pub floor_db: f32,
+    /// Logarithmic frequency axis from 20 Hz, instead of linear from 0.
+    pub log_frequency: bool,
+}
+
+impl Default for SpectrogramOptions {
+    fn default() -> Self {
+        Self {
+            width: 1600,
+            height: 512,
+            fft_size: 4096,
+            floor_db: -120.0,
+            log_frequency: false,
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct Spectrogram {
+    pub image_path: String,
+    pub width: u32,
+    pub height: u32,
+    pub sample_rate: u32,
+    pub duration_secs: f64,
+    /// Frequencies at the bottom and top edges, in Hz.
+    pub min_freq_hz: f32,
+    pub max_freq_hz: f32,
+    pub floor_db: f32,
+    pub log_frequency: bool,
+}
+
+/// Render a file's spectrogram into `dir`, or return the existing image.
+pub fn render(path: &str, options: &SpectrogramOptions, dir: &Path) -> Result<Spectrogram, String> {
+    validate(options)?;
+    let mut decoder = AudioDecoder::open(path)?;
+    let sample_rate = decoder.sample_rate();
+    let max_freq_hz = sample_rate as f32 / 2.0;
+    let min_freq_hz = if options.log_frequency {
+        LOG_MIN_FREQ_HZ
+    } else {
+        0.0
+    };
+
+    let image_file = dir.join(image_name(path, options));
+    let mut spectrogram = Spectrogram {
+        image_path: image_file.to_string_lossy().to_string(),
+        width: options.width,
+        height: options.height,
+        sample_rate,
+        duration_secs: decoder.duration_secs,
+        min_freq_hz,
+        max_freq_hz,
+        floor_db: options.floor_db,
+        log_frequency: options.log_frequency,
+    };
+    if image_file.exists() {
+        return Ok(spectrogram);
+    }
+
+    let (image, frames) = compute(&mut decoder, options, min_freq_hz..max_freq_hz)?;
+    spectrogram.duration_secs = frames as f64 / f64::from(sample_rate);
+
+    let mut png = Vec::new();
+    image
+        .write_to(&mut Cursor::new(&mut png), ImageFormat::Png)
+        .map_err(|e| format!("Failed to encode spectrogram: {}", e))?;
+    // Written under a temporary name so an interrupted write never passes
+    // for a finished image
+    std::fs::create_dir_all(dir).map_err(|e| format!("Failed to create dir: {}", e))?;
+    let partial = image_file.with_extension("part");
+    std::fs::write(&partial, png).map_err(|e| format!("Write failed: {}", e))?;
+    std::fs::rename(&partial, &image_file).map_err(|e| format!("Write failed: {}", e))?;
+
+    Ok(spectrogram)
+}
+
+fn validate(options: &SpectrogramOptions) -> Result<(), String> {
+    if !(64..=4096).contains(&options.width) || !(64..=2048).contains(&options.height) {
+        return Err("Spectrogram size must be 64–4096 by 64–2048 pixels".to_string());
+    }
+    if !options.fft_size.is_power_of_two() || !(256..=32768).contains(&options.fft_size) {
+        return Err("FFT size must be a power of two from 256 to 32768".to_string());
+    }
+    if !(-200.0..0.0).contains(&options.floor_db) {
+        return Err("Floor must be between -200 and 0 dBFS".to_string());
+    }
+    Ok(())
+}
+
+/// Decode the whole file into an image. Also returns the frames decoded.
+fn compute(
+    decoder: &mut AudioDecoder,
+    options: &SpectrogramOptions,
+    freq_range: Range<f32>,
+) -> Result<(RgbImage, u64), String> {
+    let channels = decoder.channels().max(1);
+    let fft_size = options.fft_size;
+    let width = options.width as usize;
+    let bin_hz = decoder.sample_rate() as f32 / fft_size as f32;
+    let rows = row_bins(options, freq_range, bin_hz);
+
+    let expected_frames = (decoder.duration_secs * f64::from(decoder.sample_rate())) as usize;
+    let hop = if expected_frames > 0 {
+        (expected_frames / width).clamp(1, fft_size / 2)
+    } else {
+        fft_size / 2
+    };
+    // Columns are merged in pairs whenever there are twice as many as the
+    // image is wide, so memory stays bounded even when the length is unknown
+    let mut windows_per_column = if expected_frames > 0 {
+        (expected_frames / hop).div_ceil(width).max(1)
+    } else {
+        1
+    };
+
+    let fft = RealFftPlanner::<f32>::new().plan_fft_forward(fft_size);
+    let mut input = fft.make_input_vec();
+    let mut output = fft.make_output_vec();
+    let window: Vec<f32> = (0..fft_size)
+        .map(|i| 0.5 - 0.5 * (2.0 * std::f32::consts::PI * i as f32 / fft_size as f32).cos())
+        .collect();
+    // Power of a full-scale sine's bin is 1.0
+    let scale = 4.0 / window.iter().sum::<f32>().powi(2);
+
+    // Per column: summed row power and the number of windows summed
+    let mut columns: Vec<(Vec<f32>, usize)> = Vec::new();
+    let mut column = (vec![0.0f32; rows.len()], 0usize);
+    let mut power = vec![0.0f32; output.len()];
+    let mut mono = Vec::with_capacity(fft_size);
+    let mut frames = 0u64;
+    loop {
+        let samples = match decoder.next_samples() {
+            Ok(s) => s,
+            Err(DecodeStatus::EndOfStream) => break,
+            Err(DecodeStatus::Error(e)) => return Err(format!("Decode failed: {}", e)),
+        };
+        for frame in samples.chunks_exact(channels) {
+            frames += 1;
+            mono.push(frame.iter().sum::<f32>() / channels as f32);
+            if mono.len() < fft_size {
+                continue;
+            }
+            for ((x, s), w) in input.iter_mut().zip(&mono).zip(&window) {
+                *x = s * w;
+            }
+            mono.drain(..hop);
+            fft.process(&mut input, &mut output)
+                .map_err(|e| format!("Spectrogram failed: {}", e))?;
+
+            for (p, c) in power.iter_mut().zip(&output) {
+                *p = c.norm_sqr() * scale;
+            }
+            for (total, bins) in column.0.iter_mut().zip(&rows) {
+                *total += mean(&power[bins.clone()]);
+            }
+            column.1 += 1;
+            if column.1 < windows_per_column {
+                continue;
+            }
+            columns.push(std::mem::replace(&mut column, (vec![0.0; rows.len()], 0)));
+            if columns.len() >= 2 * width {
+                columns = merge_pairs(columns);
+                windows_per_column *= 2;
+            }
+        }
+    }
+    if column.1 > 0 {
+        columns.push(column);
+    }
+    if columns.is_empty() {
+        return Err("Track is too short for a spectrogram".to_string());
+    }
+
+    let height = options.height;
+    let mut image = RgbImage::new(options.width, height);
+    for x in 0..width {
+        // Several columns per pixel for long files, a repeated one for short
+        let start = x * columns.len() / width;
+        let end = ((x + 1) * columns.len() / width).max(start + 1);
+        let spanned = &columns[start..end];
+        let windows: usize = spanned.iter().map(|c| c.1).sum();
+        for row in 0..rows.len() {
+            let total: f32 = spanned.iter().map(|c| c.0[row]).sum();
+            let level = db(total / windows.max(1) as f32);
+            let y = height - 1 - row as u32;
+            image.put_pixel(x as u32, y, colour(level, options.floor_db));
+        }
+    }
+    Ok((image, frames))
+}
+
+/// FFT bins averaged into each row, bottom row first.
+fn row_bins(
+    options: &SpectrogramOptions,
+    freq_range: Range<f32>,
+    bin_hz: f32,
+) -> Vec<Range<usize>> {
+    let height = options.height as f32;
+    let bins = options.fft_size / 2 + 1;
+    let edge = |row: f32| {
+        let position = row / height;
+        if options.log_frequency {
+            freq_range.start * (freq_range.end / freq_range.start).powf(position)
+        } else {
+            freq_range.start + (freq_range.end - freq_range.start) * position
+        }
+    };
+    (0..options.height)
+        .map(|row| {
+            let lo = ((edge(row as f32) / bin_hz) as usize).min(bins - 1);
+            let hi = ((edge(row as f32 + 1.0) / bin_hz).ceil() as usize).clamp(lo + 1, bins);
+            lo..hi
+        })
+        .collect()
+}
+
+fn merge_pairs(columns: Vec<(Vec<f32>, usize)>) -> Vec<(Vec<f32>, usize)> {
+    columns
+        .chunks(2)
+        .map(|pair| {
+            let mut merged = pair[0].clone();
+            if let Some(next) = pair.get(1) {
+                for (total, p) in merged.0.iter_mut().zip(&next.0) {
+                    *total += p;
+                }
+                merged.1 += next.1;
+            }
+            merged
+        })
+        .collect()
+}
+
+/// Palette colour for a level between `floor_db` and 0 dBFS.
+fn colour(level_db: f32, floor_db: f32) -> Rgb<u8> {
+    let t = ((level_db - floor_db) / -floor_db).clamp(0.0, 1.0) * (PALETTE.len() - 1) as f32;
+    let i = (t as usize).min(PALETTE.len() - 2);
+    let f = t - i as f32;
+    let (a, b) = (PALETTE[i], PALETTE[i + 1]);
+    Rgb(std::array::from_fn(|c| {
+        (f32::from(a[c]) + (f32::from(b[c]) - f32::from(a[c])) * f).round() as u8
+    }))
+}
+
+/// File name from the source's path and mtime and every option that
+/// changes the picture.
+fn image_name(path: &str, options: &SpectrogramOptions) -> String {
+    let modified_at = std::fs::metadata(path)
+        .and_then(|m| m.modified())
+        .ok()
+        .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or(0);
+    format!(
+        "{}-{}x{}-{}-{}{}.png",
+        artwork::cache_key(path, modified_at),
+        options.width,
+        options.height,
+        options.fft_size,
+        -options.floor_db,
+        if options.log_frequency { "-log" } else { "" }
+    )
+}
+
+fn mean(values: &[f32]) -> f32 {
+    values.iter().sum::<f32>() / values.len().max(1) as f32
+}
+
+fn db(power: f32) -> f32 {
+    10.0 * power.max(1e-20).log10()
+}
@@ -0,0 +1,131 @@
+//! True-peak (inter-sample peak) analysis of the playing track.
+//!
+//! Sample peaks miss the overs a DAC's oversampling filter or a lossy encode
+//! reconstructs between samples; hot masters often peak above 0 dBTP with no
+//! sample at full scale. The file is decoded on demand and measured with
+//! `ebur128`'s 4x oversampling true-peak meter (ITU-R BS.1770). The result
+//! is also given after the ReplayGain gain currently applied, since
+//! clipping prevention works from sample peaks and can still let a boosted
+//! track's true peak through.
+
+use ebur128::{EbuR128, Mode};
+use serde::Serialize;
+
+use super::decoder::{AudioDecoder, DecodeStatus};
+use super::engine::AudioEngine;
+
+/// Levels are reported down to this; silence reads as this.
+const FLOOR_DB: f64 = -200.0;
+
+#[derive(Clone, Serialize)]
+pub struct TruePeakAnalysis {
+    pub file: String,
+    /// Highest true peak over all channels.
+    pub true_peak_dbtp: f64,
+    /// True peak per channel.
+    pub channel_true_peaks_dbtp: Vec<f64>,
+    pub sample_peak_dbfs: f64,
+    /// The true peak is above 0 dBTP.
+    pub over_zero: bool,
+    /// ReplayGain gain applied now, and the true peak after it; `None` when
+    /// ReplayGain is off or the file has no gain tag.
+    pub replaygain_gain_db: Option<f32>,
+    pub true_peak_after_gain_dbtp: Option<f64>,
+    /// Set when the peak, with or without the gain, goes over 0 dBTP.
+    pub warning: Option<String>,
+}
+
+/// Measure the true peak of what is playing (the cue-sheet segment, for
+/// cue tracks). Decodes the whole track, so run it off the UI thread.
+pub fn analyze_current(engine: &AudioEngine) -> Result<TruePeakAnalysis, String> {
+    let state = engine.get_state();
+    let file = state.current_file.ok_or("Nothing is playing")?;
+    let (true_peaks, sample_peak) = measure(&file, state.start_secs, state.end_secs)?;
+
+    let true_peak = true_peaks.iter().copied().fold(0.0, f64::max);
+    let true_peak_dbtp = to_db(true_peak);
+    let replaygain_gain_db = engine.replaygain_gain_db();
+    let true_peak_after_gain_dbtp = replaygain_gain_db.map(|g| true_peak_dbtp + f64::from(g));
+
+    let warning = if true_peak_dbtp > 0.0 {
+        Some(format!(
+            "True peak is {:+.1} dBTP: inter-sample overs will clip in the DAC or a lossy encode.",
+            true_peak_dbtp
+        ))
+    } else {
+        true_peak_after_gain_dbtp
+            .filter(|&after| after > 0.0)
+            .map(|after| {
+                format!(
+                    "With ReplayGain the true peak reaches {:+.1} dBTP and the limiter will cut it.",
+                    after
+                )
+            })
+    };
+
+    Ok(TruePeakAnalysis {
+        file,
+        true_peak_dbtp,
+        channel_true_peaks_dbtp: true_peaks.into_iter().map(to_db).collect(),
+        sample_peak_dbfs: to_db(sample_peak),
+        over_zero: true_peak_dbtp > 0.0,
+        replaygain_gain_db,
+        true_peak_after_gain_dbtp,
+        warning,
+    })
+}
+
+/// Per-channel true peaks and the overall sample peak (linear) of a file,
+/// from `start_secs` to `end_secs` (or the end).
+fn measure(path: &str, start_secs: f64, end_secs: Option<f64>) -> Result<(Vec<f64>, f64), String> {
+    let mut decoder = AudioDecoder::open(path)?;
+    let channels = decoder.channels();
+    let sample_rate = decoder.sample_rate();
+    let mut meter = EbuR128::new(
+        channels as u32,
+        sample_rate,
+        Mode::TRUE_PEAK | Mode::SAMPLE_PEAK,
+    )
+    .map_err(|e| format!("Failed to start true-peak analysis: {}", e))?;
+    if start_secs > 0.0 {
+        decoder.seek(start_secs)?;
+    }
+    let mut remaining =
+        end_secs.map(|end| ((end - start_secs).max(0.0) * f64::from(sample_rate)) as usize);
+
+    loop {
+        let mut samples = match decoder.next_samples() {
+            Ok(s) => s,
+            Err(DecodeStatus::EndOfStream) => break,
+            Err(DecodeStatus::Error(e)) => return Err(format!("Decode failed: {}", e)),
+        };
+        if let Some(left) = remaining.as_mut() {
+            let frames = (samples.len() / channels.max(1)).min(*left);
+            samples.truncate(frames * channels);
+            *left -= frames;
+        }
+        meter
+            .add_frames_f32(&samples)
+            .map_err(|e| format!("True-peak measurement failed: {}", e))?;
+        if remaining == Some(0) {
+            break;
+        }
+    }
+
+    let mut true_peaks = Vec::with_capacity(channels);
+    let mut sample_peak: f64 = 0.0;
+    for channel in 0..channels as u32 {
+        let failed = |e: ebur128::Error| format!("True-peak measurement failed: {}", e);
+        true_peaks.push(meter.true_peak(channel).map_err(failed)?);
+        sample_peak = sample_peak.max(meter.sample_peak(channel).map_err(failed)?);
+    }
+    Ok((true_peaks, sample_peak))
+}
+
+fn to_db(linear: f64) -> f64 {
+    if linear > 0.0 {
+        (20.0 * linear.log10()).max(FLOOR_DB)
+    } else {
+        FLOOR_DB
+    }
+}
@@ -0,0 +1,83 @@
+//! WAV (RIFF PCM) file writer for the recorder.
+//!
+//! Samples are written as 16- or 24-bit integers as they come, and the
+//! RIFF and data sizes, unknown until then, are filled in by
+//! [`WavWriter::finish`]. A file cut short keeps zero sizes, which most
+//! editors still open.
+
+use std::fs::File;
+use std::io::{BufWriter, Seek, SeekFrom, Write};
+use std::path::Path;
+
+/// Largest data chunk a RIFF header can describe.
+const MAX_DATA_BYTES: u64 = u32::MAX as u64 - 36;
+
+pub struct WavWriter {
+    out: BufWriter<File>,
+    bits: u8,
+    data_bytes: u64,
+}
+
+impl WavWriter {
+    pub fn create(path: &Path, sample_rate: u32, channels: u16, bits: u8) -> Result<Self, String> {
+        let file = File::create(path).map_err(|e| format!("Failed to create file: {}", e))?;
+        let mut out = BufWriter::new(file);
+        let block_align = channels * u16::from(bits / 8);
+        let mut header = Vec::with_capacity(44);
+        header.extend_from_slice(b"RIFF");
+        header.extend_from_slice(&0u32.to_le_bytes());
+        header.extend_from_slice(b"WAVEfmt ");
+        header.extend_from_slice(&16u32.to_le_bytes());
+        // PCM
+        header.extend_from_slice(&1u16.to_le_bytes());
+        header.extend_from_slice(&channels.to_le_bytes());
+        header.extend_from_slice(&sample_rate.to_le_bytes());
+        header.extend_from_slice(&(sample_rate * u32::from(block_align)).to_le_bytes());
+        header.extend_from_slice(&block_align.to_le_bytes());
+        header.extend_from_slice(&u16::from(bits).to_le_bytes());
+        header.extend_from_slice(b"data");
+        header.extend_from_slice(&0u32.to_le_bytes());
+        out.write_all(&header)
+            .map_err(|e| format!("Write failed: {}", e))?;
+        Ok(Self {
+            out,
+            bits,
+            data_bytes: 0,
+        })
+    }
+
+    /// Bytes of audio written so far.
+    pub fn data_bytes(&self) -> u64 {
+        self.data_bytes
+    }
+
+    /// Append interleaved samples.
+    pub fn write(&mut self, samples: &[i32]) -> Result<(), String> {
+        let width = usize::from(self.bits / 8);
+        if self.data_bytes + (samples.len() * width) as u64 > MAX_DATA_BYTES {
+            return Err("WAV files can't be larger than 4 GB".to_string());
+        }
+        let mut bytes = Vec::with_capacity(samples.len() * width);
+        for &s in samples {
+            bytes.extend_from_slice(&s.to_le_bytes()[..width]);
+        }
+        self.out
+            .write_all(&bytes)
+            .map_err(|e| format!("Write failed: {}", e))?;
+        self.data_bytes += bytes.len() as u64;
+        Ok(())
+    }
+
+    /// Fill in the sizes and close the file.
+    pub fn finish(mut self) -> Result<(), String> {
+        let data = self.data_bytes as u32;
+        let write_sizes = |out: &mut BufWriter<File>| -> std::io::Result<()> {
+            out.seek(SeekFrom::Start(4))?;
+            out.write_all(&(data + 36).to_le_bytes())?;
+            out.seek(SeekFrom::Start(40))?;
+            out.write_all(&data.to_le_bytes())?;
+            out.flush()
+        };
+        write_sizes(&mut self.out).map_err(|e| format!("Write failed: {}", e))
+    }
+}
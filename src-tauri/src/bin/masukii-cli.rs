@@ -0,0 +1,439 @@
+//! Headless companion to the app: plays files through the same engine and
+//! queue, and queries the library the app keeps, from a terminal or over
+//! SSH. No window, no tray; handy for testing and for Raspberry Pi
+//! endpoints.
+//!
+//! ```text
+//! masukii-cli play <file>...   play files in order, taking commands on stdin
+//! masukii-cli shell            the same, starting with an empty queue
+//! masukii-cli search <words>   library search
+//! masukii-cli albums           every album, with its id
+//! masukii-cli album <id>       an album's tracks
+//! masukii-cli stats            library totals
+//! masukii-cli devices          output devices
+//! ```
+//!
+//! While playing, each line read is a command (`help` lists them). When
+//! stdin closes the queue plays out and the program exits.
+
+use std::io::{BufRead, Write};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use masukii_lib::audio::engine::{self, AudioCommand, AudioEngine};
+use masukii_lib::library::database::{LibraryDb, LibraryTrack};
+use masukii_lib::library::query::Paging;
+use masukii_lib::library::search::DEFAULT_SEARCH_LIMIT;
+use masukii_lib::logging;
+use masukii_lib::paths::{self, OPEN_TIMEOUT};
+use masukii_lib::playlist::queue::{PlayQueue, PreviousAction, QueueEntry};
+
+const USAGE: &str = "\
+Usage: masukii-cli <command>
+
+  play <file>...   play files in order, taking commands on stdin
+  shell            the same, starting with an empty queue
+  search <words>   search the library
+  albums           list albums
+  album <id>       list an album's tracks
+  stats            library totals
+  devices          list output devices";
+
+const SHELL_HELP: &str = "\
+  pause, resume, toggle    pause or resume
+  next, prev, stop         move through the queue or stop
+  seek <secs>              seek within the track
+  volume <0-1>             set the volume
+  add <file>               add a file to the queue
+  add-album <id>           add a library album
+  add-track <id>           add a library track
+  queue                    show the queue
+  jump <n>                 play queue entry n
+  status                   show what is playing
+  search <words>           search the library
+  quit                     stop and exit";
+
+/// How often the engine is checked for the end of a track.
+const POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+fn main() {
+    logging::init(None);
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let (command, rest) = match args.split_first() {
+        Some((command, rest)) => (command.as_str(), rest),
+        None => {
+            eprintln!("{}", USAGE);
+            std::process::exit(2);
+        }
+    };
+    let result = match command {
+        "play" if !rest.is_empty() => play(rest),
+        "shell" => play(&[]),
+        "search" if !rest.is_empty() => {
+            open_library().and_then(|db| print_search(&db, &rest.join(" ")))
+        }
+        "albums" => open_library().and_then(|db| print_albums(&db)),
+        "album" if rest.len() == 1 => {
+            open_library().and_then(|db| print_album(&db, parse_id(&rest[0])?))
+        }
+        "stats" => open_library().and_then(|db| print_stats(&db)),
+        "devices" => {
+            for device in engine::get_output_devices() {
+                let default = if device.is_default { " (default)" } else { "" };
+                println!("{}{}", device.name, default);
+            }
+            Ok(())
+        }
+        "help" | "--help" | "-h" => {
+            println!("{}", USAGE);
+            Ok(())
+        }
+        _ => {
+            eprintln!("{}", USAGE);
+            std::process::exit(2);
+        }
+    };
+    if let Err(e) = result {
+        eprintln!("masukii-cli: {}", e);
+        std::process::exit(1);
+    }
+}
+
+/// The app's library database, in the same app data directory.
+fn open_library() -> Result<LibraryDb, String> {
+    let app_data_dir = dirs_next::data_dir()
+        .unwrap_or_else(|| PathBuf::from("."))
+        .join("masukii");
+    LibraryDb::open(&app_data_dir)
+}
+
+// ─── Playback ───
+
+/// Where the player is between polls.
+enum Playing {
+    Idle,
+    /// `Play` sent, waiting for the engine to start.
+    Starting(Instant),
+    Started,
+}
+
+struct Player {
+    engine: AudioEngine,
+    queue: PlayQueue,
+    playing: Playing,
+    library: Option<LibraryDb>,
+}
+
+fn play(files: &[String]) -> Result<(), String> {
+    let mut player = Player {
+        engine: AudioEngine::new(),
+        queue: PlayQueue::new(),
+        playing: Playing::Idle,
+        library: None,
+    };
+    if !files.is_empty() {
+        player.enqueue(files.iter().map(|f| absolute(f)).collect());
+        if let Some(entry) = player.queue.advance() {
+            player.start(&entry);
+        }
+    }
+
+    let commands = read_lines();
+    let mut stdin_open = true;
+    loop {
+        if stdin_open {
+            match commands.recv_timeout(POLL_INTERVAL) {
+                Ok(line) => {
+                    match player.run(line.trim()) {
+                        Ok(true) => {}
+                        Ok(false) => break,
+                        Err(e) => eprintln!("{}", e),
+                    }
+                    prompt();
+                    continue;
+                }
+                Err(RecvTimeoutError::Timeout) => {}
+                Err(RecvTimeoutError::Disconnected) => stdin_open = false,
+            }
+        } else {
+            thread::sleep(POLL_INTERVAL);
+        }
+        if !player.poll() && !stdin_open {
+            break;
+        }
+    }
+    player.engine.send_command(AudioCommand::Shutdown);
+    Ok(())
+}
+
+impl Player {
+    /// Follow the engine, moving on when a track ends or fails to start.
+    /// Returns false once there is nothing left to play.
+    fn poll(&mut self) -> bool {
+        let state = self.engine.get_state();
+        let ended = match self.playing {
+            Playing::Idle => return false,
+            Playing::Starting(since) => {
+                if state.is_playing || state.is_paused {
+                    self.playing = Playing::Started;
+                    false
+                } else {
+                    since.elapsed() > OPEN_TIMEOUT
+                }
+            }
+            Playing::Started => !state.is_playing && !state.is_paused,
+        };
+        if ended {
+            match self.queue.advance() {
+                Some(entry) => self.start(&entry),
+                None => {
+                    self.playing = Playing::Idle;
+                    println!("End of queue");
+                    return false;
+                }
+            }
+        }
+        true
+    }
+
+    /// Run one command line. Returns false to quit.
+    fn run(&mut self, line: &str) -> Result<bool, String> {
+        let (command, arg) = line.split_once(' ').unwrap_or((line, ""));
+        let arg = arg.trim();
+        match command {
+            "" => {}
+            "pause" => self.engine.send_command(AudioCommand::Pause),
+            "resume" | "play" => self.engine.send_command(AudioCommand::Resume),
+            "toggle" => {
+                let command = if self.engine.get_state().is_playing {
+                    AudioCommand::Pause
+                } else {
+                    AudioCommand::Resume
+                };
+                self.engine.send_command(command);
+            }
+            "next" => match self.queue.advance() {
+                Some(entry) => self.start(&entry),
+                None => println!("End of queue"),
+            },
+            "prev" | "previous" => match self.queue.previous(self.engine.get_position_ms()) {
+                PreviousAction::Restart => self.engine.send_command(AudioCommand::Seek(0.0)),
+                PreviousAction::Play(entry) => self.start(&entry),
+            },
+            "stop" => {
+                self.engine.send_command(AudioCommand::Stop);
+                self.playing = Playing::Idle;
+            }
+            "seek" => {
+                let secs = arg
+                    .parse()
+                    .map_err(|_| format!("Invalid position: {}", arg))?;
+                self.engine.send_command(AudioCommand::Seek(secs));
+            }
+            "volume" => {
+                let volume: f32 = arg
+                    .parse()
+                    .map_err(|_| format!("Invalid volume: {}", arg))?;
+                self.engine
+                    .send_command(AudioCommand::SetVolume(volume.clamp(0.0, 1.0)));
+            }
+            "add" => self.enqueue(vec![absolute(arg)]),
+            "add-album" => {
+                let tracks = self.library()?.album_tracks(parse_id(arg)?)?;
+                self.enqueue(tracks.into_iter().map(|t| t.path).collect());
+            }
+            "add-track" => {
+                let track = library_track(self.library()?, parse_id(arg)?)?;
+                self.enqueue(vec![track.path]);
+            }
+            "queue" => {
+                let snapshot = self.queue.snapshot();
+                for (i, entry) in snapshot.entries.iter().enumerate() {
+                    let marker = if snapshot.current_index == Some(i) {
+                        '>'
+                    } else {
+                        ' '
+                    };
+                    println!("{} {:>3}  {}", marker, i, entry_name(entry));
+                }
+            }
+            "jump" => {
+                let index = arg.parse().map_err(|_| format!("Invalid index: {}", arg))?;
+                let entry = self
+                    .queue
+                    .jump_to(index)
+                    .ok_or_else(|| format!("Queue index {} out of range", index))?;
+                self.start(&entry);
+            }
+            "status" => {
+                let state = self.engine.get_state();
+                match &state.current_file {
+                    Some(file) if state.is_playing || state.is_paused => println!(
+                        "{} {}  {} / {}  {} Hz{}",
+                        if state.is_paused { "Paused" } else { "Playing" },
+                        file,
+                        clock(state.position_secs),
+                        clock(state.duration_secs),
+                        state.sample_rate,
+                        state
+                            .bit_depth
+                            .map(|b| format!(", {} bit", b))
+                            .unwrap_or_default(),
+                    ),
+                    _ => println!("Stopped"),
+                }
+            }
+            "search" => print_search(self.library()?, arg)?,
+            "help" => println!("{}", SHELL_HELP),
+            "quit" | "exit" => return Ok(false),
+            _ => return Err(format!("Unknown command: {} (try help)", command)),
+        }
+        Ok(true)
+    }
+
+    fn start(&mut self, entry: &QueueEntry) {
+        println!("Playing {}", entry_name(entry));
+        self.engine.send_command(AudioCommand::Play {
+            path: entry.path.clone(),
+            start_secs: entry.start_secs,
+            end_secs: entry.end_secs,
+        });
+        self.playing = Playing::Starting(Instant::now());
+    }
+
+    fn enqueue(&mut self, paths: Vec<String>) {
+        let report = self.queue.enqueue(paths);
+        for error in &report.errors {
+            eprintln!("{}", error);
+        }
+        println!("Queued {}", report.added.len() + report.moved.len());
+    }
+
+    /// The library, opened on first use.
+    fn library(&mut self) -> Result<&LibraryDb, String> {
+        if self.library.is_none() {
+            self.library = Some(open_library()?);
+        }
+        Ok(self.library.as_ref().expect("opened above"))
+    }
+}
+
+/// Stdin's lines, read on their own thread. Disconnects at end of input.
+fn read_lines() -> Receiver<String> {
+    let (tx, rx) = mpsc::channel();
+    thread::spawn(move || {
+        prompt();
+        for line in std::io::stdin().lock().lines() {
+            let Ok(line) = line else {
+                break;
+            };
+            if tx.send(line).is_err() {
+                break;
+            }
+        }
+    });
+    rx
+}
+
+fn prompt() {
+    print!("> ");
+    let _ = std::io::stdout().flush();
+}
+
+// ─── Library ───
+
+fn print_search(db: &LibraryDb, query: &str) -> Result<(), String> {
+    for hit in db.search(query, DEFAULT_SEARCH_LIMIT, true)? {
+        print_track(&hit.track);
+    }
+    Ok(())
+}
+
+fn print_albums(db: &LibraryDb) -> Result<(), String> {
+    for album in db.albums(Paging::ALL)? {
+        println!(
+            "{:>6}  {} — {}{}",
+            album.id,
+            album.artist.as_deref().unwrap_or("Unknown Artist"),
+            album.title,
+            album.year.map(|y| format!(" ({})", y)).unwrap_or_default(),
+        );
+    }
+    Ok(())
+}
+
+fn print_album(db: &LibraryDb, id: i64) -> Result<(), String> {
+    for track in db.album_tracks(id)? {
+        print_track(&track);
+    }
+    Ok(())
+}
+
+fn print_stats(db: &LibraryDb) -> Result<(), String> {
+    let stats = db.stats()?;
+    println!("Tracks    {}", stats.track_count);
+    println!("Albums    {}", stats.album_count);
+    println!("Artists   {}", stats.artist_count);
+    println!("Genres    {}", stats.genre_count);
+    println!("Duration  {}", clock(stats.total_duration_secs));
+    println!("Size      {:.1} GB", stats.total_size_bytes as f64 / 1e9);
+    println!(
+        "Lossless  {} ({} hi-res)",
+        stats.lossless_track_count, stats.hires_track_count
+    );
+    Ok(())
+}
+
+fn print_track(track: &LibraryTrack) {
+    println!(
+        "{:>6}  {} — {}  [{}]  {}",
+        track.id,
+        track.artist.as_deref().unwrap_or("Unknown Artist"),
+        track.title.as_deref().unwrap_or("Untitled"),
+        clock(track.duration_secs),
+        track.path,
+    );
+}
+
+fn library_track(db: &LibraryDb, id: i64) -> Result<LibraryTrack, String> {
+    db.tracks_by_ids(&[id])?
+        .into_iter()
+        .next()
+        .ok_or_else(|| format!("Track {} not found", id))
+}
+
+// ─── Formatting ───
+
+fn entry_name(entry: &QueueEntry) -> String {
+    entry.title.clone().unwrap_or_else(|| {
+        Path::new(&entry.path)
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_else(|| entry.path.clone())
+    })
+}
+
+/// `h:mm:ss`, or `m:ss` under an hour.
+fn clock(secs: f64) -> String {
+    let secs = secs.max(0.0) as u64;
+    let (h, m, s) = (secs / 3600, secs / 60 % 60, secs % 60);
+    if h > 0 {
+        format!("{}:{:02}:{:02}", h, m, s)
+    } else {
+        format!("{}:{:02}", m, s)
+    }
+}
+
+fn parse_id(text: &str) -> Result<i64, String> {
+    text.parse().map_err(|_| format!("Invalid id: {}", text))
+}
+
+/// A path as given, made absolute so queue entries don't depend on where
+/// the CLI was started.
+fn absolute(path: &str) -> String {
+    std::path::absolute(path)
+        .map(|p| paths::plain_path(&p.to_string_lossy()))
+        .unwrap_or_else(|_| path.to_string())
+}
@@ -0,0 +1,158 @@
+//! How far ahead playback buffers, by the device's conditions.
+//!
+//! On mobile, battery saver and metered networks are checked every few
+//! seconds. Battery saver decodes further ahead and in bursts,
+//! so the CPU wakes less often. A metered network downloads only a little
+//! of a stream ahead, so skipping a track doesn't waste what was fetched.
+//! [`BufferMode`] in the settings overrides this. Desktops report neither
+//! condition, so they buffer as [`BufferMode::Standard`] unless told
+//! otherwise.
+
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+
+use crate::audio::engine::{AudioCommand, AudioEngine, BufferPolicy};
+
+/// How often battery saver and the network are checked.
+#[cfg(any(target_os = "android", target_os = "ios"))]
+const CONDITIONS_INTERVAL: std::time::Duration = std::time::Duration::from_secs(15);
+
+const STANDARD_AHEAD_MS: u32 = 1000;
+/// Battery saver: fill up this far, then sleep until down to the refill mark.
+const SAVER_AHEAD_MS: u32 = 4000;
+const SAVER_REFILL_MS: u32 = 1500;
+/// Enough of a stream to ride out a slow patch of network.
+const STANDARD_PREFETCH_BYTES: usize = 4 << 20;
+const METERED_PREFETCH_BYTES: usize = 256 << 10;
+
+#[derive(Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BufferMode {
+    /// Follow battery saver and the network.
+    #[default]
+    Auto,
+    /// Buffer as on mains power and an unmetered network.
+    Standard,
+    /// Buffer as with battery saver on a metered network.
+    Saver,
+}
+
+#[derive(Clone, Copy, PartialEq, Default, Serialize)]
+pub struct Conditions {
+    pub power_saving: bool,
+    pub metered: bool,
+}
+
+#[derive(Clone, Serialize)]
+pub struct BufferingStatus {
+    pub mode: BufferMode,
+    pub conditions: Conditions,
+    pub policy: BufferPolicy,
+}
+
+/// The policy for `conditions`, unless `mode` overrides them.
+pub fn policy(mode: BufferMode, conditions: Conditions) -> BufferPolicy {
+    let Conditions {
+        power_saving,
+        metered,
+    } = match mode {
+        BufferMode::Auto => conditions,
+        BufferMode::Standard => Conditions::default(),
+        BufferMode::Saver => Conditions {
+            power_saving: true,
+            metered: true,
+        },
+    };
+    let (ahead_ms, refill_ms) = if power_saving {
+        (SAVER_AHEAD_MS, SAVER_REFILL_MS)
+    } else {
+        (STANDARD_AHEAD_MS, STANDARD_AHEAD_MS)
+    };
+    BufferPolicy {
+        ahead_ms,
+        refill_ms,
+        prefetch_bytes: if metered {
+            METERED_PREFETCH_BYTES
+        } else {
+            STANDARD_PREFETCH_BYTES
+        },
+    }
+}
+
+/// The last known conditions and the policy the engine has.
+#[derive(Default)]
+pub struct Buffering {
+    conditions: Mutex<Conditions>,
+    applied: Mutex<Option<BufferPolicy>>,
+}
+
+impl Buffering {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn status(&self, mode: BufferMode) -> BufferingStatus {
+        let conditions = *self.conditions.lock();
+        BufferingStatus {
+            mode,
+            conditions,
+            policy: policy(mode, conditions),
+        }
+    }
+
+    /// Record new conditions. Returns whether they changed.
+    pub fn set_conditions(&self, conditions: Conditions) -> bool {
+        std::mem::replace(&mut *self.conditions.lock(), conditions) != conditions
+    }
+
+    /// Give the engine the policy for `mode`, if it doesn't have it already.
+    pub fn apply(&self, mode: BufferMode, engine: &AudioEngine) {
+        let policy = self.status(mode).policy;
+        let mut applied = self.applied.lock();
+        if *applied != Some(policy) {
+            log::info!(
+                "Buffering {} ms ahead (refill at {} ms), prefetching {} KiB",
+                policy.ahead_ms,
+                policy.refill_ms,
+                policy.prefetch_bytes >> 10
+            );
+            engine.send_command(AudioCommand::SetBufferPolicy(policy));
+            *applied = Some(policy);
+        }
+    }
+}
+
+/// Watch battery saver and the network on a thread and adapt the
+/// buffering to them.
+#[cfg(any(target_os = "android", target_os = "ios"))]
+pub fn spawn(app: &tauri::AppHandle) {
+    use tauri::{Emitter, Manager};
+    use tauri_plugin_media_session::MediaSessionExt;
+
+    use crate::commands::AppState;
+
+    let app = app.clone();
+    std::thread::Builder::new()
+        .name("buffering".into())
+        .spawn(move || {
+            let state = app.state::<AppState>();
+            loop {
+                match app.media_session().conditions() {
+                    Ok(c) => {
+                        let conditions = Conditions {
+                            power_saving: c.power_saving,
+                            metered: c.metered,
+                        };
+                        if state.buffering.set_conditions(conditions) {
+                            let mode = state.settings.get().playback.buffer_mode;
+                            state.buffering.apply(mode, &state.engine);
+                            let _ = app.emit("buffering://changed", state.buffering.status(mode));
+                        }
+                    }
+                    Err(e) => log::warn!("{}", e),
+                }
+                std::thread::sleep(CONDITIONS_INTERVAL);
+            }
+        })
+        .expect("Failed to spawn buffering thread");
+}
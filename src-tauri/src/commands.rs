@@ -1,25 +1,137 @@
+use crate::audio::bit_meter::BitActivity;
+use crate::audio::clipping::TrackClipping;
 use crate::audio::device_profiles::{DeviceProfile, DeviceProfileStore};
+use crate::audio::diagnostic_report;
+use crate::audio::diagnostics_history::DiagnosticsSample;
+use crate::audio::dropouts::DropoutLog;
 use crate::audio::engine::{
     AudioCommand, AudioDeviceInfo, AudioDiagnostics, AudioEngine, PlaybackState, ReplayGainMode,
 };
+use crate::audio::fingerprint;
+use crate::audio::latency::{self, LatencyMeasurement};
+use crate::audio::levels::AudioLevels;
 use crate::audio::null_test;
-use crate::metadata::reader;
+use crate::audio::recorder::{RecordOptions, Recorder, RecordingStatus};
+use crate::audio::replaygain::ReplayGainInfo;
+use crate::audio::signal_path::{self, SignalPath};
+use crate::audio::snapcast::{SnapcastOutput, SnapcastSettings, SnapcastStatus};
+use crate::audio::spectrogram::{self, Spectrogram, SpectrogramOptions};
+use crate::audio::true_peak::{self, TruePeakAnalysis};
+use crate::buffering::{BufferMode, Buffering, BufferingStatus};
+use crate::convert::{ConvertJob, ConvertOptions, Converter};
+use crate::dsp_plugins::{ChainEntry, DspPlugins, PluginList};
+use crate::errors::{self, AppError};
+use crate::http;
+use crate::library::accuraterip::{self, AccurateRipDisc};
+use crate::library::albums::{AlbumDetail, LibraryAlbum};
+use crate::library::artists::{ArtistDetail, LibraryArtist};
+use crate::library::artwork;
+use crate::library::backup::{LibraryBackup, PathMapping, RestoreReport};
+use crate::library::bookmarks::{self, Bookmark, BookmarkRules, BookmarkStore};
+use crate::library::cd_drive::{self, CdDriveInfo};
+use crate::library::classical::{ClassicalWork, LibraryComposer};
+use crate::library::database::{LibraryDb, LibraryRoot, LibraryTrack};
+use crate::library::duplicates::{DuplicateGroup, DEFAULT_DURATION_TOLERANCE_SECS};
+use crate::library::dynamic_range;
+use crate::library::encodings;
+use crate::library::exclude::ExcludeRules;
+use crate::library::folders::FolderListing;
+use crate::library::genres::{GenreDetail, LibraryGenre};
+use crate::library::integrity::{self, IntegrityProblem, IntegrityStatus};
+use crate::library::itunes;
+use crate::library::lists::{LibraryList, ListChunk, ListStreamError, DEFAULT_CHUNK_SIZE};
+use crate::library::metadata_cache;
+use crate::library::organize::{self, CollisionPolicy, PlannedMove};
+use crate::library::plays::{PlayPeriod, PlayedTrack};
+use crate::library::query::{Paging, TrackFilter, TrackPage, TrackSort, DEFAULT_QUERY_LIMIT};
+use crate::library::replaygain;
+use crate::library::ripper::{self, CdDisc, RipRequest};
+use crate::library::scanner::{self, JobProgress, JobSummary, ScanControl};
+use crate::library::search::{SearchHit, DEFAULT_SEARCH_LIMIT};
+use crate::library::stats::LibraryStats;
+use crate::library::transcodes::{self, DEFAULT_MIN_CONFIDENCE};
+use crate::library::waveform::{self, Waveform};
+use crate::listenbrainz::{ListenBrainz, ListenBrainzStatus};
+use crate::logging::{self, LogEntry, LogLevel};
+use crate::metadata::acoustid::{self, TagSuggestion};
+use crate::metadata::cover::{self, ArtResize, DEFAULT_MAX_ART_BYTES};
+use crate::metadata::coverart::{self, CoverArtQuery, CoverCandidate};
+use crate::metadata::encoding::{self, EncodingChoice, EncodingRepair};
+use crate::metadata::fields::{self, TagFields};
+use crate::metadata::lrclib::{self, LyricsQuery, OnlineLyrics};
+use crate::metadata::lyrics::LyricLine;
+use crate::metadata::reader::EmbeddedPicture;
+use crate::metadata::replaygain::write_replaygain;
+use crate::metadata::write_settings::{self, TagWriteSettings};
+use crate::metadata::writer::{self, MetadataFields};
+use crate::metadata::{cue, lyrics, rating, reader};
+use crate::mqtt::{MqttBridge, MqttSettings, MqttStatus};
+use crate::playlist::m3u;
+use crate::playlist::manager::{Playlist, PlaylistSortKey, PlaylistStore, PlaylistSummary};
+use crate::playlist::queue::{
+    DedupMode, EnqueueReport, HistoryEntry, PlayQueue, PreviousAction, QueueEntry, QueueSnapshot,
+    QueueStream, RepeatMode,
+};
+use crate::remote::{RemoteServer, RemoteServerStatus};
+use crate::scripting::{Scripts, ScriptsStatus};
+use crate::settings::{Settings, SettingsStore};
+use crate::sources::dlna::{self, DlnaBrowsePage, DlnaServer};
+use crate::sources::jellyfin::{
+    self, Jellyfin, JellyfinAlbum, JellyfinArtist, JellyfinLibrary, JellyfinPage,
+    JellyfinSearchResults, JellyfinServer, JellyfinTrack,
+};
+use crate::sources::radio::{Radio, RadioStation, RadioTitle};
+use crate::sources::subsonic::{
+    self, AlbumListType, Subsonic, SubsonicAlbum, SubsonicArtist, SubsonicSearchResults,
+    SubsonicServer, SubsonicSong,
+};
 use parking_lot::Mutex;
-use std::path::PathBuf;
+use serde::Serialize;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
-use tauri::State;
+use tauri::{AppHandle, Emitter, Manager, State};
 
 pub struct AppState {
     pub engine: Arc<AudioEngine>,
     pub device_profiles: Arc<Mutex<DeviceProfileStore>>,
+    pub queue: Arc<Mutex<PlayQueue>>,
+    pub playlists: Arc<Mutex<PlaylistStore>>,
+    pub bookmarks: Arc<Mutex<BookmarkStore>>,
+    pub library: Arc<Mutex<LibraryDb>>,
+    pub scan: Arc<ScanControl>,
+    pub waveforms: Arc<ScanControl>,
+    pub replaygain: Arc<ScanControl>,
+    pub dynamic_range: Arc<ScanControl>,
+    pub transcodes: Arc<ScanControl>,
+    pub integrity: Arc<ScanControl>,
+    pub organize: Arc<ScanControl>,
+    pub rip: Arc<ScanControl>,
+    pub scan_exclusions: Arc<Mutex<ExcludeRules>>,
+    pub listenbrainz: Arc<ListenBrainz>,
+    pub subsonic: Arc<Subsonic>,
+    pub jellyfin: Arc<Jellyfin>,
+    pub radio: Arc<Radio>,
+    pub recorder: Arc<Recorder>,
+    pub converter: Arc<Converter>,
+    pub snapcast: Arc<SnapcastOutput>,
+    pub mqtt: Arc<MqttBridge>,
+    pub scripts: Arc<Scripts>,
+    pub settings: Arc<SettingsStore>,
+    pub buffering: Arc<Buffering>,
+    pub dsp_plugins: Arc<DspPlugins>,
+    pub remote: Arc<RemoteServer>,
     pub app_data_dir: PathBuf,
 }
 
 // ─── Playback Commands ───
 
+/// Play a file, or the segment of a cue-sheet track path (`Album.cue#03`).
 #[tauri::command]
 pub fn play_file(path: String, state: State<'_, AppState>) -> Result<(), String> {
-    state.engine.send_command(AudioCommand::Play(path));
+    match cue::find_virtual_track(&path)? {
+        Some((_, track)) => start_playback(&state, track.file, track.start_secs, track.end_secs),
+        None => start_playback(&state, path, 0.0, None),
+    }
     Ok(())
 }
 
@@ -63,22 +175,473 @@ pub fn get_position(state: State<'_, AppState>) -> u64 {
     state.engine.get_position_ms()
 }
 
-// ─── ReplayGain Commands ───
+// ─── Queue Commands ───
+
+#[tauri::command]
+pub fn get_queue(state: State<'_, AppState>) -> QueueSnapshot {
+    state.queue.lock().snapshot()
+}
 
 #[tauri::command]
-pub fn set_replaygain_mode(mode: ReplayGainMode, state: State<'_, AppState>) -> Result<(), String> {
-    state.engine.send_command(AudioCommand::SetReplayGain(mode));
+pub fn add_to_queue(paths: Vec<String>, state: State<'_, AppState>) -> EnqueueReport {
+    state.queue.lock().enqueue(paths)
+}
+
+/// Queue streams from media servers by URL, with their titles.
+#[tauri::command]
+pub fn add_streams_to_queue(
+    streams: Vec<QueueStream>,
+    state: State<'_, AppState>,
+) -> EnqueueReport {
+    state.queue.lock().enqueue_streams(streams)
+}
+
+#[tauri::command]
+pub fn clear_queue(state: State<'_, AppState>) -> Result<(), String> {
+    state.queue.lock().clear();
     Ok(())
 }
 
 #[tauri::command]
-pub fn set_clipping_prevention(enabled: bool, state: State<'_, AppState>) -> Result<(), String> {
-    state
-        .engine
-        .send_command(AudioCommand::SetClippingPrevention(enabled));
+pub fn set_shuffle(enabled: bool, state: State<'_, AppState>) -> Result<(), String> {
+    state.queue.lock().set_shuffle(enabled);
     Ok(())
 }
 
+#[tauri::command]
+pub fn set_queue_dedup(mode: DedupMode, state: State<'_, AppState>) -> Result<(), String> {
+    state.queue.lock().set_dedup(mode);
+    Ok(())
+}
+
+#[tauri::command]
+pub fn set_repeat_mode(mode: RepeatMode, state: State<'_, AppState>) -> Result<(), String> {
+    state.queue.lock().set_repeat(mode);
+    Ok(())
+}
+
+/// Advance the queue and start playing the next entry.
+/// Returns `None` when the end of the queue is reached (playback is left alone).
+#[tauri::command]
+pub fn next_track(state: State<'_, AppState>) -> Option<QueueEntry> {
+    let entry = state.queue.lock().advance()?;
+    play_entry(&state, &entry);
+    Some(entry)
+}
+
+/// Start a specific queue entry. Goes through the same path as next/previous so
+/// the backend queue position and history stay in sync with what is playing.
+#[tauri::command]
+pub fn play_queue_index(index: usize, state: State<'_, AppState>) -> Result<QueueEntry, String> {
+    let entry = state
+        .queue
+        .lock()
+        .jump_to(index)
+        .ok_or_else(|| format!("Queue index {} out of range", index))?;
+    play_entry(&state, &entry);
+    Ok(entry)
+}
+
+/// Go back to the previously *played* track, or restart the current one when
+/// more than 3s in. Returns the entry now playing.
+#[tauri::command]
+pub fn previous_track(state: State<'_, AppState>) -> Option<QueueEntry> {
+    let position_ms = state.engine.get_position_ms();
+    let mut queue = state.queue.lock();
+    match queue.previous(position_ms) {
+        PreviousAction::Restart => {
+            state.engine.send_command(AudioCommand::Seek(0.0));
+            queue.current().cloned()
+        }
+        PreviousAction::Play(entry) => {
+            play_entry(&state, &entry);
+            Some(entry)
+        }
+    }
+}
+
+#[tauri::command]
+pub fn get_history(state: State<'_, AppState>) -> Vec<HistoryEntry> {
+    state.queue.lock().history()
+}
+
+/// Enqueue files handed to the app from outside — file-association launches and
+/// paths forwarded by a second instance. Relative paths are resolved against
+/// `cwd`. Starts playback of the first new entry when nothing is playing;
+/// otherwise the files are just appended. Emits `queue://external-enqueue`.
+pub fn open_external_paths(app: &AppHandle, paths: Vec<String>, cwd: &str) {
+    let files: Vec<String> = paths
+        .into_iter()
+        .map(|p| Path::new(cwd).join(p))
+        .filter(|p| p.is_file())
+        .map(|p| p.to_string_lossy().to_string())
+        .collect();
+    if files.is_empty() {
+        return;
+    }
+
+    let state = app.state::<AppState>();
+    let idle = {
+        let s = state.engine.get_state();
+        !s.is_playing && !s.is_paused
+    };
+
+    let report = {
+        let mut queue = state.queue.lock();
+        let report = queue.enqueue(files);
+        if idle {
+            let first = report.added.first().or(report.moved.first());
+            let index = first.and_then(|e| queue.index_of(e.id));
+            if let Some(entry) = index.and_then(|i| queue.jump_to(i)) {
+                play_entry(&state, &entry);
+            }
+        }
+        report
+    };
+
+    let _ = app.emit("queue://external-enqueue", &report);
+}
+
+/// Watch the engine on a thread and move on to the next queue entry, as
+/// [`next_track`] does, whenever a track plays to its end. Stopping clears
+/// the engine's current file, so only tracks that ran out advance. Emits
+/// `queue://advanced` with the entry now playing, or `null` at the end of
+/// the queue.
+pub fn spawn_queue_advance(app: &AppHandle) {
+    const INTERVAL: std::time::Duration = std::time::Duration::from_millis(100);
+
+    let app = app.clone();
+    std::thread::Builder::new()
+        .name("queue-advance".into())
+        .spawn(move || {
+            let mut was_playing = false;
+            loop {
+                std::thread::sleep(INTERVAL);
+                let state = app.state::<AppState>();
+                let s = state.engine.get_state();
+                let ended =
+                    was_playing && !s.is_playing && !s.is_paused && s.current_file.is_some();
+                was_playing = s.is_playing;
+                if ended {
+                    let entry = next_track(state);
+                    let _ = app.emit("queue://advanced", &entry);
+                }
+            }
+        })
+        .expect("Failed to spawn queue advance thread");
+}
+
+/// Start playback of a queue entry (honours cue-sheet segment offsets).
+fn play_entry(state: &AppState, entry: &QueueEntry) {
+    start_playback(state, entry.path.clone(), entry.start_secs, entry.end_secs);
+}
+
+/// Single entry point for starting playback: sends `Play` and resumes from a
+/// saved bookmark if the file, or the cue-sheet track, has one within it.
+fn start_playback(state: &AppState, path: String, start_secs: f64, end_secs: Option<f64>) {
+    let resume = bookmarks::bookmark_path(&state.library, &path, start_secs, end_secs)
+        .and_then(|bookmark| state.bookmarks.lock().resume_position(&bookmark))
+        .filter(|&position| end_secs.is_none_or(|end| position < end - start_secs));
+    state.engine.send_command(AudioCommand::Play {
+        path,
+        start_secs,
+        end_secs,
+    });
+    if let Some(position_secs) = resume {
+        state.engine.send_command(AudioCommand::Seek(position_secs));
+    }
+}
+
+// ─── Bookmark Commands ───
+
+#[tauri::command]
+pub fn set_bookmark(
+    path: String,
+    position_secs: f64,
+    state: State<'_, AppState>,
+) -> Result<(), String> {
+    let mut store = state.bookmarks.lock();
+    store.set(&path, position_secs);
+    store.save(&state.app_data_dir)
+}
+
+#[tauri::command]
+pub fn clear_bookmark(path: String, state: State<'_, AppState>) -> Result<(), String> {
+    let mut store = state.bookmarks.lock();
+    store.clear(&path);
+    store.save(&state.app_data_dir)
+}
+
+#[tauri::command]
+pub fn list_bookmarks(state: State<'_, AppState>) -> Vec<Bookmark> {
+    state.bookmarks.lock().list()
+}
+
+#[tauri::command]
+pub fn get_bookmark_rules(state: State<'_, AppState>) -> BookmarkRules {
+    state.bookmarks.lock().rules()
+}
+
+/// Configure which folders/genres get automatic resume bookmarks.
+#[tauri::command]
+pub fn set_bookmark_rules(rules: BookmarkRules, state: State<'_, AppState>) -> Result<(), String> {
+    let mut store = state.bookmarks.lock();
+    store.set_rules(rules);
+    store.save(&state.app_data_dir)
+}
+
+// ─── Playlist Commands ───
+
+#[tauri::command]
+pub fn list_playlists(state: State<'_, AppState>) -> Vec<PlaylistSummary> {
+    state.playlists.lock().list()
+}
+
+#[tauri::command]
+pub fn get_playlist(id: u64, state: State<'_, AppState>) -> Result<Playlist, String> {
+    state.playlists.lock().get(id)
+}
+
+#[tauri::command]
+pub fn create_playlist(name: String, state: State<'_, AppState>) -> Result<Playlist, String> {
+    let mut store = state.playlists.lock();
+    let playlist = store.create(name);
+    store.save(&state.app_data_dir)?;
+    Ok(playlist)
+}
+
+#[tauri::command]
+pub fn rename_playlist(id: u64, name: String, state: State<'_, AppState>) -> Result<(), String> {
+    let mut store = state.playlists.lock();
+    store.rename(id, name)?;
+    store.save(&state.app_data_dir)
+}
+
+#[tauri::command]
+pub fn delete_playlist(id: u64, state: State<'_, AppState>) -> Result<(), String> {
+    let mut store = state.playlists.lock();
+    store.delete(id);
+    store.save(&state.app_data_dir)
+}
+
+#[tauri::command]
+pub fn add_to_playlist(
+    id: u64,
+    paths: Vec<String>,
+    state: State<'_, AppState>,
+) -> Result<(), String> {
+    let mut store = state.playlists.lock();
+    store.add_tracks(id, paths)?;
+    store.save(&state.app_data_dir)
+}
+
+#[tauri::command]
+pub fn remove_from_playlist(
+    id: u64,
+    indices: Vec<usize>,
+    state: State<'_, AppState>,
+) -> Result<(), String> {
+    let mut store = state.playlists.lock();
+    store.remove_tracks(id, indices)?;
+    store.save(&state.app_data_dir)
+}
+
+/// Manually move a track. Switches the playlist to manual order.
+#[tauri::command]
+pub fn move_playlist_track(
+    id: u64,
+    from: usize,
+    to: usize,
+    state: State<'_, AppState>,
+) -> Result<(), String> {
+    let mut store = state.playlists.lock();
+    store.move_track(id, from, to)?;
+    store.save(&state.app_data_dir)
+}
+
+/// Re-sort a playlist and remember the key for future additions.
+#[tauri::command]
+pub fn sort_playlist(
+    id: u64,
+    key: PlaylistSortKey,
+    descending: bool,
+    state: State<'_, AppState>,
+) -> Result<Playlist, String> {
+    let mut store = state.playlists.lock();
+    store.sort(id, key, descending)?;
+    store.save(&state.app_data_dir)?;
+    store.get(id)
+}
+
+/// Keep the current order as a fixed manual order.
+#[tauri::command]
+pub fn lock_playlist_order(id: u64, state: State<'_, AppState>) -> Result<(), String> {
+    let mut store = state.playlists.lock();
+    store.lock_order(id)?;
+    store.save(&state.app_data_dir)
+}
+
+/// Save an M3U / M3U8 file (as exported by foobar2000, MusicBee and most
+/// players) as a playlist named after the file.
+#[tauri::command]
+pub fn import_playlist_file(path: String, state: State<'_, AppState>) -> Result<Playlist, String> {
+    if !m3u::is_m3u_file(&path) {
+        return Err(format!("Not an M3U playlist: {}", path));
+    }
+    let tracks: Vec<String> = m3u::read_m3u(&path)?
+        .into_iter()
+        .filter(|t| !m3u::is_m3u_file(t))
+        .collect();
+    let name = Path::new(&path)
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_else(|| "Imported".to_string());
+
+    let mut store = state.playlists.lock();
+    let id = store.create(name).id;
+    store.add_tracks(id, tracks)?;
+    store.save(&state.app_data_dir)?;
+    store.get(id)
+}
+
+// ─── ReplayGain Commands ───
+
+#[tauri::command]
+pub fn set_replaygain_mode(
+    mode: ReplayGainMode,
+    app: AppHandle,
+    state: State<'_, AppState>,
+) -> Result<(), String> {
+    update_settings(&app, &state, |s| s.playback.replaygain_mode = mode).map(|_| ())
+}
+
+#[tauri::command]
+pub fn set_clipping_prevention(
+    enabled: bool,
+    app: AppHandle,
+    state: State<'_, AppState>,
+) -> Result<(), String> {
+    update_settings(&app, &state, |s| s.playback.clipping_prevention = enabled).map(|_| ())
+}
+
+// ─── Settings ───
+
+#[tauri::command]
+pub fn get_settings(state: State<'_, AppState>) -> Settings {
+    state.settings.get()
+}
+
+/// Replace the settings. Playback settings take effect immediately; the
+/// new settings are emitted as `settings://changed`.
+#[tauri::command]
+pub fn set_settings(
+    settings: Settings,
+    app: AppHandle,
+    state: State<'_, AppState>,
+) -> Result<Settings, String> {
+    update_settings(&app, &state, |s| *s = settings)
+}
+
+// ─── Errors ───
+
+/// Errors reported since startup (the last few), oldest first; the ones
+/// after come as `app://error`.
+#[tauri::command]
+pub fn get_recent_errors() -> Vec<AppError> {
+    errors::recent()
+}
+
+// ─── Logs ───
+
+/// The last `count` records at `level` or above, oldest first.
+#[tauri::command]
+pub fn get_recent_logs(level: LogLevel, count: usize) -> Vec<LogEntry> {
+    logging::recent_at(level, count)
+}
+
+/// Set the lowest level written to the log file and kept for reports.
+#[tauri::command]
+pub fn set_log_level(
+    level: LogLevel,
+    app: AppHandle,
+    state: State<'_, AppState>,
+) -> Result<(), String> {
+    update_settings(&app, &state, |s| s.logging.level = level).map(|_| ())
+}
+
+/// Folder with the log files, to attach to bug reports.
+#[tauri::command]
+pub fn get_log_dir() -> Option<String> {
+    logging::log_dir().map(|d| d.to_string_lossy().to_string())
+}
+
+// ─── Buffering ───
+
+/// The buffer mode, the device's conditions and the policy they give.
+#[tauri::command]
+pub fn get_buffering(state: State<'_, AppState>) -> BufferingStatus {
+    let mode = state.settings.get().playback.buffer_mode;
+    state.buffering.status(mode)
+}
+
+/// Follow battery saver and the network (`auto`) or buffer one way always.
+#[tauri::command]
+pub fn set_buffer_mode(
+    mode: BufferMode,
+    app: AppHandle,
+    state: State<'_, AppState>,
+) -> Result<BufferingStatus, String> {
+    update_settings(&app, &state, |s| s.playback.buffer_mode = mode)?;
+    let status = state.buffering.status(mode);
+    let _ = app.emit("buffering://changed", &status);
+    Ok(status)
+}
+
+// ─── DSP Plugins ───
+
+/// Plugins in the plugins folder with their parameters, and the libraries
+/// that failed to load.
+#[tauri::command]
+pub fn get_dsp_plugins(state: State<'_, AppState>) -> PluginList {
+    state.dsp_plugins.list()
+}
+
+/// Load the plugins folder again and rebuild the chain from it.
+#[tauri::command]
+pub fn rescan_dsp_plugins(state: State<'_, AppState>) -> PluginList {
+    let list = state.dsp_plugins.rescan();
+    let chain = state.settings.get().playback.dsp_chain;
+    state.dsp_plugins.apply(&chain, &state.engine);
+    list
+}
+
+/// Replace the DSP chain; it takes effect immediately.
+#[tauri::command]
+pub fn set_dsp_chain(
+    chain: Vec<ChainEntry>,
+    app: AppHandle,
+    state: State<'_, AppState>,
+) -> Result<Settings, String> {
+    update_settings(&app, &state, |s| s.playback.dsp_chain = chain)
+}
+
+fn update_settings(
+    app: &AppHandle,
+    state: &AppState,
+    change: impl FnOnce(&mut Settings),
+) -> Result<Settings, String> {
+    let settings = state.settings.update(change)?;
+    settings.apply(&state.engine);
+    state
+        .buffering
+        .apply(settings.playback.buffer_mode, &state.engine);
+    state
+        .dsp_plugins
+        .apply(&settings.playback.dsp_chain, &state.engine);
+    let _ = app.emit("settings://changed", &settings);
+    Ok(settings)
+}
+
 // ─── Audio Diagnostics (Latency Analyzer) ───
 
 #[tauri::command]
@@ -86,6 +649,91 @@ pub fn get_audio_diagnostics(state: State<'_, AppState>) -> AudioDiagnostics {
     state.engine.get_diagnostics()
 }
 
+/// Each stage the current file passes through to the device, from the
+/// engine's live state; `None` when nothing is loaded.
+#[tauri::command]
+pub fn get_signal_path(state: State<'_, AppState>) -> Option<SignalPath> {
+    signal_path::describe(&state.engine)
+}
+
+/// Bits in use in the output samples per channel, since the previous call
+/// and since the track started. Poll it like [`get_levels`].
+#[tauri::command]
+pub fn get_bit_activity(state: State<'_, AppState>) -> BitActivity {
+    state.engine.get_bit_activity()
+}
+
+/// Buffer fill, latency, callback load and dropouts once a second over the
+/// last five minutes of playback, oldest first.
+#[tauri::command]
+pub fn get_diagnostics_history(state: State<'_, AppState>) -> Vec<DiagnosticsSample> {
+    state.engine.get_diagnostics_history()
+}
+
+/// Per-channel peak, RMS and peak-hold levels (dBFS) of the output, with
+/// meter ballistics applied since the previous call. Poll it at display rate.
+#[tauri::command]
+pub fn get_levels(state: State<'_, AppState>) -> AudioLevels {
+    state.engine.get_levels()
+}
+
+/// Play test sweeps and time their return through `input_device` (default
+/// input when omitted) — a loopback cable or a microphone. Stops playback.
+#[tauri::command]
+pub async fn measure_round_trip_latency(
+    input_device: Option<String>,
+    state: State<'_, AppState>,
+) -> Result<LatencyMeasurement, String> {
+    let engine = state.engine.clone();
+    tauri::async_runtime::spawn_blocking(move || {
+        latency::measure_round_trip(&engine, input_device.as_deref())
+    })
+    .await
+    .map_err(|e| format!("Latency measurement failed: {}", e))?
+}
+
+/// Write a JSON diagnostic report (devices, settings, playback state,
+/// diagnostics, dropout log and recent log records) to `path`, to attach to
+/// bug reports.
+#[tauri::command]
+pub async fn export_diagnostics_report(
+    path: String,
+    state: State<'_, AppState>,
+) -> Result<(), String> {
+    let engine = state.engine.clone();
+    let device_profiles = state.device_profiles.lock().clone();
+    tauri::async_runtime::spawn_blocking(move || {
+        let report = diagnostic_report::build(&engine, device_profiles);
+        diagnostic_report::export(&report, Path::new(&path))
+    })
+    .await
+    .map_err(|e| format!("Report export failed: {}", e))?
+}
+
+/// True peak (dBTP) of the playing track, before and after the current
+/// ReplayGain gain, with a warning when it goes over 0 dBTP.
+#[tauri::command]
+pub async fn analyze_true_peak(state: State<'_, AppState>) -> Result<TruePeakAnalysis, String> {
+    let engine = state.engine.clone();
+    tauri::async_runtime::spawn_blocking(move || true_peak::analyze_current(&engine))
+        .await
+        .map_err(|e| format!("True-peak analysis failed: {}", e))?
+}
+
+/// Samples at or over full scale and samples the limiter cut, for each track
+/// played this session (up to the last 200), ending with the current one.
+#[tauri::command]
+pub fn get_clipping_report(state: State<'_, AppState>) -> Vec<TrackClipping> {
+    state.engine.get_clipping_report()
+}
+
+/// Every buffer underrun this session (up to the last 500), with the file,
+/// position and buffer fill, plus the last minute of buffer fill history.
+#[tauri::command]
+pub fn get_dropout_log(state: State<'_, AppState>) -> DropoutLog {
+    state.engine.get_dropout_log()
+}
+
 // ─── Bit-Perfect Null Test ───
 
 #[tauri::command]
@@ -93,58 +741,1817 @@ pub fn run_null_test(path: String) -> Result<null_test::NullTestResult, String>
     null_test::run_null_test(&path)
 }
 
-// ─── Device Commands ───
+/// Null a file run through the current ReplayGain, volume and limiter
+/// settings against the plain decode, reporting the deviation in dBFS.
+#[tauri::command]
+pub async fn run_dsp_null_test(
+    path: String,
+    state: State<'_, AppState>,
+) -> Result<null_test::NullTestResult, String> {
+    let engine = state.engine.clone();
+    tauri::async_runtime::spawn_blocking(move || null_test::run_dsp_null_test(&engine, &path))
+        .await
+        .map_err(|e| format!("DSP null test failed: {}", e))?
+}
 
+/// Play the first `seconds` (default 10) of a file and null what reaches the
+/// output device, recorded with WASAPI loopback, against the decoded file.
+/// Stops whatever is playing.
 #[tauri::command]
-pub fn get_audio_devices() -> Vec<AudioDeviceInfo> {
-    crate::audio::engine::get_output_devices()
+pub async fn run_loopback_null_test(
+    path: String,
+    seconds: Option<f64>,
+    state: State<'_, AppState>,
+) -> Result<null_test::NullTestResult, String> {
+    let engine = state.engine.clone();
+    let seconds = seconds.unwrap_or(null_test::LOOPBACK_DEFAULT_SECS);
+    tauri::async_runtime::spawn_blocking(move || {
+        null_test::run_loopback_null_test(&engine, &path, seconds)
+    })
+    .await
+    .map_err(|e| format!("Loopback null test failed: {}", e))?
 }
 
-// ─── Per-Device Audio Profiles ───
+// ─── Spectrogram ───
 
+/// Render a file's spectrogram to a PNG in the app data directory. An image
+/// already rendered for the same file and options is returned as is.
 #[tauri::command]
-pub fn get_device_profile(
-    device_name: String,
+pub async fn generate_spectrogram(
+    path: String,
+    options: SpectrogramOptions,
     state: State<'_, AppState>,
-) -> DeviceProfile {
-    state.device_profiles.lock().get(&device_name)
+) -> Result<Spectrogram, String> {
+    let dir = state.app_data_dir.join(spectrogram::SPECTROGRAM_DIR);
+    tauri::async_runtime::spawn_blocking(move || spectrogram::render(&path, &options, &dir))
+        .await
+        .map_err(|e| format!("Spectrogram failed: {}", e))?
 }
 
+// ─── Recording ───
+
 #[tauri::command]
-pub fn save_device_profile(
-    profile: DeviceProfile,
+pub fn get_recording_status(state: State<'_, AppState>) -> RecordingStatus {
+    state.recorder.status()
+}
+
+/// Record what's playing to WAV or FLAC files. `recorder://saved` is
+/// emitted with each file finished.
+#[tauri::command]
+pub fn start_recording(
+    options: RecordOptions,
+    app: AppHandle,
     state: State<'_, AppState>,
-) -> Result<(), String> {
-    let mut store = state.device_profiles.lock();
-    store.set(profile);
-    store.save(&state.app_data_dir)
+) -> Result<RecordingStatus, String> {
+    state.recorder.start(options, move |saved| {
+        let _ = app.emit("recorder://saved", saved);
+    })?;
+    Ok(state.recorder.status())
 }
 
+/// Stop recording, finishing the file being written.
 #[tauri::command]
-pub fn list_device_profiles(state: State<'_, AppState>) -> Vec<DeviceProfile> {
-    state.device_profiles.lock().list()
+pub fn stop_recording(state: State<'_, AppState>) -> RecordingStatus {
+    state.recorder.stop()
 }
 
+// ─── Converter ───
+
+/// Queue library tracks for converting, e.g. to Opus for a phone. Each job's
+/// changes are emitted as `convert://job`.
 #[tauri::command]
-pub fn delete_device_profile(
-    device_name: String,
+pub fn convert_tracks(
+    track_ids: Vec<i64>,
+    options: ConvertOptions,
     state: State<'_, AppState>,
-) -> Result<(), String> {
-    let mut store = state.device_profiles.lock();
-    store.delete(&device_name);
-    store.save(&state.app_data_dir)
+) -> Result<Vec<ConvertJob>, String> {
+    // No ids would mean the whole library to `tracks_by_ids`
+    if track_ids.is_empty() {
+        return Ok(Vec::new());
+    }
+    let tracks = state.library.lock().tracks_by_ids(&track_ids)?;
+    state.converter.enqueue(tracks, options)
 }
 
-// ─── Metadata Commands ───
+#[tauri::command]
+pub fn get_convert_jobs(state: State<'_, AppState>) -> Vec<ConvertJob> {
+    state.converter.jobs()
+}
 
+/// Cancel a queued or running job; false if it had already finished.
 #[tauri::command]
-pub fn read_file_metadata(path: String) -> Result<reader::TrackMetadata, String> {
-    reader::read_metadata(&path)
+pub fn cancel_convert_job(id: u64, state: State<'_, AppState>) -> bool {
+    state.converter.cancel(id)
 }
 
+/// Forget jobs that are no longer queued or running.
 #[tauri::command]
-pub fn get_album_art_base64(path: String) -> Result<Option<String>, String> {
-    reader::get_album_art_base64(&path)
+pub fn clear_finished_convert_jobs(state: State<'_, AppState>) -> Vec<ConvertJob> {
+    state.converter.clear_finished();
+    state.converter.jobs()
+}
+
+// ─── Snapcast ───
+
+#[tauri::command]
+pub fn get_snapcast_status(state: State<'_, AppState>) -> SnapcastStatus {
+    state.snapcast.status()
+}
+
+/// Stream what's playing to a Snapcast server for multi-room playback, or
+/// stop, keeping local playback going either way.
+#[tauri::command]
+pub fn set_snapcast_output(
+    settings: SnapcastSettings,
+    state: State<'_, AppState>,
+) -> Result<SnapcastStatus, String> {
+    state.snapcast.configure(settings)
+}
+
+// ─── Device Commands ───
+
+#[tauri::command]
+pub fn get_audio_devices() -> Vec<AudioDeviceInfo> {
+    crate::audio::engine::get_output_devices()
+}
+
+/// Recording devices, for picking the latency measurement's input.
+#[tauri::command]
+pub fn get_input_devices() -> Vec<AudioDeviceInfo> {
+    crate::audio::engine::get_input_devices()
+}
+
+// ─── Per-Device Audio Profiles ───
+
+#[tauri::command]
+pub fn get_device_profile(
+    device_name: String,
+    state: State<'_, AppState>,
+) -> DeviceProfile {
+    state.device_profiles.lock().get(&device_name)
+}
+
+#[tauri::command]
+pub fn save_device_profile(
+    profile: DeviceProfile,
+    state: State<'_, AppState>,
+) -> Result<(), String> {
+    let mut store = state.device_profiles.lock();
+    store.set(profile);
+    store.save(&state.app_data_dir)
+}
+
+#[tauri::command]
+pub fn list_device_profiles(state: State<'_, AppState>) -> Vec<DeviceProfile> {
+    state.device_profiles.lock().list()
+}
+
+#[tauri::command]
+pub fn delete_device_profile(
+    device_name: String,
+    state: State<'_, AppState>,
+) -> Result<(), String> {
+    let mut store = state.device_profiles.lock();
+    store.delete(&device_name);
+    store.save(&state.app_data_dir)
+}
+
+// ─── Library Commands ───
+
+#[tauri::command]
+pub fn list_library_roots(state: State<'_, AppState>) -> Result<Vec<LibraryRoot>, String> {
+    state.library.lock().roots()
+}
+
+#[tauri::command]
+pub fn add_library_root(path: String, state: State<'_, AppState>) -> Result<LibraryRoot, String> {
+    state.library.lock().add_root(&path)
+}
+
+/// Unregister a root and remove its tracks from the library.
+#[tauri::command]
+pub fn remove_library_root(id: i64, state: State<'_, AppState>) -> Result<(), String> {
+    if state.scan.is_running() {
+        return Err("Cannot remove a library root while a scan is running".to_string());
+    }
+    state.library.lock().remove_root(id)
+}
+
+/// Disabled roots are skipped by scans and their tracks are hidden.
+#[tauri::command]
+pub fn set_library_root_enabled(
+    id: i64,
+    enabled: bool,
+    state: State<'_, AppState>,
+) -> Result<(), String> {
+    state.library.lock().set_root_enabled(id, enabled)
+}
+
+/// Start a background scan of one library root, or of every enabled root
+/// when `id` is omitted. Progress is reported via `library://scan-progress`;
+/// the result arrives as `library://scan-complete` (a `ScanSummary`) or
+/// `library://scan-error`.
+///
+/// With `dry_run` nothing is written and the summary lists every change the
+/// scan would make. A dry run can try out `exclude` rules before they are
+/// saved; real scans always use the saved ones.
+#[tauri::command]
+pub fn scan_library(
+    id: Option<i64>,
+    dry_run: Option<bool>,
+    exclude: Option<ExcludeRules>,
+    app: AppHandle,
+    state: State<'_, AppState>,
+) -> Result<(), String> {
+    let dry_run = dry_run.unwrap_or(false);
+    if exclude.is_some() && !dry_run {
+        return Err("Unsaved exclude rules can only be used for a dry run".to_string());
+    }
+    let roots = match id {
+        Some(id) => vec![state.library.lock().root(id)?],
+        None => state
+            .library
+            .lock()
+            .roots()?
+            .into_iter()
+            .filter(|r| r.enabled)
+            .collect(),
+    };
+    let exclude = match exclude {
+        Some(rules) => rules.compile()?,
+        None => state.scan_exclusions.lock().compile()?,
+    };
+    if !state.scan.try_start() {
+        return Err("A library scan is already running".to_string());
+    }
+    let library = state.library.clone();
+    let control = state.scan.clone();
+
+    let spawned = std::thread::Builder::new()
+        .name("library-scan".into())
+        .spawn({
+            let control = control.clone();
+            move || {
+                let result =
+                    scanner::scan_roots(&library, &roots, &exclude, dry_run, &control, &mut |p| {
+                        let _ = app.emit("library://scan-progress", p);
+                    });
+                control.finish();
+                match result {
+                    Ok(summary) => {
+                        let _ = app.emit("library://scan-complete", &summary);
+                    }
+                    Err(e) => {
+                        log::error!("Library scan failed: {}", e);
+                        let _ = app.emit("library://scan-error", &e);
+                    }
+                }
+            }
+        });
+    if let Err(e) = spawned {
+        control.finish();
+        return Err(format!("Failed to start scan: {}", e));
+    }
+    Ok(())
+}
+
+/// Stop the running scan. Returns false if no scan was running.
+#[tauri::command]
+pub fn cancel_scan(state: State<'_, AppState>) -> bool {
+    state.scan.cancel()
+}
+
+#[tauri::command]
+pub fn is_scan_running(state: State<'_, AppState>) -> bool {
+    state.scan.is_running()
+}
+
+/// Waveform peaks of a file, decoded on first request and cached for
+/// library tracks.
+#[tauri::command]
+pub async fn get_waveform(path: String, state: State<'_, AppState>) -> Result<Waveform, String> {
+    let library = state.library.clone();
+    tauri::async_runtime::spawn_blocking(move || waveform::waveform(&library, &path))
+        .await
+        .map_err(|e| format!("Waveform task failed: {}", e))?
+}
+
+/// Start decoding waveforms for every library track that lacks one.
+/// Progress is reported via `library://waveform-progress`; the result
+/// arrives as `library://waveform-complete` (a `WaveformSummary`) or
+/// `library://waveform-error`.
+#[tauri::command]
+pub fn generate_waveforms(app: AppHandle, state: State<'_, AppState>) -> Result<(), String> {
+    let library = state.library.clone();
+    spawn_library_job(
+        app,
+        state.waveforms.clone(),
+        "waveform",
+        move |control, on_progress| waveform::generate_missing(&library, control, on_progress),
+    )
+}
+
+/// Stop waveform generation. Returns false if it wasn't running.
+#[tauri::command]
+pub fn cancel_waveforms(state: State<'_, AppState>) -> bool {
+    state.waveforms.cancel()
+}
+
+/// Measure loudness and write ReplayGain tags for the given files and
+/// albums, or the whole library when both are omitted. Selected library
+/// tracks are analysed with the rest of their album. Progress is reported
+/// via `library://replaygain-progress`; the result arrives as
+/// `library://replaygain-complete` (a `JobSummary`) or
+/// `library://replaygain-error`.
+#[tauri::command]
+pub fn scan_replaygain(
+    paths: Option<Vec<String>>,
+    album_ids: Option<Vec<i64>>,
+    app: AppHandle,
+    state: State<'_, AppState>,
+) -> Result<(), String> {
+    let library = state.library.clone();
+    let paths = paths.unwrap_or_default();
+    let album_ids = album_ids.unwrap_or_default();
+    spawn_library_job(
+        app,
+        state.replaygain.clone(),
+        "replaygain",
+        move |control, on_progress| {
+            replaygain::run(&library, &paths, &album_ids, control, on_progress)
+        },
+    )
+}
+
+/// Stop ReplayGain analysis. Albums already written keep their tags.
+#[tauri::command]
+pub fn cancel_replaygain_scan(state: State<'_, AppState>) -> bool {
+    state.replaygain.cancel()
+}
+
+/// Measure the dynamic range (DR) of the given files and albums, or the
+/// whole library when both are omitted, and store it in the library. With
+/// `write_tags`, `DYNAMIC RANGE` / `ALBUM DYNAMIC RANGE` tags are written
+/// too. Events are `library://dynamic-range-progress`, `-complete` and
+/// `-error`.
+#[tauri::command]
+pub fn scan_dynamic_range(
+    paths: Option<Vec<String>>,
+    album_ids: Option<Vec<i64>>,
+    write_tags: Option<bool>,
+    app: AppHandle,
+    state: State<'_, AppState>,
+) -> Result<(), String> {
+    let library = state.library.clone();
+    let paths = paths.unwrap_or_default();
+    let album_ids = album_ids.unwrap_or_default();
+    let write_tags = write_tags.unwrap_or(false);
+    spawn_library_job(
+        app,
+        state.dynamic_range.clone(),
+        "dynamic-range",
+        move |control, on_progress| {
+            dynamic_range::run(
+                &library,
+                &paths,
+                &album_ids,
+                write_tags,
+                control,
+                on_progress,
+            )
+        },
+    )
+}
+
+/// Stop DR analysis. Values measured so far are kept.
+#[tauri::command]
+pub fn cancel_dynamic_range_scan(state: State<'_, AppState>) -> bool {
+    state.dynamic_range.cancel()
+}
+
+/// Check lossless files for signs of a lossy source, or every unchecked
+/// lossless library track when `paths` is omitted. Events are
+/// `library://transcodes-progress`, `-complete` and `-error`.
+#[tauri::command]
+pub fn scan_transcodes(
+    paths: Option<Vec<String>>,
+    app: AppHandle,
+    state: State<'_, AppState>,
+) -> Result<(), String> {
+    let library = state.library.clone();
+    let paths = paths.unwrap_or_default();
+    spawn_library_job(
+        app,
+        state.transcodes.clone(),
+        "transcodes",
+        move |control, on_progress| transcodes::run(&library, &paths, control, on_progress),
+    )
+}
+
+/// Stop the transcode check. Results so far are kept.
+#[tauri::command]
+pub fn cancel_transcode_scan(state: State<'_, AppState>) -> bool {
+    state.transcodes.cancel()
+}
+
+/// Decode files in full and record damaged ones. Without `paths`, checks
+/// library files never checked or changed since, or every file with
+/// `full`. Events are `library://integrity-progress`, `-complete` and
+/// `-error`.
+#[tauri::command]
+pub fn scan_integrity(
+    paths: Option<Vec<String>>,
+    full: Option<bool>,
+    app: AppHandle,
+    state: State<'_, AppState>,
+) -> Result<(), String> {
+    let library = state.library.clone();
+    let paths = paths.unwrap_or_default();
+    let full = full.unwrap_or(false);
+    spawn_library_job(
+        app,
+        state.integrity.clone(),
+        "integrity",
+        move |control, on_progress| integrity::run(&library, &paths, full, control, on_progress),
+    )
+}
+
+/// Stop the integrity scan. Files checked so far keep their results.
+#[tauri::command]
+pub fn cancel_integrity_scan(state: State<'_, AppState>) -> bool {
+    state.integrity.cancel()
+}
+
+#[tauri::command]
+pub fn get_integrity_problems(
+    limit: Option<usize>,
+    offset: Option<usize>,
+    state: State<'_, AppState>,
+) -> Result<Vec<IntegrityProblem>, String> {
+    state
+        .library
+        .lock()
+        .integrity_problems(Paging::new(limit, offset))
+}
+
+#[tauri::command]
+pub fn get_integrity_status(state: State<'_, AppState>) -> Result<IntegrityStatus, String> {
+    state.library.lock().integrity_status()
+}
+
+/// Check a ripped CD album against the AccurateRip database, disc by disc.
+#[tauri::command]
+pub async fn verify_album_accuraterip(
+    album_id: i64,
+    state: State<'_, AppState>,
+) -> Result<Vec<AccurateRipDisc>, String> {
+    let tracks = state.library.lock().album_tracks(album_id)?;
+    tauri::async_runtime::spawn_blocking(move || accuraterip::verify_album(&tracks))
+        .await
+        .map_err(|e| format!("AccurateRip verification failed: {}", e))?
+}
+
+#[tauri::command]
+pub fn get_cd_drives() -> Vec<CdDriveInfo> {
+    cd_drive::drives()
+}
+
+/// The disc in a drive: its table of contents, and the MusicBrainz
+/// releases to choose tags from.
+#[tauri::command]
+pub async fn read_cd(drive: String) -> Result<CdDisc, String> {
+    tauri::async_runtime::spawn_blocking(move || ripper::read_disc(&drive))
+        .await
+        .map_err(|e| format!("Failed to read disc: {}", e))?
+}
+
+/// Rip the disc in a drive to FLAC, reading securely and checking the
+/// result against AccurateRip. Progress (in sectors) is reported via
+/// `library://rip-progress`; the result arrives as `library://rip-complete`
+/// (a `RipSummary`) or `library://rip-error`.
+#[tauri::command]
+pub fn rip_cd(
+    request: RipRequest,
+    app: AppHandle,
+    state: State<'_, AppState>,
+) -> Result<(), String> {
+    let library = state.library.clone();
+    spawn_library_job(
+        app,
+        state.rip.clone(),
+        "rip",
+        move |control, on_progress| ripper::rip(&library, &request, control, on_progress),
+    )
+}
+
+/// Stop ripping. Tracks already ripped are kept.
+#[tauri::command]
+pub fn cancel_rip(state: State<'_, AppState>) -> bool {
+    state.rip.cancel()
+}
+
+/// Where files would go if organized by `pattern` (e.g.
+/// `%albumartist%/%album%/%track% - %title%`), without touching them.
+/// Omitting `track_ids` selects the whole library; omitting `destination`
+/// keeps each file under its own library root.
+#[tauri::command]
+pub async fn preview_organize(
+    pattern: String,
+    track_ids: Option<Vec<i64>>,
+    destination: Option<String>,
+    collision: Option<CollisionPolicy>,
+    state: State<'_, AppState>,
+) -> Result<Vec<PlannedMove>, String> {
+    let library = state.library.clone();
+    tauri::async_runtime::spawn_blocking(move || {
+        organize::plan(
+            &library,
+            &pattern,
+            &track_ids.unwrap_or_default(),
+            destination.as_deref(),
+            collision.unwrap_or_default(),
+        )
+    })
+    .await
+    .map_err(|e| format!("Organize preview failed: {}", e))?
+}
+
+/// Rename and move files as [`preview_organize`] shows, updating library
+/// paths, playlists and bookmarks. Progress is reported via
+/// `library://organize-progress`; the result arrives as
+/// `library://organize-complete` (a `JobSummary`) or
+/// `library://organize-error`.
+#[tauri::command]
+pub fn organize_files(
+    pattern: String,
+    track_ids: Option<Vec<i64>>,
+    destination: Option<String>,
+    collision: Option<CollisionPolicy>,
+    app: AppHandle,
+    state: State<'_, AppState>,
+) -> Result<(), String> {
+    if state.scan.is_running() {
+        return Err("Cannot organize files while a scan is running".to_string());
+    }
+    organize::check_pattern(&pattern)?;
+    let library = state.library.clone();
+    let playlists = state.playlists.clone();
+    let bookmarks = state.bookmarks.clone();
+    let app_data_dir = state.app_data_dir.clone();
+    spawn_library_job(
+        app,
+        state.organize.clone(),
+        "organize",
+        move |control, on_progress| {
+            let plan = organize::plan(
+                &library,
+                &pattern,
+                &track_ids.unwrap_or_default(),
+                destination.as_deref(),
+                collision.unwrap_or_default(),
+            )?;
+            let (mut summary, moved) = organize::run(&library, &plan, control, on_progress)?;
+            let mut playlists = playlists.lock();
+            if playlists.rename_paths(&moved) {
+                if let Err(e) = playlists.save(&app_data_dir) {
+                    summary.errors.push(format!("Playlists: {}", e));
+                }
+            }
+            let mut bookmarks = bookmarks.lock();
+            if bookmarks.rename_paths(&moved) {
+                if let Err(e) = bookmarks.save(&app_data_dir) {
+                    summary.errors.push(format!("Bookmarks: {}", e));
+                }
+            }
+            Ok(summary)
+        },
+    )
+}
+
+/// Stop organizing. Files already moved stay at their new paths.
+#[tauri::command]
+pub fn cancel_organize(state: State<'_, AppState>) -> bool {
+    state.organize.cancel()
+}
+
+/// Run a per-file library job on a background thread. Progress and the
+/// result are emitted as `library://{event}-progress`, `-complete` and
+/// `-error`.
+fn spawn_library_job<S: Serialize + Clone>(
+    app: AppHandle,
+    control: Arc<ScanControl>,
+    event: &'static str,
+    job: impl FnOnce(&ScanControl, &mut dyn FnMut(&JobProgress)) -> Result<S, String> + Send + 'static,
+) -> Result<(), String> {
+    if !control.try_start() {
+        return Err(format!("A {} job is already running", event));
+    }
+    let spawned = std::thread::Builder::new()
+        .name(format!("library-{}", event))
+        .spawn({
+            let control = control.clone();
+            move || {
+                let result = job(&control, &mut |p| {
+                    let _ = app.emit(&format!("library://{}-progress", event), p);
+                });
+                control.finish();
+                match result {
+                    Ok(summary) => {
+                        let _ = app.emit(&format!("library://{}-complete", event), &summary);
+                    }
+                    Err(e) => {
+                        log::error!("Library {} job failed: {}", event, e);
+                        let _ = app.emit(&format!("library://{}-error", event), &e);
+                    }
+                }
+            }
+        });
+    if let Err(e) = spawned {
+        control.finish();
+        return Err(format!("Failed to start {} job: {}", event, e));
+    }
+    Ok(())
+}
+
+#[tauri::command]
+pub fn get_scan_exclusions(state: State<'_, AppState>) -> ExcludeRules {
+    state.scan_exclusions.lock().clone()
+}
+
+/// Replace the exclude rules. They take effect on the next scan, which also
+/// removes already-indexed tracks that are now excluded.
+#[tauri::command]
+pub fn set_scan_exclusions(rules: ExcludeRules, state: State<'_, AppState>) -> Result<(), String> {
+    // Reject invalid glob patterns up front rather than at scan time
+    rules.compile()?;
+    let mut current = state.scan_exclusions.lock();
+    *current = rules;
+    current.save(&state.app_data_dir)
+}
+
+/// All tracks in album order, or a window of them with `limit`/`offset`.
+/// The list commands below all page the same way; without a limit they
+/// return the whole list.
+#[tauri::command]
+pub fn get_library_tracks(
+    limit: Option<usize>,
+    offset: Option<usize>,
+    state: State<'_, AppState>,
+) -> Result<Vec<LibraryTrack>, String> {
+    state.library.lock().tracks(Paging::new(limit, offset))
+}
+
+/// Send a library list as `library://list-chunk` events of `chunk_size`
+/// items (default 1000) from a background thread, so a large list doesn't
+/// arrive as one IPC response. Every chunk carries the caller's
+/// `stream_id`; the last one has `done` set. A failure ends the stream with
+/// `library://list-error`.
+#[tauri::command]
+pub fn stream_library_list(
+    list: LibraryList,
+    stream_id: u32,
+    chunk_size: Option<usize>,
+    app: AppHandle,
+    state: State<'_, AppState>,
+) -> Result<(), String> {
+    let library = state.library.clone();
+    let chunk_size = chunk_size.unwrap_or(DEFAULT_CHUNK_SIZE).max(1);
+    std::thread::Builder::new()
+        .name("library-list-stream".to_string())
+        .spawn(move || {
+            let mut offset = 0;
+            loop {
+                // Locked per chunk so other commands aren't held up
+                let result = library
+                    .lock()
+                    .list(&list, Paging::new(Some(chunk_size), Some(offset)));
+                let items = match result {
+                    Ok(items) => items,
+                    Err(error) => {
+                        let _ = app.emit(
+                            "library://list-error",
+                            &ListStreamError { stream_id, error },
+                        );
+                        break;
+                    }
+                };
+                let count = items.len();
+                let done = count < chunk_size;
+                let _ = app.emit(
+                    "library://list-chunk",
+                    &ListChunk {
+                        stream_id,
+                        offset,
+                        items,
+                        done,
+                    },
+                );
+                if done {
+                    break;
+                }
+                offset += count;
+            }
+        })
+        .map_err(|e| format!("Failed to start list stream: {}", e))?;
+    Ok(())
+}
+
+/// Subfolders and audio files of a folder, with library metadata for the
+/// files that are indexed.
+#[tauri::command]
+pub fn browse_folder(path: String, state: State<'_, AppState>) -> Result<FolderListing, String> {
+    state.library.lock().browse_folder(&path)
+}
+
+/// Full-text search (title/artist/album/album artist/path), best matches first.
+/// Typo-tolerant unless `fuzzy` is false.
+#[tauri::command]
+pub fn search_library(
+    query: String,
+    limit: Option<usize>,
+    fuzzy: Option<bool>,
+    state: State<'_, AppState>,
+) -> Result<Vec<SearchHit>, String> {
+    state.library.lock().search(
+        &query,
+        limit.unwrap_or(DEFAULT_SEARCH_LIMIT),
+        fuzzy.unwrap_or(true),
+    )
+}
+
+/// Filtered, sorted, paged track listing.
+#[tauri::command]
+pub fn query_tracks(
+    filter: Option<TrackFilter>,
+    sort: Option<TrackSort>,
+    limit: Option<usize>,
+    offset: Option<usize>,
+    state: State<'_, AppState>,
+) -> Result<TrackPage, String> {
+    state.library.lock().query(
+        &filter.unwrap_or_default(),
+        sort,
+        limit.unwrap_or(DEFAULT_QUERY_LIMIT),
+        offset.unwrap_or(0),
+    )
+}
+
+#[tauri::command]
+pub fn list_albums(
+    limit: Option<usize>,
+    offset: Option<usize>,
+    state: State<'_, AppState>,
+) -> Result<Vec<LibraryAlbum>, String> {
+    state.library.lock().albums(Paging::new(limit, offset))
+}
+
+/// Album with its tracks grouped by disc. Generates the thumbnail if the scan
+/// hasn't yet.
+#[tauri::command]
+pub fn get_album(id: i64, state: State<'_, AppState>) -> Result<AlbumDetail, String> {
+    let (mut detail, source, dir) = {
+        let library = state.library.lock();
+        (
+            library.album(id)?,
+            library.cover_source(id)?,
+            library.thumbnail_dir().to_path_buf(),
+        )
+    };
+    if detail.album.thumbnail.is_none() {
+        if let Some(source) = source {
+            detail.album.thumbnail =
+                artwork::ensure_thumbnail(&dir, &source.path, source.modified_at)?;
+        }
+    }
+    Ok(detail)
+}
+
+/// Albums known to have no artwork, embedded or as a folder image.
+#[tauri::command]
+pub fn get_albums_missing_art(state: State<'_, AppState>) -> Result<Vec<LibraryAlbum>, String> {
+    state.library.lock().albums_missing_art()
+}
+
+/// Artists with tracks in the library; only album artists if `album_artists`.
+#[tauri::command]
+pub fn get_library_artists(
+    album_artists: Option<bool>,
+    limit: Option<usize>,
+    offset: Option<usize>,
+    state: State<'_, AppState>,
+) -> Result<Vec<LibraryArtist>, String> {
+    state
+        .library
+        .lock()
+        .artists(album_artists.unwrap_or(false), Paging::new(limit, offset))
+}
+
+/// Artist page: own albums, albums they appear on, and guest appearances.
+#[tauri::command]
+pub fn get_artist(id: i64, state: State<'_, AppState>) -> Result<ArtistDetail, String> {
+    state.library.lock().artist(id)
+}
+
+#[tauri::command]
+pub fn list_composers(
+    limit: Option<usize>,
+    offset: Option<usize>,
+    state: State<'_, AppState>,
+) -> Result<Vec<LibraryComposer>, String> {
+    state.library.lock().composers(Paging::new(limit, offset))
+}
+
+/// A composer's works, each with its movements in order.
+#[tauri::command]
+pub fn get_composer_works(
+    name: String,
+    state: State<'_, AppState>,
+) -> Result<Vec<ClassicalWork>, String> {
+    state.library.lock().composer_works(&name)
+}
+
+#[tauri::command]
+pub fn list_genres(
+    limit: Option<usize>,
+    offset: Option<usize>,
+    state: State<'_, AppState>,
+) -> Result<Vec<LibraryGenre>, String> {
+    state.library.lock().genres(Paging::new(limit, offset))
+}
+
+/// Genre with the albums that have tracks in it.
+#[tauri::command]
+pub fn get_genre(id: i64, state: State<'_, AppState>) -> Result<GenreDetail, String> {
+    state.library.lock().genre(id)
+}
+
+#[tauri::command]
+pub fn get_genre_tracks(
+    id: i64,
+    limit: Option<usize>,
+    offset: Option<usize>,
+    state: State<'_, AppState>,
+) -> Result<Vec<LibraryTrack>, String> {
+    state
+        .library
+        .lock()
+        .genre_tracks(id, Paging::new(limit, offset))
+}
+
+/// Rate a track 1–5 stars, or clear its rating with 0. Unless `write_tags` is
+/// false the rating is also written to the file (POPM / FMPS_RATING) so
+/// other players see it and rescans keep it; cue-sheet tracks share their
+/// image's tags, so theirs is kept in the library only.
+#[tauri::command]
+pub fn set_rating(
+    id: i64,
+    rating: u8,
+    write_tags: Option<bool>,
+    state: State<'_, AppState>,
+) -> Result<(), String> {
+    set_ratings(vec![id], rating, write_tags, state)
+}
+
+/// Rate several tracks at once, e.g. a whole album.
+#[tauri::command]
+pub fn set_ratings(
+    ids: Vec<i64>,
+    rating: u8,
+    write_tags: Option<bool>,
+    state: State<'_, AppState>,
+) -> Result<(), String> {
+    if rating > 5 {
+        return Err("Rating must be 0–5".to_string());
+    }
+    let stars = (rating > 0).then_some(rating);
+    let paths = state.library.lock().set_rating(&ids, stars)?;
+    for path in &paths {
+        state.scripts.rating_changed(path, stars);
+    }
+
+    if write_tags.unwrap_or(true) {
+        let failures: Vec<String> = paths
+            .iter()
+            .filter(|path| cue::split_virtual_track_path(path).is_none())
+            .filter_map(|path| {
+                rating::write_rating(path, stars)
+                    .err()
+                    .map(|e| format!("{}: {}", path, e))
+            })
+            .collect();
+        if let Some(first) = failures.first() {
+            return Err(format!(
+                "Rating saved, but writing tags failed for {} file(s): {}",
+                failures.len(),
+                first
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Albums by date added, newest first.
+#[tauri::command]
+pub fn get_recently_added(
+    limit: Option<usize>,
+    offset: Option<usize>,
+    state: State<'_, AppState>,
+) -> Result<Vec<LibraryAlbum>, String> {
+    state
+        .library
+        .lock()
+        .recently_added(limit.unwrap_or(DEFAULT_QUERY_LIMIT), offset.unwrap_or(0))
+}
+
+/// Most played tracks within a period (all time by default).
+#[tauri::command]
+pub fn get_most_played(
+    period: Option<PlayPeriod>,
+    limit: Option<usize>,
+    offset: Option<usize>,
+    state: State<'_, AppState>,
+) -> Result<Vec<PlayedTrack>, String> {
+    state.library.lock().most_played(
+        period.unwrap_or(PlayPeriod::AllTime),
+        limit.unwrap_or(DEFAULT_QUERY_LIMIT),
+        offset.unwrap_or(0),
+    )
+}
+
+#[tauri::command]
+pub fn get_never_played(
+    limit: Option<usize>,
+    offset: Option<usize>,
+    state: State<'_, AppState>,
+) -> Result<TrackPage, String> {
+    state
+        .library
+        .lock()
+        .never_played(limit.unwrap_or(DEFAULT_QUERY_LIMIT), offset.unwrap_or(0))
+}
+
+/// Totals, per-format breakdown, lossless/hi-res counts and average bitrate.
+#[tauri::command]
+pub fn get_library_stats(state: State<'_, AppState>) -> Result<LibraryStats, String> {
+    state.library.lock().stats()
+}
+
+/// Tracks with matching artist and title whose durations differ by at most
+/// `tolerance_secs` (default 2 s), grouped and ranked by quality.
+#[tauri::command]
+pub fn find_duplicates(
+    tolerance_secs: Option<f64>,
+    limit: Option<usize>,
+    offset: Option<usize>,
+    state: State<'_, AppState>,
+) -> Result<Vec<DuplicateGroup>, String> {
+    state.library.lock().duplicates(
+        tolerance_secs.unwrap_or(DEFAULT_DURATION_TOLERANCE_SECS),
+        Paging::new(limit, offset),
+    )
+}
+
+/// Lossless tracks that are likely lossy transcodes, with a confidence of at
+/// least `min_confidence` (default 0.5), most suspect first.
+#[tauri::command]
+pub fn get_suspected_transcodes(
+    min_confidence: Option<f64>,
+    limit: Option<usize>,
+    offset: Option<usize>,
+    state: State<'_, AppState>,
+) -> Result<Vec<LibraryTrack>, String> {
+    state.library.lock().suspected_transcodes(
+        min_confidence.unwrap_or(DEFAULT_MIN_CONFIDENCE),
+        Paging::new(limit, offset),
+    )
+}
+
+/// Write roots, ratings, play history, playlists and bookmarks to a JSON
+/// backup at `path`.
+#[tauri::command]
+pub fn export_library_backup(path: String, state: State<'_, AppState>) -> Result<(), String> {
+    let mut backup = state.library.lock().backup()?;
+    backup.playlists = state.playlists.lock().all();
+    let bookmarks = state.bookmarks.lock();
+    backup.bookmarks = bookmarks.list();
+    backup.bookmark_rules = bookmarks.rules();
+    backup.write(&path)
+}
+
+/// Restore a backup made by `export_library_backup`, optionally moving its
+/// paths to where the music lives on this machine. Track data for files not
+/// scanned yet is applied by the next scan.
+#[tauri::command]
+pub fn restore_library_backup(
+    path: String,
+    path_mappings: Option<Vec<PathMapping>>,
+    state: State<'_, AppState>,
+) -> Result<RestoreReport, String> {
+    restore_backup(
+        &state,
+        LibraryBackup::read(&path)?,
+        &path_mappings.unwrap_or_default(),
+    )
+}
+
+/// Import ratings, play counts, add dates and playlists from an iTunes /
+/// Apple Music (or MusicBee) `Library.xml`, optionally moving its paths to
+/// where the music lives on this machine.
+#[tauri::command]
+pub fn import_itunes_library(
+    path: String,
+    path_mappings: Option<Vec<PathMapping>>,
+    state: State<'_, AppState>,
+) -> Result<RestoreReport, String> {
+    restore_backup(
+        &state,
+        itunes::read_itunes_library(&path)?,
+        &path_mappings.unwrap_or_default(),
+    )
+}
+
+fn restore_backup(
+    state: &AppState,
+    mut backup: LibraryBackup,
+    path_mappings: &[PathMapping],
+) -> Result<RestoreReport, String> {
+    if state.scan.is_running() {
+        return Err("Cannot restore a backup while a scan is running".to_string());
+    }
+    backup.remap_paths(path_mappings);
+
+    let mut report = RestoreReport::default();
+    state
+        .library
+        .lock()
+        .restore_user_data(&backup.roots, &backup.tracks, &mut report)?;
+
+    report.playlists = backup.playlists.len() as u32;
+    let mut playlists = state.playlists.lock();
+    playlists.restore(backup.playlists);
+    playlists.save(&state.app_data_dir)?;
+
+    report.bookmarks = backup.bookmarks.len() as u32;
+    let mut bookmarks = state.bookmarks.lock();
+    bookmarks.restore(backup.bookmarks, backup.bookmark_rules);
+    bookmarks.save(&state.app_data_dir)?;
+    Ok(report)
+}
+
+// ─── Metadata Commands ───
+
+/// A file's tags, cached while the file is unchanged.
+#[tauri::command]
+pub fn read_file_metadata(
+    path: String,
+    state: State<'_, AppState>,
+) -> Result<reader::TrackMetadata, String> {
+    metadata_cache::metadata(&state.library, &path)
+}
+
+/// Edit a file's tags. Library tracks are updated to match; the updated
+/// track is returned, or `None` for files outside the library.
+#[tauri::command]
+pub fn write_metadata(
+    path: String,
+    fields: MetadataFields,
+    state: State<'_, AppState>,
+) -> Result<Option<LibraryTrack>, String> {
+    writer::write_metadata(&path, &fields)?;
+    scanner::refresh_file(&state.library, &path)?;
+    state.library.lock().track_by_path(&path)
+}
+
+/// Write ReplayGain values to a file in its format's convention (R128 gains
+/// for Opus). Values left out are removed from the file. Library tracks are
+/// updated to match.
+#[tauri::command]
+pub fn write_replaygain_tags(
+    path: String,
+    values: ReplayGainInfo,
+    state: State<'_, AppState>,
+) -> Result<(), String> {
+    write_replaygain(&path, &values)?;
+    scanner::refresh_file(&state.library, &path)
+}
+
+/// Every text field in a file's primary tag under its native name, for
+/// fields the editor doesn't cover.
+#[tauri::command]
+pub fn get_tag_fields(path: String) -> Result<TagFields, String> {
+    fields::read_tag_fields(&path)
+}
+
+/// Replace all values of one raw tag field, removing it when `values` is
+/// empty. Library tracks are updated to match.
+#[tauri::command]
+pub fn set_tag_field(
+    path: String,
+    key: String,
+    values: Vec<String>,
+    state: State<'_, AppState>,
+) -> Result<(), String> {
+    fields::write_tag_field(&path, &key, &values)?;
+    scanner::refresh_file(&state.library, &path)
+}
+
+#[tauri::command]
+pub fn get_tag_write_settings() -> TagWriteSettings {
+    write_settings::current()
+}
+
+/// Choose the ID3v2 version tags are written in and which duplicate tags
+/// (ID3v1, APEv2) are stripped. Applies to every tag write from now on.
+#[tauri::command]
+pub fn set_tag_write_settings(
+    settings: TagWriteSettings,
+    state: State<'_, AppState>,
+) -> Result<(), String> {
+    settings.save(&state.app_data_dir)?;
+    write_settings::apply(settings);
+    Ok(())
+}
+
+/// MP3/AIFF/WAV library tracks whose tags look like they were written in a
+/// legacy codepage (CP1251, Shift-JIS, GBK), by path.
+#[tauri::command]
+pub fn get_legacy_encoded_tracks(state: State<'_, AppState>) -> Result<Vec<LibraryTrack>, String> {
+    state.library.lock().legacy_encoded_tracks()
+}
+
+/// The fields of a file's ID3 tags as they'd read decoded with `encoding`
+/// ("windows-1251", "shift_jis", "gbk", ...), or the detected codepage.
+/// Nothing is written.
+#[tauri::command]
+pub fn preview_encoding_repair(
+    path: String,
+    encoding: Option<String>,
+) -> Result<EncodingRepair, String> {
+    encoding::preview_repair(&path, encoding.as_deref())
+}
+
+/// Rewrite the ID3 tags of each file as UTF-8, decoded with its chosen
+/// codepage or the detected one. Library tracks are updated to match.
+#[tauri::command]
+pub async fn repair_tag_encodings(
+    files: Vec<EncodingChoice>,
+    state: State<'_, AppState>,
+) -> Result<JobSummary, String> {
+    let library = Arc::clone(&state.library);
+    tauri::async_runtime::spawn_blocking(move || encodings::repair(&library, &files))
+        .await
+        .map_err(|e| format!("Repairing tag encodings failed: {}", e))
+}
+
+/// Identify a file by its audio fingerprint through AcoustID, returning tag
+/// suggestions best match first. `api_key` is the AcoustID client key.
+#[tauri::command]
+pub async fn identify_track(path: String, api_key: String) -> Result<Vec<TagSuggestion>, String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let fingerprint = fingerprint::fingerprint_file(&path)?;
+        acoustid::lookup(&api_key, &fingerprint)
+    })
+    .await
+    .map_err(|e| format!("Identification failed: {}", e))?
+}
+
+/// Search the Cover Art Archive by MusicBrainz id or artist/album name.
+#[tauri::command]
+pub async fn search_cover_art(query: CoverArtQuery) -> Result<Vec<CoverCandidate>, String> {
+    tauri::async_runtime::spawn_blocking(move || coverart::search(&query))
+        .await
+        .map_err(|e| format!("Cover art search failed: {}", e))?
+}
+
+/// Download `image_url` and make it the album's cover: embedded in every
+/// track (`embed`, default true) and/or saved as `folder.jpg` next to them
+/// (`save_folder`, default false).
+#[tauri::command]
+pub async fn apply_cover_art(
+    album_id: i64,
+    image_url: String,
+    embed: Option<bool>,
+    save_folder: Option<bool>,
+    state: State<'_, AppState>,
+) -> Result<JobSummary, String> {
+    let embed = embed.unwrap_or(true);
+    let save_folder = save_folder.unwrap_or(false);
+    if !embed && !save_folder {
+        return Err("Nothing to do: neither embedding nor saving a folder image".to_string());
+    }
+    let library = Arc::clone(&state.library);
+    tauri::async_runtime::spawn_blocking(move || {
+        let image = http::get_bytes(&image_url)?;
+        image::load_from_memory(&image)
+            .map_err(|e| format!("Downloaded file is not a usable image: {}", e))?;
+        artwork::apply_album_cover(&library, album_id, &image, embed, save_folder)
+    })
+    .await
+    .map_err(|e| format!("Applying cover art failed: {}", e))?
+}
+
+/// Embed the image at `image_path` as the front cover of every track of an
+/// album, replacing what was there. `resize` scales it down and/or recompresses
+/// it first; `save_folder` also writes it as `folder.jpg`.
+#[tauri::command]
+pub async fn set_album_art(
+    album_id: i64,
+    image_path: String,
+    resize: Option<ArtResize>,
+    save_folder: Option<bool>,
+    state: State<'_, AppState>,
+) -> Result<JobSummary, String> {
+    let library = Arc::clone(&state.library);
+    let save_folder = save_folder.unwrap_or(false);
+    tauri::async_runtime::spawn_blocking(move || {
+        let image = std::fs::read(&image_path)
+            .map_err(|e| format!("Failed to read {}: {}", image_path, e))?;
+        let image = match resize {
+            Some(resize) => cover::resize_image(&image, &resize)?,
+            None => image,
+        };
+        artwork::apply_album_cover(&library, album_id, &image, true, save_folder)
+    })
+    .await
+    .map_err(|e| format!("Setting album art failed: {}", e))?
+}
+
+/// Strip all embedded artwork from an album's tracks.
+#[tauri::command]
+pub async fn remove_album_art(
+    album_id: i64,
+    state: State<'_, AppState>,
+) -> Result<JobSummary, String> {
+    let library = Arc::clone(&state.library);
+    tauri::async_runtime::spawn_blocking(move || artwork::remove_album_art(&library, album_id))
+        .await
+        .map_err(|e| format!("Removing album art failed: {}", e))?
+}
+
+/// Shrink embedded pictures over `max_bytes` (default 1 MB) in an album's
+/// tracks with `resize`, or remove them if no `resize` is given.
+#[tauri::command]
+pub async fn shrink_album_art(
+    album_id: i64,
+    max_bytes: Option<usize>,
+    resize: Option<ArtResize>,
+    state: State<'_, AppState>,
+) -> Result<JobSummary, String> {
+    let library = Arc::clone(&state.library);
+    let max_bytes = max_bytes.unwrap_or(DEFAULT_MAX_ART_BYTES);
+    tauri::async_runtime::spawn_blocking(move || {
+        artwork::shrink_album_art(&library, album_id, max_bytes, resize.as_ref())
+    })
+    .await
+    .map_err(|e| format!("Shrinking album art failed: {}", e))?
+}
+
+/// Save the pictures embedded in an album's tracks as image files next to
+/// them: `folder.jpg` for the front cover, others by type. Existing files are
+/// kept unless `overwrite`.
+#[tauri::command]
+pub async fn extract_album_art(
+    album_id: i64,
+    overwrite: Option<bool>,
+    state: State<'_, AppState>,
+) -> Result<JobSummary, String> {
+    let library = Arc::clone(&state.library);
+    let overwrite = overwrite.unwrap_or(false);
+    tauri::async_runtime::spawn_blocking(move || {
+        artwork::extract_album_art(&library, album_id, overwrite)
+    })
+    .await
+    .map_err(|e| format!("Extracting album art failed: {}", e))?
+}
+
+/// The embedded cover as a `data:` URL, for copying or saving; images
+/// shown in the UI load from `art://` instead.
+#[tauri::command]
+pub fn get_album_art_base64(path: String) -> Result<Option<String>, String> {
+    metadata_cache::cover_data_url(&path)
+}
+
+/// Every picture embedded in a file with its type, dimensions and size.
+/// Image data is only included with `include_data`.
+#[tauri::command]
+pub fn get_embedded_pictures(
+    path: String,
+    include_data: Option<bool>,
+) -> Result<Vec<EmbeddedPicture>, String> {
+    reader::read_embedded_pictures(&path, include_data.unwrap_or(false))
+}
+
+/// Unsynced lyrics embedded in a file, `None` if it has none.
+#[tauri::command]
+pub fn get_lyrics(path: String) -> Result<Option<String>, String> {
+    lyrics::read_lyrics(&path)
+}
+
+/// Timestamped lyrics from a sidecar `.lrc`, a SYLT frame or LRC-formatted
+/// embedded lyrics, `None` if there are none. While a file plays, the current
+/// line is announced as `lyrics://line` events.
+#[tauri::command]
+pub fn get_synced_lyrics(path: String) -> Result<Option<Vec<LyricLine>>, String> {
+    lyrics::read_synced_lyrics(&path)
+}
+
+/// Look a file's lyrics up on LRCLIB by its tags; answers are cached in the
+/// app data directory. With `save`, lyrics the file is missing are kept:
+/// synced ones as a sidecar `.lrc`, plain ones embedded in its tags.
+#[tauri::command]
+pub async fn fetch_lyrics(
+    path: String,
+    save: Option<bool>,
+    state: State<'_, AppState>,
+) -> Result<Option<OnlineLyrics>, String> {
+    let library = Arc::clone(&state.library);
+    let app_data_dir = state.app_data_dir.clone();
+    tauri::async_runtime::spawn_blocking(move || {
+        let meta = reader::read_metadata(&path)?;
+        let (Some(artist), Some(title)) = (meta.artist, meta.title) else {
+            return Err("Lyrics lookup needs the artist and title tags".to_string());
+        };
+        let query = LyricsQuery {
+            artist,
+            title,
+            album: meta.album,
+            duration_secs: meta.duration_secs,
+        };
+        let Some(found) = lrclib::lookup(&app_data_dir, &query)? else {
+            return Ok(None);
+        };
+
+        if save.unwrap_or(false) {
+            if let Some(synced) = &found.synced {
+                lyrics::save_lrc(&path, synced)?;
+            }
+            if let Some(plain) = &found.plain {
+                if lyrics::read_lyrics(&path)?.is_none() {
+                    lyrics::write_lyrics(&path, plain)?;
+                    scanner::refresh_file(&library, &path)?;
+                }
+            }
+        }
+        Ok(Some(found))
+    })
+    .await
+    .map_err(|e| format!("Lyrics lookup failed: {}", e))?
+}
+
+// ─── ListenBrainz ───
+
+#[tauri::command]
+pub fn get_listenbrainz_status(state: State<'_, AppState>) -> ListenBrainzStatus {
+    state.listenbrainz.status()
+}
+
+/// Check a ListenBrainz user token, store it and start submitting listens.
+#[tauri::command]
+pub async fn connect_listenbrainz(
+    token: String,
+    state: State<'_, AppState>,
+) -> Result<ListenBrainzStatus, String> {
+    let listenbrainz = state.listenbrainz.clone();
+    tauri::async_runtime::spawn_blocking(move || listenbrainz.connect(&token))
+        .await
+        .map_err(|e| format!("ListenBrainz connection failed: {}", e))?
+}
+
+/// Forget the ListenBrainz token and the listens not sent yet.
+#[tauri::command]
+pub fn disconnect_listenbrainz(state: State<'_, AppState>) -> Result<(), String> {
+    state.listenbrainz.disconnect()
+}
+
+/// Switch ListenBrainz submission on or off, keeping the token.
+#[tauri::command]
+pub fn set_listenbrainz_enabled(enabled: bool, state: State<'_, AppState>) -> Result<(), String> {
+    state.listenbrainz.set_enabled(enabled)
+}
+
+// ─── Remote Control Server ───
+
+#[tauri::command]
+pub fn get_remote_server_status(state: State<'_, AppState>) -> RemoteServerStatus {
+    state.remote.status()
+}
+
+/// Switch the LAN remote control server on or off and choose its port.
+#[tauri::command]
+pub fn set_remote_server(
+    enabled: bool,
+    port: u16,
+    app: AppHandle,
+    state: State<'_, AppState>,
+) -> Result<RemoteServerStatus, String> {
+    state.remote.configure(&app, enabled, port)
+}
+
+/// Give the remote control server a new token, disconnecting every client.
+#[tauri::command]
+pub fn regenerate_remote_server_token(
+    app: AppHandle,
+    state: State<'_, AppState>,
+) -> Result<RemoteServerStatus, String> {
+    state.remote.regenerate_token(&app)
+}
+
+// ─── MQTT ───
+
+#[tauri::command]
+pub fn get_mqtt_status(state: State<'_, AppState>) -> MqttStatus {
+    state.mqtt.status()
+}
+
+/// Connect to an MQTT broker for home automation, or disconnect, and save
+/// the settings.
+#[tauri::command]
+pub fn set_mqtt_bridge(
+    settings: MqttSettings,
+    app: AppHandle,
+    state: State<'_, AppState>,
+) -> Result<MqttStatus, String> {
+    state.mqtt.configure(&app, settings)
+}
+
+// ─── Scripts ───
+
+#[tauri::command]
+pub fn get_scripts(state: State<'_, AppState>) -> ScriptsStatus {
+    state.scripts.status()
+}
+
+/// Load the scripts in the scripts folder again, after adding or editing
+/// them. Loading is asynchronous; the status updates once it's done.
+#[tauri::command]
+pub fn reload_scripts(state: State<'_, AppState>) {
+    state.scripts.reload();
+}
+
+// ─── DLNA Media Servers ───
+
+/// Media servers that answer an SSDP search on the LAN (takes a few seconds).
+#[tauri::command]
+pub async fn discover_dlna_servers() -> Result<Vec<DlnaServer>, String> {
+    tauri::async_runtime::spawn_blocking(dlna::discover)
+        .await
+        .map_err(|e| format!("Discovery failed: {}", e))?
+}
+
+/// A media server by the URL of its device description, for networks where
+/// discovery doesn't get through.
+#[tauri::command]
+pub async fn add_dlna_server(location: String) -> Result<DlnaServer, String> {
+    tauri::async_runtime::spawn_blocking(move || dlna::describe(&location))
+        .await
+        .map_err(|e| format!("Failed to read server: {}", e))?
+}
+
+/// One page of a container on a media server; `object_id` defaults to the
+/// server's root. Items' URLs play and queue like paths.
+#[tauri::command]
+pub async fn browse_dlna_server(
+    server: DlnaServer,
+    object_id: Option<String>,
+    start: Option<u32>,
+    count: Option<u32>,
+) -> Result<DlnaBrowsePage, String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        dlna::browse(
+            &server,
+            object_id.as_deref().unwrap_or(dlna::ROOT_ID),
+            start.unwrap_or(0),
+            count.unwrap_or(dlna::DEFAULT_BROWSE_COUNT),
+        )
+    })
+    .await
+    .map_err(|e| format!("Browse failed: {}", e))?
+}
+
+// ─── Subsonic Servers ───
+
+#[tauri::command]
+pub fn get_subsonic_servers(state: State<'_, AppState>) -> Vec<SubsonicServer> {
+    state.subsonic.servers()
+}
+
+/// Sign in to a Subsonic-API server (Navidrome, Airsonic, ...) and store it.
+/// The password is only used to derive the auth token.
+#[tauri::command]
+pub async fn add_subsonic_server(
+    name: Option<String>,
+    url: String,
+    username: String,
+    password: String,
+    state: State<'_, AppState>,
+) -> Result<SubsonicServer, String> {
+    let subsonic = state.subsonic.clone();
+    tauri::async_runtime::spawn_blocking(move || {
+        subsonic.add_server(name, &url, &username, &password)
+    })
+    .await
+    .map_err(|e| format!("Failed to add server: {}", e))?
+}
+
+#[tauri::command]
+pub fn remove_subsonic_server(server_id: String, state: State<'_, AppState>) -> Result<(), String> {
+    state.subsonic.remove_server(&server_id)
+}
+
+/// Cap a server's stream bit rate (`None` streams originals) and switch
+/// scrobbling to it on or off.
+#[tauri::command]
+pub fn set_subsonic_server_options(
+    server_id: String,
+    max_bit_rate: Option<u32>,
+    scrobble: bool,
+    state: State<'_, AppState>,
+) -> Result<SubsonicServer, String> {
+    state
+        .subsonic
+        .set_options(&server_id, max_bit_rate, scrobble)
+}
+
+#[tauri::command]
+pub async fn get_subsonic_artists(
+    server_id: String,
+    state: State<'_, AppState>,
+) -> Result<Vec<SubsonicArtist>, String> {
+    let subsonic = state.subsonic.clone();
+    tauri::async_runtime::spawn_blocking(move || subsonic.artists(&server_id))
+        .await
+        .map_err(|e| format!("Failed to list artists: {}", e))?
+}
+
+#[tauri::command]
+pub async fn get_subsonic_artist_albums(
+    server_id: String,
+    artist_id: String,
+    state: State<'_, AppState>,
+) -> Result<Vec<SubsonicAlbum>, String> {
+    let subsonic = state.subsonic.clone();
+    tauri::async_runtime::spawn_blocking(move || subsonic.artist_albums(&server_id, &artist_id))
+        .await
+        .map_err(|e| format!("Failed to list albums: {}", e))?
+}
+
+/// One page of a server's album list, e.g. the newest or most played.
+#[tauri::command]
+pub async fn get_subsonic_album_list(
+    server_id: String,
+    list: AlbumListType,
+    offset: Option<u32>,
+    size: Option<u32>,
+    state: State<'_, AppState>,
+) -> Result<Vec<SubsonicAlbum>, String> {
+    let subsonic = state.subsonic.clone();
+    tauri::async_runtime::spawn_blocking(move || {
+        subsonic.album_list(&server_id, list, offset.unwrap_or(0), size.unwrap_or(50))
+    })
+    .await
+    .map_err(|e| format!("Failed to list albums: {}", e))?
+}
+
+/// An album's songs, with stream URLs that play and queue like paths.
+#[tauri::command]
+pub async fn get_subsonic_album_songs(
+    server_id: String,
+    album_id: String,
+    state: State<'_, AppState>,
+) -> Result<Vec<SubsonicSong>, String> {
+    let subsonic = state.subsonic.clone();
+    tauri::async_runtime::spawn_blocking(move || subsonic.album_songs(&server_id, &album_id))
+        .await
+        .map_err(|e| format!("Failed to list songs: {}", e))?
+}
+
+#[tauri::command]
+pub async fn search_subsonic(
+    server_id: String,
+    query: String,
+    limit: Option<u32>,
+    state: State<'_, AppState>,
+) -> Result<SubsonicSearchResults, String> {
+    let limit = limit.unwrap_or(subsonic::DEFAULT_SEARCH_LIMIT);
+    let subsonic = state.subsonic.clone();
+    tauri::async_runtime::spawn_blocking(move || subsonic.search(&server_id, &query, limit))
+        .await
+        .map_err(|e| format!("Search failed: {}", e))?
+}
+
+// ─── Jellyfin Servers ───
+
+#[tauri::command]
+pub fn get_jellyfin_servers(state: State<'_, AppState>) -> Vec<JellyfinServer> {
+    state.jellyfin.servers()
+}
+
+/// Check an API key with a Jellyfin server and store the server.
+/// `user_name` picks whose libraries to show; it may be left out when the
+/// server has one user.
+#[tauri::command]
+pub async fn add_jellyfin_server(
+    url: String,
+    api_key: String,
+    user_name: Option<String>,
+    state: State<'_, AppState>,
+) -> Result<JellyfinServer, String> {
+    let jellyfin = state.jellyfin.clone();
+    tauri::async_runtime::spawn_blocking(move || {
+        jellyfin.add_server(&url, &api_key, user_name.as_deref())
+    })
+    .await
+    .map_err(|e| format!("Failed to add server: {}", e))?
+}
+
+#[tauri::command]
+pub fn remove_jellyfin_server(server_id: String, state: State<'_, AppState>) -> Result<(), String> {
+    state.jellyfin.remove_server(&server_id)
+}
+
+#[tauri::command]
+pub async fn get_jellyfin_libraries(
+    server_id: String,
+    state: State<'_, AppState>,
+) -> Result<Vec<JellyfinLibrary>, String> {
+    let jellyfin = state.jellyfin.clone();
+    tauri::async_runtime::spawn_blocking(move || jellyfin.libraries(&server_id))
+        .await
+        .map_err(|e| format!("Failed to list libraries: {}", e))?
+}
+
+/// One page of album artists, from one music library or all of them.
+#[tauri::command]
+pub async fn get_jellyfin_artists(
+    server_id: String,
+    library_id: Option<String>,
+    start: Option<u32>,
+    limit: Option<u32>,
+    state: State<'_, AppState>,
+) -> Result<JellyfinPage<JellyfinArtist>, String> {
+    let limit = limit.unwrap_or(jellyfin::DEFAULT_PAGE_SIZE);
+    let jellyfin = state.jellyfin.clone();
+    tauri::async_runtime::spawn_blocking(move || {
+        jellyfin.artists(&server_id, library_id.as_deref(), start.unwrap_or(0), limit)
+    })
+    .await
+    .map_err(|e| format!("Failed to list artists: {}", e))?
+}
+
+/// One page of albums, from one music library or all of them.
+#[tauri::command]
+pub async fn get_jellyfin_albums(
+    server_id: String,
+    library_id: Option<String>,
+    start: Option<u32>,
+    limit: Option<u32>,
+    state: State<'_, AppState>,
+) -> Result<JellyfinPage<JellyfinAlbum>, String> {
+    let limit = limit.unwrap_or(jellyfin::DEFAULT_PAGE_SIZE);
+    let jellyfin = state.jellyfin.clone();
+    tauri::async_runtime::spawn_blocking(move || {
+        jellyfin.albums(&server_id, library_id.as_deref(), start.unwrap_or(0), limit)
+    })
+    .await
+    .map_err(|e| format!("Failed to list albums: {}", e))?
+}
+
+#[tauri::command]
+pub async fn get_jellyfin_artist_albums(
+    server_id: String,
+    artist_id: String,
+    state: State<'_, AppState>,
+) -> Result<Vec<JellyfinAlbum>, String> {
+    let jellyfin = state.jellyfin.clone();
+    tauri::async_runtime::spawn_blocking(move || jellyfin.artist_albums(&server_id, &artist_id))
+        .await
+        .map_err(|e| format!("Failed to list albums: {}", e))?
+}
+
+/// An album's tracks, with stream URLs that play and queue like paths.
+#[tauri::command]
+pub async fn get_jellyfin_album_tracks(
+    server_id: String,
+    album_id: String,
+    state: State<'_, AppState>,
+) -> Result<Vec<JellyfinTrack>, String> {
+    let jellyfin = state.jellyfin.clone();
+    tauri::async_runtime::spawn_blocking(move || jellyfin.album_tracks(&server_id, &album_id))
+        .await
+        .map_err(|e| format!("Failed to list tracks: {}", e))?
+}
+
+#[tauri::command]
+pub async fn search_jellyfin(
+    server_id: String,
+    query: String,
+    limit: Option<u32>,
+    state: State<'_, AppState>,
+) -> Result<JellyfinSearchResults, String> {
+    let limit = limit.unwrap_or(jellyfin::DEFAULT_SEARCH_LIMIT);
+    let jellyfin = state.jellyfin.clone();
+    tauri::async_runtime::spawn_blocking(move || jellyfin.search(&server_id, &query, limit))
+        .await
+        .map_err(|e| format!("Search failed: {}", e))?
+}
+
+// ─── Internet Radio ───
+
+/// Saved stations, in the user's order.
+#[tauri::command]
+pub fn get_radio_stations(state: State<'_, AppState>) -> Vec<RadioStation> {
+    state.radio.stations()
+}
+
+/// Save a station by its stream URL, or a playlist URL listing it.
+#[tauri::command]
+pub async fn add_radio_station(
+    name: String,
+    url: String,
+    group: Option<String>,
+    state: State<'_, AppState>,
+) -> Result<RadioStation, String> {
+    let radio = state.radio.clone();
+    tauri::async_runtime::spawn_blocking(move || radio.add_station(&name, &url, group))
+        .await
+        .map_err(|e| format!("Failed to add station: {}", e))?
+}
+
+/// Save every stream a `.pls` or `.m3u` station playlist (file or URL) lists.
+#[tauri::command]
+pub async fn import_radio_playlist(
+    path: String,
+    group: Option<String>,
+    state: State<'_, AppState>,
+) -> Result<Vec<RadioStation>, String> {
+    let radio = state.radio.clone();
+    tauri::async_runtime::spawn_blocking(move || radio.import_playlist(&path, group))
+        .await
+        .map_err(|e| format!("Import failed: {}", e))?
+}
+
+#[tauri::command]
+pub fn update_radio_station(
+    station_id: String,
+    name: String,
+    group: Option<String>,
+    favorite: bool,
+    state: State<'_, AppState>,
+) -> Result<RadioStation, String> {
+    state
+        .radio
+        .update_station(&station_id, &name, group, favorite)
+}
+
+#[tauri::command]
+pub fn move_radio_station(
+    station_id: String,
+    index: usize,
+    state: State<'_, AppState>,
+) -> Result<(), String> {
+    state.radio.move_station(&station_id, index)
+}
+
+#[tauri::command]
+pub fn remove_radio_station(station_id: String, state: State<'_, AppState>) -> Result<(), String> {
+    state.radio.remove_station(&station_id)
+}
+
+/// Play a station, as `play_file` would its stream URL.
+#[tauri::command]
+pub fn play_radio_station(station_id: String, state: State<'_, AppState>) -> Result<(), String> {
+    let station = state.radio.station(&station_id)?;
+    start_playback(&state, station.url, 0.0, None);
+    Ok(())
+}
+
+/// Queue stations by their stream URLs, titled with their names.
+#[tauri::command]
+pub fn add_radio_stations_to_queue(
+    station_ids: Vec<String>,
+    state: State<'_, AppState>,
+) -> Result<EnqueueReport, String> {
+    let streams = station_ids
+        .iter()
+        .map(|id| {
+            state.radio.station(id).map(|station| QueueStream {
+                url: station.url,
+                title: Some(station.name),
+            })
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(state.queue.lock().enqueue_streams(streams))
+}
+
+/// Titles the station announced while playing, newest last.
+#[tauri::command]
+pub fn get_radio_history(station_id: String, state: State<'_, AppState>) -> Vec<RadioTitle> {
+    state.radio.history(&station_id)
+}
+
+#[tauri::command]
+pub fn clear_radio_history(station_id: String, state: State<'_, AppState>) -> Result<(), String> {
+    state.radio.clear_history(&station_id)
 }
 
 // ─── File Dialog Commands ───
@@ -158,9 +2565,10 @@ pub async fn open_files_dialog(app: tauri::AppHandle) -> Result<Vec<String>, Str
         .file()
         .add_filter(
             "Audio Files",
-            &["flac", "mp3", "wav", "ogg", "m4a", "aac", "wma"],
+            &["flac", "mp3", "wav", "ogg", "m4a", "aac", "wma", "cue"],
         )
         .add_filter("FLAC", &["flac"])
+        .add_filter("Cue Sheets", &["cue"])
         .add_filter("All Files", &["*"])
         .blocking_pick_files();
 
@@ -0,0 +1,296 @@
+//! Converting one track: decode, encode, then tag.
+//!
+//! WAV and FLAC are written by the player's own encoders. ALAC, Opus and
+//! MP3 are encoded by `ffmpeg`, fed PCM on stdin, so it must be on the
+//! PATH for those. Tags are copied from the source afterwards (every item
+//! lofty can carry across, ReplayGain included, and the pictures); a
+//! cue-sheet track gets its fields from the library instead, with the
+//! image's cover.
+
+use lofty::file::FileType;
+use lofty::prelude::*;
+use lofty::probe::Probe;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Child, ChildStdin, Command, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use super::{ConvertFormat, ConvertOptions};
+use crate::audio::decoder::{AudioDecoder, DecodeStatus};
+use crate::audio::flac_writer::FlacWriter;
+use crate::audio::wav_writer::WavWriter;
+use crate::library::database::LibraryTrack;
+use crate::metadata::cover;
+use crate::metadata::reader;
+use crate::metadata::write_settings;
+use crate::metadata::writer::{self, MetadataFields};
+
+/// Default bitrates of the lossy formats, in kbps.
+pub const DEFAULT_OPUS_KBPS: u32 = 160;
+pub const DEFAULT_MP3_KBPS: u32 = 320;
+
+enum Sink {
+    Wav(WavWriter),
+    Flac(FlacWriter),
+    Ffmpeg(Child, ChildStdin),
+}
+
+/// Convert `track` to `dest`. `on_progress` gets the fraction done.
+/// Returns `Ok(false)` if cancelled; the partial file is removed then, and
+/// on failure.
+pub fn convert(
+    track: &LibraryTrack,
+    dest: &Path,
+    options: &ConvertOptions,
+    cancelled: &AtomicBool,
+    on_progress: &mut dyn FnMut(f64),
+) -> Result<bool, String> {
+    if let Some(folder) = dest.parent() {
+        std::fs::create_dir_all(folder).map_err(|e| format!("Failed to create folder: {}", e))?;
+    }
+    let result = encode(track, dest, options, cancelled, on_progress).and_then(|done| {
+        if done {
+            tag(track, dest, options)?;
+        }
+        Ok(done)
+    });
+    if !matches!(result, Ok(true)) {
+        let _ = std::fs::remove_file(dest);
+    }
+    result
+}
+
+fn encode(
+    track: &LibraryTrack,
+    dest: &Path,
+    options: &ConvertOptions,
+    cancelled: &AtomicBool,
+    on_progress: &mut dyn FnMut(f64),
+) -> Result<bool, String> {
+    let file = track.source_path.as_deref().unwrap_or(&track.path);
+    let mut decoder = AudioDecoder::open(file)?;
+    let rate = decoder.sample_rate();
+    let channels = decoder.channels();
+    // Segment of a cue-sheet track
+    let start = track
+        .source_path
+        .as_ref()
+        .and(track.start_secs)
+        .unwrap_or(0.0);
+    if start > 0.0 {
+        decoder.seek(start)?;
+    }
+    let mut remaining = track
+        .source_path
+        .as_ref()
+        .and(track.end_secs)
+        .map(|end| ((end - start).max(0.0) * f64::from(rate)) as u64);
+    let total_frames = remaining
+        .unwrap_or((decoder.duration_secs.max(0.0) * f64::from(rate)) as u64)
+        .max(1);
+
+    let bits = options
+        .bit_depth
+        .unwrap_or(match decoder.bit_depth() {
+            Some(b) if b > 16 => 24,
+            _ => 16,
+        })
+        .min(24);
+    let mut sink = match options.format {
+        ConvertFormat::Wav => Sink::Wav(WavWriter::create(dest, rate, channels as u16, bits)?),
+        ConvertFormat::Flac => Sink::Flac(FlacWriter::create(dest, rate, channels as u16, bits)?),
+        format => {
+            let mut child = ffmpeg(format, options, dest, rate, channels, bits)?;
+            let stdin = child.stdin.take().ok_or("ffmpeg has no input")?;
+            Sink::Ffmpeg(child, stdin)
+        }
+    };
+
+    let scale = f64::from(1u32 << (bits - 1));
+    let mut frames: u64 = 0;
+    let mut reported = 0.0;
+    loop {
+        if cancelled.load(Ordering::Relaxed) {
+            if let Sink::Ffmpeg(mut child, stdin) = sink {
+                drop(stdin);
+                let _ = child.kill();
+                let _ = child.wait();
+            }
+            return Ok(false);
+        }
+        let mut samples = match decoder.next_samples() {
+            Ok(s) => s,
+            Err(DecodeStatus::EndOfStream) => break,
+            Err(DecodeStatus::Error(e)) => return Err(format!("Decode failed: {}", e)),
+        };
+        if let Some(left) = remaining.as_mut() {
+            let keep = (*left as usize).min(samples.len() / channels);
+            samples.truncate(keep * channels);
+            *left -= keep as u64;
+        }
+        let ints: Vec<i32> = samples
+            .iter()
+            .map(|&s| (f64::from(s) * scale).round().clamp(-scale, scale - 1.0) as i32)
+            .collect();
+        match &mut sink {
+            Sink::Wav(w) => w.write(&ints)?,
+            Sink::Flac(w) => w.write(&ints)?,
+            Sink::Ffmpeg(_, stdin) => {
+                let width = usize::from(bits / 8);
+                let mut bytes = Vec::with_capacity(ints.len() * width);
+                for s in &ints {
+                    bytes.extend_from_slice(&s.to_le_bytes()[..width]);
+                }
+                // A broken pipe means ffmpeg quit; its own message follows
+                if stdin.write_all(&bytes).is_err() {
+                    break;
+                }
+            }
+        }
+
+        frames += (samples.len() / channels) as u64;
+        let done = (frames as f64 / total_frames as f64).min(1.0);
+        if done - reported >= 0.01 {
+            reported = done;
+            on_progress(done);
+        }
+        if remaining == Some(0) {
+            break;
+        }
+    }
+
+    match sink {
+        Sink::Wav(w) => w.finish()?,
+        Sink::Flac(w) => w.finish()?,
+        Sink::Ffmpeg(child, stdin) => {
+            drop(stdin);
+            let output = child
+                .wait_with_output()
+                .map_err(|e| format!("ffmpeg failed: {}", e))?;
+            if !output.status.success() {
+                let message = String::from_utf8_lossy(&output.stderr);
+                return Err(format!("ffmpeg failed: {}", message.trim()));
+            }
+        }
+    }
+    Ok(true)
+}
+
+/// Start `ffmpeg` encoding raw PCM from stdin to `dest`.
+fn ffmpeg(
+    format: ConvertFormat,
+    options: &ConvertOptions,
+    dest: &Path,
+    rate: u32,
+    channels: usize,
+    bits: u8,
+) -> Result<Child, String> {
+    let program = find_ffmpeg().ok_or_else(|| {
+        format!(
+            "Converting to {} needs ffmpeg, which isn't on the PATH",
+            format.name()
+        )
+    })?;
+    let mut command = Command::new(program);
+    command
+        .args(["-hide_banner", "-loglevel", "error", "-y"])
+        .args(["-f", if bits > 16 { "s24le" } else { "s16le" }])
+        .args(["-ar", &rate.to_string(), "-ac", &channels.to_string()])
+        .args(["-i", "pipe:0", "-map_metadata", "-1", "-vn"]);
+    match format {
+        ConvertFormat::Alac => {
+            command.args(["-c:a", "alac"]);
+        }
+        ConvertFormat::Opus => {
+            let kbps = options.bitrate_kbps.unwrap_or(DEFAULT_OPUS_KBPS);
+            command.args(["-c:a", "libopus", "-b:a", &format!("{}k", kbps)]);
+        }
+        ConvertFormat::Mp3 => {
+            let kbps = options.bitrate_kbps.unwrap_or(DEFAULT_MP3_KBPS);
+            command.args(["-c:a", "libmp3lame", "-b:a", &format!("{}k", kbps)]);
+        }
+        ConvertFormat::Wav | ConvertFormat::Flac => {}
+    }
+    command
+        .arg(dest)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped());
+    #[cfg(windows)]
+    {
+        use std::os::windows::process::CommandExt;
+        // No console window flashing up for each file
+        const CREATE_NO_WINDOW: u32 = 0x0800_0000;
+        command.creation_flags(CREATE_NO_WINDOW);
+    }
+    command
+        .spawn()
+        .map_err(|e| format!("Failed to start ffmpeg: {}", e))
+}
+
+/// `ffmpeg` on the PATH.
+pub fn find_ffmpeg() -> Option<PathBuf> {
+    let name = if cfg!(windows) {
+        "ffmpeg.exe"
+    } else {
+        "ffmpeg"
+    };
+    std::env::split_paths(&std::env::var_os("PATH")?)
+        .map(|dir| dir.join(name))
+        .find(|path| path.is_file())
+}
+
+fn tag(track: &LibraryTrack, dest: &Path, options: &ConvertOptions) -> Result<(), String> {
+    let dest = dest.to_string_lossy().to_string();
+    match &track.source_path {
+        None => copy_tags(&track.path, &dest, options.keep_art)?,
+        Some(image) => {
+            writer::write_metadata(
+                &dest,
+                &MetadataFields {
+                    title: track.title.clone(),
+                    artist: track.artist.clone(),
+                    album: track.album.clone(),
+                    album_artist: track.album_artist.clone(),
+                    year: track.year,
+                    genre: track.genre.clone(),
+                    track_number: track.track_number,
+                    disc_number: track.disc_number,
+                },
+            )?;
+            if options.keep_art {
+                if let Some(art) = reader::read_cover_art(image)? {
+                    cover::embed_cover(&dest, &art)?;
+                }
+            }
+        }
+    }
+    if let Some(resize) = options.art_resize.as_ref().filter(|_| options.keep_art) {
+        // Every picture, however small, goes through the resize
+        cover::shrink_pictures(&dest, 0, Some(resize))?;
+    }
+    Ok(())
+}
+
+/// The source's primary tag, rewritten as the destination's kind of tag.
+fn copy_tags(source: &str, dest: &str, keep_art: bool) -> Result<(), String> {
+    let tagged_file = Probe::open(source)
+        .map_err(|e| format!("Failed to open file: {}", e))?
+        .read()
+        .map_err(|e| format!("Failed to read tags: {}", e))?;
+    let Some(mut tag) = tagged_file
+        .primary_tag()
+        .or_else(|| tagged_file.first_tag())
+        .cloned()
+    else {
+        return Ok(());
+    };
+    let dest_type = FileType::from_path(dest).ok_or("Converted file has an unknown type")?;
+    tag.re_map(dest_type.primary_tag_type());
+    if !keep_art {
+        while tag.picture_count() > 0 {
+            tag.remove_picture(0);
+        }
+    }
+    write_settings::save_tag(&tag, dest)
+}
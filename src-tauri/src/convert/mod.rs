@@ -0,0 +1,370 @@
+//! Converting library tracks to other formats, e.g. to copy them to a phone
+//! or a DAP.
+//!
+//! Tracks are queued as jobs, one per track, and converted one at a time
+//! by a worker thread in the order they were added. Each job's path comes
+//! from a tag pattern, as in [`crate::library::organize`], under a chosen
+//! folder, with the new format's extension. Every change to a job (started,
+//! a percent further, finished) is reported to the worker's callback. See
+//! [`encode`] for how a track is converted.
+
+pub mod encode;
+
+use crossbeam_channel::{Receiver, Sender};
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::library::database::LibraryTrack;
+use crate::library::organize::{self, CollisionPolicy};
+use crate::metadata::cover::ArtResize;
+
+/// Pattern used when the caller gives none.
+pub const DEFAULT_PATTERN: &str = "%albumartist%/%album%/%track% - %title%";
+
+/// Least time between progress reports of a job.
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(250);
+
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ConvertFormat {
+    Wav,
+    Flac,
+    /// Apple Lossless in an `.m4a`.
+    Alac,
+    Opus,
+    Mp3,
+}
+
+impl ConvertFormat {
+    pub fn extension(self) -> &'static str {
+        match self {
+            ConvertFormat::Wav => "wav",
+            ConvertFormat::Flac => "flac",
+            ConvertFormat::Alac => "m4a",
+            ConvertFormat::Opus => "opus",
+            ConvertFormat::Mp3 => "mp3",
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ConvertFormat::Wav => "WAV",
+            ConvertFormat::Flac => "FLAC",
+            ConvertFormat::Alac => "ALAC",
+            ConvertFormat::Opus => "Opus",
+            ConvertFormat::Mp3 => "MP3",
+        }
+    }
+
+    fn needs_ffmpeg(self) -> bool {
+        matches!(
+            self,
+            ConvertFormat::Alac | ConvertFormat::Opus | ConvertFormat::Mp3
+        )
+    }
+}
+
+#[derive(Clone, Deserialize)]
+pub struct ConvertOptions {
+    pub format: ConvertFormat,
+    /// Folder the pattern's paths start from.
+    pub destination: String,
+    /// e.g. `%albumartist%/%album%/%track% - %title%`.
+    #[serde(default = "default_pattern")]
+    pub pattern: String,
+    #[serde(default)]
+    pub collision: CollisionPolicy,
+    /// 16 or 24 for the lossless formats; the source's (at most 24) when
+    /// omitted.
+    #[serde(default)]
+    pub bit_depth: Option<u8>,
+    /// For Opus and MP3; 160 and 320 kbps when omitted.
+    #[serde(default)]
+    pub bitrate_kbps: Option<u32>,
+    /// Copy embedded artwork.
+    #[serde(default = "default_true")]
+    pub keep_art: bool,
+    /// Shrink the copied artwork, as many players want small covers.
+    #[serde(default)]
+    pub art_resize: Option<ArtResize>,
+}
+
+fn default_pattern() -> String {
+    DEFAULT_PATTERN.to_string()
+}
+
+fn default_true() -> bool {
+    true
+}
+
+#[derive(Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JobStatus {
+    Queued,
+    Running,
+    Done,
+    Failed,
+    Cancelled,
+    /// Not converted; see `error`.
+    Skipped,
+}
+
+#[derive(Clone, Serialize)]
+pub struct ConvertJob {
+    pub id: u64,
+    pub track_id: i64,
+    pub source: String,
+    pub destination: String,
+    pub format: ConvertFormat,
+    pub status: JobStatus,
+    /// 0 to 1.
+    pub progress: f64,
+    pub error: Option<String>,
+}
+
+struct Entry {
+    job: ConvertJob,
+    track: LibraryTrack,
+    options: Arc<ConvertOptions>,
+    cancelled: Arc<AtomicBool>,
+}
+
+pub struct Converter {
+    entries: Mutex<Vec<Entry>>,
+    queue: Sender<u64>,
+    /// Taken by [`Converter::spawn_worker`].
+    pending: Mutex<Option<Receiver<u64>>>,
+    next_id: AtomicU64,
+}
+
+impl Default for Converter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Converter {
+    /// An empty queue. Jobs wait until [`Converter::spawn_worker`].
+    pub fn new() -> Self {
+        let (queue, pending) = crossbeam_channel::unbounded();
+        Self {
+            entries: Mutex::new(Vec::new()),
+            queue,
+            pending: Mutex::new(Some(pending)),
+            next_id: AtomicU64::new(1),
+        }
+    }
+
+    /// Every job since the app started or the list was last cleared,
+    /// oldest first.
+    pub fn jobs(&self) -> Vec<ConvertJob> {
+        self.entries.lock().iter().map(|e| e.job.clone()).collect()
+    }
+
+    /// Queue a job per track. Tracks whose path is taken (and the policy
+    /// says skip) come back as skipped jobs.
+    pub fn enqueue(
+        &self,
+        tracks: Vec<LibraryTrack>,
+        options: ConvertOptions,
+    ) -> Result<Vec<ConvertJob>, String> {
+        organize::check_pattern(&options.pattern)?;
+        if options.destination.trim().is_empty() {
+            return Err("Choose a folder to convert to".to_string());
+        }
+        if options.bit_depth.is_some_and(|b| b != 16 && b != 24) {
+            return Err("Bit depth must be 16 or 24".to_string());
+        }
+        if options.format.needs_ffmpeg() && encode::find_ffmpeg().is_none() {
+            return Err(format!(
+                "Converting to {} needs ffmpeg, which isn't on the PATH",
+                options.format.name()
+            ));
+        }
+
+        let options = Arc::new(options);
+        let mut entries = self.entries.lock();
+        // Paths of jobs still to run, which a new job mustn't write over
+        let mut claimed: HashSet<String> = entries
+            .iter()
+            .filter(|e| matches!(e.job.status, JobStatus::Queued | JobStatus::Running))
+            .map(|e| e.job.destination.to_lowercase())
+            .collect();
+
+        let mut added = Vec::with_capacity(tracks.len());
+        for track in tracks {
+            let target = organize::target_path(
+                &options.destination,
+                &options.pattern,
+                &track,
+                Some(options.format.extension()),
+            );
+            let (destination, skipped) = place(&track, target, options.collision, &mut claimed);
+            let job = ConvertJob {
+                id: self.next_id.fetch_add(1, Ordering::Relaxed),
+                track_id: track.id,
+                source: track.path.clone(),
+                destination: destination.to_string_lossy().to_string(),
+                format: options.format,
+                status: if skipped.is_some() {
+                    JobStatus::Skipped
+                } else {
+                    JobStatus::Queued
+                },
+                progress: 0.0,
+                error: skipped,
+            };
+            if job.status == JobStatus::Queued {
+                let _ = self.queue.send(job.id);
+            }
+            added.push(job.clone());
+            entries.push(Entry {
+                job,
+                track,
+                options: options.clone(),
+                cancelled: Arc::new(AtomicBool::new(false)),
+            });
+        }
+        Ok(added)
+    }
+
+    /// Cancel a queued or running job. Returns false if it had already
+    /// finished.
+    pub fn cancel(&self, id: u64) -> bool {
+        let mut entries = self.entries.lock();
+        let Some(entry) = entries.iter_mut().find(|e| e.job.id == id) else {
+            return false;
+        };
+        match entry.job.status {
+            JobStatus::Queued => {
+                entry.job.status = JobStatus::Cancelled;
+                true
+            }
+            JobStatus::Running => {
+                entry.cancelled.store(true, Ordering::Relaxed);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Forget finished, failed, cancelled and skipped jobs.
+    pub fn clear_finished(&self) {
+        self.entries
+            .lock()
+            .retain(|e| matches!(e.job.status, JobStatus::Queued | JobStatus::Running));
+    }
+
+    /// Start the thread that runs the queue. `on_update` is called from it
+    /// with a job whenever it changes.
+    pub fn spawn_worker(self: &Arc<Self>, mut on_update: impl FnMut(&ConvertJob) + Send + 'static) {
+        let Some(queue) = self.pending.lock().take() else {
+            return;
+        };
+        let converter = self.clone();
+        thread::Builder::new()
+            .name("converter".into())
+            .spawn(move || {
+                for id in queue {
+                    converter.run_job(id, &mut on_update);
+                }
+            })
+            .expect("Failed to spawn converter thread");
+    }
+
+    fn run_job(&self, id: u64, on_update: &mut dyn FnMut(&ConvertJob)) {
+        let (track, options, cancelled, destination) = {
+            let mut entries = self.entries.lock();
+            let Some(entry) = entries.iter_mut().find(|e| e.job.id == id) else {
+                return;
+            };
+            if entry.job.status != JobStatus::Queued {
+                return;
+            }
+            entry.job.status = JobStatus::Running;
+            on_update(&entry.job);
+            (
+                entry.track.clone(),
+                entry.options.clone(),
+                entry.cancelled.clone(),
+                PathBuf::from(&entry.job.destination),
+            )
+        };
+
+        let mut reported = Instant::now();
+        let result = encode::convert(&track, &destination, &options, &cancelled, &mut |done| {
+            let job = self.update(id, |job| job.progress = done);
+            if let Some(job) = job.filter(|_| reported.elapsed() >= PROGRESS_INTERVAL) {
+                reported = Instant::now();
+                on_update(&job);
+            }
+        });
+        let job = self.update(id, |job| match result {
+            Ok(true) => {
+                job.status = JobStatus::Done;
+                job.progress = 1.0;
+            }
+            Ok(false) => job.status = JobStatus::Cancelled,
+            Err(e) => {
+                log::warn!("Converting {} failed: {}", job.source, e);
+                job.status = JobStatus::Failed;
+                job.error = Some(e);
+            }
+        });
+        if let Some(job) = job {
+            on_update(&job);
+        }
+    }
+
+    /// Change a job, returning it as changed.
+    fn update(&self, id: u64, change: impl FnOnce(&mut ConvertJob)) -> Option<ConvertJob> {
+        let mut entries = self.entries.lock();
+        let entry = entries.iter_mut().find(|e| e.job.id == id)?;
+        change(&mut entry.job);
+        Some(entry.job.clone())
+    }
+}
+
+/// Where a job writes: `target`, or a numbered variant of it, as `policy`
+/// says. The reason comes back instead if the job is skipped.
+fn place(
+    track: &LibraryTrack,
+    target: PathBuf,
+    policy: CollisionPolicy,
+    claimed: &mut HashSet<String>,
+) -> (PathBuf, Option<String>) {
+    let source = track.source_path.as_deref().unwrap_or(&track.path);
+    if Path::new(source) == target {
+        return (target, Some("Would overwrite the source file".to_string()));
+    }
+    // Case-insensitive filesystems see "a.flac" and "A.flac" as one file
+    let taken = |path: &Path, claimed: &HashSet<String>| {
+        path.exists() || claimed.contains(&path.to_string_lossy().to_lowercase())
+    };
+    let mut path = target.clone();
+    if taken(&path, claimed) {
+        match policy {
+            CollisionPolicy::Skip => {
+                return (
+                    target,
+                    Some("A file with that name already exists".to_string()),
+                );
+            }
+            CollisionPolicy::Number => {
+                let mut n = 2;
+                while taken(&path, claimed) {
+                    path = organize::numbered(&target, n);
+                    n += 1;
+                }
+            }
+        }
+    }
+    claimed.insert(path.to_string_lossy().to_lowercase());
+    (path, None)
+}
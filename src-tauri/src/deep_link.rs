@@ -0,0 +1,149 @@
+//! `masukii://` links, so other apps, browser extensions and scripts can
+//! drive the player:
+//!
+//! - `masukii://play?path=<file>`: play a file or cue-sheet track, leaving
+//!   the queue alone; `?track=<id>` plays a library track instead
+//! - `masukii://enqueue?path=<file>&path=...`: add to the queue; `track=<id>`
+//!   and `album=<id>` add library tracks and whole albums, in link order
+//! - `masukii://album/<id>`, `masukii://track/<id>`: show an album or track,
+//!   emitted as `deep-link://open` (a [`Destination`]) for the UI
+//! - `masukii://pause`, `resume`, `toggle`, `stop`, `next`, `previous`
+//!
+//! Values are percent-encoded. Links reach the app as arguments on Windows
+//! and Linux, from the first launch or forwarded by a second one, and from
+//! the deep-link plugin on macOS and mobile. The plugin also registers the
+//! scheme (`plugins.deep-link` in `tauri.conf.json`) for builds that weren't
+//! installed. Playback links run as the matching [`RemoteCommand`], so the
+//! UI sees them as `remote://command` too.
+
+use serde::Serialize;
+use tauri::{AppHandle, Emitter, Manager, Url};
+
+use crate::commands::AppState;
+use crate::library::database::LibraryTrack;
+use crate::remote::api::{self, RemoteCommand};
+
+pub const SCHEME: &str = "masukii";
+
+/// Payload of `deep-link://open`.
+#[derive(Clone, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Destination {
+    Album { id: i64 },
+    Track { id: i64, album_id: Option<i64> },
+}
+
+/// Whether a command-line argument is a link rather than a file.
+pub fn is_link(arg: &str) -> bool {
+    arg.get(..SCHEME.len() + 1)
+        .is_some_and(|prefix| prefix.eq_ignore_ascii_case(&format!("{}:", SCHEME)))
+}
+
+/// Act on a link.
+pub fn open(app: &AppHandle, link: &str) -> Result<(), String> {
+    let url = Url::parse(link).map_err(|e| format!("Invalid link {}: {}", link, e))?;
+    if url.scheme() != SCHEME {
+        return Err(format!("Not a {}:// link: {}", SCHEME, link));
+    }
+    let action = url.host_str().unwrap_or_default().to_ascii_lowercase();
+    let id = url.path().trim_matches('/');
+    let query: Vec<(String, String)> = url.query_pairs().into_owned().collect();
+    let first = |name: &str| {
+        query
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, v)| v.as_str())
+    };
+
+    let command = match action.as_str() {
+        "play" => {
+            let path = match (first("path"), first("track")) {
+                (Some(path), _) => path.to_string(),
+                (None, Some(id)) => track(app, parse_id(id)?)?.path,
+                (None, None) => return Err("play needs a path or track".to_string()),
+            };
+            RemoteCommand::Play { path }
+        }
+        "enqueue" => {
+            let mut paths = Vec::new();
+            for (name, value) in &query {
+                match name.as_str() {
+                    "path" => paths.push(value.clone()),
+                    "track" => paths.push(track(app, parse_id(value)?)?.path),
+                    "album" => {
+                        let tracks = app
+                            .state::<AppState>()
+                            .library
+                            .lock()
+                            .album_tracks(parse_id(value)?)?;
+                        paths.extend(tracks.into_iter().map(|t| t.path));
+                    }
+                    _ => {}
+                }
+            }
+            if paths.is_empty() {
+                return Err("Nothing to enqueue".to_string());
+            }
+            RemoteCommand::Enqueue { paths }
+        }
+        "album" => {
+            let id = parse_id(id)?;
+            app.state::<AppState>().library.lock().album(id)?;
+            return emit(app, Destination::Album { id });
+        }
+        "track" => {
+            let track = track(app, parse_id(id)?)?;
+            return emit(
+                app,
+                Destination::Track {
+                    id: track.id,
+                    album_id: track.album_id,
+                },
+            );
+        }
+        "pause" => RemoteCommand::Pause,
+        "resume" => RemoteCommand::Resume,
+        "toggle" => RemoteCommand::Toggle,
+        "stop" => RemoteCommand::Stop,
+        "next" => RemoteCommand::Next,
+        "previous" => RemoteCommand::Previous,
+        _ => return Err(format!("Unknown link action: {}", action)),
+    };
+    api::run(app, command).map(|_| ())
+}
+
+/// Open every link among a launch's arguments, logging the ones that fail,
+/// and return the rest.
+pub fn open_args(app: &AppHandle, args: Vec<String>) -> Vec<String> {
+    let (links, rest): (Vec<String>, Vec<String>) = args.into_iter().partition(|a| is_link(a));
+    for link in links {
+        if let Err(e) = open(app, &link) {
+            log::warn!("{}", e);
+        }
+    }
+    rest
+}
+
+fn track(app: &AppHandle, id: i64) -> Result<LibraryTrack, String> {
+    app.state::<AppState>()
+        .library
+        .lock()
+        .tracks_by_ids(&[id])?
+        .into_iter()
+        .next()
+        .ok_or_else(|| format!("Track {} not found", id))
+}
+
+fn parse_id(text: &str) -> Result<i64, String> {
+    text.parse()
+        .map_err(|_| format!("Invalid library id: {}", text))
+}
+
+fn emit(app: &AppHandle, destination: Destination) -> Result<(), String> {
+    if let Some(window) = app.get_webview_window("main") {
+        let _ = window.unminimize();
+        let _ = window.set_focus();
+    }
+    app.emit("deep-link://open", destination)
+        .map_err(|e| format!("Failed to emit deep link: {}", e))
+}
@@ -0,0 +1,177 @@
+//! DSP plugins from the `plugins` folder in the app data directory, and the
+//! chain of them playback runs.
+//!
+//! The plugin interface itself is in [`crate::audio::dsp_plugin`]. Here the
+//! folder is scanned at startup and on request. The chain is saved in the
+//! settings by plugin id, with parameters by their ids, so it survives
+//! plugins being rebuilt or reordered in the folder. An entry whose plugin
+//! is missing is skipped and reported. Values outside a parameter's range
+//! are clamped, and missing ones take the default.
+
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use crate::audio::dsp_plugin::{self, ChainSlot, DspPlugin, PluginInfo, PluginLoadError};
+use crate::audio::engine::{AudioCommand, AudioEngine};
+use crate::errors::{self, ErrorCode};
+
+const PLUGINS_DIR: &str = "plugins";
+
+/// Longest chain allowed in the settings.
+pub const MAX_CHAIN_LEN: usize = 16;
+
+/// A plugin in the chain, as saved.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct ChainEntry {
+    /// The plugin's id.
+    pub plugin: String,
+    pub enabled: bool,
+    /// By parameter id; missing ones take the default.
+    #[serde(default)]
+    pub params: BTreeMap<String, f32>,
+}
+
+#[derive(Clone, Serialize)]
+pub struct PluginList {
+    /// Where to put plugins.
+    pub folder: String,
+    pub plugins: Vec<PluginInfo>,
+    /// Libraries that failed to load, and why.
+    pub failed: Vec<PluginLoadError>,
+}
+
+/// Each plugin in a chain by id, with its values.
+type ChainKey = Vec<(String, Vec<f32>)>;
+
+pub struct DspPlugins {
+    dir: PathBuf,
+    plugins: Mutex<Vec<Arc<DspPlugin>>>,
+    failed: Mutex<Vec<PluginLoadError>>,
+    /// What the engine was last given.
+    applied: Mutex<Option<ChainKey>>,
+}
+
+impl DspPlugins {
+    /// Create the plugins folder if needed and load what's in it.
+    pub fn load(app_data_dir: &Path) -> Self {
+        let dir = app_data_dir.join(PLUGINS_DIR);
+        if let Err(e) = std::fs::create_dir_all(&dir) {
+            log::warn!("Failed to create {}: {}", dir.display(), e);
+        }
+        let plugins = Self {
+            dir,
+            plugins: Mutex::new(Vec::new()),
+            failed: Mutex::new(Vec::new()),
+            applied: Mutex::new(None),
+        };
+        plugins.rescan();
+        plugins
+    }
+
+    pub fn list(&self) -> PluginList {
+        PluginList {
+            folder: self.dir.to_string_lossy().to_string(),
+            plugins: self
+                .plugins
+                .lock()
+                .iter()
+                .map(|p| p.info().clone())
+                .collect(),
+            failed: self.failed.lock().clone(),
+        }
+    }
+
+    /// Load the plugins in the folder again, e.g. after adding one. The
+    /// chain picks up the new ones at the next [`Self::apply`].
+    pub fn rescan(&self) -> PluginList {
+        let discovery = dsp_plugin::discover(&self.dir);
+        for failure in &discovery.failed {
+            errors::report(
+                ErrorCode::DspPlugin,
+                format!(
+                    "Failed to load DSP plugin {}: {}",
+                    failure.path, failure.error
+                ),
+            );
+        }
+        *self.plugins.lock() = discovery.plugins;
+        *self.failed.lock() = discovery.failed;
+        *self.applied.lock() = None;
+        self.list()
+    }
+
+    /// Give the engine the enabled plugins in `chain`, if it doesn't have
+    /// them already.
+    pub fn apply(&self, chain: &[ChainEntry], engine: &AudioEngine) {
+        let plugins = self.plugins.lock();
+        let mut slots = Vec::new();
+        let mut missing = Vec::new();
+        for entry in chain.iter().filter(|e| e.enabled) {
+            let Some(plugin) = plugins.iter().find(|p| p.id() == entry.plugin) else {
+                missing.push(entry.plugin.as_str());
+                continue;
+            };
+            let params = plugin
+                .params()
+                .iter()
+                .map(|p| p.clamp(entry.params.get(&p.id).copied().unwrap_or(p.default_value)))
+                .collect();
+            slots.push(ChainSlot {
+                plugin: plugin.clone(),
+                params,
+            });
+        }
+
+        let key: ChainKey = slots
+            .iter()
+            .map(|s| (s.plugin.id().to_string(), s.params.clone()))
+            .collect();
+        let mut applied = self.applied.lock();
+        if applied.as_ref() == Some(&key) {
+            return;
+        }
+        for id in missing {
+            errors::report(
+                ErrorCode::DspPlugin,
+                format!("DSP plugin {} isn't installed; skipping it", id),
+            );
+        }
+        let ids: Vec<&str> = key.iter().map(|(id, _)| id.as_str()).collect();
+        log::info!("DSP chain: {}", ids.join(" → "));
+        engine.send_command(AudioCommand::SetDspChain(slots));
+        *applied = Some(key);
+    }
+}
+
+/// Check a chain before saving it.
+pub fn validate(chain: &[ChainEntry]) -> Result<(), String> {
+    if chain.len() > MAX_CHAIN_LEN {
+        return Err(format!(
+            "The DSP chain can have at most {} plugins",
+            MAX_CHAIN_LEN
+        ));
+    }
+    for entry in chain {
+        if entry.plugin.trim().is_empty() {
+            return Err("DSP chain entry has no plugin".to_string());
+        }
+        if entry.params.values().any(|v| !v.is_finite()) {
+            return Err(format!("Invalid parameter value for {}", entry.plugin));
+        }
+    }
+    Ok(())
+}
+
+/// Make a chain loaded from disk pass [`validate`]: entries without a
+/// plugin are dropped, invalid parameter values go back to their defaults
+/// and plugins past the limit are cut off.
+pub fn repair(chain: &mut Vec<ChainEntry>) {
+    chain.retain(|entry| !entry.plugin.trim().is_empty());
+    chain.truncate(MAX_CHAIN_LEN);
+    for entry in chain {
+        entry.params.retain(|_, v| v.is_finite());
+    }
+}
@@ -0,0 +1,138 @@
+//! `app://error`: failures no command returned, so the UI can show them
+//! instead of playback or saving quietly misbehaving. Each has an
+//! [`ErrorCode`] to act on and a message to show.
+//!
+//! Subsystems call [`report`], which logs the error too; repeats within a
+//! minute are only logged. Errors from before the window is up (loading
+//! settings, opening the library) are only kept: the last [`RECENT_LEN`]
+//! are in [`recent`] for the UI to read when it starts.
+
+use parking_lot::Mutex;
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::sync::{Arc, OnceLock};
+use std::thread;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tauri::{AppHandle, Emitter};
+
+use crate::audio::engine::{AudioEngine, EngineError, EngineErrorKind};
+
+/// Errors kept for [`recent`].
+pub const RECENT_LEN: usize = 50;
+
+/// The same error again within this long is only logged, so something
+/// failing on every tick doesn't flood the UI.
+const REPEAT_MS: u64 = 60_000;
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorCode {
+    /// A file or stream couldn't be opened for playback.
+    PlaybackOpen,
+    PlaybackSeek,
+    /// Decoding failed mid-track and playback stopped.
+    PlaybackDecode,
+    /// No output device, or it failed or went away.
+    OutputDevice,
+    /// ReplayGain is on but a file's tags couldn't be read.
+    ReplayGainTags,
+    /// A settings or profile file was unreadable and defaults are in use.
+    ConfigLoad,
+    /// Something couldn't be written to disk in the background.
+    SaveFailed,
+    /// The library database couldn't be opened; it's in memory for now.
+    LibraryOpen,
+    Recording,
+    /// A DSP plugin failed to load, start or process and is left out.
+    DspPlugin,
+}
+
+#[derive(Clone, Serialize)]
+pub struct AppError {
+    pub code: ErrorCode,
+    pub message: String,
+    /// File or URL it's about, if any.
+    pub path: Option<String>,
+    /// Unix time in milliseconds.
+    pub timestamp_ms: u64,
+}
+
+static APP: OnceLock<AppHandle> = OnceLock::new();
+static RECENT: Mutex<VecDeque<AppError>> = Mutex::new(VecDeque::new());
+
+/// Emit errors from here on, and forward the engine's.
+pub fn init(app: &AppHandle, engine: Arc<AudioEngine>) {
+    let _ = APP.set(app.clone());
+    let errors = engine.errors();
+    thread::Builder::new()
+        .name("engine-errors".into())
+        .spawn(move || {
+            // Already logged by the engine
+            for error in errors {
+                publish(from_engine(error));
+            }
+        })
+        .expect("Failed to spawn engine error thread");
+}
+
+/// Log an error and tell the UI.
+pub fn report(code: ErrorCode, message: impl Into<String>) {
+    let message = message.into();
+    log::error!("{}", message);
+    publish(AppError {
+        code,
+        message,
+        path: None,
+        timestamp_ms: now_ms(),
+    });
+}
+
+/// The last [`RECENT_LEN`] errors, oldest first.
+pub fn recent() -> Vec<AppError> {
+    RECENT.lock().iter().cloned().collect()
+}
+
+fn publish(error: AppError) {
+    {
+        let mut recent = RECENT.lock();
+        let repeat = recent.iter().rev().any(|e| {
+            e.code == error.code
+                && e.message == error.message
+                && error.timestamp_ms.saturating_sub(e.timestamp_ms) < REPEAT_MS
+        });
+        if repeat {
+            return;
+        }
+        if recent.len() == RECENT_LEN {
+            recent.pop_front();
+        }
+        recent.push_back(error.clone());
+    }
+    if let Some(app) = APP.get() {
+        let _ = app.emit("app://error", error);
+    }
+}
+
+fn from_engine(error: EngineError) -> AppError {
+    let code = match error.kind {
+        EngineErrorKind::Open => ErrorCode::PlaybackOpen,
+        EngineErrorKind::Seek => ErrorCode::PlaybackSeek,
+        EngineErrorKind::Decode => ErrorCode::PlaybackDecode,
+        EngineErrorKind::Output => ErrorCode::OutputDevice,
+        EngineErrorKind::ReplayGainTags => ErrorCode::ReplayGainTags,
+        EngineErrorKind::Plugin => ErrorCode::DspPlugin,
+    };
+    AppError {
+        code,
+        message: error.message,
+        path: error.path,
+        timestamp_ms: now_ms(),
+    }
+}
+
+fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
@@ -1,34 +1,239 @@
+pub mod art_protocol;
 pub mod audio;
+pub mod buffering;
 pub mod commands;
+pub mod convert;
+pub mod deep_link;
+pub mod dsp_plugins;
+pub mod errors;
 pub mod library;
+pub mod listenbrainz;
+pub mod logging;
+#[cfg(any(windows, target_os = "macos"))]
+pub mod media_controls;
+#[cfg(any(target_os = "android", target_os = "ios"))]
+pub mod media_session;
 pub mod metadata;
+pub mod mqtt;
 pub mod playlist;
+pub mod remote;
+pub mod scripting;
+pub mod settings;
+pub mod sources;
+#[cfg(windows)]
+pub mod taskbar;
+
+// Shared with the audio engine, which lives in its own crate
+pub use masukii_audio::{http, paths};
 
 use audio::device_profiles::DeviceProfileStore;
+use audio::recorder::Recorder;
+use audio::snapcast::SnapcastOutput;
+use audio::{loudness_meter, lyrics_sync};
+use buffering::Buffering;
 use commands::AppState;
+use convert::Converter;
+use dsp_plugins::DspPlugins;
+use library::bookmarks::{self, BookmarkStore};
+use library::database::LibraryDb;
+use library::exclude::ExcludeRules;
+use library::plays;
+use library::scanner::ScanControl;
+use listenbrainz::ListenBrainz;
+use metadata::write_settings::{self, TagWriteSettings};
+use mqtt::MqttBridge;
 use parking_lot::Mutex;
+use playlist::manager::PlaylistStore;
+use playlist::queue::PlayQueue;
+use remote::RemoteServer;
+use scripting::Scripts;
+use settings::SettingsStore;
+use sources::jellyfin::Jellyfin;
+use sources::radio::Radio;
+use sources::subsonic::Subsonic;
 use std::path::PathBuf;
 use std::sync::Arc;
+use tauri::{Emitter, Manager};
+use tauri_plugin_deep_link::DeepLinkExt;
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
-    let engine = Arc::new(audio::engine::AudioEngine::new());
-
     // App data directory for storing profiles, library DB, etc.
     let app_data_dir = dirs_next::data_dir()
         .unwrap_or_else(|| PathBuf::from("."))
         .join("masukii");
+    logging::init(Some(&app_data_dir));
+    let engine = Arc::new(audio::engine::AudioEngine::new());
 
+    let settings = Arc::new(SettingsStore::load(&app_data_dir));
+    settings.get().apply(&engine);
+    let buffering = Arc::new(Buffering::new());
+    buffering.apply(settings.get().playback.buffer_mode, &engine);
+    let dsp_plugins = Arc::new(DspPlugins::load(&app_data_dir));
+    dsp_plugins.apply(&settings.get().playback.dsp_chain, &engine);
     let device_profiles = Arc::new(Mutex::new(DeviceProfileStore::load(&app_data_dir)));
+    let playlists = Arc::new(Mutex::new(PlaylistStore::load(&app_data_dir)));
+    let bookmarks = Arc::new(Mutex::new(BookmarkStore::load(&app_data_dir)));
+    let scan_exclusions = Arc::new(Mutex::new(ExcludeRules::load(&app_data_dir)));
+    let remote = Arc::new(RemoteServer::load(&app_data_dir));
+    let radio = Arc::new(Radio::load(&app_data_dir));
+    let scripts = Scripts::spawn(&app_data_dir, engine.clone());
+    let converter = Arc::new(Converter::new());
+    let snapcast = Arc::new(SnapcastOutput::load(&app_data_dir, engine.clone()));
+    snapcast.start();
+    write_settings::apply(TagWriteSettings::load(&app_data_dir));
+    let library = LibraryDb::open(&app_data_dir)
+        .or_else(|e| {
+            errors::report(
+                errors::ErrorCode::LibraryOpen,
+                format!("{} — falling back to an in-memory library", e),
+            );
+            LibraryDb::open_in_memory()
+        })
+        .expect("Failed to open library database");
+    let library = Arc::new(Mutex::new(library));
+    bookmarks::spawn_tracker(
+        engine.clone(),
+        bookmarks.clone(),
+        library.clone(),
+        app_data_dir.clone(),
+    );
+    let listenbrainz = ListenBrainz::spawn(&app_data_dir, library.clone());
+    let subsonic = Subsonic::spawn(&app_data_dir);
+    let jellyfin = Jellyfin::spawn(&app_data_dir, engine.clone());
+    let (submitter, scrobbler, reporter) =
+        (listenbrainz.clone(), subsonic.clone(), jellyfin.clone());
+    plays::spawn_counter(engine.clone(), library.clone(), move |state, at| {
+        submitter.listened(state, at);
+        scrobbler.listened(state, at);
+        reporter.listened(state);
+    });
 
-    tauri::Builder::default()
+    let builder = tauri::Builder::default();
+    // Must be registered first: a second launch (e.g. double-clicking files in
+    // Explorer/Finder) forwards its arguments here and exits.
+    #[cfg(desktop)]
+    let builder = builder.plugin(tauri_plugin_single_instance::init(|app, args, cwd| {
+        let paths = deep_link::open_args(app, args.into_iter().skip(1).collect());
+        commands::open_external_paths(app, paths, &cwd);
+        if let Some(window) = app.get_webview_window("main") {
+            let _ = window.unminimize();
+            let _ = window.set_focus();
+        }
+    }));
+    #[cfg(any(target_os = "android", target_os = "ios"))]
+    let builder = builder.plugin(tauri_plugin_media_session::init());
+    builder
+        .plugin(tauri_plugin_deep_link::init())
         .plugin(tauri_plugin_dialog::init())
         .plugin(tauri_plugin_shell::init())
+        .register_asynchronous_uri_scheme_protocol(
+            art_protocol::SCHEME,
+            |ctx, request, responder| art_protocol::handle(ctx.app_handle(), request, responder),
+        )
         .manage(AppState {
             engine: engine.clone(),
             device_profiles,
+            queue: Arc::new(Mutex::new(PlayQueue::new())),
+            playlists,
+            bookmarks,
+            library,
+            scan: Arc::new(ScanControl::new()),
+            waveforms: Arc::new(ScanControl::new()),
+            replaygain: Arc::new(ScanControl::new()),
+            dynamic_range: Arc::new(ScanControl::new()),
+            transcodes: Arc::new(ScanControl::new()),
+            integrity: Arc::new(ScanControl::new()),
+            organize: Arc::new(ScanControl::new()),
+            rip: Arc::new(ScanControl::new()),
+            scan_exclusions,
+            listenbrainz,
+            subsonic,
+            jellyfin,
+            radio: radio.clone(),
+            recorder: Arc::new(Recorder::new(engine.clone())),
+            converter: converter.clone(),
+            snapcast,
+            mqtt: Arc::new(MqttBridge::load(&app_data_dir)),
+            scripts,
+            remote,
+            settings,
+            buffering,
+            dsp_plugins,
             app_data_dir,
         })
+        .setup(move |app| {
+            errors::init(app.handle(), engine.clone());
+            // Files and links passed on the command line by a file-association
+            // or URL-scheme launch
+            let cwd = std::env::current_dir()
+                .map(|d| d.to_string_lossy().to_string())
+                .unwrap_or_default();
+            let paths = deep_link::open_args(app.handle(), std::env::args().skip(1).collect());
+            commands::open_external_paths(app.handle(), paths, &cwd);
+            // Links opened while running, on macOS and mobile; on Windows and
+            // Linux they come as arguments, forwarded by a second launch
+            let handle = app.handle().clone();
+            app.deep_link().on_open_url(move |event| {
+                let links = event
+                    .urls()
+                    .into_iter()
+                    .filter(|u| u.scheme() == deep_link::SCHEME);
+                for link in links {
+                    if let Err(e) = deep_link::open(&handle, link.as_str()) {
+                        log::warn!("{}", e);
+                    }
+                }
+            });
+            // Installers register the scheme; this covers builds run without
+            // one (an AppImage, `tauri dev`)
+            #[cfg(any(windows, target_os = "linux"))]
+            if let Err(e) = app.deep_link().register_all() {
+                log::warn!("Failed to register {}:// links: {}", deep_link::SCHEME, e);
+            }
+
+            let handle = app.handle().clone();
+            loudness_meter::spawn_meter(engine.clone(), move |reading| {
+                let _ = handle.emit("audio://loudness", reading);
+            });
+            let handle = app.handle().clone();
+            radio.spawn_tracker(engine.clone(), move |now_playing| {
+                let _ = handle.emit("radio://title", now_playing);
+            });
+            let handle = app.handle().clone();
+            converter.spawn_worker(move |job| {
+                let _ = handle.emit("convert://job", job);
+            });
+            let handle = app.handle().clone();
+            lyrics_sync::spawn_tracker(engine, move |line| {
+                let _ = handle.emit("lyrics://line", line);
+            });
+            commands::spawn_queue_advance(app.handle());
+            #[cfg(any(windows, target_os = "macos"))]
+            media_controls::spawn(app.handle());
+            #[cfg(any(target_os = "android", target_os = "ios"))]
+            media_session::spawn(app.handle());
+            #[cfg(any(target_os = "android", target_os = "ios"))]
+            buffering::spawn(app.handle());
+            #[cfg(windows)]
+            taskbar::spawn(app.handle());
+            app.state::<AppState>().remote.start(app.handle());
+            app.state::<AppState>().mqtt.start(app.handle());
+            if app
+                .state::<AppState>()
+                .settings
+                .get()
+                .library
+                .scan_on_startup
+            {
+                if let Err(e) =
+                    commands::scan_library(None, None, None, app.handle().clone(), app.state())
+                {
+                    log::warn!("Startup library scan failed: {}", e);
+                }
+            }
+            Ok(())
+        })
         .invoke_handler(tauri::generate_handler![
             // Playback
             commands::play_file,
@@ -39,15 +244,144 @@ pub fn run() {
             commands::set_volume,
             commands::get_playback_state,
             commands::get_position,
+            // Queue
+            commands::get_queue,
+            commands::add_to_queue,
+            commands::add_streams_to_queue,
+            commands::clear_queue,
+            commands::set_shuffle,
+            commands::set_repeat_mode,
+            commands::set_queue_dedup,
+            commands::next_track,
+            commands::previous_track,
+            commands::play_queue_index,
+            commands::get_history,
+            // Playlists
+            commands::list_playlists,
+            commands::get_playlist,
+            commands::create_playlist,
+            commands::rename_playlist,
+            commands::delete_playlist,
+            commands::add_to_playlist,
+            commands::remove_from_playlist,
+            commands::move_playlist_track,
+            commands::sort_playlist,
+            commands::lock_playlist_order,
+            commands::import_playlist_file,
+            // Bookmarks
+            commands::set_bookmark,
+            commands::clear_bookmark,
+            commands::list_bookmarks,
+            commands::get_bookmark_rules,
+            commands::set_bookmark_rules,
+            // Library
+            commands::list_library_roots,
+            commands::add_library_root,
+            commands::remove_library_root,
+            commands::set_library_root_enabled,
+            commands::scan_library,
+            commands::cancel_scan,
+            commands::is_scan_running,
+            commands::get_waveform,
+            commands::generate_waveforms,
+            commands::cancel_waveforms,
+            commands::scan_replaygain,
+            commands::cancel_replaygain_scan,
+            commands::scan_dynamic_range,
+            commands::cancel_dynamic_range_scan,
+            commands::scan_transcodes,
+            commands::cancel_transcode_scan,
+            commands::scan_integrity,
+            commands::cancel_integrity_scan,
+            commands::get_integrity_problems,
+            commands::get_integrity_status,
+            commands::verify_album_accuraterip,
+            commands::get_cd_drives,
+            commands::read_cd,
+            commands::rip_cd,
+            commands::cancel_rip,
+            commands::preview_organize,
+            commands::organize_files,
+            commands::cancel_organize,
+            commands::get_scan_exclusions,
+            commands::set_scan_exclusions,
+            commands::get_library_tracks,
+            commands::stream_library_list,
+            commands::browse_folder,
+            commands::search_library,
+            commands::query_tracks,
+            commands::list_albums,
+            commands::get_album,
+            commands::get_albums_missing_art,
+            commands::get_library_artists,
+            commands::get_artist,
+            commands::list_composers,
+            commands::get_composer_works,
+            commands::list_genres,
+            commands::get_genre,
+            commands::get_genre_tracks,
+            commands::set_rating,
+            commands::set_ratings,
+            commands::get_recently_added,
+            commands::get_most_played,
+            commands::get_never_played,
+            commands::get_library_stats,
+            commands::find_duplicates,
+            commands::get_suspected_transcodes,
+            commands::export_library_backup,
+            commands::restore_library_backup,
+            commands::import_itunes_library,
             // ReplayGain
             commands::set_replaygain_mode,
             commands::set_clipping_prevention,
+            // Settings
+            commands::get_settings,
+            commands::set_settings,
+            // Errors
+            commands::get_recent_errors,
+            // Logs
+            commands::get_recent_logs,
+            commands::set_log_level,
+            commands::get_log_dir,
+            // Buffering
+            commands::get_buffering,
+            commands::set_buffer_mode,
+            // DSP Plugins
+            commands::get_dsp_plugins,
+            commands::rescan_dsp_plugins,
+            commands::set_dsp_chain,
             // Diagnostics
             commands::get_audio_diagnostics,
+            commands::get_signal_path,
+            commands::get_diagnostics_history,
+            commands::get_levels,
+            commands::get_bit_activity,
+            commands::get_dropout_log,
+            commands::get_clipping_report,
+            commands::analyze_true_peak,
+            commands::export_diagnostics_report,
+            commands::measure_round_trip_latency,
             // Bit-Perfect Null Test
             commands::run_null_test,
+            commands::run_dsp_null_test,
+            commands::run_loopback_null_test,
+            // Spectrogram
+            commands::generate_spectrogram,
+            // Recording
+            commands::get_recording_status,
+            commands::start_recording,
+            commands::stop_recording,
+            // Converter
+            commands::convert_tracks,
+            commands::get_convert_jobs,
+            commands::cancel_convert_job,
+            commands::clear_finished_convert_jobs,
+            // Snapcast
+            commands::get_snapcast_status,
+            commands::set_snapcast_output,
             // Devices
             commands::get_audio_devices,
+            commands::get_input_devices,
             // Device Profiles
             commands::get_device_profile,
             commands::save_device_profile,
@@ -55,11 +389,95 @@ pub fn run() {
             commands::delete_device_profile,
             // Metadata
             commands::read_file_metadata,
+            commands::write_metadata,
+            commands::write_replaygain_tags,
+            commands::get_tag_fields,
+            commands::set_tag_field,
+            commands::get_tag_write_settings,
+            commands::set_tag_write_settings,
+            commands::get_legacy_encoded_tracks,
+            commands::preview_encoding_repair,
+            commands::repair_tag_encodings,
+            commands::identify_track,
+            commands::search_cover_art,
+            commands::apply_cover_art,
+            commands::set_album_art,
+            commands::remove_album_art,
+            commands::shrink_album_art,
+            commands::extract_album_art,
             commands::get_album_art_base64,
+            commands::get_embedded_pictures,
+            commands::get_lyrics,
+            commands::get_synced_lyrics,
+            commands::fetch_lyrics,
+            // ListenBrainz
+            commands::get_listenbrainz_status,
+            commands::connect_listenbrainz,
+            commands::disconnect_listenbrainz,
+            commands::set_listenbrainz_enabled,
+            // Remote control server
+            commands::get_remote_server_status,
+            commands::set_remote_server,
+            commands::regenerate_remote_server_token,
+            // MQTT
+            commands::get_mqtt_status,
+            commands::set_mqtt_bridge,
+            // Scripts
+            commands::get_scripts,
+            commands::reload_scripts,
+            // DLNA media servers
+            commands::discover_dlna_servers,
+            commands::add_dlna_server,
+            commands::browse_dlna_server,
+            // Subsonic servers
+            commands::get_subsonic_servers,
+            commands::add_subsonic_server,
+            commands::remove_subsonic_server,
+            commands::set_subsonic_server_options,
+            commands::get_subsonic_artists,
+            commands::get_subsonic_artist_albums,
+            commands::get_subsonic_album_list,
+            commands::get_subsonic_album_songs,
+            commands::search_subsonic,
+            // Jellyfin servers
+            commands::get_jellyfin_servers,
+            commands::add_jellyfin_server,
+            commands::remove_jellyfin_server,
+            commands::get_jellyfin_libraries,
+            commands::get_jellyfin_artists,
+            commands::get_jellyfin_albums,
+            commands::get_jellyfin_artist_albums,
+            commands::get_jellyfin_album_tracks,
+            commands::search_jellyfin,
+            // Internet radio
+            commands::get_radio_stations,
+            commands::add_radio_station,
+            commands::import_radio_playlist,
+            commands::update_radio_station,
+            commands::move_radio_station,
+            commands::remove_radio_station,
+            commands::play_radio_station,
+            commands::add_radio_stations_to_queue,
+            commands::get_radio_history,
+            commands::clear_radio_history,
             // Dialogs
             commands::open_files_dialog,
             commands::open_folder_dialog,
         ])
-        .run(tauri::generate_context!())
-        .expect("error while running tauri application");
+        .build(tauri::generate_context!())
+        .expect("error while building tauri application")
+        .run(|_app, _event| {
+            // macOS delivers file-association opens as an event, not argv;
+            // links in the same event go to the deep-link plugin
+            #[cfg(any(target_os = "macos", target_os = "ios"))]
+            if let tauri::RunEvent::Opened { urls } = _event {
+                let paths = urls
+                    .into_iter()
+                    .filter(|u| u.scheme() == "file")
+                    .filter_map(|u| u.to_file_path().ok())
+                    .map(|p| p.to_string_lossy().to_string())
+                    .collect();
+                commands::open_external_paths(_app, paths, "");
+            }
+        });
 }
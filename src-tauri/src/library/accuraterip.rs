@@ -0,0 +1,408 @@
+//! AccurateRip verification of ripped CDs in the library.
+//!
+//! An album ripped to 16-bit / 44.1 kHz stereo (one file per track, or an
+//! image with a cue sheet) is checked against the AccurateRip database of
+//! other people's rips. The disc is looked up by ids computed from its
+//! table of contents, which is rebuilt from the track lengths: every CD
+//! track is a whole number of 588-frame sectors, so a rip that isn't can't
+//! be matched. Each track's v1 and v2 checksums are then compared with the
+//! ones submitted for every pressing of the disc; a match means the rip is
+//! bit-identical to what other drives read, and the confidence is how many
+//! rips agreed.
+//!
+//! The rip must already be offset-corrected, as EAC, dBpoweramp and
+//! [`super::ripper`] do; the ripper checks its own rips as it reads them.
+//! Discs with a data track (enhanced CDs) have a table of contents that
+//! can't be rebuilt from the audio and aren't found.
+
+use serde::Serialize;
+use std::collections::{BTreeMap, VecDeque};
+
+use super::database::LibraryTrack;
+use crate::audio::decoder::{AudioDecoder, DecodeStatus};
+use crate::http;
+
+const DATABASE_URL: &str = "http://www.accuraterip.com/accuraterip";
+
+/// Stereo frames per CD sector.
+pub(super) const SECTOR_FRAMES: u64 = 588;
+
+/// Sectors per second.
+const SECTORS_PER_SEC: f64 = 75.0;
+
+/// Frames left out of the checksums at the start of the first track and
+/// the end of the last, where drives can't read reliably.
+const SKIPPED_FRAMES: u32 = 5 * SECTOR_FRAMES as u32;
+
+/// Sectors before the first track (the lead-in), counted by CDDB ids.
+const LEAD_IN_SECTORS: u64 = 150;
+
+#[derive(Clone, Serialize)]
+pub struct AccurateRipTrack {
+    pub path: String,
+    pub title: Option<String>,
+    /// Position on the disc, from 1.
+    pub number: u32,
+    /// e.g. `a1b2c3d4`.
+    pub crc_v1: String,
+    pub crc_v2: String,
+    /// Rips that agreed with this one, `None` if none did.
+    pub confidence: Option<u32>,
+    /// `v1` or `v2`, the checksum that matched.
+    pub matched: Option<String>,
+}
+
+#[derive(Clone, Serialize)]
+pub struct AccurateRipDisc {
+    pub disc_number: Option<u32>,
+    /// e.g. `dBAR-012-0015d6f3-00c8a2b1-b10a8f0c`.
+    pub disc_id: String,
+    /// The disc is in the database at all.
+    pub found: bool,
+    pub tracks: Vec<AccurateRipTrack>,
+    /// Every track matched.
+    pub accurate: bool,
+}
+
+/// Verify an album's tracks (ordered by disc and track, as the library
+/// lists them) disc by disc. Decodes every track and blocks on the network.
+pub fn verify_album(tracks: &[LibraryTrack]) -> Result<Vec<AccurateRipDisc>, String> {
+    if tracks.is_empty() {
+        return Err("The album has no tracks".to_string());
+    }
+    let mut discs: BTreeMap<Option<u32>, Vec<&LibraryTrack>> = BTreeMap::new();
+    for track in tracks {
+        discs.entry(track.disc_number).or_default().push(track);
+    }
+    discs
+        .into_iter()
+        .map(|(disc_number, tracks)| verify_disc(disc_number, &tracks))
+        .collect()
+}
+
+fn verify_disc(
+    disc_number: Option<u32>,
+    tracks: &[&LibraryTrack],
+) -> Result<AccurateRipDisc, String> {
+    let checksums = checksum_tracks(tracks)?;
+
+    // Table of contents: where each track starts, in sectors, and the
+    // lead-out after the last. An image's first track may start late,
+    // after a hidden track the rip left in the pregap
+    let first = tracks[0]
+        .source_path
+        .as_ref()
+        .map(|_| sectors_at(tracks[0].start_secs.unwrap_or(0.0)))
+        .unwrap_or(0);
+    let mut offsets = Vec::with_capacity(checksums.len());
+    let mut position = first;
+    for checksum in &checksums {
+        offsets.push(position);
+        position += checksum.sectors;
+    }
+    let tracks: Vec<(String, Option<String>)> = tracks
+        .iter()
+        .map(|t| (t.path.clone(), t.title.clone()))
+        .collect();
+    compare(disc_number, &offsets, position, &tracks, &checksums)
+}
+
+/// Look a disc up by its table of contents (track starts and lead-out, in
+/// sectors) and compare the checksums of its tracks, given by path and
+/// title, with the submitted ones.
+pub(super) fn compare(
+    disc_number: Option<u32>,
+    offsets: &[u64],
+    lead_out: u64,
+    tracks: &[(String, Option<String>)],
+    checksums: &[TrackChecksum],
+) -> Result<AccurateRipDisc, String> {
+    let ids = DiscIds::new(offsets, lead_out);
+    let disc_id = ids.name(offsets.len());
+
+    let submitted = lookup(&ids, offsets.len())?;
+    let mut accurate = submitted.is_some();
+    let tracks = tracks
+        .iter()
+        .zip(checksums)
+        .enumerate()
+        .map(|(i, ((path, title), checksum))| {
+            let mut confidence = None;
+            let mut matched = None;
+            for pressing in submitted.iter().flatten() {
+                let Some(&(count, crc)) = pressing.get(i) else {
+                    continue;
+                };
+                let version = if crc == checksum.v1 {
+                    "v1"
+                } else if crc == checksum.v2 {
+                    "v2"
+                } else {
+                    continue;
+                };
+                confidence = Some(confidence.unwrap_or(0) + count);
+                matched.get_or_insert(version);
+            }
+            accurate &= confidence.is_some();
+            AccurateRipTrack {
+                path: path.clone(),
+                title: title.clone(),
+                number: i as u32 + 1,
+                crc_v1: format!("{:08x}", checksum.v1),
+                crc_v2: format!("{:08x}", checksum.v2),
+                confidence,
+                matched: matched.map(str::to_string),
+            }
+        })
+        .collect();
+
+    Ok(AccurateRipDisc {
+        disc_number,
+        disc_id,
+        found: submitted.is_some(),
+        tracks,
+        accurate,
+    })
+}
+
+pub(super) struct TrackChecksum {
+    sectors: u64,
+    v1: u32,
+    v2: u32,
+}
+
+/// Decode a disc's tracks and checksum each. Tracks of one image are
+/// decoded in a single pass.
+fn checksum_tracks(tracks: &[&LibraryTrack]) -> Result<Vec<TrackChecksum>, String> {
+    let count = tracks.len();
+    let mut checksums = Vec::with_capacity(count);
+    let mut i = 0;
+    while i < count {
+        let file = tracks[i].source_path.as_deref().unwrap_or(&tracks[i].path);
+        // The run of tracks that come from this file
+        let mut run = i + 1;
+        while run < count && tracks[run].source_path.as_deref() == Some(file) {
+            run += 1;
+        }
+
+        let mut decoder = AudioDecoder::open(file)?;
+        if decoder.sample_rate() != 44_100
+            || decoder.channels() != 2
+            || decoder.bit_depth().is_some_and(|b| b != 16)
+        {
+            return Err(format!(
+                "{} isn't CD audio (16-bit / 44.1 kHz stereo)",
+                file
+            ));
+        }
+        // Frame each track of the run ends at; the last runs to the end
+        let ends: Vec<Option<u64>> = tracks[i..run]
+            .iter()
+            .map(|t| t.end_secs.map(|s| sectors_at(s) * SECTOR_FRAMES))
+            .collect();
+        let mut frame = tracks[i]
+            .start_secs
+            .filter(|_| tracks[i].source_path.is_some())
+            .map_or(0, |s| sectors_at(s) * SECTOR_FRAMES);
+        let mut skip = frame;
+        let mut current = 0;
+        let mut checksum = Checksum::new(i == 0);
+        loop {
+            let samples = match decoder.next_samples() {
+                Ok(s) => s,
+                Err(DecodeStatus::EndOfStream) => break,
+                Err(DecodeStatus::Error(e)) => return Err(format!("Decode failed: {}", e)),
+            };
+            for pair in samples.chunks_exact(2) {
+                // Audio before the first track of an image (its pregap)
+                if skip > 0 {
+                    skip -= 1;
+                    continue;
+                }
+                while ends[current].is_some_and(|end| frame >= end) && current + 1 < ends.len() {
+                    checksums.push(checksum.finish(false, file)?);
+                    current += 1;
+                    checksum = Checksum::new(false);
+                }
+                if ends[current].is_some_and(|end| frame >= end) {
+                    break;
+                }
+                checksum.push(sample_word(pair[0], pair[1]));
+                frame += 1;
+            }
+        }
+        checksums.push(checksum.finish(run == count, file)?);
+        if current + 1 < ends.len() {
+            return Err(format!("{} ends before its last cue-sheet track", file));
+        }
+        i = run;
+    }
+    Ok(checksums)
+}
+
+/// A stereo frame as AccurateRip sums it: left in the low half, right in
+/// the high half.
+fn sample_word(left: f32, right: f32) -> u32 {
+    let to_i16 = |s: f32| (s * 32768.0).round().clamp(-32768.0, 32767.0) as i16;
+    cd_sample_word(to_i16(left), to_i16(right))
+}
+
+/// [`sample_word`] for samples straight from the disc.
+pub(super) fn cd_sample_word(left: i16, right: i16) -> u32 {
+    u32::from(left as u16) | (u32::from(right as u16) << 16)
+}
+
+/// The v1 and v2 checksums of one track, summed as it's decoded.
+pub(super) struct Checksum {
+    /// Frames so far; the multiplier of the latest.
+    frames: u32,
+    /// First multiplier that counts.
+    from: u32,
+    /// The last [`SKIPPED_FRAMES`] frames, held back until it's known
+    /// whether this is the disc's last track.
+    tail: VecDeque<(u32, u32)>,
+    v1: u32,
+    v2: u32,
+}
+
+impl Checksum {
+    pub(super) fn new(first_track: bool) -> Self {
+        Self {
+            frames: 0,
+            // The first track skips five sectors less one frame
+            from: if first_track { SKIPPED_FRAMES } else { 1 },
+            tail: VecDeque::with_capacity(SKIPPED_FRAMES as usize + 1),
+            v1: 0,
+            v2: 0,
+        }
+    }
+
+    /// Add a stereo frame, as [`cd_sample_word`] packs it.
+    pub(super) fn push(&mut self, sample: u32) {
+        self.frames = self.frames.wrapping_add(1);
+        if self.frames < self.from {
+            return;
+        }
+        self.tail.push_back((sample, self.frames));
+        if self.tail.len() > SKIPPED_FRAMES as usize {
+            let (sample, multiplier) = self.tail.pop_front().unwrap_or_default();
+            self.add(sample, multiplier);
+        }
+    }
+
+    fn add(&mut self, sample: u32, multiplier: u32) {
+        self.v1 = self.v1.wrapping_add(sample.wrapping_mul(multiplier));
+        let product = u64::from(sample) * u64::from(multiplier);
+        self.v2 = self
+            .v2
+            .wrapping_add(product as u32)
+            .wrapping_add((product >> 32) as u32);
+    }
+
+    pub(super) fn finish(mut self, last_track: bool, file: &str) -> Result<TrackChecksum, String> {
+        if u64::from(self.frames) % SECTOR_FRAMES != 0 {
+            return Err(format!(
+                "{} isn't a CD rip: a track isn't a whole number of sectors",
+                file
+            ));
+        }
+        if !last_track {
+            while let Some((sample, multiplier)) = self.tail.pop_front() {
+                self.add(sample, multiplier);
+            }
+        }
+        Ok(TrackChecksum {
+            sectors: u64::from(self.frames) / SECTOR_FRAMES,
+            v1: self.v1,
+            v2: self.v2,
+        })
+    }
+}
+
+/// A cue-sheet time in whole sectors. Cue times are sector-exact.
+fn sectors_at(secs: f64) -> u64 {
+    (secs * SECTORS_PER_SEC).round().max(0.0) as u64
+}
+
+/// The three ids AccurateRip files a disc under.
+struct DiscIds {
+    id1: u32,
+    id2: u32,
+    cddb: u32,
+}
+
+impl DiscIds {
+    fn new(offsets: &[u64], lead_out: u64) -> Self {
+        let mut id1: u32 = 0;
+        let mut id2: u32 = 0;
+        let mut digit_sum: u32 = 0;
+        for (i, &offset) in offsets.iter().enumerate() {
+            id1 = id1.wrapping_add(offset as u32);
+            id2 = id2.wrapping_add((offset.max(1) as u32).wrapping_mul(i as u32 + 1));
+            let mut secs = (offset + LEAD_IN_SECTORS) / 75;
+            while secs > 0 {
+                digit_sum += (secs % 10) as u32;
+                secs /= 10;
+            }
+        }
+        id1 = id1.wrapping_add(lead_out as u32);
+        id2 = id2.wrapping_add((lead_out as u32).wrapping_mul(offsets.len() as u32 + 1));
+        let length = (lead_out + LEAD_IN_SECTORS) / 75 - (offsets[0] + LEAD_IN_SECTORS) / 75;
+        let cddb = ((digit_sum % 255) << 24) | ((length as u32) << 8) | offsets.len() as u32;
+        Self { id1, id2, cddb }
+    }
+
+    fn name(&self, tracks: usize) -> String {
+        format!(
+            "dBAR-{:03}-{:08x}-{:08x}-{:08x}",
+            tracks, self.id1, self.id2, self.cddb
+        )
+    }
+}
+
+/// Confidence and checksum of each track of one pressing of a disc.
+type Pressing = Vec<(u32, u32)>;
+
+/// Every pressing submitted for the disc; `None` if the disc isn't in the
+/// database.
+fn lookup(ids: &DiscIds, tracks: usize) -> Result<Option<Vec<Pressing>>, String> {
+    let url = format!(
+        "{}/{:x}/{:x}/{:x}/{}.bin",
+        DATABASE_URL,
+        ids.id1 & 0xf,
+        (ids.id1 >> 4) & 0xf,
+        (ids.id1 >> 8) & 0xf,
+        ids.name(tracks)
+    );
+    let Some(bytes) = http::get_bytes_if_found(&url)? else {
+        return Ok(None);
+    };
+
+    // Per pressing: track count, the three ids, then for each track its
+    // confidence, checksum and the checksum of sector 450
+    let u32_at =
+        |at: usize| u32::from_le_bytes([bytes[at], bytes[at + 1], bytes[at + 2], bytes[at + 3]]);
+    let mut pressings = Vec::new();
+    let mut at = 0;
+    while at + 13 <= bytes.len() {
+        let count = bytes[at] as usize;
+        let end = at + 13 + count * 9;
+        if end > bytes.len() {
+            break;
+        }
+        let matches_disc =
+            count == tracks && u32_at(at + 1) == ids.id1 && u32_at(at + 5) == ids.id2;
+        if matches_disc {
+            pressings.push(
+                (0..count)
+                    .map(|t| {
+                        let entry = at + 13 + t * 9;
+                        (u32::from(bytes[entry]), u32_at(entry + 1))
+                    })
+                    .collect(),
+            );
+        }
+        at = end;
+    }
+    Ok(Some(pressings))
+}
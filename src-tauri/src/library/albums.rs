@@ -0,0 +1,216 @@
+//! Album browsing: the album list with thumbnails, and album detail with its
+//! tracks grouped by disc in disc/track order.
+
+use rusqlite::{params, Row, ToSql};
+use serde::Serialize;
+
+use super::artwork::{self, CoverSource};
+use super::compilations::is_various_artists;
+use super::database::{LibraryDb, LibraryTrack, TRACK_COLUMNS};
+use super::query::Paging;
+
+#[derive(Clone, Serialize)]
+pub struct LibraryAlbum {
+    pub id: i64,
+    pub title: String,
+    pub artist: Option<String>,
+    pub year: Option<u32>,
+    pub track_count: u32,
+    pub disc_count: u32,
+    pub duration_secs: f64,
+    /// Filed under "Various Artists".
+    pub compilation: bool,
+    /// Unix timestamp (seconds) when its newest track was added to the library.
+    pub added_at: Option<i64>,
+    /// Average DR of its analysed tracks.
+    pub dynamic_range: Option<f64>,
+    /// Path of the cached thumbnail, `None` until generated (or if the album
+    /// has no artwork).
+    pub thumbnail: Option<String>,
+}
+
+#[derive(Clone, Serialize)]
+pub struct AlbumDisc {
+    /// `None` for tracks without a disc number.
+    pub number: Option<u32>,
+    pub tracks: Vec<LibraryTrack>,
+}
+
+#[derive(Clone, Serialize)]
+pub struct AlbumDetail {
+    pub album: LibraryAlbum,
+    pub discs: Vec<AlbumDisc>,
+}
+
+/// One row per album matching the `{where}` condition (over `al`, the albums
+/// table), sorted by `{order}` (over `a`); the cover track is the first track
+/// with embedded art, else the first track, in disc/track order.
+const ALBUM_SQL: &str = "
+    SELECT a.id, a.title, a.artist, a.year, a.track_count, a.disc_count, a.duration_secs,
+           a.added_at, ct.path, ct.modified_at, a.dynamic_range
+      FROM (SELECT al.id, al.title, ar.name AS artist, al.year,
+                   COALESCE(ar.sort_name, ar.name) AS artist_sort,
+                   COALESCE(al.sort_title, al.title) AS title_sort,
+                   COUNT(t.id) AS track_count,
+                   COUNT(DISTINCT COALESCE(t.disc_number, 0)) AS disc_count,
+                   COALESCE(SUM(t.duration_secs), 0) AS duration_secs,
+                   MAX(t.added_at) AS added_at,
+                   AVG(t.dynamic_range) AS dynamic_range,
+                   (SELECT c.id FROM visible_tracks c
+                     WHERE c.album_id = al.id
+                     ORDER BY c.has_album_art DESC, c.disc_number, c.track_number, c.path
+                     LIMIT 1) AS cover_id
+              FROM albums al
+              LEFT JOIN artists ar ON ar.id = al.artist_id
+              JOIN visible_tracks t ON t.album_id = al.id
+             WHERE {where}
+             GROUP BY al.id) a
+      LEFT JOIN tracks ct ON ct.id = a.cover_id
+     ORDER BY {order}";
+
+/// Album artists and titles in sort-tag order.
+const ALBUM_ORDER: &str = "a.artist_sort COLLATE NOCASE, a.year, a.title_sort COLLATE NOCASE";
+
+impl LibraryDb {
+    pub fn albums(&self, paging: Paging) -> Result<Vec<LibraryAlbum>, String> {
+        Ok(self
+            .album_rows_ordered("1", &format!("{}{}", ALBUM_ORDER, paging.sql()), params![])?
+            .into_iter()
+            .map(|(album, _)| album)
+            .collect())
+    }
+
+    pub fn album(&self, id: i64) -> Result<AlbumDetail, String> {
+        let (album, _) = self
+            .album_rows("al.id = ?1", params![id])?
+            .into_iter()
+            .next()
+            .ok_or_else(|| format!("Album {} not found", id))?;
+
+        let mut discs: Vec<AlbumDisc> = Vec::new();
+        for track in self.album_tracks(id)? {
+            match discs.last_mut() {
+                Some(disc) if disc.number == track.disc_number => disc.tracks.push(track),
+                _ => discs.push(AlbumDisc {
+                    number: track.disc_number,
+                    tracks: vec![track],
+                }),
+            }
+        }
+
+        Ok(AlbumDetail { album, discs })
+    }
+
+    pub fn album_tracks(&self, album_id: i64) -> Result<Vec<LibraryTrack>, String> {
+        self.query_tracks(
+            &format!(
+                "SELECT {} FROM visible_tracks WHERE album_id = ?1
+                  ORDER BY disc_number IS NULL, disc_number, track_number IS NULL, track_number, path",
+                TRACK_COLUMNS
+            ),
+            params![album_id],
+        )
+    }
+
+    /// Cover track of one album.
+    pub fn cover_source(&self, album_id: i64) -> Result<Option<CoverSource>, String> {
+        Ok(self
+            .album_rows("al.id = ?1", params![album_id])?
+            .into_iter()
+            .next()
+            .and_then(|(_, source)| source))
+    }
+
+    /// Cover tracks of every album that has no thumbnail (or marker) yet.
+    pub fn pending_cover_sources(&self) -> Result<Vec<CoverSource>, String> {
+        let dir = self.thumbnail_dir();
+        Ok(self
+            .album_rows("1", params![])?
+            .into_iter()
+            .filter_map(|(_, source)| source)
+            .filter(|s| artwork::needs_thumbnail(dir, &s.path, s.modified_at))
+            .collect())
+    }
+
+    /// Albums whose cover track was found to have no artwork, embedded or in
+    /// its folder. Albums not yet probed by a scan are not listed.
+    pub fn albums_missing_art(&self) -> Result<Vec<LibraryAlbum>, String> {
+        let dir = self.thumbnail_dir();
+        Ok(self
+            .album_rows("1", params![])?
+            .into_iter()
+            .filter(|(_, source)| {
+                source
+                    .as_ref()
+                    .is_some_and(|s| artwork::is_artless(dir, &s.path, s.modified_at))
+            })
+            .map(|(album, _)| album)
+            .collect())
+    }
+
+    /// Albums matching an SQL condition over `al` (the albums table).
+    pub(super) fn album_rows(
+        &self,
+        condition: &str,
+        params: &[&dyn ToSql],
+    ) -> Result<Vec<(LibraryAlbum, Option<CoverSource>)>, String> {
+        self.album_rows_ordered(condition, ALBUM_ORDER, params)
+    }
+
+    /// Like [`Self::album_rows`], with an ORDER BY clause (over `a`, which has
+    /// the `LibraryAlbum` columns) that may end in LIMIT/OFFSET.
+    pub(super) fn album_rows_ordered(
+        &self,
+        condition: &str,
+        order: &str,
+        params: &[&dyn ToSql],
+    ) -> Result<Vec<(LibraryAlbum, Option<CoverSource>)>, String> {
+        let sql = ALBUM_SQL
+            .replace("{where}", condition)
+            .replace("{order}", order);
+        let mut stmt = self
+            .conn
+            .prepare(&sql)
+            .map_err(|e| format!("Failed to query albums: {}", e))?;
+        let rows = stmt
+            .query_map(params, |row| self.album_from_row(row))
+            .map_err(|e| format!("Failed to query albums: {}", e))?;
+        rows.collect::<Result<_, _>>()
+            .map_err(|e| format!("Failed to query albums: {}", e))
+    }
+
+    fn album_from_row(&self, row: &Row) -> rusqlite::Result<(LibraryAlbum, Option<CoverSource>)> {
+        let id: i64 = row.get(0)?;
+        let artist: Option<String> = row.get(2)?;
+        let compilation = artist.as_deref().is_some_and(is_various_artists);
+        let cover_path: Option<String> = row.get(8)?;
+        let cover_modified: Option<i64> = row.get(9)?;
+        let source = cover_path
+            .zip(cover_modified)
+            .map(|(path, modified_at)| CoverSource {
+                album_id: id,
+                path,
+                modified_at,
+            });
+        let thumbnail = source
+            .as_ref()
+            .and_then(|s| artwork::cached_thumbnail(self.thumbnail_dir(), &s.path, s.modified_at));
+
+        Ok((
+            LibraryAlbum {
+                id,
+                title: row.get(1)?,
+                artist,
+                year: row.get(3)?,
+                track_count: row.get(4)?,
+                disc_count: row.get(5)?,
+                duration_secs: row.get(6)?,
+                compilation,
+                added_at: row.get(7)?,
+                dynamic_range: row.get(10)?,
+                thumbnail,
+            },
+            source,
+        ))
+    }
+}
@@ -0,0 +1,90 @@
+//! Track selection for analysis jobs (ReplayGain, dynamic range).
+//!
+//! Album values need every track of the album, so selecting one library
+//! track selects its whole album. Files outside the library (or without an
+//! album) are analysed on their own. A cue sheet's tracks are analysed as
+//! their image file, once: tags go into the image, and it has only one set.
+
+use rusqlite::params;
+use std::collections::{BTreeMap, HashSet};
+
+use super::database::LibraryDb;
+use crate::metadata::cue;
+
+/// Files analysed together: one album, or a single track.
+pub struct AnalysisGroup {
+    pub paths: Vec<String>,
+    /// The group is a whole album, so album values apply.
+    pub album: bool,
+}
+
+impl LibraryDb {
+    /// Group the selection into albums. With no paths and no album ids, every
+    /// visible track is selected.
+    pub fn analysis_groups(
+        &self,
+        paths: &[String],
+        album_ids: &[i64],
+    ) -> Result<Vec<AnalysisGroup>, String> {
+        let whole_library = paths.is_empty() && album_ids.is_empty();
+        let mut albums: HashSet<i64> = album_ids.iter().copied().collect();
+        let mut groups = Vec::new();
+        // Files already in a single-file group
+        let mut singles = HashSet::new();
+        for path in paths {
+            match self.track_by_path(path)?.and_then(|t| t.album_id) {
+                Some(album_id) => {
+                    albums.insert(album_id);
+                }
+                None => {
+                    let file = cue::audio_file(path).unwrap_or_else(|_| path.clone());
+                    if singles.insert(file.clone()) {
+                        groups.push(AnalysisGroup {
+                            paths: vec![file],
+                            album: false,
+                        });
+                    }
+                }
+            }
+        }
+
+        let mut stmt = self
+            .conn
+            .prepare(
+                "SELECT album_id, COALESCE(source_path, path) FROM visible_tracks
+                  ORDER BY album_id, disc_number, track_number, path",
+            )
+            .map_err(|e| format!("Failed to query library: {}", e))?;
+        let rows = stmt
+            .query_map(params![], |row| {
+                Ok((row.get::<_, Option<i64>>(0)?, row.get::<_, String>(1)?))
+            })
+            .map_err(|e| format!("Failed to query library: {}", e))?;
+
+        let mut by_album: BTreeMap<i64, Vec<String>> = BTreeMap::new();
+        for row in rows {
+            let (album_id, path) = row.map_err(|e| format!("Failed to query library: {}", e))?;
+            match album_id {
+                Some(id) if whole_library || albums.contains(&id) => {
+                    let album = by_album.entry(id).or_default();
+                    if !album.contains(&path) {
+                        album.push(path);
+                    }
+                }
+                None if whole_library && singles.insert(path.clone()) => {
+                    groups.push(AnalysisGroup {
+                        paths: vec![path],
+                        album: false,
+                    })
+                }
+                _ => {}
+            }
+        }
+        groups.extend(
+            by_album
+                .into_values()
+                .map(|paths| AnalysisGroup { paths, album: true }),
+        );
+        Ok(groups)
+    }
+}
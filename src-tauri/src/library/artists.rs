@@ -0,0 +1,220 @@
+//! Artist browsing.
+//!
+//! A track's ARTIST tag is split into its main artist and any "feat." guests
+//! (also picked up from the title, e.g. "Song (feat. Guest)"), so
+//! "Artist feat. Guest" doesn't become an artist page of its own. Albums
+//! belong to their ALBUMARTIST; an artist's page lists the albums they own,
+//! the albums they appear on (compilations, guest spots) and the tracks they
+//! are featured on.
+
+use rusqlite::{params, Connection, Row, ToSql};
+use serde::Serialize;
+
+use super::albums::LibraryAlbum;
+use super::database::{
+    ensure_artist, prune_orphans, LibraryDb, LibraryTrack, TRACK_ALBUM_ORDER, TRACK_COLUMNS,
+};
+use super::query::Paging;
+use crate::metadata::values::{split_artists, split_featured};
+
+/// One row per artist matching the `{}` condition (over `ar`, the artists
+/// table and the count aliases).
+const ARTIST_SQL: &str = "
+    SELECT ar.id, ar.name, ar.sort_name,
+           (SELECT COUNT(DISTINCT t.album_id) FROM visible_tracks t
+             JOIN albums al ON al.id = t.album_id
+             WHERE al.artist_id = ar.id) AS album_count,
+           (SELECT COUNT(*) FROM visible_tracks t WHERE t.artist_id = ar.id) AS track_count,
+           (SELECT COUNT(*) FROM featured_artists f
+             JOIN visible_tracks t ON t.id = f.track_id
+             WHERE f.artist_id = ar.id) AS featured_count
+      FROM artists ar
+     WHERE {}
+     ORDER BY COALESCE(ar.sort_name, ar.name) COLLATE NOCASE";
+
+#[derive(Clone, Serialize)]
+pub struct LibraryArtist {
+    pub id: i64,
+    pub name: String,
+    /// From ARTISTSORT / ALBUMARTISTSORT tags, e.g. "Beatles, The".
+    pub sort_name: Option<String>,
+    /// Albums with this artist as album artist.
+    pub album_count: u32,
+    /// Tracks with this artist as main artist.
+    pub track_count: u32,
+    /// Tracks this artist is a guest on.
+    pub featured_count: u32,
+}
+
+#[derive(Clone, Serialize)]
+pub struct ArtistDetail {
+    pub artist: LibraryArtist,
+    /// Albums with this artist as album artist.
+    pub albums: Vec<LibraryAlbum>,
+    /// Other albums with tracks by or featuring this artist.
+    pub appears_on: Vec<LibraryAlbum>,
+    /// Tracks this artist is a guest on.
+    pub featured_on: Vec<LibraryTrack>,
+}
+
+impl LibraryDb {
+    /// All artists with visible tracks, or only album artists.
+    pub fn artists(
+        &self,
+        album_artists_only: bool,
+        paging: Paging,
+    ) -> Result<Vec<LibraryArtist>, String> {
+        self.artist_rows(
+            "album_count > 0
+              OR (?1 = 0 AND (track_count > 0 OR featured_count > 0))",
+            paging,
+            params![album_artists_only],
+        )
+    }
+
+    pub fn artist(&self, id: i64) -> Result<ArtistDetail, String> {
+        let artist = self
+            .artist_rows("ar.id = ?1", Paging::ALL, params![id])?
+            .into_iter()
+            .next()
+            .ok_or_else(|| format!("Artist {} not found", id))?;
+
+        let albums = self
+            .album_rows("al.artist_id = ?1", params![id])?
+            .into_iter()
+            .map(|(album, _)| album)
+            .collect();
+        let appears_on = self
+            .album_rows(
+                "al.artist_id IS NOT ?1
+                 AND al.id IN (SELECT album_id FROM visible_tracks WHERE artist_id = ?1
+                               UNION
+                               SELECT t.album_id FROM featured_artists f
+                                 JOIN visible_tracks t ON t.id = f.track_id
+                                WHERE f.artist_id = ?1)",
+                params![id],
+            )?
+            .into_iter()
+            .map(|(album, _)| album)
+            .collect();
+        let featured_on = self.query_tracks(
+            &format!(
+                "SELECT {} FROM visible_tracks
+                  WHERE id IN (SELECT track_id FROM featured_artists WHERE artist_id = ?1)
+                  ORDER BY {}",
+                TRACK_COLUMNS, TRACK_ALBUM_ORDER
+            ),
+            params![id],
+        )?;
+
+        Ok(ArtistDetail {
+            artist,
+            albums,
+            appears_on,
+            featured_on,
+        })
+    }
+
+    fn artist_rows(
+        &self,
+        condition: &str,
+        paging: Paging,
+        params: &[&dyn ToSql],
+    ) -> Result<Vec<LibraryArtist>, String> {
+        let mut stmt = self
+            .conn
+            .prepare(&format!(
+                "{}{}",
+                ARTIST_SQL.replace("{}", condition),
+                paging.sql()
+            ))
+            .map_err(|e| format!("Failed to query artists: {}", e))?;
+        let rows = stmt
+            .query_map(params, artist_from_row)
+            .map_err(|e| format!("Failed to query artists: {}", e))?;
+        rows.collect::<Result<_, _>>()
+            .map_err(|e| format!("Failed to query artists: {}", e))
+    }
+}
+
+fn artist_from_row(row: &Row) -> rusqlite::Result<LibraryArtist> {
+    Ok(LibraryArtist {
+        id: row.get(0)?,
+        name: row.get(1)?,
+        sort_name: row.get(2)?,
+        album_count: row.get(3)?,
+        track_count: row.get(4)?,
+        featured_count: row.get(5)?,
+    })
+}
+
+/// Main artist and guest artists credited by a track's artist and title tags.
+/// Further artists in a multi-value tag ("A; B") count as guests. Guests are
+/// deduplicated and never include the main artist.
+pub(super) fn credited_artists(
+    artist: Option<&str>,
+    title: Option<&str>,
+) -> (Option<String>, Vec<String>) {
+    let mut artists = split_artists(artist);
+    let main = (!artists.is_empty()).then(|| artists.remove(0));
+    let mut guests = artists;
+    if let Some(title) = title {
+        guests.extend(split_featured(title).1);
+    }
+
+    let mut seen: Vec<String> = main.iter().map(|m| m.to_lowercase()).collect();
+    guests.retain(|g| {
+        let key = g.to_lowercase();
+        if seen.contains(&key) {
+            false
+        } else {
+            seen.push(key);
+            true
+        }
+    });
+    (main, guests)
+}
+
+/// Replace the guest credits of a track.
+pub(super) fn set_featured(
+    conn: &Connection,
+    track_id: i64,
+    guests: &[String],
+) -> rusqlite::Result<()> {
+    conn.execute(
+        "DELETE FROM featured_artists WHERE track_id = ?1",
+        params![track_id],
+    )?;
+    for name in guests {
+        let artist_id = ensure_artist(conn, name)?;
+        conn.execute(
+            "INSERT OR IGNORE INTO featured_artists (track_id, artist_id) VALUES (?1, ?2)",
+            params![track_id, artist_id],
+        )?;
+    }
+    Ok(())
+}
+
+/// Re-derive main and guest artists from the stored tags. Run once when
+/// upgrading a library indexed before guest credits existed, inside the
+/// migration's transaction.
+pub(super) fn reindex_credits(conn: &Connection) -> rusqlite::Result<()> {
+    let tracks: Vec<(i64, Option<String>, Option<String>)> = {
+        let mut stmt = conn.prepare("SELECT id, artist, title FROM tracks")?;
+        let rows = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))?;
+        rows.collect::<Result<_, _>>()?
+    };
+    for (id, artist, title) in tracks {
+        let (main, guests) = credited_artists(artist.as_deref(), title.as_deref());
+        let artist_id = match main.as_deref() {
+            Some(name) => Some(ensure_artist(conn, name)?),
+            None => None,
+        };
+        conn.execute(
+            "UPDATE tracks SET artist_id = ?2 WHERE id = ?1 AND artist_id IS NOT ?2",
+            params![id, artist_id],
+        )?;
+        set_featured(conn, id, &guests)?;
+    }
+    prune_orphans(conn)
+}
@@ -0,0 +1,433 @@
+//! Album thumbnail cache.
+//!
+//! Thumbnails are small JPEGs made from an album's cover track — its embedded
+//! front cover, or a `cover.jpg`/`folder.jpg`-style image next to it. Files
+//! are named after a hash of the cover track's path and mtime, so a retagged
+//! file gets a fresh thumbnail and stale entries are simply never referenced.
+//! Albums with no artwork at all get an empty `.none` marker so they aren't
+//! re-probed on every scan.
+
+use image::codecs::jpeg::JpegEncoder;
+use image::ImageFormat;
+use lofty::picture::{MimeType, Picture, PictureType};
+use parking_lot::Mutex;
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+use super::database::{LibraryDb, LibraryTrack};
+use super::scanner::{self, JobSummary, ScanControl, ScanPhase, ScanProgress, PROGRESS_INTERVAL};
+use crate::metadata::cover::{self, ArtResize};
+use crate::metadata::reader;
+
+/// Subfolder of the app data directory holding the thumbnails.
+pub const THUMBNAIL_DIR: &str = "thumbnails";
+
+/// Longest edge of a thumbnail, in pixels.
+const THUMBNAIL_SIZE: u32 = 300;
+
+const JPEG_QUALITY: u8 = 85;
+
+/// Folder images used when a track has no embedded art (matched by stem).
+const FOLDER_ART_NAMES: &[&str] = &["cover", "folder", "front", "album"];
+
+/// File written next to an album's tracks by [`apply_album_cover`].
+const FOLDER_ART_FILE: &str = "folder.jpg";
+
+/// The track that provides an album's artwork.
+pub struct CoverSource {
+    pub album_id: i64,
+    pub path: String,
+    pub modified_at: i64,
+}
+
+/// Path of the cached thumbnail for a cover track, if it has been generated.
+pub fn cached_thumbnail(dir: &Path, source_path: &str, modified_at: i64) -> Option<String> {
+    let path = thumbnail_file(dir, source_path, modified_at);
+    path.exists().then(|| path.to_string_lossy().to_string())
+}
+
+/// Whether a cover track still needs a thumbnail (or an "artless" marker).
+pub fn needs_thumbnail(dir: &Path, source_path: &str, modified_at: i64) -> bool {
+    !thumbnail_file(dir, source_path, modified_at).exists()
+        && !none_marker(dir, source_path, modified_at).exists()
+}
+
+/// Whether a cover track was probed and found to have no artwork.
+pub fn is_artless(dir: &Path, source_path: &str, modified_at: i64) -> bool {
+    none_marker(dir, source_path, modified_at).exists()
+}
+
+/// Drop a cover track's thumbnail and "artless" marker so the next
+/// [`ensure_thumbnail`] probes it again.
+pub fn forget(dir: &Path, source_path: &str, modified_at: i64) {
+    let _ = std::fs::remove_file(thumbnail_file(dir, source_path, modified_at));
+    let _ = std::fs::remove_file(none_marker(dir, source_path, modified_at));
+}
+
+/// Return the thumbnail for a cover track, generating it if needed.
+/// `Ok(None)` means the album has no artwork.
+pub fn ensure_thumbnail(
+    dir: &Path,
+    source_path: &str,
+    modified_at: i64,
+) -> Result<Option<String>, String> {
+    if let Some(existing) = cached_thumbnail(dir, source_path, modified_at) {
+        return Ok(Some(existing));
+    }
+    std::fs::create_dir_all(dir).map_err(|e| format!("Failed to create dir: {}", e))?;
+
+    let Some(bytes) = find_cover_image(source_path) else {
+        std::fs::write(none_marker(dir, source_path, modified_at), [])
+            .map_err(|e| format!("Write failed: {}", e))?;
+        return Ok(None);
+    };
+
+    let image = image::load_from_memory(&bytes)
+        .map_err(|e| format!("Failed to decode artwork: {}", e))?
+        .thumbnail(THUMBNAIL_SIZE, THUMBNAIL_SIZE)
+        .to_rgb8();
+    let path = thumbnail_file(dir, source_path, modified_at);
+    let mut out = Vec::new();
+    JpegEncoder::new_with_quality(&mut out, JPEG_QUALITY)
+        .encode_image(&image)
+        .map_err(|e| format!("Failed to encode thumbnail: {}", e))?;
+    std::fs::write(&path, out).map_err(|e| format!("Write failed: {}", e))?;
+
+    Ok(Some(path.to_string_lossy().to_string()))
+}
+
+/// Generate thumbnails for every album that doesn't have one yet. Runs as the
+/// last phase of a library scan. Individual failures are logged and skipped.
+pub fn generate_missing(
+    db: &Mutex<LibraryDb>,
+    control: &ScanControl,
+    on_progress: &mut dyn FnMut(&ScanProgress),
+) -> Result<(), String> {
+    let (dir, pending) = {
+        let db = db.lock();
+        (
+            db.thumbnail_dir().to_path_buf(),
+            db.pending_cover_sources()?,
+        )
+    };
+    let started = Instant::now();
+    let mut last_report = Instant::now();
+
+    for (i, source) in pending.iter().enumerate() {
+        if control.is_cancelled() {
+            break;
+        }
+        if last_report.elapsed() >= PROGRESS_INTERVAL {
+            last_report = Instant::now();
+            on_progress(&ScanProgress {
+                phase: ScanPhase::Artwork,
+                files_found: pending.len(),
+                files_processed: i,
+                current_folder: Path::new(&source.path)
+                    .parent()
+                    .map(|p| p.to_string_lossy().to_string())
+                    .unwrap_or_default(),
+                tracks_per_sec: i as f64 / started.elapsed().as_secs_f64().max(0.001),
+            });
+        }
+        if let Err(e) = ensure_thumbnail(&dir, &source.path, source.modified_at) {
+            log::warn!("Thumbnail for album {} failed: {}", source.album_id, e);
+        }
+    }
+    Ok(())
+}
+
+/// Give an album new artwork: embed `image` as the front cover of each of its
+/// tracks and/or save it as `folder.jpg` in each of its folders, then rebuild
+/// the thumbnail. Failures are per file, except that a non-JPEG image has to
+/// decode for the folder copy.
+pub fn apply_album_cover(
+    db: &Mutex<LibraryDb>,
+    album_id: i64,
+    image: &[u8],
+    embed: bool,
+    save_folder: bool,
+) -> Result<JobSummary, String> {
+    let (tracks, old_source) = album_files(db, album_id)?;
+    let mut summary = JobSummary::default();
+
+    if embed {
+        edit_tracks(db, &tracks, &mut summary, |path| {
+            cover::embed_cover(path, image).map(|()| true)
+        });
+    }
+
+    if save_folder {
+        let jpeg = folder_jpeg(image)?;
+        let folders: BTreeSet<&Path> = tracks
+            .iter()
+            .filter_map(|t| Path::new(&t.path).parent())
+            .collect();
+        for folder in folders {
+            let path = folder.join(FOLDER_ART_FILE);
+            match std::fs::write(&path, &jpeg) {
+                Ok(()) => summary.processed += 1,
+                Err(e) => summary
+                    .errors
+                    .push(format!("{}: Write failed: {}", path.display(), e)),
+            }
+        }
+    }
+
+    rebuild_thumbnail(db, album_id, old_source, &mut summary)?;
+    Ok(summary)
+}
+
+/// Strip all embedded pictures from an album's tracks. Folder images are left
+/// alone. `processed` counts the files that had any.
+pub fn remove_album_art(db: &Mutex<LibraryDb>, album_id: i64) -> Result<JobSummary, String> {
+    let (tracks, old_source) = album_files(db, album_id)?;
+    let mut summary = JobSummary::default();
+    edit_tracks(db, &tracks, &mut summary, |path| {
+        cover::remove_pictures(path).map(|n| n > 0)
+    });
+    rebuild_thumbnail(db, album_id, old_source, &mut summary)?;
+    Ok(summary)
+}
+
+/// Deal with embedded pictures over `max_bytes` in an album's tracks: shrink
+/// them with `resize`, or remove them when it is `None`. `processed` counts
+/// the files changed.
+pub fn shrink_album_art(
+    db: &Mutex<LibraryDb>,
+    album_id: i64,
+    max_bytes: usize,
+    resize: Option<&ArtResize>,
+) -> Result<JobSummary, String> {
+    let (tracks, old_source) = album_files(db, album_id)?;
+    let mut summary = JobSummary::default();
+    edit_tracks(db, &tracks, &mut summary, |path| {
+        cover::shrink_pictures(path, max_bytes, resize).map(|n| n > 0)
+    });
+    rebuild_thumbnail(db, album_id, old_source, &mut summary)?;
+    Ok(summary)
+}
+
+/// Write the pictures embedded in an album's tracks out as image files in
+/// each track folder: the front cover as `folder.jpg`, others named by type
+/// (`back.jpg`, `booklet.png`, …) with a number for repeats. Identical
+/// pictures shared by several tracks are written once. Existing files are
+/// kept unless `overwrite`.
+pub fn extract_album_art(
+    db: &Mutex<LibraryDb>,
+    album_id: i64,
+    overwrite: bool,
+) -> Result<JobSummary, String> {
+    let (tracks, _) = album_files(db, album_id)?;
+    let mut summary = JobSummary::default();
+    let mut by_folder: BTreeMap<&Path, Vec<&str>> = BTreeMap::new();
+    for track in &tracks {
+        if let Some(folder) = Path::new(&track.path).parent() {
+            by_folder.entry(folder).or_default().push(&track.path);
+        }
+    }
+
+    for (folder, paths) in by_folder {
+        let mut seen: Vec<Picture> = Vec::new();
+        for path in paths {
+            match cover::read_pictures(path) {
+                Ok(pictures) => {
+                    for picture in pictures {
+                        if !seen.contains(&picture) {
+                            seen.push(picture);
+                        }
+                    }
+                }
+                Err(e) => summary.errors.push(format!("{}: {}", path, e)),
+            }
+        }
+
+        let mut stems_used: BTreeMap<&str, usize> = BTreeMap::new();
+        for picture in &seen {
+            let stem = picture_stem(picture.pic_type());
+            let count = stems_used.entry(stem).or_default();
+            *count += 1;
+            let (bytes, ext) = if picture.pic_type() == PictureType::CoverFront && *count == 1 {
+                match folder_jpeg(picture.data()) {
+                    Ok(jpeg) => (jpeg, "jpg"),
+                    Err(e) => {
+                        summary.errors.push(format!("{}: {}", folder.display(), e));
+                        continue;
+                    }
+                }
+            } else {
+                (picture.data().to_vec(), picture_extension(picture))
+            };
+            let name = match *count {
+                1 => format!("{}.{}", stem, ext),
+                n => format!("{}-{}.{}", stem, n, ext),
+            };
+            let path = folder.join(name);
+            if path.exists() && !overwrite {
+                continue;
+            }
+            match std::fs::write(&path, bytes) {
+                Ok(()) => summary.processed += 1,
+                Err(e) => summary
+                    .errors
+                    .push(format!("{}: Write failed: {}", path.display(), e)),
+            }
+        }
+    }
+    Ok(summary)
+}
+
+/// File name stem for an extracted picture of a given type.
+fn picture_stem(pic_type: PictureType) -> &'static str {
+    match pic_type {
+        PictureType::CoverFront => "folder",
+        PictureType::CoverBack => "back",
+        PictureType::Leaflet => "booklet",
+        PictureType::Media => "disc",
+        PictureType::LeadArtist | PictureType::Artist | PictureType::Band => "artist",
+        PictureType::Conductor => "conductor",
+        PictureType::Composer => "composer",
+        PictureType::Illustration => "illustration",
+        PictureType::BandLogo => "logo",
+        _ => "other",
+    }
+}
+
+/// File extension for a picture's data, from its MIME type or its contents.
+fn picture_extension(picture: &Picture) -> &'static str {
+    match picture.mime_type() {
+        Some(MimeType::Jpeg) => return "jpg",
+        Some(MimeType::Png) => return "png",
+        Some(MimeType::Gif) => return "gif",
+        Some(MimeType::Bmp) => return "bmp",
+        Some(MimeType::Tiff) => return "tif",
+        _ => {}
+    }
+    match image::guess_format(picture.data()) {
+        Ok(ImageFormat::Png) => "png",
+        Ok(ImageFormat::Gif) => "gif",
+        Ok(ImageFormat::Bmp) => "bmp",
+        Ok(ImageFormat::Tiff) => "tif",
+        Ok(ImageFormat::WebP) => "webp",
+        _ => "jpg",
+    }
+}
+
+/// An album's tracks and current cover track.
+fn album_files(
+    db: &Mutex<LibraryDb>,
+    album_id: i64,
+) -> Result<(Vec<LibraryTrack>, Option<CoverSource>), String> {
+    let db = db.lock();
+    let tracks = db.album_tracks(album_id)?;
+    if tracks.is_empty() {
+        return Err(format!("Album {} not found", album_id));
+    }
+    Ok((tracks, db.cover_source(album_id)?))
+}
+
+/// Run `edit` on each track and re-read the ones it reports as changed.
+fn edit_tracks(
+    db: &Mutex<LibraryDb>,
+    tracks: &[LibraryTrack],
+    summary: &mut JobSummary,
+    edit: impl Fn(&str) -> Result<bool, String>,
+) {
+    for track in tracks {
+        match edit(&track.path) {
+            Ok(false) => {}
+            Ok(true) => match scanner::refresh_file(db, &track.path) {
+                Ok(()) => summary.processed += 1,
+                Err(e) => summary.errors.push(format!("{}: {}", track.path, e)),
+            },
+            Err(e) => summary.errors.push(format!("{}: {}", track.path, e)),
+        }
+    }
+}
+
+/// Regenerate an album's thumbnail after its artwork changed.
+fn rebuild_thumbnail(
+    db: &Mutex<LibraryDb>,
+    album_id: i64,
+    old_source: Option<CoverSource>,
+    summary: &mut JobSummary,
+) -> Result<(), String> {
+    let (dir, source) = {
+        let db = db.lock();
+        (db.thumbnail_dir().to_path_buf(), db.cover_source(album_id)?)
+    };
+    // Embedding changes the cover track's mtime and so its cache key, but a
+    // folder image alone does not
+    if let Some(old) = old_source {
+        forget(&dir, &old.path, old.modified_at);
+    }
+    if let Some(source) = source {
+        forget(&dir, &source.path, source.modified_at);
+        if let Err(e) = ensure_thumbnail(&dir, &source.path, source.modified_at) {
+            summary.errors.push(format!("Thumbnail: {}", e));
+        }
+    }
+    Ok(())
+}
+
+/// `image` as JPEG bytes, re-encoded if it is another format.
+fn folder_jpeg(image: &[u8]) -> Result<Vec<u8>, String> {
+    if image::guess_format(image).ok() == Some(ImageFormat::Jpeg) {
+        return Ok(image.to_vec());
+    }
+    let decoded = image::load_from_memory(image)
+        .map_err(|e| format!("Failed to decode artwork: {}", e))?
+        .to_rgb8();
+    let mut out = Vec::new();
+    JpegEncoder::new_with_quality(&mut out, JPEG_QUALITY)
+        .encode_image(&decoded)
+        .map_err(|e| format!("Failed to encode artwork: {}", e))?;
+    Ok(out)
+}
+
+pub fn find_cover_image(track_path: &str) -> Option<Vec<u8>> {
+    if let Ok(Some(bytes)) = reader::read_cover_art(track_path) {
+        return Some(bytes);
+    }
+    let folder = Path::new(track_path).parent()?;
+    let entries = std::fs::read_dir(folder).ok()?;
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let stem = path
+            .file_stem()
+            .map(|s| s.to_string_lossy().to_lowercase())
+            .unwrap_or_default();
+        let ext = path
+            .extension()
+            .map(|e| e.to_string_lossy().to_lowercase())
+            .unwrap_or_default();
+        if FOLDER_ART_NAMES.contains(&stem.as_str())
+            && matches!(ext.as_str(), "jpg" | "jpeg" | "png")
+        {
+            if let Ok(bytes) = std::fs::read(&path) {
+                return Some(bytes);
+            }
+        }
+    }
+    None
+}
+
+fn thumbnail_file(dir: &Path, source_path: &str, modified_at: i64) -> PathBuf {
+    dir.join(format!("{}.jpg", cache_key(source_path, modified_at)))
+}
+
+fn none_marker(dir: &Path, source_path: &str, modified_at: i64) -> PathBuf {
+    dir.join(format!("{}.none", cache_key(source_path, modified_at)))
+}
+
+/// FNV-1a over path + mtime. Stable across runs and Rust versions, unlike
+/// `DefaultHasher`, so cached files stay valid after an update.
+pub fn cache_key(source_path: &str, modified_at: i64) -> String {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for byte in source_path.bytes().chain(modified_at.to_le_bytes()) {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(0x0000_0100_0000_01b3);
+    }
+    format!("{:016x}", hash)
+}
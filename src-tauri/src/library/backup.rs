@@ -0,0 +1,273 @@
+//! Library backup and restore.
+//!
+//! A backup is a portable JSON snapshot of everything a rescan can't recover:
+//! library roots, per-track ratings, add dates and play history, playlists
+//! and bookmarks. Tags and file stats are left out — restoring on another
+//! machine rescans the files there.
+//!
+//! Paths can be remapped on restore ("D:\Music" → "/Volumes/Music"). Track
+//! data is matched by path; entries for files not indexed yet wait in
+//! `pending_restore` and are applied as scans add the files.
+
+use rusqlite::{params, Connection};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+use super::bookmarks::{Bookmark, BookmarkRules};
+use super::database::{unix_now, LibraryDb};
+use crate::playlist::manager::Playlist;
+
+/// Format version written to new backups. Restoring a newer one is refused.
+pub const BACKUP_VERSION: u32 = 1;
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct LibraryBackup {
+    pub version: u32,
+    /// Unix timestamp (seconds).
+    pub created_at: i64,
+    pub roots: Vec<String>,
+    pub tracks: Vec<TrackBackup>,
+    pub playlists: Vec<Playlist>,
+    pub bookmarks: Vec<Bookmark>,
+    pub bookmark_rules: BookmarkRules,
+}
+
+/// User data of one track.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct TrackBackup {
+    pub path: String,
+    pub rating: Option<u8>,
+    pub added_at: Option<i64>,
+    /// Unix timestamps (seconds) of every play.
+    pub plays: Vec<i64>,
+}
+
+/// Replace the `from` folder prefix of paths with `to`.
+#[derive(Clone, Deserialize)]
+pub struct PathMapping {
+    pub from: String,
+    pub to: String,
+}
+
+#[derive(Clone, Serialize, Default)]
+pub struct RestoreReport {
+    pub roots_added: u32,
+    /// Roots that couldn't be added (missing folder, overlapping root).
+    pub roots_skipped: Vec<String>,
+    /// Tracks whose data was applied right away.
+    pub tracks_restored: u32,
+    /// Tracks not in the library yet; applied when a scan finds them.
+    pub tracks_pending: u32,
+    pub playlists: u32,
+    pub bookmarks: u32,
+}
+
+impl LibraryBackup {
+    pub fn read(path: &str) -> Result<Self, String> {
+        let data =
+            std::fs::read_to_string(path).map_err(|e| format!("Failed to read backup: {}", e))?;
+        let backup: Self =
+            serde_json::from_str(&data).map_err(|e| format!("Invalid backup file: {}", e))?;
+        if backup.version > BACKUP_VERSION {
+            return Err(format!(
+                "Backup version {} is newer than this app supports ({})",
+                backup.version, BACKUP_VERSION
+            ));
+        }
+        Ok(backup)
+    }
+
+    pub fn write(&self, path: &str) -> Result<(), String> {
+        if let Some(dir) = Path::new(path).parent() {
+            std::fs::create_dir_all(dir).map_err(|e| format!("Failed to create dir: {}", e))?;
+        }
+        let json =
+            serde_json::to_string_pretty(self).map_err(|e| format!("Serialize failed: {}", e))?;
+        std::fs::write(path, json).map_err(|e| format!("Failed to write backup: {}", e))
+    }
+
+    /// Rewrite every stored path with the first matching mapping.
+    pub fn remap_paths(&mut self, mappings: &[PathMapping]) {
+        if mappings.is_empty() {
+            return;
+        }
+        let map = |path: &mut String| *path = remap_path(path, mappings);
+        self.roots.iter_mut().for_each(map);
+        self.tracks.iter_mut().for_each(|t| map(&mut t.path));
+        for playlist in &mut self.playlists {
+            playlist.tracks.iter_mut().for_each(map);
+        }
+        self.bookmarks.iter_mut().for_each(|b| map(&mut b.path));
+        self.bookmark_rules.folders.iter_mut().for_each(map);
+    }
+}
+
+impl LibraryDb {
+    /// A backup of the library's roots and per-track user data, including
+    /// restored data still waiting for its files to be scanned. Playlists and
+    /// bookmarks are added by the caller, which owns those stores.
+    pub fn backup(&self) -> Result<LibraryBackup, String> {
+        let roots = self.roots()?.into_iter().map(|r| r.path).collect();
+        let mut stmt = self
+            .conn
+            .prepare(
+                "SELECT t.path, t.rating, t.added_at,
+                        (SELECT group_concat(played_at) FROM plays WHERE track_id = t.id)
+                   FROM tracks t
+                 UNION ALL
+                 SELECT path, rating, added_at, plays FROM pending_restore
+                  ORDER BY 1",
+            )
+            .map_err(|e| format!("Failed to export library: {}", e))?;
+        let rows = stmt
+            .query_map([], |row| {
+                let plays: Option<String> = row.get(3)?;
+                Ok(TrackBackup {
+                    path: row.get(0)?,
+                    rating: row.get(1)?,
+                    added_at: row.get(2)?,
+                    plays: parse_plays(plays.as_deref()),
+                })
+            })
+            .map_err(|e| format!("Failed to export library: {}", e))?;
+        let tracks = rows
+            .collect::<Result<_, _>>()
+            .map_err(|e| format!("Failed to export library: {}", e))?;
+        Ok(LibraryBackup {
+            version: BACKUP_VERSION,
+            created_at: unix_now(),
+            roots,
+            tracks,
+            playlists: Vec::new(),
+            bookmarks: Vec::new(),
+            bookmark_rules: BookmarkRules::default(),
+        })
+    }
+
+    /// Add the backup's roots and apply its track data. Fills in the library
+    /// parts of the report.
+    pub fn restore_user_data(
+        &mut self,
+        roots: &[String],
+        tracks: &[TrackBackup],
+        report: &mut RestoreReport,
+    ) -> Result<(), String> {
+        let existing: Vec<String> = self.roots()?.into_iter().map(|r| r.path).collect();
+        for root in roots {
+            if existing.contains(root) {
+                continue;
+            }
+            match self.add_root(root) {
+                Ok(_) => report.roots_added += 1,
+                Err(e) => {
+                    log::warn!("Skipping library root {}: {}", root, e);
+                    report.roots_skipped.push(root.clone());
+                }
+            }
+        }
+
+        let tx = self
+            .conn
+            .transaction()
+            .map_err(|e| format!("Failed to start transaction: {}", e))?;
+        for track in tracks {
+            let plays = track
+                .plays
+                .iter()
+                .map(i64::to_string)
+                .collect::<Vec<_>>()
+                .join(",");
+            tx.execute(
+                "INSERT OR REPLACE INTO pending_restore (path, rating, added_at, plays)
+                 VALUES (?1, ?2, ?3, ?4)",
+                params![track.path, track.rating, track.added_at, plays],
+            )
+            .map_err(|e| format!("Failed to restore {}: {}", track.path, e))?;
+        }
+        let restored =
+            apply_pending(&tx).map_err(|e| format!("Failed to restore library data: {}", e))?;
+        tx.commit()
+            .map_err(|e| format!("Failed to commit library changes: {}", e))?;
+
+        report.tracks_restored = restored as u32;
+        report.tracks_pending = tracks.len().saturating_sub(restored) as u32;
+        Ok(())
+    }
+}
+
+/// Apply restored data to tracks that are now indexed and drop it from the
+/// pending table. Backup values win over existing ones; plays already
+/// recorded are not duplicated. Returns the number of tracks updated.
+pub(super) fn apply_pending(conn: &Connection) -> rusqlite::Result<usize> {
+    let matched: Vec<(i64, TrackBackup)> = {
+        let mut stmt = conn.prepare(
+            "SELECT t.id, p.path, p.rating, p.added_at, p.plays
+               FROM pending_restore p JOIN tracks t ON t.path = p.path",
+        )?;
+        let rows = stmt.query_map([], |row| {
+            let plays: String = row.get(4)?;
+            Ok((
+                row.get(0)?,
+                TrackBackup {
+                    path: row.get(1)?,
+                    rating: row.get(2)?,
+                    added_at: row.get(3)?,
+                    plays: parse_plays(Some(&plays)),
+                },
+            ))
+        })?;
+        rows.collect::<Result<_, _>>()?
+    };
+    for (track_id, track) in &matched {
+        conn.execute(
+            "UPDATE tracks SET rating = COALESCE(?2, rating), added_at = COALESCE(?3, added_at)
+              WHERE id = ?1",
+            params![track_id, track.rating, track.added_at],
+        )?;
+        for played_at in &track.plays {
+            conn.execute(
+                "INSERT INTO plays (track_id, played_at)
+                 SELECT ?1, ?2 WHERE NOT EXISTS
+                        (SELECT 1 FROM plays WHERE track_id = ?1 AND played_at = ?2)",
+                params![track_id, played_at],
+            )?;
+        }
+        conn.execute(
+            "DELETE FROM pending_restore WHERE path = ?1",
+            params![track.path],
+        )?;
+    }
+    Ok(matched.len())
+}
+
+/// Comma-separated play timestamps, as stored by `group_concat`.
+fn parse_plays(plays: Option<&str>) -> Vec<i64> {
+    plays
+        .unwrap_or_default()
+        .split(',')
+        .filter_map(|p| p.trim().parse().ok())
+        .collect()
+}
+
+/// `path` with its prefix replaced by the first matching mapping. The rest
+/// of the path takes the target's separator style, so Windows paths can be
+/// mapped onto a Mac or Linux folder and vice versa.
+fn remap_path(path: &str, mappings: &[PathMapping]) -> String {
+    for mapping in mappings {
+        let from = mapping.from.trim_end_matches(['/', '\\']);
+        let Some(rest) = path.strip_prefix(from) else {
+            continue;
+        };
+        if !(rest.is_empty() || rest.starts_with(['/', '\\'])) {
+            // "Music2" doesn't match "Music"
+            continue;
+        }
+        let separator = if mapping.to.contains('\\') { '\\' } else { '/' };
+        let rest: String = rest
+            .chars()
+            .map(|c| if c == '/' || c == '\\' { separator } else { c })
+            .collect();
+        return format!("{}{}", mapping.to.trim_end_matches(['/', '\\']), rest);
+    }
+    path.to_string()
+}
@@ -0,0 +1,276 @@
+//! Per-track resume bookmarks.
+//!
+//! Long-form content (audiobooks, podcasts, DJ mixes) should pick up where it
+//! was left off. Bookmarking is opt-in: only files inside one of the configured
+//! folders, or tagged with one of the configured genres, are tracked
+//! automatically. Bookmarks set explicitly by the user are always kept.
+//!
+//! A tracker thread samples the engine position every few seconds; starting
+//! playback of a bookmarked file seeks to the saved position. Cue-sheet
+//! tracks are bookmarked by their own path (`Album.cue#03`), not their
+//! image's, with positions within the track.
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use parking_lot::Mutex;
+
+use super::database::LibraryDb;
+use crate::audio::engine::AudioEngine;
+use crate::errors::{self, ErrorCode};
+use crate::metadata::reader;
+
+const BOOKMARKS_FILE: &str = "bookmarks.json";
+
+/// How often the tracker samples the playback position.
+const TRACK_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Positions this close to the end count as "finished" and clear the bookmark.
+const FINISHED_MARGIN_SECS: f64 = 10.0;
+
+/// Positions this close to the start aren't worth resuming from.
+const MIN_RESUME_SECS: f64 = 5.0;
+
+/// Which files get automatic bookmarks.
+#[derive(Clone, Serialize, Deserialize, Default)]
+pub struct BookmarkRules {
+    /// Folder prefixes (e.g. "D:\\Audiobooks").
+    pub folders: Vec<String>,
+    /// Genre names, matched case-insensitively (e.g. "Audiobook", "Podcast").
+    pub genres: Vec<String>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Bookmark {
+    pub path: String,
+    pub position_secs: f64,
+    /// Unix timestamp (seconds) of the last update.
+    pub updated_at: u64,
+}
+
+#[derive(Clone, Serialize, Deserialize, Default)]
+pub struct BookmarkStore {
+    rules: BookmarkRules,
+    bookmarks: HashMap<String, Bookmark>,
+}
+
+impl BookmarkStore {
+    /// Load bookmarks from disk. Returns empty store if file doesn't exist.
+    pub fn load(app_data_dir: &Path) -> Self {
+        let path = app_data_dir.join(BOOKMARKS_FILE);
+        if let Ok(data) = std::fs::read_to_string(&path) {
+            serde_json::from_str(&data).unwrap_or_default()
+        } else {
+            Self::default()
+        }
+    }
+
+    /// Save bookmarks to disk.
+    pub fn save(&self, app_data_dir: &Path) -> Result<(), String> {
+        let path = app_data_dir.join(BOOKMARKS_FILE);
+        std::fs::create_dir_all(app_data_dir)
+            .map_err(|e| format!("Failed to create dir: {}", e))?;
+        let json =
+            serde_json::to_string_pretty(self).map_err(|e| format!("Serialize failed: {}", e))?;
+        std::fs::write(&path, json).map_err(|e| format!("Write failed: {}", e))?;
+        Ok(())
+    }
+
+    pub fn rules(&self) -> BookmarkRules {
+        self.rules.clone()
+    }
+
+    pub fn set_rules(&mut self, rules: BookmarkRules) {
+        self.rules = rules;
+    }
+
+    /// Position to resume from, if the file has a bookmark worth resuming.
+    pub fn resume_position(&self, path: &str) -> Option<f64> {
+        self.bookmarks
+            .get(path)
+            .map(|b| b.position_secs)
+            .filter(|&p| p >= MIN_RESUME_SECS)
+    }
+
+    pub fn set(&mut self, path: &str, position_secs: f64) {
+        self.bookmarks.insert(
+            path.to_string(),
+            Bookmark {
+                path: path.to_string(),
+                position_secs,
+                updated_at: unix_now(),
+            },
+        );
+    }
+
+    /// Remove a bookmark. Returns true if one existed.
+    pub fn clear(&mut self, path: &str) -> bool {
+        self.bookmarks.remove(path).is_some()
+    }
+
+    /// Follow files that were moved (old path to new). Returns true if any
+    /// bookmark changed.
+    pub fn rename_paths(&mut self, moved: &HashMap<String, String>) -> bool {
+        let mut changed = false;
+        for (old, new) in moved {
+            if let Some(mut bookmark) = self.bookmarks.remove(old) {
+                bookmark.path = new.clone();
+                self.bookmarks.insert(new.clone(), bookmark);
+                changed = true;
+            }
+        }
+        changed
+    }
+
+    /// Merge bookmarks and rules from a backup. The newer of two bookmarks
+    /// for the same file wins.
+    pub fn restore(&mut self, bookmarks: Vec<Bookmark>, rules: BookmarkRules) {
+        for bookmark in bookmarks {
+            let newer = self
+                .bookmarks
+                .get(&bookmark.path)
+                .is_none_or(|b| b.updated_at < bookmark.updated_at);
+            if newer {
+                self.bookmarks.insert(bookmark.path.clone(), bookmark);
+            }
+        }
+        for folder in rules.folders {
+            if !self.rules.folders.contains(&folder) {
+                self.rules.folders.push(folder);
+            }
+        }
+        for genre in rules.genres {
+            if !self
+                .rules
+                .genres
+                .iter()
+                .any(|g| g.eq_ignore_ascii_case(&genre))
+            {
+                self.rules.genres.push(genre);
+            }
+        }
+    }
+
+    /// All bookmarks, most recently updated first.
+    pub fn list(&self) -> Vec<Bookmark> {
+        let mut list: Vec<Bookmark> = self.bookmarks.values().cloned().collect();
+        list.sort_by_key(|b| std::cmp::Reverse(b.updated_at));
+        list
+    }
+
+    /// Whether a file opts in to automatic bookmarking.
+    fn qualifies(&self, path: &str) -> bool {
+        let in_folder = self
+            .rules
+            .folders
+            .iter()
+            .any(|f| Path::new(path).starts_with(f));
+        if in_folder {
+            return true;
+        }
+        if self.rules.genres.is_empty() {
+            return false;
+        }
+        let genre = reader::read_metadata(path)
+            .ok()
+            .and_then(|m| m.genre)
+            .unwrap_or_default()
+            .to_lowercase();
+        self.rules
+            .genres
+            .iter()
+            .any(|g| !g.is_empty() && genre.contains(&g.to_lowercase()))
+    }
+}
+
+/// The path a play is bookmarked under: the file, or for a segment of it
+/// the cue-sheet track found in the library by its start. `None` for a
+/// segment the library doesn't know, which has nothing of its own to be
+/// bookmarked under.
+pub fn bookmark_path(
+    library: &Mutex<LibraryDb>,
+    file: &str,
+    start_secs: f64,
+    end_secs: Option<f64>,
+) -> Option<String> {
+    if start_secs <= 0.0 && end_secs.is_none() {
+        return Some(file.to_string());
+    }
+    match library.lock().track_by_segment(file, start_secs) {
+        Ok(track) => track.map(|t| t.path),
+        Err(e) => {
+            log::warn!("{}", e);
+            None
+        }
+    }
+}
+
+/// Spawn the thread that records playback positions for opted-in files.
+pub fn spawn_tracker(
+    engine: Arc<AudioEngine>,
+    store: Arc<Mutex<BookmarkStore>>,
+    library: Arc<Mutex<LibraryDb>>,
+    app_data_dir: PathBuf,
+) {
+    thread::Builder::new()
+        .name("bookmark-tracker".into())
+        .spawn(move || {
+            // (file, start, bookmark path if it qualifies) for the current
+            // play — genre lookups hit the disk
+            let mut cached: Option<(String, f64, Option<String>)> = None;
+
+            loop {
+                thread::sleep(TRACK_INTERVAL);
+
+                let s = engine.get_state();
+                let Some(file) = s.current_file else {
+                    continue;
+                };
+
+                let path = match &cached {
+                    Some((f, start, path)) if *f == file && *start == s.start_secs => path.clone(),
+                    _ => {
+                        let path = bookmark_path(&library, &file, s.start_secs, s.end_secs)
+                            .filter(|_| store.lock().qualifies(&file));
+                        cached = Some((file.clone(), s.start_secs, path.clone()));
+                        path
+                    }
+                };
+                let Some(path) = path else {
+                    continue;
+                };
+
+                let mut store = store.lock();
+                let finished = s.duration_secs > 0.0
+                    && s.position_secs >= s.duration_secs - FINISHED_MARGIN_SECS;
+                let changed = if finished {
+                    store.clear(&path)
+                } else if s.is_playing {
+                    store.set(&path, s.position_secs);
+                    true
+                } else {
+                    false
+                };
+                if changed {
+                    if let Err(e) = store.save(&app_data_dir) {
+                        errors::report(
+                            ErrorCode::SaveFailed,
+                            format!("Failed to save bookmarks: {}", e),
+                        );
+                    }
+                }
+            }
+        })
+        .expect("Failed to spawn bookmark tracker thread");
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
@@ -0,0 +1,328 @@
+//! Audio CD drives: the table of contents, and raw reads of audio sectors
+//! together with the C2 error pointers the drive reports for them.
+//!
+//! Drives are sent MMC commands (READ TOC, READ CD) directly, through the
+//! SG_IO interface on Linux. Other platforms list no drives, and opening
+//! one fails.
+
+use serde::Serialize;
+
+/// Bytes of audio in a sector: 588 stereo frames of 16-bit samples.
+pub const SECTOR_BYTES: usize = 2352;
+
+/// Gap between the last audio session and a data session (enhanced CDs):
+/// the audio lead-out, lead-in and pregap, in sectors.
+const SESSION_GAP_SECTORS: u32 = 11_400;
+
+#[derive(Clone, Serialize)]
+pub struct CdDriveInfo {
+    /// e.g. `/dev/sr0`.
+    pub path: String,
+    /// Vendor and model, e.g. "PLEXTOR DVDR PX-716A".
+    pub name: String,
+}
+
+#[derive(Clone, Copy, Serialize)]
+pub struct TocTrack {
+    pub number: u32,
+    /// First sector.
+    pub start: u32,
+    pub audio: bool,
+}
+
+#[derive(Clone, Serialize)]
+pub struct Toc {
+    pub tracks: Vec<TocTrack>,
+    /// Sector after the last track.
+    pub lead_out: u32,
+}
+
+impl Toc {
+    /// Sector after track `index`: the next track's start, or for the last
+    /// audio track before a data session, the end of the audio session.
+    pub fn end_of(&self, index: usize) -> u32 {
+        match self.tracks.get(index + 1) {
+            Some(next) if self.tracks[index].audio && !next.audio => {
+                next.start.saturating_sub(SESSION_GAP_SECTORS)
+            }
+            Some(next) => next.start,
+            None => self.lead_out,
+        }
+    }
+
+    /// End of the audio: the lead-out, or where a data session begins.
+    pub fn audio_lead_out(&self) -> u32 {
+        match self.tracks.iter().rposition(|t| t.audio) {
+            Some(last) => self.end_of(last),
+            None => self.lead_out,
+        }
+    }
+
+    pub fn has_data_track(&self) -> bool {
+        self.tracks.iter().any(|t| !t.audio)
+    }
+}
+
+/// Sectors read, and for each whether the drive flagged C2 errors in it.
+pub struct SectorRead {
+    pub audio: Vec<u8>,
+    pub c2_errors: Vec<bool>,
+}
+
+pub struct CdDrive {
+    #[cfg(target_os = "linux")]
+    file: std::fs::File,
+    /// The drive returns C2 error pointers; turned off the first time it
+    /// rejects a read asking for them.
+    c2: bool,
+}
+
+impl CdDrive {
+    pub fn c2_supported(&self) -> bool {
+        self.c2
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod linux {
+    use std::ffi::c_void;
+    use std::os::fd::AsRawFd;
+    use std::os::unix::fs::OpenOptionsExt;
+
+    use super::*;
+
+    const SG_IO: u64 = 0x2285;
+    const SG_DXFER_FROM_DEV: i32 = -3;
+    /// Per command; a damaged sector can keep a drive busy a long while.
+    const TIMEOUT_MS: u32 = 60_000;
+    const SENSE_ILLEGAL_REQUEST: u8 = 0x05;
+
+    /// Bytes of C2 error pointers per sector, a bit for each audio byte.
+    const C2_BYTES: usize = 294;
+
+    /// Track number of the lead-out in a table of contents.
+    const LEAD_OUT: u8 = 0xaa;
+
+    /// `struct sg_io_hdr` from `<scsi/sg.h>`.
+    #[repr(C)]
+    struct SgIoHdr {
+        interface_id: i32,
+        dxfer_direction: i32,
+        cmd_len: u8,
+        mx_sb_len: u8,
+        iovec_count: u16,
+        dxfer_len: u32,
+        dxferp: *mut c_void,
+        cmdp: *const u8,
+        sbp: *mut u8,
+        timeout: u32,
+        flags: u32,
+        pack_id: i32,
+        usr_ptr: *mut c_void,
+        status: u8,
+        masked_status: u8,
+        msg_status: u8,
+        sb_len_wr: u8,
+        host_status: u16,
+        driver_status: u16,
+        resid: i32,
+        duration: u32,
+        info: u32,
+    }
+
+    /// A command the drive failed, with the sense key if it gave one.
+    struct CommandError {
+        sense_key: Option<u8>,
+        message: String,
+    }
+
+    pub fn drives() -> Vec<CdDriveInfo> {
+        let Ok(entries) = std::fs::read_dir("/sys/block") else {
+            return Vec::new();
+        };
+        let mut drives: Vec<CdDriveInfo> = entries
+            .flatten()
+            .filter_map(|entry| {
+                let name = entry.file_name().to_string_lossy().to_string();
+                if !name.starts_with("sr") {
+                    return None;
+                }
+                let read = |field: &str| {
+                    std::fs::read_to_string(entry.path().join("device").join(field))
+                        .map(|s| s.trim().to_string())
+                        .unwrap_or_default()
+                };
+                let model = format!("{} {}", read("vendor"), read("model"));
+                Some(CdDriveInfo {
+                    path: format!("/dev/{}", name),
+                    name: match model.trim() {
+                        "" => name,
+                        model => model.to_string(),
+                    },
+                })
+            })
+            .collect();
+        drives.sort_by(|a, b| a.path.cmp(&b.path));
+        drives
+    }
+
+    impl CdDrive {
+        pub fn open(path: &str) -> Result<Self, String> {
+            let file = std::fs::OpenOptions::new()
+                .read(true)
+                .custom_flags(libc::O_NONBLOCK)
+                .open(path)
+                .map_err(|e| format!("Failed to open {}: {}", path, e))?;
+            Ok(Self { file, c2: true })
+        }
+
+        pub fn toc(&self) -> Result<Toc, String> {
+            // READ TOC, format 0, addresses as sectors
+            let mut response = vec![0u8; 4 + 100 * 8];
+            let len = response.len() as u16;
+            let cdb = [0x43, 0, 0, 0, 0, 0, 1, (len >> 8) as u8, len as u8, 0];
+            self.command(&cdb, &mut response)
+                .map_err(|e| format!("Failed to read the table of contents: {}", e.message))?;
+
+            let data_len = usize::from(u16::from_be_bytes([response[0], response[1]])) + 2;
+            let mut tracks = Vec::new();
+            let mut lead_out = None;
+            for entry in response[4..data_len.min(response.len())].chunks_exact(8) {
+                let start = u32::from_be_bytes([entry[4], entry[5], entry[6], entry[7]]);
+                if entry[2] == LEAD_OUT {
+                    lead_out = Some(start);
+                } else {
+                    tracks.push(TocTrack {
+                        number: u32::from(entry[2]),
+                        start,
+                        // Control bit 2 marks data tracks
+                        audio: entry[1] & 0x04 == 0,
+                    });
+                }
+            }
+            match lead_out {
+                Some(lead_out) if !tracks.is_empty() => Ok(Toc { tracks, lead_out }),
+                _ => Err("No disc, or the disc has no tracks".to_string()),
+            }
+        }
+
+        pub fn read(&mut self, lba: u32, count: u32) -> Result<SectorRead, String> {
+            if self.c2 {
+                match self.read_cd(lba, count, true) {
+                    Err(e) if e.sense_key == Some(SENSE_ILLEGAL_REQUEST) => {
+                        log::info!("Drive doesn't report C2 errors; reading without them");
+                        self.c2 = false;
+                    }
+                    result => return result.map_err(|e| e.message),
+                }
+            }
+            self.read_cd(lba, count, false).map_err(|e| e.message)
+        }
+
+        fn read_cd(&self, lba: u32, count: u32, c2: bool) -> Result<SectorRead, CommandError> {
+            let per_sector = SECTOR_BYTES + if c2 { C2_BYTES } else { 0 };
+            let mut buffer = vec![0u8; per_sector * count as usize];
+            let lba = lba.to_be_bytes();
+            let count_bytes = count.to_be_bytes();
+            // READ CD: CD-DA sectors, user data, and the C2 error bits
+            let cdb = [
+                0xbe,
+                0x04,
+                lba[0],
+                lba[1],
+                lba[2],
+                lba[3],
+                count_bytes[1],
+                count_bytes[2],
+                count_bytes[3],
+                if c2 { 0x12 } else { 0x10 },
+                0,
+                0,
+            ];
+            self.command(&cdb, &mut buffer)?;
+
+            let mut audio = Vec::with_capacity(SECTOR_BYTES * count as usize);
+            let mut c2_errors = Vec::with_capacity(count as usize);
+            for sector in buffer.chunks_exact(per_sector) {
+                audio.extend_from_slice(&sector[..SECTOR_BYTES]);
+                c2_errors.push(sector[SECTOR_BYTES..].iter().any(|&b| b != 0));
+            }
+            Ok(SectorRead { audio, c2_errors })
+        }
+
+        fn command(&self, cdb: &[u8], buffer: &mut [u8]) -> Result<(), CommandError> {
+            let mut sense = [0u8; 32];
+            let mut header = SgIoHdr {
+                interface_id: i32::from(b'S'),
+                dxfer_direction: SG_DXFER_FROM_DEV,
+                cmd_len: cdb.len() as u8,
+                mx_sb_len: sense.len() as u8,
+                iovec_count: 0,
+                dxfer_len: buffer.len() as u32,
+                dxferp: buffer.as_mut_ptr() as *mut c_void,
+                cmdp: cdb.as_ptr(),
+                sbp: sense.as_mut_ptr(),
+                timeout: TIMEOUT_MS,
+                flags: 0,
+                pack_id: 0,
+                usr_ptr: std::ptr::null_mut(),
+                status: 0,
+                masked_status: 0,
+                msg_status: 0,
+                sb_len_wr: 0,
+                host_status: 0,
+                driver_status: 0,
+                resid: 0,
+                duration: 0,
+                info: 0,
+            };
+            // SAFETY: the header points at `cdb`, `buffer` and `sense`, which
+            // outlive the call, with their lengths
+            let rc = unsafe { libc::ioctl(self.file.as_raw_fd(), SG_IO as _, &mut header) };
+            if rc < 0 {
+                return Err(CommandError {
+                    sense_key: None,
+                    message: format!("Drive command failed: {}", std::io::Error::last_os_error()),
+                });
+            }
+            if header.status != 0 || header.host_status != 0 || header.driver_status & 0x0f != 0 {
+                let sense_key = (header.sb_len_wr > 2).then(|| sense[2] & 0x0f);
+                return Err(CommandError {
+                    sense_key,
+                    message: match sense_key {
+                        Some(key) => format!(
+                            "Drive error (sense {:x}/{:02x}/{:02x})",
+                            key, sense[12], sense[13]
+                        ),
+                        None => format!("Drive error (status {:02x})", header.status),
+                    },
+                });
+            }
+            Ok(())
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+pub use linux::drives;
+
+/// Drives on this machine.
+#[cfg(not(target_os = "linux"))]
+pub fn drives() -> Vec<CdDriveInfo> {
+    Vec::new()
+}
+
+#[cfg(not(target_os = "linux"))]
+impl CdDrive {
+    pub fn open(_path: &str) -> Result<Self, String> {
+        Err("Reading audio CDs isn't supported on this platform yet".to_string())
+    }
+
+    pub fn toc(&self) -> Result<Toc, String> {
+        unreachable!("no drive can be opened")
+    }
+
+    pub fn read(&mut self, _lba: u32, _count: u32) -> Result<SectorRead, String> {
+        unreachable!("no drive can be opened")
+    }
+}
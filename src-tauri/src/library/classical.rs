@@ -0,0 +1,112 @@
+//! Classical browsing: composers, and each composer's works with their
+//! movements grouped underneath.
+//!
+//! A work is identified by its WORK tag within one album, so two recordings
+//! of the same symphony show up as two works. Tracks with a composer but no
+//! WORK tag are listed as single-movement works under their own title.
+
+use rusqlite::params;
+use serde::Serialize;
+
+use super::database::{LibraryDb, LibraryTrack, TRACK_COLUMNS};
+use super::query::Paging;
+
+#[derive(Clone, Serialize)]
+pub struct LibraryComposer {
+    pub name: String,
+    pub work_count: u32,
+    pub track_count: u32,
+}
+
+#[derive(Clone, Serialize)]
+pub struct ClassicalWork {
+    pub title: String,
+    pub composer: String,
+    /// Album (recording) the work is on.
+    pub album: Option<String>,
+    pub album_id: Option<i64>,
+    pub duration_secs: f64,
+    /// Movements in movement/track order.
+    pub movements: Vec<LibraryTrack>,
+}
+
+impl LibraryDb {
+    /// Composers with visible tracks. Names are grouped case-insensitively.
+    pub fn composers(&self, paging: Paging) -> Result<Vec<LibraryComposer>, String> {
+        let mut stmt = self
+            .conn
+            .prepare(&format!(
+                "SELECT MIN(composer),
+                        COUNT(DISTINCT lower(COALESCE(work, 'track:' || id)) || '|' ||
+                                       COALESCE(album_id, '')),
+                        COUNT(*)
+                   FROM visible_tracks
+                  WHERE composer IS NOT NULL
+                  GROUP BY composer COLLATE NOCASE
+                  ORDER BY composer COLLATE NOCASE{}",
+                paging.sql()
+            ))
+            .map_err(|e| format!("Failed to query composers: {}", e))?;
+        let rows = stmt
+            .query_map([], |row| {
+                Ok(LibraryComposer {
+                    name: row.get(0)?,
+                    work_count: row.get(1)?,
+                    track_count: row.get(2)?,
+                })
+            })
+            .map_err(|e| format!("Failed to query composers: {}", e))?;
+        rows.collect::<Result<_, _>>()
+            .map_err(|e| format!("Failed to query composers: {}", e))
+    }
+
+    /// Works by a composer, each with its movements.
+    pub fn composer_works(&self, composer: &str) -> Result<Vec<ClassicalWork>, String> {
+        let tracks = self.query_tracks(
+            &format!(
+                "SELECT {} FROM visible_tracks
+                  WHERE composer = ?1 COLLATE NOCASE
+                  ORDER BY COALESCE(work, title) COLLATE NOCASE, album_id, work IS NULL,
+                           movement_number IS NULL, movement_number,
+                           disc_number, track_number, path",
+                TRACK_COLUMNS
+            ),
+            params![composer],
+        )?;
+
+        let mut works: Vec<ClassicalWork> = Vec::new();
+        for track in tracks {
+            if let Some(work) = works.last_mut() {
+                if same_work(work, &track) {
+                    work.duration_secs += track.duration_secs;
+                    work.movements.push(track);
+                    continue;
+                }
+            }
+            works.push(ClassicalWork {
+                title: track
+                    .work
+                    .clone()
+                    .or_else(|| track.title.clone())
+                    .unwrap_or_else(|| track.path.clone()),
+                composer: track.composer.clone().unwrap_or_default(),
+                album: track.album.clone(),
+                album_id: track.album_id,
+                duration_secs: track.duration_secs,
+                movements: vec![track],
+            });
+        }
+        Ok(works)
+    }
+}
+
+/// Whether `track` is another movement of `work`. Work-less tracks never
+/// group, each is a work of its own.
+fn same_work(work: &ClassicalWork, track: &LibraryTrack) -> bool {
+    let Some(title) = track.work.as_deref() else {
+        return false;
+    };
+    work.movements[0].work.is_some()
+        && work.album_id == track.album_id
+        && work.title.to_lowercase() == title.to_lowercase()
+}
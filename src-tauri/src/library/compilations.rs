@@ -0,0 +1,108 @@
+//! Various-artists compilations.
+//!
+//! A compilation is filed under a single "Various Artists" album instead of
+//! one album per track artist. Tracks are recognised as such by their
+//! COMPILATION flag, a "Various Artists"-style album artist, or — for
+//! untagged rips — by a folder holding one album title by many artists.
+
+use rusqlite::{params, Connection};
+use std::collections::{HashMap, HashSet};
+use std::path::MAIN_SEPARATOR;
+
+use super::database::{dir_prefix, ensure_album, ensure_artist};
+
+/// Album artist that compilations are filed under.
+pub const VARIOUS_ARTISTS: &str = "Various Artists";
+
+/// Album artist tags meaning "various artists".
+const VARIOUS_ALIASES: &[&str] = &["various artists", "various", "va", "v.a.", "v/a"];
+
+/// Distinct track artists needed before a folder counts as a compilation.
+const MIN_ARTISTS: usize = 3;
+
+/// A track without album artist, as seen by the folder heuristic.
+struct FolderTrack {
+    id: i64,
+    album: String,
+    artist_id: Option<i64>,
+    year: Option<u32>,
+}
+
+pub fn is_various_artists(name: &str) -> bool {
+    VARIOUS_ALIASES.contains(&name.trim().to_lowercase().as_str())
+}
+
+/// Re-evaluate the folder heuristic for the given folders: tracks directly in
+/// one folder that share an album title but have no album artist or
+/// compilation flag are moved to a "Various Artists" album when they are by
+/// several artists, none of which has half the tracks. Otherwise they're
+/// (re)assigned to their own artist's album, undoing an earlier grouping.
+pub(super) fn group_by_folder(conn: &Connection, dirs: &HashSet<String>) -> rusqlite::Result<()> {
+    let mut various_id = None;
+    for dir in dirs {
+        // Tracks directly in `dir`, not in subfolders
+        let tracks: Vec<FolderTrack> = {
+            let mut stmt = conn.prepare(
+                "SELECT id, album, artist_id, year FROM tracks
+                  WHERE album IS NOT NULL AND album_artist IS NULL AND compilation = 0
+                    AND substr(path, 1, length(?1)) = ?1
+                    AND instr(substr(path, length(?1) + 1), ?2) = 0",
+            )?;
+            let rows = stmt.query_map(
+                params![dir_prefix(dir), MAIN_SEPARATOR.to_string()],
+                |row| {
+                    Ok(FolderTrack {
+                        id: row.get(0)?,
+                        album: row.get(1)?,
+                        artist_id: row.get(2)?,
+                        year: row.get(3)?,
+                    })
+                },
+            )?;
+            rows.collect::<Result<_, _>>()?
+        };
+
+        let mut albums: HashMap<String, Vec<FolderTrack>> = HashMap::new();
+        for track in tracks {
+            albums
+                .entry(track.album.to_lowercase())
+                .or_default()
+                .push(track);
+        }
+
+        for group in albums.values() {
+            let artists: Vec<Option<i64>> = group.iter().map(|t| t.artist_id).collect();
+            let compilation = looks_like_compilation(&artists);
+            let year = group.iter().filter_map(|t| t.year).min();
+            // Spelled as the first track has it, for case variants
+            let various_title = &group[0].album;
+            for track in group {
+                let album_id = if compilation {
+                    let owner = match various_id {
+                        Some(owner) => owner,
+                        None => *various_id.insert(ensure_artist(conn, VARIOUS_ARTISTS)?),
+                    };
+                    ensure_album(conn, various_title, Some(owner), year)?
+                } else {
+                    ensure_album(conn, &track.album, track.artist_id, track.year)?
+                };
+                conn.execute(
+                    "UPDATE tracks SET album_id = ?2 WHERE id = ?1 AND album_id IS NOT ?2",
+                    params![track.id, album_id],
+                )?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Several artists, none of which has half the tracks — a compilation rather
+/// than an artist album with a few guest spots.
+fn looks_like_compilation(artist_ids: &[Option<i64>]) -> bool {
+    let mut counts: HashMap<Option<i64>, usize> = HashMap::new();
+    for id in artist_ids {
+        *counts.entry(*id).or_default() += 1;
+    }
+    let top = counts.values().copied().max().unwrap_or(0);
+    counts.len() >= MIN_ARTISTS && top * 2 < artist_ids.len()
+}
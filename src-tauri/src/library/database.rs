@@ -1,2 +1,721 @@
-// Library database - will be implemented in Phase 2
-// Placeholder for SQLite-backed music library
+//! SQLite-backed music library.
+//!
+//! Scans populate `tracks` with the tag metadata and file stats (size, mtime)
+//! of every audio file, plus normalised `artists` and `albums` rows, so the
+//! library can be listed without re-reading any tags. The file stats let a
+//! rescan skip files that haven't changed.
+//!
+//! Tracks come from one or more library roots (internal drive, NAS, external
+//! disk). Tracks under a disabled root stay indexed but are hidden from every
+//! query via the `visible_tracks` view; tracks under an offline (unmounted)
+//! root are kept as-is until it comes back.
+
+use rusqlite::{params, Connection, OptionalExtension, Row};
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf, MAIN_SEPARATOR};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use super::artists;
+use super::artwork::THUMBNAIL_DIR;
+use super::backup;
+use super::compilations::{self, is_various_artists, VARIOUS_ARTISTS};
+use super::genres;
+use super::migrations;
+use super::query::Paging;
+use crate::metadata::reader::TrackMetadata;
+use crate::metadata::values::split_featured;
+use crate::paths::{self, ROOT_TIMEOUT};
+
+const DB_FILE: &str = "library.db";
+/// Copy of the database taken before a schema upgrade.
+const BACKUP_FILE: &str = "library.db.bak";
+
+pub(super) const TRACK_COLUMNS: &str =
+    "id, path, title, artist, album, album_artist, year, genre, \
+    track_number, disc_number, duration_secs, sample_rate, bit_depth, channels, format, \
+    has_album_art, file_size, modified_at, artist_id, album_id, rating, \
+    composer, work, movement, movement_number, compilation, added_at, dynamic_range, \
+    spectral_cutoff_hz, transcode_confidence, artist_sort, album_sort, album_artist_sort, \
+    source_path, start_secs, end_secs";
+
+/// Tracks grouped by album, album artists and albums in sort-tag order.
+pub(super) const TRACK_ALBUM_ORDER: &str =
+    "COALESCE(album_artist_sort, album_artist) COLLATE NOCASE, \
+    COALESCE(album_sort, album) COLLATE NOCASE, disc_number, track_number, path";
+
+/// Number of columns in [`TRACK_COLUMNS`]; extra selected columns start here.
+pub(super) const TRACK_COLUMN_COUNT: usize = 36;
+
+#[derive(Clone, Serialize)]
+pub struct LibraryRoot {
+    pub id: i64,
+    pub path: String,
+    pub enabled: bool,
+    /// False when the folder can't be reached (unmounted disk, NAS offline).
+    pub online: bool,
+    pub track_count: u32,
+    /// Unix timestamp (seconds) of the last completed scan.
+    pub last_scanned_at: Option<i64>,
+}
+
+/// Size and modification time of a file, used to detect changes between scans.
+#[derive(Clone, Copy, PartialEq)]
+pub struct FileStats {
+    pub size: i64,
+    /// Unix timestamp (seconds).
+    pub modified_at: i64,
+}
+
+#[derive(Clone, Serialize)]
+pub struct LibraryTrack {
+    pub id: i64,
+    pub path: String,
+    pub title: Option<String>,
+    pub artist: Option<String>,
+    pub album: Option<String>,
+    pub album_artist: Option<String>,
+    pub year: Option<u32>,
+    pub genre: Option<String>,
+    pub track_number: Option<u32>,
+    pub disc_number: Option<u32>,
+    pub duration_secs: f64,
+    pub sample_rate: Option<u32>,
+    pub bit_depth: Option<u8>,
+    pub channels: Option<u8>,
+    pub format: String,
+    pub has_album_art: bool,
+    pub file_size: i64,
+    pub modified_at: i64,
+    pub artist_id: Option<i64>,
+    pub album_id: Option<i64>,
+    /// 1–5 stars, `None` = unrated.
+    pub rating: Option<u8>,
+    pub composer: Option<String>,
+    pub work: Option<String>,
+    pub movement: Option<String>,
+    pub movement_number: Option<u32>,
+    /// Tagged as part of a compilation.
+    pub compilation: bool,
+    /// Unix timestamp (seconds) when the track was first indexed.
+    pub added_at: Option<i64>,
+    /// Measured DR (TT-DR scale, unrounded), `None` until analysed.
+    pub dynamic_range: Option<f64>,
+    /// Highest frequency with content, from the transcode analysis.
+    pub spectral_cutoff_hz: Option<u32>,
+    /// Likelihood (0.0–1.0) that a lossless file was transcoded from a
+    /// lossy source, `None` until analysed.
+    pub transcode_confidence: Option<f64>,
+    /// Sort tags, used for ordering when present.
+    pub artist_sort: Option<String>,
+    pub album_sort: Option<String>,
+    pub album_artist_sort: Option<String>,
+    /// Audio file of a cue-sheet track, whose `path` is `Album.cue#03`;
+    /// `None` for plain files.
+    pub source_path: Option<String>,
+    /// Segment of `source_path` the cue-sheet track covers.
+    pub start_secs: Option<f64>,
+    pub end_secs: Option<f64>,
+}
+
+pub struct LibraryDb {
+    pub(super) conn: Connection,
+    thumbnail_dir: PathBuf,
+}
+
+impl LibraryDb {
+    /// Open (or create) the library database in the app data directory.
+    pub fn open(app_data_dir: &Path) -> Result<Self, String> {
+        std::fs::create_dir_all(app_data_dir)
+            .map_err(|e| format!("Failed to create dir: {}", e))?;
+        let conn = Connection::open(app_data_dir.join(DB_FILE))
+            .map_err(|e| format!("Failed to open library database: {}", e))?;
+        Self::init(
+            conn,
+            app_data_dir.join(THUMBNAIL_DIR),
+            Some(app_data_dir.join(BACKUP_FILE)),
+        )
+    }
+
+    /// In-memory database, used when the on-disk one can't be opened.
+    pub fn open_in_memory() -> Result<Self, String> {
+        let conn = Connection::open_in_memory()
+            .map_err(|e| format!("Failed to open library database: {}", e))?;
+        Self::init(
+            conn,
+            std::env::temp_dir().join("masukii").join(THUMBNAIL_DIR),
+            None,
+        )
+    }
+
+    fn init(
+        mut conn: Connection,
+        thumbnail_dir: PathBuf,
+        backup_path: Option<PathBuf>,
+    ) -> Result<Self, String> {
+        conn.execute_batch("PRAGMA journal_mode = WAL; PRAGMA foreign_keys = ON;")
+            .map_err(|e| format!("Failed to configure library database: {}", e))?;
+        migrations::migrate(&mut conn, backup_path.as_deref())?;
+
+        // Temp views live per connection, so this is recreated on every open
+        conn.execute_batch(&format!(
+            "CREATE TEMP VIEW IF NOT EXISTS visible_tracks AS
+             SELECT * FROM tracks t
+              WHERE NOT EXISTS (
+                    SELECT 1 FROM library_roots r
+                     WHERE r.enabled = 0 AND {})",
+            under_root_sql("t.path", "r.path")
+        ))
+        .map_err(|e| format!("Failed to create library views: {}", e))?;
+        Ok(Self {
+            conn,
+            thumbnail_dir,
+        })
+    }
+
+    /// Where album thumbnails are cached.
+    pub fn thumbnail_dir(&self) -> &Path {
+        &self.thumbnail_dir
+    }
+
+    pub fn roots(&self) -> Result<Vec<LibraryRoot>, String> {
+        let mut stmt = self
+            .conn
+            .prepare(&format!(
+                "SELECT r.id, r.path, r.enabled, r.last_scanned_at,
+                        (SELECT COUNT(*) FROM tracks t WHERE {})
+                   FROM library_roots r
+                  ORDER BY r.path",
+                under_root_sql("t.path", "r.path")
+            ))
+            .map_err(|e| format!("Failed to query library roots: {}", e))?;
+        let rows = stmt
+            .query_map([], |row| {
+                let path: String = row.get(1)?;
+                Ok(LibraryRoot {
+                    id: row.get(0)?,
+                    online: false,
+                    path,
+                    enabled: row.get(2)?,
+                    last_scanned_at: row.get(3)?,
+                    track_count: row.get(4)?,
+                })
+            })
+            .map_err(|e| format!("Failed to query library roots: {}", e))?;
+        let mut roots: Vec<LibraryRoot> = rows
+            .collect::<Result<_, _>>()
+            .map_err(|e| format!("Failed to query library roots: {}", e))?;
+
+        // A share that doesn't answer in time counts as offline
+        let root_paths: Vec<String> = roots.iter().map(|r| r.path.clone()).collect();
+        let online = paths::dirs_reachable(&root_paths, ROOT_TIMEOUT);
+        for (root, online) in roots.iter_mut().zip(online) {
+            root.online = online;
+        }
+        Ok(roots)
+    }
+
+    pub fn root(&self, id: i64) -> Result<LibraryRoot, String> {
+        self.roots()?
+            .into_iter()
+            .find(|r| r.id == id)
+            .ok_or_else(|| format!("Library root {} not found", id))
+    }
+
+    /// Register a new library root. Folders inside (or containing) an existing
+    /// root are rejected, since their tracks would belong to both.
+    pub fn add_root(&mut self, path: &str) -> Result<LibraryRoot, String> {
+        let path = normalize_root(path);
+        let is_dir = paths::with_timeout(ROOT_TIMEOUT, {
+            let path = path.clone();
+            move || Path::new(&path).is_dir()
+        })
+        .map_err(|e| format!("Folder not reachable: {}: {}", path, e))?;
+        if !is_dir {
+            return Err(format!("Not a folder: {}", path));
+        }
+        for existing in self.roots()? {
+            if Path::new(&path).starts_with(&existing.path)
+                || Path::new(&existing.path).starts_with(&path)
+            {
+                return Err(format!("Overlaps existing library root {}", existing.path));
+            }
+        }
+        self.conn
+            .execute(
+                "INSERT INTO library_roots (path) VALUES (?1)",
+                params![path],
+            )
+            .map_err(|e| format!("Failed to add library root: {}", e))?;
+        self.root(self.conn.last_insert_rowid())
+    }
+
+    /// Unregister a root and drop its tracks from the library.
+    pub fn remove_root(&mut self, id: i64) -> Result<(), String> {
+        let root = self.root(id)?;
+        let paths: Vec<String> = self.file_stats_under(&root.path)?.into_keys().collect();
+        self.remove_tracks(&paths)?;
+        self.conn
+            .execute("DELETE FROM library_roots WHERE id = ?1", params![id])
+            .map_err(|e| format!("Failed to remove library root: {}", e))?;
+        Ok(())
+    }
+
+    pub fn set_root_enabled(&mut self, id: i64, enabled: bool) -> Result<(), String> {
+        let changed = self
+            .conn
+            .execute(
+                "UPDATE library_roots SET enabled = ?2 WHERE id = ?1",
+                params![id, enabled],
+            )
+            .map_err(|e| format!("Failed to update library root: {}", e))?;
+        if changed == 0 {
+            return Err(format!("Library root {} not found", id));
+        }
+        Ok(())
+    }
+
+    pub fn mark_root_scanned(&mut self, id: i64, at: i64) -> Result<(), String> {
+        self.conn
+            .execute(
+                "UPDATE library_roots SET last_scanned_at = ?2 WHERE id = ?1",
+                params![id, at],
+            )
+            .map_err(|e| format!("Failed to update library root: {}", e))?;
+        Ok(())
+    }
+
+    /// File stats of every indexed track under `root`, keyed by path.
+    pub fn file_stats_under(&self, root: &str) -> Result<HashMap<String, FileStats>, String> {
+        let mut stmt = self
+            .conn
+            .prepare(
+                "SELECT path, file_size, modified_at FROM tracks
+                 WHERE substr(path, 1, length(?1)) = ?1",
+            )
+            .map_err(|e| format!("Failed to query library: {}", e))?;
+        let rows = stmt
+            .query_map(params![dir_prefix(root)], |row| {
+                Ok((
+                    row.get::<_, String>(0)?,
+                    FileStats {
+                        size: row.get(1)?,
+                        modified_at: row.get(2)?,
+                    },
+                ))
+            })
+            .map_err(|e| format!("Failed to query library: {}", e))?;
+        rows.collect::<Result<_, _>>()
+            .map_err(|e| format!("Failed to query library: {}", e))
+    }
+
+    /// Paths of indexed tracks under `root` shorter than `min_secs`.
+    pub fn short_tracks_under(&self, root: &str, min_secs: f64) -> Result<HashSet<String>, String> {
+        let mut stmt = self
+            .conn
+            .prepare(
+                "SELECT path FROM tracks
+                 WHERE substr(path, 1, length(?1)) = ?1 AND duration_secs < ?2",
+            )
+            .map_err(|e| format!("Failed to query library: {}", e))?;
+        let rows = stmt
+            .query_map(params![dir_prefix(root), min_secs], |row| row.get(0))
+            .map_err(|e| format!("Failed to query library: {}", e))?;
+        rows.collect::<Result<_, _>>()
+            .map_err(|e| format!("Failed to query library: {}", e))
+    }
+
+    /// Insert or update tracks in a single transaction.
+    pub fn upsert_tracks(&mut self, tracks: &[(TrackMetadata, FileStats)]) -> Result<(), String> {
+        let tx = self
+            .conn
+            .transaction()
+            .map_err(|e| format!("Failed to start transaction: {}", e))?;
+        let mut dirs = HashSet::new();
+        for (meta, stats) in tracks {
+            upsert_track(&tx, meta, *stats)
+                .map_err(|e| format!("Failed to store {}: {}", meta.file_path, e))?;
+            if let Some(dir) = Path::new(&meta.file_path).parent() {
+                dirs.insert(dir.to_string_lossy().to_string());
+            }
+        }
+        compilations::group_by_folder(&tx, &dirs)
+            .map_err(|e| format!("Failed to group compilations: {}", e))?;
+        // Retagged tracks may have left their old album/artist empty
+        prune_orphans(&tx).map_err(|e| format!("Failed to prune library: {}", e))?;
+        backup::apply_pending(&tx)
+            .map_err(|e| format!("Failed to apply restored library data: {}", e))?;
+        tx.commit()
+            .map_err(|e| format!("Failed to commit library changes: {}", e))
+    }
+
+    /// Remove tracks by path.
+    pub fn remove_tracks(&mut self, paths: &[String]) -> Result<(), String> {
+        let tx = self
+            .conn
+            .transaction()
+            .map_err(|e| format!("Failed to start transaction: {}", e))?;
+        for path in paths {
+            tx.execute("DELETE FROM tracks WHERE path = ?1", params![path])
+                .map_err(|e| format!("Failed to remove {}: {}", path, e))?;
+        }
+        prune_orphans(&tx).map_err(|e| format!("Failed to prune library: {}", e))?;
+        tx.commit()
+            .map_err(|e| format!("Failed to commit library changes: {}", e))
+    }
+
+    pub fn tracks(&self, paging: Paging) -> Result<Vec<LibraryTrack>, String> {
+        self.query_tracks(
+            &format!(
+                "SELECT {} FROM visible_tracks ORDER BY {}{}",
+                TRACK_COLUMNS,
+                TRACK_ALBUM_ORDER,
+                paging.sql()
+            ),
+            params![],
+        )
+    }
+
+    pub fn track_by_path(&self, path: &str) -> Result<Option<LibraryTrack>, String> {
+        self.conn
+            .query_row(
+                &format!("SELECT {} FROM tracks WHERE path = ?1", TRACK_COLUMNS),
+                params![path],
+                track_from_row,
+            )
+            .optional()
+            .map_err(|e| format!("Failed to query library: {}", e))
+    }
+
+    /// The cue-sheet track that starts `start_secs` into the audio file
+    /// `source`.
+    pub fn track_by_segment(
+        &self,
+        source: &str,
+        start_secs: f64,
+    ) -> Result<Option<LibraryTrack>, String> {
+        self.conn
+            .query_row(
+                &format!(
+                    "SELECT {} FROM tracks
+                      WHERE source_path = ?1 AND ABS(start_secs - ?2) < 0.001",
+                    TRACK_COLUMNS
+                ),
+                params![source, start_secs],
+                track_from_row,
+            )
+            .optional()
+            .map_err(|e| format!("Failed to query library: {}", e))
+    }
+
+    /// Set (1–5 stars) or clear the rating of tracks. Returns the paths of
+    /// the tracks that were found.
+    pub fn set_rating(&mut self, ids: &[i64], rating: Option<u8>) -> Result<Vec<String>, String> {
+        let tx = self
+            .conn
+            .transaction()
+            .map_err(|e| format!("Failed to start transaction: {}", e))?;
+        let mut paths = Vec::with_capacity(ids.len());
+        for id in ids {
+            let path: Option<String> = tx
+                .query_row(
+                    "UPDATE tracks SET rating = ?2 WHERE id = ?1 RETURNING path",
+                    params![id, rating],
+                    |row| row.get(0),
+                )
+                .optional()
+                .map_err(|e| format!("Failed to set rating: {}", e))?;
+            paths.extend(path);
+        }
+        tx.commit()
+            .map_err(|e| format!("Failed to commit library changes: {}", e))?;
+        Ok(paths)
+    }
+
+    pub(super) fn query_tracks(
+        &self,
+        sql: &str,
+        params: &[&dyn rusqlite::ToSql],
+    ) -> Result<Vec<LibraryTrack>, String> {
+        let mut stmt = self
+            .conn
+            .prepare(sql)
+            .map_err(|e| format!("Failed to query tracks: {}", e))?;
+        let rows = stmt
+            .query_map(params, track_from_row)
+            .map_err(|e| format!("Failed to query tracks: {}", e))?;
+        rows.collect::<Result<_, _>>()
+            .map_err(|e| format!("Failed to query tracks: {}", e))
+    }
+}
+
+fn upsert_track(conn: &Connection, meta: &TrackMetadata, stats: FileStats) -> rusqlite::Result<()> {
+    let (main_artist, guests) =
+        artists::credited_artists(meta.artist.as_deref(), meta.title.as_deref());
+    let artist_id = match main_artist.as_deref() {
+        Some(name) => Some(ensure_artist(conn, name)?),
+        None => None,
+    };
+    if let Some(id) = artist_id {
+        set_artist_sort(conn, id, meta.artist_sort.as_deref())?;
+    }
+    // Albums belong to the album artist, falling back to the track artist
+    let album_id = match meta.album.as_deref() {
+        Some(title) => {
+            let album_artist = artists::credited_artists(meta.album_artist.as_deref(), None).0;
+            let owner =
+                if meta.compilation || album_artist.as_deref().is_some_and(is_various_artists) {
+                    Some(ensure_artist(conn, VARIOUS_ARTISTS)?)
+                } else {
+                    match album_artist.as_deref() {
+                        Some(name) => {
+                            let id = ensure_artist(conn, name)?;
+                            set_artist_sort(conn, id, meta.album_artist_sort.as_deref())?;
+                            Some(id)
+                        }
+                        None => artist_id,
+                    }
+                };
+            let album_id = ensure_album(conn, title, owner, meta.year)?;
+            if let Some(sort) = meta.album_sort.as_deref() {
+                conn.execute(
+                    "UPDATE albums SET sort_title = ?2 WHERE id = ?1 AND sort_title IS NOT ?2",
+                    params![album_id, sort],
+                )?;
+            }
+            Some(album_id)
+        }
+        None => None,
+    };
+
+    // An unrated file keeps a rating given in the app without writing tags
+    conn.execute(
+        "INSERT INTO tracks (path, title, artist, album, album_artist, year, genre,
+                             track_number, disc_number, duration_secs, sample_rate, bit_depth,
+                             channels, format, has_album_art, file_size, modified_at,
+                             artist_id, album_id, composer, work, movement, movement_number,
+                             compilation, added_at, artist_sort, album_sort, album_artist_sort,
+                             rating, source_path, start_secs, end_secs)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18, ?19,
+                 ?20, ?21, ?22, ?23, ?24, ?25, ?26, ?27, ?28, ?29, ?30, ?31, ?32)
+         ON CONFLICT(path) DO UPDATE SET
+             title = excluded.title, artist = excluded.artist, album = excluded.album,
+             album_artist = excluded.album_artist, year = excluded.year, genre = excluded.genre,
+             track_number = excluded.track_number, disc_number = excluded.disc_number,
+             duration_secs = excluded.duration_secs, sample_rate = excluded.sample_rate,
+             bit_depth = excluded.bit_depth, channels = excluded.channels,
+             format = excluded.format, has_album_art = excluded.has_album_art,
+             file_size = excluded.file_size, modified_at = excluded.modified_at,
+             artist_id = excluded.artist_id, album_id = excluded.album_id,
+             composer = excluded.composer, work = excluded.work,
+             movement = excluded.movement, movement_number = excluded.movement_number,
+             compilation = excluded.compilation, artist_sort = excluded.artist_sort,
+             album_sort = excluded.album_sort, album_artist_sort = excluded.album_artist_sort,
+             rating = COALESCE(excluded.rating, rating), source_path = excluded.source_path,
+             start_secs = excluded.start_secs, end_secs = excluded.end_secs",
+        params![
+            meta.file_path,
+            meta.title,
+            meta.artist,
+            meta.album,
+            meta.album_artist,
+            meta.year,
+            meta.genre,
+            meta.track_number,
+            meta.disc_number,
+            meta.duration_secs,
+            meta.sample_rate,
+            meta.bit_depth,
+            meta.channels,
+            meta.format,
+            meta.has_album_art,
+            stats.size,
+            stats.modified_at,
+            artist_id,
+            album_id,
+            meta.composer,
+            meta.work,
+            meta.movement,
+            meta.movement_number,
+            meta.compilation,
+            unix_now(),
+            meta.artist_sort,
+            meta.album_sort,
+            meta.album_artist_sort,
+            meta.rating,
+            meta.source_path,
+            meta.source_path.as_ref().map(|_| meta.start_secs),
+            meta.end_secs,
+        ],
+    )?;
+    let track_id: i64 = conn.query_row(
+        "SELECT id FROM tracks WHERE path = ?1",
+        params![meta.file_path],
+        |row| row.get(0),
+    )?;
+    artists::set_featured(conn, track_id, &guests)?;
+    genres::set_genres(conn, track_id, meta.genre.as_deref())
+}
+
+/// Strip a verbatim prefix and trailing separators so roots compare
+/// consistently ("\\\\?\\D:\\Music\\" → "D:\\Music").
+fn normalize_root(path: &str) -> String {
+    let path = paths::plain_path(path);
+    let trimmed = path.trim_end_matches(['/', '\\']);
+    if trimmed.is_empty() || trimmed.ends_with(':') {
+        // Filesystem root ("/", "D:\\") — keep the separator
+        path
+    } else {
+        trimmed.to_string()
+    }
+}
+
+/// `root` with a trailing separator, for prefix matches that don't also catch
+/// sibling folders ("Music" must not match "Music2").
+pub(super) fn dir_prefix(root: &str) -> String {
+    if root.ends_with(MAIN_SEPARATOR) {
+        root.to_string()
+    } else {
+        format!("{}{}", root, MAIN_SEPARATOR)
+    }
+}
+
+/// SQL condition: `path_col` lies under the root folder in `root_col`.
+/// Same rule as [`dir_prefix`].
+fn under_root_sql(path_col: &str, root_col: &str) -> String {
+    let prefix = format!(
+        "(CASE WHEN substr({root}, -1) = '{sep}' THEN {root} ELSE {root} || '{sep}' END)",
+        root = root_col,
+        sep = MAIN_SEPARATOR
+    );
+    format!("substr({}, 1, length({p})) = {p}", path_col, p = prefix)
+}
+
+/// Drop albums, artists and genres that no track refers to anymore.
+pub(super) fn prune_orphans(conn: &Connection) -> rusqlite::Result<()> {
+    conn.execute_batch(
+        "DELETE FROM albums
+          WHERE id NOT IN (SELECT album_id FROM tracks WHERE album_id IS NOT NULL);
+         DELETE FROM artists
+          WHERE id NOT IN (SELECT artist_id FROM tracks WHERE artist_id IS NOT NULL)
+            AND id NOT IN (SELECT artist_id FROM albums WHERE artist_id IS NOT NULL)
+            AND id NOT IN (SELECT artist_id FROM featured_artists);
+         DELETE FROM genres WHERE id NOT IN (SELECT genre_id FROM track_genres);",
+    )
+}
+
+/// Look up an artist by name, case-insensitively so "The Beatles" and
+/// "the Beatles" share one page; create it if new.
+pub(super) fn ensure_artist(conn: &Connection, name: &str) -> rusqlite::Result<i64> {
+    let existing: Option<i64> = conn
+        .query_row(
+            "SELECT id FROM artists WHERE name = ?1 COLLATE NOCASE ORDER BY id LIMIT 1",
+            params![name],
+            |row| row.get(0),
+        )
+        .optional()?;
+    match existing {
+        Some(id) => Ok(id),
+        None => {
+            conn.execute("INSERT INTO artists (name) VALUES (?1)", params![name])?;
+            Ok(conn.last_insert_rowid())
+        }
+    }
+}
+
+/// Record an artist's sort name from a sort tag, minus any "feat." guests.
+/// Artists keep their last known sort name when a file has no sort tag.
+fn set_artist_sort(conn: &Connection, artist_id: i64, tag: Option<&str>) -> rusqlite::Result<()> {
+    let Some((sort, _)) = tag.map(split_featured) else {
+        return Ok(());
+    };
+    if !sort.is_empty() {
+        conn.execute(
+            "UPDATE artists SET sort_name = ?2 WHERE id = ?1 AND sort_name IS NOT ?2",
+            params![artist_id, sort],
+        )?;
+    }
+    Ok(())
+}
+
+pub(super) fn ensure_album(
+    conn: &Connection,
+    title: &str,
+    artist_id: Option<i64>,
+    year: Option<u32>,
+) -> rusqlite::Result<i64> {
+    // UNIQUE doesn't treat NULLs as equal, so look up with IS instead of relying on it
+    let existing: Option<i64> = conn
+        .query_row(
+            "SELECT id FROM albums WHERE title = ?1 AND artist_id IS ?2",
+            params![title, artist_id],
+            |row| row.get(0),
+        )
+        .optional()?;
+    match existing {
+        Some(id) => {
+            if year.is_some() {
+                conn.execute(
+                    "UPDATE albums SET year = ?2 WHERE id = ?1 AND year IS NULL",
+                    params![id, year],
+                )?;
+            }
+            Ok(id)
+        }
+        None => {
+            conn.execute(
+                "INSERT INTO albums (title, artist_id, year) VALUES (?1, ?2, ?3)",
+                params![title, artist_id, year],
+            )?;
+            Ok(conn.last_insert_rowid())
+        }
+    }
+}
+
+pub(super) fn unix_now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or(0)
+}
+
+pub(super) fn track_from_row(row: &Row) -> rusqlite::Result<LibraryTrack> {
+    Ok(LibraryTrack {
+        id: row.get(0)?,
+        path: row.get(1)?,
+        title: row.get(2)?,
+        artist: row.get(3)?,
+        album: row.get(4)?,
+        album_artist: row.get(5)?,
+        year: row.get(6)?,
+        genre: row.get(7)?,
+        track_number: row.get(8)?,
+        disc_number: row.get(9)?,
+        duration_secs: row.get(10)?,
+        sample_rate: row.get(11)?,
+        bit_depth: row.get(12)?,
+        channels: row.get(13)?,
+        format: row.get(14)?,
+        has_album_art: row.get(15)?,
+        file_size: row.get(16)?,
+        modified_at: row.get(17)?,
+        artist_id: row.get(18)?,
+        album_id: row.get(19)?,
+        rating: row.get(20)?,
+        composer: row.get(21)?,
+        work: row.get(22)?,
+        movement: row.get(23)?,
+        movement_number: row.get(24)?,
+        compilation: row.get(25)?,
+        added_at: row.get(26)?,
+        dynamic_range: row.get(27)?,
+        spectral_cutoff_hz: row.get(28)?,
+        transcode_confidence: row.get(29)?,
+        artist_sort: row.get(30)?,
+        album_sort: row.get(31)?,
+        album_artist_sort: row.get(32)?,
+        source_path: row.get(33)?,
+        start_secs: row.get(34)?,
+        end_secs: row.get(35)?,
+    })
+}
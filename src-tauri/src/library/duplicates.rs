@@ -0,0 +1,136 @@
+//! Duplicate detection: the same recording present more than once, e.g. a
+//! FLAC rip next to an old MP3 of it, or the same album copied into two
+//! folders.
+//!
+//! Tracks count as duplicates when their artist and title match (ignoring
+//! case, punctuation and feat. credits) and their durations are within a
+//! tolerance. Each group is ranked by quality so the best copy can be kept.
+
+use serde::Serialize;
+use std::collections::HashMap;
+
+use super::database::{LibraryDb, LibraryTrack, TRACK_COLUMNS};
+use super::query::Paging;
+use super::stats::{bitrate_kbps, LOSSLESS_FORMATS};
+use crate::metadata::values::split_featured;
+
+/// Duration difference below which two matching tracks are the same
+/// recording. Covers encoder padding and differently trimmed silence.
+pub const DEFAULT_DURATION_TOLERANCE_SECS: f64 = 2.0;
+
+#[derive(Clone, Serialize)]
+pub struct DuplicateCandidate {
+    pub track: LibraryTrack,
+    pub lossless: bool,
+    /// Size over duration, so tags and artwork count too.
+    pub bitrate_kbps: f64,
+    /// Highest quality in its group: lossless first, then bit depth, sample
+    /// rate and bitrate.
+    pub best: bool,
+}
+
+#[derive(Clone, Serialize)]
+pub struct DuplicateGroup {
+    pub artist: String,
+    pub title: String,
+    /// Best copy first.
+    pub candidates: Vec<DuplicateCandidate>,
+}
+
+impl LibraryDb {
+    /// Groups of two or more tracks that look like the same recording,
+    /// sorted by artist and title. Groups are found over the whole library;
+    /// `paging` only picks the window returned.
+    pub fn duplicates(
+        &self,
+        tolerance_secs: f64,
+        paging: Paging,
+    ) -> Result<Vec<DuplicateGroup>, String> {
+        let tracks = self.query_tracks(
+            &format!(
+                "SELECT {} FROM visible_tracks
+                  WHERE artist IS NOT NULL AND title IS NOT NULL",
+                TRACK_COLUMNS
+            ),
+            &[],
+        )?;
+
+        let mut by_name: HashMap<(String, String), Vec<LibraryTrack>> = HashMap::new();
+        for track in tracks {
+            let key = match (track.artist.as_deref(), track.title.as_deref()) {
+                (Some(artist), Some(title)) => (match_key(artist), match_key(title)),
+                _ => continue,
+            };
+            if !key.0.is_empty() && !key.1.is_empty() {
+                by_name.entry(key).or_default().push(track);
+            }
+        }
+
+        let mut groups = Vec::new();
+        for mut tracks in by_name.into_values().filter(|t| t.len() > 1) {
+            // Split into runs of tracks within the tolerance of their neighbour,
+            // so a live version or radio edit isn't matched with the album take
+            tracks.sort_by(|a, b| a.duration_secs.total_cmp(&b.duration_secs));
+            let mut run: Vec<LibraryTrack> = Vec::new();
+            for track in tracks {
+                if run
+                    .last()
+                    .is_some_and(|last| track.duration_secs - last.duration_secs > tolerance_secs)
+                {
+                    groups.extend(duplicate_group(std::mem::take(&mut run)));
+                }
+                run.push(track);
+            }
+            groups.extend(duplicate_group(run));
+        }
+
+        // Ties broken by path so pages stay stable
+        groups.sort_by(|a, b| {
+            (a.artist.to_lowercase(), a.title.to_lowercase())
+                .cmp(&(b.artist.to_lowercase(), b.title.to_lowercase()))
+                .then_with(|| a.candidates[0].track.path.cmp(&b.candidates[0].track.path))
+        });
+        Ok(paging.apply(groups))
+    }
+}
+
+/// Rank a run of matching tracks, best first; `None` unless it holds at least
+/// two tracks.
+fn duplicate_group(tracks: Vec<LibraryTrack>) -> Option<DuplicateGroup> {
+    if tracks.len() < 2 {
+        return None;
+    }
+    let mut candidates: Vec<DuplicateCandidate> = tracks
+        .into_iter()
+        .map(|track| DuplicateCandidate {
+            lossless: LOSSLESS_FORMATS.contains(&track.format.to_uppercase().as_str()),
+            bitrate_kbps: bitrate_kbps(track.file_size, track.duration_secs),
+            best: false,
+            track,
+        })
+        .collect();
+    candidates.sort_by(|a, b| {
+        (b.lossless, b.track.bit_depth, b.track.sample_rate)
+            .cmp(&(a.lossless, a.track.bit_depth, a.track.sample_rate))
+            .then(b.bitrate_kbps.total_cmp(&a.bitrate_kbps))
+            .then_with(|| a.track.path.cmp(&b.track.path))
+    });
+    candidates[0].best = true;
+
+    let first = &candidates[0].track;
+    Some(DuplicateGroup {
+        artist: first.artist.clone().unwrap_or_default(),
+        title: first.title.clone().unwrap_or_default(),
+        candidates,
+    })
+}
+
+/// Lowercased letters and digits of the text without its feat. credit:
+/// "Song (feat. Guest)" and "song" give the same key.
+fn match_key(text: &str) -> String {
+    let (main, _) = split_featured(text);
+    main.chars()
+        .filter(|c| c.is_alphanumeric())
+        .flat_map(char::to_lowercase)
+        .collect()
+}
@@ -0,0 +1,104 @@
+//! Batch dynamic range (DR) analysis.
+//!
+//! Measures files, whole albums or the whole library and stores the track
+//! values in the library; album DR is the average of its tracks' values.
+//! Optionally writes `DYNAMIC RANGE` / `ALBUM DYNAMIC RANGE` tags as well.
+//! Values are kept across rescans, since tags never feed the stored value.
+
+use parking_lot::Mutex;
+use rusqlite::params;
+use std::time::Instant;
+
+use super::analysis::AnalysisGroup;
+use super::database::LibraryDb;
+use super::scanner::{JobProgress, JobSummary, ScanControl, PROGRESS_INTERVAL};
+use crate::audio::dynamic_range;
+use crate::metadata::dynamic_range::write_dynamic_range;
+
+impl LibraryDb {
+    /// Store a file's DR, on every cue-sheet track when it is their image.
+    /// Files outside the library are ignored.
+    pub fn set_dynamic_range(&mut self, path: &str, dr: f64) -> Result<(), String> {
+        self.conn
+            .execute(
+                "UPDATE tracks SET dynamic_range = ?2 WHERE path = ?1 OR source_path = ?1",
+                params![path, dr],
+            )
+            .map_err(|e| format!("Failed to store dynamic range: {}", e))?;
+        Ok(())
+    }
+}
+
+/// Analyse each group, store the values and optionally write tags. Runs on
+/// the caller's thread; `on_progress` is called at most every
+/// [`PROGRESS_INTERVAL`]. An album with a file that fails to decode gets no
+/// album tag.
+pub fn analyze_groups(
+    db: &Mutex<LibraryDb>,
+    groups: &[AnalysisGroup],
+    write_tags: bool,
+    control: &ScanControl,
+    on_progress: &mut dyn FnMut(&JobProgress),
+) -> Result<JobSummary, String> {
+    let total = groups.iter().map(|g| g.paths.len()).sum();
+    let mut summary = JobSummary::default();
+    let mut processed = 0;
+    let mut last_report = Instant::now();
+
+    for group in groups {
+        let mut measured: Vec<(&str, f64)> = Vec::new();
+        let mut complete = true;
+        for path in &group.paths {
+            if control.is_cancelled() {
+                summary.cancelled = true;
+                return Ok(summary);
+            }
+            if last_report.elapsed() >= PROGRESS_INTERVAL {
+                last_report = Instant::now();
+                on_progress(&JobProgress {
+                    total,
+                    processed,
+                    current_file: path.clone(),
+                });
+            }
+            processed += 1;
+            match dynamic_range::analyze_file(path) {
+                Ok(dr) => {
+                    db.lock().set_dynamic_range(path, dr)?;
+                    measured.push((path, dr));
+                }
+                Err(e) => {
+                    complete = false;
+                    summary.errors.push(format!("{}: {}", path, e));
+                }
+            }
+        }
+
+        if !write_tags {
+            summary.processed += measured.len();
+            continue;
+        }
+        let album_dr = (group.album && complete && !measured.is_empty())
+            .then(|| measured.iter().map(|(_, dr)| dr).sum::<f64>() / measured.len() as f64);
+        for (path, dr) in &measured {
+            match write_dynamic_range(path, *dr, album_dr) {
+                Ok(()) => summary.processed += 1,
+                Err(e) => summary.errors.push(format!("{}: {}", path, e)),
+            }
+        }
+    }
+    Ok(summary)
+}
+
+/// Analyse a selection on the caller's thread (see [`analyze_groups`]).
+pub fn run(
+    db: &Mutex<LibraryDb>,
+    paths: &[String],
+    album_ids: &[i64],
+    write_tags: bool,
+    control: &ScanControl,
+    on_progress: &mut dyn FnMut(&JobProgress),
+) -> Result<JobSummary, String> {
+    let groups = db.lock().analysis_groups(paths, album_ids)?;
+    analyze_groups(db, &groups, write_tags, control, on_progress)
+}
@@ -0,0 +1,63 @@
+//! Library-wide legacy codepage detection and repair.
+//!
+//! Detection runs on the tag text stored in the library, so finding
+//! candidates doesn't touch the files; see [`crate::metadata::encoding`]
+//! for the heuristics and the repair itself.
+
+use parking_lot::Mutex;
+use rusqlite::ToSql;
+
+use super::database::{LibraryDb, LibraryTrack, TRACK_COLUMNS};
+use super::scanner::{self, JobSummary};
+use crate::metadata::encoding::{self, EncodingChoice};
+
+/// Formats whose files carry ID3 tags.
+const ID3_FORMATS: &[&str] = &["MP3", "AIF", "AIFF", "WAV"];
+
+impl LibraryDb {
+    /// Visible ID3-tagged tracks whose title, artist or album reads as
+    /// mojibake, by path.
+    pub fn legacy_encoded_tracks(&self) -> Result<Vec<LibraryTrack>, String> {
+        let params: Vec<&dyn ToSql> = ID3_FORMATS.iter().map(|f| f as &dyn ToSql).collect();
+        // GLOB '*[^ -~]*' matches text with a character outside printable
+        // ASCII, which cheaply leaves out most of the library
+        let tracks = self.query_tracks(
+            &format!(
+                "SELECT {} FROM visible_tracks
+                  WHERE upper(format) IN ({})
+                    AND (title GLOB '*[^ -~]*' OR artist GLOB '*[^ -~]*'
+                         OR album GLOB '*[^ -~]*' OR album_artist GLOB '*[^ -~]*')
+                  ORDER BY path",
+                TRACK_COLUMNS,
+                vec!["?"; ID3_FORMATS.len()].join(", ")
+            ),
+            &params,
+        )?;
+        Ok(tracks
+            .into_iter()
+            .filter(|t| {
+                [&t.title, &t.artist, &t.album, &t.album_artist]
+                    .into_iter()
+                    .flatten()
+                    .any(|text| encoding::detect(text).is_some())
+            })
+            .collect())
+    }
+}
+
+/// Repair each file with its chosen codepage, or the detected one. Library
+/// tracks are updated to match; `processed` counts the files changed.
+pub fn repair(db: &Mutex<LibraryDb>, choices: &[EncodingChoice]) -> JobSummary {
+    let mut summary = JobSummary::default();
+    for choice in choices {
+        match encoding::repair(&choice.path, choice.encoding.as_deref()) {
+            Ok(repair) if repair.fields.is_empty() => {}
+            Ok(_) => match scanner::refresh_file(db, &choice.path) {
+                Ok(()) => summary.processed += 1,
+                Err(e) => summary.errors.push(format!("{}: {}", choice.path, e)),
+            },
+            Err(e) => summary.errors.push(format!("{}: {}", choice.path, e)),
+        }
+    }
+    summary
+}
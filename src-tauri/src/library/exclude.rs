@@ -0,0 +1,172 @@
+//! Scanner exclude rules.
+//!
+//! Keeps sample packs, ringtones, recycle-bin folders and the like out of the
+//! library. Rules are stored as JSON in the app data directory and compiled
+//! into an [`ExcludeMatcher`] at the start of each scan.
+
+use glob::{MatchOptions, Pattern};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+const EXCLUDE_FILE: &str = "scan_exclusions.json";
+
+const MATCH_OPTIONS: MatchOptions = MatchOptions {
+    case_sensitive: false,
+    require_literal_separator: false,
+    require_literal_leading_dot: false,
+};
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ExcludeRules {
+    /// Glob patterns. A pattern containing a path separator is matched against
+    /// the full path (e.g. `**/Samples/**`); otherwise against the file or
+    /// folder name (e.g. `*ringtone*`).
+    pub patterns: Vec<String>,
+    /// Skip dot-folders/files, `$RECYCLE.BIN`-style system folders and, on
+    /// Windows, anything with the hidden attribute.
+    pub skip_hidden: bool,
+    /// File extensions to ignore, without the dot (e.g. "wav").
+    pub extensions: Vec<String>,
+    /// Tracks shorter than this are left out. 0 disables the check.
+    pub min_duration_secs: f64,
+}
+
+impl Default for ExcludeRules {
+    fn default() -> Self {
+        Self {
+            patterns: Vec::new(),
+            skip_hidden: true,
+            extensions: Vec::new(),
+            min_duration_secs: 0.0,
+        }
+    }
+}
+
+impl ExcludeRules {
+    /// Load rules from disk. Returns defaults if the file doesn't exist.
+    pub fn load(app_data_dir: &Path) -> Self {
+        let path = app_data_dir.join(EXCLUDE_FILE);
+        if let Ok(data) = std::fs::read_to_string(&path) {
+            serde_json::from_str(&data).unwrap_or_default()
+        } else {
+            Self::default()
+        }
+    }
+
+    /// Save rules to disk.
+    pub fn save(&self, app_data_dir: &Path) -> Result<(), String> {
+        let path = app_data_dir.join(EXCLUDE_FILE);
+        std::fs::create_dir_all(app_data_dir)
+            .map_err(|e| format!("Failed to create dir: {}", e))?;
+        let json =
+            serde_json::to_string_pretty(self).map_err(|e| format!("Serialize failed: {}", e))?;
+        std::fs::write(&path, json).map_err(|e| format!("Write failed: {}", e))?;
+        Ok(())
+    }
+
+    /// Compile the rules. Fails on an invalid glob pattern.
+    pub fn compile(&self) -> Result<ExcludeMatcher, String> {
+        let mut path_patterns = Vec::new();
+        let mut name_patterns = Vec::new();
+        for p in self
+            .patterns
+            .iter()
+            .map(|p| p.trim())
+            .filter(|p| !p.is_empty())
+        {
+            let pattern =
+                Pattern::new(p).map_err(|e| format!("Invalid pattern \"{}\": {}", p, e))?;
+            if p.contains(['/', '\\']) {
+                path_patterns.push(pattern);
+            } else {
+                name_patterns.push(pattern);
+            }
+        }
+        Ok(ExcludeMatcher {
+            path_patterns,
+            name_patterns,
+            skip_hidden: self.skip_hidden,
+            extensions: self
+                .extensions
+                .iter()
+                .map(|e| e.trim().trim_start_matches('.').to_lowercase())
+                .filter(|e| !e.is_empty())
+                .collect(),
+            min_duration_secs: self.min_duration_secs.max(0.0),
+        })
+    }
+}
+
+/// Compiled [`ExcludeRules`]. The default matcher excludes nothing.
+#[derive(Default)]
+pub struct ExcludeMatcher {
+    path_patterns: Vec<Pattern>,
+    name_patterns: Vec<Pattern>,
+    skip_hidden: bool,
+    extensions: Vec<String>,
+    min_duration_secs: f64,
+}
+
+impl ExcludeMatcher {
+    /// Whether a folder (and everything below it) should be skipped.
+    pub fn excludes_dir(&self, path: &Path) -> bool {
+        (self.skip_hidden && is_hidden(path)) || self.matches_pattern(path)
+    }
+
+    /// Whether a file should be skipped, before its tags are read.
+    pub fn excludes_file(&self, path: &Path) -> bool {
+        if self.skip_hidden && is_hidden(path) {
+            return true;
+        }
+        let ext = path
+            .extension()
+            .map(|e| e.to_string_lossy().to_lowercase())
+            .unwrap_or_default();
+        self.extensions.contains(&ext) || self.matches_pattern(path)
+    }
+
+    pub fn min_duration_secs(&self) -> f64 {
+        self.min_duration_secs
+    }
+
+    pub fn too_short(&self, duration_secs: f64) -> bool {
+        duration_secs < self.min_duration_secs
+    }
+
+    fn matches_pattern(&self, path: &Path) -> bool {
+        let name = path
+            .file_name()
+            .map(|n| n.to_string_lossy())
+            .unwrap_or_default();
+        self.name_patterns
+            .iter()
+            .any(|p| p.matches_with(&name, MATCH_OPTIONS))
+            || self
+                .path_patterns
+                .iter()
+                .any(|p| p.matches_path_with(path, MATCH_OPTIONS))
+    }
+}
+
+fn is_hidden(path: &Path) -> bool {
+    let name = path
+        .file_name()
+        .map(|n| n.to_string_lossy())
+        .unwrap_or_default();
+    // ".Trash-1000", ".AppleDouble", "$RECYCLE.BIN", "System Volume Information"
+    if name.starts_with('.') || name.starts_with('$') || name == "System Volume Information" {
+        return true;
+    }
+
+    #[cfg(windows)]
+    {
+        use std::os::windows::fs::MetadataExt;
+        const FILE_ATTRIBUTE_HIDDEN: u32 = 0x2;
+        if let Ok(meta) = std::fs::metadata(path) {
+            return meta.file_attributes() & FILE_ATTRIBUTE_HIDDEN != 0;
+        }
+    }
+
+    false
+}
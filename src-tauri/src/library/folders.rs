@@ -0,0 +1,127 @@
+//! Folder view: the subfolders and audio files of one folder, with metadata
+//! from the library index where the files are indexed. Tags are never read
+//! here, so listing a large folder stays fast; files outside the library are
+//! listed without metadata.
+
+use rusqlite::params;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::path::{Path, MAIN_SEPARATOR};
+
+use super::database::{dir_prefix, LibraryDb, LibraryTrack, TRACK_COLUMNS};
+use super::scanner::is_audio_file;
+
+#[derive(Clone, Serialize)]
+pub struct FolderListing {
+    pub path: String,
+    /// `None` at a filesystem root.
+    pub parent: Option<String>,
+    pub folders: Vec<FolderEntry>,
+    pub files: Vec<FolderFile>,
+}
+
+#[derive(Clone, Serialize)]
+pub struct FolderEntry {
+    pub name: String,
+    pub path: String,
+    /// Indexed tracks anywhere below the folder.
+    pub track_count: u32,
+}
+
+#[derive(Clone, Serialize)]
+pub struct FolderFile {
+    pub name: String,
+    pub path: String,
+    pub file_size: i64,
+    /// `None` if the file isn't in the library.
+    pub track: Option<LibraryTrack>,
+}
+
+impl LibraryDb {
+    /// List a folder. Hidden entries (dot files) are left out; folders and
+    /// files are sorted by name.
+    pub fn browse_folder(&self, path: &str) -> Result<FolderListing, String> {
+        let dir = Path::new(path);
+        let entries =
+            std::fs::read_dir(dir).map_err(|e| format!("Failed to read folder: {}", e))?;
+
+        let mut folders = Vec::new();
+        let mut files = Vec::new();
+        for entry in entries.flatten() {
+            let name = entry.file_name().to_string_lossy().to_string();
+            if name.starts_with('.') {
+                continue;
+            }
+            let entry_path = entry.path();
+            let path_str = entry_path.to_string_lossy().to_string();
+            if entry_path.is_dir() {
+                folders.push(FolderEntry {
+                    name,
+                    path: path_str,
+                    track_count: 0,
+                });
+            } else if is_audio_file(&entry_path) {
+                files.push(FolderFile {
+                    name,
+                    file_size: entry.metadata().map(|m| m.len() as i64).unwrap_or(0),
+                    path: path_str,
+                    track: None,
+                });
+            }
+        }
+
+        let mut tracks = self.tracks_in_folder(path)?;
+        for file in &mut files {
+            file.track = tracks.remove(&file.path);
+        }
+        let counts = self.subfolder_track_counts(path)?;
+        for folder in &mut folders {
+            folder.track_count = counts.get(&folder.name).copied().unwrap_or(0);
+        }
+
+        folders.sort_by_key(|f| f.name.to_lowercase());
+        files.sort_by_key(|f| f.name.to_lowercase());
+        Ok(FolderListing {
+            path: path.to_string(),
+            parent: dir.parent().map(|p| p.to_string_lossy().to_string()),
+            folders,
+            files,
+        })
+    }
+
+    /// Indexed tracks directly in `dir`, not in subfolders, by path.
+    fn tracks_in_folder(&self, dir: &str) -> Result<HashMap<String, LibraryTrack>, String> {
+        let tracks = self.query_tracks(
+            &format!(
+                "SELECT {} FROM visible_tracks
+                  WHERE substr(path, 1, length(?1)) = ?1
+                    AND instr(substr(path, length(?1) + 1), ?2) = 0",
+                TRACK_COLUMNS
+            ),
+            params![dir_prefix(dir), MAIN_SEPARATOR.to_string()],
+        )?;
+        Ok(tracks.into_iter().map(|t| (t.path.clone(), t)).collect())
+    }
+
+    /// Number of indexed tracks below each subfolder of `dir`, by folder name.
+    fn subfolder_track_counts(&self, dir: &str) -> Result<HashMap<String, u32>, String> {
+        let mut stmt = self
+            .conn
+            .prepare(
+                "SELECT substr(rest, 1, instr(rest, ?2) - 1), COUNT(*)
+                   FROM (SELECT substr(path, length(?1) + 1) AS rest FROM visible_tracks
+                          WHERE substr(path, 1, length(?1)) = ?1)
+                  WHERE instr(rest, ?2) > 0
+                  GROUP BY 1",
+            )
+            .map_err(|e| format!("Failed to query library: {}", e))?;
+        let rows = stmt
+            .query_map(
+                params![dir_prefix(dir), MAIN_SEPARATOR.to_string()],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .map_err(|e| format!("Failed to query library: {}", e))?;
+        rows.collect::<Result<_, _>>()
+            .map_err(|e| format!("Failed to query library: {}", e))
+    }
+}
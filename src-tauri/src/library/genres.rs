@@ -0,0 +1,150 @@
+//! Genre browsing.
+//!
+//! Genre tags often hold several values ("Rock; Blues", or separate
+//! NUL-separated ID3v2.4 / repeated Vorbis fields), so each value is indexed
+//! separately in `track_genres`. The raw tag stays in `tracks.genre` for
+//! display and sorting.
+
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::Serialize;
+
+use super::albums::LibraryAlbum;
+use super::database::{LibraryDb, LibraryTrack, TRACK_ALBUM_ORDER, TRACK_COLUMNS};
+use super::query::Paging;
+use crate::metadata::values::split_values;
+
+#[derive(Clone, Serialize)]
+pub struct LibraryGenre {
+    pub id: i64,
+    pub name: String,
+    pub album_count: u32,
+    pub track_count: u32,
+}
+
+#[derive(Clone, Serialize)]
+pub struct GenreDetail {
+    pub genre: LibraryGenre,
+    /// Albums with at least one track in the genre.
+    pub albums: Vec<LibraryAlbum>,
+}
+
+impl LibraryDb {
+    pub fn genres(&self, paging: Paging) -> Result<Vec<LibraryGenre>, String> {
+        self.genre_rows(None, paging)
+    }
+
+    pub fn genre(&self, id: i64) -> Result<GenreDetail, String> {
+        let genre = self
+            .genre_rows(Some(id), Paging::ALL)?
+            .into_iter()
+            .next()
+            .ok_or_else(|| format!("Genre {} not found", id))?;
+        let albums = self
+            .album_rows(
+                "al.id IN (SELECT t.album_id FROM visible_tracks t
+                             JOIN track_genres tg ON tg.track_id = t.id
+                            WHERE tg.genre_id = ?1)",
+                params![id],
+            )?
+            .into_iter()
+            .map(|(album, _)| album)
+            .collect();
+        Ok(GenreDetail { genre, albums })
+    }
+
+    /// Tracks in a genre, in album order.
+    pub fn genre_tracks(&self, id: i64, paging: Paging) -> Result<Vec<LibraryTrack>, String> {
+        self.query_tracks(
+            &format!(
+                "SELECT {} FROM visible_tracks
+                  WHERE id IN (SELECT track_id FROM track_genres WHERE genre_id = ?1)
+                  ORDER BY {}{}",
+                TRACK_COLUMNS,
+                TRACK_ALBUM_ORDER,
+                paging.sql()
+            ),
+            params![id],
+        )
+    }
+
+    fn genre_rows(&self, id: Option<i64>, paging: Paging) -> Result<Vec<LibraryGenre>, String> {
+        let mut stmt = self
+            .conn
+            .prepare(&format!(
+                "SELECT g.id, g.name, COUNT(DISTINCT t.album_id), COUNT(t.id)
+                   FROM genres g
+                   JOIN track_genres tg ON tg.genre_id = g.id
+                   JOIN visible_tracks t ON t.id = tg.track_id
+                  WHERE ?1 IS NULL OR g.id = ?1
+                  GROUP BY g.id
+                  ORDER BY g.name COLLATE NOCASE{}",
+                paging.sql()
+            ))
+            .map_err(|e| format!("Failed to query genres: {}", e))?;
+        let rows = stmt
+            .query_map(params![id], |row| {
+                Ok(LibraryGenre {
+                    id: row.get(0)?,
+                    name: row.get(1)?,
+                    album_count: row.get(2)?,
+                    track_count: row.get(3)?,
+                })
+            })
+            .map_err(|e| format!("Failed to query genres: {}", e))?;
+        rows.collect::<Result<_, _>>()
+            .map_err(|e| format!("Failed to query genres: {}", e))
+    }
+}
+
+/// Replace the genres of a track.
+pub(super) fn set_genres(
+    conn: &Connection,
+    track_id: i64,
+    genre: Option<&str>,
+) -> rusqlite::Result<()> {
+    conn.execute(
+        "DELETE FROM track_genres WHERE track_id = ?1",
+        params![track_id],
+    )?;
+    for name in split_values(genre) {
+        let genre_id = ensure_genre(conn, &name)?;
+        conn.execute(
+            "INSERT OR IGNORE INTO track_genres (track_id, genre_id) VALUES (?1, ?2)",
+            params![track_id, genre_id],
+        )?;
+    }
+    Ok(())
+}
+
+/// Split the stored genre tags of every track. Run once when upgrading a
+/// library indexed before genres had their own table, inside the
+/// migration's transaction.
+pub(super) fn reindex_genres(conn: &Connection) -> rusqlite::Result<()> {
+    let tracks: Vec<(i64, String)> = {
+        let mut stmt = conn.prepare("SELECT id, genre FROM tracks WHERE genre IS NOT NULL")?;
+        let rows = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?;
+        rows.collect::<Result<_, _>>()?
+    };
+    for (id, genre) in tracks {
+        set_genres(conn, id, Some(&genre))?;
+    }
+    Ok(())
+}
+
+/// Case-insensitive, so "Hip-Hop" and "hip-hop" are one genre.
+fn ensure_genre(conn: &Connection, name: &str) -> rusqlite::Result<i64> {
+    let existing: Option<i64> = conn
+        .query_row(
+            "SELECT id FROM genres WHERE name = ?1 COLLATE NOCASE",
+            params![name],
+            |row| row.get(0),
+        )
+        .optional()?;
+    match existing {
+        Some(id) => Ok(id),
+        None => {
+            conn.execute("INSERT INTO genres (name) VALUES (?1)", params![name])?;
+            Ok(conn.last_insert_rowid())
+        }
+    }
+}
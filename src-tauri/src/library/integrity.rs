@@ -0,0 +1,184 @@
+//! Library integrity scan.
+//!
+//! Every file is decoded in full (see [`crate::audio::integrity`]) and the
+//! outcome is stored in `integrity_checks` with the file's mtime. Bit-rot
+//! doesn't touch the mtime, so a full scan checks every file again; the
+//! default scan only covers files never checked or changed since. A cue
+//! sheet's image is decoded once, and the outcome stored on each of its
+//! tracks.
+
+use parking_lot::Mutex;
+use rusqlite::params;
+use serde::Serialize;
+use std::time::Instant;
+
+use super::database::{
+    track_from_row, unix_now, LibraryDb, LibraryTrack, TRACK_COLUMNS, TRACK_COLUMN_COUNT,
+};
+use super::query::Paging;
+use super::scanner::{JobProgress, JobSummary, ScanControl, PROGRESS_INTERVAL};
+use crate::audio::integrity::{self, IntegrityReport};
+use crate::metadata::cue;
+
+#[derive(Clone, Serialize)]
+pub struct IntegrityProblem {
+    pub track: LibraryTrack,
+    pub error: String,
+    /// Unix timestamp (seconds) of the check.
+    pub checked_at: i64,
+    /// The file has changed since it was checked.
+    pub stale: bool,
+}
+
+#[derive(Clone, Default, Serialize)]
+pub struct IntegrityStatus {
+    /// Visible tracks with a current check.
+    pub checked: u32,
+    /// Visible tracks never checked or changed since.
+    pub unchecked: u32,
+    /// Checks whose embedded checksum matched.
+    pub checksum_verified: u32,
+    pub problems: u32,
+    /// Unix timestamp (seconds) of the oldest current check.
+    pub oldest_check: Option<i64>,
+}
+
+impl LibraryDb {
+    /// Store the outcome of checking a file, on every cue-sheet track when
+    /// it is their image. Files outside the library are ignored.
+    pub fn store_integrity_check(
+        &mut self,
+        path: &str,
+        report: &Result<IntegrityReport, String>,
+    ) -> Result<(), String> {
+        let (error, verified) = match report {
+            Ok(r) => (r.error.clone(), r.checksum_verified),
+            Err(e) => (Some(e.clone()), false),
+        };
+        self.conn
+            .execute(
+                "INSERT OR REPLACE INTO integrity_checks
+                        (track_id, modified_at, checked_at, error, checksum_verified)
+                 SELECT id, modified_at, ?2, ?3, ?4 FROM tracks
+                  WHERE path = ?1 OR source_path = ?1",
+                params![path, unix_now(), error, verified],
+            )
+            .map_err(|e| format!("Failed to store integrity check: {}", e))?;
+        Ok(())
+    }
+
+    /// Files to check: those of every visible track with `full`, otherwise
+    /// of tracks never checked or changed since. Cue-sheet tracks give their
+    /// image, once.
+    pub fn integrity_pending(&self, full: bool) -> Result<Vec<String>, String> {
+        let mut stmt = self
+            .conn
+            .prepare(
+                "SELECT DISTINCT COALESCE(t.source_path, t.path) AS file FROM visible_tracks t
+                   LEFT JOIN integrity_checks c
+                          ON c.track_id = t.id AND c.modified_at = t.modified_at
+                  WHERE ?1 OR c.track_id IS NULL
+                  ORDER BY file",
+            )
+            .map_err(|e| format!("Failed to query library: {}", e))?;
+        let rows = stmt
+            .query_map(params![full], |row| row.get(0))
+            .map_err(|e| format!("Failed to query library: {}", e))?;
+        rows.collect::<Result<_, _>>()
+            .map_err(|e| format!("Failed to query library: {}", e))
+    }
+
+    /// Tracks whose last check found a problem, by path.
+    pub fn integrity_problems(&self, paging: Paging) -> Result<Vec<IntegrityProblem>, String> {
+        let mut stmt = self
+            .conn
+            .prepare(&format!(
+                "SELECT {}, c.error, c.checked_at, c.checked_mtime != visible_tracks.modified_at
+                   FROM visible_tracks
+                   JOIN (SELECT track_id, error, checked_at, modified_at AS checked_mtime
+                           FROM integrity_checks WHERE error IS NOT NULL) c
+                     ON c.track_id = visible_tracks.id
+                  ORDER BY visible_tracks.path{}",
+                TRACK_COLUMNS,
+                paging.sql()
+            ))
+            .map_err(|e| format!("Failed to query library: {}", e))?;
+        let rows = stmt
+            .query_map(params![], |row| {
+                Ok(IntegrityProblem {
+                    track: track_from_row(row)?,
+                    error: row.get(TRACK_COLUMN_COUNT)?,
+                    checked_at: row.get(TRACK_COLUMN_COUNT + 1)?,
+                    stale: row.get(TRACK_COLUMN_COUNT + 2)?,
+                })
+            })
+            .map_err(|e| format!("Failed to query library: {}", e))?;
+        rows.collect::<Result<_, _>>()
+            .map_err(|e| format!("Failed to query library: {}", e))
+    }
+
+    /// Counts for the integrity panel.
+    pub fn integrity_status(&self) -> Result<IntegrityStatus, String> {
+        self.conn
+            .query_row(
+                "SELECT COUNT(c.track_id),
+                        COUNT(*) - COUNT(c.track_id),
+                        COUNT(*) FILTER (WHERE c.checksum_verified),
+                        COUNT(c.error),
+                        MIN(c.checked_at)
+                   FROM visible_tracks t
+                   LEFT JOIN integrity_checks c
+                          ON c.track_id = t.id AND c.modified_at = t.modified_at",
+                params![],
+                |row| {
+                    Ok(IntegrityStatus {
+                        checked: row.get(0)?,
+                        unchecked: row.get(1)?,
+                        checksum_verified: row.get(2)?,
+                        problems: row.get(3)?,
+                        oldest_check: row.get(4)?,
+                    })
+                },
+            )
+            .map_err(|e| format!("Failed to query library: {}", e))
+    }
+}
+
+/// Check the given files, or the library (see
+/// [`LibraryDb::integrity_pending`]) when `paths` is empty. Damaged files are
+/// recorded in the library, not reported as job errors. Runs on the caller's
+/// thread; `on_progress` is called at most every [`PROGRESS_INTERVAL`].
+pub fn run(
+    db: &Mutex<LibraryDb>,
+    paths: &[String],
+    full: bool,
+    control: &ScanControl,
+    on_progress: &mut dyn FnMut(&JobProgress),
+) -> Result<JobSummary, String> {
+    let pending = if paths.is_empty() {
+        db.lock().integrity_pending(full)?
+    } else {
+        cue::audio_files(paths)
+    };
+    let mut summary = JobSummary::default();
+    let mut last_report = Instant::now();
+
+    for (i, path) in pending.iter().enumerate() {
+        if control.is_cancelled() {
+            summary.cancelled = true;
+            break;
+        }
+        if last_report.elapsed() >= PROGRESS_INTERVAL {
+            last_report = Instant::now();
+            on_progress(&JobProgress {
+                total: pending.len(),
+                processed: i,
+                current_file: path.clone(),
+            });
+        }
+        let report = integrity::check_file(path);
+        db.lock().store_integrity_check(path, &report)?;
+        summary.processed += 1;
+    }
+    Ok(summary)
+}
@@ -0,0 +1,190 @@
+//! Import from an iTunes / Apple Music `Library.xml` (File › Library › Export
+//! Library…). MusicBee can write the same format ("iTunes Music Library.xml"),
+//! so this covers it too.
+//!
+//! The export is converted into a [`LibraryBackup`] and restored like one:
+//! ratings, add dates, play counts and playlists are matched to library
+//! tracks by path, with optional path remapping for a different machine.
+
+use plist::{Dictionary, Value};
+use std::collections::HashMap;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use super::backup::{LibraryBackup, TrackBackup, BACKUP_VERSION};
+use super::bookmarks::BookmarkRules;
+use super::database::unix_now;
+use crate::playlist::manager::Playlist;
+
+/// iTunes stores ratings as 0–100, 20 per star.
+const POINTS_PER_STAR: u64 = 20;
+
+/// Read an iTunes library export as a backup with its tracks and playlists.
+pub fn read_itunes_library(path: &str) -> Result<LibraryBackup, String> {
+    let root = Value::from_file(path).map_err(|e| format!("Failed to read library: {}", e))?;
+    let root = root.as_dictionary().ok_or("Not an iTunes library file")?;
+
+    let mut paths: HashMap<u64, String> = HashMap::new();
+    let mut tracks = Vec::new();
+    for track in root
+        .get("Tracks")
+        .and_then(Value::as_dictionary)
+        .ok_or("Not an iTunes library file")?
+        .values()
+        .filter_map(Value::as_dictionary)
+    {
+        let (Some(id), Some(path)) = (
+            track.get("Track ID").and_then(Value::as_unsigned_integer),
+            track
+                .get("Location")
+                .and_then(Value::as_string)
+                .and_then(location_to_path),
+        ) else {
+            // Streams and cloud-only tracks have no local file
+            continue;
+        };
+        paths.insert(id, path.clone());
+        tracks.push(track_backup(path, track));
+    }
+
+    let playlists = root
+        .get("Playlists")
+        .and_then(Value::as_array)
+        .map(|p| p.iter().filter_map(Value::as_dictionary))
+        .into_iter()
+        .flatten()
+        .filter_map(|p| playlist(p, &paths))
+        .collect();
+
+    // The iTunes Media folder, when it's on a local disk
+    let roots = root
+        .get("Music Folder")
+        .and_then(Value::as_string)
+        .and_then(location_to_path)
+        .into_iter()
+        .collect();
+
+    Ok(LibraryBackup {
+        version: BACKUP_VERSION,
+        created_at: unix_now(),
+        roots,
+        tracks,
+        playlists,
+        bookmarks: Vec::new(),
+        bookmark_rules: BookmarkRules::default(),
+    })
+}
+
+fn track_backup(path: String, track: &Dictionary) -> TrackBackup {
+    // Album ratings shown on unrated tracks aren't the track's own
+    let computed = track
+        .get("Rating Computed")
+        .and_then(Value::as_boolean)
+        .unwrap_or(false);
+    let rating = track
+        .get("Rating")
+        .and_then(Value::as_unsigned_integer)
+        .filter(|_| !computed)
+        .map(|r| ((r + POINTS_PER_STAR / 2) / POINTS_PER_STAR).min(5) as u8)
+        .filter(|&stars| stars > 0);
+    let added_at = date(track, "Date Added");
+
+    // Only the count and the latest play are exported, so the history is
+    // rebuilt as that many plays a second apart ending at the latest one.
+    // Importing the same file twice gives the same timestamps, which restore
+    // doesn't duplicate.
+    let play_count = track
+        .get("Play Count")
+        .and_then(Value::as_unsigned_integer)
+        .unwrap_or(0);
+    let plays = match date(track, "Play Date UTC").or(added_at) {
+        Some(last) => (0..play_count as i64).map(|i| last - i).collect(),
+        None => Vec::new(),
+    };
+
+    TrackBackup {
+        path,
+        rating,
+        added_at,
+        plays,
+    }
+}
+
+/// A user playlist, with its tracks resolved to paths. Built-in lists
+/// (Library, Music, Podcasts…) and folders are skipped. Smart playlists are
+/// imported as a snapshot of their current contents.
+fn playlist(playlist: &Dictionary, paths: &HashMap<u64, String>) -> Option<Playlist> {
+    let flag = |key: &str| {
+        playlist
+            .get(key)
+            .and_then(Value::as_boolean)
+            .unwrap_or(false)
+    };
+    if flag("Master") || flag("Folder") || playlist.contains_key("Distinguished Kind") {
+        return None;
+    }
+    let name = playlist.get("Name").and_then(Value::as_string)?;
+    let tracks = playlist
+        .get("Playlist Items")
+        .and_then(Value::as_array)
+        .map(|items| {
+            items
+                .iter()
+                .filter_map(Value::as_dictionary)
+                .filter_map(|item| item.get("Track ID").and_then(Value::as_unsigned_integer))
+                .filter_map(|id| paths.get(&id).cloned())
+                .collect()
+        })
+        .unwrap_or_default();
+    Some(Playlist {
+        id: 0,
+        name: name.to_string(),
+        tracks,
+        sort_key: None,
+        sort_descending: false,
+    })
+}
+
+/// Unix timestamp (seconds) of a date field.
+fn date(dict: &Dictionary, key: &str) -> Option<i64> {
+    let time: SystemTime = dict.get(key).and_then(Value::as_date)?.into();
+    time.duration_since(UNIX_EPOCH)
+        .ok()
+        .map(|d| d.as_secs() as i64)
+}
+
+/// Local path of a `file://` location URL:
+/// "file:///Users/me/Music/a%20b.m4a" → "/Users/me/Music/a b.m4a",
+/// "file://localhost/C:/Music/a.flac" → "C:\Music\a.flac".
+fn location_to_path(location: &str) -> Option<String> {
+    let rest = location.strip_prefix("file://")?;
+    let rest = rest.strip_prefix("localhost").unwrap_or(rest);
+    let path = percent_decode(rest)?;
+    let path = path.trim_end_matches('/');
+
+    // "/C:/Music" is a Windows drive path
+    let bytes = path.as_bytes();
+    if bytes.len() >= 3 && bytes[0] == b'/' && bytes[1].is_ascii_alphabetic() && bytes[2] == b':' {
+        return Some(path[1..].replace('/', "\\"));
+    }
+    if path.starts_with("//") {
+        // Network share: file:////server/share
+        return Some(path.replace('/', "\\"));
+    }
+    Some(path.to_string())
+}
+
+/// Decode `%XX` escapes as UTF-8. `None` for malformed input.
+fn percent_decode(text: &str) -> Option<String> {
+    let mut bytes = Vec::with_capacity(text.len());
+    let mut iter = text.bytes();
+    while let Some(b) = iter.next() {
+        if b == b'%' {
+            let hex = [iter.next()?, iter.next()?];
+            let hex = std::str::from_utf8(&hex).ok()?;
+            bytes.push(u8::from_str_radix(hex, 16).ok()?);
+        } else {
+            bytes.push(b);
+        }
+    }
+    String::from_utf8(bytes).ok()
+}
@@ -0,0 +1,133 @@
+//! Library lists addressed by name, so any of them can be paged through the
+//! same way. Used by the `stream_library_list` command, which sends a list
+//! in chunks as events instead of one large IPC response.
+
+use serde::{Deserialize, Serialize};
+
+use super::albums::LibraryAlbum;
+use super::artists::LibraryArtist;
+use super::classical::LibraryComposer;
+use super::database::{LibraryDb, LibraryTrack};
+use super::duplicates::{DuplicateGroup, DEFAULT_DURATION_TOLERANCE_SECS};
+use super::genres::LibraryGenre;
+use super::integrity::IntegrityProblem;
+use super::query::{Paging, TrackFilter, TrackSort};
+use super::transcodes::DEFAULT_MIN_CONFIDENCE;
+
+/// Rows per chunk when the caller doesn't choose.
+pub const DEFAULT_CHUNK_SIZE: usize = 1000;
+
+#[derive(Clone, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum LibraryList {
+    Tracks,
+    Query {
+        #[serde(default)]
+        filter: TrackFilter,
+        sort: Option<TrackSort>,
+    },
+    Albums,
+    Artists {
+        #[serde(default)]
+        album_artists: bool,
+    },
+    Composers,
+    Genres,
+    GenreTracks {
+        id: i64,
+    },
+    Duplicates {
+        tolerance_secs: Option<f64>,
+    },
+    SuspectedTranscodes {
+        min_confidence: Option<f64>,
+    },
+    IntegrityProblems,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(untagged)]
+pub enum ListItems {
+    Tracks(Vec<LibraryTrack>),
+    Albums(Vec<LibraryAlbum>),
+    Artists(Vec<LibraryArtist>),
+    Composers(Vec<LibraryComposer>),
+    Genres(Vec<LibraryGenre>),
+    Duplicates(Vec<DuplicateGroup>),
+    IntegrityProblems(Vec<IntegrityProblem>),
+}
+
+impl ListItems {
+    pub fn len(&self) -> usize {
+        match self {
+            ListItems::Tracks(items) => items.len(),
+            ListItems::Albums(items) => items.len(),
+            ListItems::Artists(items) => items.len(),
+            ListItems::Composers(items) => items.len(),
+            ListItems::Genres(items) => items.len(),
+            ListItems::Duplicates(items) => items.len(),
+            ListItems::IntegrityProblems(items) => items.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// One chunk of a streamed list (`library://list-chunk`).
+#[derive(Clone, Serialize)]
+pub struct ListChunk {
+    /// Chosen by the caller, to tell concurrent streams apart.
+    pub stream_id: u32,
+    /// Position of the first item in the whole list.
+    pub offset: usize,
+    pub items: ListItems,
+    /// Last chunk of the stream.
+    pub done: bool,
+}
+
+/// A stream that failed part way (`library://list-error`).
+#[derive(Clone, Serialize)]
+pub struct ListStreamError {
+    pub stream_id: u32,
+    pub error: String,
+}
+
+impl LibraryDb {
+    /// A window of a list.
+    pub fn list(&self, list: &LibraryList, paging: Paging) -> Result<ListItems, String> {
+        Ok(match list {
+            LibraryList::Tracks => ListItems::Tracks(self.tracks(paging)?),
+            LibraryList::Query { filter, sort } => ListItems::Tracks(
+                self.query(
+                    filter,
+                    *sort,
+                    paging.limit.unwrap_or(i64::MAX as usize),
+                    paging.offset,
+                )?
+                .tracks,
+            ),
+            LibraryList::Albums => ListItems::Albums(self.albums(paging)?),
+            LibraryList::Artists { album_artists } => {
+                ListItems::Artists(self.artists(*album_artists, paging)?)
+            }
+            LibraryList::Composers => ListItems::Composers(self.composers(paging)?),
+            LibraryList::Genres => ListItems::Genres(self.genres(paging)?),
+            LibraryList::GenreTracks { id } => ListItems::Tracks(self.genre_tracks(*id, paging)?),
+            LibraryList::Duplicates { tolerance_secs } => ListItems::Duplicates(self.duplicates(
+                tolerance_secs.unwrap_or(DEFAULT_DURATION_TOLERANCE_SECS),
+                paging,
+            )?),
+            LibraryList::SuspectedTranscodes { min_confidence } => {
+                ListItems::Tracks(self.suspected_transcodes(
+                    min_confidence.unwrap_or(DEFAULT_MIN_CONFIDENCE),
+                    paging,
+                )?)
+            }
+            LibraryList::IntegrityProblems => {
+                ListItems::IntegrityProblems(self.integrity_problems(paging)?)
+            }
+        })
+    }
+}
@@ -0,0 +1,213 @@
+//! Cache for the tag reads the UI and now-playing integrations repeat: a
+//! file's metadata and its cover.
+//!
+//! Entries are keyed by path together with the file's size and mtime to the
+//! nanosecond (for cue-sheet tracks, the image's size and the newer of the
+//! two mtimes), so a file written since is read again, even within the same
+//! second. [`super::scanner::refresh_file`] also drops them after tag edits.
+//! The last [`MEMORY_ENTRIES`] reads are kept in memory. Metadata of library
+//! tracks is also stored as JSON in the `metadata_cache` table, so it
+//! survives a restart. Covers are only kept in memory, the last
+//! [`ART_ENTRIES`].
+
+use parking_lot::Mutex;
+use rusqlite::{params, OptionalExtension};
+use std::collections::VecDeque;
+use std::time::UNIX_EPOCH;
+
+use super::database::LibraryDb;
+use crate::metadata::cue;
+use crate::metadata::reader::{self, TrackMetadata};
+
+/// Files whose metadata is kept in memory.
+pub const MEMORY_ENTRIES: usize = 256;
+
+/// Covers kept in memory; they can be megabytes each.
+pub const ART_ENTRIES: usize = 8;
+
+/// What a file looked like when it was read.
+#[derive(Clone, Copy, PartialEq)]
+pub struct Stamp {
+    pub size: i64,
+    /// Nanoseconds since the epoch.
+    pub modified_ns: i64,
+}
+
+impl Stamp {
+    /// `None` if the file, or for a cue-sheet track the sheet or its
+    /// image, can't be read.
+    pub fn of(path: &str) -> Option<Self> {
+        match cue::split_virtual_track_path(path) {
+            Some((cue_path, _)) => {
+                let (_, track) = cue::find_virtual_track(path).ok().flatten()?;
+                let sheet = Self::of_file(cue_path)?;
+                let image = Self::of_file(&track.file)?;
+                Some(Stamp {
+                    size: image.size,
+                    modified_ns: sheet.modified_ns.max(image.modified_ns),
+                })
+            }
+            None => Self::of_file(path),
+        }
+    }
+
+    fn of_file(path: &str) -> Option<Self> {
+        let meta = std::fs::metadata(path).ok()?;
+        let modified_ns = meta
+            .modified()
+            .ok()
+            .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+            .map(|d| d.as_nanos() as i64)
+            .unwrap_or(0);
+        Some(Stamp {
+            size: meta.len() as i64,
+            modified_ns,
+        })
+    }
+}
+
+struct Entry<T> {
+    path: String,
+    stamp: Stamp,
+    value: T,
+}
+
+static METADATA: Mutex<VecDeque<Entry<TrackMetadata>>> = Mutex::new(VecDeque::new());
+static ART: Mutex<VecDeque<Entry<Option<String>>>> = Mutex::new(VecDeque::new());
+
+/// [`reader::read_metadata`], from the cache while the file is unchanged.
+pub fn metadata(db: &Mutex<LibraryDb>, path: &str) -> Result<TrackMetadata, String> {
+    let Some(stamp) = Stamp::of(path) else {
+        // Gone or unreadable: let the reader say why
+        return reader::read_metadata(path);
+    };
+    if let Some(meta) = lookup(&METADATA, path, stamp) {
+        return Ok(meta);
+    }
+    // Skip the database rather than wait out a scan holding it
+    let stored = db
+        .try_lock()
+        .and_then(|db| db.cached_metadata(path, stamp).ok().flatten());
+    let meta = match stored {
+        Some(meta) => meta,
+        None => {
+            let meta = reader::read_metadata(path)?;
+            if let Some(mut db) = db.try_lock() {
+                if let Err(e) = db.store_metadata(path, stamp, &meta) {
+                    log::warn!("{}", e);
+                }
+            }
+            meta
+        }
+    };
+    insert(&METADATA, MEMORY_ENTRIES, path, stamp, meta.clone());
+    Ok(meta)
+}
+
+/// [`reader::get_album_art_base64`], from the cache while the file is
+/// unchanged.
+pub fn cover_data_url(path: &str) -> Result<Option<String>, String> {
+    let Some(stamp) = Stamp::of(path) else {
+        return reader::get_album_art_base64(path);
+    };
+    if let Some(art) = lookup(&ART, path, stamp) {
+        return Ok(art);
+    }
+    let art = reader::get_album_art_base64(path)?;
+    insert(&ART, ART_ENTRIES, path, stamp, art.clone());
+    Ok(art)
+}
+
+/// Drop what's cached for a file, after writing to it.
+pub fn forget(db: &LibraryDb, path: &str) {
+    METADATA.lock().retain(|e| e.path != path);
+    ART.lock().retain(|e| e.path != path);
+    if let Err(e) = db.forget_metadata(path) {
+        log::warn!("{}", e);
+    }
+}
+
+fn lookup<T: Clone>(cache: &Mutex<VecDeque<Entry<T>>>, path: &str, stamp: Stamp) -> Option<T> {
+    let mut cache = cache.lock();
+    let index = cache.iter().position(|e| e.path == path)?;
+    let entry = cache.remove(index)?;
+    if entry.stamp != stamp {
+        return None;
+    }
+    let value = entry.value.clone();
+    // Most recently used last
+    cache.push_back(entry);
+    Some(value)
+}
+
+fn insert<T>(
+    cache: &Mutex<VecDeque<Entry<T>>>,
+    capacity: usize,
+    path: &str,
+    stamp: Stamp,
+    value: T,
+) {
+    let mut cache = cache.lock();
+    cache.retain(|e| e.path != path);
+    if cache.len() >= capacity {
+        cache.pop_front();
+    }
+    cache.push_back(Entry {
+        path: path.to_string(),
+        stamp,
+        value,
+    });
+}
+
+impl LibraryDb {
+    /// The stored metadata of a library track, if it was read from the file
+    /// as it is now.
+    pub fn cached_metadata(
+        &self,
+        path: &str,
+        stamp: Stamp,
+    ) -> Result<Option<TrackMetadata>, String> {
+        let json: Option<String> = self
+            .conn
+            .query_row(
+                "SELECT m.metadata FROM metadata_cache m JOIN tracks t ON t.id = m.track_id
+                  WHERE t.path = ?1 AND m.file_size = ?2 AND m.modified_ns = ?3",
+                params![path, stamp.size, stamp.modified_ns],
+                |row| row.get(0),
+            )
+            .optional()
+            .map_err(|e| format!("Failed to query metadata cache: {}", e))?;
+        // Written by an older version with other fields: read the file again
+        Ok(json.and_then(|j| serde_json::from_str(&j).ok()))
+    }
+
+    /// Store a track's metadata. Files outside the library are ignored.
+    pub fn store_metadata(
+        &mut self,
+        path: &str,
+        stamp: Stamp,
+        meta: &TrackMetadata,
+    ) -> Result<(), String> {
+        let json =
+            serde_json::to_string(meta).map_err(|e| format!("Failed to store metadata: {}", e))?;
+        self.conn
+            .execute(
+                "INSERT OR REPLACE INTO metadata_cache (track_id, file_size, modified_ns, metadata)
+                 SELECT id, ?2, ?3, ?4 FROM tracks WHERE path = ?1",
+                params![path, stamp.size, stamp.modified_ns, json],
+            )
+            .map_err(|e| format!("Failed to store metadata: {}", e))?;
+        Ok(())
+    }
+
+    pub fn forget_metadata(&self, path: &str) -> Result<(), String> {
+        self.conn
+            .execute(
+                "DELETE FROM metadata_cache
+                  WHERE track_id IN (SELECT id FROM tracks WHERE path = ?1)",
+                params![path],
+            )
+            .map_err(|e| format!("Failed to clear metadata cache: {}", e))?;
+        Ok(())
+    }
+}
@@ -0,0 +1,347 @@
+//! Versioned schema migrations for the library database.
+//!
+//! `PRAGMA user_version` records how many entries of [`MIGRATIONS`] a
+//! database has had applied. On open, the missing ones run in order, each in
+//! its own transaction together with the version bump, so an upgrade that
+//! fails part way leaves the database at the last good version instead of
+//! half-migrated. An existing library is copied to `library.db.bak` first.
+//!
+//! Version 1 is the schema as it stood when versioning was introduced. It
+//! is written to upgrade any older library too, since those all report
+//! version 0.
+
+use rusqlite::{params, Connection};
+use std::path::Path;
+
+use super::artists;
+use super::genres;
+
+type Migration = fn(&Connection) -> Result<(), String>;
+
+/// Schema upgrades in order; entry `n` takes a database to version `n + 1`.
+/// Append only: a released migration must never change, since databases
+/// that already ran it won't run it again.
+const MIGRATIONS: &[Migration] = &[
+    baseline,
+    sort_tags,
+    rating_tags,
+    cue_tracks,
+    metadata_cache,
+    cue_sources,
+];
+
+/// Bring the database up to the latest schema version, backing it up to
+/// `backup_path` first when there is anything to upgrade.
+pub(super) fn migrate(conn: &mut Connection, backup_path: Option<&Path>) -> Result<(), String> {
+    let version: usize = conn
+        .pragma_query_value(None, "user_version", |row| row.get(0))
+        .map_err(|e| format!("Failed to read library schema version: {}", e))?;
+    if version > MIGRATIONS.len() {
+        // Opening it anyway could break the newer app's data
+        return Err(format!(
+            "Library database has schema version {}, this version of the app supports up to {}",
+            version,
+            MIGRATIONS.len()
+        ));
+    }
+    if version == MIGRATIONS.len() {
+        return Ok(());
+    }
+
+    let has_data = table_exists(conn, "tracks")
+        .map_err(|e| format!("Failed to inspect library schema: {}", e))?;
+    if let (Some(backup_path), true) = (backup_path, has_data) {
+        backup(conn, backup_path)?;
+    }
+
+    for (i, migration) in MIGRATIONS.iter().enumerate().skip(version) {
+        let tx = conn
+            .transaction()
+            .map_err(|e| format!("Failed to upgrade library schema: {}", e))?;
+        migration(&tx)?;
+        tx.pragma_update(None, "user_version", i + 1)
+            .map_err(|e| format!("Failed to upgrade library schema: {}", e))?;
+        tx.commit()
+            .map_err(|e| format!("Failed to upgrade library schema: {}", e))?;
+    }
+    Ok(())
+}
+
+/// Write a consistent copy of the database, replacing an older backup.
+fn backup(conn: &Connection, path: &Path) -> Result<(), String> {
+    if path.exists() {
+        std::fs::remove_file(path)
+            .map_err(|e| format!("Failed to replace library backup: {}", e))?;
+    }
+    conn.execute("VACUUM INTO ?1", params![path.to_string_lossy()])
+        .map_err(|e| format!("Failed to back up library before upgrade: {}", e))?;
+    Ok(())
+}
+
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS artists (
+        id   INTEGER PRIMARY KEY,
+        name TEXT NOT NULL UNIQUE
+    );
+    CREATE TABLE IF NOT EXISTS albums (
+        id        INTEGER PRIMARY KEY,
+        title     TEXT NOT NULL,
+        artist_id INTEGER REFERENCES artists(id),
+        year      INTEGER,
+        UNIQUE(title, artist_id)
+    );
+    CREATE TABLE IF NOT EXISTS tracks (
+        id            INTEGER PRIMARY KEY,
+        path          TEXT NOT NULL UNIQUE,
+        title         TEXT,
+        artist        TEXT,
+        album         TEXT,
+        album_artist  TEXT,
+        year          INTEGER,
+        genre         TEXT,
+        track_number  INTEGER,
+        disc_number   INTEGER,
+        duration_secs REAL NOT NULL,
+        sample_rate   INTEGER,
+        bit_depth     INTEGER,
+        channels      INTEGER,
+        format        TEXT NOT NULL,
+        has_album_art INTEGER NOT NULL,
+        file_size     INTEGER NOT NULL,
+        modified_at   INTEGER NOT NULL,
+        artist_id     INTEGER REFERENCES artists(id),
+        album_id      INTEGER REFERENCES albums(id),
+        rating        INTEGER,
+        composer      TEXT,
+        work          TEXT,
+        movement      TEXT,
+        movement_number INTEGER,
+        compilation   INTEGER NOT NULL DEFAULT 0,
+        added_at      INTEGER
+    );
+    CREATE INDEX IF NOT EXISTS idx_tracks_album ON tracks(album_id);
+    CREATE INDEX IF NOT EXISTS idx_tracks_artist ON tracks(artist_id);
+    CREATE TABLE IF NOT EXISTS featured_artists (
+        track_id  INTEGER NOT NULL REFERENCES tracks(id) ON DELETE CASCADE,
+        artist_id INTEGER NOT NULL REFERENCES artists(id),
+        PRIMARY KEY (track_id, artist_id)
+    );
+    CREATE INDEX IF NOT EXISTS idx_featured_artist ON featured_artists(artist_id);
+    CREATE TABLE IF NOT EXISTS genres (
+        id   INTEGER PRIMARY KEY,
+        name TEXT NOT NULL UNIQUE COLLATE NOCASE
+    );
+    CREATE TABLE IF NOT EXISTS track_genres (
+        track_id INTEGER NOT NULL REFERENCES tracks(id) ON DELETE CASCADE,
+        genre_id INTEGER NOT NULL REFERENCES genres(id),
+        PRIMARY KEY (track_id, genre_id)
+    );
+    CREATE INDEX IF NOT EXISTS idx_track_genres_genre ON track_genres(genre_id);
+    CREATE TABLE IF NOT EXISTS plays (
+        id        INTEGER PRIMARY KEY,
+        track_id  INTEGER NOT NULL REFERENCES tracks(id) ON DELETE CASCADE,
+        played_at INTEGER NOT NULL
+    );
+    CREATE INDEX IF NOT EXISTS idx_plays_track ON plays(track_id);
+    CREATE INDEX IF NOT EXISTS idx_plays_played_at ON plays(played_at);
+    CREATE TABLE IF NOT EXISTS waveforms (
+        track_id    INTEGER PRIMARY KEY REFERENCES tracks(id) ON DELETE CASCADE,
+        modified_at INTEGER NOT NULL,
+        peaks       BLOB NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS integrity_checks (
+        track_id          INTEGER PRIMARY KEY REFERENCES tracks(id) ON DELETE CASCADE,
+        modified_at       INTEGER NOT NULL,
+        checked_at        INTEGER NOT NULL,
+        error             TEXT,
+        checksum_verified INTEGER NOT NULL DEFAULT 0
+    );
+    CREATE TABLE IF NOT EXISTS pending_restore (
+        path     TEXT PRIMARY KEY,
+        rating   INTEGER,
+        added_at INTEGER,
+        plays    TEXT NOT NULL DEFAULT ''
+    );
+    CREATE TABLE IF NOT EXISTS library_roots (
+        id              INTEGER PRIMARY KEY,
+        path            TEXT NOT NULL UNIQUE,
+        enabled         INTEGER NOT NULL DEFAULT 1,
+        last_scanned_at INTEGER
+    );
+";
+
+/// Columns added to existing tables before schema versioning:
+/// (table, column, definition, read from tags). The baseline adds any that
+/// are missing, since `CREATE TABLE IF NOT EXISTS` leaves existing tables
+/// alone. Adding a column that is read from tags makes the next scan re-read
+/// every file. Don't extend this list; new columns get a migration.
+const ADDED_COLUMNS: &[(&str, &str, &str, bool)] = &[
+    ("tracks", "rating", "INTEGER", false),
+    ("tracks", "composer", "TEXT", true),
+    ("tracks", "work", "TEXT", true),
+    ("tracks", "movement", "TEXT", true),
+    ("tracks", "movement_number", "INTEGER", true),
+    ("tracks", "compilation", "INTEGER NOT NULL DEFAULT 0", true),
+    ("tracks", "added_at", "INTEGER", false),
+    ("tracks", "dynamic_range", "REAL", false),
+    ("tracks", "spectral_cutoff_hz", "INTEGER", false),
+    ("tracks", "transcode_confidence", "REAL", false),
+];
+
+/// Full-text index over `tracks`, kept in sync by triggers. External content:
+/// the index stores no copy of the text, only the tokens.
+const FTS_SCHEMA: &str = "
+    CREATE VIRTUAL TABLE IF NOT EXISTS tracks_fts USING fts5(
+        title, artist, album, album_artist, path,
+        content = 'tracks', content_rowid = 'id',
+        tokenize = 'unicode61 remove_diacritics 2'
+    );
+    CREATE VIRTUAL TABLE IF NOT EXISTS tracks_fts_vocab USING fts5vocab(tracks_fts, 'row');
+    CREATE TRIGGER IF NOT EXISTS tracks_fts_ai AFTER INSERT ON tracks BEGIN
+        INSERT INTO tracks_fts (rowid, title, artist, album, album_artist, path)
+        VALUES (new.id, new.title, new.artist, new.album, new.album_artist, new.path);
+    END;
+    CREATE TRIGGER IF NOT EXISTS tracks_fts_ad AFTER DELETE ON tracks BEGIN
+        INSERT INTO tracks_fts (tracks_fts, rowid, title, artist, album, album_artist, path)
+        VALUES ('delete', old.id, old.title, old.artist, old.album, old.album_artist, old.path);
+    END;
+    CREATE TRIGGER IF NOT EXISTS tracks_fts_au AFTER UPDATE ON tracks BEGIN
+        INSERT INTO tracks_fts (tracks_fts, rowid, title, artist, album, album_artist, path)
+        VALUES ('delete', old.id, old.title, old.artist, old.album, old.album_artist, old.path);
+        INSERT INTO tracks_fts (rowid, title, artist, album, album_artist, path)
+        VALUES (new.id, new.title, new.artist, new.album, new.album_artist, new.path);
+    END;
+";
+
+/// Version 1: create the schema, or upgrade an unversioned library to it.
+fn baseline(conn: &Connection) -> Result<(), String> {
+    let has_credits = table_exists(conn, "featured_artists")
+        .map_err(|e| format!("Failed to inspect library schema: {}", e))?;
+    let has_genres = table_exists(conn, "track_genres")
+        .map_err(|e| format!("Failed to inspect library schema: {}", e))?;
+    conn.execute_batch(SCHEMA)
+        .map_err(|e| format!("Failed to create library schema: {}", e))?;
+    let mut reread_tags = false;
+    for (table, column, definition, from_tags) in ADDED_COLUMNS {
+        let added = add_column_if_missing(conn, table, column, definition)
+            .map_err(|e| format!("Failed to upgrade library schema: {}", e))?;
+        reread_tags |= added && *from_tags;
+    }
+    // Best guess for tracks indexed before the add time was recorded
+    conn.execute(
+        "UPDATE tracks SET added_at = modified_at WHERE added_at IS NULL",
+        [],
+    )
+    .map_err(|e| format!("Failed to upgrade library schema: {}", e))?;
+    if reread_tags {
+        // Scans skip files whose size and mtime are unchanged
+        conn.execute("UPDATE tracks SET modified_at = 0", [])
+            .map_err(|e| format!("Failed to upgrade library schema: {}", e))?;
+    }
+
+    // Libraries indexed before guest artists were split out need it done once
+    if !has_credits {
+        artists::reindex_credits(conn)
+            .map_err(|e| format!("Failed to upgrade library schema: {}", e))?;
+    }
+    // ...and before multi-value genres were split
+    if !has_genres {
+        genres::reindex_genres(conn)
+            .map_err(|e| format!("Failed to upgrade library schema: {}", e))?;
+    }
+
+    // Libraries created before the search index existed need it filled once
+    let has_fts = table_exists(conn, "tracks_fts")
+        .map_err(|e| format!("Failed to inspect library schema: {}", e))?;
+    conn.execute_batch(FTS_SCHEMA)
+        .map_err(|e| format!("Failed to create search index: {}", e))?;
+    if !has_fts {
+        conn.execute("INSERT INTO tracks_fts (tracks_fts) VALUES ('rebuild')", [])
+            .map_err(|e| format!("Failed to build search index: {}", e))?;
+    }
+    Ok(())
+}
+
+/// Version 2: sort tags on tracks and the sort names derived from them on
+/// artists and albums. Tags are re-read by the next scan to fill them.
+fn sort_tags(conn: &Connection) -> Result<(), String> {
+    conn.execute_batch(
+        "ALTER TABLE tracks ADD COLUMN artist_sort TEXT;
+         ALTER TABLE tracks ADD COLUMN album_sort TEXT;
+         ALTER TABLE tracks ADD COLUMN album_artist_sort TEXT;
+         ALTER TABLE artists ADD COLUMN sort_name TEXT;
+         ALTER TABLE albums ADD COLUMN sort_title TEXT;
+         UPDATE tracks SET modified_at = 0;",
+    )
+    .map_err(|e| format!("Failed to upgrade library schema: {}", e))
+}
+
+/// Version 3: ratings are now read from tags. Nothing changes in the
+/// schema; tags are re-read by the next scan to pick up existing ratings.
+fn rating_tags(conn: &Connection) -> Result<(), String> {
+    conn.execute("UPDATE tracks SET modified_at = 0", [])
+        .map(|_| ())
+        .map_err(|e| format!("Failed to upgrade library schema: {}", e))
+}
+
+/// Version 4: cue-sheet tracks, which point into a segment of an image file.
+fn cue_tracks(conn: &Connection) -> Result<(), String> {
+    conn.execute_batch(
+        "ALTER TABLE tracks ADD COLUMN source_path TEXT;
+         ALTER TABLE tracks ADD COLUMN start_secs REAL;
+         ALTER TABLE tracks ADD COLUMN end_secs REAL;",
+    )
+    .map_err(|e| format!("Failed to upgrade library schema: {}", e))
+}
+
+/// Version 5: tags read for the UI, kept per track for
+/// [`super::metadata_cache`].
+fn metadata_cache(conn: &Connection) -> Result<(), String> {
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS metadata_cache (
+            track_id    INTEGER PRIMARY KEY REFERENCES tracks(id) ON DELETE CASCADE,
+            file_size   INTEGER NOT NULL,
+            modified_ns INTEGER NOT NULL,
+            metadata    TEXT NOT NULL
+        );",
+    )
+    .map_err(|e| format!("Failed to upgrade library schema: {}", e))
+}
+
+/// Version 6: cue-sheet tracks looked up by their image file, which the
+/// analysis jobs check once for all of its tracks.
+fn cue_sources(conn: &Connection) -> Result<(), String> {
+    conn.execute_batch("CREATE INDEX IF NOT EXISTS idx_tracks_source ON tracks(source_path);")
+        .map_err(|e| format!("Failed to upgrade library schema: {}", e))
+}
+
+fn table_exists(conn: &Connection, name: &str) -> rusqlite::Result<bool> {
+    conn.query_row(
+        "SELECT EXISTS(SELECT 1 FROM sqlite_master WHERE name = ?1)",
+        params![name],
+        |row| row.get(0),
+    )
+}
+
+fn add_column_if_missing(
+    conn: &Connection,
+    table: &str,
+    column: &str,
+    definition: &str,
+) -> rusqlite::Result<bool> {
+    let exists: bool = conn.query_row(
+        &format!(
+            "SELECT EXISTS(SELECT 1 FROM pragma_table_info('{}') WHERE name = ?1)",
+            table
+        ),
+        params![column],
+        |row| row.get(0),
+    )?;
+    if !exists {
+        conn.execute_batch(&format!(
+            "ALTER TABLE {} ADD COLUMN {} {}",
+            table, column, definition
+        ))?;
+    }
+    Ok(!exists)
+}
@@ -1,2 +1,32 @@
-pub mod scanner;
+pub mod accuraterip;
+pub mod albums;
+pub mod analysis;
+pub mod artists;
+pub mod artwork;
+pub mod backup;
+pub mod bookmarks;
+pub mod cd_drive;
+pub mod classical;
+pub mod compilations;
 pub mod database;
+pub mod duplicates;
+pub mod dynamic_range;
+pub mod encodings;
+pub mod exclude;
+pub mod folders;
+pub mod genres;
+pub mod integrity;
+pub mod itunes;
+pub mod lists;
+pub mod metadata_cache;
+pub mod migrations;
+pub mod organize;
+pub mod plays;
+pub mod query;
+pub mod replaygain;
+pub mod ripper;
+pub mod scanner;
+pub mod search;
+pub mod stats;
+pub mod transcodes;
+pub mod waveform;
@@ -0,0 +1,600 @@
+//! Rename and move files by a tag pattern.
+//!
+//! A pattern such as `%albumartist%/%album%/%track% - %title%` gives each
+//! track's path relative to its library root (or a chosen destination
+//! folder); the original extension is kept. Tag values are made safe for
+//! every filesystem the library may live on, so `AC/DC` becomes `AC_DC`
+//! rather than an extra folder.
+//!
+//! A cue sheet moves as a whole: the sheet and its image files go together
+//! into the folder the pattern gives its first track, keeping their names
+//! so the sheet's FILE lines still point at the images.
+//!
+//! [`plan`] only looks; [`run`] moves the files and updates the library
+//! paths as it goes.
+
+use parking_lot::Mutex;
+use rusqlite::ToSql;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+use super::compilations::VARIOUS_ARTISTS;
+use super::database::{dir_prefix, LibraryDb, LibraryTrack, TRACK_COLUMNS};
+use super::scanner::{JobProgress, JobSummary, ScanControl, PROGRESS_INTERVAL};
+use crate::metadata::cue;
+
+const PLACEHOLDERS: &[&str] = &[
+    "title",
+    "artist",
+    "album",
+    "albumartist",
+    "year",
+    "genre",
+    "track",
+    "disc",
+    "composer",
+];
+
+/// Longest file or folder name written, in characters. Leaves room under
+/// the usual 255-byte limit for multi-byte characters and numbering.
+const MAX_NAME_CHARS: usize = 120;
+
+/// What to do when the new path is already taken.
+#[derive(Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CollisionPolicy {
+    /// Leave the file where it is.
+    #[default]
+    Skip,
+    /// Add " (2)", " (3)", … to the file name.
+    Number,
+}
+
+#[derive(Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MoveStatus {
+    Move,
+    /// Already at its new path.
+    Unchanged,
+    /// Left alone; see `reason`.
+    Skipped,
+}
+
+#[derive(Clone, Serialize)]
+pub struct PlannedMove {
+    /// For a cue sheet, its first track.
+    pub track_id: i64,
+    /// The file, or for cue-sheet tracks the sheet.
+    pub from: String,
+    pub to: String,
+    pub status: MoveStatus,
+    pub reason: Option<String>,
+}
+
+impl LibraryDb {
+    /// The given tracks, or every visible track when `track_ids` is empty.
+    pub fn tracks_by_ids(&self, track_ids: &[i64]) -> Result<Vec<LibraryTrack>, String> {
+        if track_ids.is_empty() {
+            self.query_tracks(
+                &format!("SELECT {} FROM visible_tracks ORDER BY path", TRACK_COLUMNS),
+                &[],
+            )
+        } else {
+            let params: Vec<&dyn ToSql> = track_ids.iter().map(|id| id as &dyn ToSql).collect();
+            self.query_tracks(
+                &format!(
+                    "SELECT {} FROM visible_tracks WHERE id IN ({}) ORDER BY path",
+                    TRACK_COLUMNS,
+                    vec!["?"; track_ids.len()].join(", ")
+                ),
+                &params,
+            )
+        }
+    }
+
+    /// Point a track at its new path after the file was moved.
+    pub fn set_track_path(&mut self, id: i64, path: &str) -> Result<(), String> {
+        self.conn
+            .execute(
+                "UPDATE tracks SET path = ?2 WHERE id = ?1",
+                rusqlite::params![id, path],
+            )
+            .map_err(|e| format!("Failed to update track path: {}", e))?;
+        Ok(())
+    }
+
+    /// Point a cue sheet's tracks at the sheet's new path and their images'
+    /// after the files were moved. Returns the old and new path of each
+    /// track.
+    pub fn set_cue_sheet_path(
+        &mut self,
+        from: &str,
+        to: &str,
+        images: &[(String, String)],
+    ) -> Result<Vec<(String, String)>, String> {
+        let err = |e: rusqlite::Error| format!("Failed to update track path: {}", e);
+        let tx = self.conn.transaction().map_err(err)?;
+        let tracks: Vec<(i64, String)> = {
+            let mut stmt = tx
+                .prepare(
+                    "SELECT id, path FROM tracks
+                      WHERE source_path IS NOT NULL AND substr(path, 1, length(?1)) = ?1",
+                )
+                .map_err(err)?;
+            let rows = stmt
+                .query_map([from], |row| Ok((row.get(0)?, row.get(1)?)))
+                .map_err(err)?;
+            rows.collect::<Result<_, _>>().map_err(err)?
+        };
+        let mut renamed = Vec::new();
+        for (id, path) in tracks {
+            let Some((_, number)) =
+                cue::split_virtual_track_path(&path).filter(|(sheet, _)| *sheet == from)
+            else {
+                continue;
+            };
+            let new_path = cue::virtual_track_path(to, number);
+            tx.execute(
+                "UPDATE tracks SET path = ?2 WHERE id = ?1",
+                rusqlite::params![id, new_path],
+            )
+            .map_err(err)?;
+            renamed.push((path, new_path));
+        }
+        for (old, new) in images {
+            tx.execute(
+                "UPDATE tracks SET source_path = ?2 WHERE source_path = ?1",
+                rusqlite::params![old, new],
+            )
+            .map_err(err)?;
+        }
+        tx.commit().map_err(err)?;
+        Ok(renamed)
+    }
+}
+
+/// Work out where each track would go (see [`LibraryDb::tracks_by_ids`]
+/// for `track_ids`); `destination` replaces each track's library root.
+/// Cue-sheet tracks give one move for their whole sheet.
+///
+/// A path counts as taken when a file exists there or an earlier track in
+/// the plan claims it, even if that file is about to move away.
+pub fn plan(
+    db: &Mutex<LibraryDb>,
+    pattern: &str,
+    track_ids: &[i64],
+    destination: Option<&str>,
+    collision: CollisionPolicy,
+) -> Result<Vec<PlannedMove>, String> {
+    check_pattern(pattern)?;
+    // Lock released before the files are looked at, which can be slow
+    let (roots, tracks) = {
+        let db = db.lock();
+        let roots: Vec<String> = db.roots()?.into_iter().map(|r| r.path).collect();
+        (roots, db.tracks_by_ids(track_ids)?)
+    };
+
+    let mut claimed = HashSet::new();
+    let mut sheets = HashSet::new();
+    let mut plan = Vec::with_capacity(tracks.len());
+    for track in tracks {
+        let sheet = cue::split_virtual_track_path(&track.path).map(|(sheet, _)| sheet.to_string());
+        if let Some(sheet) = &sheet {
+            if !sheets.insert(sheet.clone()) {
+                continue;
+            }
+        }
+        let base = match destination {
+            Some(dest) => Some(dest.to_string()),
+            None => roots
+                .iter()
+                .find(|root| track.path.starts_with(&dir_prefix(root)))
+                .cloned(),
+        };
+        let from = sheet.as_deref().unwrap_or(&track.path);
+        let Some(base) = base else {
+            plan.push(skipped(
+                track.id,
+                from,
+                "Not under a library root".to_string(),
+            ));
+            continue;
+        };
+        let target = target_path(&base, pattern, &track, None);
+        plan.push(match &sheet {
+            Some(sheet) => place_sheet(track.id, sheet, &target, &mut claimed),
+            None => place(track.id, &track.path, target, collision, &mut claimed),
+        });
+    }
+    Ok(plan)
+}
+
+fn skipped(track_id: i64, from: &str, reason: String) -> PlannedMove {
+    PlannedMove {
+        track_id,
+        from: from.to_string(),
+        to: from.to_string(),
+        status: MoveStatus::Skipped,
+        reason: Some(reason),
+    }
+}
+
+/// Settle a file's target against files on disk and earlier targets.
+fn place(
+    track_id: i64,
+    from: &str,
+    target: PathBuf,
+    collision: CollisionPolicy,
+    claimed: &mut HashSet<String>,
+) -> PlannedMove {
+    let mut to = target.clone();
+    let mut n = 1;
+    loop {
+        let to_str = to.to_string_lossy().to_string();
+        // Case-insensitive filesystems see "a.flac" and "A.flac" as one file
+        let key = to_str.to_lowercase();
+        if to_str == from {
+            claimed.insert(key);
+            return PlannedMove {
+                track_id,
+                from: from.to_string(),
+                to: to_str,
+                status: MoveStatus::Unchanged,
+                reason: None,
+            };
+        }
+        let same_file = key == from.to_lowercase();
+        let taken = claimed.contains(&key) || (!same_file && to.exists());
+        if !taken {
+            claimed.insert(key);
+            return PlannedMove {
+                track_id,
+                from: from.to_string(),
+                to: to_str,
+                status: MoveStatus::Move,
+                reason: None,
+            };
+        }
+        match collision {
+            CollisionPolicy::Skip => {
+                return skipped(track_id, from, format!("{} already exists", to_str));
+            }
+            CollisionPolicy::Number => {
+                n += 1;
+                to = numbered(&target, n);
+            }
+        }
+    }
+}
+
+/// Settle a cue sheet's move into the folder of its first track's
+/// `target`, with its images. Names are kept, so a name already taken
+/// there skips the sheet whatever the collision policy.
+fn place_sheet(
+    track_id: i64,
+    sheet: &str,
+    target: &Path,
+    claimed: &mut HashSet<String>,
+) -> PlannedMove {
+    let folder = target.parent().unwrap_or(target);
+    let images = match sheet_images(sheet) {
+        Ok(images) => images,
+        Err(e) => return skipped(track_id, sheet, e),
+    };
+    let mut targets = Vec::with_capacity(images.len() + 1);
+    for file in std::iter::once(sheet).chain(images.iter().map(String::as_str)) {
+        let Some(name) = Path::new(file).file_name() else {
+            return skipped(track_id, sheet, format!("{} has no file name", file));
+        };
+        targets.push((file, folder.join(name)));
+    }
+
+    let to = targets[0].1.to_string_lossy().to_string();
+    if to == sheet {
+        for (_, target) in &targets {
+            claimed.insert(target.to_string_lossy().to_lowercase());
+        }
+        return PlannedMove {
+            track_id,
+            from: sheet.to_string(),
+            to,
+            status: MoveStatus::Unchanged,
+            reason: None,
+        };
+    }
+    for (file, target) in &targets {
+        let key = target.to_string_lossy().to_lowercase();
+        let same_file = key == file.to_lowercase();
+        if claimed.contains(&key) || (!same_file && target.exists()) {
+            let reason = format!("{} already exists", target.to_string_lossy());
+            return skipped(track_id, sheet, reason);
+        }
+    }
+    for (_, target) in &targets {
+        claimed.insert(target.to_string_lossy().to_lowercase());
+    }
+    PlannedMove {
+        track_id,
+        from: sheet.to_string(),
+        to,
+        status: MoveStatus::Move,
+        reason: None,
+    }
+}
+
+/// The image files a cue sheet splits.
+fn sheet_images(sheet: &str) -> Result<Vec<String>, String> {
+    let mut images: Vec<String> = Vec::new();
+    for track in cue::parse_cue_file(sheet)?.tracks {
+        if !images.contains(&track.file) {
+            images.push(track.file);
+        }
+    }
+    Ok(images)
+}
+
+/// `path` with ` (n)` added to the file name.
+pub fn numbered(path: &Path, n: u32) -> PathBuf {
+    let stem = path
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let name = match path.extension() {
+        Some(ext) => format!("{} ({}).{}", stem, n, ext.to_string_lossy()),
+        None => format!("{} ({})", stem, n),
+    };
+    path.with_file_name(name)
+}
+
+/// Fail on placeholders the pattern language doesn't know.
+pub fn check_pattern(pattern: &str) -> Result<(), String> {
+    if pattern.trim().is_empty() {
+        return Err("Pattern is empty".to_string());
+    }
+    let parts: Vec<&str> = pattern.split('%').collect();
+    if parts.len().is_multiple_of(2) {
+        return Err("Pattern has an unmatched %".to_string());
+    }
+    for name in parts.iter().skip(1).step_by(2) {
+        if !PLACEHOLDERS.contains(&name.to_lowercase().as_str()) {
+            return Err(format!("Unknown placeholder %{}%", name));
+        }
+    }
+    Ok(())
+}
+
+/// `base` joined with the expanded pattern and `extension`, or the track's
+/// own extension when `None`.
+pub fn target_path(
+    base: &str,
+    pattern: &str,
+    track: &LibraryTrack,
+    extension: Option<&str>,
+) -> PathBuf {
+    let mut path = PathBuf::from(base);
+    let components: Vec<String> = pattern
+        .split(['/', '\\'])
+        .map(|part| sanitize(&expand(part, track)))
+        .filter(|part| !part.is_empty())
+        .collect();
+    let Some((file_name, folders)) = components.split_last() else {
+        return PathBuf::from(&track.path);
+    };
+    for folder in folders {
+        path.push(folder);
+    }
+    let extension = extension.map(str::to_string).or_else(|| {
+        Path::new(&track.path)
+            .extension()
+            .map(|ext| ext.to_string_lossy().to_string())
+    });
+    match extension {
+        Some(ext) => path.push(format!("{}.{}", file_name, ext)),
+        None => path.push(file_name),
+    }
+    path
+}
+
+fn expand(part: &str, track: &LibraryTrack) -> String {
+    let text = |value: &Option<String>| {
+        value
+            .as_deref()
+            .map(str::trim)
+            .filter(|v| !v.is_empty())
+            .map(str::to_string)
+    };
+    let artist = text(&track.artist);
+    part.split('%')
+        .enumerate()
+        .map(|(i, piece)| {
+            if i % 2 == 0 {
+                return piece.to_string();
+            }
+            match piece.to_lowercase().as_str() {
+                "title" => text(&track.title).unwrap_or_else(|| {
+                    Path::new(&track.path)
+                        .file_stem()
+                        .map(|s| s.to_string_lossy().to_string())
+                        .unwrap_or_default()
+                }),
+                "artist" => artist
+                    .clone()
+                    .unwrap_or_else(|| "Unknown Artist".to_string()),
+                "album" => text(&track.album).unwrap_or_else(|| "Unknown Album".to_string()),
+                "albumartist" => text(&track.album_artist)
+                    .or_else(|| track.compilation.then(|| VARIOUS_ARTISTS.to_string()))
+                    .or_else(|| artist.clone())
+                    .unwrap_or_else(|| "Unknown Artist".to_string()),
+                "year" => track.year.map(|y| y.to_string()).unwrap_or_default(),
+                "genre" => text(&track.genre).unwrap_or_default(),
+                "track" => track
+                    .track_number
+                    .map(|n| format!("{:02}", n))
+                    .unwrap_or_default(),
+                "disc" => track.disc_number.map(|n| n.to_string()).unwrap_or_default(),
+                "composer" => text(&track.composer).unwrap_or_default(),
+                _ => String::new(),
+            }
+        })
+        .collect()
+}
+
+/// A file or folder name that is valid on Windows, macOS and Linux.
+pub fn sanitize(name: &str) -> String {
+    let replaced: String = name
+        .chars()
+        .map(|c| match c {
+            '<' | '>' | ':' | '"' | '/' | '\\' | '|' | '?' | '*' => '_',
+            c if c.is_control() => '_',
+            c => c,
+        })
+        .take(MAX_NAME_CHARS)
+        .collect();
+    // Left over from empty placeholders, e.g. " - Title" without a track number
+    let trimmed = replaced
+        .trim()
+        .trim_start_matches("- ")
+        .trim_end_matches(" -")
+        // Windows drops trailing dots, which would change the name
+        .trim_end_matches('.')
+        .trim()
+        .to_string();
+    let stem = trimmed.split('.').next().unwrap_or("").to_uppercase();
+    let reserved = matches!(stem.as_str(), "CON" | "PRN" | "AUX" | "NUL")
+        || ((stem.starts_with("COM") || stem.starts_with("LPT"))
+            && stem.len() == 4
+            && stem.ends_with(|c: char| c.is_ascii_digit()));
+    if reserved {
+        format!("_{}", trimmed)
+    } else {
+        trimmed
+    }
+}
+
+/// Move the files of a plan and update the library, returning the old and
+/// new path of every file moved. Runs on the caller's thread;
+/// `on_progress` is called at most every [`PROGRESS_INTERVAL`]. Folders
+/// left empty inside a library root are removed.
+pub fn run(
+    db: &Mutex<LibraryDb>,
+    plan: &[PlannedMove],
+    control: &ScanControl,
+    on_progress: &mut dyn FnMut(&JobProgress),
+) -> Result<(JobSummary, HashMap<String, String>), String> {
+    let roots: Vec<String> = db.lock().roots()?.into_iter().map(|r| r.path).collect();
+    let moves: Vec<&PlannedMove> = plan
+        .iter()
+        .filter(|m| m.status == MoveStatus::Move)
+        .collect();
+    let mut summary = JobSummary::default();
+    let mut moved = HashMap::new();
+    let mut last_report = Instant::now();
+
+    for (i, planned) in moves.iter().enumerate() {
+        if control.is_cancelled() {
+            summary.cancelled = true;
+            break;
+        }
+        if last_report.elapsed() >= PROGRESS_INTERVAL {
+            last_report = Instant::now();
+            on_progress(&JobProgress {
+                total: moves.len(),
+                processed: i,
+                current_file: planned.from.clone(),
+            });
+        }
+        let result = if cue::is_cue_file(&planned.from) {
+            move_sheet(db, &planned.from, &planned.to)
+        } else {
+            move_file(&planned.from, &planned.to)
+                .and_then(|()| db.lock().set_track_path(planned.track_id, &planned.to))
+                .map(|()| vec![(planned.from.clone(), planned.to.clone())])
+        };
+        match result {
+            Ok(renamed) => {
+                summary.processed += 1;
+                moved.extend(renamed);
+                remove_empty_dirs(Path::new(&planned.from), &roots);
+            }
+            Err(e) => summary.errors.push(format!("{}: {}", planned.from, e)),
+        }
+    }
+    Ok((summary, moved))
+}
+
+/// Move a cue sheet and its images into the folder of `to`, returning the
+/// old and new path of each of its tracks. Images already moved go back
+/// if one of them can't be, so the sheet never loses its audio.
+fn move_sheet(
+    db: &Mutex<LibraryDb>,
+    from: &str,
+    to: &str,
+) -> Result<Vec<(String, String)>, String> {
+    let folder = Path::new(to).parent().unwrap_or(Path::new(to));
+    let mut images: Vec<(String, String)> = Vec::new();
+    for image in sheet_images(from)? {
+        let Some(name) = Path::new(&image).file_name() else {
+            continue;
+        };
+        let target = folder.join(name).to_string_lossy().to_string();
+        if target == image {
+            continue;
+        }
+        if let Err(e) = move_file(&image, &target) {
+            for (image, target) in images.iter().rev() {
+                let _ = move_file(target, image);
+            }
+            return Err(format!("{}: {}", image, e));
+        }
+        images.push((image, target));
+    }
+    if let Err(e) = move_file(from, to) {
+        for (image, target) in images.iter().rev() {
+            let _ = move_file(target, image);
+        }
+        return Err(e);
+    }
+    db.lock().set_cue_sheet_path(from, to, &images)
+}
+
+fn move_file(from: &str, to: &str) -> Result<(), String> {
+    let to_path = Path::new(to);
+    // The plan may be stale; never overwrite (a case-only rename is fine)
+    if to_path.exists() && from.to_lowercase() != to.to_lowercase() {
+        return Err(format!("{} already exists", to));
+    }
+    if let Some(parent) = to_path.parent() {
+        std::fs::create_dir_all(parent).map_err(|e| format!("Failed to create folder: {}", e))?;
+    }
+    if std::fs::rename(from, to).is_ok() {
+        return Ok(());
+    }
+    // Renames can't cross drives; copy instead
+    if let Err(e) = std::fs::copy(from, to) {
+        let _ = std::fs::remove_file(to);
+        return Err(format!("Failed to move file: {}", e));
+    }
+    std::fs::remove_file(from).map_err(|e| format!("Copied, but failed to remove original: {}", e))
+}
+
+/// Remove the folder of a moved file and its parents while they're empty,
+/// stopping below the library root.
+fn remove_empty_dirs(file: &Path, roots: &[String]) {
+    let mut dir = file.parent();
+    while let Some(d) = dir {
+        let d_str = d.to_string_lossy();
+        if !roots
+            .iter()
+            .any(|root| d_str.starts_with(&dir_prefix(root)))
+        {
+            break;
+        }
+        // Fails, and stops, at the first folder that still has something in it
+        if std::fs::remove_dir(d).is_err() {
+            break;
+        }
+        dir = d.parent();
+    }
+}
@@ -0,0 +1,200 @@
+//! Play counts and the canned "recently added / most played / never played"
+//! views.
+//!
+//! A counter thread watches the engine and logs a play in the `plays` table
+//! once a track has been listened to for half its length or four minutes,
+//! whichever comes first (the Last.fm scrobble rule), so skipped tracks don't
+//! count. The same plays are handed on for submission to
+//! [`crate::listenbrainz`].
+
+use parking_lot::Mutex;
+use rusqlite::params;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use super::albums::LibraryAlbum;
+use super::database::{
+    track_from_row, unix_now, LibraryDb, LibraryTrack, TRACK_COLUMNS, TRACK_COLUMN_COUNT,
+};
+use super::query::{TrackFilter, TrackPage, TrackSort, TrackSortField};
+use crate::audio::engine::{AudioEngine, PlaybackState};
+
+/// How often the counter samples the playback position.
+const SAMPLE_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Listening time after which a play always counts.
+const MAX_PLAY_THRESHOLD_SECS: f64 = 240.0;
+
+/// Time window for the most-played view.
+#[derive(Clone, Copy, Serialize, Deserialize)]
+pub enum PlayPeriod {
+    Week,
+    Month,
+    Year,
+    AllTime,
+}
+
+impl PlayPeriod {
+    /// Start of the window as a Unix timestamp.
+    fn since(self, now: i64) -> i64 {
+        const DAY: i64 = 24 * 60 * 60;
+        match self {
+            PlayPeriod::Week => now - 7 * DAY,
+            PlayPeriod::Month => now - 30 * DAY,
+            PlayPeriod::Year => now - 365 * DAY,
+            PlayPeriod::AllTime => 0,
+        }
+    }
+}
+
+#[derive(Clone, Serialize)]
+pub struct PlayedTrack {
+    pub track: LibraryTrack,
+    /// Plays within the requested period.
+    pub play_count: u32,
+    /// Unix timestamp (seconds) of the latest play.
+    pub last_played_at: i64,
+}
+
+impl LibraryDb {
+    /// Log a play of the track at `path`. Files outside the library are ignored.
+    pub fn record_play(&mut self, path: &str, at: i64) -> Result<(), String> {
+        self.conn
+            .execute(
+                "INSERT INTO plays (track_id, played_at)
+                 SELECT id, ?2 FROM tracks WHERE path = ?1",
+                params![path, at],
+            )
+            .map_err(|e| format!("Failed to record play: {}", e))?;
+        Ok(())
+    }
+
+    /// Albums by when their newest track was added, newest first.
+    pub fn recently_added(&self, limit: usize, offset: usize) -> Result<Vec<LibraryAlbum>, String> {
+        Ok(self
+            .album_rows_ordered(
+                "1",
+                &format!(
+                    "a.added_at IS NULL, a.added_at DESC, a.id DESC LIMIT {} OFFSET {}",
+                    limit, offset
+                ),
+                params![],
+            )?
+            .into_iter()
+            .map(|(album, _)| album)
+            .collect())
+    }
+
+    /// Tracks by play count within `period`, most played first.
+    pub fn most_played(
+        &self,
+        period: PlayPeriod,
+        limit: usize,
+        offset: usize,
+    ) -> Result<Vec<PlayedTrack>, String> {
+        let mut stmt = self
+            .conn
+            .prepare(&format!(
+                "SELECT {}, p.play_count, p.last_played_at FROM visible_tracks
+                   JOIN (SELECT track_id, COUNT(*) AS play_count, MAX(played_at) AS last_played_at
+                           FROM plays WHERE played_at >= ?1
+                          GROUP BY track_id) p
+                     ON p.track_id = visible_tracks.id
+                  ORDER BY p.play_count DESC, p.last_played_at DESC
+                  LIMIT ?2 OFFSET ?3",
+                TRACK_COLUMNS
+            ))
+            .map_err(|e| format!("Failed to query plays: {}", e))?;
+        let rows = stmt
+            .query_map(
+                params![period.since(unix_now()), limit as i64, offset as i64],
+                |row| {
+                    Ok(PlayedTrack {
+                        track: track_from_row(row)?,
+                        play_count: row.get(TRACK_COLUMN_COUNT)?,
+                        last_played_at: row.get(TRACK_COLUMN_COUNT + 1)?,
+                    })
+                },
+            )
+            .map_err(|e| format!("Failed to query plays: {}", e))?;
+        rows.collect::<Result<_, _>>()
+            .map_err(|e| format!("Failed to query plays: {}", e))
+    }
+
+    /// Tracks that have never been played, most recently added first.
+    pub fn never_played(&self, limit: usize, offset: usize) -> Result<TrackPage, String> {
+        self.query(
+            &TrackFilter {
+                never_played: true,
+                ..TrackFilter::default()
+            },
+            Some(TrackSort {
+                field: TrackSortField::Added,
+                descending: true,
+            }),
+            limit,
+            offset,
+        )
+    }
+}
+
+/// Spawn the thread that counts plays of the engine's current file.
+/// `on_play` gets the playback state and Unix time of every play counted.
+pub fn spawn_counter(
+    engine: Arc<AudioEngine>,
+    library: Arc<Mutex<LibraryDb>>,
+    on_play: impl Fn(&PlaybackState, i64) + Send + 'static,
+) {
+    thread::Builder::new()
+        .name("play-counter".into())
+        .spawn(move || {
+            let mut current: Option<String> = None;
+            let mut last_position = 0.0;
+            let mut listened = 0.0;
+            let mut counted = false;
+            let interval = SAMPLE_INTERVAL.as_secs_f64();
+
+            loop {
+                thread::sleep(SAMPLE_INTERVAL);
+
+                let s = engine.get_state();
+                let Some(file) = s.current_file.clone() else {
+                    current = None;
+                    continue;
+                };
+
+                // A new file, or the same one starting over (repeat-one)
+                let restarted = s.position_secs < interval && last_position > interval * 2.0;
+                if current.as_deref() != Some(file.as_str()) || restarted {
+                    current = Some(file.clone());
+                    listened = 0.0;
+                    counted = false;
+                    last_position = s.position_secs;
+                }
+
+                // Only count forward progress at normal speed — seeks don't add up
+                let delta = s.position_secs - last_position;
+                last_position = s.position_secs;
+                if s.is_playing && !s.is_paused && delta > 0.0 && delta <= interval * 2.0 {
+                    listened += delta;
+                }
+
+                let threshold = if s.duration_secs > 0.0 {
+                    (s.duration_secs / 2.0).min(MAX_PLAY_THRESHOLD_SECS)
+                } else {
+                    MAX_PLAY_THRESHOLD_SECS
+                };
+                if !counted && listened >= threshold {
+                    counted = true;
+                    let at = unix_now();
+                    if let Err(e) = library.lock().record_play(&file, at) {
+                        log::warn!("{}", e);
+                    }
+                    on_play(&s, at);
+                }
+            }
+        })
+        .expect("Failed to spawn play counter thread");
+}
@@ -0,0 +1,274 @@
+//! Structured track queries: filter predicates, sorting and paging, compiled
+//! into a single SQL statement over `visible_tracks`.
+
+use rusqlite::types::Value;
+use serde::{Deserialize, Serialize};
+
+use super::database::{track_from_row, LibraryDb, LibraryTrack, TRACK_ALBUM_ORDER, TRACK_COLUMNS};
+
+/// Page size used when the caller doesn't pass a limit.
+pub const DEFAULT_QUERY_LIMIT: usize = 500;
+
+/// Window of a list: `limit` rows starting at `offset`, or every row from
+/// `offset` on without a limit. Lists keep a stable order, so consecutive
+/// windows never overlap.
+#[derive(Clone, Copy, Default)]
+pub struct Paging {
+    pub limit: Option<usize>,
+    pub offset: usize,
+}
+
+impl Paging {
+    /// The whole list.
+    pub const ALL: Paging = Paging {
+        limit: None,
+        offset: 0,
+    };
+
+    pub fn new(limit: Option<usize>, offset: Option<usize>) -> Self {
+        Paging {
+            limit,
+            offset: offset.unwrap_or(0),
+        }
+    }
+
+    /// ` LIMIT … OFFSET …` to append after an ORDER BY clause.
+    pub(super) fn sql(&self) -> String {
+        match self.limit {
+            Some(limit) => format!(" LIMIT {} OFFSET {}", limit, self.offset),
+            None if self.offset > 0 => format!(" LIMIT -1 OFFSET {}", self.offset),
+            None => String::new(),
+        }
+    }
+
+    /// The window of a list built in memory.
+    pub(super) fn apply<T>(&self, items: Vec<T>) -> Vec<T> {
+        items
+            .into_iter()
+            .skip(self.offset)
+            .take(self.limit.unwrap_or(usize::MAX))
+            .collect()
+    }
+}
+
+/// Track predicates. Every field is optional; set fields are ANDed together.
+/// Ranges are inclusive.
+#[derive(Clone, Serialize, Deserialize, Default)]
+#[serde(default)]
+pub struct TrackFilter {
+    pub year_min: Option<u32>,
+    pub year_max: Option<u32>,
+    /// Any of these genres (case-insensitive). Matches each value of a
+    /// multi-genre tag.
+    pub genres: Vec<String>,
+    pub genre_id: Option<i64>,
+    /// Any of these formats, e.g. "FLAC" (case-insensitive).
+    pub formats: Vec<String>,
+    pub sample_rate_min: Option<u32>,
+    pub sample_rate_max: Option<u32>,
+    pub bit_depth_min: Option<u8>,
+    pub bit_depth_max: Option<u8>,
+    pub duration_min_secs: Option<f64>,
+    pub duration_max_secs: Option<f64>,
+    pub rating_min: Option<u8>,
+    pub rating_max: Option<u8>,
+    /// Only tracks without a rating.
+    pub unrated: bool,
+    /// Only tracks that have never been played.
+    pub never_played: bool,
+    pub artist_id: Option<i64>,
+    pub album_id: Option<i64>,
+}
+
+#[derive(Clone, Copy, Serialize, Deserialize)]
+pub enum TrackSortField {
+    Title,
+    Artist,
+    Album,
+    Year,
+    Genre,
+    Format,
+    Duration,
+    SampleRate,
+    BitDepth,
+    Rating,
+    FileSize,
+    Modified,
+    Added,
+    Path,
+}
+
+#[derive(Clone, Copy, Serialize, Deserialize)]
+pub struct TrackSort {
+    pub field: TrackSortField,
+    #[serde(default)]
+    pub descending: bool,
+}
+
+#[derive(Clone, Serialize)]
+pub struct TrackPage {
+    pub tracks: Vec<LibraryTrack>,
+    /// Number of tracks matching the filter, ignoring limit/offset.
+    pub total: u32,
+}
+
+impl LibraryDb {
+    pub fn query(
+        &self,
+        filter: &TrackFilter,
+        sort: Option<TrackSort>,
+        limit: usize,
+        offset: usize,
+    ) -> Result<TrackPage, String> {
+        let (where_sql, mut params) = build_where(filter);
+
+        let total: u32 = self
+            .conn
+            .query_row(
+                &format!("SELECT COUNT(*) FROM visible_tracks {}", where_sql),
+                rusqlite::params_from_iter(params.iter()),
+                |row| row.get(0),
+            )
+            .map_err(|e| format!("Failed to query tracks: {}", e))?;
+
+        let sql = format!(
+            "SELECT {} FROM visible_tracks {} ORDER BY {} LIMIT ? OFFSET ?",
+            TRACK_COLUMNS,
+            where_sql,
+            order_by(sort)
+        );
+        params.push(Value::Integer(limit as i64));
+        params.push(Value::Integer(offset as i64));
+
+        let mut stmt = self
+            .conn
+            .prepare(&sql)
+            .map_err(|e| format!("Failed to query tracks: {}", e))?;
+        let rows = stmt
+            .query_map(rusqlite::params_from_iter(params.iter()), track_from_row)
+            .map_err(|e| format!("Failed to query tracks: {}", e))?;
+        let tracks = rows
+            .collect::<Result<_, _>>()
+            .map_err(|e| format!("Failed to query tracks: {}", e))?;
+
+        Ok(TrackPage { tracks, total })
+    }
+}
+
+/// `WHERE ...` clause (empty if no predicates) and its positional parameters.
+fn build_where(filter: &TrackFilter) -> (String, Vec<Value>) {
+    let mut clauses: Vec<String> = Vec::new();
+    let mut params: Vec<Value> = Vec::new();
+
+    let mut range = |column: &str, min: Option<Value>, max: Option<Value>| {
+        if let Some(min) = min {
+            clauses.push(format!("{} >= ?", column));
+            params.push(min);
+        }
+        if let Some(max) = max {
+            clauses.push(format!("{} <= ?", column));
+            params.push(max);
+        }
+    };
+    let int = |v: Option<u32>| v.map(|v| Value::Integer(v as i64));
+    let small = |v: Option<u8>| v.map(|v| Value::Integer(v as i64));
+    let real = |v: Option<f64>| v.map(Value::Real);
+
+    range("year", int(filter.year_min), int(filter.year_max));
+    range(
+        "sample_rate",
+        int(filter.sample_rate_min),
+        int(filter.sample_rate_max),
+    );
+    range(
+        "bit_depth",
+        small(filter.bit_depth_min),
+        small(filter.bit_depth_max),
+    );
+    range(
+        "duration_secs",
+        real(filter.duration_min_secs),
+        real(filter.duration_max_secs),
+    );
+    range("rating", small(filter.rating_min), small(filter.rating_max));
+
+    // `{}` is replaced by "lower(?), lower(?), ..."
+    let mut any_of = |clause: &str, values: &[String]| {
+        let values: Vec<&String> = values.iter().filter(|v| !v.trim().is_empty()).collect();
+        if values.is_empty() {
+            return;
+        }
+        let placeholders = vec!["lower(?)"; values.len()].join(", ");
+        clauses.push(clause.replace("{}", &placeholders));
+        params.extend(
+            values
+                .into_iter()
+                .map(|v| Value::Text(v.trim().to_string())),
+        );
+    };
+    any_of(
+        "id IN (SELECT tg.track_id FROM track_genres tg
+                  JOIN genres g ON g.id = tg.genre_id
+                 WHERE lower(g.name) IN ({}))",
+        &filter.genres,
+    );
+    any_of("lower(format) IN ({})", &filter.formats);
+
+    if filter.unrated {
+        clauses.push("rating IS NULL".to_string());
+    }
+    if filter.never_played {
+        clauses.push("id NOT IN (SELECT track_id FROM plays)".to_string());
+    }
+    if let Some(id) = filter.genre_id {
+        clauses.push("id IN (SELECT track_id FROM track_genres WHERE genre_id = ?)".to_string());
+        params.push(Value::Integer(id));
+    }
+    if let Some(id) = filter.artist_id {
+        clauses.push("artist_id = ?".to_string());
+        params.push(Value::Integer(id));
+    }
+    if let Some(id) = filter.album_id {
+        clauses.push("album_id = ?".to_string());
+        params.push(Value::Integer(id));
+    }
+
+    if clauses.is_empty() {
+        (String::new(), params)
+    } else {
+        (format!("WHERE {}", clauses.join(" AND ")), params)
+    }
+}
+
+/// ORDER BY clause. Missing values sort last in either direction; ties fall
+/// back to album order so equal keys stay grouped sensibly.
+fn order_by(sort: Option<TrackSort>) -> String {
+    let Some(sort) = sort else {
+        return TRACK_ALBUM_ORDER.to_string();
+    };
+    let column = match sort.field {
+        TrackSortField::Title => "title COLLATE NOCASE",
+        TrackSortField::Artist => "COALESCE(artist_sort, artist) COLLATE NOCASE",
+        TrackSortField::Album => "COALESCE(album_sort, album) COLLATE NOCASE",
+        TrackSortField::Year => "year",
+        TrackSortField::Genre => "genre COLLATE NOCASE",
+        TrackSortField::Format => "format",
+        TrackSortField::Duration => "duration_secs",
+        TrackSortField::SampleRate => "sample_rate",
+        TrackSortField::BitDepth => "bit_depth",
+        TrackSortField::Rating => "rating",
+        TrackSortField::FileSize => "file_size",
+        TrackSortField::Modified => "modified_at",
+        TrackSortField::Added => "added_at",
+        TrackSortField::Path => "path",
+    };
+    // "title COLLATE NOCASE" → "title" for the NULL check
+    let bare = column.trim_end_matches(" COLLATE NOCASE");
+    format!(
+        "{} IS NULL, {} {}, {}",
+        bare,
+        column,
+        if sort.descending { "DESC" } else { "ASC" },
+        TRACK_ALBUM_ORDER
+    )
+}
@@ -0,0 +1,111 @@
+//! Batch ReplayGain analysis.
+//!
+//! Measures the EBU R128 loudness of files, whole albums or the whole
+//! library and writes track and album gain/peak tags, so ReplayGain playback
+//! works without an external scanner. Album gain needs every track of the
+//! album, so selecting one library track analyses its whole album. Files
+//! outside the library (or without an album) get track values only.
+
+use parking_lot::Mutex;
+use std::time::Instant;
+
+use super::analysis::AnalysisGroup;
+use super::database::LibraryDb;
+use super::scanner::{JobProgress, JobSummary, ScanControl, PROGRESS_INTERVAL};
+use crate::audio::loudness::{self, TrackLoudness};
+use crate::audio::replaygain::ReplayGainInfo;
+use crate::metadata::replaygain::write_replaygain;
+
+/// Analyse each group and write its tags. Runs on the caller's thread;
+/// `on_progress` is called at most every [`PROGRESS_INTERVAL`]. An album
+/// with a file that fails to decode gets track values only.
+pub fn analyze_groups(
+    groups: &[AnalysisGroup],
+    control: &ScanControl,
+    on_progress: &mut dyn FnMut(&JobProgress),
+) -> JobSummary {
+    let total = groups.iter().map(|g| g.paths.len()).sum();
+    let mut summary = JobSummary::default();
+    let mut processed = 0;
+    let mut last_report = Instant::now();
+
+    for group in groups {
+        let mut measured: Vec<(&str, TrackLoudness)> = Vec::new();
+        let mut complete = true;
+        for path in &group.paths {
+            if control.is_cancelled() {
+                // Don't write a partly analysed album
+                summary.cancelled = true;
+                return summary;
+            }
+            if last_report.elapsed() >= PROGRESS_INTERVAL {
+                last_report = Instant::now();
+                on_progress(&JobProgress {
+                    total,
+                    processed,
+                    current_file: path.clone(),
+                });
+            }
+            processed += 1;
+            match loudness::analyze_file(path) {
+                Ok(track) => measured.push((path, track)),
+                Err(e) => {
+                    complete = false;
+                    summary.errors.push(format!("{}: {}", path, e));
+                }
+            }
+        }
+
+        let album = if group.album && complete {
+            match album_values(&measured) {
+                Ok(values) => Some(values),
+                Err(e) => {
+                    summary.errors.push(e);
+                    None
+                }
+            }
+        } else {
+            None
+        };
+        for (path, track) in &measured {
+            match write_track(path, track, album) {
+                Ok(()) => summary.processed += 1,
+                Err(e) => summary.errors.push(format!("{}: {}", path, e)),
+            }
+        }
+    }
+    summary
+}
+
+/// Album gain and peak.
+fn album_values(tracks: &[(&str, TrackLoudness)]) -> Result<(f32, f32), String> {
+    let loudness: Vec<&TrackLoudness> = tracks.iter().map(|(_, t)| t).collect();
+    let lufs = loudness::album_loudness_lufs(loudness.iter().copied())?;
+    let mut peak: f64 = 0.0;
+    for track in loudness {
+        peak = peak.max(track.peak()?);
+    }
+    Ok((loudness::gain_db(lufs) as f32, peak as f32))
+}
+
+fn write_track(path: &str, track: &TrackLoudness, album: Option<(f32, f32)>) -> Result<(), String> {
+    let info = ReplayGainInfo {
+        track_gain_db: Some(loudness::gain_db(track.loudness_lufs()?) as f32),
+        track_peak: Some(track.peak()? as f32),
+        album_gain_db: album.map(|(gain, _)| gain),
+        album_peak: album.map(|(_, peak)| peak),
+    };
+    write_replaygain(path, &info)
+}
+
+/// Analyse a selection on the caller's thread (see [`analyze_groups`]).
+pub fn run(
+    db: &Mutex<LibraryDb>,
+    paths: &[String],
+    album_ids: &[i64],
+    control: &ScanControl,
+    on_progress: &mut dyn FnMut(&JobProgress),
+) -> Result<JobSummary, String> {
+    let groups = db.lock().analysis_groups(paths, album_ids)?;
+    Ok(analyze_groups(&groups, control, on_progress))
+}
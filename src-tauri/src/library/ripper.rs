@@ -0,0 +1,634 @@
+//! Secure ripping of audio CDs to FLAC.
+//!
+//! Sectors are read in batches, each batch at least twice with a read at
+//! the far end of the disc in between, so the second pass comes from the
+//! disc rather than the drive's cache. A sector is settled once the same
+//! data has been read twice without C2 errors; unsettled sectors are read
+//! again, up to [`MAX_REREADS`] more times. Failing that, the version read
+//! most often is kept and the sector is reported as suspicious.
+//!
+//! Samples are shifted by the drive's read offset, so the rip can be
+//! checked against AccurateRip as it's read, and samples the drive can't
+//! reach (before the first sector or past the last) are silence, as in
+//! EAC. Tracks are tagged from the disc's MusicBrainz release, with its
+//! Cover Art Archive front cover, written as
+//! `<album artist>/<album>/<NN> - <title>.flac`, and added to the library
+//! when they land in a library folder.
+
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::time::Instant;
+
+use super::accuraterip::{self, AccurateRipDisc, Checksum, SECTOR_FRAMES};
+use super::cd_drive::{CdDrive, Toc, SECTOR_BYTES};
+use super::database::LibraryDb;
+use super::organize;
+use super::scanner::{self, JobProgress, ScanControl, PROGRESS_INTERVAL};
+use crate::audio::flac_writer::FlacWriter;
+use crate::http;
+use crate::metadata::cover;
+use crate::metadata::coverart::{self, ArtistCredit, CoverArtQuery, MUSICBRAINZ_RATE_LIMIT};
+use crate::metadata::writer::{self, MetadataFields};
+
+const MUSICBRAINZ_URL: &str = "https://musicbrainz.org/ws/2/discid/-";
+
+/// Sectors per read; with C2 pointers, just under 64 KiB, which every
+/// drive and SG_IO driver accepts.
+const BATCH_SECTORS: u32 = 24;
+
+/// Reads of a batch beyond the first two.
+const MAX_REREADS: u32 = 16;
+
+/// Sectors before the first track, which MusicBrainz tables of contents
+/// count.
+const LEAD_IN_SECTORS: u32 = 150;
+
+#[derive(Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct RipRequest {
+    /// Drive path, from [`super::cd_drive::drives`].
+    pub drive: String,
+    /// The drive's read offset in samples, as AccurateRip lists it.
+    pub read_offset: i32,
+    /// MusicBrainz release to tag the tracks from; the first match if
+    /// unset.
+    pub release_id: Option<String>,
+    /// Folder the album folder is created in; the first library folder if
+    /// unset.
+    pub destination: Option<String>,
+}
+
+/// A disc in a drive, with the releases MusicBrainz has for it.
+#[derive(Clone, Serialize)]
+pub struct CdDisc {
+    pub toc: Toc,
+    pub releases: Vec<DiscRelease>,
+}
+
+#[derive(Clone, Serialize)]
+pub struct DiscRelease {
+    /// MusicBrainz release id.
+    pub id: String,
+    pub title: String,
+    pub artist: String,
+    pub year: Option<u32>,
+    /// The disc's position, for releases of more than one disc.
+    pub disc_number: Option<u32>,
+    pub tracks: Vec<DiscReleaseTrack>,
+}
+
+#[derive(Clone, Serialize)]
+pub struct DiscReleaseTrack {
+    pub title: String,
+    pub artist: String,
+}
+
+#[derive(Clone, Serialize)]
+pub struct RippedTrack {
+    pub number: u32,
+    pub path: String,
+    pub title: String,
+    /// Batch reads beyond the first two.
+    pub rereads: u32,
+    /// Sectors that were never read the same way twice without C2 errors.
+    pub suspicious_sectors: Vec<u32>,
+}
+
+#[derive(Clone, Serialize)]
+pub struct RipSummary {
+    /// The album folder.
+    pub folder: String,
+    /// MusicBrainz release the tracks were tagged from.
+    pub release_id: Option<String>,
+    pub tracks: Vec<RippedTrack>,
+    /// `None` for discs with a data track, which can't be looked up.
+    pub accuraterip: Option<AccurateRipDisc>,
+    /// The drive reported C2 errors; without them only rereads catch
+    /// damage.
+    pub c2_supported: bool,
+    /// Added to the library.
+    pub imported: bool,
+    pub cancelled: bool,
+}
+
+#[derive(Deserialize)]
+struct DiscLookup {
+    #[serde(default)]
+    releases: Vec<MbRelease>,
+}
+
+#[derive(Deserialize)]
+struct MbRelease {
+    id: String,
+    title: String,
+    date: Option<String>,
+    #[serde(rename = "artist-credit", default)]
+    artist_credit: Vec<ArtistCredit>,
+    #[serde(default)]
+    media: Vec<MbMedium>,
+}
+
+#[derive(Deserialize)]
+struct MbMedium {
+    position: Option<u32>,
+    #[serde(default)]
+    tracks: Vec<MbTrack>,
+}
+
+#[derive(Deserialize)]
+struct MbTrack {
+    title: String,
+    #[serde(rename = "artist-credit", default)]
+    artist_credit: Vec<ArtistCredit>,
+}
+
+/// Read the disc in `drive` and look it up on MusicBrainz. A failed
+/// lookup is logged and leaves the releases empty.
+pub fn read_disc(drive: &str) -> Result<CdDisc, String> {
+    let toc = CdDrive::open(drive)?.toc()?;
+    let releases = lookup_releases(&toc).unwrap_or_else(|e| {
+        log::warn!("MusicBrainz disc lookup failed: {}", e);
+        Vec::new()
+    });
+    Ok(CdDisc { toc, releases })
+}
+
+/// Releases whose table of contents matches the disc's audio session, with
+/// the medium that has as many tracks as the disc.
+fn lookup_releases(toc: &Toc) -> Result<Vec<DiscRelease>, String> {
+    let audio: Vec<_> = toc.tracks.iter().filter(|t| t.audio).collect();
+    let (Some(first), Some(last)) = (audio.first(), audio.last()) else {
+        return Ok(Vec::new());
+    };
+    let mut parts = vec![
+        first.number,
+        last.number,
+        toc.audio_lead_out() + LEAD_IN_SECTORS,
+    ];
+    parts.extend(audio.iter().map(|t| t.start + LEAD_IN_SECTORS));
+    let toc_param = parts
+        .iter()
+        .map(u32::to_string)
+        .collect::<Vec<_>>()
+        .join(" ");
+
+    MUSICBRAINZ_RATE_LIMIT.wait();
+    let Some(found) = http::get_json_if_found::<DiscLookup>(
+        MUSICBRAINZ_URL,
+        &[
+            ("toc", &toc_param),
+            ("inc", "artist-credits recordings"),
+            ("fmt", "json"),
+        ],
+    )?
+    else {
+        return Ok(Vec::new());
+    };
+
+    Ok(found
+        .releases
+        .into_iter()
+        .filter_map(|release| {
+            let media_count = release.media.len();
+            let medium = release
+                .media
+                .into_iter()
+                .find(|m| m.tracks.len() == audio.len())?;
+            let artist = coverart::credit_name(&release.artist_credit);
+            Some(DiscRelease {
+                id: release.id,
+                title: release.title,
+                year: release
+                    .date
+                    .as_deref()
+                    .and_then(|d| d.get(..4))
+                    .and_then(|y| y.parse().ok()),
+                disc_number: medium.position.filter(|_| media_count > 1),
+                tracks: medium
+                    .tracks
+                    .iter()
+                    .map(|t| DiscReleaseTrack {
+                        title: t.title.clone(),
+                        artist: match coverart::credit_name(&t.artist_credit) {
+                            name if name.is_empty() => artist.clone(),
+                            name => name,
+                        },
+                    })
+                    .collect(),
+                artist,
+            })
+        })
+        .collect())
+}
+
+/// Rip every audio track of the disc. Runs on the caller's thread;
+/// `on_progress` counts sectors and is called at most every
+/// [`PROGRESS_INTERVAL`]. A cancelled rip keeps the tracks already
+/// finished.
+pub fn rip(
+    db: &Mutex<LibraryDb>,
+    request: &RipRequest,
+    control: &ScanControl,
+    on_progress: &mut dyn FnMut(&JobProgress),
+) -> Result<RipSummary, String> {
+    let mut drive = CdDrive::open(&request.drive)?;
+    let toc = drive.toc()?;
+    let audio: Vec<usize> = (0..toc.tracks.len())
+        .filter(|&i| toc.tracks[i].audio)
+        .collect();
+    if audio.is_empty() {
+        return Err("The disc has no audio tracks".to_string());
+    }
+    let disc_end = toc.audio_lead_out();
+
+    let releases = lookup_releases(&toc).unwrap_or_else(|e| {
+        log::warn!("MusicBrainz disc lookup failed: {}", e);
+        Vec::new()
+    });
+    let release = match &request.release_id {
+        Some(id) => releases.iter().find(|r| &r.id == id),
+        None => releases.first(),
+    };
+
+    let roots = db.lock().roots()?;
+    let destination = match &request.destination {
+        Some(destination) => PathBuf::from(destination),
+        None => roots
+            .iter()
+            .find(|r| r.enabled && r.online)
+            .map(|r| PathBuf::from(&r.path))
+            .ok_or("Choose a folder to rip to, or add a library folder")?,
+    };
+    let album_artist = release.map_or("Unknown Artist", |r| r.artist.as_str());
+    let album = release.map_or("Unknown Album", |r| r.title.as_str());
+    let folder = destination
+        .join(organize::sanitize(album_artist))
+        .join(organize::sanitize(album));
+    std::fs::create_dir_all(&folder)
+        .map_err(|e| format!("Failed to create {}: {}", folder.display(), e))?;
+    let imported = roots
+        .iter()
+        .any(|r| r.enabled && folder.starts_with(&r.path));
+
+    let cover_image = release.and_then(|r| match front_cover(&r.id) {
+        Ok(image) => image,
+        Err(e) => {
+            log::warn!("Failed to fetch cover art: {}", e);
+            None
+        }
+    });
+
+    let total: u32 = audio
+        .iter()
+        .map(|&i| toc.end_of(i) - toc.tracks[i].start)
+        .sum();
+    let mut progress = Progress {
+        total,
+        done: 0,
+        last: Instant::now(),
+        on_progress,
+        current_file: String::new(),
+    };
+    let mut summary = RipSummary {
+        folder: folder.to_string_lossy().to_string(),
+        release_id: release.map(|r| r.id.clone()),
+        tracks: Vec::new(),
+        accuraterip: None,
+        c2_supported: true,
+        imported,
+        cancelled: false,
+    };
+    let mut checksums = Vec::with_capacity(audio.len());
+
+    for (position, &index) in audio.iter().enumerate() {
+        let track = toc.tracks[index];
+        let tagged = release.and_then(|r| r.tracks.get(position));
+        let title =
+            tagged.map_or_else(|| format!("Track {:02}", track.number), |t| t.title.clone());
+        let path = folder.join(format!(
+            "{:02} - {}.flac",
+            track.number,
+            organize::sanitize(&title)
+        ));
+        let path_str = path.to_string_lossy().to_string();
+        progress.current_file = path_str.clone();
+
+        let mut output = TrackOutput {
+            writer: FlacWriter::create(&path, 44_100, 2, 16)?,
+            checksum: Checksum::new(position == 0),
+            samples: Vec::new(),
+        };
+        let mut stats = ReadStats::default();
+        let read = rip_track(
+            &mut drive,
+            RipSpan {
+                start: track.start,
+                end: toc.end_of(index),
+                disc_end,
+                read_offset: request.read_offset,
+            },
+            &mut output,
+            &mut stats,
+            control,
+            &mut progress,
+        );
+        summary.c2_supported = drive.c2_supported();
+        if let Err(e) = read {
+            // Leave no partial track behind
+            drop(output);
+            let _ = std::fs::remove_file(&path);
+            if control.is_cancelled() {
+                summary.cancelled = true;
+                break;
+            }
+            return Err(format!("Track {}: {}", track.number, e));
+        }
+        output.writer.finish()?;
+        checksums.push(
+            output
+                .checksum
+                .finish(position + 1 == audio.len(), &path_str)?,
+        );
+
+        let fields = MetadataFields {
+            title: Some(title.clone()),
+            artist: Some(tagged.map_or_else(|| album_artist.to_string(), |t| t.artist.clone())),
+            album: Some(album.to_string()),
+            album_artist: Some(album_artist.to_string()),
+            year: release.and_then(|r| r.year),
+            genre: None,
+            track_number: Some(track.number),
+            disc_number: release.and_then(|r| r.disc_number),
+        };
+        writer::write_metadata(&path_str, &fields)?;
+        if let Some(image) = &cover_image {
+            if let Err(e) = cover::embed_cover(&path_str, image) {
+                log::warn!("Failed to embed cover in {}: {}", path_str, e);
+            }
+        }
+        if imported {
+            scanner::import_file(db, &path_str)?;
+        }
+        summary.tracks.push(RippedTrack {
+            number: track.number,
+            path: path_str,
+            title,
+            rereads: stats.rereads,
+            suspicious_sectors: stats.suspicious,
+        });
+    }
+
+    if !summary.cancelled && !toc.has_data_track() {
+        let offsets: Vec<u64> = audio
+            .iter()
+            .map(|&i| u64::from(toc.tracks[i].start))
+            .collect();
+        let tracks: Vec<(String, Option<String>)> = summary
+            .tracks
+            .iter()
+            .map(|t| (t.path.clone(), Some(t.title.clone())))
+            .collect();
+        summary.accuraterip = Some(accuraterip::compare(
+            release.and_then(|r| r.disc_number),
+            &offsets,
+            u64::from(disc_end),
+            &tracks,
+            &checksums,
+        )?);
+    }
+    Ok(summary)
+}
+
+/// The release's front cover from the Cover Art Archive, if it has one.
+fn front_cover(release_id: &str) -> Result<Option<Vec<u8>>, String> {
+    let candidates = coverart::search(&CoverArtQuery {
+        release_id: Some(release_id.to_string()),
+        ..Default::default()
+    })?;
+    match candidates.into_iter().find(|c| c.front) {
+        Some(front) => http::get_bytes(&front.image_url).map(Some),
+        None => Ok(None),
+    }
+}
+
+struct Progress<'a> {
+    total: u32,
+    done: u32,
+    last: Instant,
+    on_progress: &'a mut dyn FnMut(&JobProgress),
+    current_file: String,
+}
+
+impl Progress<'_> {
+    fn advance(&mut self, sectors: u32) {
+        self.done += sectors;
+        if self.last.elapsed() >= PROGRESS_INTERVAL || self.done >= self.total {
+            self.last = Instant::now();
+            (self.on_progress)(&JobProgress {
+                total: self.total as usize,
+                processed: self.done as usize,
+                current_file: self.current_file.clone(),
+            });
+        }
+    }
+}
+
+/// A track's sectors on the disc.
+struct RipSpan {
+    start: u32,
+    end: u32,
+    /// End of the audio session.
+    disc_end: u32,
+    read_offset: i32,
+}
+
+#[derive(Default)]
+struct ReadStats {
+    rereads: u32,
+    suspicious: Vec<u32>,
+}
+
+struct TrackOutput {
+    writer: FlacWriter,
+    checksum: Checksum,
+    /// Reused buffer of samples for the writer.
+    samples: Vec<i32>,
+}
+
+impl TrackOutput {
+    /// Add 16-bit little-endian stereo frames.
+    fn push(&mut self, frames: &[u8]) -> Result<(), String> {
+        self.samples.clear();
+        for frame in frames.chunks_exact(4) {
+            let left = i16::from_le_bytes([frame[0], frame[1]]);
+            let right = i16::from_le_bytes([frame[2], frame[3]]);
+            self.checksum.push(accuraterip::cd_sample_word(left, right));
+            self.samples.push(i32::from(left));
+            self.samples.push(i32::from(right));
+        }
+        self.writer.write(&self.samples)
+    }
+
+    fn push_silence(&mut self, frames: u64) -> Result<(), String> {
+        let silence = [0u8; SECTOR_BYTES];
+        let mut left = frames as usize * 4;
+        while left > 0 {
+            let take = left.min(silence.len());
+            self.push(&silence[..take])?;
+            left -= take;
+        }
+        Ok(())
+    }
+}
+
+/// Read a track's frames, shifted by the read offset, into `output`.
+fn rip_track(
+    drive: &mut CdDrive,
+    span: RipSpan,
+    output: &mut TrackOutput,
+    stats: &mut ReadStats,
+    control: &ScanControl,
+    progress: &mut Progress,
+) -> Result<(), String> {
+    let sector_frames = SECTOR_FRAMES as i64;
+    let offset = i64::from(span.read_offset);
+    let end = i64::from(span.end) * sector_frames + offset;
+    let readable_end = end.min(i64::from(span.disc_end) * sector_frames);
+    let mut frame = i64::from(span.start) * sector_frames + offset;
+
+    // Before the first sector
+    if frame < 0 {
+        let silence = (-frame).min(end - frame);
+        output.push_silence(silence as u64)?;
+        frame += silence;
+    }
+    let mut reported = 0;
+    while frame < readable_end {
+        if control.is_cancelled() {
+            return Err("Cancelled".to_string());
+        }
+        let sector = frame / sector_frames;
+        let last_sector = (readable_end - 1) / sector_frames;
+        let count = (last_sector - sector + 1).min(i64::from(BATCH_SECTORS)) as u32;
+        let audio = read_secure(drive, sector as u32, count, span.disc_end, stats, control)?;
+
+        let skip = (frame - sector * sector_frames) as usize;
+        let take = (i64::from(count) * sector_frames - skip as i64).min(readable_end - frame);
+        output.push(&audio[skip * 4..(skip + take as usize) * 4])?;
+        frame += take;
+
+        let sectors_done =
+            ((frame - i64::from(span.start) * sector_frames - offset) / sector_frames) as u32;
+        progress.advance(sectors_done - reported);
+        reported = sectors_done;
+    }
+    // Past the last sector
+    if frame < end {
+        output.push_silence((end - frame) as u64)?;
+    }
+    progress.advance((span.end - span.start) - reported);
+    Ok(())
+}
+
+/// One way a sector has been read.
+struct Version {
+    audio: Vec<u8>,
+    /// Reads without C2 errors.
+    clean: u32,
+    /// Reads the drive flagged.
+    flagged: u32,
+}
+
+/// Read `count` sectors from `lba` until each is settled or the rereads
+/// run out, and return their audio.
+fn read_secure(
+    drive: &mut CdDrive,
+    lba: u32,
+    count: u32,
+    disc_end: u32,
+    stats: &mut ReadStats,
+    control: &ScanControl,
+) -> Result<Vec<u8>, String> {
+    let count = count as usize;
+    let mut versions: Vec<Vec<Version>> = (0..count).map(|_| Vec::new()).collect();
+    let mut settled = vec![false; count];
+    // Far from this batch, so reading it pushes the batch out of the cache
+    let far = if lba < disc_end / 2 {
+        disc_end.saturating_sub(1)
+    } else {
+        0
+    };
+    let mut last_error = None;
+
+    for pass in 0..2 + MAX_REREADS {
+        let Some(from) = settled.iter().position(|&s| !s) else {
+            break;
+        };
+        let to = settled.iter().rposition(|&s| !s).unwrap_or(from) + 1;
+        if control.is_cancelled() {
+            return Err("Cancelled".to_string());
+        }
+        if pass >= 2 {
+            stats.rereads += 1;
+        }
+        if pass > 0 {
+            let _ = drive.read(far, 1);
+        }
+        let read = match drive.read(lba + from as u32, (to - from) as u32) {
+            Ok(read) => read,
+            Err(e) => {
+                log::warn!(
+                    "Read of sectors {}+{} failed: {}",
+                    lba + from as u32,
+                    to - from,
+                    e
+                );
+                last_error = Some(e);
+                continue;
+            }
+        };
+        let sectors = read.audio.chunks_exact(SECTOR_BYTES).zip(&read.c2_errors);
+        for (i, (audio, &c2_error)) in sectors.enumerate() {
+            let index = from + i;
+            if settled[index] {
+                continue;
+            }
+            let known = versions[index].iter().position(|v| v.audio == audio);
+            let version = match known {
+                Some(k) => &mut versions[index][k],
+                None => {
+                    versions[index].push(Version {
+                        audio: audio.to_vec(),
+                        clean: 0,
+                        flagged: 0,
+                    });
+                    versions[index].last_mut().expect("just pushed")
+                }
+            };
+            if c2_error {
+                version.flagged += 1;
+            } else {
+                version.clean += 1;
+                settled[index] = version.clean >= 2;
+            }
+        }
+    }
+
+    let mut audio = Vec::with_capacity(count * SECTOR_BYTES);
+    for (i, versions) in versions.iter().enumerate() {
+        let sector = lba + i as u32;
+        let Some(best) = versions.iter().max_by_key(|v| (v.clean, v.flagged)) else {
+            return Err(format!(
+                "Sector {} couldn't be read: {}",
+                sector,
+                last_error.as_deref().unwrap_or("no data")
+            ));
+        };
+        if !settled[i] {
+            stats.suspicious.push(sector);
+        }
+        audio.extend_from_slice(&best.audio);
+    }
+    Ok(audio)
+}
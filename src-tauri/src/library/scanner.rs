@@ -1,37 +1,682 @@
-use std::path::Path;
-use walkdir::WalkDir;
+use parking_lot::Mutex;
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use super::artwork;
+use super::database::{dir_prefix, FileStats, LibraryDb, LibraryRoot};
+use super::exclude::ExcludeMatcher;
+use super::metadata_cache;
+use crate::metadata::cue::{self, CueSheet};
+use crate::metadata::reader::{self, TrackMetadata};
+use crate::paths::{self, ROOT_TIMEOUT};
 
 const AUDIO_EXTENSIONS: &[&str] = &[
     "flac", "mp3", "wav", "ogg", "m4a", "aac", "wma", "alac", "ape", "opus",
 ];
 
+/// Tracks are written to the database in batches of this size.
+const WRITE_BATCH: usize = 200;
+
+/// Minimum time between progress reports.
+pub(super) const PROGRESS_INTERVAL: Duration = Duration::from_millis(200);
+
+#[derive(Clone, Serialize, Default)]
+pub struct ScanSummary {
+    pub added: usize,
+    pub updated: usize,
+    pub removed: usize,
+    pub unchanged: usize,
+    /// Files left out by the exclude rules (files inside excluded folders
+    /// aren't counted — those folders aren't walked at all).
+    pub excluded: usize,
+    /// Files whose tags couldn't be read, with the reason.
+    pub errors: Vec<String>,
+    /// The scan was stopped by `cancel_scan`. Changes made up to that point are
+    /// kept, but missing files are not removed.
+    pub cancelled: bool,
+    /// Nothing was written; the counts and `changes` say what a scan would do.
+    pub dry_run: bool,
+    /// Every add, update and removal, listed by dry runs only.
+    pub changes: Vec<ScanChange>,
+}
+
+#[derive(Clone, Copy, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChangeKind {
+    Add,
+    Update,
+    Remove,
+}
+
+#[derive(Clone, Copy, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChangeReason {
+    /// Not in the library yet.
+    NewFile,
+    /// Size differs from the indexed file.
+    SizeChanged,
+    /// Same size, newer or older modification time.
+    Modified,
+    /// The file is gone.
+    Missing,
+    /// The file is still there but matches an exclude rule.
+    Excluded,
+    /// Shorter than the minimum duration.
+    TooShort,
+}
+
+#[derive(Clone, Serialize)]
+pub struct ScanChange {
+    pub path: String,
+    pub kind: ChangeKind,
+    pub reason: ChangeReason,
+}
+
+#[derive(Clone, Copy, Serialize)]
+pub enum ScanPhase {
+    /// Walking the folder tree.
+    Discovering,
+    /// Reading tags and writing the database.
+    Indexing,
+    /// Generating album thumbnails.
+    Artwork,
+}
+
+#[derive(Clone, Serialize)]
+pub struct ScanProgress {
+    pub phase: ScanPhase,
+    pub files_found: usize,
+    pub files_processed: usize,
+    pub current_folder: String,
+    pub tracks_per_sec: f64,
+}
+
+/// Progress of a per-file library job (waveforms, ReplayGain analysis).
+#[derive(Clone, Serialize)]
+pub struct JobProgress {
+    pub total: usize,
+    pub processed: usize,
+    pub current_file: String,
+}
+
+#[derive(Clone, Serialize, Default)]
+pub struct JobSummary {
+    /// Files handled successfully.
+    pub processed: usize,
+    /// Files that failed, with the reason.
+    pub errors: Vec<String>,
+    pub cancelled: bool,
+}
+
+/// Shared run/cancel flags for a single background library job (the scan,
+/// waveform generation, ReplayGain analysis).
+#[derive(Default)]
+pub struct ScanControl {
+    running: AtomicBool,
+    cancelled: AtomicBool,
+}
+
+impl ScanControl {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Claim the scanner. Returns false if a scan is already running.
+    pub fn try_start(&self) -> bool {
+        let started = self
+            .running
+            .compare_exchange(false, true, Ordering::SeqCst, Ordering::SeqCst)
+            .is_ok();
+        if started {
+            self.cancelled.store(false, Ordering::SeqCst);
+        }
+        started
+    }
+
+    pub fn finish(&self) {
+        self.running.store(false, Ordering::SeqCst);
+    }
+
+    /// Request cancellation. Returns false if no scan is running.
+    pub fn cancel(&self) -> bool {
+        self.cancelled.store(true, Ordering::SeqCst);
+        self.running.load(Ordering::SeqCst)
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.running.load(Ordering::SeqCst)
+    }
+
+    pub(super) fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+    }
+}
+
 /// Scan a directory recursively for audio files.
 pub fn scan_directory(path: &str) -> Vec<String> {
     let mut files = Vec::new();
 
     // Use simple recursive directory walk
-    scan_dir_recursive(Path::new(path), &mut files);
+    scan_dir_recursive(
+        Path::new(path),
+        &mut files,
+        &mut HashMap::new(),
+        &ExcludeMatcher::default(),
+        &mut 0,
+        &mut Vec::new(),
+        &mut |_, _| true,
+    );
 
+    files.retain(|f| !cue::is_cue_file(f));
     files.sort();
     files
 }
 
-fn scan_dir_recursive(dir: &Path, files: &mut Vec<String>) {
-    if let Ok(entries) = std::fs::read_dir(dir) {
-        for entry in entries.flatten() {
-            let path = entry.path();
-            if path.is_dir() {
-                scan_dir_recursive(&path, files);
-            } else if is_audio_file(&path) {
-                if let Some(path_str) = path.to_str() {
-                    files.push(path_str.to_string());
+/// Scan several library roots one after another, merging the results, then
+/// generate thumbnails for new albums. A `dry_run` does neither and only
+/// reports the changes.
+///
+/// Offline roots are skipped (and reported in `errors`) rather than scanned:
+/// an unmounted disk looks empty, which would otherwise remove all its tracks.
+pub fn scan_roots(
+    db: &Mutex<LibraryDb>,
+    roots: &[LibraryRoot],
+    exclude: &ExcludeMatcher,
+    dry_run: bool,
+    control: &ScanControl,
+    on_progress: &mut dyn FnMut(&ScanProgress),
+) -> Result<ScanSummary, String> {
+    let mut total = ScanSummary {
+        dry_run,
+        ..Default::default()
+    };
+    for root in roots {
+        if !root.online {
+            total
+                .errors
+                .push(format!("{}: library root is offline", root.path));
+            continue;
+        }
+        let summary = scan_into_library(db, &root.path, exclude, dry_run, control, on_progress)?;
+        total.added += summary.added;
+        total.updated += summary.updated;
+        total.removed += summary.removed;
+        total.unchanged += summary.unchanged;
+        total.excluded += summary.excluded;
+        total.errors.extend(summary.errors);
+        total.changes.extend(summary.changes);
+        if summary.cancelled {
+            total.cancelled = true;
+            break;
+        }
+        if dry_run {
+            continue;
+        }
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs() as i64)
+            .unwrap_or(0);
+        db.lock().mark_root_scanned(root.id, now)?;
+    }
+    if !total.cancelled && !dry_run {
+        artwork::generate_missing(db, control, on_progress)?;
+    }
+    Ok(total)
+}
+
+/// Bring the library database in sync with `root`: index new files, re-read
+/// files whose size or mtime changed, and drop files that no longer exist or
+/// are now excluded.
+///
+/// A cue sheet is indexed as one track per sheet track, with the path
+/// `Album.cue#03`, in place of the image file it splits.
+///
+/// Files in folders that couldn't be read are never dropped, and if the root
+/// stops answering mid-scan (a network share going away) nothing is dropped
+/// at all: the scan ends with an error instead.
+///
+/// A `dry_run` reads everything a real scan would, tags included, but
+/// writes nothing and lists each change it would make in `changes`.
+///
+/// Runs on the caller's thread; `on_progress` is called at most every
+/// [`PROGRESS_INTERVAL`]. The database lock is only held while reading/writing
+/// rows, not while tags are read from disk.
+pub fn scan_into_library(
+    db: &Mutex<LibraryDb>,
+    root: &str,
+    exclude: &ExcludeMatcher,
+    dry_run: bool,
+    control: &ScanControl,
+    on_progress: &mut dyn FnMut(&ScanProgress),
+) -> Result<ScanSummary, String> {
+    if !paths::is_dir_within(root, ROOT_TIMEOUT) {
+        return Err(format!("Library root is offline: {}", root));
+    }
+    let mut summary = ScanSummary {
+        dry_run,
+        ..Default::default()
+    };
+    let mut last_report = Instant::now();
+
+    // ── Discover ──
+    let mut files = Vec::new();
+    let mut walked = HashMap::new();
+    let mut excluded = 0;
+    let mut unreadable = Vec::new();
+    let finished = scan_dir_recursive(
+        Path::new(root),
+        &mut files,
+        &mut walked,
+        exclude,
+        &mut excluded,
+        &mut unreadable,
+        &mut |folder, found| {
+            if last_report.elapsed() >= PROGRESS_INTERVAL {
+                last_report = Instant::now();
+                on_progress(&ScanProgress {
+                    phase: ScanPhase::Discovering,
+                    files_found: found,
+                    files_processed: 0,
+                    current_folder: folder.to_string_lossy().to_string(),
+                    tracks_per_sec: 0.0,
+                });
+            }
+            !control.is_cancelled()
+        },
+    );
+    if control.is_cancelled() {
+        summary.cancelled = true;
+        return Ok(summary);
+    }
+    if !finished {
+        return Err(format!(
+            "Library root went offline during the scan: {}",
+            root
+        ));
+    }
+    summary.excluded = excluded;
+    summary.errors.extend(
+        unreadable
+            .iter()
+            .map(|(folder, e)| format!("{}: folder could not be read: {}", folder, e)),
+    );
+    let sheets = expand_cue_sheets(&mut files, &mut summary.errors);
+
+    // ── Index ──
+    let mut known = db.lock().file_stats_under(root)?;
+    // Indexed tracks that are below the minimum duration (it may have changed
+    // since they were added)
+    let short = if exclude.min_duration_secs() > 0.0 {
+        db.lock()
+            .short_tracks_under(root, exclude.min_duration_secs())?
+    } else {
+        Default::default()
+    };
+    // Indexed tracks to be removed for being too short
+    let mut too_short = HashSet::new();
+    let mut batch = Vec::with_capacity(WRITE_BATCH);
+    let mut offline = false;
+    let started = Instant::now();
+    let total = files.len();
+    let mut image_cache = None;
+
+    for (i, path) in files.iter().enumerate() {
+        if control.is_cancelled() {
+            summary.cancelled = true;
+            break;
+        }
+        if last_report.elapsed() >= PROGRESS_INTERVAL {
+            last_report = Instant::now();
+            on_progress(&ScanProgress {
+                phase: ScanPhase::Indexing,
+                files_found: total,
+                files_processed: i,
+                current_folder: Path::new(path)
+                    .parent()
+                    .map(|p| p.to_string_lossy().to_string())
+                    .unwrap_or_default(),
+                tracks_per_sec: i as f64 / started.elapsed().as_secs_f64().max(0.001),
+            });
+        }
+
+        let cue_track = cue::split_virtual_track_path(path).and_then(|(cue_path, number)| {
+            let sheet = sheets.get(cue_path)?;
+            let track = sheet.tracks.iter().find(|t| t.number == number)?;
+            Some((cue_path, sheet, track))
+        });
+        let stats = match cue_track {
+            Some((cue_path, _, track)) => {
+                cue_stats(cue_path, &track.file, |p| walked_stats(&walked, p))
+            }
+            None => walked_stats(&walked, path),
+        };
+        let Some(stats) = stats else {
+            // Keep the indexed track; the file was there a moment ago
+            known.remove(path);
+            if !paths::is_dir_within(root, ROOT_TIMEOUT) {
+                summary.errors.push(format!(
+                    "{}: library root went offline during the scan",
+                    root
+                ));
+                offline = true;
+                break;
+            }
+            summary
+                .errors
+                .push(format!("{}: file could not be read", path));
+            continue;
+        };
+        let previous = known.remove(path);
+        if previous == Some(stats) {
+            if short.contains(path) {
+                // Left in `known` so it gets removed below
+                known.insert(path.clone(), stats);
+                too_short.insert(path.clone());
+                summary.excluded += 1;
+            } else {
+                summary.unchanged += 1;
+            }
+            continue;
+        }
+
+        let meta = match cue_track {
+            Some((_, sheet, track)) => image_metadata(&mut image_cache, &track.file)
+                .map(|image| reader::cue_track_metadata(path, sheet, track, image)),
+            None => reader::read_metadata(path),
+        };
+        match meta {
+            Ok(meta) if exclude.too_short(meta.duration_secs) => {
+                if let Some(previous) = previous {
+                    known.insert(path.clone(), previous);
+                    too_short.insert(path.clone());
+                }
+                summary.excluded += 1;
+            }
+            Ok(meta) => {
+                let (kind, reason) = match previous {
+                    Some(previous) if previous.size != stats.size => {
+                        (ChangeKind::Update, ChangeReason::SizeChanged)
+                    }
+                    Some(_) => (ChangeKind::Update, ChangeReason::Modified),
+                    None => (ChangeKind::Add, ChangeReason::NewFile),
+                };
+                match kind {
+                    ChangeKind::Add => summary.added += 1,
+                    _ => summary.updated += 1,
+                }
+                if dry_run {
+                    summary.changes.push(ScanChange {
+                        path: path.clone(),
+                        kind,
+                        reason,
+                    });
+                } else {
+                    batch.push((meta, stats));
+                }
+            }
+            Err(e) => summary.errors.push(format!("{}: {}", path, e)),
+        }
+
+        if batch.len() >= WRITE_BATCH {
+            db.lock().upsert_tracks(&batch)?;
+            batch.clear();
+        }
+    }
+    if !batch.is_empty() {
+        db.lock().upsert_tracks(&batch)?;
+    }
+
+    // Anything left in `known` was indexed before but wasn't found (or is now
+    // excluded). A cancelled or interrupted scan didn't look at every file,
+    // and an unreadable folder hides its files, so neither can tell.
+    if !summary.cancelled && !offline {
+        let unreadable: Vec<String> = unreadable.iter().map(|(f, _)| dir_prefix(f)).collect();
+        let missing: Vec<String> = known
+            .into_keys()
+            .filter(|path| !unreadable.iter().any(|f| path.starts_with(f.as_str())))
+            .collect();
+        summary.removed = missing.len();
+        if dry_run {
+            summary.changes.extend(missing.into_iter().map(|path| {
+                let reason = if too_short.contains(&path) {
+                    ChangeReason::TooShort
+                } else if Path::new(&path).exists() {
+                    ChangeReason::Excluded
+                } else {
+                    ChangeReason::Missing
+                };
+                ScanChange {
+                    path,
+                    kind: ChangeKind::Remove,
+                    reason,
+                }
+            }));
+        } else if !missing.is_empty() {
+            db.lock().remove_tracks(&missing)?;
+        }
+    }
+
+    Ok(summary)
+}
+
+/// Re-read the tags of a library file edited outside a scan. Files that
+/// aren't in the library stay out of it.
+pub fn refresh_file(db: &Mutex<LibraryDb>, path: &str) -> Result<(), String> {
+    metadata_cache::forget(&db.lock(), path);
+    if db.lock().track_by_path(path)?.is_none() {
+        return Ok(());
+    }
+    let stats = match cue::find_virtual_track(path)? {
+        Some((_, track)) => cue::split_virtual_track_path(path)
+            .and_then(|(cue_path, _)| cue_stats(cue_path, &track.file, file_stats)),
+        None => file_stats(path),
+    }
+    .ok_or_else(|| format!("{}: file could not be read", path))?;
+    let meta = reader::read_metadata(path)?;
+    db.lock().upsert_tracks(&[(meta, stats)])
+}
+
+/// Add a file the app wrote into a library folder (a CD rip), without
+/// waiting for the next scan.
+pub fn import_file(db: &Mutex<LibraryDb>, path: &str) -> Result<(), String> {
+    metadata_cache::forget(&db.lock(), path);
+    let stats = file_stats(path).ok_or_else(|| format!("{}: file could not be read", path))?;
+    let meta = reader::read_metadata(path)?;
+    db.lock().upsert_tracks(&[(meta, stats)])
+}
+
+fn file_stats(path: &str) -> Option<FileStats> {
+    std::fs::metadata(path).ok().map(|meta| stats_from(&meta))
+}
+
+fn stats_from(meta: &std::fs::Metadata) -> FileStats {
+    let modified_at = meta
+        .modified()
+        .ok()
+        .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or(0);
+    FileStats {
+        size: meta.len() as i64,
+        modified_at,
+    }
+}
+
+/// A file's stats as the walk found them. Files outside the walk (a cue
+/// sheet's image in another folder) are read now, within [`ROOT_TIMEOUT`].
+fn walked_stats(walked: &HashMap<String, FileStats>, path: &str) -> Option<FileStats> {
+    if let Some(&stats) = walked.get(path) {
+        return Some(stats);
+    }
+    let path = path.to_string();
+    paths::with_timeout(ROOT_TIMEOUT, move || file_stats(&path))
+        .ok()
+        .flatten()
+}
+
+/// Change detection for a cue-sheet track: the image's size, and the newer
+/// of the sheet's and the image's modification times, so editing either
+/// re-reads the track.
+fn cue_stats(
+    cue_path: &str,
+    image: &str,
+    stats: impl Fn(&str) -> Option<FileStats>,
+) -> Option<FileStats> {
+    let sheet = stats(cue_path)?;
+    let image = stats(image)?;
+    Some(FileStats {
+        size: image.size,
+        modified_at: sheet.modified_at.max(image.modified_at),
+    })
+}
+
+/// Replace the cue sheets in `files` with their virtual track paths and drop
+/// the image files they split, so an image isn't also indexed as one long
+/// track. Returns the parsed sheets by path. Sheets that can't be read are
+/// reported in `errors`, and their images are indexed as plain files.
+fn expand_cue_sheets(
+    files: &mut Vec<String>,
+    errors: &mut Vec<String>,
+) -> HashMap<String, CueSheet> {
+    let mut sheets = HashMap::new();
+    let mut images = HashSet::new();
+    let mut expanded = Vec::with_capacity(files.len());
+    for path in files.drain(..) {
+        if !cue::is_cue_file(&path) {
+            expanded.push(path);
+            continue;
+        }
+        match cue::parse_cue_file(&path) {
+            Ok(sheet) => {
+                for track in &sheet.tracks {
+                    // Tracks whose audio file is gone have nothing to play
+                    if Path::new(&track.file).is_file() {
+                        images.insert(track.file.clone());
+                        expanded.push(cue::virtual_track_path(&path, track.number));
+                    }
+                }
+                sheets.insert(path, sheet);
+            }
+            Err(e) => errors.push(format!("{}: {}", path, e)),
+        }
+    }
+    expanded.retain(|path| !images.contains(path));
+    expanded.sort();
+    expanded.dedup();
+    *files = expanded;
+    sheets
+}
+
+/// Tags of a cue sheet's image file. Tracks of one image are scanned one
+/// after another, so only the last image is kept.
+fn image_metadata(
+    cache: &mut Option<(String, Result<TrackMetadata, String>)>,
+    image: &str,
+) -> Result<TrackMetadata, String> {
+    if let Some((path, meta)) = cache {
+        if path == image {
+            return meta.clone();
+        }
+    }
+    let meta = reader::read_metadata(image);
+    *cache = Some((image.to_string(), meta.clone()));
+    meta
+}
+
+/// Walk `dir`, calling `visit(folder, files_found_so_far)` for every folder.
+/// Returning false from `visit` stops the walk. Exclude rules apply to the
+/// entries below `dir`, never to `dir` itself. Folders that can't be listed
+/// are added to `unreadable` with the error, and the stats of the files
+/// found to `stats`.
+///
+/// Each folder is listed, and its entries stat'ed, within [`ROOT_TIMEOUT`].
+/// A folder that takes longer is skipped as unreadable; if its parent then
+/// doesn't answer either, the share has gone away and the walk stops,
+/// returning false.
+fn scan_dir_recursive(
+    dir: &Path,
+    files: &mut Vec<String>,
+    stats: &mut HashMap<String, FileStats>,
+    exclude: &ExcludeMatcher,
+    excluded: &mut usize,
+    unreadable: &mut Vec<(String, String)>,
+    visit: &mut dyn FnMut(&Path, usize) -> bool,
+) -> bool {
+    if !visit(dir, files.len()) {
+        return false;
+    }
+    let entries = match list_dir(dir) {
+        Ok(Ok(entries)) => entries,
+        Ok(Err(e)) => {
+            unreadable.push((dir.to_string_lossy().to_string(), e));
+            return true;
+        }
+        Err(e) => {
+            unreadable.push((dir.to_string_lossy().to_string(), e));
+            let parent = dir.parent().and_then(Path::to_str).unwrap_or_default();
+            return parent.is_empty() || paths::is_dir_within(parent, ROOT_TIMEOUT);
+        }
+    };
+    for entry in entries {
+        let path = entry.path;
+        if entry.is_dir {
+            if exclude.excludes_dir(&path) {
+                continue;
+            }
+            if !scan_dir_recursive(&path, files, stats, exclude, excluded, unreadable, visit) {
+                return false;
+            }
+        } else if is_audio_file(&path) || path.to_str().is_some_and(cue::is_cue_file) {
+            if exclude.excludes_file(&path) {
+                *excluded += 1;
+                continue;
+            }
+            if let Some(path_str) = path.to_str() {
+                if let Some(file_stats) = entry.stats {
+                    stats.insert(path_str.to_string(), file_stats);
                 }
+                files.push(path_str.to_string());
             }
         }
     }
+    true
+}
+
+struct DirEntry {
+    path: PathBuf,
+    is_dir: bool,
+    /// For files; `None` if they couldn't be stat'ed.
+    stats: Option<FileStats>,
+}
+
+/// The entries of `dir`, with their types and stats, as one call bounded by
+/// [`ROOT_TIMEOUT`]. The outer error is the timeout, the inner one the
+/// listing's.
+fn list_dir(dir: &Path) -> Result<Result<Vec<DirEntry>, String>, String> {
+    let dir = dir.to_path_buf();
+    paths::with_timeout(ROOT_TIMEOUT, move || {
+        let entries = std::fs::read_dir(&dir).map_err(|e| e.to_string())?;
+        Ok(entries
+            .flatten()
+            .map(|entry| {
+                let path = entry.path();
+                // Follows links, as `Path::is_dir` does
+                let meta = std::fs::metadata(&path).ok();
+                let is_dir = meta.as_ref().is_some_and(|m| m.is_dir());
+                DirEntry {
+                    stats: meta.filter(|_| !is_dir).map(|m| stats_from(&m)),
+                    path,
+                    is_dir,
+                }
+            })
+            .collect())
+    })
 }
 
-fn is_audio_file(path: &Path) -> bool {
+pub(super) fn is_audio_file(path: &Path) -> bool {
     path.extension()
         .and_then(|ext| ext.to_str())
         .map(|ext| AUDIO_EXTENSIONS.contains(&ext.to_lowercase().as_str()))
@@ -0,0 +1,255 @@
+//! Full-text search over the library.
+//!
+//! Backed by the `tracks_fts` FTS5 index (see `database.rs`). Every word of
+//! the query must match, as a prefix, one of title/artist/album/album artist/
+//! path; results are ranked with BM25, weighting title matches highest.
+//!
+//! Fuzzy search: a query word that isn't a prefix of any indexed term is
+//! replaced by the indexed terms within a small edit distance of it (so
+//! "raidohead" finds "radiohead"). Hits that only match through such a
+//! correction have their score scaled down by how close the correction was.
+
+use rusqlite::params;
+use serde::Serialize;
+use std::collections::HashSet;
+
+use super::database::{track_from_row, LibraryDb, LibraryTrack, TRACK_COLUMNS, TRACK_COLUMN_COUNT};
+
+/// Results returned when the caller doesn't pass a limit.
+pub const DEFAULT_SEARCH_LIMIT: usize = 100;
+
+/// BM25 column weights, in `tracks_fts` column order:
+/// title, artist, album, album_artist, path.
+const BM25_WEIGHTS: &str = "10.0, 6.0, 4.0, 3.0, 1.0";
+
+/// Corrections considered per misspelled word.
+const MAX_CORRECTIONS: usize = 8;
+
+#[derive(Clone, Serialize)]
+pub struct SearchHit {
+    pub track: LibraryTrack,
+    /// Relevance, higher is better. Only meaningful relative to other hits of
+    /// the same search.
+    pub score: f64,
+}
+
+/// A query word and, if it needed correcting, the indexed terms it was
+/// expanded to with their similarity (0–1).
+struct QueryWord {
+    word: String,
+    corrections: Vec<(String, f64)>,
+}
+
+impl LibraryDb {
+    pub fn search(&self, query: &str, limit: usize, fuzzy: bool) -> Result<Vec<SearchHit>, String> {
+        let words: Vec<String> = query
+            .split_whitespace()
+            .filter(|w| w.chars().any(char::is_alphanumeric))
+            .map(str::to_lowercase)
+            .collect();
+        if words.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let exact_query = words
+            .iter()
+            .map(|w| prefix_term(w))
+            .collect::<Vec<_>>()
+            .join(" AND ");
+        let mut hits = self.ranked_hits(&exact_query, limit)?;
+        if !fuzzy {
+            return Ok(hits);
+        }
+
+        let mut query_words = Vec::with_capacity(words.len());
+        for word in words {
+            let corrections = if self.has_prefix_match(&word)? {
+                Vec::new()
+            } else {
+                self.corrections(&word)?
+            };
+            query_words.push(QueryWord { word, corrections });
+        }
+        if query_words.iter().all(|w| w.corrections.is_empty()) {
+            return Ok(hits);
+        }
+
+        let fuzzy_query = query_words
+            .iter()
+            .map(|w| {
+                if w.corrections.is_empty() {
+                    prefix_term(&w.word)
+                } else {
+                    let alternatives: Vec<String> = std::iter::once(prefix_term(&w.word))
+                        .chain(w.corrections.iter().map(|(t, _)| quote(t)))
+                        .collect();
+                    format!("({})", alternatives.join(" OR "))
+                }
+            })
+            .collect::<Vec<_>>()
+            .join(" AND ");
+
+        let seen: HashSet<i64> = hits.iter().map(|h| h.track.id).collect();
+        for mut hit in self.ranked_hits(&fuzzy_query, limit)? {
+            if seen.contains(&hit.track.id) {
+                continue;
+            }
+            hit.score *= correction_factor(&hit.track, &query_words);
+            hits.push(hit);
+        }
+
+        hits.sort_by(|a, b| b.score.total_cmp(&a.score));
+        hits.truncate(limit);
+        Ok(hits)
+    }
+
+    fn ranked_hits(&self, fts_query: &str, limit: usize) -> Result<Vec<SearchHit>, String> {
+        let mut stmt = self
+            .conn
+            .prepare(&format!(
+                "SELECT {}, hits.rank FROM visible_tracks
+                   JOIN (SELECT rowid AS hit_id, bm25(tracks_fts, {}) AS rank
+                           FROM tracks_fts WHERE tracks_fts MATCH ?1) hits
+                     ON hits.hit_id = visible_tracks.id
+                  ORDER BY hits.rank
+                  LIMIT ?2",
+                TRACK_COLUMNS, BM25_WEIGHTS
+            ))
+            .map_err(|e| format!("Failed to search library: {}", e))?;
+        let rows = stmt
+            .query_map(params![fts_query, limit as i64], |row| {
+                let rank: f64 = row.get(TRACK_COLUMN_COUNT)?;
+                Ok(SearchHit {
+                    track: track_from_row(row)?,
+                    // bm25() is negative, more negative = better match
+                    score: -rank,
+                })
+            })
+            .map_err(|e| format!("Failed to search library: {}", e))?;
+        rows.collect::<Result<_, _>>()
+            .map_err(|e| format!("Failed to search library: {}", e))
+    }
+
+    fn has_prefix_match(&self, word: &str) -> Result<bool, String> {
+        self.conn
+            .query_row(
+                "SELECT EXISTS(SELECT 1 FROM tracks_fts_vocab
+                                WHERE substr(term, 1, length(?1)) = ?1)",
+                params![word],
+                |row| row.get(0),
+            )
+            .map_err(|e| format!("Failed to search library: {}", e))
+    }
+
+    /// Indexed terms within edit distance of `word`, closest first.
+    fn corrections(&self, word: &str) -> Result<Vec<(String, f64)>, String> {
+        let len = word.chars().count();
+        let max_distance = max_edit_distance(len);
+        if max_distance == 0 {
+            return Ok(Vec::new());
+        }
+
+        let mut stmt = self
+            .conn
+            .prepare("SELECT term FROM tracks_fts_vocab WHERE length(term) BETWEEN ?1 AND ?2")
+            .map_err(|e| format!("Failed to search library: {}", e))?;
+        let terms = stmt
+            .query_map(
+                params![
+                    len.saturating_sub(max_distance) as i64,
+                    (len + max_distance) as i64
+                ],
+                |row| row.get::<_, String>(0),
+            )
+            .map_err(|e| format!("Failed to search library: {}", e))?;
+
+        let mut found = Vec::new();
+        for term in terms {
+            let term = term.map_err(|e| format!("Failed to search library: {}", e))?;
+            let distance = edit_distance(word, &term);
+            if distance <= max_distance {
+                let similarity = 1.0 - distance as f64 / len.max(term.chars().count()) as f64;
+                found.push((term, similarity));
+            }
+        }
+        found.sort_by(|a, b| b.1.total_cmp(&a.1));
+        found.truncate(MAX_CORRECTIONS);
+        Ok(found)
+    }
+}
+
+/// Typos allowed for a word of `len` characters. Short words get none —
+/// "the" is one edit away from far too much.
+fn max_edit_distance(len: usize) -> usize {
+    match len {
+        0..=3 => 0,
+        4..=7 => 1,
+        _ => 2,
+    }
+}
+
+/// How much to trust a hit that matched through corrections: the product of
+/// the best similarity of each corrected word that the track actually contains.
+fn correction_factor(track: &LibraryTrack, words: &[QueryWord]) -> f64 {
+    let text = [
+        track.title.as_deref(),
+        track.artist.as_deref(),
+        track.album.as_deref(),
+        track.album_artist.as_deref(),
+        Some(track.path.as_str()),
+    ]
+    .iter()
+    .flatten()
+    .map(|s| s.to_lowercase())
+    .collect::<Vec<_>>()
+    .join(" ");
+    let tokens: Vec<&str> = text.split(|c: char| !c.is_alphanumeric()).collect();
+
+    words
+        .iter()
+        .filter(|w| !w.corrections.is_empty())
+        .map(|w| {
+            if tokens.iter().any(|t| t.starts_with(&w.word)) {
+                return 1.0;
+            }
+            w.corrections
+                .iter()
+                .filter(|(term, _)| tokens.contains(&term.as_str()))
+                .map(|(_, similarity)| *similarity)
+                .fold(0.0, f64::max)
+        })
+        .product()
+}
+
+/// Optimal string alignment distance: Levenshtein plus adjacent transpositions
+/// ("raido" → "radio" is one edit).
+fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    let mut prev2: Vec<usize> = vec![0; b.len() + 1];
+    let mut prev: Vec<usize> = (0..=b.len()).collect();
+    let mut cur: Vec<usize> = vec![0; b.len() + 1];
+
+    for i in 1..=a.len() {
+        cur[0] = i;
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            cur[j] = (prev[j] + 1).min(cur[j - 1] + 1).min(prev[j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                cur[j] = cur[j].min(prev2[j - 2] + 1);
+            }
+        }
+        std::mem::swap(&mut prev2, &mut prev);
+        std::mem::swap(&mut prev, &mut cur);
+    }
+    prev[b.len()]
+}
+
+/// A quoted prefix term (`"radio"*`), so user input is never parsed as FTS syntax.
+fn prefix_term(word: &str) -> String {
+    format!("{}*", quote(word))
+}
+
+fn quote(word: &str) -> String {
+    format!("\"{}\"", word.replace('"', "\"\""))
+}
@@ -0,0 +1,116 @@
+//! Library statistics: totals, a per-format breakdown and quality figures.
+
+use rusqlite::params_from_iter;
+use serde::Serialize;
+
+use super::database::LibraryDb;
+
+/// Formats (file extensions) that are always lossless. M4A is left out: it
+/// holds either ALAC or AAC.
+pub(super) const LOSSLESS_FORMATS: &[&str] =
+    &["FLAC", "WAV", "AIFF", "AIF", "APE", "WV", "DSF", "DFF"];
+
+#[derive(Clone, Serialize)]
+pub struct LibraryStats {
+    pub track_count: u32,
+    pub album_count: u32,
+    pub artist_count: u32,
+    pub genre_count: u32,
+    pub total_duration_secs: f64,
+    pub total_size_bytes: i64,
+    pub lossless_track_count: u32,
+    /// Tracks beyond CD quality: more than 16 bits or 48 kHz.
+    pub hires_track_count: u32,
+    /// Size over duration across the library, so tags and artwork count too.
+    pub average_bitrate_kbps: f64,
+    /// Largest share first.
+    pub formats: Vec<FormatStats>,
+}
+
+#[derive(Clone, Serialize)]
+pub struct FormatStats {
+    pub format: String,
+    pub track_count: u32,
+    pub total_duration_secs: f64,
+    pub total_size_bytes: i64,
+    pub average_bitrate_kbps: f64,
+}
+
+impl LibraryDb {
+    pub fn stats(&self) -> Result<LibraryStats, String> {
+        let placeholders = vec!["?"; LOSSLESS_FORMATS.len()].join(", ");
+        let mut stats = self
+            .conn
+            .query_row(
+                &format!(
+                    "SELECT COUNT(*),
+                            COUNT(DISTINCT album_id),
+                            COUNT(DISTINCT artist_id),
+                            (SELECT COUNT(DISTINCT tg.genre_id) FROM track_genres tg
+                              JOIN visible_tracks t ON t.id = tg.track_id),
+                            COALESCE(SUM(duration_secs), 0),
+                            COALESCE(SUM(file_size), 0),
+                            COUNT(*) FILTER (WHERE upper(format) IN ({})),
+                            COUNT(*) FILTER (WHERE bit_depth > 16 OR sample_rate > 48000)
+                       FROM visible_tracks",
+                    placeholders
+                ),
+                params_from_iter(LOSSLESS_FORMATS),
+                |row| {
+                    let total_duration_secs: f64 = row.get(4)?;
+                    let total_size_bytes: i64 = row.get(5)?;
+                    Ok(LibraryStats {
+                        track_count: row.get(0)?,
+                        album_count: row.get(1)?,
+                        artist_count: row.get(2)?,
+                        genre_count: row.get(3)?,
+                        total_duration_secs,
+                        total_size_bytes,
+                        lossless_track_count: row.get(6)?,
+                        hires_track_count: row.get(7)?,
+                        average_bitrate_kbps: bitrate_kbps(total_size_bytes, total_duration_secs),
+                        formats: Vec::new(),
+                    })
+                },
+            )
+            .map_err(|e| format!("Failed to query library stats: {}", e))?;
+        stats.formats = self.format_stats()?;
+        Ok(stats)
+    }
+
+    fn format_stats(&self) -> Result<Vec<FormatStats>, String> {
+        let mut stmt = self
+            .conn
+            .prepare(
+                "SELECT upper(format), COUNT(*),
+                        COALESCE(SUM(duration_secs), 0), COALESCE(SUM(file_size), 0)
+                   FROM visible_tracks
+                  GROUP BY upper(format)
+                  ORDER BY COUNT(*) DESC, upper(format)",
+            )
+            .map_err(|e| format!("Failed to query library stats: {}", e))?;
+        let rows = stmt
+            .query_map([], |row| {
+                let total_duration_secs: f64 = row.get(2)?;
+                let total_size_bytes: i64 = row.get(3)?;
+                Ok(FormatStats {
+                    format: row.get(0)?,
+                    track_count: row.get(1)?,
+                    total_duration_secs,
+                    total_size_bytes,
+                    average_bitrate_kbps: bitrate_kbps(total_size_bytes, total_duration_secs),
+                })
+            })
+            .map_err(|e| format!("Failed to query library stats: {}", e))?;
+        rows.collect::<Result<_, _>>()
+            .map_err(|e| format!("Failed to query library stats: {}", e))
+    }
+}
+
+pub(super) fn bitrate_kbps(size_bytes: i64, duration_secs: f64) -> f64 {
+    if duration_secs > 0.0 {
+        size_bytes as f64 * 8.0 / duration_secs / 1000.0
+    } else {
+        0.0
+    }
+}
@@ -0,0 +1,130 @@
+//! Lossy-transcode ("fake FLAC") detection.
+//!
+//! Lossless files are run through the spectral analysis in
+//! [`crate::audio::spectral`] and the cutoff frequency and confidence are
+//! stored on the track. DSD files are left out: their noise-shaped spectrum
+//! says nothing about a lossy source. A cue sheet's image is analysed once,
+//! and the result stored on each of its tracks. Results are kept across
+//! rescans; analysing a file again replaces them.
+
+use parking_lot::Mutex;
+use rusqlite::{params, params_from_iter};
+use std::time::Instant;
+
+use super::database::{LibraryDb, LibraryTrack, TRACK_COLUMNS};
+use super::query::Paging;
+use super::scanner::{JobProgress, JobSummary, ScanControl, PROGRESS_INTERVAL};
+use super::stats::LOSSLESS_FORMATS;
+use crate::audio::spectral::{self, SpectralReport};
+use crate::metadata::cue;
+
+/// Confidence from which a track is listed as a likely transcode.
+pub const DEFAULT_MIN_CONFIDENCE: f64 = 0.5;
+
+const DSD_FORMATS: &[&str] = &["DSF", "DFF"];
+
+impl LibraryDb {
+    /// Store a file's analysis result, on every cue-sheet track when it is
+    /// their image. Files outside the library are ignored.
+    pub fn store_spectral_report(
+        &mut self,
+        path: &str,
+        report: &SpectralReport,
+    ) -> Result<(), String> {
+        self.conn
+            .execute(
+                "UPDATE tracks SET spectral_cutoff_hz = ?2, transcode_confidence = ?3
+                  WHERE path = ?1 OR source_path = ?1",
+                params![path, report.cutoff_hz, f64::from(report.confidence)],
+            )
+            .map_err(|e| format!("Failed to store transcode analysis: {}", e))?;
+        Ok(())
+    }
+
+    /// Files of visible PCM lossless tracks that haven't been analysed.
+    /// Cue-sheet tracks give their image, once.
+    pub fn tracks_without_transcode_check(&self) -> Result<Vec<String>, String> {
+        let formats = pcm_lossless_formats();
+        let mut stmt = self
+            .conn
+            .prepare(&format!(
+                "SELECT DISTINCT COALESCE(source_path, path) AS file FROM visible_tracks
+                  WHERE transcode_confidence IS NULL AND upper(format) IN ({})
+                  ORDER BY file",
+                vec!["?"; formats.len()].join(", ")
+            ))
+            .map_err(|e| format!("Failed to query library: {}", e))?;
+        let rows = stmt
+            .query_map(params_from_iter(formats), |row| row.get(0))
+            .map_err(|e| format!("Failed to query library: {}", e))?;
+        rows.collect::<Result<_, _>>()
+            .map_err(|e| format!("Failed to query library: {}", e))
+    }
+
+    /// Analysed tracks with at least `min_confidence`, most suspect first.
+    pub fn suspected_transcodes(
+        &self,
+        min_confidence: f64,
+        paging: Paging,
+    ) -> Result<Vec<LibraryTrack>, String> {
+        self.query_tracks(
+            &format!(
+                "SELECT {} FROM visible_tracks
+                  WHERE transcode_confidence >= ?1
+                  ORDER BY transcode_confidence DESC, path{}",
+                TRACK_COLUMNS,
+                paging.sql()
+            ),
+            params![min_confidence],
+        )
+    }
+}
+
+fn pcm_lossless_formats() -> Vec<&'static str> {
+    LOSSLESS_FORMATS
+        .iter()
+        .copied()
+        .filter(|f| !DSD_FORMATS.contains(f))
+        .collect()
+}
+
+/// Analyse the given files, or every lossless library track not analysed
+/// yet when `paths` is empty. Runs on the caller's thread; `on_progress` is
+/// called at most every [`PROGRESS_INTERVAL`].
+pub fn run(
+    db: &Mutex<LibraryDb>,
+    paths: &[String],
+    control: &ScanControl,
+    on_progress: &mut dyn FnMut(&JobProgress),
+) -> Result<JobSummary, String> {
+    let pending = if paths.is_empty() {
+        db.lock().tracks_without_transcode_check()?
+    } else {
+        cue::audio_files(paths)
+    };
+    let mut summary = JobSummary::default();
+    let mut last_report = Instant::now();
+
+    for (i, path) in pending.iter().enumerate() {
+        if control.is_cancelled() {
+            summary.cancelled = true;
+            break;
+        }
+        if last_report.elapsed() >= PROGRESS_INTERVAL {
+            last_report = Instant::now();
+            on_progress(&JobProgress {
+                total: pending.len(),
+                processed: i,
+                current_file: path.clone(),
+            });
+        }
+        match spectral::analyze_file(path) {
+            Ok(report) => {
+                db.lock().store_spectral_report(path, &report)?;
+                summary.processed += 1;
+            }
+            Err(e) => summary.errors.push(format!("{}: {}", path, e)),
+        }
+    }
+    Ok(summary)
+}
@@ -0,0 +1,222 @@
+//! Waveform peaks for the seek bar.
+//!
+//! A track is decoded once into [`WAVEFORM_POINTS`] buckets holding the
+//! lowest and highest sample of all channels in that stretch of time. Peaks
+//! are stored in the `waveforms` table as one signed byte each (plenty for
+//! drawing) together with the file's mtime, so a changed file is decoded
+//! again. Tracks outside the library are decoded on every request. A
+//! cue-sheet track's waveform covers its segment of the image.
+
+use parking_lot::Mutex;
+use rusqlite::{params, OptionalExtension};
+use serde::Serialize;
+use std::path::Path;
+use std::time::Instant;
+
+use super::database::LibraryDb;
+use super::scanner::{JobProgress, JobSummary, ScanControl, PROGRESS_INTERVAL};
+use crate::audio::decoder::{AudioDecoder, DecodeStatus};
+use crate::metadata::cue;
+
+/// Buckets per waveform.
+pub const WAVEFORM_POINTS: usize = 1000;
+
+/// Frames per bucket while decoding when the duration is unknown; the
+/// buckets are merged down to [`WAVEFORM_POINTS`] at the end.
+const FALLBACK_BLOCK_FRAMES: usize = 1024;
+
+#[derive(Clone, Serialize)]
+pub struct Waveform {
+    /// Lowest sample per bucket, -1.0–0.0.
+    pub min: Vec<f32>,
+    /// Highest sample per bucket, 0.0–1.0.
+    pub max: Vec<f32>,
+}
+
+impl LibraryDb {
+    /// The stored waveform of a library track, if it's still current.
+    pub fn cached_waveform(&self, path: &str) -> Result<Option<Waveform>, String> {
+        let data: Option<Vec<u8>> = self
+            .conn
+            .query_row(
+                "SELECT w.peaks FROM waveforms w JOIN tracks t ON t.id = w.track_id
+                  WHERE t.path = ?1 AND w.modified_at = t.modified_at",
+                params![path],
+                |row| row.get(0),
+            )
+            .optional()
+            .map_err(|e| format!("Failed to query waveform: {}", e))?;
+        Ok(data.map(|d| decode_peaks(&d)))
+    }
+
+    /// Store a track's waveform. Files outside the library are ignored.
+    pub fn store_waveform(&mut self, path: &str, waveform: &Waveform) -> Result<(), String> {
+        self.conn
+            .execute(
+                "INSERT OR REPLACE INTO waveforms (track_id, modified_at, peaks)
+                 SELECT id, modified_at, ?2 FROM tracks WHERE path = ?1",
+                params![path, encode_peaks(waveform)],
+            )
+            .map_err(|e| format!("Failed to store waveform: {}", e))?;
+        Ok(())
+    }
+
+    /// Paths of visible tracks without a current waveform.
+    pub fn tracks_without_waveform(&self) -> Result<Vec<String>, String> {
+        let mut stmt = self
+            .conn
+            .prepare(
+                "SELECT t.path FROM visible_tracks t
+                   LEFT JOIN waveforms w ON w.track_id = t.id AND w.modified_at = t.modified_at
+                  WHERE w.track_id IS NULL
+                  ORDER BY t.path",
+            )
+            .map_err(|e| format!("Failed to query library: {}", e))?;
+        let rows = stmt
+            .query_map([], |row| row.get(0))
+            .map_err(|e| format!("Failed to query library: {}", e))?;
+        rows.collect::<Result<_, _>>()
+            .map_err(|e| format!("Failed to query library: {}", e))
+    }
+}
+
+/// Waveform of a file: from the library if stored, otherwise decoded (and
+/// stored for library tracks). The lock is not held while decoding.
+pub fn waveform(db: &Mutex<LibraryDb>, path: &str) -> Result<Waveform, String> {
+    if let Some(waveform) = db.lock().cached_waveform(path)? {
+        return Ok(waveform);
+    }
+    let waveform = compute_waveform(path)?;
+    db.lock().store_waveform(path, &waveform)?;
+    Ok(waveform)
+}
+
+/// Decode and store waveforms for every library track that lacks one.
+/// Runs on the caller's thread; `on_progress` is called at most every
+/// [`PROGRESS_INTERVAL`].
+pub fn generate_missing(
+    db: &Mutex<LibraryDb>,
+    control: &ScanControl,
+    on_progress: &mut dyn FnMut(&JobProgress),
+) -> Result<JobSummary, String> {
+    let pending = db.lock().tracks_without_waveform()?;
+    let mut summary = JobSummary::default();
+    let mut last_report = Instant::now();
+
+    for (i, path) in pending.iter().enumerate() {
+        if control.is_cancelled() {
+            summary.cancelled = true;
+            break;
+        }
+        if last_report.elapsed() >= PROGRESS_INTERVAL {
+            last_report = Instant::now();
+            on_progress(&JobProgress {
+                total: pending.len(),
+                processed: i,
+                current_file: path.clone(),
+            });
+        }
+        match compute_waveform(path) {
+            Ok(waveform) => {
+                db.lock().store_waveform(path, &waveform)?;
+                summary.processed += 1;
+            }
+            Err(e) => summary.errors.push(format!("{}: {}", path, e)),
+        }
+    }
+    Ok(summary)
+}
+
+/// Decode a whole file into min/max peaks, or for a cue-sheet track its
+/// segment of the image.
+pub fn compute_waveform(path: &str) -> Result<Waveform, String> {
+    let (file, start_secs, end_secs) = match cue::find_virtual_track(path)? {
+        Some((_, track)) => (track.file, track.start_secs, track.end_secs),
+        None => (path.to_string(), 0.0, None),
+    };
+    if !Path::new(&file).is_file() {
+        return Err(format!("File not found: {}", file));
+    }
+    let mut decoder = AudioDecoder::open(&file)?;
+    let channels = decoder.channels().max(1);
+    let rate = f64::from(decoder.sample_rate());
+    if start_secs > 0.0 {
+        decoder.seek(start_secs)?;
+    }
+    // Frames left of a segment that ends before the image does
+    let mut remaining = end_secs.map(|end| ((end - start_secs).max(0.0) * rate) as usize);
+
+    // Aim for a few blocks per final bucket so merging stays even
+    let expected_frames =
+        remaining.unwrap_or(((decoder.duration_secs - start_secs).max(0.0) * rate) as usize);
+    let block_frames = if expected_frames > 0 {
+        (expected_frames / (WAVEFORM_POINTS * 4)).max(1)
+    } else {
+        FALLBACK_BLOCK_FRAMES
+    };
+
+    let mut blocks: Vec<(f32, f32)> = Vec::new();
+    let (mut lo, mut hi, mut frames) = (0.0f32, 0.0f32, 0);
+    while remaining != Some(0) {
+        let mut samples = match decoder.next_samples() {
+            Ok(s) => s,
+            Err(DecodeStatus::EndOfStream) => break,
+            Err(DecodeStatus::Error(e)) => return Err(format!("Decode failed: {}", e)),
+        };
+        if let Some(left) = remaining.as_mut() {
+            let keep = (*left).min(samples.len() / channels);
+            samples.truncate(keep * channels);
+            *left -= keep;
+        }
+        for frame in samples.chunks(channels) {
+            for &s in frame {
+                lo = lo.min(s);
+                hi = hi.max(s);
+            }
+            frames += 1;
+            if frames == block_frames {
+                blocks.push((lo, hi));
+                (lo, hi, frames) = (0.0, 0.0, 0);
+            }
+        }
+    }
+    if frames > 0 {
+        blocks.push((lo, hi));
+    }
+
+    // Merge the blocks into the final buckets
+    let points = WAVEFORM_POINTS.min(blocks.len());
+    let mut waveform = Waveform {
+        min: Vec::with_capacity(points),
+        max: Vec::with_capacity(points),
+    };
+    for i in 0..points {
+        let bucket = &blocks[i * blocks.len() / points..(i + 1) * blocks.len() / points];
+        waveform
+            .min
+            .push(bucket.iter().map(|b| b.0).fold(0.0, f32::min).max(-1.0));
+        waveform
+            .max
+            .push(bucket.iter().map(|b| b.1).fold(0.0, f32::max).min(1.0));
+    }
+    Ok(waveform)
+}
+
+/// Interleaved min/max pairs, one signed byte each.
+fn encode_peaks(waveform: &Waveform) -> Vec<u8> {
+    waveform
+        .min
+        .iter()
+        .zip(&waveform.max)
+        .flat_map(|(&lo, &hi)| [lo, hi])
+        .map(|s| (s * 127.0).round() as i8 as u8)
+        .collect()
+}
+
+fn decode_peaks(data: &[u8]) -> Waveform {
+    let sample = |b: u8| f32::from(b as i8) / 127.0;
+    Waveform {
+        min: data.chunks_exact(2).map(|p| sample(p[0])).collect(),
+        max: data.chunks_exact(2).map(|p| sample(p[1])).collect(),
+    }
+}
@@ -0,0 +1,403 @@
+//! ListenBrainz submission: every play the play counter counts is sent to
+//! the user's ListenBrainz account as a listen, with the MusicBrainz ids in
+//! the file's tags when it has them.
+//!
+//! The user connects with the user token from their ListenBrainz settings
+//! page; it is checked with the API and stored in the app data directory.
+//! Submission can be switched off without forgetting the token, and is
+//! independent of other scrobbling services.
+//!
+//! Listens go through a queue saved next to the settings, so listens made
+//! offline, or while the token was refused, are sent later: a worker thread
+//! retries every few minutes and as soon as a new listen comes in. Listens
+//! the API rejects as invalid are dropped.
+
+use crossbeam_channel::{Receiver, RecvTimeoutError, Sender};
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use crate::audio::engine::PlaybackState;
+use crate::errors::{self, ErrorCode};
+use crate::http;
+use crate::library::database::LibraryDb;
+use crate::metadata::reader::{self, MusicBrainzIds};
+
+const API_URL: &str = "https://api.listenbrainz.org/1";
+
+const SETTINGS_FILE: &str = "listenbrainz.json";
+const QUEUE_FILE: &str = "listenbrainz_queue.json";
+
+/// Listens sent in one request (the API's limit).
+const MAX_BATCH: usize = 1000;
+
+/// Listens kept while they can't be sent; the oldest are dropped.
+const MAX_QUEUED: usize = 10_000;
+
+/// How often queued listens are retried.
+const RETRY_INTERVAL: Duration = Duration::from_secs(5 * 60);
+
+#[derive(Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+struct Settings {
+    enabled: bool,
+    token: Option<String>,
+    /// Account the token belongs to.
+    username: Option<String>,
+}
+
+#[derive(Clone, Serialize)]
+pub struct ListenBrainzStatus {
+    /// Listens are being submitted.
+    pub enabled: bool,
+    /// A token is stored.
+    pub connected: bool,
+    pub username: Option<String>,
+    /// Listens waiting to be sent.
+    pub queued: usize,
+    /// Why the last submission failed, until one succeeds.
+    pub last_error: Option<String>,
+}
+
+/// A listen as the API takes it.
+#[derive(Clone, Serialize, Deserialize)]
+struct Listen {
+    listened_at: i64,
+    track_metadata: TrackMetadata,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+struct TrackMetadata {
+    artist_name: String,
+    track_name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    release_name: Option<String>,
+    additional_info: AdditionalInfo,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+struct AdditionalInfo {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    recording_mbid: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    track_mbid: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    release_mbid: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    release_group_mbid: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty", default)]
+    artist_mbids: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tracknumber: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    duration_ms: Option<u64>,
+    media_player: String,
+    submission_client: String,
+    submission_client_version: String,
+}
+
+#[derive(Serialize)]
+struct Submission<'a> {
+    listen_type: &'static str,
+    payload: &'a [Listen],
+}
+
+#[derive(Deserialize)]
+struct TokenValidation {
+    valid: bool,
+    user_name: Option<String>,
+}
+
+/// A play handed over by the play counter.
+struct Played {
+    file: String,
+    start_secs: f64,
+    end_secs: Option<f64>,
+    duration_secs: f64,
+    listened_at: i64,
+}
+
+enum Job {
+    Listen(Played),
+    Retry,
+}
+
+pub struct ListenBrainz {
+    app_data_dir: PathBuf,
+    settings: Mutex<Settings>,
+    queue: Mutex<Vec<Listen>>,
+    last_error: Mutex<Option<String>>,
+    jobs: Sender<Job>,
+}
+
+impl ListenBrainz {
+    /// Load the settings and queue and start the submission thread.
+    pub fn spawn(app_data_dir: &Path, library: Arc<Mutex<LibraryDb>>) -> Arc<Self> {
+        let (jobs, rx) = crossbeam_channel::unbounded();
+        let listenbrainz = Arc::new(Self {
+            app_data_dir: app_data_dir.to_path_buf(),
+            settings: Mutex::new(load(&app_data_dir.join(SETTINGS_FILE))),
+            queue: Mutex::new(load(&app_data_dir.join(QUEUE_FILE))),
+            last_error: Mutex::new(None),
+            jobs,
+        });
+        let worker = listenbrainz.clone();
+        thread::Builder::new()
+            .name("listenbrainz".into())
+            .spawn(move || worker.run(rx, library))
+            .expect("Failed to spawn ListenBrainz thread");
+        listenbrainz
+    }
+
+    /// Submit a play counted at `listened_at` (Unix time), when enabled.
+    pub fn listened(&self, state: &PlaybackState, listened_at: i64) {
+        let Some(file) = state.current_file.clone() else {
+            return;
+        };
+        if !self.active() {
+            return;
+        }
+        let _ = self.jobs.send(Job::Listen(Played {
+            file,
+            start_secs: state.start_secs,
+            end_secs: state.end_secs,
+            duration_secs: state.duration_secs,
+            listened_at,
+        }));
+    }
+
+    pub fn status(&self) -> ListenBrainzStatus {
+        let settings = self.settings.lock();
+        ListenBrainzStatus {
+            enabled: settings.enabled,
+            connected: settings.token.is_some(),
+            username: settings.username.clone(),
+            queued: self.queue.lock().len(),
+            last_error: self.last_error.lock().clone(),
+        }
+    }
+
+    /// Check `token` with ListenBrainz, store it and start submitting.
+    /// Blocks on the network.
+    pub fn connect(&self, token: &str) -> Result<ListenBrainzStatus, String> {
+        let token = token.trim();
+        if token.is_empty() {
+            return Err("No ListenBrainz token given".to_string());
+        }
+        let validation: TokenValidation = http::get_json_with_headers(
+            &format!("{}/validate-token", API_URL),
+            &[("Authorization", &format!("Token {}", token))],
+        )?;
+        if !validation.valid {
+            return Err("ListenBrainz doesn't recognize this token".to_string());
+        }
+        {
+            let mut settings = self.settings.lock();
+            *settings = Settings {
+                enabled: true,
+                token: Some(token.to_string()),
+                username: validation.user_name,
+            };
+            self.save_settings(&settings)?;
+        }
+        *self.last_error.lock() = None;
+        let _ = self.jobs.send(Job::Retry);
+        Ok(self.status())
+    }
+
+    /// Forget the token and drop the listens still queued for the account.
+    pub fn disconnect(&self) -> Result<(), String> {
+        let mut settings = self.settings.lock();
+        *settings = Settings::default();
+        self.save_settings(&settings)?;
+        self.queue.lock().clear();
+        self.save_queue();
+        *self.last_error.lock() = None;
+        Ok(())
+    }
+
+    /// Switch submission on or off, keeping the token.
+    pub fn set_enabled(&self, enabled: bool) -> Result<(), String> {
+        let mut settings = self.settings.lock();
+        settings.enabled = enabled;
+        self.save_settings(&settings)?;
+        if enabled {
+            let _ = self.jobs.send(Job::Retry);
+        }
+        Ok(())
+    }
+
+    fn active(&self) -> bool {
+        let settings = self.settings.lock();
+        settings.enabled && settings.token.is_some()
+    }
+
+    fn run(&self, jobs: Receiver<Job>, library: Arc<Mutex<LibraryDb>>) {
+        loop {
+            match jobs.recv_timeout(RETRY_INTERVAL) {
+                Ok(Job::Listen(played)) => {
+                    if let Some(listen) = build_listen(&library, &played) {
+                        let mut queue = self.queue.lock();
+                        if queue.len() == MAX_QUEUED {
+                            queue.remove(0);
+                        }
+                        queue.push(listen);
+                        drop(queue);
+                        self.save_queue();
+                    }
+                }
+                Ok(Job::Retry) | Err(RecvTimeoutError::Timeout) => {}
+                Err(RecvTimeoutError::Disconnected) => return,
+            }
+            self.flush();
+        }
+    }
+
+    /// Send queued listens, oldest first, until the queue is empty or a
+    /// request fails.
+    fn flush(&self) {
+        loop {
+            let token = {
+                let settings = self.settings.lock();
+                match (&settings.token, settings.enabled) {
+                    (Some(token), true) => token.clone(),
+                    _ => return,
+                }
+            };
+            let batch: Vec<Listen> = {
+                let queue = self.queue.lock();
+                queue[..queue.len().min(MAX_BATCH)].to_vec()
+            };
+            if batch.is_empty() {
+                return;
+            }
+            let submission = Submission {
+                listen_type: if batch.len() == 1 { "single" } else { "import" },
+                payload: &batch,
+            };
+            let result: Result<serde_json::Value, _> = http::post_json(
+                &format!("{}/submit-listens", API_URL),
+                &[("Authorization", &format!("Token {}", token))],
+                &submission,
+            );
+            match result {
+                Ok(_) => *self.last_error.lock() = None,
+                Err(e) if e.status == Some(400) => {
+                    log::warn!(
+                        "ListenBrainz rejected {} listens: {}",
+                        batch.len(),
+                        e.message
+                    );
+                }
+                Err(e) => {
+                    log::warn!("ListenBrainz submission failed: {}", e.message);
+                    *self.last_error.lock() = Some(e.message);
+                    return;
+                }
+            }
+            // Only this thread takes listens off the front
+            let mut queue = self.queue.lock();
+            let sent = batch.len().min(queue.len());
+            queue.drain(..sent);
+            drop(queue);
+            self.save_queue();
+        }
+    }
+
+    fn save_settings(&self, settings: &Settings) -> Result<(), String> {
+        save(&self.app_data_dir, SETTINGS_FILE, settings)
+    }
+
+    fn save_queue(&self) {
+        let queue = self.queue.lock().clone();
+        if let Err(e) = save(&self.app_data_dir, QUEUE_FILE, &queue) {
+            errors::report(
+                ErrorCode::SaveFailed,
+                format!("Failed to save ListenBrainz queue: {}", e),
+            );
+        }
+    }
+}
+
+/// The listen for a play, from the file's tags; cue-sheet tracks are found
+/// in the library by their segment. `None` when the artist or title is
+/// unknown, which the API requires.
+fn build_listen(library: &Mutex<LibraryDb>, played: &Played) -> Option<Listen> {
+    let segment = played.start_secs > 0.0 || played.end_secs.is_some();
+    let path = if segment {
+        match library
+            .lock()
+            .track_by_segment(&played.file, played.start_secs)
+        {
+            Ok(Some(track)) => track.path,
+            Ok(None) => played.file.clone(),
+            Err(e) => {
+                log::warn!("{}", e);
+                played.file.clone()
+            }
+        }
+    } else {
+        played.file.clone()
+    };
+
+    let meta = match reader::read_metadata(&path) {
+        Ok(meta) => meta,
+        Err(e) => {
+            log::warn!("No listen for {}: {}", path, e);
+            return None;
+        }
+    };
+    let ids = reader::read_musicbrainz_ids(&played.file).unwrap_or_default();
+    // A cue sheet's audio file is tagged for the whole release
+    let ids = if segment {
+        MusicBrainzIds {
+            release_id: ids.release_id,
+            release_group_id: ids.release_group_id,
+            ..MusicBrainzIds::default()
+        }
+    } else {
+        ids
+    };
+
+    let duration_secs = if meta.duration_secs > 0.0 {
+        meta.duration_secs
+    } else {
+        played.duration_secs
+    };
+    Some(Listen {
+        listened_at: played.listened_at,
+        track_metadata: TrackMetadata {
+            artist_name: meta.artist?,
+            track_name: meta.title?,
+            release_name: meta.album,
+            additional_info: AdditionalInfo {
+                recording_mbid: ids.recording_id,
+                track_mbid: ids.track_id,
+                release_mbid: ids.release_id,
+                release_group_mbid: ids.release_group_id,
+                artist_mbids: ids.artist_ids,
+                tracknumber: meta.track_number,
+                duration_ms: (duration_secs > 0.0).then_some((duration_secs * 1000.0) as u64),
+                media_player: "Masukii".to_string(),
+                submission_client: "Masukii".to_string(),
+                submission_client_version: env!("CARGO_PKG_VERSION").to_string(),
+            },
+        },
+    })
+}
+
+fn load<T: Default + serde::de::DeserializeOwned>(path: &Path) -> T {
+    std::fs::read_to_string(path)
+        .ok()
+        .and_then(|data| serde_json::from_str(&data).ok())
+        .unwrap_or_default()
+}
+
+fn save<T: Serialize>(app_data_dir: &Path, file: &str, value: &T) -> Result<(), String> {
+    std::fs::create_dir_all(app_data_dir).map_err(|e| format!("Failed to create dir: {}", e))?;
+    let json = serde_json::to_string(value).map_err(|e| format!("Serialize failed: {}", e))?;
+    std::fs::write(app_data_dir.join(file), json).map_err(|e| format!("Write failed: {}", e))
+}
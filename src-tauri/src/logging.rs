@@ -0,0 +1,284 @@
+//! Logging: `env_logger` output (filtered by `RUST_LOG`, info by default),
+//! plus records at the app's log level and above written to a rotating file
+//! in `logs/` under the app data directory and kept in memory, so they can
+//! go into bug reports even when nobody was watching the console.
+//!
+//! The file is `masukii.log`; when it passes [`MAX_FILE_BYTES`] it becomes
+//! `masukii.1.log`, the old `.1` becomes `.2` and so on, keeping
+//! [`KEEP_FILES`] old files.
+
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::OnceLock;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use log::{Level, LevelFilter, Log, Metadata, Record};
+
+/// Records kept in memory; older ones are dropped.
+const RECENT_LEN: usize = 1000;
+
+const LOG_DIR: &str = "logs";
+const FILE_STEM: &str = "masukii";
+
+pub const MAX_FILE_BYTES: u64 = 2 * 1024 * 1024;
+pub const KEEP_FILES: usize = 3;
+
+/// Lowest level written to the file and kept in memory.
+#[derive(Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum LogLevel {
+    Error,
+    Warn,
+    #[default]
+    Info,
+    Debug,
+    Trace,
+}
+
+impl LogLevel {
+    fn filter(self) -> LevelFilter {
+        match self {
+            LogLevel::Error => LevelFilter::Error,
+            LogLevel::Warn => LevelFilter::Warn,
+            LogLevel::Info => LevelFilter::Info,
+            LogLevel::Debug => LevelFilter::Debug,
+            LogLevel::Trace => LevelFilter::Trace,
+        }
+    }
+}
+
+#[derive(Clone, Serialize)]
+pub struct LogEntry {
+    /// Unix time in milliseconds.
+    pub timestamp_ms: u64,
+    pub level: String,
+    /// Module the record came from.
+    pub target: String,
+    pub message: String,
+}
+
+struct LogFile {
+    dir: PathBuf,
+    out: Option<File>,
+    len: u64,
+}
+
+impl LogFile {
+    fn path(&self, index: usize) -> PathBuf {
+        match index {
+            0 => self.dir.join(format!("{}.log", FILE_STEM)),
+            n => self.dir.join(format!("{}.{}.log", FILE_STEM, n)),
+        }
+    }
+
+    fn open(&mut self) {
+        if let Err(e) = std::fs::create_dir_all(&self.dir) {
+            eprintln!("Failed to create {}: {}", self.dir.display(), e);
+            return;
+        }
+        let path = self.path(0);
+        match std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+        {
+            Ok(file) => {
+                self.len = file.metadata().map(|m| m.len()).unwrap_or(0);
+                self.out = Some(file);
+            }
+            Err(e) => eprintln!("Failed to open {}: {}", path.display(), e),
+        }
+    }
+
+    fn rotate(&mut self) {
+        self.out = None;
+        let _ = std::fs::remove_file(self.path(KEEP_FILES));
+        for index in (0..KEEP_FILES).rev() {
+            let _ = std::fs::rename(self.path(index), self.path(index + 1));
+        }
+        self.open();
+    }
+
+    fn write(&mut self, line: &str) {
+        if self.len >= MAX_FILE_BYTES {
+            self.rotate();
+        }
+        if let Some(file) = &mut self.out {
+            if file.write_all(line.as_bytes()).is_ok() {
+                self.len += line.len() as u64;
+            }
+        }
+    }
+}
+
+struct Logger {
+    console: env_logger::Logger,
+    /// A [`LevelFilter`] as `usize`.
+    level: AtomicUsize,
+    /// None when logging to the console only.
+    file: Mutex<Option<LogFile>>,
+    recent: Mutex<VecDeque<LogEntry>>,
+}
+
+impl Logger {
+    fn level(&self) -> LevelFilter {
+        match self.level.load(Ordering::Relaxed) {
+            0 => LevelFilter::Off,
+            1 => LevelFilter::Error,
+            2 => LevelFilter::Warn,
+            3 => LevelFilter::Info,
+            4 => LevelFilter::Debug,
+            _ => LevelFilter::Trace,
+        }
+    }
+}
+
+static LOGGER: OnceLock<Logger> = OnceLock::new();
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= self.level() || self.console.enabled(metadata)
+    }
+
+    fn log(&self, record: &Record) {
+        if self.console.matches(record) {
+            self.console.log(record);
+        }
+        if record.level() > self.level() {
+            return;
+        }
+        let entry = LogEntry {
+            timestamp_ms: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_millis() as u64)
+                .unwrap_or(0),
+            level: record.level().to_string(),
+            target: record.target().to_string(),
+            message: record.args().to_string(),
+        };
+        if let Some(file) = &mut *self.file.lock() {
+            file.write(&format!(
+                "{} {:<5} {}: {}\n",
+                format_utc(entry.timestamp_ms),
+                entry.level,
+                entry.target,
+                entry.message
+            ));
+        }
+        let mut recent = self.recent.lock();
+        if recent.len() == RECENT_LEN {
+            recent.pop_front();
+        }
+        recent.push_back(entry);
+    }
+
+    fn flush(&self) {
+        self.console.flush();
+        if let Some(file) = self.file.lock().as_mut().and_then(|f| f.out.as_mut()) {
+            let _ = file.flush();
+        }
+    }
+}
+
+/// Install the logger, writing files to `logs/` under `app_data_dir` if
+/// given. Call once, at startup.
+pub fn init(app_data_dir: Option<&Path>) {
+    let console =
+        env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info")).build();
+    let logger = LOGGER.get_or_init(|| {
+        let file = app_data_dir.map(|dir| {
+            let mut file = LogFile {
+                dir: dir.join(LOG_DIR),
+                out: None,
+                len: 0,
+            };
+            file.open();
+            file
+        });
+        Logger {
+            console,
+            level: AtomicUsize::new(LogLevel::default().filter() as usize),
+            file: Mutex::new(file),
+            recent: Mutex::new(VecDeque::new()),
+        }
+    });
+    if log::set_logger(logger).is_ok() {
+        update_max_level(logger);
+    }
+}
+
+/// Change what's written to the file and kept in memory. The console still
+/// follows `RUST_LOG`.
+pub fn set_level(level: LogLevel) {
+    if let Some(logger) = LOGGER.get() {
+        logger
+            .level
+            .store(level.filter() as usize, Ordering::Relaxed);
+        update_max_level(logger);
+    }
+}
+
+fn update_max_level(logger: &Logger) {
+    log::set_max_level(logger.console.filter().max(logger.level()));
+}
+
+/// Where log files go.
+pub fn log_dir() -> Option<PathBuf> {
+    LOGGER.get()?.file.lock().as_ref().map(|f| f.dir.clone())
+}
+
+/// Recent records, oldest first.
+pub fn recent() -> Vec<LogEntry> {
+    LOGGER
+        .get()
+        .map(|l| l.recent.lock().iter().cloned().collect())
+        .unwrap_or_default()
+}
+
+/// The last `count` recent records at `level` or above, oldest first.
+pub fn recent_at(level: LogLevel, count: usize) -> Vec<LogEntry> {
+    let Some(logger) = LOGGER.get() else {
+        return Vec::new();
+    };
+    let recent = logger.recent.lock();
+    let mut entries: Vec<LogEntry> = recent
+        .iter()
+        .rev()
+        .filter(|e| e.level.parse::<Level>().is_ok_and(|l| l <= level.filter()))
+        .take(count)
+        .cloned()
+        .collect();
+    entries.reverse();
+    entries
+}
+
+/// `2026-01-31T12:00:00.000Z`.
+fn format_utc(timestamp_ms: u64) -> String {
+    let secs = timestamp_ms / 1000;
+    let (days, rest) = ((secs / 86_400) as i64, secs % 86_400);
+    // Days since 1970-01-01 to a civil date (Howard Hinnant's algorithm)
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:03}Z",
+        year,
+        month,
+        day,
+        rest / 3600,
+        rest % 3600 / 60,
+        rest % 60,
+        timestamp_ms % 1000
+    )
+}
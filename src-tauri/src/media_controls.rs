@@ -0,0 +1,264 @@
+//! OS media controls, through the `souvlaki` crate: the System Media
+//! Transport Controls on Windows (media keys, the volume flyout's
+//! now-playing card, Bluetooth AVRCP buttons) and `MPNowPlayingInfoCenter`
+//! with `MPRemoteCommandCenter` on macOS (Control Center, AirPods, media
+//! keys). Their buttons control playback, and the OS is shown the playing
+//! track's title, artist, album, cover and position.
+//!
+//! A thread owns the controls. It watches the engine and pushes metadata
+//! when the track changes and the playback status when it changes, on seeks
+//! and every few seconds while playing. Button presses act on the engine and
+//! queue directly, like the matching commands, and are also emitted as
+//! `media://control` so the UI can refresh.
+
+use serde::Serialize;
+use souvlaki::{
+    MediaControlEvent, MediaControls, MediaMetadata, MediaPlayback, MediaPosition, PlatformConfig,
+    SeekDirection,
+};
+use std::path::Path;
+use std::thread;
+use std::time::{Duration, Instant, UNIX_EPOCH};
+use tauri::{AppHandle, Emitter, Manager};
+
+use crate::audio::engine::{AudioCommand, PlaybackState};
+use crate::commands::{self, AppState};
+use crate::library::{artwork, metadata_cache};
+
+/// How often the engine is checked for changes.
+const SYNC_INTERVAL: Duration = Duration::from_millis(250);
+
+/// How often the position is pushed while playing; the OS runs the clock
+/// in between.
+const POSITION_INTERVAL: Duration = Duration::from_secs(5);
+
+/// A position this far from where the clock should be is a seek.
+const SEEK_THRESHOLD_SECS: f64 = 1.5;
+
+/// Skip for the plain seek buttons.
+const SEEK_STEP: Duration = Duration::from_secs(10);
+
+/// Payload of `media://control`.
+#[derive(Clone, Copy, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MediaAction {
+    Play,
+    Pause,
+    Stop,
+    Next,
+    Previous,
+    Seek,
+}
+
+/// Register with the OS and start the thread. Logs and gives up when the
+/// controls (or, on Windows, the main window they attach to) aren't
+/// available.
+pub fn spawn(app: &AppHandle) {
+    #[cfg(windows)]
+    let hwnd = match app.get_webview_window("main").map(|w| w.hwnd()) {
+        Some(Ok(hwnd)) => Some(hwnd.0 as usize),
+        Some(Err(e)) => {
+            log::warn!("Media controls unavailable: {}", e);
+            return;
+        }
+        None => return,
+    };
+    #[cfg(not(windows))]
+    let hwnd: Option<usize> = None;
+    let app = app.clone();
+    thread::Builder::new()
+        .name("media-controls".into())
+        .spawn(move || {
+            let config = PlatformConfig {
+                dbus_name: "masukii",
+                display_name: "Masukii",
+                hwnd: hwnd.map(|h| h as *mut std::ffi::c_void),
+            };
+            let mut controls = match MediaControls::new(config) {
+                Ok(c) => c,
+                Err(e) => {
+                    log::warn!("Media controls unavailable: {:?}", e);
+                    return;
+                }
+            };
+            let handler = app.clone();
+            if let Err(e) = controls.attach(move |event| on_event(&handler, event)) {
+                log::warn!("Media controls unavailable: {:?}", e);
+                return;
+            }
+            sync(&app, &mut controls);
+        })
+        .expect("Failed to spawn media controls thread");
+}
+
+/// Keep the OS up to date with the engine. Never returns.
+fn sync(app: &AppHandle, controls: &mut MediaControls) {
+    let state = app.state::<AppState>();
+    // (file, segment start) whose metadata is shown
+    let mut shown: Option<(String, f64)> = None;
+    // Status and position last pushed, and when
+    let mut pushed: Option<(bool, bool, f64)> = None;
+    let mut pushed_at = Instant::now();
+
+    loop {
+        thread::sleep(SYNC_INTERVAL);
+        let playback = state.engine.get_state();
+
+        let track = playback
+            .current_file
+            .clone()
+            .map(|f| (f, playback.start_secs));
+        if track != shown {
+            let result = match &track {
+                Some(_) => push_metadata(app, &playback, controls),
+                None => controls.set_metadata(MediaMetadata::default()),
+            };
+            if let Err(e) = result {
+                log::warn!("Failed to update media controls: {:?}", e);
+            }
+            shown = track;
+            pushed = None;
+        }
+
+        let position = playback.position_secs;
+        let due = match pushed {
+            Some((playing, paused, at)) => {
+                let expected = if playing {
+                    at + pushed_at.elapsed().as_secs_f64()
+                } else {
+                    at
+                };
+                playing != playback.is_playing
+                    || paused != playback.is_paused
+                    || (position - expected).abs() > SEEK_THRESHOLD_SECS
+                    || (playing && pushed_at.elapsed() >= POSITION_INTERVAL)
+            }
+            None => true,
+        };
+        if !due {
+            continue;
+        }
+        let progress = Some(MediaPosition(Duration::from_secs_f64(position.max(0.0))));
+        let status = if playback.is_playing {
+            MediaPlayback::Playing { progress }
+        } else if playback.is_paused {
+            MediaPlayback::Paused { progress }
+        } else {
+            MediaPlayback::Stopped
+        };
+        if let Err(e) = controls.set_playback(status) {
+            log::warn!("Failed to update media controls: {:?}", e);
+        }
+        pushed = Some((playback.is_playing, playback.is_paused, position));
+        pushed_at = Instant::now();
+    }
+}
+
+/// Title, artist, album, cover and length of the playing track.
+fn push_metadata(
+    app: &AppHandle,
+    playback: &PlaybackState,
+    controls: &mut MediaControls,
+) -> Result<(), souvlaki::Error> {
+    let Some(file) = playback.current_file.as_deref() else {
+        return Ok(());
+    };
+    let state = app.state::<AppState>();
+    let tags = metadata_cache::metadata(&state.library, file).ok();
+
+    // Cue-sheet tracks share one audio file; their title is in the queue
+    let queued_title = state.queue.lock().current().and_then(|entry| {
+        (entry.path == file && entry.start_secs == playback.start_secs)
+            .then(|| entry.title.clone())
+            .flatten()
+    });
+    let title = queued_title
+        .or_else(|| tags.as_ref().and_then(|t| t.title.clone()))
+        .or_else(|| {
+            Path::new(file)
+                .file_stem()
+                .map(|s| s.to_string_lossy().to_string())
+        });
+
+    let modified_at = std::fs::metadata(file)
+        .and_then(|m| m.modified())
+        .ok()
+        .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or(0);
+    let thumbnail_dir = state.library.lock().thumbnail_dir().to_path_buf();
+    let cover_url = artwork::ensure_thumbnail(&thumbnail_dir, file, modified_at)
+        .ok()
+        .flatten()
+        .map(|path| format!("file://{}", path));
+
+    controls.set_metadata(MediaMetadata {
+        title: title.as_deref(),
+        artist: tags.as_ref().and_then(|t| t.artist.as_deref()),
+        album: tags.as_ref().and_then(|t| t.album.as_deref()),
+        cover_url: cover_url.as_deref(),
+        duration: Some(Duration::from_secs_f64(playback.duration_secs.max(0.0))),
+    })
+}
+
+fn on_event(app: &AppHandle, event: MediaControlEvent) {
+    let state = app.state::<AppState>();
+    let engine = &state.engine;
+    let action = match event {
+        MediaControlEvent::Play => {
+            engine.send_command(AudioCommand::Resume);
+            MediaAction::Play
+        }
+        MediaControlEvent::Pause => {
+            engine.send_command(AudioCommand::Pause);
+            MediaAction::Pause
+        }
+        MediaControlEvent::Toggle => {
+            if engine.get_state().is_playing {
+                engine.send_command(AudioCommand::Pause);
+                MediaAction::Pause
+            } else {
+                engine.send_command(AudioCommand::Resume);
+                MediaAction::Play
+            }
+        }
+        MediaControlEvent::Stop => {
+            engine.send_command(AudioCommand::Stop);
+            MediaAction::Stop
+        }
+        MediaControlEvent::Next => {
+            commands::next_track(app.state::<AppState>());
+            MediaAction::Next
+        }
+        MediaControlEvent::Previous => {
+            commands::previous_track(app.state::<AppState>());
+            MediaAction::Previous
+        }
+        MediaControlEvent::SetPosition(MediaPosition(position)) => {
+            engine.send_command(AudioCommand::Seek(position.as_secs_f64()));
+            MediaAction::Seek
+        }
+        MediaControlEvent::Seek(direction) => {
+            seek_by(app, direction, SEEK_STEP);
+            MediaAction::Seek
+        }
+        MediaControlEvent::SeekBy(direction, by) => {
+            seek_by(app, direction, by);
+            MediaAction::Seek
+        }
+        _ => return,
+    };
+    let _ = app.emit("media://control", action);
+}
+
+fn seek_by(app: &AppHandle, direction: SeekDirection, by: Duration) {
+    let engine = &app.state::<AppState>().engine;
+    let playback = engine.get_state();
+    let target = match direction {
+        SeekDirection::Forward => playback.position_secs + by.as_secs_f64(),
+        SeekDirection::Backward => playback.position_secs - by.as_secs_f64(),
+    };
+    engine.send_command(AudioCommand::Seek(
+        target.clamp(0.0, playback.duration_secs.max(0.0)),
+    ));
+}
@@ -0,0 +1,250 @@
+//! Mobile media controls, through the `media-session` plugin: the lock
+//! screen and notification transport controls and headset buttons, with
+//! the playing track's title, artist, album, cover and position.
+//!
+//! Like `media_controls` on desktop, a thread watches the engine
+//! and pushes metadata when the track changes and the status when it
+//! changes, on seeks and every few seconds while playing. Button presses
+//! act on the engine and queue directly and are emitted as
+//! `media://control`.
+//!
+//! Audio focus: a call or assistant pauses playback and resumes it when
+//! it's done; a short interruption such as a navigation prompt ducks the
+//! volume to [`DUCK_VOLUME`] of its level instead. Losing focus for good
+//! (another player started) or unplugging headphones pauses. On iOS the
+//! same events come from audio session interruptions and route changes.
+
+use parking_lot::Mutex;
+use serde::Serialize;
+use std::path::Path;
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant, UNIX_EPOCH};
+use tauri::{AppHandle, Emitter, Manager};
+use tauri_plugin_media_session::{MediaSessionExt, NowPlaying, SessionEvent, Status};
+
+use crate::audio::engine::{AudioCommand, PlaybackState};
+use crate::commands::{self, AppState};
+use crate::library::{artwork, metadata_cache};
+
+/// How often the engine is checked for changes.
+const SYNC_INTERVAL: Duration = Duration::from_millis(250);
+
+/// How often the position is pushed while playing; the OS runs the clock
+/// in between.
+const POSITION_INTERVAL: Duration = Duration::from_secs(5);
+
+/// A position this far from where the clock should be is a seek.
+const SEEK_THRESHOLD_SECS: f64 = 1.5;
+
+/// Share of the volume kept while ducked.
+pub const DUCK_VOLUME: f32 = 0.2;
+
+/// Payload of `media://control`.
+#[derive(Clone, Copy, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MediaAction {
+    Play,
+    Pause,
+    Stop,
+    Next,
+    Previous,
+    Seek,
+}
+
+/// What audio focus changes did, to undo when focus comes back.
+#[derive(Default)]
+struct Focus {
+    /// Paused for a transient loss.
+    paused: bool,
+    /// The volume before ducking.
+    ducked_from: Option<f32>,
+}
+
+/// Attach to the session and start the thread.
+pub fn spawn(app: &AppHandle) {
+    let focus = Arc::new(Mutex::new(Focus::default()));
+    let handler = app.clone();
+    if let Err(e) = app
+        .media_session()
+        .set_event_handler(move |event| on_event(&handler, &focus, event))
+    {
+        log::warn!("Media session unavailable: {}", e);
+        return;
+    }
+    let app = app.clone();
+    thread::Builder::new()
+        .name("media-session".into())
+        .spawn(move || sync(&app))
+        .expect("Failed to spawn media session thread");
+}
+
+/// Keep the session up to date with the engine. Never returns.
+fn sync(app: &AppHandle) {
+    let state = app.state::<AppState>();
+    let session = app.media_session();
+    // (file, segment start) whose metadata is shown
+    let mut shown: Option<(String, f64)> = None;
+    // Status and position last pushed, and when
+    let mut pushed: Option<(Status, f64)> = None;
+    let mut pushed_at = Instant::now();
+
+    loop {
+        thread::sleep(SYNC_INTERVAL);
+        let playback = state.engine.get_state();
+
+        let track = playback
+            .current_file
+            .clone()
+            .map(|f| (f, playback.start_secs));
+        if track != shown {
+            let now_playing = match &track {
+                Some(_) => now_playing(app, &playback),
+                None => NowPlaying::default(),
+            };
+            if let Err(e) = session.set_metadata(&now_playing) {
+                log::warn!("{}", e);
+            }
+            shown = track;
+            pushed = None;
+        }
+
+        let status = if playback.is_playing {
+            Status::Playing
+        } else if playback.is_paused {
+            Status::Paused
+        } else {
+            Status::Stopped
+        };
+        let position = playback.position_secs;
+        let due = match pushed {
+            Some((pushed_status, at)) => {
+                let playing = pushed_status == Status::Playing;
+                let expected = if playing {
+                    at + pushed_at.elapsed().as_secs_f64()
+                } else {
+                    at
+                };
+                pushed_status != status
+                    || (position - expected).abs() > SEEK_THRESHOLD_SECS
+                    || (playing && pushed_at.elapsed() >= POSITION_INTERVAL)
+            }
+            None => true,
+        };
+        if !due {
+            continue;
+        }
+        if let Err(e) = session.set_playback(status, (position.max(0.0) * 1000.0) as u64) {
+            log::warn!("{}", e);
+        }
+        pushed = Some((status, position));
+        pushed_at = Instant::now();
+    }
+}
+
+/// Title, artist, album, cover and length of the playing track.
+fn now_playing(app: &AppHandle, playback: &PlaybackState) -> NowPlaying {
+    let Some(file) = playback.current_file.as_deref() else {
+        return NowPlaying::default();
+    };
+    let state = app.state::<AppState>();
+    let tags = metadata_cache::metadata(&state.library, file).ok();
+
+    // Cue-sheet tracks share one audio file; their title is in the queue
+    let queued_title = state.queue.lock().current().and_then(|entry| {
+        (entry.path == file && entry.start_secs == playback.start_secs)
+            .then(|| entry.title.clone())
+            .flatten()
+    });
+    let title = queued_title
+        .or_else(|| tags.as_ref().and_then(|t| t.title.clone()))
+        .or_else(|| {
+            Path::new(file)
+                .file_stem()
+                .map(|s| s.to_string_lossy().to_string())
+        });
+
+    let modified_at = std::fs::metadata(file)
+        .and_then(|m| m.modified())
+        .ok()
+        .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or(0);
+    let thumbnail_dir = state.library.lock().thumbnail_dir().to_path_buf();
+    let artwork_path = artwork::ensure_thumbnail(&thumbnail_dir, file, modified_at)
+        .ok()
+        .flatten();
+
+    NowPlaying {
+        title,
+        artist: tags.as_ref().and_then(|t| t.artist.clone()),
+        album: tags.as_ref().and_then(|t| t.album.clone()),
+        artwork_path,
+        duration_ms: (playback.duration_secs.max(0.0) * 1000.0) as u64,
+    }
+}
+
+fn on_event(app: &AppHandle, focus: &Mutex<Focus>, event: SessionEvent) {
+    let state = app.state::<AppState>();
+    let engine = &state.engine;
+    let action = match event {
+        SessionEvent::Play => {
+            engine.send_command(AudioCommand::Resume);
+            MediaAction::Play
+        }
+        SessionEvent::Pause | SessionEvent::FocusLost | SessionEvent::BecomingNoisy => {
+            // Not coming back after all; stay paused
+            focus.lock().paused = false;
+            if !engine.get_state().is_playing {
+                return;
+            }
+            engine.send_command(AudioCommand::Pause);
+            MediaAction::Pause
+        }
+        SessionEvent::Stop => {
+            engine.send_command(AudioCommand::Stop);
+            MediaAction::Stop
+        }
+        SessionEvent::Next => {
+            commands::next_track(app.state::<AppState>());
+            MediaAction::Next
+        }
+        SessionEvent::Previous => {
+            commands::previous_track(app.state::<AppState>());
+            MediaAction::Previous
+        }
+        SessionEvent::SeekTo { position_ms } => {
+            engine.send_command(AudioCommand::Seek(position_ms as f64 / 1000.0));
+            MediaAction::Seek
+        }
+        SessionEvent::FocusLostTransient => {
+            if !engine.get_state().is_playing {
+                return;
+            }
+            focus.lock().paused = true;
+            engine.send_command(AudioCommand::Pause);
+            MediaAction::Pause
+        }
+        SessionEvent::Duck => {
+            let mut focus = focus.lock();
+            if focus.ducked_from.is_none() {
+                let volume = engine.dsp_settings().volume;
+                focus.ducked_from = Some(volume);
+                engine.send_command(AudioCommand::SetVolume(volume * DUCK_VOLUME));
+            }
+            return;
+        }
+        SessionEvent::FocusGained => {
+            let mut focus = focus.lock();
+            if let Some(volume) = focus.ducked_from.take() {
+                engine.send_command(AudioCommand::SetVolume(volume));
+            }
+            if !std::mem::take(&mut focus.paused) {
+                return;
+            }
+            engine.send_command(AudioCommand::Resume);
+            MediaAction::Play
+        }
+    };
+    let _ = app.emit("media://control", action);
+}
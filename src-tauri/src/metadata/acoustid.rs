@@ -0,0 +1,201 @@
+//! Track identification through AcoustID.
+//!
+//! A file's Chromaprint fingerprint is looked up on AcoustID, which maps it
+//! to MusicBrainz recordings and the releases they appear on. Each match
+//! becomes a [`TagSuggestion`] whose fields can go straight to
+//! [`super::writer::write_metadata`].
+//!
+//! The API needs a client key (free, from acoustid.org) and allows three
+//! requests a second.
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::time::Duration;
+
+use super::writer::MetadataFields;
+use crate::audio::fingerprint::Fingerprint;
+use crate::http::{self, RateLimit};
+
+const LOOKUP_URL: &str = "https://api.acoustid.org/v2/lookup";
+
+static RATE_LIMIT: RateLimit = RateLimit::new(Duration::from_millis(334));
+
+/// Matches scoring lower than this are dropped.
+const MIN_SCORE: f64 = 0.5;
+
+/// Most suggestions returned for one file.
+const MAX_SUGGESTIONS: usize = 20;
+
+#[derive(Clone, Serialize)]
+pub struct TagSuggestion {
+    /// How well the fingerprint matched, 0.0–1.0.
+    pub score: f64,
+    /// MusicBrainz recording id.
+    pub recording_id: String,
+    /// MusicBrainz release id, when the match names a release.
+    pub release_id: Option<String>,
+    pub fields: MetadataFields,
+}
+
+#[derive(Deserialize)]
+struct LookupResponse {
+    status: String,
+    error: Option<ApiError>,
+    #[serde(default)]
+    results: Vec<LookupResult>,
+}
+
+#[derive(Deserialize)]
+struct ApiError {
+    message: String,
+}
+
+#[derive(Deserialize)]
+struct LookupResult {
+    score: f64,
+    #[serde(default)]
+    recordings: Vec<Recording>,
+}
+
+#[derive(Deserialize)]
+struct Recording {
+    id: String,
+    title: Option<String>,
+    #[serde(default)]
+    artists: Vec<ArtistCredit>,
+    #[serde(default)]
+    releases: Vec<Release>,
+}
+
+#[derive(Deserialize)]
+struct ArtistCredit {
+    name: String,
+    joinphrase: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct Release {
+    id: String,
+    title: Option<String>,
+    #[serde(default)]
+    artists: Vec<ArtistCredit>,
+    date: Option<ReleaseDate>,
+    #[serde(default)]
+    mediums: Vec<Medium>,
+}
+
+#[derive(Deserialize)]
+struct ReleaseDate {
+    year: Option<u32>,
+}
+
+#[derive(Deserialize)]
+struct Medium {
+    position: Option<u32>,
+    /// Only the matched track is listed.
+    #[serde(default)]
+    tracks: Vec<MediumTrack>,
+}
+
+#[derive(Deserialize)]
+struct MediumTrack {
+    position: Option<u32>,
+}
+
+/// Look a fingerprint up, best match first.
+pub fn lookup(api_key: &str, fingerprint: &Fingerprint) -> Result<Vec<TagSuggestion>, String> {
+    if api_key.trim().is_empty() {
+        return Err("An AcoustID client key is required".to_string());
+    }
+    let duration = (fingerprint.duration_secs.round() as u64).to_string();
+    RATE_LIMIT.wait();
+    let response: LookupResponse = http::post_form_json(
+        LOOKUP_URL,
+        &[
+            ("client", api_key.trim()),
+            ("duration", &duration),
+            ("fingerprint", &fingerprint.fingerprint),
+            ("meta", "recordings releases"),
+        ],
+    )?;
+    if response.status != "ok" {
+        let message = response.error.map(|e| e.message).unwrap_or(response.status);
+        return Err(format!("AcoustID lookup failed: {}", message));
+    }
+
+    let mut suggestions = Vec::new();
+    let mut seen = HashSet::new();
+    for result in response.results.iter().filter(|r| r.score >= MIN_SCORE) {
+        for recording in &result.recordings {
+            for suggestion in recording_suggestions(result.score, recording) {
+                let f = &suggestion.fields;
+                let key = (
+                    f.title.clone(),
+                    f.artist.clone(),
+                    f.album.clone(),
+                    f.track_number,
+                );
+                if seen.insert(key) {
+                    suggestions.push(suggestion);
+                }
+            }
+        }
+    }
+    suggestions.sort_by(|a, b| b.score.total_cmp(&a.score));
+    suggestions.truncate(MAX_SUGGESTIONS);
+    Ok(suggestions)
+}
+
+/// One suggestion per release of the recording, or a single one without
+/// album fields when it isn't on any.
+fn recording_suggestions(score: f64, recording: &Recording) -> Vec<TagSuggestion> {
+    let artist = credit_name(&recording.artists);
+    let base = MetadataFields {
+        title: recording.title.clone(),
+        artist: artist.clone(),
+        ..Default::default()
+    };
+    if recording.releases.is_empty() {
+        return vec![TagSuggestion {
+            score,
+            recording_id: recording.id.clone(),
+            release_id: None,
+            fields: base,
+        }];
+    }
+    recording
+        .releases
+        .iter()
+        .map(|release| {
+            let medium = release.mediums.first();
+            TagSuggestion {
+                score,
+                recording_id: recording.id.clone(),
+                release_id: Some(release.id.clone()),
+                fields: MetadataFields {
+                    album: release.title.clone(),
+                    album_artist: credit_name(&release.artists).or_else(|| artist.clone()),
+                    year: release.date.as_ref().and_then(|d| d.year),
+                    track_number: medium
+                        .and_then(|m| m.tracks.first())
+                        .and_then(|t| t.position),
+                    disc_number: medium.and_then(|m| m.position),
+                    ..base.clone()
+                },
+            }
+        })
+        .collect()
+}
+
+/// "Artist A feat. Artist B" from a MusicBrainz artist credit.
+fn credit_name(credits: &[ArtistCredit]) -> Option<String> {
+    if credits.is_empty() {
+        return None;
+    }
+    let mut name = String::new();
+    for credit in credits {
+        name.push_str(&credit.name);
+        name.push_str(credit.joinphrase.as_deref().unwrap_or(""));
+    }
+    Some(name.trim().to_string())
+}
@@ -0,0 +1,689 @@
+//! Chapter lists of long-form audio (audiobooks, podcasts, DJ mixes).
+//!
+//! Chapters come from ID3v2 CHAP frames (MP3), QuickTime chapter tracks or
+//! Nero `chpl` atoms (M4A / M4B), the Matroska `Chapters` element (MKA) and
+//! `CHAPTERxxx` comments (Ogg, Opus, FLAC). A chapter without an end runs to
+//! the start of the next one, or to the end of the track.
+
+use lofty::id3::v2::{Frame, FrameFlags, FrameId, Id3v2Tag, Id3v2Version, TextInformationFrame};
+use lofty::tag::{ItemKey, Tag};
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::Path;
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Chapter {
+    pub title: String,
+    pub start_secs: f64,
+    pub end_secs: f64,
+}
+
+/// A chapter as stored, before titles and ends are filled in.
+struct RawChapter {
+    title: Option<String>,
+    start_secs: f64,
+    end_secs: Option<f64>,
+}
+
+/// Chapters of a file, in playback order. Unreadable chapter data gives an
+/// empty list rather than failing the whole metadata read.
+pub fn read_chapters(
+    path: &str,
+    tag: Option<&Tag>,
+    id3v2: Option<&Id3v2Tag>,
+    duration_secs: f64,
+) -> Vec<Chapter> {
+    let extension = Path::new(path)
+        .extension()
+        .map(|e| e.to_string_lossy().to_ascii_lowercase())
+        .unwrap_or_default();
+    let raw = if let Some(id3v2) = id3v2 {
+        id3v2_chapters(id3v2)
+    } else {
+        match extension.as_str() {
+            "m4a" | "m4b" | "mp4" => mp4_chapters(path).unwrap_or_default(),
+            "mka" | "mkv" | "webm" => matroska_chapters(path).unwrap_or_default(),
+            _ => tag.map(vorbis_chapters).unwrap_or_default(),
+        }
+    };
+    finish(raw, duration_secs)
+}
+
+/// Sort chapters, fill in missing ends and name untitled ones "Chapter n".
+fn finish(mut raw: Vec<RawChapter>, duration_secs: f64) -> Vec<Chapter> {
+    raw.retain(|c| c.start_secs.is_finite() && c.start_secs >= 0.0);
+    raw.sort_by(|a, b| a.start_secs.total_cmp(&b.start_secs));
+    (0..raw.len())
+        .map(|i| {
+            let chapter = &raw[i];
+            let next = raw
+                .get(i + 1)
+                .map(|c| c.start_secs)
+                .unwrap_or(duration_secs)
+                .max(chapter.start_secs);
+            let end_secs = chapter
+                .end_secs
+                .filter(|&end| end > chapter.start_secs)
+                .unwrap_or(next);
+            let title = chapter
+                .title
+                .as_deref()
+                .map(str::trim)
+                .filter(|t| !t.is_empty())
+                .map(str::to_string)
+                .unwrap_or_else(|| format!("Chapter {}", i + 1));
+            Chapter {
+                title,
+                start_secs: chapter.start_secs,
+                end_secs,
+            }
+        })
+        .collect()
+}
+
+// ─── ID3v2 ───
+
+/// CHAP frames, which lofty leaves unparsed.
+fn id3v2_chapters(id3v2: &Id3v2Tag) -> Vec<RawChapter> {
+    let version = id3v2.original_version();
+    id3v2
+        .into_iter()
+        .filter_map(|frame| match frame {
+            Frame::Binary(binary) if frame.id().as_str() == "CHAP" => {
+                parse_chap(&binary.data, version)
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+/// Element ID, start and end in ms, byte offsets (unused), then sub-frames
+/// of which TIT2 holds the title.
+fn parse_chap(data: &[u8], version: Id3v2Version) -> Option<RawChapter> {
+    let id_end = data.iter().position(|&b| b == 0)?;
+    let data = data.get(id_end + 1..)?;
+    let start_ms = u32::from_be_bytes(data.get(0..4)?.try_into().ok()?);
+    let end_ms = u32::from_be_bytes(data.get(4..8)?.try_into().ok()?);
+
+    let mut title = None;
+    let mut frames = data.get(16..).unwrap_or_default();
+    while frames.len() >= 10 {
+        let size_bytes: [u8; 4] = frames[4..8].try_into().ok()?;
+        let size = if version == Id3v2Version::V4 {
+            // Synchsafe: 7 bits per byte
+            size_bytes
+                .iter()
+                .fold(0usize, |size, &b| (size << 7) | usize::from(b & 0x7f))
+        } else {
+            u32::from_be_bytes(size_bytes) as usize
+        };
+        let body = frames.get(10..10 + size)?;
+        if &frames[..4] == b"TIT2" {
+            title = TextInformationFrame::parse(
+                &mut &body[..],
+                FrameId::Valid(Cow::Borrowed("TIT2")),
+                FrameFlags::default(),
+                version,
+            )
+            .ok()
+            .flatten()
+            .map(|frame| frame.value);
+        }
+        frames = &frames[10 + size..];
+    }
+
+    Some(RawChapter {
+        title,
+        start_secs: f64::from(start_ms) / 1000.0,
+        end_secs: Some(f64::from(end_ms) / 1000.0),
+    })
+}
+
+// ─── Vorbis comments ───
+
+/// `CHAPTER001=00:01:02.500` with the title in `CHAPTER001NAME`.
+fn vorbis_chapters(tag: &Tag) -> Vec<RawChapter> {
+    let mut starts: Vec<(String, f64)> = Vec::new();
+    let mut names: Vec<(String, String)> = Vec::new();
+    for item in tag.items() {
+        let ItemKey::Unknown(key) = item.key() else {
+            continue;
+        };
+        let key = key.to_ascii_uppercase();
+        let Some(rest) = key.strip_prefix("CHAPTER") else {
+            continue;
+        };
+        let Some(value) = item.value().text() else {
+            continue;
+        };
+        let digits = rest.trim_end_matches("NAME");
+        if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit()) {
+            continue;
+        }
+        if digits.len() == rest.len() {
+            if let Some(secs) = parse_clock(value) {
+                starts.push((digits.to_string(), secs));
+            }
+        } else {
+            names.push((digits.to_string(), value.to_string()));
+        }
+    }
+
+    starts
+        .into_iter()
+        .map(|(number, start_secs)| RawChapter {
+            title: names
+                .iter()
+                .find(|(n, _)| *n == number)
+                .map(|(_, name)| name.clone()),
+            start_secs,
+            end_secs: None,
+        })
+        .collect()
+}
+
+/// "HH:MM:SS.mmm" (hours and fraction optional) to seconds.
+fn parse_clock(value: &str) -> Option<f64> {
+    let mut secs = 0.0;
+    for part in value.trim().split(':') {
+        let part: f64 = part.parse().ok()?;
+        if !part.is_finite() || part < 0.0 {
+            return None;
+        }
+        secs = secs * 60.0 + part;
+    }
+    Some(secs)
+}
+
+// ─── MP4 ───
+
+/// A box's type and the file range of its content.
+#[derive(Clone)]
+struct Mp4Box {
+    kind: [u8; 4],
+    start: u64,
+    end: u64,
+}
+
+/// Chapters from a QuickTime chapter track (what Apple players read),
+/// falling back to the Nero `chpl` atom.
+fn mp4_chapters(path: &str) -> io::Result<Vec<RawChapter>> {
+    let mut file = File::open(path)?;
+    let len = file.metadata()?.len();
+    let Some(moov) = find_box(&mut file, 0, len, b"moov")? else {
+        return Ok(Vec::new());
+    };
+
+    let traks: Vec<Mp4Box> = mp4_boxes(&mut file, moov.start, moov.end)?
+        .into_iter()
+        .filter(|b| &b.kind == b"trak")
+        .collect();
+    let mut chapter_track_ids = Vec::new();
+    for trak in &traks {
+        if let Some(chap) = find_path(&mut file, trak, &[b"tref", b"chap"])? {
+            let ids = read_range(&mut file, &chap)?;
+            chapter_track_ids.extend(
+                ids.chunks_exact(4)
+                    .map(|id| u32::from_be_bytes([id[0], id[1], id[2], id[3]])),
+            );
+        }
+    }
+    for trak in &traks {
+        if chapter_track_ids.contains(&track_id(&mut file, trak)?.unwrap_or(0)) {
+            let chapters = text_track_chapters(&mut file, trak)?;
+            if !chapters.is_empty() {
+                return Ok(chapters);
+            }
+        }
+    }
+
+    match find_path(&mut file, &moov, &[b"udta", b"chpl"])? {
+        Some(chpl) => Ok(parse_chpl(&read_range(&mut file, &chpl)?).unwrap_or_default()),
+        None => Ok(Vec::new()),
+    }
+}
+
+/// Child boxes of the content range `start..end`.
+fn mp4_boxes(file: &mut File, start: u64, end: u64) -> io::Result<Vec<Mp4Box>> {
+    let mut boxes = Vec::new();
+    let mut pos = start;
+    while pos + 8 <= end {
+        file.seek(SeekFrom::Start(pos))?;
+        let mut header = [0u8; 8];
+        file.read_exact(&mut header)?;
+        let size = u32::from_be_bytes([header[0], header[1], header[2], header[3]]);
+        let kind = [header[4], header[5], header[6], header[7]];
+        let (content_start, box_end) = match size {
+            // Runs to the end of the parent
+            0 => (pos + 8, end),
+            1 => {
+                let mut large = [0u8; 8];
+                file.read_exact(&mut large)?;
+                (pos + 16, pos.saturating_add(u64::from_be_bytes(large)))
+            }
+            _ => (pos + 8, pos + u64::from(size)),
+        };
+        if box_end < content_start || box_end > end {
+            break;
+        }
+        boxes.push(Mp4Box {
+            kind,
+            start: content_start,
+            end: box_end,
+        });
+        pos = box_end;
+    }
+    Ok(boxes)
+}
+
+fn find_box(file: &mut File, start: u64, end: u64, kind: &[u8; 4]) -> io::Result<Option<Mp4Box>> {
+    Ok(mp4_boxes(file, start, end)?
+        .into_iter()
+        .find(|b| &b.kind == kind))
+}
+
+/// The box at `path` below `parent`, e.g. `mdia/minf/stbl`.
+fn find_path(file: &mut File, parent: &Mp4Box, path: &[&[u8; 4]]) -> io::Result<Option<Mp4Box>> {
+    let mut current = parent.clone();
+    for kind in path {
+        match find_box(file, current.start, current.end, kind)? {
+            Some(child) => current = child,
+            None => return Ok(None),
+        }
+    }
+    Ok(Some(current))
+}
+
+/// Box content, for the small index boxes read here.
+fn read_range(file: &mut File, b: &Mp4Box) -> io::Result<Vec<u8>> {
+    // Index tables of a long audiobook stay far below this
+    const MAX_BOX_BYTES: u64 = 64 * 1024 * 1024;
+    if b.end - b.start > MAX_BOX_BYTES {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "Box too large"));
+    }
+    file.seek(SeekFrom::Start(b.start))?;
+    let mut data = vec![0u8; (b.end - b.start) as usize];
+    file.read_exact(&mut data)?;
+    Ok(data)
+}
+
+fn be_u32(data: &[u8], at: usize) -> Option<u32> {
+    Some(u32::from_be_bytes(data.get(at..at + 4)?.try_into().ok()?))
+}
+
+fn be_u64(data: &[u8], at: usize) -> Option<u64> {
+    Some(u64::from_be_bytes(data.get(at..at + 8)?.try_into().ok()?))
+}
+
+/// Track ID from `tkhd`, whose layout depends on its version byte.
+fn track_id(file: &mut File, trak: &Mp4Box) -> io::Result<Option<u32>> {
+    let Some(tkhd) = find_path(file, trak, &[b"tkhd"])? else {
+        return Ok(None);
+    };
+    let data = read_range(file, &tkhd)?;
+    let offset = if data.first() == Some(&1) { 20 } else { 12 };
+    Ok(be_u32(&data, offset))
+}
+
+/// Chapters stored as timed text samples: each sample is a 16-bit length and
+/// the title, lasting for its `stts` duration.
+fn text_track_chapters(file: &mut File, trak: &Mp4Box) -> io::Result<Vec<RawChapter>> {
+    let Some(mdhd) = find_path(file, trak, &[b"mdia", b"mdhd"])? else {
+        return Ok(Vec::new());
+    };
+    let mdhd = read_range(file, &mdhd)?;
+    let timescale_at = if mdhd.first() == Some(&1) { 20 } else { 12 };
+    let timescale = match be_u32(&mdhd, timescale_at) {
+        Some(t) if t > 0 => f64::from(t),
+        _ => return Ok(Vec::new()),
+    };
+    let Some(stbl) = find_path(file, trak, &[b"mdia", b"minf", b"stbl"])? else {
+        return Ok(Vec::new());
+    };
+
+    let mut table = |kind: &[u8; 4]| -> io::Result<Vec<u8>> {
+        match find_box(file, stbl.start, stbl.end, kind)? {
+            Some(b) => read_range(file, &b),
+            None => Ok(Vec::new()),
+        }
+    };
+    let stts = table(b"stts")?;
+    let stsz = table(b"stsz")?;
+    let stsc = table(b"stsc")?;
+    let stco = table(b"stco")?;
+    let co64 = table(b"co64")?;
+
+    // Sample durations
+    let mut durations = Vec::new();
+    let entries = be_u32(&stts, 4).unwrap_or(0) as usize;
+    for i in 0..entries {
+        let (Some(count), Some(delta)) = (be_u32(&stts, 8 + i * 8), be_u32(&stts, 12 + i * 8))
+        else {
+            break;
+        };
+        durations.extend(std::iter::repeat_n(delta, count.min(100_000) as usize));
+    }
+
+    // Sample sizes
+    let fixed_size = be_u32(&stsz, 4).unwrap_or(0);
+    let sample_count = be_u32(&stsz, 8).unwrap_or(0) as usize;
+    let sizes: Vec<u32> = (0..sample_count.min(durations.len()))
+        .map_while(|i| {
+            if fixed_size > 0 {
+                Some(fixed_size)
+            } else {
+                be_u32(&stsz, 12 + i * 4)
+            }
+        })
+        .collect();
+
+    // Chunk offsets, and how many samples each chunk holds
+    let chunk_offsets: Vec<u64> = if co64.is_empty() {
+        let count = be_u32(&stco, 4).unwrap_or(0) as usize;
+        (0..count)
+            .map_while(|i| be_u32(&stco, 8 + i * 4).map(u64::from))
+            .collect()
+    } else {
+        let count = be_u32(&co64, 4).unwrap_or(0) as usize;
+        (0..count).map_while(|i| be_u64(&co64, 8 + i * 8)).collect()
+    };
+    let stsc_entries: Vec<(u32, u32)> = (0..be_u32(&stsc, 4).unwrap_or(0) as usize)
+        .map_while(|i| Some((be_u32(&stsc, 8 + i * 12)?, be_u32(&stsc, 12 + i * 12)?)))
+        .collect();
+    let mut sample_offsets = Vec::with_capacity(sizes.len());
+    for (chunk, &chunk_offset) in chunk_offsets.iter().enumerate() {
+        let chunk_number = chunk as u32 + 1;
+        let per_chunk = stsc_entries
+            .iter()
+            .rev()
+            .find(|(first, _)| *first <= chunk_number)
+            .map(|(_, n)| *n)
+            .unwrap_or(1);
+        let mut offset = chunk_offset;
+        for _ in 0..per_chunk {
+            let Some(&size) = sizes.get(sample_offsets.len()) else {
+                break;
+            };
+            sample_offsets.push(offset);
+            offset += u64::from(size);
+        }
+    }
+
+    let mut chapters = Vec::with_capacity(sample_offsets.len());
+    let mut time = 0u64;
+    for (i, &offset) in sample_offsets.iter().enumerate() {
+        let duration = u64::from(durations[i]);
+        let mut sample = vec![0u8; sizes[i].min(64 * 1024) as usize];
+        file.seek(SeekFrom::Start(offset))?;
+        file.read_exact(&mut sample)?;
+        let title = sample.get(..2).and_then(|len| {
+            let len = usize::from(u16::from_be_bytes([len[0], len[1]]));
+            sample.get(2..2 + len).map(decode_text)
+        });
+        chapters.push(RawChapter {
+            title,
+            start_secs: time as f64 / timescale,
+            end_secs: Some((time + duration) as f64 / timescale),
+        });
+        time += duration;
+    }
+    Ok(chapters)
+}
+
+/// UTF-8, or UTF-16 when the text starts with a byte order mark.
+fn decode_text(bytes: &[u8]) -> String {
+    let utf16 = |le: bool| {
+        let units: Vec<u16> = bytes[2..]
+            .chunks_exact(2)
+            .map(|u| {
+                if le {
+                    u16::from_le_bytes([u[0], u[1]])
+                } else {
+                    u16::from_be_bytes([u[0], u[1]])
+                }
+            })
+            .collect();
+        String::from_utf16_lossy(&units)
+    };
+    match bytes {
+        [0xFE, 0xFF, ..] => utf16(false),
+        [0xFF, 0xFE, ..] => utf16(true),
+        _ => String::from_utf8_lossy(bytes).into_owned(),
+    }
+}
+
+/// Nero chapters: version and flags, 4 more bytes in version 1, a count, then
+/// per chapter a start in 100 ns units and a length-prefixed title.
+fn parse_chpl(data: &[u8]) -> Option<Vec<RawChapter>> {
+    let mut pos = if *data.first()? == 1 { 8 } else { 4 };
+    let count = *data.get(pos)?;
+    pos += 1;
+    let mut chapters = Vec::with_capacity(usize::from(count));
+    for _ in 0..count {
+        let start = be_u64(data, pos)?;
+        let len = usize::from(*data.get(pos + 8)?);
+        let title = data.get(pos + 9..pos + 9 + len)?;
+        chapters.push(RawChapter {
+            title: Some(String::from_utf8_lossy(title).into_owned()),
+            start_secs: start as f64 / 10_000_000.0,
+            end_secs: None,
+        });
+        pos += 9 + len;
+    }
+    Some(chapters)
+}
+
+// ─── Matroska ───
+
+const EBML_SEGMENT: u32 = 0x1853_8067;
+const EBML_SEEK_HEAD: u32 = 0x114D_9B74;
+const EBML_SEEK: u32 = 0x4DBB;
+const EBML_SEEK_ID: u32 = 0x53AB;
+const EBML_SEEK_POSITION: u32 = 0x53AC;
+const EBML_CLUSTER: u32 = 0x1F43_B675;
+const EBML_CHAPTERS: u32 = 0x1043_A770;
+const EBML_EDITION_ENTRY: u32 = 0x45B9;
+const EBML_CHAPTER_ATOM: u32 = 0xB6;
+const EBML_CHAPTER_TIME_START: u32 = 0x91;
+const EBML_CHAPTER_TIME_END: u32 = 0x92;
+const EBML_CHAPTER_FLAG_HIDDEN: u32 = 0x98;
+const EBML_CHAPTER_DISPLAY: u32 = 0x80;
+const EBML_CHAP_STRING: u32 = 0x85;
+
+/// Chapters of the first edition. The `Chapters` element is found in the
+/// segment's top level, through the seek head when it sits after the
+/// clusters.
+fn matroska_chapters(path: &str) -> io::Result<Vec<RawChapter>> {
+    let mut file = File::open(path)?;
+    let len = file.metadata()?.len();
+
+    // Skip the EBML header to the segment
+    let mut pos = 0;
+    let segment_start = loop {
+        let Some((id, content_start, size)) = read_element_header(&mut file, pos)? else {
+            return Ok(Vec::new());
+        };
+        if id == EBML_SEGMENT {
+            break content_start;
+        }
+        match size {
+            Some(size) => pos = content_start + size,
+            None => return Ok(Vec::new()),
+        }
+    };
+
+    let mut chapters_at = None;
+    let mut pos = segment_start;
+    while pos < len {
+        let Some((id, content_start, size)) = read_element_header(&mut file, pos)? else {
+            break;
+        };
+        match id {
+            EBML_CHAPTERS => {
+                let Some(size) = size else {
+                    break;
+                };
+                return Ok(parse_chapters_element(&read_bytes(
+                    &mut file,
+                    content_start,
+                    size,
+                )?));
+            }
+            EBML_SEEK_HEAD => {
+                if let Some(size) = size {
+                    let seek_head = read_bytes(&mut file, content_start, size)?;
+                    chapters_at = seek_position(&seek_head, EBML_CHAPTERS)
+                        .map(|offset| segment_start + offset);
+                }
+            }
+            EBML_CLUSTER => {
+                // Audio from here on; jump straight to the chapters if indexed
+                match chapters_at.take() {
+                    Some(at) if at > pos => {
+                        pos = at;
+                        continue;
+                    }
+                    _ if size.is_none() => break,
+                    _ => {}
+                }
+            }
+            _ => {}
+        }
+        match size {
+            Some(size) => pos = content_start + size,
+            None => break,
+        }
+    }
+    Ok(Vec::new())
+}
+
+/// Element ID, content start and size (`None` when unknown) at `pos`.
+fn read_element_header(file: &mut File, pos: u64) -> io::Result<Option<(u32, u64, Option<u64>)>> {
+    file.seek(SeekFrom::Start(pos))?;
+    let mut header = [0u8; 12];
+    let read = file.read(&mut header)?;
+    let Some((id, id_len)) = read_vint(&header[..read], true) else {
+        return Ok(None);
+    };
+    let Some((size, size_len)) = read_vint(&header[id_len..read], false) else {
+        return Ok(None);
+    };
+    // All value bits set means the size is unknown
+    let unknown = size == (1u64 << (7 * size_len)) - 1;
+    Ok(Some((
+        id as u32,
+        pos + (id_len + size_len) as u64,
+        (!unknown).then_some(size),
+    )))
+}
+
+/// An EBML variable-length integer and its length. IDs keep their marker
+/// bit, sizes drop it.
+fn read_vint(data: &[u8], keep_marker: bool) -> Option<(u64, usize)> {
+    let first = *data.first()?;
+    let len = first.leading_zeros() as usize + 1;
+    if len > 8 || data.len() < len {
+        return None;
+    }
+    let mut value = if keep_marker {
+        u64::from(first)
+    } else {
+        u64::from(first) & ((1u64 << (8 - len)) - 1)
+    };
+    for &b in &data[1..len] {
+        value = (value << 8) | u64::from(b);
+    }
+    Some((value, len))
+}
+
+fn read_bytes(file: &mut File, start: u64, size: u64) -> io::Result<Vec<u8>> {
+    // Chapter lists and seek heads are small
+    if size > 16 * 1024 * 1024 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "Element too large",
+        ));
+    }
+    file.seek(SeekFrom::Start(start))?;
+    let mut data = vec![0u8; size as usize];
+    file.read_exact(&mut data)?;
+    Ok(data)
+}
+
+/// Children of an in-memory master element.
+fn ebml_children(mut data: &[u8]) -> Vec<(u32, &[u8])> {
+    let mut children = Vec::new();
+    while let Some((id, id_len)) = read_vint(data, true) {
+        let Some((size, size_len)) = read_vint(&data[id_len..], false) else {
+            break;
+        };
+        let start = id_len + size_len;
+        let Some(content) = usize::try_from(size)
+            .ok()
+            .and_then(|size| data.get(start..start.checked_add(size)?))
+        else {
+            break;
+        };
+        children.push((id as u32, content));
+        data = &data[start + content.len()..];
+    }
+    children
+}
+
+fn ebml_uint(data: &[u8]) -> u64 {
+    data.iter().take(8).fold(0, |v, &b| (v << 8) | u64::from(b))
+}
+
+/// Segment-relative position of `target` listed in a seek head.
+fn seek_position(seek_head: &[u8], target: u32) -> Option<u64> {
+    ebml_children(seek_head)
+        .into_iter()
+        .filter(|(id, _)| *id == EBML_SEEK)
+        .find_map(|(_, seek)| {
+            let children = ebml_children(seek);
+            let id = children.iter().find(|(id, _)| *id == EBML_SEEK_ID)?.1;
+            let position = children.iter().find(|(id, _)| *id == EBML_SEEK_POSITION)?.1;
+            (ebml_uint(id) == u64::from(target)).then(|| ebml_uint(position))
+        })
+}
+
+/// Top-level chapter atoms of the first edition; times are in nanoseconds.
+fn parse_chapters_element(data: &[u8]) -> Vec<RawChapter> {
+    let Some((_, edition)) = ebml_children(data)
+        .into_iter()
+        .find(|(id, _)| *id == EBML_EDITION_ENTRY)
+    else {
+        return Vec::new();
+    };
+    ebml_children(edition)
+        .into_iter()
+        .filter(|(id, _)| *id == EBML_CHAPTER_ATOM)
+        .filter_map(|(_, atom)| {
+            let children = ebml_children(atom);
+            let value = |wanted: u32| {
+                children
+                    .iter()
+                    .find(|(id, _)| *id == wanted)
+                    .map(|(_, v)| *v)
+            };
+            if value(EBML_CHAPTER_FLAG_HIDDEN).is_some_and(|v| ebml_uint(v) == 1) {
+                return None;
+            }
+            let start = ebml_uint(value(EBML_CHAPTER_TIME_START)?);
+            let end = value(EBML_CHAPTER_TIME_END).map(ebml_uint);
+            let title = value(EBML_CHAPTER_DISPLAY).and_then(|display| {
+                ebml_children(display)
+                    .into_iter()
+                    .find(|(id, _)| *id == EBML_CHAP_STRING)
+                    .map(|(_, s)| String::from_utf8_lossy(s).into_owned())
+            });
+            Some(RawChapter {
+                title,
+                start_secs: start as f64 / 1e9,
+                end_secs: end.map(|end| end as f64 / 1e9),
+            })
+        })
+        .collect()
+}
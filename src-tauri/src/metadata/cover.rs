@@ -0,0 +1,173 @@
+//! Embedded cover art writing.
+//!
+//! The front cover goes into the file's primary tag as a picture of type
+//! "Cover (front)", replacing any earlier front cover. Other pictures
+//! (back cover, booklet pages) are kept. Artwork can be scaled down and
+//! recompressed to JPEG on the way in, or afterwards when a file carries an
+//! oversized picture.
+
+use image::codecs::jpeg::JpegEncoder;
+use image::imageops::FilterType;
+use lofty::file::TaggedFile;
+use lofty::picture::{MimeType, Picture, PictureType};
+use lofty::prelude::*;
+use lofty::probe::Probe;
+use lofty::tag::Tag;
+use serde::Deserialize;
+
+use super::write_settings;
+
+/// Pictures above this size count as oversized unless told otherwise.
+pub const DEFAULT_MAX_ART_BYTES: usize = 1024 * 1024;
+
+/// Quality used when recompressing without an explicit one.
+const DEFAULT_JPEG_QUALITY: u8 = 90;
+
+/// How to shrink artwork before it is embedded.
+#[derive(Clone, Copy, Default, Deserialize)]
+#[serde(default)]
+pub struct ArtResize {
+    /// Longest edge in pixels; larger images are scaled down.
+    pub max_dimension: Option<u32>,
+    /// Re-encode as JPEG at this quality (1–100) even when not scaled.
+    pub jpeg_quality: Option<u8>,
+}
+
+/// Embed `image` (JPEG or PNG bytes) as the file's front cover.
+pub fn embed_cover(path: &str, image: &[u8]) -> Result<(), String> {
+    let mut picture =
+        Picture::from_reader(&mut &image[..]).map_err(|e| format!("Unsupported image: {}", e))?;
+    picture.set_pic_type(PictureType::CoverFront);
+
+    let tagged_file = read_tags(path)?;
+    let tag_type = tagged_file.primary_tag_type();
+    let mut tag = tagged_file
+        .tag(tag_type)
+        .cloned()
+        .unwrap_or_else(|| Tag::new(tag_type));
+
+    tag.remove_picture_type(PictureType::CoverFront);
+    tag.push_picture(picture);
+    write_settings::save_tag(&tag, path)
+}
+
+/// Every embedded picture, from all of the file's tags.
+pub fn read_pictures(path: &str) -> Result<Vec<Picture>, String> {
+    Ok(read_tags(path)?
+        .tags()
+        .iter()
+        .flat_map(|tag| tag.pictures().iter().cloned())
+        .collect())
+}
+
+/// Remove every embedded picture from every tag. Returns how many were
+/// removed; the file is only rewritten if that is more than zero.
+pub fn remove_pictures(path: &str) -> Result<usize, String> {
+    edit_pictures(path, |_| Ok(PictureEdit::Remove))
+}
+
+/// Shrink embedded pictures larger than `max_bytes` with `resize`, or remove
+/// them when `resize` is `None`. Returns how many pictures changed.
+pub fn shrink_pictures(
+    path: &str,
+    max_bytes: usize,
+    resize: Option<&ArtResize>,
+) -> Result<usize, String> {
+    edit_pictures(path, |picture| {
+        if picture.data().len() <= max_bytes {
+            return Ok(PictureEdit::Keep);
+        }
+        let Some(resize) = resize else {
+            return Ok(PictureEdit::Remove);
+        };
+        let data = resize_image(picture.data(), resize)?;
+        if data.len() >= picture.data().len() {
+            // Nothing gained; keep the original rather than lose quality
+            return Ok(PictureEdit::Keep);
+        }
+        Ok(PictureEdit::Replace(Picture::new_unchecked(
+            picture.pic_type(),
+            Some(MimeType::Jpeg),
+            picture.description().map(str::to_string),
+            data,
+        )))
+    })
+}
+
+/// `image` scaled and recompressed as `resize` asks. Returned unchanged when
+/// it is already within `max_dimension` and no quality is given.
+pub fn resize_image(image: &[u8], resize: &ArtResize) -> Result<Vec<u8>, String> {
+    let decoded =
+        image::load_from_memory(image).map_err(|e| format!("Failed to decode artwork: {}", e))?;
+    let too_large = resize
+        .max_dimension
+        .is_some_and(|max| decoded.width().max(decoded.height()) > max);
+    if !too_large && resize.jpeg_quality.is_none() {
+        return Ok(image.to_vec());
+    }
+
+    let scaled = match resize.max_dimension {
+        Some(max) if too_large => decoded.resize(max, max, FilterType::Lanczos3),
+        _ => decoded,
+    };
+    let quality = resize
+        .jpeg_quality
+        .unwrap_or(DEFAULT_JPEG_QUALITY)
+        .clamp(1, 100);
+    let mut out = Vec::new();
+    JpegEncoder::new_with_quality(&mut out, quality)
+        .encode_image(&scaled.to_rgb8())
+        .map_err(|e| format!("Failed to encode artwork: {}", e))?;
+    Ok(out)
+}
+
+enum PictureEdit {
+    Keep,
+    Replace(Picture),
+    Remove,
+}
+
+/// Apply `edit` to each picture in each tag and save if anything changed.
+/// Returns the number changed.
+fn edit_pictures(
+    path: &str,
+    mut edit: impl FnMut(&Picture) -> Result<PictureEdit, String>,
+) -> Result<usize, String> {
+    let mut tagged_file = read_tags(path)?;
+    let tag_types: Vec<_> = tagged_file.tags().iter().map(|t| t.tag_type()).collect();
+    let mut changed = 0;
+
+    for tag_type in tag_types {
+        let Some(tag) = tagged_file.tag_mut(tag_type) else {
+            continue;
+        };
+        // Walk backwards so removals don't shift pictures still to visit
+        for index in (0..tag.pictures().len()).rev() {
+            match edit(&tag.pictures()[index])? {
+                PictureEdit::Keep => {}
+                PictureEdit::Replace(edited) => {
+                    tag.set_picture(index, edited);
+                    changed += 1;
+                }
+                PictureEdit::Remove => {
+                    tag.remove_picture(index);
+                    changed += 1;
+                }
+            }
+        }
+    }
+
+    if changed > 0 {
+        tagged_file
+            .save_to_path(path, write_settings::write_options())
+            .map_err(|e| format!("Failed to write tags: {}", e))?;
+    }
+    Ok(changed)
+}
+
+fn read_tags(path: &str) -> Result<TaggedFile, String> {
+    Probe::open(path)
+        .map_err(|e| format!("Failed to open file: {}", e))?
+        .read()
+        .map_err(|e| format!("Failed to read tags: {}", e))
+}
@@ -0,0 +1,177 @@
+//! Cover Art Archive search.
+//!
+//! Artwork is looked up by MusicBrainz release (or release group) id, or by
+//! artist and album name through a MusicBrainz release search first.
+//! MusicBrainz allows one request a second; the archive itself has no
+//! set limit.
+
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+use crate::http::{self, RateLimit};
+
+const MUSICBRAINZ_URL: &str = "https://musicbrainz.org/ws/2/release";
+const ARCHIVE_URL: &str = "https://coverartarchive.org";
+
+pub(crate) static MUSICBRAINZ_RATE_LIMIT: RateLimit = RateLimit::new(Duration::from_secs(1));
+
+/// Releases whose artwork is looked up for a text search.
+const SEARCH_RELEASES: usize = 8;
+
+/// What to look artwork up by. Ids win over text.
+#[derive(Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct CoverArtQuery {
+    /// MusicBrainz release id.
+    pub release_id: Option<String>,
+    /// MusicBrainz release group id: artwork chosen for the group as a whole.
+    pub release_group_id: Option<String>,
+    pub artist: Option<String>,
+    pub album: Option<String>,
+}
+
+#[derive(Clone, Serialize)]
+pub struct CoverCandidate {
+    pub release_id: Option<String>,
+    /// Release title and artist, for text searches.
+    pub title: Option<String>,
+    pub artist: Option<String>,
+    pub date: Option<String>,
+    pub country: Option<String>,
+    /// Full-size image.
+    pub image_url: String,
+    /// 500 px preview, when the archive has one.
+    pub thumbnail_url: Option<String>,
+    /// Marked as the front cover.
+    pub front: bool,
+    /// Archive image types ("Front", "Back", "Booklet", …).
+    pub types: Vec<String>,
+}
+
+#[derive(Deserialize)]
+struct ArchiveListing {
+    #[serde(default)]
+    images: Vec<ArchiveImage>,
+}
+
+#[derive(Deserialize)]
+struct ArchiveImage {
+    image: String,
+    #[serde(default)]
+    thumbnails: ArchiveThumbnails,
+    #[serde(default)]
+    front: bool,
+    #[serde(default)]
+    types: Vec<String>,
+}
+
+#[derive(Default, Deserialize)]
+struct ArchiveThumbnails {
+    #[serde(rename = "500")]
+    medium: Option<String>,
+    large: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct ReleaseSearch {
+    #[serde(default)]
+    releases: Vec<SearchRelease>,
+}
+
+#[derive(Deserialize)]
+struct SearchRelease {
+    id: String,
+    title: Option<String>,
+    date: Option<String>,
+    country: Option<String>,
+    #[serde(rename = "artist-credit", default)]
+    artist_credit: Vec<ArtistCredit>,
+}
+
+#[derive(Deserialize)]
+pub(crate) struct ArtistCredit {
+    name: String,
+    joinphrase: Option<String>,
+}
+
+/// Artwork candidates, front covers first.
+pub fn search(query: &CoverArtQuery) -> Result<Vec<CoverCandidate>, String> {
+    let id = |s: &Option<String>| {
+        s.as_deref()
+            .map(str::trim)
+            .filter(|s| !s.is_empty())
+            .map(str::to_string)
+    };
+    if let Some(release_id) = id(&query.release_id) {
+        return listing("release", &release_id, None);
+    }
+    if let Some(group_id) = id(&query.release_group_id) {
+        return listing("release-group", &group_id, None);
+    }
+    let Some(album) = id(&query.album) else {
+        return Err("Search needs a release id or an album name".to_string());
+    };
+
+    let mut lucene = format!("release:\"{}\"", lucene_escape(&album));
+    if let Some(artist) = id(&query.artist) {
+        lucene.push_str(&format!(" AND artist:\"{}\"", lucene_escape(&artist)));
+    }
+    let limit = SEARCH_RELEASES.to_string();
+    MUSICBRAINZ_RATE_LIMIT.wait();
+    let search: ReleaseSearch = http::get_json(
+        MUSICBRAINZ_URL,
+        &[("query", &lucene), ("fmt", "json"), ("limit", &limit)],
+    )?;
+
+    let mut candidates = Vec::new();
+    for release in &search.releases {
+        // Most releases have no artwork; those answer 404
+        let found = listing("release", &release.id, Some(release))?;
+        // One image per release is enough to choose between releases
+        candidates.extend(found.into_iter().next());
+    }
+    Ok(candidates)
+}
+
+/// The archive's images for a release or release group.
+fn listing(
+    kind: &str,
+    id: &str,
+    release: Option<&SearchRelease>,
+) -> Result<Vec<CoverCandidate>, String> {
+    let url = format!("{}/{}/{}", ARCHIVE_URL, kind, id);
+    let Some(listing) = http::get_json_if_found::<ArchiveListing>(&url, &[])? else {
+        return Ok(Vec::new());
+    };
+    let mut candidates: Vec<CoverCandidate> = listing
+        .images
+        .into_iter()
+        .map(|image| CoverCandidate {
+            release_id: (kind == "release").then(|| id.to_string()),
+            title: release.and_then(|r| r.title.clone()),
+            artist: release.map(|r| credit_name(&r.artist_credit)),
+            date: release.and_then(|r| r.date.clone()),
+            country: release.and_then(|r| r.country.clone()),
+            image_url: image.image,
+            thumbnail_url: image.thumbnails.medium.or(image.thumbnails.large),
+            front: image.front,
+            types: image.types,
+        })
+        .collect();
+    candidates.sort_by_key(|c| !c.front);
+    Ok(candidates)
+}
+
+pub(crate) fn credit_name(credits: &[ArtistCredit]) -> String {
+    credits
+        .iter()
+        .map(|c| format!("{}{}", c.name, c.joinphrase.as_deref().unwrap_or("")))
+        .collect::<String>()
+        .trim()
+        .to_string()
+}
+
+/// Escape text for use inside a quoted Lucene phrase.
+fn lucene_escape(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('"', "\\\"")
+}
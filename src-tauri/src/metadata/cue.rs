@@ -0,0 +1,259 @@
+//! Cue sheet parser.
+//!
+//! A cue sheet describes "virtual tracks" inside one (or several) audio image
+//! files: each TRACK has an `INDEX 01` start position in mm:ss:ff (75 frames per
+//! second). A track ends where the next track on the same FILE starts, or at
+//! the end of the file for the last one.
+//!
+//! The library and queue refer to a virtual track by the cue sheet's path and
+//! the track number, e.g. `Album.cue#03`.
+
+use serde::Serialize;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+/// CD frames per second used by cue sheet timestamps.
+const CUE_FRAMES_PER_SEC: f64 = 75.0;
+
+/// Separates the cue sheet path from the track number in a virtual track path.
+const VIRTUAL_TRACK_SEPARATOR: char = '#';
+
+#[derive(Clone, Serialize)]
+pub struct CueSheet {
+    pub title: Option<String>,
+    pub performer: Option<String>,
+    /// `REM DATE`, e.g. "1999" or "1999-05-01".
+    pub year: Option<u32>,
+    /// `REM GENRE`.
+    pub genre: Option<String>,
+    /// `REM DISCNUMBER`, for multi-disc sets with one sheet per disc.
+    pub disc_number: Option<u32>,
+    pub tracks: Vec<CueTrack>,
+}
+
+#[derive(Clone, Serialize)]
+pub struct CueTrack {
+    pub number: u32,
+    pub title: Option<String>,
+    pub performer: Option<String>,
+    pub songwriter: Option<String>,
+    /// Absolute path to the audio file this track lives in.
+    pub file: String,
+    /// Start offset into `file` (INDEX 01).
+    pub start_secs: f64,
+    /// End offset into `file`. `None` means play to the end of the file.
+    pub end_secs: Option<f64>,
+}
+
+pub fn is_cue_file(path: &str) -> bool {
+    Path::new(path)
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(|e| e.eq_ignore_ascii_case("cue"))
+        .unwrap_or(false)
+}
+
+/// Library/queue path of a cue-sheet track: `Album.cue#03`.
+pub fn virtual_track_path(cue_path: &str, number: u32) -> String {
+    format!("{}{}{:02}", cue_path, VIRTUAL_TRACK_SEPARATOR, number)
+}
+
+/// The cue sheet path and track number of a virtual track path, or `None`
+/// for the path of a plain file.
+pub fn split_virtual_track_path(path: &str) -> Option<(&str, u32)> {
+    let (cue_path, number) = path.rsplit_once(VIRTUAL_TRACK_SEPARATOR)?;
+    if !is_cue_file(cue_path) || !number.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    Some((cue_path, number.parse().ok()?))
+}
+
+/// The sheet and track a virtual track path points at, or `None` for the
+/// path of a plain file.
+pub fn find_virtual_track(path: &str) -> Result<Option<(CueSheet, CueTrack)>, String> {
+    let Some((cue_path, number)) = split_virtual_track_path(path) else {
+        return Ok(None);
+    };
+    let sheet = parse_cue_file(cue_path)?;
+    let track = sheet
+        .tracks
+        .iter()
+        .find(|t| t.number == number)
+        .cloned()
+        .ok_or_else(|| format!("Cue sheet has no track {}", number))?;
+    Ok(Some((sheet, track)))
+}
+
+/// The file holding a path's audio: the image file for cue-sheet tracks,
+/// otherwise the path itself.
+pub fn audio_file(path: &str) -> Result<String, String> {
+    Ok(match find_virtual_track(path)? {
+        Some((_, track)) => track.file,
+        None => path.to_string(),
+    })
+}
+
+/// The files holding the audio of `paths`: cue-sheet tracks become their
+/// image, once. A sheet that can't be read leaves the path as it is, for the
+/// caller to report when opening it fails.
+pub fn audio_files(paths: &[String]) -> Vec<String> {
+    let mut seen = HashSet::new();
+    paths
+        .iter()
+        .map(|path| audio_file(path).unwrap_or_else(|_| path.clone()))
+        .filter(|file| seen.insert(file.clone()))
+        .collect()
+}
+
+/// Read and parse a .cue file. FILE entries are resolved relative to the cue's folder.
+pub fn parse_cue_file(path: &str) -> Result<CueSheet, String> {
+    let bytes = std::fs::read(path).map_err(|e| format!("Failed to read cue sheet: {}", e))?;
+    // Cue sheets are frequently not UTF-8 — decode lossily rather than refusing them
+    let text = String::from_utf8_lossy(&bytes);
+    let base_dir = Path::new(path)
+        .parent()
+        .map(Path::to_path_buf)
+        .unwrap_or_default();
+    parse_cue(&text, &base_dir)
+}
+
+pub fn parse_cue(text: &str, base_dir: &Path) -> Result<CueSheet, String> {
+    let mut sheet = CueSheet {
+        title: None,
+        performer: None,
+        year: None,
+        genre: None,
+        disc_number: None,
+        tracks: Vec::new(),
+    };
+    let mut current_file: Option<String> = None;
+    let mut track: Option<CueTrack> = None;
+
+    for line in text.trim_start_matches('\u{feff}').lines() {
+        let line = line.trim();
+        let (keyword, rest) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+        let rest = rest.trim();
+
+        match keyword.to_ascii_uppercase().as_str() {
+            "FILE" => {
+                let name = unquote(strip_file_type(rest));
+                current_file = Some(resolve_path(base_dir, &name));
+            }
+            "TRACK" => {
+                if let Some(t) = track.take() {
+                    sheet.tracks.push(t);
+                }
+                let file = current_file
+                    .clone()
+                    .ok_or("Cue sheet has a TRACK before any FILE")?;
+                let number = rest
+                    .split_whitespace()
+                    .next()
+                    .and_then(|n| n.parse().ok())
+                    .unwrap_or(sheet.tracks.len() as u32 + 1);
+                track = Some(CueTrack {
+                    number,
+                    title: None,
+                    performer: None,
+                    songwriter: None,
+                    file,
+                    start_secs: 0.0,
+                    end_secs: None,
+                });
+            }
+            "TITLE" => match track.as_mut() {
+                Some(t) => t.title = Some(unquote(rest)),
+                None => sheet.title = Some(unquote(rest)),
+            },
+            "PERFORMER" => match track.as_mut() {
+                Some(t) => t.performer = Some(unquote(rest)),
+                None => sheet.performer = Some(unquote(rest)),
+            },
+            "SONGWRITER" => {
+                if let Some(t) = track.as_mut() {
+                    t.songwriter = Some(unquote(rest));
+                }
+            }
+            // Sheet-wide comments written by EAC, foobar2000 and others
+            "REM" if track.is_none() => {
+                let (field, value) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+                let value = unquote(value);
+                match field.to_ascii_uppercase().as_str() {
+                    "DATE" => sheet.year = value.get(..4).and_then(|y| y.parse().ok()),
+                    "GENRE" => sheet.genre = Some(value).filter(|g| !g.is_empty()),
+                    "DISCNUMBER" => sheet.disc_number = value.parse().ok(),
+                    _ => {}
+                }
+            }
+            "INDEX" => {
+                let mut parts = rest.split_whitespace();
+                let index_no = parts.next().and_then(|n| n.parse::<u32>().ok());
+                let time = parts.next().and_then(parse_cue_time);
+                if let (Some(1), Some(secs), Some(t)) = (index_no, time, track.as_mut()) {
+                    t.start_secs = secs;
+                }
+            }
+            _ => {}
+        }
+    }
+    if let Some(t) = track.take() {
+        sheet.tracks.push(t);
+    }
+
+    if sheet.tracks.is_empty() {
+        return Err("Cue sheet contains no tracks".to_string());
+    }
+
+    // Each track ends where the next one on the same file begins
+    for i in 0..sheet.tracks.len().saturating_sub(1) {
+        if sheet.tracks[i].file == sheet.tracks[i + 1].file {
+            sheet.tracks[i].end_secs = Some(sheet.tracks[i + 1].start_secs);
+        }
+    }
+
+    Ok(sheet)
+}
+
+/// Parse "mm:ss:ff" (75 frames per second) into seconds.
+fn parse_cue_time(s: &str) -> Option<f64> {
+    let mut parts = s.split(':').map(|p| p.parse::<u32>().ok());
+    let mm = parts.next()??;
+    let ss = parts.next()??;
+    let ff = parts.next()??;
+    Some(mm as f64 * 60.0 + ss as f64 + ff as f64 / CUE_FRAMES_PER_SEC)
+}
+
+/// `"Album.flac" WAVE` → `"Album.flac"`
+fn strip_file_type(s: &str) -> &str {
+    if let Some(quoted) = s.strip_prefix('"') {
+        match quoted.find('"') {
+            Some(end) => &s[..end + 2],
+            None => s,
+        }
+    } else {
+        s.rsplit_once(char::is_whitespace)
+            .map(|(n, _)| n)
+            .unwrap_or(s)
+    }
+}
+
+fn unquote(s: &str) -> String {
+    s.trim().trim_matches('"').to_string()
+}
+
+/// Resolve a FILE entry. Rips are often re-encoded after the cue was written
+/// (`FILE "Album.wav"` next to `Album.flac`), so fall back to the same stem
+/// with another audio extension when the named file doesn't exist.
+fn resolve_path(base_dir: &Path, name: &str) -> String {
+    let p = PathBuf::from(name);
+    let full = if p.is_absolute() { p } else { base_dir.join(p) };
+    if !full.exists() {
+        for ext in ["flac", "wav", "ape", "wv", "m4a"] {
+            let candidate = full.with_extension(ext);
+            if candidate.exists() {
+                return candidate.to_string_lossy().to_string();
+            }
+        }
+    }
+    full.to_string_lossy().to_string()
+}
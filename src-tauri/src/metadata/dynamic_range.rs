@@ -0,0 +1,52 @@
+//! Dynamic range tags, as written by the foobar2000 DR Meter and picked up
+//! by most library tools: `DYNAMIC RANGE` and `ALBUM DYNAMIC RANGE`, the
+//! rounded DR value. ID3v2 stores them as `TXXX` frames, MP4 as freeform
+//! atoms.
+
+use lofty::prelude::*;
+use lofty::probe::Probe;
+use lofty::tag::{Tag, TagType};
+
+use super::write_settings;
+
+const TRACK_FIELD: &str = "DYNAMIC RANGE";
+const ALBUM_FIELD: &str = "ALBUM DYNAMIC RANGE";
+
+/// Replace the DR tags of a file. A `None` album value removes the album
+/// tag, so it doesn't outlive a track-only analysis.
+pub fn write_dynamic_range(path: &str, track_dr: f64, album_dr: Option<f64>) -> Result<(), String> {
+    let tagged_file = Probe::open(path)
+        .map_err(|e| format!("Failed to open file: {}", e))?
+        .read()
+        .map_err(|e| format!("Failed to read tags: {}", e))?;
+    let tag_type = tagged_file.primary_tag_type();
+    let mut tag = tagged_file
+        .tag(tag_type)
+        .cloned()
+        .unwrap_or_else(|| Tag::new(tag_type));
+
+    let track_key = ItemKey::Unknown(field_key(tag_type, TRACK_FIELD));
+    let album_key = ItemKey::Unknown(field_key(tag_type, ALBUM_FIELD));
+    tag.remove_key(&track_key);
+    tag.remove_key(&album_key);
+    tag.insert_text(track_key, dr_text(track_dr));
+    if let Some(album_dr) = album_dr {
+        tag.insert_text(album_key, dr_text(album_dr));
+    }
+
+    write_settings::save_tag(&tag, path)
+}
+
+/// Field name in the given tag format.
+fn field_key(tag_type: TagType, field: &str) -> String {
+    match tag_type {
+        TagType::Mp4Ilst => format!("----:com.apple.iTunes:{}", field),
+        // ID3v2 stores it as a TXXX frame description
+        _ => field.to_string(),
+    }
+}
+
+/// Rounded to whole dB, the way DR values are shown.
+fn dr_text(dr: f64) -> String {
+    format!("{}", dr.round() as i32)
+}
@@ -0,0 +1,294 @@
+//! Repair of tags written in legacy codepages.
+//!
+//! Old taggers wrote ID3v1 and ID3v2.3 text in the system codepage (CP1251,
+//! Shift-JIS, GBK) while flagging it as Latin-1, so it reads back as
+//! mojibake: "Ïðèâåò" for "Привет". Such text maps back to its original
+//! bytes one character per byte, which are then decoded with the right
+//! codepage. Repaired text is written as a Unicode ID3v2 tag in the version
+//! [`super::write_settings`] asks for; a repaired ID3v1 tag, which can't hold
+//! it, is folded into the ID3v2 tag and removed.
+
+use encoding_rs::{Encoding, GBK, SHIFT_JIS, WINDOWS_1251};
+use lofty::prelude::*;
+use lofty::probe::Probe;
+use lofty::tag::{ItemValue, Tag, TagItem, TagType};
+use serde::{Deserialize, Serialize};
+
+use super::write_settings;
+
+/// Codepages tried when detecting, in order of preference on a tie.
+const LEGACY_ENCODINGS: [&Encoding; 3] = [WINDOWS_1251, SHIFT_JIS, GBK];
+
+/// Share of non-ASCII characters that have to look like real text in a
+/// codepage for it to be taken.
+const MIN_PLAUSIBLE: f64 = 0.75;
+
+/// A file and the codepage to repair it with, `None` to detect it.
+#[derive(Clone, Deserialize)]
+pub struct EncodingChoice {
+    pub path: String,
+    pub encoding: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct EncodingRepair {
+    /// Codepage the preview uses: the one asked for, else the detected one.
+    pub encoding: Option<String>,
+    /// Codepage the tags look like they were written in.
+    pub detected: Option<String>,
+    /// Fields that would change. Empty when there is nothing to repair.
+    pub fields: Vec<RepairedField>,
+}
+
+#[derive(Serialize)]
+pub struct RepairedField {
+    /// "ID3v1" or "ID3v2"
+    pub tag_type: String,
+    /// Field name as the tag names it, e.g. "TIT2"
+    pub key: String,
+    pub original: String,
+    pub repaired: String,
+}
+
+/// The codepage `text` was most likely written in, if it reads as mojibake.
+pub fn detect(text: &str) -> Option<&'static Encoding> {
+    let bytes = latin1_bytes(text)?;
+    if bytes.iter().filter(|b| **b >= 0x80).count() < 2 {
+        return None;
+    }
+    LEGACY_ENCODINGS
+        .into_iter()
+        .filter_map(|encoding| {
+            let decoded = encoding.decode_without_bom_handling_and_without_replacement(&bytes)?;
+            Some((encoding, plausibility(&decoded, encoding)))
+        })
+        .filter(|(_, score)| *score >= MIN_PLAUSIBLE)
+        .fold(
+            None,
+            |best: Option<(&Encoding, f64)>, (encoding, score)| match best {
+                Some((_, best_score)) if best_score >= score => best,
+                _ => Some((encoding, score)),
+            },
+        )
+        .map(|(encoding, _)| encoding)
+}
+
+/// What a file's ID3 tags would read as when decoded with `encoding`
+/// (a WHATWG label such as "windows-1251", "shift_jis" or "gbk"), or with
+/// the detected codepage when `None`.
+pub fn preview_repair(path: &str, encoding: Option<&str>) -> Result<EncodingRepair, String> {
+    let tagged_file = Probe::open(path)
+        .map_err(|e| format!("Failed to open file: {}", e))?
+        .read()
+        .map_err(|e| format!("Failed to read tags: {}", e))?;
+    let tags: Vec<&Tag> = [TagType::Id3v2, TagType::Id3v1]
+        .into_iter()
+        .filter_map(|tag_type| tagged_file.tag(tag_type))
+        .collect();
+    Ok(plan_repair(&tags, parse_encoding(encoding)?))
+}
+
+/// Rewrite a file's ID3 tags decoded with `encoding`, or the detected
+/// codepage when `None`. Returns what was changed.
+pub fn repair(path: &str, encoding: Option<&str>) -> Result<EncodingRepair, String> {
+    let tagged_file = Probe::open(path)
+        .map_err(|e| format!("Failed to open file: {}", e))?
+        .read()
+        .map_err(|e| format!("Failed to read tags: {}", e))?;
+    let id3v2 = tagged_file.tag(TagType::Id3v2);
+    let id3v1 = tagged_file.tag(TagType::Id3v1);
+    let tags: Vec<&Tag> = id3v2.into_iter().chain(id3v1).collect();
+    let forced = parse_encoding(encoding)?;
+    let plan = plan_repair(&tags, forced);
+    let Some(decoder) = forced.or_else(|| detect_tags(&tags)) else {
+        return Ok(plan);
+    };
+    if plan.fields.is_empty() {
+        return Ok(plan);
+    }
+    let forced = forced.is_some();
+
+    // ID3v1 can only hold Latin-1, so everything goes into the ID3v2 tag
+    // (or the primary tag, for formats without ID3v2)
+    let target_type = if tagged_file.supports_tag_type(TagType::Id3v2) {
+        TagType::Id3v2
+    } else {
+        tagged_file.primary_tag_type()
+    };
+    let mut target = tagged_file
+        .tag(target_type)
+        .cloned()
+        .unwrap_or_else(|| Tag::new(target_type));
+    let items: Vec<TagItem> = target.items().cloned().collect();
+    target.retain(|_| false);
+    for item in items {
+        target.push_unchecked(repair_item(&item, decoder, forced).unwrap_or(item));
+    }
+
+    let id3v1_repaired = plan.fields.iter().any(|f| f.tag_type == "ID3v1");
+    if let Some(id3v1) = id3v1.filter(|_| id3v1_repaired && target_type != TagType::Id3v1) {
+        for item in id3v1.items() {
+            if target.get(item.key()).is_none() {
+                let item = repair_item(item, decoder, forced).unwrap_or_else(|| item.clone());
+                target.push(item);
+            }
+        }
+    }
+
+    write_settings::save_tag(&target, path)?;
+    if id3v1_repaired && target_type != TagType::Id3v1 {
+        TagType::Id3v1
+            .remove_from_path(path)
+            .map_err(|e| format!("Failed to remove ID3v1 tag: {}", e))?;
+    }
+    Ok(plan)
+}
+
+fn parse_encoding(label: Option<&str>) -> Result<Option<&'static Encoding>, String> {
+    label
+        .map(|label| {
+            Encoding::for_label(label.trim().as_bytes())
+                .ok_or_else(|| format!("Unknown encoding: {}", label))
+        })
+        .transpose()
+}
+
+/// The codepage detected over the text of `tags` that could be mojibake.
+fn detect_tags(tags: &[&Tag]) -> Option<&'static Encoding> {
+    let texts: Vec<&str> = tags
+        .iter()
+        .flat_map(|tag| tag.items())
+        .filter_map(|item| item.value().text())
+        .filter(|text| latin1_bytes(text).is_some())
+        .collect();
+    detect(&texts.join("\n"))
+}
+
+/// Fields of `tags` that decode differently with `forced`, or with the
+/// detected codepage.
+fn plan_repair(tags: &[&Tag], forced: Option<&'static Encoding>) -> EncodingRepair {
+    let detected = detect_tags(tags);
+
+    let mut fields = Vec::new();
+    if let Some(encoding) = forced.or(detected) {
+        for tag in tags {
+            for item in tag.items() {
+                let Some(repaired) = repair_item(item, encoding, forced.is_some()) else {
+                    continue;
+                };
+                let (Some(original), Some(repaired)) =
+                    (item.value().text(), repaired.value().text())
+                else {
+                    continue;
+                };
+                fields.push(RepairedField {
+                    tag_type: tag_type_name(tag.tag_type()).to_string(),
+                    key: item
+                        .key()
+                        .map_key(tag.tag_type(), true)
+                        .unwrap_or_default()
+                        .to_string(),
+                    original: original.to_string(),
+                    repaired: repaired.to_string(),
+                });
+            }
+        }
+    }
+
+    EncodingRepair {
+        encoding: forced.or(detected).map(|e| e.name().to_string()),
+        detected: detected.map(|e| e.name().to_string()),
+        fields,
+    }
+}
+
+/// `item` with its text decoded with `encoding`, `None` if that changes
+/// nothing. Unless `forced`, a field is only touched when the text reads
+/// as that codepage on its own, so genuine Latin-1 ("Café") next to
+/// mojibake is left alone.
+fn repair_item(item: &TagItem, encoding: &'static Encoding, forced: bool) -> Option<TagItem> {
+    let text = item.value().text()?;
+    let bytes = latin1_bytes(text)?;
+    let decoded = encoding.decode_without_bom_handling_and_without_replacement(&bytes)?;
+    if decoded == text || (!forced && plausibility(&decoded, encoding) < MIN_PLAUSIBLE) {
+        return None;
+    }
+    let mut repaired = TagItem::new(item.key().clone(), ItemValue::Text(decoded.into_owned()));
+    repaired.set_lang(*item.lang());
+    repaired.set_description(item.description().to_string());
+    Some(repaired)
+}
+
+/// The bytes text decoded as Latin-1 came from; `None` if it holds
+/// characters Latin-1 doesn't have (it was decoded properly) or is plain
+/// ASCII.
+fn latin1_bytes(text: &str) -> Option<Vec<u8>> {
+    let bytes = text
+        .chars()
+        .map(|c| u8::try_from(u32::from(c)).ok())
+        .collect::<Option<Vec<u8>>>()?;
+    bytes.iter().any(|b| *b >= 0x80).then_some(bytes)
+}
+
+/// Share of the non-ASCII characters of `text` that are common in text
+/// written in `encoding`. Non-ASCII characters inside a word with Latin
+/// letters don't count: that's Latin-1 ("Motörhead") read the wrong way.
+fn plausibility(text: &str, encoding: &'static Encoding) -> f64 {
+    let mut non_ascii = 0;
+    let mut plausible = 0;
+    for word in text.split_whitespace() {
+        let mixed = word.chars().any(|c| c.is_ascii_alphabetic());
+        for c in word.chars().filter(|c| !c.is_ascii()) {
+            non_ascii += 1;
+            if !mixed && is_common(c, encoding) {
+                plausible += 1;
+            }
+        }
+    }
+    if non_ascii == 0 {
+        return 0.0;
+    }
+    f64::from(plausible) / f64::from(non_ascii)
+}
+
+fn is_common(c: char, encoding: &'static Encoding) -> bool {
+    let cjk_punctuation = matches!(c, '\u{3000}'..='\u{303F}' | '\u{FF01}'..='\u{FF5E}');
+    if encoding == WINDOWS_1251 {
+        // Russian, Ukrainian, Belarusian, Serbian letters and typography
+        matches!(c, '\u{0401}'..='\u{045F}' | '\u{0490}' | '\u{0491}')
+            || matches!(c, '«' | '»' | '–' | '—' | '…' | '№' | '“' | '”' | '„')
+    } else if encoding == SHIFT_JIS {
+        // Kana, and kanji in JIS level 1 and the start of level 2; GBK
+        // text read as Shift-JIS lands in half-width kana and the rest of
+        // level 2 instead
+        matches!(c, '\u{3041}'..='\u{30FF}')
+            || cjk_punctuation
+            || (matches!(c, '\u{4E00}'..='\u{9FFF}')
+                && lead_byte(c, SHIFT_JIS).is_some_and(|b| b <= 0x9F))
+    } else if encoding == GBK {
+        // GB2312 hanzi; Shift-JIS kana read as GBK land outside it
+        cjk_punctuation
+            || (matches!(c, '\u{4E00}'..='\u{9FFF}')
+                && lead_byte(c, GBK).is_some_and(|b| (0xB0..=0xF7).contains(&b)))
+    } else {
+        false
+    }
+}
+
+/// First byte of `c` in `encoding`, `None` if it has no encoding there.
+fn lead_byte(c: char, encoding: &'static Encoding) -> Option<u8> {
+    let mut buf = [0u8; 4];
+    let (bytes, _, unmappable) = encoding.encode(c.encode_utf8(&mut buf));
+    if unmappable {
+        return None;
+    }
+    bytes.first().copied()
+}
+
+fn tag_type_name(tag_type: TagType) -> &'static str {
+    match tag_type {
+        TagType::Id3v1 => "ID3v1",
+        TagType::Id3v2 => "ID3v2",
+        _ => "Other",
+    }
+}
@@ -0,0 +1,110 @@
+//! Raw tag field access for fields the editor doesn't model: `TXXX`
+//! descriptions, arbitrary Vorbis comments, APEv2 items, MP4 freeform atoms.
+//!
+//! Fields are named as the format names them ("TPE1", "ARTIST", "©ART",
+//! "----:com.apple.iTunes:MOOD"), in the file's primary tag like
+//! [`super::writer`]. Binary items are left out.
+
+use lofty::prelude::*;
+use lofty::probe::Probe;
+use lofty::tag::{ItemValue, Tag, TagItem, TagType};
+use serde::Serialize;
+
+use super::write_settings;
+
+#[derive(Serialize)]
+pub struct TagField {
+    pub key: String,
+    /// One entry per value of a multi-value field, in file order.
+    pub values: Vec<String>,
+}
+
+#[derive(Serialize)]
+pub struct TagFields {
+    /// "ID3v2", "Vorbis Comments", "APEv2", "MP4", ...
+    pub tag_type: String,
+    pub fields: Vec<TagField>,
+}
+
+/// Every text field in the file's primary tag, in the order first seen.
+pub fn read_tag_fields(path: &str) -> Result<TagFields, String> {
+    let tagged_file = Probe::open(path)
+        .map_err(|e| format!("Failed to open file: {}", e))?
+        .read()
+        .map_err(|e| format!("Failed to read tags: {}", e))?;
+    let tag_type = tagged_file.primary_tag_type();
+
+    let mut fields: Vec<TagField> = Vec::new();
+    if let Some(tag) = tagged_file.tag(tag_type) {
+        for item in tag.items() {
+            let value = match item.value() {
+                ItemValue::Text(text) | ItemValue::Locator(text) => text,
+                ItemValue::Binary(_) => continue,
+            };
+            let Some(key) = item.key().map_key(tag_type, true) else {
+                continue;
+            };
+            match fields.iter_mut().find(|f| f.key == key) {
+                Some(field) => field.values.push(value.clone()),
+                None => fields.push(TagField {
+                    key: key.to_string(),
+                    values: vec![value.clone()],
+                }),
+            }
+        }
+    }
+
+    Ok(TagFields {
+        tag_type: tag_type_name(tag_type).to_string(),
+        fields,
+    })
+}
+
+/// Replace every value of one field. No values (or only blank ones) removes
+/// the field.
+///
+/// ID3v2 keys of four characters are frame ids; any other key is a `TXXX`
+/// description.
+pub fn write_tag_field(path: &str, key: &str, values: &[String]) -> Result<(), String> {
+    let key = key.trim();
+    if key.is_empty() || key.chars().any(char::is_control) {
+        return Err("Invalid field name".to_string());
+    }
+
+    let tagged_file = Probe::open(path)
+        .map_err(|e| format!("Failed to open file: {}", e))?
+        .read()
+        .map_err(|e| format!("Failed to read tags: {}", e))?;
+    let tag_type = tagged_file.primary_tag_type();
+    if tag_type == TagType::VorbisComments && key.contains('=') {
+        return Err("Vorbis comment names can't contain '='".to_string());
+    }
+    let mut tag = tagged_file
+        .tag(tag_type)
+        .cloned()
+        .unwrap_or_else(|| Tag::new(tag_type));
+
+    let item_key = ItemKey::from_key(tag_type, key);
+    tag.remove_key(&item_key);
+    for value in values.iter().map(|v| v.trim()).filter(|v| !v.is_empty()) {
+        tag.push(TagItem::new(
+            item_key.clone(),
+            ItemValue::Text(value.to_string()),
+        ));
+    }
+
+    write_settings::save_tag(&tag, path)
+}
+
+fn tag_type_name(tag_type: TagType) -> &'static str {
+    match tag_type {
+        TagType::Id3v2 => "ID3v2",
+        TagType::Id3v1 => "ID3v1",
+        TagType::Ape => "APEv2",
+        TagType::VorbisComments => "Vorbis Comments",
+        TagType::Mp4Ilst => "MP4",
+        TagType::RiffInfo => "RIFF INFO",
+        TagType::AiffText => "AIFF Text",
+        _ => "Unknown",
+    }
+}
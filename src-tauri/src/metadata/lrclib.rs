@@ -0,0 +1,119 @@
+//! Online lyrics from LRCLIB.
+//!
+//! Lyrics are looked up by artist, title, album and duration; LRCLIB matches
+//! the duration within a couple of seconds. Answers, including "not found",
+//! are cached as JSON files under the app data directory so a track is only
+//! asked about once (misses are retried after [`MISS_TTL_SECS`]).
+
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::http;
+
+const GET_URL: &str = "https://lrclib.net/api/get";
+
+/// Subfolder of the app data directory holding cached answers.
+pub const CACHE_DIR: &str = "lyrics";
+
+/// How long a "not found" answer is trusted, since lyrics get added.
+const MISS_TTL_SECS: u64 = 7 * 24 * 60 * 60;
+
+/// What to look lyrics up by.
+#[derive(Clone)]
+pub struct LyricsQuery {
+    pub artist: String,
+    pub title: String,
+    pub album: Option<String>,
+    pub duration_secs: f64,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct OnlineLyrics {
+    /// Plain text lyrics.
+    pub plain: Option<String>,
+    /// LRC-formatted synced lyrics.
+    pub synced: Option<String>,
+    /// The track is known to have no lyrics.
+    pub instrumental: bool,
+}
+
+#[derive(Serialize, Deserialize)]
+struct CacheEntry {
+    fetched_at: u64,
+    lyrics: Option<OnlineLyrics>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct GetResponse {
+    plain_lyrics: Option<String>,
+    synced_lyrics: Option<String>,
+    #[serde(default)]
+    instrumental: bool,
+}
+
+/// Lyrics for a track, from the cache or LRCLIB. `Ok(None)` means LRCLIB
+/// doesn't know the track.
+pub fn lookup(app_data_dir: &Path, query: &LyricsQuery) -> Result<Option<OnlineLyrics>, String> {
+    let dir = app_data_dir.join(CACHE_DIR);
+    let cache_file = dir.join(format!("{}.json", cache_key(query)));
+    let now = unix_now();
+    if let Some(entry) = std::fs::read_to_string(&cache_file)
+        .ok()
+        .and_then(|data| serde_json::from_str::<CacheEntry>(&data).ok())
+    {
+        if entry.lyrics.is_some() || now.saturating_sub(entry.fetched_at) < MISS_TTL_SECS {
+            return Ok(entry.lyrics);
+        }
+    }
+
+    let duration = (query.duration_secs.round() as u64).to_string();
+    let mut params = vec![
+        ("artist_name", query.artist.as_str()),
+        ("track_name", query.title.as_str()),
+        ("duration", duration.as_str()),
+    ];
+    if let Some(album) = &query.album {
+        params.push(("album_name", album));
+    }
+    let lyrics = http::get_json_if_found::<GetResponse>(GET_URL, &params)?.map(|r| OnlineLyrics {
+        plain: r.plain_lyrics.filter(|s| !s.trim().is_empty()),
+        synced: r.synced_lyrics.filter(|s| !s.trim().is_empty()),
+        instrumental: r.instrumental,
+    });
+
+    // A failed cache write only costs a repeat request
+    let entry = CacheEntry {
+        fetched_at: now,
+        lyrics: lyrics.clone(),
+    };
+    if let Ok(json) = serde_json::to_string(&entry) {
+        let _ = std::fs::create_dir_all(&dir).and_then(|()| std::fs::write(&cache_file, json));
+    }
+    Ok(lyrics)
+}
+
+/// FNV-1a over the query, case-insensitively, with the duration in seconds.
+fn cache_key(query: &LyricsQuery) -> String {
+    let text = format!(
+        "{}\n{}\n{}\n{}",
+        query.artist.to_lowercase(),
+        query.title.to_lowercase(),
+        query.album.as_deref().unwrap_or("").to_lowercase(),
+        query.duration_secs.round() as u64
+    );
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for byte in text.bytes() {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(0x0000_0100_0000_01b3);
+    }
+    format!("{:016x}", hash)
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
@@ -0,0 +1,230 @@
+//! Lyrics, unsynced and synced.
+//!
+//! Unsynced lyrics live in USLT (ID3v2), LYRICS (Vorbis comments, APE) or
+//! ©lyr (MP4). Some taggers write UNSYNCEDLYRICS to Vorbis comments instead,
+//! which is read as a fallback.
+//!
+//! Synced (timestamped) lyrics come from a sidecar `.lrc` file with the same
+//! name as the track, an ID3v2 SYLT frame, or embedded lyrics that are
+//! themselves in LRC format, in that order.
+
+use lofty::config::ParseOptions;
+use lofty::id3::v2::{Frame, SynchronizedTextFrame, TimestampFormat};
+use lofty::mpeg::MpegFile;
+use lofty::prelude::*;
+use lofty::probe::Probe;
+use lofty::tag::{ItemKey, Tag};
+use serde::Serialize;
+use std::path::Path;
+
+use super::write_settings;
+
+/// One timestamped line of synced lyrics.
+#[derive(Clone, Serialize)]
+pub struct LyricLine {
+    /// Where the line starts, in milliseconds from the start of the track.
+    pub time_ms: u64,
+    pub text: String,
+}
+
+/// Vorbis field used by foobar2000 and Mp3tag for unsynced lyrics.
+const UNSYNCED_LYRICS_KEY: &str = "UNSYNCEDLYRICS";
+
+/// The file's unsynced lyrics, from its primary tag first, then any other.
+/// `Ok(None)` means it has none.
+pub fn read_lyrics(path: &str) -> Result<Option<String>, String> {
+    let tagged_file = Probe::open(path)
+        .map_err(|e| format!("Failed to open file: {}", e))?
+        .read()
+        .map_err(|e| format!("Failed to read tags: {}", e))?;
+
+    let primary = tagged_file.primary_tag_type();
+    let mut tags: Vec<_> = tagged_file.tags().iter().collect();
+    tags.sort_by_key(|t| t.tag_type() != primary);
+
+    let unsynced = ItemKey::Unknown(UNSYNCED_LYRICS_KEY.to_string());
+    let lyrics = tags
+        .into_iter()
+        .flat_map(|t| [t.get_string(&ItemKey::Lyrics), t.get_string(&unsynced)])
+        .flatten()
+        .map(normalize_lines)
+        .find(|text| !text.is_empty());
+    Ok(lyrics)
+}
+
+/// Embed unsynced lyrics in the file's primary tag, replacing any there.
+pub fn write_lyrics(path: &str, text: &str) -> Result<(), String> {
+    let tagged_file = Probe::open(path)
+        .map_err(|e| format!("Failed to open file: {}", e))?
+        .read()
+        .map_err(|e| format!("Failed to read tags: {}", e))?;
+    let tag_type = tagged_file.primary_tag_type();
+    let mut tag = tagged_file
+        .tag(tag_type)
+        .cloned()
+        .unwrap_or_else(|| Tag::new(tag_type));
+
+    tag.remove_key(&ItemKey::Unknown(UNSYNCED_LYRICS_KEY.to_string()));
+    tag.insert_text(ItemKey::Lyrics, normalize_lines(text));
+    write_settings::save_tag(&tag, path)
+}
+
+/// Save LRC text as the track's sidecar `.lrc` file. An existing sidecar is
+/// kept; returns whether the file was written.
+pub fn save_lrc(path: &str, lrc: &str) -> Result<bool, String> {
+    let sidecar = Path::new(path).with_extension("lrc");
+    if sidecar.exists() {
+        return Ok(false);
+    }
+    std::fs::write(&sidecar, normalize_lines(lrc)).map_err(|e| format!("Write failed: {}", e))?;
+    Ok(true)
+}
+
+/// Line endings vary with the tagger; the UI expects `\n`.
+fn normalize_lines(text: &str) -> String {
+    text.replace("\r\n", "\n")
+        .replace('\r', "\n")
+        .trim()
+        .to_string()
+}
+
+/// The file's synced lyrics, sorted by time. `Ok(None)` means it has none.
+pub fn read_synced_lyrics(path: &str) -> Result<Option<Vec<LyricLine>>, String> {
+    let sidecar = Path::new(path).with_extension("lrc");
+    if let Ok(bytes) = std::fs::read(&sidecar) {
+        let lines = parse_lrc(&String::from_utf8_lossy(&bytes));
+        if !lines.is_empty() {
+            return Ok(Some(lines));
+        }
+    }
+    if let Some(lines) = read_sylt(path)? {
+        return Ok(Some(lines));
+    }
+    let embedded = read_lyrics(path)?
+        .map(|text| parse_lrc(&text))
+        .filter(|lines| !lines.is_empty());
+    Ok(embedded)
+}
+
+/// Parse LRC text: `[mm:ss.xx]` timestamps (several may share a line), an
+/// optional `[offset:±ms]` tag, and enhanced `<mm:ss.xx>` word timings, which
+/// are dropped. Other `[tag:value]` lines and untimed text are ignored.
+pub fn parse_lrc(text: &str) -> Vec<LyricLine> {
+    let text = text.trim_start_matches('\u{feff}');
+    // A positive offset makes lyrics appear sooner
+    let offset_ms: i64 = text
+        .lines()
+        .find_map(|line| {
+            let value = line.trim().strip_prefix("[offset:")?.strip_suffix(']')?;
+            value.trim().parse().ok()
+        })
+        .unwrap_or(0);
+
+    let mut lines = Vec::new();
+    for line in text.lines() {
+        let mut rest = line.trim();
+        let mut times = Vec::new();
+        while let Some(tag) = rest.strip_prefix('[') {
+            let Some(end) = tag.find(']') else {
+                break;
+            };
+            match parse_timestamp(&tag[..end]) {
+                Some(ms) => times.push(ms),
+                None => break,
+            }
+            rest = &tag[end + 1..];
+        }
+        let words = strip_word_timings(rest);
+        for time in times {
+            lines.push(LyricLine {
+                time_ms: (time - offset_ms).max(0) as u64,
+                text: words.clone(),
+            });
+        }
+    }
+    lines.sort_by_key(|l| l.time_ms);
+    lines
+}
+
+/// "mm:ss", "mm:ss.x[x[x]]" or "mm:ss:xx" to milliseconds.
+fn parse_timestamp(stamp: &str) -> Option<i64> {
+    let (minutes, rest) = stamp.split_once(':')?;
+    let (seconds, fraction) = match rest.split_once(['.', ':']) {
+        Some((seconds, fraction)) => (seconds, fraction),
+        None => (rest, ""),
+    };
+    let minutes: i64 = minutes.trim().parse().ok()?;
+    let seconds: i64 = seconds.trim().parse().ok()?;
+    let fraction_ms = match fraction.len() {
+        0 => 0,
+        len @ 1..=3 => fraction.parse::<i64>().ok()? * 10_i64.pow(3 - len as u32),
+        _ => fraction[..3].parse().ok()?,
+    };
+    Some((minutes * 60 + seconds) * 1000 + fraction_ms)
+}
+
+/// Remove enhanced LRC word timings: "<00:01.20>Hello <00:01.80>world".
+fn strip_word_timings(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('<') {
+        out.push_str(&rest[..start]);
+        match rest[start..].find('>') {
+            Some(end) if parse_timestamp(&rest[start + 1..start + end]).is_some() => {
+                rest = &rest[start + end + 1..];
+            }
+            _ => {
+                out.push('<');
+                rest = &rest[start + 1..];
+            }
+        }
+    }
+    out.push_str(rest);
+    out.trim().to_string()
+}
+
+/// Lyrics from an MP3's SYLT frame, if it has one timed in milliseconds.
+fn read_sylt(path: &str) -> Result<Option<Vec<LyricLine>>, String> {
+    let is_mp3 = Path::new(path)
+        .extension()
+        .is_some_and(|e| e.eq_ignore_ascii_case("mp3"));
+    if !is_mp3 {
+        return Ok(None);
+    }
+    let mut file = std::fs::File::open(path).map_err(|e| format!("Failed to open file: {}", e))?;
+    let mpeg = MpegFile::read_from(&mut file, ParseOptions::new().read_properties(false))
+        .map_err(|e| format!("Failed to read tags: {}", e))?;
+    let Some(id3v2) = mpeg.id3v2() else {
+        return Ok(None);
+    };
+
+    for frame in id3v2 {
+        // lofty leaves SYLT frames unparsed
+        let Frame::Binary(binary) = frame else {
+            continue;
+        };
+        if frame.id().as_str() != "SYLT" {
+            continue;
+        }
+        let Ok(sylt) = SynchronizedTextFrame::parse(&binary.data, frame.flags()) else {
+            continue;
+        };
+        if sylt.timestamp_format != TimestampFormat::MS {
+            continue;
+        }
+        let mut lines: Vec<LyricLine> = sylt
+            .content
+            .into_iter()
+            .map(|(time_ms, text)| LyricLine {
+                time_ms: time_ms as u64,
+                // Many taggers start each entry with the line break
+                text: text.trim().to_string(),
+            })
+            .collect();
+        if !lines.is_empty() {
+            lines.sort_by_key(|l| l.time_ms);
+            return Ok(Some(lines));
+        }
+    }
+    Ok(None)
+}
@@ -1 +1,17 @@
+pub mod acoustid;
+pub mod chapters;
+pub mod cover;
+pub mod coverart;
+pub mod cue;
+pub mod dynamic_range;
+pub mod encoding;
+pub mod fields;
+pub mod lrclib;
+pub mod lyrics;
+pub mod rating;
 pub mod reader;
+pub mod replaygain;
+pub mod technical;
+pub mod values;
+pub mod write_settings;
+pub mod writer;
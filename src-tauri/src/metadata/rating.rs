@@ -0,0 +1,124 @@
+//! Star ratings stored in tags.
+//!
+//! ID3v2 files get a POPM frame using the Windows Media Player email and
+//! byte scale, which Explorer, foobar2000 and MusicBee all read, plus a
+//! `TXXX:FMPS_Rating` frame. Every other format gets an FMPS_RATING field
+//! (0.0–1.0, in steps of 0.2 per star).
+//!
+//! Reading also accepts POPM frames from other players and the 0–100
+//! `RATING` / `rate` fields some taggers write.
+
+use lofty::id3::v2::{Frame, Id3v2Tag, PopularimeterFrame};
+use lofty::prelude::*;
+use lofty::probe::Probe;
+use lofty::tag::{ItemValue, Tag, TagItem, TagType};
+
+use super::write_settings;
+
+/// POPM owner that Windows and most players read stars from.
+const POPM_EMAIL: &str = "Windows Media Player 9 Series";
+
+/// POPM byte for 1–5 stars, per the Windows Media Player convention.
+const POPM_STARS: [u8; 5] = [1, 64, 128, 196, 255];
+
+/// Read a 1–5 star rating from a tag. FMPS_RATING wins over POPM, since it
+/// has a single meaning in every format; unrated files give `None`. lofty
+/// keeps POPM frames out of the generic tag, so MP3s pass their raw ID3v2 tag.
+pub fn read_rating(tag: &Tag, id3v2: Option<&Id3v2Tag>) -> Option<u8> {
+    let fmps = tag
+        .get_string(&ItemKey::Unknown(fmps_key(tag.tag_type()).to_string()))
+        .and_then(|v| v.trim().parse::<f64>().ok())
+        .filter(|v| (0.0..=1.0).contains(v))
+        .map(|v| (v * 5.0).round() as u8);
+    if let Some(stars) = fmps {
+        return (stars > 0).then_some(stars);
+    }
+
+    let mut fallback = None;
+    for frame in id3v2.into_iter().flatten() {
+        let Frame::Popularimeter(popm) = frame else {
+            continue;
+        };
+        let stars = popm_stars(popm.rating);
+        if popm.email == POPM_EMAIL {
+            return stars;
+        }
+        fallback = fallback.or(stars);
+    }
+    fallback.or_else(|| {
+        tag.get_string(&ItemKey::Popularimeter)
+            .and_then(|v| v.trim().parse::<f64>().ok())
+            .and_then(percent_stars)
+    })
+}
+
+/// Stars for a POPM byte, using the ranges Windows reads them with.
+fn popm_stars(byte: u8) -> Option<u8> {
+    match byte {
+        0 => None,
+        1..=31 => Some(1),
+        32..=95 => Some(2),
+        96..=159 => Some(3),
+        160..=223 => Some(4),
+        224..=255 => Some(5),
+    }
+}
+
+/// Stars for a `RATING` / `rate` value: 0–100, or 1–5 from taggers that
+/// store stars directly.
+fn percent_stars(value: f64) -> Option<u8> {
+    let stars = if value <= 5.0 { value } else { value / 20.0 };
+    let stars = stars.round();
+    (1.0..=5.0).contains(&stars).then_some(stars as u8)
+}
+
+/// Write a 1–5 star rating to the file's tags, or remove it with `None`.
+pub fn write_rating(path: &str, stars: Option<u8>) -> Result<(), String> {
+    if stars.is_some_and(|s| !(1..=5).contains(&s)) {
+        return Err("Rating must be 1–5 stars".to_string());
+    }
+
+    let tagged_file = Probe::open(path)
+        .map_err(|e| format!("Failed to open file: {}", e))?
+        .read()
+        .map_err(|e| format!("Failed to read tags: {}", e))?;
+    let tag_type = tagged_file.primary_tag_type();
+    let mut tag = tagged_file
+        .tag(tag_type)
+        .cloned()
+        .unwrap_or_else(|| Tag::new(tag_type));
+
+    let fmps_key = ItemKey::Unknown(fmps_key(tag_type).to_string());
+    tag.remove_key(&fmps_key);
+    // Stale POPM / RATING values would be read back over a cleared rating
+    tag.remove_key(&ItemKey::Popularimeter);
+
+    if let Some(stars) = stars {
+        tag.insert_text(fmps_key, format!("{:.1}", f64::from(stars) / 5.0));
+        if tag_type == TagType::Id3v2 {
+            let popm = PopularimeterFrame::new(
+                POPM_EMAIL.to_string(),
+                POPM_STARS[usize::from(stars) - 1],
+                0,
+            )
+            .as_bytes()
+            .map_err(|e| format!("Failed to encode rating: {}", e))?;
+            tag.insert(TagItem::new(
+                ItemKey::Popularimeter,
+                ItemValue::Binary(popm),
+            ));
+        }
+    }
+
+    write_settings::save_tag(&tag, path)
+}
+
+/// FMPS_RATING field name in the given tag format.
+fn fmps_key(tag_type: TagType) -> &'static str {
+    match tag_type {
+        // Stored as a TXXX frame description
+        TagType::Id3v2 => "FMPS_Rating",
+        TagType::Mp4Ilst => "----:com.apple.iTunes:FMPS_Rating",
+        _ => "FMPS_RATING",
+    }
+}
@@ -1,12 +1,29 @@
 use base64::Engine;
+use lofty::aac::AacFile;
+use lofty::config::ParseOptions;
+use lofty::file::TaggedFile;
+use lofty::id3::v2::Id3v2Tag;
+use lofty::mp4::Mp4File;
+use lofty::mpeg::MpegFile;
+use lofty::picture::PictureType;
 use lofty::prelude::*;
 use lofty::probe::Probe;
-use serde::Serialize;
+use lofty::tag::Tag;
+use serde::{Deserialize, Serialize};
+use std::io::Cursor;
 use std::path::Path;
 
-#[derive(Clone, Serialize)]
+use super::chapters::{self, Chapter};
+use super::cover;
+use super::cue::{self, CueSheet, CueTrack};
+use super::technical::{self, BitrateMode, StreamDetails};
+use super::{rating, values};
+use crate::audio::replaygain::{replaygain_from_tag, ReplayGainInfo};
+
+#[derive(Clone, Serialize, Deserialize)]
 pub struct TrackMetadata {
     pub title: Option<String>,
+    /// Artist tag as written; repeated fields are joined with "; ".
     pub artist: Option<String>,
     pub album: Option<String>,
     pub album_artist: Option<String>,
@@ -22,13 +39,60 @@ pub struct TrackMetadata {
     pub file_name: String,
     pub format: String,
     pub has_album_art: bool,
+    pub composer: Option<String>,
+    /// Classical work the track belongs to (WORK / ©wrk).
+    pub work: Option<String>,
+    /// Movement name (MOVEMENTNAME / MVNM).
+    pub movement: Option<String>,
+    pub movement_number: Option<u32>,
+    /// COMPILATION / TCMP / cpil flag.
+    pub compilation: bool,
+    /// Sort tags (ARTISTSORT / TSOP / soar and friends), e.g. "Beatles, The".
+    pub artist_sort: Option<String>,
+    pub album_sort: Option<String>,
+    pub album_artist_sort: Option<String>,
+    /// Every credited artist, main artist first, split from repeated fields,
+    /// "; " lists and "feat." credits.
+    pub artists: Vec<String>,
+    pub genres: Vec<String>,
+    pub composers: Vec<String>,
+    /// 1–5 stars from POPM / FMPS_RATING, if the file is rated.
+    pub rating: Option<u8>,
+    /// Chapter outline of audiobooks, podcasts and mixes; empty for most files.
+    pub chapters: Vec<Chapter>,
+    /// Audio file of a cue-sheet track (`file_path` is then `Album.cue#03`);
+    /// `None` for plain files.
+    pub source_path: Option<String>,
+    /// Segment of `source_path` the cue-sheet track covers.
+    pub start_secs: f64,
+    pub end_secs: Option<f64>,
+    /// Encoder named by the tags or the stream, e.g. "LAME3.100" or
+    /// "reference libFLAC 1.4.3".
+    pub encoder: Option<String>,
+    /// Audio bitrate; for lossless files the average.
+    pub bitrate_kbps: Option<u32>,
+    /// CBR / VBR / ABR, known for MP3s.
+    pub bitrate_mode: Option<BitrateMode>,
+    /// e.g. "MPEG-1 Layer III", "AAC-LC", "HE-AAC".
+    pub codec_profile: Option<String>,
+    /// ReplayGain values stored in the tags.
+    pub replaygain: ReplayGainInfo,
+    pub file_size: u64,
 }
 
+/// Read a file's tags and stream properties. Cue-sheet track paths
+/// (`Album.cue#03`) get the track's metadata from the sheet.
 pub fn read_metadata(path: &str) -> Result<TrackMetadata, String> {
-    let tagged_file = Probe::open(path)
-        .map_err(|e| format!("Failed to open file: {}", e))?
-        .read()
-        .map_err(|e| format!("Failed to read tags: {}", e))?;
+    if let Some((sheet, track)) = cue::find_virtual_track(path)? {
+        let image = read_metadata(&track.file)?;
+        return Ok(cue_track_metadata(path, &sheet, &track, image));
+    }
+
+    let FileRead {
+        tagged_file,
+        id3v2,
+        details,
+    } = read_tagged_file(path)?;
 
     let properties = tagged_file.properties();
     let duration_secs = properties.duration().as_secs_f64();
@@ -36,17 +100,20 @@ pub fn read_metadata(path: &str) -> Result<TrackMetadata, String> {
     let bit_depth = properties.bit_depth();
     let channels = properties.channels();
 
-    let tag = tagged_file.primary_tag().or_else(|| tagged_file.first_tag());
+    let tag = tagged_file
+        .primary_tag()
+        .or_else(|| tagged_file.first_tag());
 
     let (title, artist, album, album_artist, year, genre, track_number, disc_number, has_art) =
         if let Some(tag) = tag {
             (
                 tag.title().map(|s| s.to_string()),
-                tag.artist().map(|s| s.to_string()),
+                joined(tag, ItemKey::TrackArtist),
                 tag.album().map(|s| s.to_string()),
                 tag.get_string(&ItemKey::AlbumArtist).map(|s| s.to_string()),
                 tag.year(),
-                tag.genre().map(|s| s.to_string()),
+                // Repeated fields are joined for the library to split
+                joined(tag, ItemKey::Genre),
                 tag.track().map(|t| t as u32),
                 tag.disk().map(|d| d as u32),
                 !tag.pictures().is_empty(),
@@ -55,6 +122,40 @@ pub fn read_metadata(path: &str) -> Result<TrackMetadata, String> {
             (None, None, None, None, None, None, None, None, false)
         };
 
+    let text = |key: ItemKey| {
+        tag.and_then(|t| t.get_string(&key))
+            .map(|s| s.trim().to_string())
+            .filter(|s| !s.is_empty())
+    };
+    let composer = text(ItemKey::Composer);
+    let work = text(ItemKey::Work);
+    let movement = text(ItemKey::Movement);
+    // "2" or "2/4"
+    let movement_number = text(ItemKey::MovementNumber)
+        .and_then(|n| n.split('/').next().and_then(|n| n.trim().parse().ok()));
+    let compilation =
+        text(ItemKey::FlagCompilation).is_some_and(|v| v == "1" || v.eq_ignore_ascii_case("true"));
+    let artist_sort = text(ItemKey::TrackArtistSortOrder);
+    let album_sort = text(ItemKey::AlbumTitleSortOrder);
+    let album_artist_sort = text(ItemKey::AlbumArtistSortOrder);
+
+    let all = |key: &'static ItemKey| -> Vec<&str> {
+        tag.map(|t| t.get_strings(key).collect())
+            .unwrap_or_default()
+    };
+    let artists = values::split_artists(all(&ItemKey::TrackArtist));
+    let genres = values::split_values(all(&ItemKey::Genre));
+    let composers = values::split_values(all(&ItemKey::Composer));
+    let rating = tag.and_then(|t| rating::read_rating(t, id3v2.as_ref()));
+    let chapters = chapters::read_chapters(path, tag, id3v2.as_ref(), duration_secs);
+    let encoder = text(ItemKey::EncoderSoftware).or(details.encoder);
+    let bitrate_kbps = properties
+        .audio_bitrate()
+        .or(properties.overall_bitrate())
+        .filter(|&b| b > 0);
+    let replaygain = tag.map(replaygain_from_tag).unwrap_or_default();
+    let file_size = std::fs::metadata(path).map(|m| m.len()).unwrap_or(0);
+
     let file_path_obj = Path::new(path);
     let file_name = file_path_obj
         .file_name()
@@ -83,20 +184,243 @@ pub fn read_metadata(path: &str) -> Result<TrackMetadata, String> {
         file_name,
         format,
         has_album_art: has_art,
+        composer,
+        work,
+        movement,
+        movement_number,
+        compilation,
+        artist_sort,
+        album_sort,
+        album_artist_sort,
+        artists,
+        genres,
+        composers,
+        rating,
+        chapters,
+        source_path: None,
+        start_secs: 0.0,
+        end_secs: None,
+        encoder,
+        bitrate_kbps,
+        bitrate_mode: details.bitrate_mode,
+        codec_profile: details.codec_profile,
+        replaygain,
+        file_size,
     })
 }
 
-pub fn get_album_art_base64(path: &str) -> Result<Option<String>, String> {
+/// Metadata of a cue-sheet track: stream properties and artwork come from
+/// the image file, and whatever the sheet says about the track or album
+/// wins over the image's own tags.
+pub fn cue_track_metadata(
+    path: &str,
+    sheet: &CueSheet,
+    track: &CueTrack,
+    image: TrackMetadata,
+) -> TrackMetadata {
+    let end_secs = track
+        .end_secs
+        .unwrap_or(image.duration_secs)
+        .max(track.start_secs);
+    let performer = track.performer.as_ref().or(sheet.performer.as_ref());
+    let (artist, artists, artist_sort) = match performer {
+        Some(performer) => (
+            Some(performer.clone()),
+            values::split_artists([performer.as_str()]),
+            None,
+        ),
+        None => (image.artist, image.artists, image.artist_sort),
+    };
+    let (album, album_sort) = match &sheet.title {
+        Some(title) => (Some(title.clone()), None),
+        None => (image.album, image.album_sort),
+    };
+    let (album_artist, album_artist_sort) = match &sheet.performer {
+        Some(performer) => (Some(performer.clone()), None),
+        None => (image.album_artist, image.album_artist_sort),
+    };
+    let (genre, genres) = match &sheet.genre {
+        Some(genre) => (Some(genre.clone()), values::split_values([genre.as_str()])),
+        None => (image.genre, image.genres),
+    };
+
+    TrackMetadata {
+        title: track
+            .title
+            .clone()
+            .or_else(|| Some(format!("Track {:02}", track.number))),
+        artist,
+        album,
+        album_artist,
+        year: sheet.year.or(image.year),
+        genre,
+        track_number: Some(track.number),
+        disc_number: sheet.disc_number.or(image.disc_number),
+        duration_secs: end_secs - track.start_secs,
+        sample_rate: image.sample_rate,
+        bit_depth: image.bit_depth,
+        channels: image.channels,
+        file_path: path.to_string(),
+        file_name: image.file_name,
+        format: image.format,
+        has_album_art: image.has_album_art,
+        // The image's composer, work and rating describe the whole album
+        composer: track.songwriter.clone(),
+        work: None,
+        movement: None,
+        movement_number: None,
+        compilation: image.compilation,
+        artist_sort,
+        album_sort,
+        album_artist_sort,
+        artists,
+        genres,
+        composers: track.songwriter.iter().cloned().collect(),
+        rating: None,
+        chapters: Vec::new(),
+        source_path: Some(track.file.clone()),
+        start_secs: track.start_secs,
+        end_secs: Some(end_secs),
+        encoder: image.encoder,
+        bitrate_kbps: image.bitrate_kbps,
+        bitrate_mode: image.bitrate_mode,
+        codec_profile: image.codec_profile,
+        // Per-track gains of a cue track would need their own analysis
+        replaygain: ReplayGainInfo {
+            track_gain_db: None,
+            track_peak: None,
+            ..image.replaygain
+        },
+        file_size: image.file_size,
+    }
+}
+
+/// MusicBrainz ids in a file's tags, as Picard writes them.
+#[derive(Clone, Default)]
+pub struct MusicBrainzIds {
+    pub recording_id: Option<String>,
+    /// Release track id: the recording's place on `release_id`.
+    pub track_id: Option<String>,
+    pub release_id: Option<String>,
+    pub release_group_id: Option<String>,
+    /// Every credited artist, split from repeated fields and "/" or "; "
+    /// lists.
+    pub artist_ids: Vec<String>,
+}
+
+/// The MusicBrainz ids in a file's tags; empty when it has none.
+pub fn read_musicbrainz_ids(path: &str) -> Result<MusicBrainzIds, String> {
+    let FileRead { tagged_file, .. } = read_tagged_file(path)?;
+    let Some(tag) = tagged_file
+        .primary_tag()
+        .or_else(|| tagged_file.first_tag())
+    else {
+        return Ok(MusicBrainzIds::default());
+    };
+    let text = |key: ItemKey| {
+        tag.get_string(&key)
+            .map(|s| s.trim().to_string())
+            .filter(|s| !s.is_empty())
+    };
+    let artist_ids = tag
+        .get_strings(&ItemKey::MusicBrainzArtistId)
+        .flat_map(|v| v.split(['/', ';']))
+        .map(str::trim)
+        .filter(|id| !id.is_empty())
+        .map(String::from)
+        .collect();
+    Ok(MusicBrainzIds {
+        recording_id: text(ItemKey::MusicBrainzRecordingId),
+        track_id: text(ItemKey::MusicBrainzTrackId),
+        release_id: text(ItemKey::MusicBrainzReleaseId),
+        release_group_id: text(ItemKey::MusicBrainzReleaseGroupId),
+        artist_ids,
+    })
+}
+
+/// A file's tags and what its format-specific reader found besides.
+struct FileRead {
+    tagged_file: TaggedFile,
+    /// The raw ID3v2 tag of an MP3, for the frames lofty leaves out of the
+    /// generic tag (POPM, CHAP).
+    id3v2: Option<Id3v2Tag>,
+    details: StreamDetails,
+}
+
+/// Read a file's tags. MP3, MP4 and AAC files are read as their own format
+/// to get at the details the generic reader drops.
+fn read_tagged_file(path: &str) -> Result<FileRead, String> {
+    let extension = Path::new(path)
+        .extension()
+        .map(|e| e.to_string_lossy().to_ascii_lowercase())
+        .unwrap_or_default();
+    let open = || std::fs::File::open(path).map_err(|e| format!("Failed to open file: {}", e));
+    let options = ParseOptions::new();
+    // Misnamed files fall through to content detection
+    match extension.as_str() {
+        "mp3" => {
+            if let Ok(mpeg) = MpegFile::read_from(&mut open()?, options) {
+                return Ok(FileRead {
+                    id3v2: mpeg.id3v2().cloned(),
+                    details: technical::mpeg_details(path, mpeg.properties()),
+                    tagged_file: mpeg.into(),
+                });
+            }
+        }
+        "m4a" | "m4b" | "mp4" | "alac" => {
+            if let Ok(mp4) = Mp4File::read_from(&mut open()?, options) {
+                return Ok(FileRead {
+                    id3v2: None,
+                    details: technical::mp4_details(mp4.properties()),
+                    tagged_file: mp4.into(),
+                });
+            }
+        }
+        "aac" => {
+            if let Ok(aac) = AacFile::read_from(&mut open()?, options) {
+                return Ok(FileRead {
+                    id3v2: None,
+                    details: technical::aac_details(aac.properties()),
+                    tagged_file: aac.into(),
+                });
+            }
+        }
+        _ => {}
+    }
     let tagged_file = Probe::open(path)
         .map_err(|e| format!("Failed to open file: {}", e))?
         .read()
         .map_err(|e| format!("Failed to read tags: {}", e))?;
+    Ok(FileRead {
+        tagged_file,
+        id3v2: None,
+        details: StreamDetails::default(),
+    })
+}
+
+/// All values of a possibly repeated field, joined with "; ".
+fn joined(tag: &Tag, key: ItemKey) -> Option<String> {
+    let values: Vec<&str> = tag.get_strings(&key).collect();
+    (!values.is_empty()).then(|| values.join("; "))
+}
+
+pub fn get_album_art_base64(path: &str) -> Result<Option<String>, String> {
+    let path = cue::audio_file(path)?;
+    let tagged_file = Probe::open(&path)
+        .map_err(|e| format!("Failed to open file: {}", e))?
+        .read()
+        .map_err(|e| format!("Failed to read tags: {}", e))?;
 
-    let tag = tagged_file.primary_tag().or_else(|| tagged_file.first_tag());
+    let tag = tagged_file
+        .primary_tag()
+        .or_else(|| tagged_file.first_tag());
 
     if let Some(tag) = tag {
         if let Some(picture) = tag.pictures().first() {
-            let mime = picture.mime_type().map(|m| m.as_str()).unwrap_or("image/jpeg");
+            let mime = picture
+                .mime_type()
+                .map(|m| m.as_str())
+                .unwrap_or("image/jpeg");
             let b64 = base64::engine::general_purpose::STANDARD.encode(picture.data());
             return Ok(Some(format!("data:{};base64,{}", mime, b64)));
         }
@@ -104,3 +428,93 @@ pub fn get_album_art_base64(path: &str) -> Result<Option<String>, String> {
 
     Ok(None)
 }
+
+/// An embedded picture, for choosing between a file's artwork.
+#[derive(Clone, Serialize)]
+pub struct EmbeddedPicture {
+    /// "front", "back", "booklet", "disc", "artist", … or "other".
+    pub picture_type: String,
+    pub mime_type: Option<String>,
+    pub description: Option<String>,
+    /// `None` when the format can't be decoded (only JPEG and PNG can).
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    pub size_bytes: usize,
+    /// `data:` URL of the picture, when asked for.
+    pub data_url: Option<String>,
+}
+
+/// Every picture embedded in a file, in tag order, with the image data
+/// only when `include_data` is set.
+pub fn read_embedded_pictures(
+    path: &str,
+    include_data: bool,
+) -> Result<Vec<EmbeddedPicture>, String> {
+    let path = cue::audio_file(path)?;
+    Ok(cover::read_pictures(&path)?
+        .iter()
+        .map(|picture| {
+            // Only the header is read for the size
+            let dimensions = image::ImageReader::new(Cursor::new(picture.data()))
+                .with_guessed_format()
+                .ok()
+                .and_then(|reader| reader.into_dimensions().ok());
+            let mime = picture.mime_type().map(|m| m.as_str().to_string());
+            EmbeddedPicture {
+                picture_type: picture_type_name(picture.pic_type()).to_string(),
+                data_url: include_data.then(|| {
+                    format!(
+                        "data:{};base64,{}",
+                        mime.as_deref().unwrap_or("image/jpeg"),
+                        base64::engine::general_purpose::STANDARD.encode(picture.data())
+                    )
+                }),
+                mime_type: mime,
+                description: picture.description().map(str::to_string),
+                width: dimensions.map(|(w, _)| w),
+                height: dimensions.map(|(_, h)| h),
+                size_bytes: picture.data().len(),
+            }
+        })
+        .collect())
+}
+
+fn picture_type_name(pic_type: PictureType) -> &'static str {
+    match pic_type {
+        PictureType::CoverFront => "front",
+        PictureType::CoverBack => "back",
+        PictureType::Leaflet => "booklet",
+        PictureType::Media => "disc",
+        PictureType::LeadArtist | PictureType::Artist | PictureType::Band => "artist",
+        PictureType::Conductor => "conductor",
+        PictureType::Composer => "composer",
+        PictureType::Lyricist => "lyricist",
+        PictureType::Illustration => "illustration",
+        PictureType::BandLogo => "logo",
+        PictureType::Icon | PictureType::OtherIcon => "icon",
+        _ => "other",
+    }
+}
+
+/// Raw bytes of the embedded front cover (or, failing that, the first picture).
+pub fn read_cover_art(path: &str) -> Result<Option<Vec<u8>>, String> {
+    let path = cue::audio_file(path)?;
+    let tagged_file = Probe::open(&path)
+        .map_err(|e| format!("Failed to open file: {}", e))?
+        .read()
+        .map_err(|e| format!("Failed to read tags: {}", e))?;
+
+    let Some(tag) = tagged_file
+        .primary_tag()
+        .or_else(|| tagged_file.first_tag())
+    else {
+        return Ok(None);
+    };
+    let pictures = tag.pictures();
+    let picture = pictures
+        .iter()
+        .find(|p| p.pic_type() == PictureType::CoverFront)
+        .or_else(|| pictures.first());
+
+    Ok(picture.map(|p| p.data().to_vec()))
+}
@@ -0,0 +1,105 @@
+//! ReplayGain tag writing.
+//!
+//! Values go into the file's primary tag under the standard field for its
+//! format — Vorbis comments, ID3v2 `TXXX` frames, APEv2 items or MP4
+//! freeform atoms (lofty maps the names). Opus files get `R128_TRACK_GAIN`
+//! / `R128_ALBUM_GAIN` instead, as RFC 7845 asks; those have no peak fields.
+
+use lofty::file::FileType;
+use lofty::prelude::*;
+use lofty::probe::Probe;
+use lofty::tag::Tag;
+
+use super::write_settings;
+use crate::audio::replaygain::{ReplayGainInfo, R128_SCALE, R128_TO_REPLAYGAIN_DB};
+
+const R128_TRACK_GAIN: &str = "R128_TRACK_GAIN";
+const R128_ALBUM_GAIN: &str = "R128_ALBUM_GAIN";
+
+/// Gains beyond this many dB either way are treated as bad input.
+const MAX_GAIN_DB: f32 = 64.0;
+
+/// Replace the ReplayGain tags of a file. Fields that are `None` are removed,
+/// so stale album values don't outlive a track-only analysis.
+pub fn write_replaygain(path: &str, info: &ReplayGainInfo) -> Result<(), String> {
+    for gain in [info.track_gain_db, info.album_gain_db]
+        .into_iter()
+        .flatten()
+    {
+        if !gain.is_finite() || gain.abs() > MAX_GAIN_DB {
+            return Err(format!("Gain {} dB is out of range", gain));
+        }
+    }
+    for peak in [info.track_peak, info.album_peak].into_iter().flatten() {
+        if !peak.is_finite() || peak < 0.0 {
+            return Err(format!("Peak {} is not a valid linear peak", peak));
+        }
+    }
+
+    let tagged_file = Probe::open(path)
+        .map_err(|e| format!("Failed to open file: {}", e))?
+        .read()
+        .map_err(|e| format!("Failed to read tags: {}", e))?;
+    let opus = tagged_file.file_type() == FileType::Opus;
+    let tag_type = tagged_file.primary_tag_type();
+    let mut tag = tagged_file
+        .tag(tag_type)
+        .cloned()
+        .unwrap_or_else(|| Tag::new(tag_type));
+
+    for key in [
+        ItemKey::ReplayGainTrackGain,
+        ItemKey::ReplayGainTrackPeak,
+        ItemKey::ReplayGainAlbumGain,
+        ItemKey::ReplayGainAlbumPeak,
+        ItemKey::Unknown(R128_TRACK_GAIN.to_string()),
+        ItemKey::Unknown(R128_ALBUM_GAIN.to_string()),
+    ] {
+        tag.remove_key(&key);
+    }
+
+    if opus {
+        let r128 = |gain_db: f32| ((gain_db - R128_TO_REPLAYGAIN_DB) * R128_SCALE).round() as i32;
+        if let Some(gain) = info.track_gain_db {
+            tag.insert_text(
+                ItemKey::Unknown(R128_TRACK_GAIN.to_string()),
+                r128(gain).to_string(),
+            );
+        }
+        if let Some(gain) = info.album_gain_db {
+            tag.insert_text(
+                ItemKey::Unknown(R128_ALBUM_GAIN.to_string()),
+                r128(gain).to_string(),
+            );
+        }
+    } else {
+        let fields = [
+            (
+                ItemKey::ReplayGainTrackGain,
+                info.track_gain_db.map(gain_text),
+            ),
+            (ItemKey::ReplayGainTrackPeak, info.track_peak.map(peak_text)),
+            (
+                ItemKey::ReplayGainAlbumGain,
+                info.album_gain_db.map(gain_text),
+            ),
+            (ItemKey::ReplayGainAlbumPeak, info.album_peak.map(peak_text)),
+        ];
+        for (key, value) in fields {
+            if let Some(value) = value {
+                tag.insert_text(key, value);
+            }
+        }
+    }
+
+    write_settings::save_tag(&tag, path)
+}
+
+/// "-7.52 dB", the form every player parses.
+fn gain_text(gain_db: f32) -> String {
+    format!("{:.2} dB", gain_db)
+}
+
+fn peak_text(peak: f32) -> String {
+    format!("{:.6}", peak)
+}
@@ -0,0 +1,166 @@
+//! Stream details for a file's properties panel: codec profile, bitrate mode
+//! and the encoder named in the stream itself.
+//!
+//! lofty exposes the MPEG version and layer and the AAC audio object type.
+//! An MP3's bitrate mode and LAME version live in the Xing/Info/VBRI header
+//! of its first frame, which lofty reads but doesn't expose, so that header
+//! is read here.
+
+use lofty::aac::AACProperties;
+use lofty::mp4::{AudioObjectType, Mp4Codec, Mp4Properties};
+use lofty::mpeg::{Layer, MpegProperties, MpegVersion};
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+
+/// How far past the ID3v2 tag the first MPEG frame is looked for.
+const FRAME_SEARCH_BYTES: usize = 16 * 1024;
+
+#[derive(Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum BitrateMode {
+    Cbr,
+    Vbr,
+    Abr,
+}
+
+/// What a format-specific read learns beyond lofty's generic properties.
+#[derive(Default)]
+pub struct StreamDetails {
+    /// e.g. "MPEG-1 Layer III", "AAC-LC", "HE-AACv2".
+    pub codec_profile: Option<String>,
+    pub bitrate_mode: Option<BitrateMode>,
+    /// Encoder from the stream itself, e.g. "LAME3.100" from an MP3's LAME
+    /// header. Tags name the encoder more often; those win.
+    pub encoder: Option<String>,
+}
+
+pub fn mpeg_details(path: &str, properties: &MpegProperties) -> StreamDetails {
+    let version = match properties.version() {
+        MpegVersion::V1 => "MPEG-1",
+        MpegVersion::V2 => "MPEG-2",
+        MpegVersion::V2_5 => "MPEG-2.5",
+        MpegVersion::V4 => "MPEG-4",
+    };
+    let layer = match properties.layer() {
+        Layer::Layer1 => "I",
+        Layer::Layer2 => "II",
+        Layer::Layer3 => "III",
+    };
+    let (bitrate_mode, encoder) = read_vbr_header(path).unwrap_or((BitrateMode::Cbr, None));
+    StreamDetails {
+        codec_profile: Some(format!("{} Layer {}", version, layer)),
+        bitrate_mode: Some(bitrate_mode),
+        encoder,
+    }
+}
+
+pub fn mp4_details(properties: &Mp4Properties) -> StreamDetails {
+    StreamDetails {
+        codec_profile: match properties.codec() {
+            Mp4Codec::AAC => properties.audio_object_type().and_then(aac_profile),
+            _ => None,
+        },
+        ..Default::default()
+    }
+}
+
+pub fn aac_details(properties: &AACProperties) -> StreamDetails {
+    StreamDetails {
+        codec_profile: aac_profile(properties.audio_object_type()),
+        ..Default::default()
+    }
+}
+
+fn aac_profile(object_type: AudioObjectType) -> Option<String> {
+    let name = match object_type {
+        AudioObjectType::AacMain => "AAC Main",
+        AudioObjectType::AacLowComplexity => "AAC-LC",
+        AudioObjectType::AacScalableSampleRate => "AAC-SSR",
+        AudioObjectType::AacLongTermPrediction => "AAC-LTP",
+        AudioObjectType::SpectralBandReplication => "HE-AAC",
+        AudioObjectType::ParametricStereo => "HE-AACv2",
+        AudioObjectType::ErrorResilientAacLowDelay => "AAC-LD",
+        _ => return None,
+    };
+    Some(name.to_string())
+}
+
+/// Bitrate mode and encoder from the first frame's VBR header. Files
+/// without one are CBR.
+fn read_vbr_header(path: &str) -> Option<(BitrateMode, Option<String>)> {
+    let mut file = File::open(path).ok()?;
+
+    // Skip the ID3v2 tag: 10-byte header, synchsafe size, optional footer
+    let mut header = [0u8; 10];
+    file.read_exact(&mut header).ok()?;
+    let start = if &header[..3] == b"ID3" {
+        let size = header[6..10]
+            .iter()
+            .fold(0u64, |size, &b| (size << 7) | u64::from(b & 0x7f));
+        let footer = if header[5] & 0x10 != 0 { 10 } else { 0 };
+        10 + size + footer
+    } else {
+        0
+    };
+    file.seek(SeekFrom::Start(start)).ok()?;
+    let mut data = Vec::with_capacity(FRAME_SEARCH_BYTES);
+    file.take(FRAME_SEARCH_BYTES as u64)
+        .read_to_end(&mut data)
+        .ok()?;
+
+    let frame = data
+        .windows(2)
+        .position(|w| w[0] == 0xFF && w[1] & 0xE0 == 0xE0)?;
+    let data = &data[frame..];
+    let mpeg1 = (data[1] >> 3) & 0x03 == 0x03;
+    let mono = data.get(3)? >> 6 == 0x03;
+    // The VBR header follows the side information
+    let side_info = match (mpeg1, mono) {
+        (true, true) => 17,
+        (true, false) => 32,
+        (false, true) => 9,
+        (false, false) => 17,
+    };
+
+    let xing = data.get(4 + side_info..)?;
+    match xing.get(..4)? {
+        b"Xing" | b"Info" => {
+            let flags = u32::from_be_bytes(xing.get(4..8)?.try_into().ok()?);
+            // Frame count, byte count, seek table and quality, when present
+            let lame_at = 8
+                + if flags & 0x1 != 0 { 4 } else { 0 }
+                + if flags & 0x2 != 0 { 4 } else { 0 }
+                + if flags & 0x4 != 0 { 100 } else { 0 }
+                + if flags & 0x8 != 0 { 4 } else { 0 };
+            let (mode, encoder) = xing.get(lame_at..).map(read_lame_tag).unwrap_or_default();
+            let mode = mode.unwrap_or(if &xing[..4] == b"Xing" {
+                BitrateMode::Vbr
+            } else {
+                BitrateMode::Cbr
+            });
+            Some((mode, encoder))
+        }
+        _ if data.get(36..40) == Some(&b"VBRI"[..]) => Some((BitrateMode::Vbr, None)),
+        _ => None,
+    }
+}
+
+/// The LAME tag after a Xing/Info header: 9 bytes of encoder version, then
+/// the VBR method in the low nibble of the next byte.
+fn read_lame_tag(tag: &[u8]) -> (Option<BitrateMode>, Option<String>) {
+    let Some(version) = tag
+        .get(..9)
+        .filter(|v| v.iter().all(|b| b.is_ascii_graphic() || *b == b' '))
+    else {
+        return (None, None);
+    };
+    let encoder = String::from_utf8_lossy(version).trim().to_string();
+    let mode = tag.get(9).and_then(|method| match method & 0x0F {
+        1 | 8 => Some(BitrateMode::Cbr),
+        2 | 9 => Some(BitrateMode::Abr),
+        3..=6 => Some(BitrateMode::Vbr),
+        _ => None,
+    });
+    (mode, Some(encoder).filter(|e| !e.is_empty()))
+}
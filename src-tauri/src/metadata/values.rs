@@ -0,0 +1,84 @@
+//! Multi-value tag splitting.
+//!
+//! Tags that can name several artists, genres or composers arrive as repeated
+//! fields (Vorbis comments, ID3v2.4 NUL-separated frames) or as one string
+//! with a delimiter ("Rock; Blues"). Artist credits also hide guests behind
+//! "feat." ("Artist A feat. Artist B"). These helpers turn either form into
+//! a list of individual names.
+
+/// Words introducing guest artists, longest first so "feat." wins over "feat".
+const FEAT_MARKERS: &[&str] = &["featuring", "feat.", "feat", "ft.", "ft"];
+
+/// Individual values of one or more tag fields, split on NUL and ';' and
+/// deduplicated case-insensitively: ["Rock; Blues", "rock"] → ["Rock", "Blues"].
+pub fn split_values<'a>(fields: impl IntoIterator<Item = &'a str>) -> Vec<String> {
+    let mut values: Vec<String> = Vec::new();
+    for field in fields {
+        for value in field.split([';', '\0']).map(str::trim) {
+            push_unique(&mut values, value);
+        }
+    }
+    values
+}
+
+/// Every artist credited by one or more artist fields, main artist first:
+/// ["A feat. B; C"] → ["A", "B", "C"]. "&" only separates guests, so acts
+/// like "Simon & Garfunkel" stay whole.
+pub fn split_artists<'a>(fields: impl IntoIterator<Item = &'a str>) -> Vec<String> {
+    let mut artists: Vec<String> = Vec::new();
+    for value in split_values(fields) {
+        let (main, guests) = split_featured(&value);
+        push_unique(&mut artists, &main);
+        for guest in &guests {
+            push_unique(&mut artists, guest);
+        }
+    }
+    artists
+}
+
+/// Split "Main feat. Guest & Other" into ("Main", ["Guest", "Other"]). Also
+/// handles bracketed credits: "Song (feat. Guest)" → ("Song", ["Guest"]).
+pub fn split_featured(text: &str) -> (String, Vec<String>) {
+    let Some((start, guests_start)) = find_feat_marker(text) else {
+        return (text.trim().to_string(), Vec::new());
+    };
+    let rest = &text[guests_start..];
+    let guests = &rest[..rest.find([')', ']']).unwrap_or(rest.len())];
+    (
+        text[..start].trim().to_string(),
+        guests
+            .split([',', '&', ';'])
+            .map(str::trim)
+            .filter(|g| !g.is_empty())
+            .map(str::to_string)
+            .collect(),
+    )
+}
+
+/// Byte offsets of the first guest credit: where it starts (including an
+/// opening bracket) and where the guest names start.
+fn find_feat_marker(text: &str) -> Option<(usize, usize)> {
+    // ASCII lowercasing keeps byte offsets valid for `text`
+    let lower = text.to_ascii_lowercase();
+    let bytes = lower.as_bytes();
+    for (i, _) in lower.char_indices().skip(1) {
+        let bracketed = matches!(bytes[i - 1], b'(' | b'[');
+        if !bracketed && bytes[i - 1] != b' ' {
+            continue;
+        }
+        for marker in FEAT_MARKERS {
+            let end = i + marker.len();
+            if lower[i..].starts_with(marker) && lower[end..].starts_with(' ') {
+                let start = if bracketed { i - 1 } else { i };
+                return Some((start, end + 1));
+            }
+        }
+    }
+    None
+}
+
+fn push_unique(values: &mut Vec<String>, value: &str) {
+    if !value.is_empty() && !values.iter().any(|v| v.eq_ignore_ascii_case(value)) {
+        values.push(value.to_string());
+    }
+}
@@ -0,0 +1,112 @@
+//! How tags are written: the ID3v2 version, and whether the redundant
+//! ID3v1 and APEv2 copies many MP3s carry are stripped, so a file ends up
+//! with one consistent tag.
+//!
+//! Stored as JSON in the app data directory. Every tag writer in
+//! [`crate::metadata`] saves through [`save_tag`] or [`write_options`]; the
+//! settings live in a process-wide value so library jobs deep in other
+//! threads pick them up without each being handed a copy.
+
+use lofty::config::WriteOptions;
+use lofty::file::FileType;
+use lofty::tag::{Tag, TagExt, TagType};
+use parking_lot::RwLock;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+const SETTINGS_FILE: &str = "tag_write_settings.json";
+
+static CURRENT: RwLock<TagWriteSettings> = parking_lot::const_rwlock(TagWriteSettings::DEFAULT);
+
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum Id3v2Version {
+    /// Upgrade tags to ID3v2.4 when they are written.
+    V24,
+    /// Write ID3v2.3, for players and car stereos that can't read v2.4.
+    V23,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TagWriteSettings {
+    pub id3v2_version: Id3v2Version,
+    /// Remove a file's ID3v1 tag when another tag is written.
+    pub strip_id3v1: bool,
+    /// Remove a file's APEv2 tag when another tag (e.g. ID3v2 in an MP3) is
+    /// written. APE and WavPack files, where APEv2 is the main tag, keep it.
+    pub strip_ape: bool,
+}
+
+impl TagWriteSettings {
+    const DEFAULT: Self = Self {
+        id3v2_version: Id3v2Version::V24,
+        strip_id3v1: false,
+        strip_ape: false,
+    };
+
+    /// Load settings from disk. Returns defaults if the file doesn't exist.
+    pub fn load(app_data_dir: &Path) -> Self {
+        let path = app_data_dir.join(SETTINGS_FILE);
+        if let Ok(data) = std::fs::read_to_string(&path) {
+            serde_json::from_str(&data).unwrap_or_default()
+        } else {
+            Self::default()
+        }
+    }
+
+    /// Save settings to disk.
+    pub fn save(&self, app_data_dir: &Path) -> Result<(), String> {
+        let path = app_data_dir.join(SETTINGS_FILE);
+        std::fs::create_dir_all(app_data_dir)
+            .map_err(|e| format!("Failed to create dir: {}", e))?;
+        let json =
+            serde_json::to_string_pretty(self).map_err(|e| format!("Serialize failed: {}", e))?;
+        std::fs::write(&path, json).map_err(|e| format!("Write failed: {}", e))?;
+        Ok(())
+    }
+}
+
+impl Default for TagWriteSettings {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+/// The settings tag writes use.
+pub fn current() -> TagWriteSettings {
+    CURRENT.read().clone()
+}
+
+/// Use `settings` for tag writes from now on.
+pub fn apply(settings: TagWriteSettings) {
+    *CURRENT.write() = settings;
+}
+
+pub fn write_options() -> WriteOptions {
+    WriteOptions::default().use_id3v23(current().id3v2_version == Id3v2Version::V23)
+}
+
+/// Save `tag` to the file, then strip the duplicate tags the settings ask
+/// to remove.
+pub fn save_tag(tag: &Tag, path: &str) -> Result<(), String> {
+    tag.save_to_path(path, write_options())
+        .map_err(|e| format!("Failed to write tags: {}", e))?;
+
+    let settings = current();
+    let file_type = FileType::from_path(path);
+    let redundant = [
+        (TagType::Id3v1, settings.strip_id3v1),
+        (TagType::Ape, settings.strip_ape),
+    ];
+    for (tag_type, strip) in redundant {
+        if !strip || tag_type == tag.tag_type() {
+            continue;
+        }
+        if file_type.is_some_and(|ft| ft.supports_tag_type(tag_type)) {
+            tag_type
+                .remove_from_path(path)
+                .map_err(|e| format!("Failed to remove {:?} tag: {}", tag_type, e))?;
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,74 @@
+//! Tag editing.
+//!
+//! Edits go into the file's primary tag (the one [`super::reader`] reads
+//! first), created if the file has none. Other tags in the file are left
+//! alone.
+
+use lofty::prelude::*;
+use lofty::probe::Probe;
+use lofty::tag::Tag;
+use serde::{Deserialize, Serialize};
+
+use super::write_settings;
+
+/// Fields to change. Omitted fields keep their current value; an empty
+/// string or a 0 number removes the field.
+#[derive(Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MetadataFields {
+    pub title: Option<String>,
+    pub artist: Option<String>,
+    pub album: Option<String>,
+    pub album_artist: Option<String>,
+    pub year: Option<u32>,
+    pub genre: Option<String>,
+    pub track_number: Option<u32>,
+    pub disc_number: Option<u32>,
+}
+
+pub fn write_metadata(path: &str, fields: &MetadataFields) -> Result<(), String> {
+    let tagged_file = Probe::open(path)
+        .map_err(|e| format!("Failed to open file: {}", e))?
+        .read()
+        .map_err(|e| format!("Failed to read tags: {}", e))?;
+    let tag_type = tagged_file.primary_tag_type();
+    let mut tag = tagged_file
+        .tag(tag_type)
+        .cloned()
+        .unwrap_or_else(|| Tag::new(tag_type));
+
+    let texts = [
+        (ItemKey::TrackTitle, &fields.title),
+        (ItemKey::TrackArtist, &fields.artist),
+        (ItemKey::AlbumTitle, &fields.album),
+        (ItemKey::AlbumArtist, &fields.album_artist),
+        (ItemKey::Genre, &fields.genre),
+    ];
+    for (key, value) in texts {
+        match value.as_deref().map(str::trim) {
+            None => {}
+            Some("") => tag.remove_key(&key),
+            Some(value) => {
+                tag.insert_text(key, value.to_string());
+            }
+        }
+    }
+
+    match fields.year {
+        None => {}
+        Some(0) => tag.remove_year(),
+        Some(year) => tag.set_year(year),
+    }
+    match fields.track_number {
+        None => {}
+        Some(0) => tag.remove_track(),
+        Some(n) => tag.set_track(n),
+    }
+    match fields.disc_number {
+        None => {}
+        Some(0) => tag.remove_disk(),
+        Some(n) => tag.set_disk(n),
+    }
+
+    write_settings::save_tag(&tag, path)
+}
@@ -0,0 +1,490 @@
+//! MQTT bridge for home automation (Home Assistant and the like): the
+//! playback state, the playing track and the volume are published to a
+//! broker, and commands published to the player are run.
+//!
+//! Topics, under a base topic (`masukii` by default):
+//!
+//! - `<base>/availability`: `online`, or `offline` (also the connection's
+//!   will, so a crash shows too)
+//! - `<base>/state`: `playing`, `paused` or `stopped`
+//! - `<base>/track`: `{"title", "artist", "album", "duration_secs",
+//!   "path"}`, or empty when nothing is loaded
+//! - `<base>/volume`: 0 to 1
+//! - `<base>/position`: seconds into the track, on changes and seeks and
+//!   every few seconds while playing
+//!
+//! All are retained, so a dashboard has them as soon as it subscribes. When
+//! commands are accepted, `<base>/command` takes an action's name (`toggle`,
+//! `next`, ...) or a remote control command as JSON
+//! (`{"action": "seek", "position_secs": 30}`; see [`crate::remote::api`]),
+//! and `<base>/volume/set` a volume from 0 to 1.
+//!
+//! Off by default. Plain TCP only, at QoS 0. The settings, password
+//! included, are stored as JSON in the app data directory. A thread keeps
+//! the connection, reconnecting every few seconds when it drops.
+
+mod packet;
+
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::io::{ErrorKind, Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Manager};
+
+use crate::audio::engine::PlaybackState;
+use crate::commands::AppState;
+use crate::library::metadata_cache;
+use crate::remote::api;
+use packet::{Packet, Will};
+
+const SETTINGS_FILE: &str = "mqtt.json";
+
+pub const DEFAULT_PORT: u16 = 1883;
+pub const DEFAULT_BASE_TOPIC: &str = "masukii";
+
+/// How often the engine is checked for changes, and the broker for
+/// commands.
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// How often the position is published while playing.
+const POSITION_INTERVAL: Duration = Duration::from_secs(5);
+
+/// A position this far from where it should be is a seek.
+const SEEK_THRESHOLD_SECS: f64 = 1.5;
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+const RETRY_INTERVAL: Duration = Duration::from_secs(5);
+const KEEP_ALIVE_SECS: u16 = 30;
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MqttSettings {
+    pub enabled: bool,
+    pub host: String,
+    pub port: u16,
+    /// Empty for brokers without authentication.
+    pub username: String,
+    pub password: String,
+    /// Unique per broker; generated the first time.
+    pub client_id: String,
+    pub base_topic: String,
+    /// Run commands published to `<base>/command` and `<base>/volume/set`.
+    pub accept_commands: bool,
+}
+
+impl Default for MqttSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            host: String::new(),
+            port: DEFAULT_PORT,
+            username: String::new(),
+            password: String::new(),
+            client_id: String::new(),
+            base_topic: DEFAULT_BASE_TOPIC.to_string(),
+            accept_commands: true,
+        }
+    }
+}
+
+#[derive(Clone, Serialize)]
+pub struct MqttStatus {
+    pub settings: MqttSettings,
+    /// Connected to the broker right now.
+    pub connected: bool,
+    /// Why the last connection failed or dropped.
+    pub error: Option<String>,
+}
+
+#[derive(Default)]
+struct Link {
+    connected: bool,
+    error: Option<String>,
+}
+
+struct Running {
+    stop: Arc<AtomicBool>,
+    thread: JoinHandle<()>,
+}
+
+pub struct MqttBridge {
+    app_data_dir: PathBuf,
+    settings: Mutex<MqttSettings>,
+    running: Mutex<Option<Running>>,
+    link: Arc<Mutex<Link>>,
+}
+
+impl MqttBridge {
+    /// Load the settings, generating a client id the first time. Call
+    /// [`MqttBridge::start`] once the app is up.
+    pub fn load(app_data_dir: &Path) -> Self {
+        let mut settings: MqttSettings = std::fs::read_to_string(app_data_dir.join(SETTINGS_FILE))
+            .ok()
+            .and_then(|data| serde_json::from_str(&data).ok())
+            .unwrap_or_default();
+        let bridge = Self {
+            app_data_dir: app_data_dir.to_path_buf(),
+            settings: Mutex::new(MqttSettings::default()),
+            running: Mutex::new(None),
+            link: Arc::new(Mutex::new(Link::default())),
+        };
+        if settings.client_id.is_empty() {
+            settings.client_id = format!("masukii-{:08x}", rand::random::<u32>());
+            if let Err(e) = bridge.save(&settings) {
+                log::warn!("{}", e);
+            }
+        }
+        *bridge.settings.lock() = settings;
+        bridge
+    }
+
+    pub fn status(&self) -> MqttStatus {
+        let link = self.link.lock();
+        MqttStatus {
+            settings: self.settings.lock().clone(),
+            connected: link.connected,
+            error: link.error.clone(),
+        }
+    }
+
+    /// Save new settings and reconnect with them.
+    pub fn configure(&self, app: &AppHandle, settings: MqttSettings) -> Result<MqttStatus, String> {
+        if settings.enabled && settings.host.trim().is_empty() {
+            return Err("Enter the MQTT broker's address".to_string());
+        }
+        if settings.port == 0 {
+            return Err("Port must be between 1 and 65535".to_string());
+        }
+        if settings.client_id.trim().is_empty() {
+            return Err("Client id can't be empty".to_string());
+        }
+        let base = settings.base_topic.trim_matches('/');
+        if base.is_empty() || base.contains(['+', '#']) {
+            return Err("Base topic can't be empty or contain + or #".to_string());
+        }
+        self.save(&settings)?;
+        *self.settings.lock() = settings;
+        self.start(app);
+        Ok(self.status())
+    }
+
+    /// (Re)start the bridge as the settings say: disconnect if connected,
+    /// and connect again when enabled.
+    pub fn start(&self, app: &AppHandle) {
+        let mut running = self.running.lock();
+        if let Some(previous) = running.take() {
+            previous.stop.store(true, Ordering::Relaxed);
+            let _ = previous.thread.join();
+        }
+        *self.link.lock() = Link::default();
+
+        let settings = self.settings.lock().clone();
+        if !settings.enabled {
+            return;
+        }
+        let stop = Arc::new(AtomicBool::new(false));
+        let (app, link, stopped) = (app.clone(), self.link.clone(), stop.clone());
+        let thread = thread::Builder::new()
+            .name("mqtt".into())
+            .spawn(move || run(&app, &settings, &stopped, &link))
+            .expect("Failed to spawn MQTT thread");
+        *running = Some(Running { stop, thread });
+    }
+
+    fn save(&self, settings: &MqttSettings) -> Result<(), String> {
+        std::fs::create_dir_all(&self.app_data_dir)
+            .map_err(|e| format!("Failed to create dir: {}", e))?;
+        let json = serde_json::to_string_pretty(settings)
+            .map_err(|e| format!("Serialize failed: {}", e))?;
+        std::fs::write(self.app_data_dir.join(SETTINGS_FILE), json)
+            .map_err(|e| format!("Write failed: {}", e))
+    }
+}
+
+/// Stay connected until stopped.
+fn run(app: &AppHandle, settings: &MqttSettings, stop: &AtomicBool, link: &Mutex<Link>) {
+    let mut retry_at = Instant::now();
+    while !stop.load(Ordering::Relaxed) {
+        if Instant::now() < retry_at {
+            thread::sleep(POLL_INTERVAL);
+            continue;
+        }
+        let result = Session::open(settings, link).and_then(|mut s| s.run(app, stop));
+        let mut link = link.lock();
+        link.connected = false;
+        if let Err(e) = result {
+            if link.error.as_ref() != Some(&e) {
+                log::warn!("{}", e);
+            }
+            link.error = Some(e);
+            retry_at = Instant::now() + RETRY_INTERVAL;
+        }
+    }
+}
+
+/// What was last published.
+#[derive(Default)]
+struct Published {
+    state: Option<&'static str>,
+    track: Option<Option<(String, u64)>>,
+    volume: Option<f32>,
+    /// Position, and when.
+    position: Option<(f64, Instant)>,
+}
+
+struct Session<'a> {
+    stream: TcpStream,
+    settings: &'a MqttSettings,
+    base: &'a str,
+    /// Bytes read but not yet a whole packet.
+    buffer: Vec<u8>,
+    sent_at: Instant,
+}
+
+impl<'a> Session<'a> {
+    /// Connect and wait for the broker to accept.
+    fn open(settings: &'a MqttSettings, link: &Mutex<Link>) -> Result<Self, String> {
+        let target = format!("{}:{}", settings.host.trim(), settings.port);
+        let address = (settings.host.trim(), settings.port)
+            .to_socket_addrs()
+            .map_err(|e| format!("Failed to resolve {}: {}", target, e))?
+            .next()
+            .ok_or_else(|| format!("Failed to resolve {}", target))?;
+        let stream = TcpStream::connect_timeout(&address, CONNECT_TIMEOUT)
+            .map_err(|e| format!("Failed to connect to MQTT broker at {}: {}", target, e))?;
+        stream
+            .set_read_timeout(Some(POLL_INTERVAL))
+            .map_err(|e| format!("Failed to connect to MQTT broker at {}: {}", target, e))?;
+
+        let mut session = Self {
+            stream,
+            settings,
+            base: settings.base_topic.trim_matches('/'),
+            buffer: Vec::new(),
+            sent_at: Instant::now(),
+        };
+        let availability = session.topic("availability");
+        let non_empty = |s: &'a str| Some(s).filter(|s| !s.is_empty());
+        session.send(&packet::connect(
+            settings.client_id.trim(),
+            non_empty(&settings.username),
+            non_empty(&settings.password),
+            KEEP_ALIVE_SECS,
+            &Will {
+                topic: &availability,
+                payload: b"offline",
+                retain: true,
+            },
+        ))?;
+
+        let started = Instant::now();
+        loop {
+            if started.elapsed() >= CONNECT_TIMEOUT {
+                return Err(format!("No answer from MQTT broker at {}", target));
+            }
+            match session.receive()?.into_iter().next() {
+                Some(Packet::ConnAck { code: 0 }) => break,
+                Some(Packet::ConnAck { code }) => return Err(refusal(code)),
+                _ => {}
+            }
+        }
+        log::info!("Connected to MQTT broker at {}", target);
+        *link.lock() = Link {
+            connected: true,
+            error: None,
+        };
+        Ok(session)
+    }
+
+    /// Publish and take commands until stopped or disconnected.
+    fn run(&mut self, app: &AppHandle, stop: &AtomicBool) -> Result<(), String> {
+        let availability = self.topic("availability");
+        let (command, volume_set) = (self.topic("command"), self.topic("volume/set"));
+        self.publish(&availability, b"online")?;
+        if self.settings.accept_commands {
+            self.send(&packet::subscribe(
+                1,
+                &[command.as_str(), volume_set.as_str()],
+            ))?;
+        }
+
+        let state = app.state::<AppState>();
+        let mut published = Published::default();
+        while !stop.load(Ordering::Relaxed) {
+            for packet in self.receive()? {
+                if let Packet::Publish { topic, payload } = packet {
+                    let payload = String::from_utf8_lossy(&payload);
+                    if let Err(e) = run_command(app, topic == volume_set, payload.trim()) {
+                        log::warn!("MQTT command on {} failed: {}", topic, e);
+                    }
+                }
+            }
+            let playback = state.engine.get_state();
+            let volume = state.engine.dsp_settings().volume;
+            self.publish_changes(app, &playback, volume, &mut published)?;
+            if self.sent_at.elapsed() >= Duration::from_secs(u64::from(KEEP_ALIVE_SECS / 2)) {
+                self.send(&packet::ping())?;
+            }
+        }
+
+        self.publish(&availability, b"offline")?;
+        self.send(&packet::disconnect())
+    }
+
+    fn publish_changes(
+        &mut self,
+        app: &AppHandle,
+        playback: &PlaybackState,
+        volume: f32,
+        published: &mut Published,
+    ) -> Result<(), String> {
+        let state = if playback.is_playing {
+            "playing"
+        } else if playback.is_paused {
+            "paused"
+        } else {
+            "stopped"
+        };
+        let state_changed = published.state != Some(state);
+        if state_changed {
+            self.publish(&self.topic("state"), state.as_bytes())?;
+            published.state = Some(state);
+        }
+
+        let track = playback
+            .current_file
+            .clone()
+            .map(|f| (f, playback.start_secs.to_bits()));
+        let track_changed = published.track.as_ref() != Some(&track);
+        if track_changed {
+            let payload = match &track {
+                Some(_) => track_json(app, playback).to_string(),
+                None => String::new(),
+            };
+            self.publish(&self.topic("track"), payload.as_bytes())?;
+            published.track = Some(track);
+        }
+
+        if published.volume != Some(volume) {
+            self.publish(&self.topic("volume"), volume.to_string().as_bytes())?;
+            published.volume = Some(volume);
+        }
+
+        let position = playback.position_secs.max(0.0);
+        let due = match published.position {
+            Some((at, when)) => {
+                let expected = if playback.is_playing {
+                    at + when.elapsed().as_secs_f64()
+                } else {
+                    at
+                };
+                state_changed
+                    || track_changed
+                    || (position - expected).abs() > SEEK_THRESHOLD_SECS
+                    || (playback.is_playing && when.elapsed() >= POSITION_INTERVAL)
+            }
+            None => true,
+        };
+        if due {
+            let payload = format!("{:.1}", position);
+            self.publish(&self.topic("position"), payload.as_bytes())?;
+            published.position = Some((position, Instant::now()));
+        }
+        Ok(())
+    }
+
+    fn topic(&self, name: &str) -> String {
+        format!("{}/{}", self.base, name)
+    }
+
+    /// Publish retained.
+    fn publish(&mut self, topic: &str, payload: &[u8]) -> Result<(), String> {
+        self.send(&packet::publish(topic, payload, true))
+    }
+
+    fn send(&mut self, bytes: &[u8]) -> Result<(), String> {
+        self.stream
+            .write_all(bytes)
+            .map_err(|e| format!("Lost connection to MQTT broker: {}", e))?;
+        self.sent_at = Instant::now();
+        Ok(())
+    }
+
+    /// Whole packets received within a poll interval.
+    fn receive(&mut self) -> Result<Vec<Packet>, String> {
+        let mut chunk = [0u8; 4096];
+        match self.stream.read(&mut chunk) {
+            Ok(0) => return Err("MQTT broker closed the connection".to_string()),
+            Ok(n) => self.buffer.extend_from_slice(&chunk[..n]),
+            Err(e) if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => {}
+            Err(e) => return Err(format!("Lost connection to MQTT broker: {}", e)),
+        }
+        let mut packets = Vec::new();
+        while let Some(packet) = packet::parse(&mut self.buffer)? {
+            packets.push(packet);
+        }
+        Ok(packets)
+    }
+}
+
+/// A command's payload: a volume on `<base>/volume/set`, otherwise an
+/// action's name or a JSON command.
+fn run_command(app: &AppHandle, volume_set: bool, payload: &str) -> Result<(), String> {
+    let command = if volume_set {
+        let volume: f32 = payload
+            .parse()
+            .map_err(|_| format!("Invalid volume: {}", payload))?;
+        serde_json::json!({ "action": "set_volume", "volume": volume.clamp(0.0, 1.0) })
+    } else if payload.starts_with('{') {
+        serde_json::from_str(payload).map_err(|e| format!("Invalid command: {}", e))?
+    } else {
+        serde_json::json!({ "action": payload })
+    };
+    api::run(app, api::parse(command)?).map(|_: Value| ())
+}
+
+/// Title, artist, album and length of the playing track.
+fn track_json(app: &AppHandle, playback: &PlaybackState) -> Value {
+    let file = playback.current_file.as_deref().unwrap_or_default();
+    let state = app.state::<AppState>();
+    let tags = metadata_cache::metadata(&state.library, file).ok();
+    // Cue-sheet tracks share one audio file; their title is in the queue
+    let queued_title = state.queue.lock().current().and_then(|entry| {
+        (entry.path == file && entry.start_secs == playback.start_secs)
+            .then(|| entry.title.clone())
+            .flatten()
+    });
+    let title = queued_title
+        .or_else(|| tags.as_ref().and_then(|t| t.title.clone()))
+        .or_else(|| {
+            Path::new(file)
+                .file_stem()
+                .map(|s| s.to_string_lossy().to_string())
+        });
+    serde_json::json!({
+        "title": title,
+        "artist": tags.as_ref().and_then(|t| t.artist.clone()),
+        "album": tags.as_ref().and_then(|t| t.album.clone()),
+        "duration_secs": playback.duration_secs,
+        "path": file,
+    })
+}
+
+/// Why the broker refused a connection, from its return code.
+fn refusal(code: u8) -> String {
+    let reason = match code {
+        1 => "it doesn't speak MQTT 3.1.1",
+        2 => "the client id was rejected",
+        3 => "the server is unavailable",
+        4 => "bad username or password",
+        5 => "not authorized",
+        _ => "unknown reason",
+    };
+    format!("MQTT broker refused the connection: {}", reason)
+}
@@ -0,0 +1,160 @@
+//! The few MQTT 3.1.1 packets the bridge needs, all at QoS 0: connect (with
+//! a will), publish, subscribe, ping and disconnect going out; connection
+//! and subscription acks, publishes and ping responses coming in.
+
+/// Packets the broker sends.
+pub enum Packet {
+    /// 0 when the connection was accepted.
+    ConnAck {
+        code: u8,
+    },
+    Publish {
+        topic: String,
+        payload: Vec<u8>,
+    },
+    SubAck,
+    PingResp,
+    /// Anything else, which QoS 0 shouldn't bring.
+    Other,
+}
+
+pub struct Will<'a> {
+    pub topic: &'a str,
+    pub payload: &'a [u8],
+    pub retain: bool,
+}
+
+pub fn connect(
+    client_id: &str,
+    username: Option<&str>,
+    password: Option<&str>,
+    keep_alive_secs: u16,
+    will: &Will,
+) -> Vec<u8> {
+    let mut body = Vec::new();
+    string(&mut body, b"MQTT");
+    // Protocol level 4 (3.1.1)
+    body.push(4);
+    let mut flags = 0x02 | 0x04;
+    if will.retain {
+        flags |= 0x20;
+    }
+    if username.is_some() {
+        flags |= 0x80;
+        if password.is_some() {
+            flags |= 0x40;
+        }
+    }
+    body.push(flags);
+    body.extend_from_slice(&keep_alive_secs.to_be_bytes());
+    string(&mut body, client_id.as_bytes());
+    string(&mut body, will.topic.as_bytes());
+    string(&mut body, will.payload);
+    if let Some(username) = username {
+        string(&mut body, username.as_bytes());
+        if let Some(password) = password {
+            string(&mut body, password.as_bytes());
+        }
+    }
+    packet(0x10, &body)
+}
+
+pub fn publish(topic: &str, payload: &[u8], retain: bool) -> Vec<u8> {
+    let mut body = Vec::with_capacity(topic.len() + payload.len() + 2);
+    string(&mut body, topic.as_bytes());
+    body.extend_from_slice(payload);
+    packet(0x30 | u8::from(retain), &body)
+}
+
+pub fn subscribe(packet_id: u16, topics: &[&str]) -> Vec<u8> {
+    let mut body = packet_id.to_be_bytes().to_vec();
+    for topic in topics {
+        string(&mut body, topic.as_bytes());
+        body.push(0);
+    }
+    packet(0x82, &body)
+}
+
+pub fn ping() -> Vec<u8> {
+    packet(0xc0, &[])
+}
+
+pub fn disconnect() -> Vec<u8> {
+    packet(0xe0, &[])
+}
+
+/// Take the first whole packet off the front of `buffer`, if it holds one.
+pub fn parse(buffer: &mut Vec<u8>) -> Result<Option<Packet>, String> {
+    let Some(&first) = buffer.first() else {
+        return Ok(None);
+    };
+    // Remaining length: 7 bits a byte, low first, up to 4 bytes
+    let mut length = 0usize;
+    let mut header = 1;
+    loop {
+        let Some(&byte) = buffer.get(header) else {
+            return Ok(None);
+        };
+        length |= usize::from(byte & 0x7f) << (7 * (header - 1));
+        header += 1;
+        if byte & 0x80 == 0 {
+            break;
+        }
+        if header > 4 {
+            return Err("Malformed MQTT packet".to_string());
+        }
+    }
+    if buffer.len() < header + length {
+        return Ok(None);
+    }
+    let body: Vec<u8> = buffer.drain(..header + length).skip(header).collect();
+
+    let packet = match first >> 4 {
+        2 => Packet::ConnAck {
+            code: body.get(1).copied().unwrap_or(0xff),
+        },
+        3 => {
+            let topic_len = body
+                .get(..2)
+                .map(|b| usize::from(u16::from_be_bytes([b[0], b[1]])))
+                .ok_or("Malformed MQTT publish")?;
+            let topic = body.get(2..2 + topic_len).ok_or("Malformed MQTT publish")?;
+            // A packet id follows the topic at QoS 1 and 2
+            let payload_start = 2 + topic_len + if first & 0x06 != 0 { 2 } else { 0 };
+            Packet::Publish {
+                topic: String::from_utf8_lossy(topic).to_string(),
+                payload: body.get(payload_start..).unwrap_or_default().to_vec(),
+            }
+        }
+        9 => Packet::SubAck,
+        13 => Packet::PingResp,
+        _ => Packet::Other,
+    };
+    Ok(Some(packet))
+}
+
+/// A fixed header (type and flags, remaining length) and the body.
+fn packet(kind: u8, body: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(body.len() + 5);
+    out.push(kind);
+    let mut length = body.len();
+    loop {
+        let mut byte = (length % 128) as u8;
+        length /= 128;
+        if length > 0 {
+            byte |= 0x80;
+        }
+        out.push(byte);
+        if length == 0 {
+            break;
+        }
+    }
+    out.extend_from_slice(body);
+    out
+}
+
+/// A length-prefixed string.
+fn string(out: &mut Vec<u8>, bytes: &[u8]) {
+    out.extend_from_slice(&(bytes.len() as u16).to_be_bytes());
+    out.extend_from_slice(bytes);
+}
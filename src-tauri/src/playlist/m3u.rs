@@ -0,0 +1,42 @@
+//! M3U / M3U8 playlist reading.
+//!
+//! Only the track locations are used: `#EXTM3U` / `#EXTINF` lines are skipped
+//! and relative entries are resolved against the playlist's folder.
+
+use std::path::Path;
+
+pub fn is_m3u_file(path: &str) -> bool {
+    Path::new(path)
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(|e| e.eq_ignore_ascii_case("m3u") || e.eq_ignore_ascii_case("m3u8"))
+        .unwrap_or(false)
+}
+
+/// Read the file paths listed in an M3U playlist.
+pub fn read_m3u(path: &str) -> Result<Vec<String>, String> {
+    let bytes = std::fs::read(path).map_err(|e| format!("Failed to read playlist: {}", e))?;
+    let text = String::from_utf8_lossy(&bytes);
+    let base_dir = Path::new(path).parent().unwrap_or(Path::new(""));
+
+    Ok(text
+        .trim_start_matches('\u{feff}')
+        .lines()
+        .map(str::trim)
+        .filter(|l| !l.is_empty() && !l.starts_with('#'))
+        .filter_map(|l| {
+            let l = l.strip_prefix("file://").unwrap_or(l);
+            if l.contains("://") {
+                // Remote URLs aren't playable by the local decoder
+                return None;
+            }
+            let p = Path::new(l);
+            let full = if p.is_absolute() {
+                p.to_path_buf()
+            } else {
+                base_dir.join(p)
+            };
+            Some(full.to_string_lossy().to_string())
+        })
+        .collect())
+}
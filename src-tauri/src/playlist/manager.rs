@@ -1,2 +1,292 @@
-// Playlist manager - will be implemented in Phase 3
-// Placeholder for playlist CRUD + M3U import
+//! Saved playlists.
+//!
+//! Each playlist remembers how it is ordered: either by a sort key (re-applied
+//! whenever tracks are added) or a manual order that is kept exactly as the
+//! user arranged it. Playlists are stored as JSON in the app data directory.
+
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::path::Path;
+
+use crate::metadata::reader;
+
+const PLAYLISTS_FILE: &str = "playlists.json";
+
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum PlaylistSortKey {
+    /// Artist → album → disc → track.
+    Artist,
+    /// Album → disc → track.
+    Album,
+    /// Track title.
+    Title,
+    /// File path.
+    Path,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Playlist {
+    pub id: u64,
+    pub name: String,
+    /// Track file paths, in display order.
+    pub tracks: Vec<String>,
+    /// `None` = manual order.
+    pub sort_key: Option<PlaylistSortKey>,
+    pub sort_descending: bool,
+}
+
+#[derive(Clone, Serialize)]
+pub struct PlaylistSummary {
+    pub id: u64,
+    pub name: String,
+    pub track_count: usize,
+    pub sort_key: Option<PlaylistSortKey>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct PlaylistStore {
+    playlists: Vec<Playlist>,
+    next_id: u64,
+}
+
+impl Default for PlaylistStore {
+    fn default() -> Self {
+        Self {
+            playlists: Vec::new(),
+            next_id: 1,
+        }
+    }
+}
+
+impl PlaylistStore {
+    /// Load playlists from disk. Returns empty store if file doesn't exist.
+    pub fn load(app_data_dir: &Path) -> Self {
+        let path = app_data_dir.join(PLAYLISTS_FILE);
+        if let Ok(data) = std::fs::read_to_string(&path) {
+            serde_json::from_str(&data).unwrap_or_default()
+        } else {
+            Self::default()
+        }
+    }
+
+    /// Save playlists to disk.
+    pub fn save(&self, app_data_dir: &Path) -> Result<(), String> {
+        let path = app_data_dir.join(PLAYLISTS_FILE);
+        std::fs::create_dir_all(app_data_dir)
+            .map_err(|e| format!("Failed to create dir: {}", e))?;
+        let json =
+            serde_json::to_string_pretty(self).map_err(|e| format!("Serialize failed: {}", e))?;
+        std::fs::write(&path, json).map_err(|e| format!("Write failed: {}", e))?;
+        Ok(())
+    }
+
+    pub fn list(&self) -> Vec<PlaylistSummary> {
+        self.playlists
+            .iter()
+            .map(|p| PlaylistSummary {
+                id: p.id,
+                name: p.name.clone(),
+                track_count: p.tracks.len(),
+                sort_key: p.sort_key,
+            })
+            .collect()
+    }
+
+    /// Every playlist, in creation order.
+    pub fn all(&self) -> Vec<Playlist> {
+        self.playlists.clone()
+    }
+
+    pub fn get(&self, id: u64) -> Result<Playlist, String> {
+        self.playlists
+            .iter()
+            .find(|p| p.id == id)
+            .cloned()
+            .ok_or_else(|| format!("Playlist {} not found", id))
+    }
+
+    pub fn create(&mut self, name: String) -> Playlist {
+        let playlist = Playlist {
+            id: self.next_id,
+            name,
+            tracks: Vec::new(),
+            sort_key: None,
+            sort_descending: false,
+        };
+        self.next_id += 1;
+        self.playlists.push(playlist.clone());
+        playlist
+    }
+
+    pub fn rename(&mut self, id: u64, name: String) -> Result<(), String> {
+        self.get_mut(id)?.name = name;
+        Ok(())
+    }
+
+    pub fn delete(&mut self, id: u64) {
+        self.playlists.retain(|p| p.id != id);
+    }
+
+    /// Add playlists from a backup under new ids. An existing playlist with
+    /// the same name is replaced.
+    pub fn restore(&mut self, playlists: Vec<Playlist>) {
+        for mut playlist in playlists {
+            self.playlists.retain(|p| p.name != playlist.name);
+            playlist.id = self.next_id;
+            self.next_id += 1;
+            self.playlists.push(playlist);
+        }
+    }
+
+    /// Follow files that were moved (old path to new). Returns true if any
+    /// playlist changed.
+    pub fn rename_paths(&mut self, moved: &HashMap<String, String>) -> bool {
+        let mut changed = false;
+        for path in self.playlists.iter_mut().flat_map(|p| p.tracks.iter_mut()) {
+            if let Some(new_path) = moved.get(path) {
+                *path = new_path.clone();
+                changed = true;
+            }
+        }
+        changed
+    }
+
+    /// Append tracks. Sorted playlists are re-sorted so new tracks land in place.
+    pub fn add_tracks(&mut self, id: u64, paths: Vec<String>) -> Result<(), String> {
+        let playlist = self.get_mut(id)?;
+        playlist.tracks.extend(paths);
+        if playlist.sort_key.is_some() {
+            playlist.apply_sort();
+        }
+        Ok(())
+    }
+
+    /// Remove tracks by index.
+    pub fn remove_tracks(&mut self, id: u64, mut indices: Vec<usize>) -> Result<(), String> {
+        let playlist = self.get_mut(id)?;
+        indices.sort_unstable();
+        indices.dedup();
+        for i in indices.into_iter().rev() {
+            if i < playlist.tracks.len() {
+                playlist.tracks.remove(i);
+            }
+        }
+        Ok(())
+    }
+
+    /// Move a track within the playlist. A manual move discards any sort key —
+    /// the playlist switches to (and keeps) the manual order.
+    pub fn move_track(&mut self, id: u64, from: usize, to: usize) -> Result<(), String> {
+        let playlist = self.get_mut(id)?;
+        let len = playlist.tracks.len();
+        if from >= len || to >= len {
+            return Err(format!("Track index out of range (len {})", len));
+        }
+        let track = playlist.tracks.remove(from);
+        playlist.tracks.insert(to, track);
+        playlist.sort_key = None;
+        Ok(())
+    }
+
+    /// Sort by `key` and remember it, so later additions keep the order.
+    pub fn sort(&mut self, id: u64, key: PlaylistSortKey, descending: bool) -> Result<(), String> {
+        let playlist = self.get_mut(id)?;
+        playlist.sort_key = Some(key);
+        playlist.sort_descending = descending;
+        playlist.apply_sort();
+        Ok(())
+    }
+
+    /// Freeze the current order as the manual order (drops the sort key).
+    pub fn lock_order(&mut self, id: u64) -> Result<(), String> {
+        self.get_mut(id)?.sort_key = None;
+        Ok(())
+    }
+
+    fn get_mut(&mut self, id: u64) -> Result<&mut Playlist, String> {
+        self.playlists
+            .iter_mut()
+            .find(|p| p.id == id)
+            .ok_or_else(|| format!("Playlist {} not found", id))
+    }
+}
+
+impl Playlist {
+    fn apply_sort(&mut self) {
+        let Some(key) = self.sort_key else {
+            return;
+        };
+
+        let mut keyed: Vec<(SortFields, String)> = self
+            .tracks
+            .drain(..)
+            .map(|path| {
+                let fields = match key {
+                    // Path order needs no tags — skip probing every file
+                    PlaylistSortKey::Path => SortFields::default(),
+                    _ => SortFields::read(&path),
+                };
+                (fields, path)
+            })
+            .collect();
+
+        keyed.sort_by(|(a, pa), (b, pb)| {
+            let ord = a.compare(b, key).then_with(|| pa.cmp(pb));
+            if self.sort_descending {
+                ord.reverse()
+            } else {
+                ord
+            }
+        });
+
+        self.tracks = keyed.into_iter().map(|(_, path)| path).collect();
+    }
+}
+
+/// Tag values used for sorting. Missing tags sort as empty strings / 0.
+#[derive(Default)]
+struct SortFields {
+    artist: String,
+    album: String,
+    title: String,
+    disc: u32,
+    track: u32,
+}
+
+impl SortFields {
+    fn read(path: &str) -> Self {
+        match reader::read_metadata(path) {
+            Ok(m) => Self {
+                artist: m
+                    .album_artist
+                    .or(m.artist)
+                    .unwrap_or_default()
+                    .to_lowercase(),
+                album: m.album.unwrap_or_default().to_lowercase(),
+                title: m.title.unwrap_or(m.file_name).to_lowercase(),
+                disc: m.disc_number.unwrap_or(0),
+                track: m.track_number.unwrap_or(0),
+            },
+            Err(_) => Self::default(),
+        }
+    }
+
+    fn compare(&self, other: &Self, key: PlaylistSortKey) -> Ordering {
+        let by_position = || {
+            self.disc
+                .cmp(&other.disc)
+                .then_with(|| self.track.cmp(&other.track))
+        };
+        match key {
+            PlaylistSortKey::Artist => self
+                .artist
+                .cmp(&other.artist)
+                .then_with(|| self.album.cmp(&other.album))
+                .then_with(by_position),
+            PlaylistSortKey::Album => self.album.cmp(&other.album).then_with(by_position),
+            PlaylistSortKey::Title => self.title.cmp(&other.title),
+            PlaylistSortKey::Path => Ordering::Equal,
+        }
+    }
+}
@@ -1 +1,3 @@
+pub mod m3u;
 pub mod manager;
+pub mod queue;
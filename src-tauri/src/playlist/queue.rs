@@ -0,0 +1,430 @@
+//! Playback queue with play history.
+//!
+//! The queue owns the ordered list of entries, the current position and the
+//! shuffle/repeat modes. Every time playback moves forward, the entry that was
+//! playing is pushed onto a history stack, so "previous" returns to the track
+//! that was *actually* heard — even in shuffle mode, where the prior queue
+//! index has nothing to do with what played before.
+//!
+//! Enqueuing a `.cue` file expands it into one entry per virtual track, each
+//! pointing at the image file with start/end offsets; a single virtual track
+//! path (`Album.cue#03`, as the library lists them) becomes that one entry.
+//! M3U playlists expand into the files they list. Streams from media servers
+//! are queued by URL.
+
+use super::m3u;
+use crate::metadata::cue;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// "Previous" restarts the current track instead of going back once playback
+/// is past this point (same behaviour as foobar2000 / Apple Music).
+pub const PREVIOUS_RESTART_THRESHOLD_MS: u64 = 3000;
+
+/// Maximum number of history entries kept. Oldest entries are dropped first.
+const MAX_HISTORY: usize = 500;
+
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RepeatMode {
+    Off,
+    All,
+    One,
+}
+
+/// How enqueuing a path that is already in the queue is handled.
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DedupMode {
+    /// Always append (duplicates allowed).
+    Off,
+    /// Leave the existing entry where it is and skip the new one.
+    Ignore,
+    /// Move the existing entry to the end of the queue instead of adding a copy.
+    MoveToEnd,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct QueueEntry {
+    /// Unique per queue entry, so the same file queued twice stays distinguishable.
+    pub id: u64,
+    pub path: String,
+    /// Start offset into `path` in seconds (non-zero for cue-sheet tracks).
+    #[serde(default)]
+    pub start_secs: f64,
+    /// End offset into `path`. `None` plays to the end of the file.
+    #[serde(default)]
+    pub end_secs: Option<f64>,
+    /// Track title from the cue sheet for virtual tracks, or from the media
+    /// server for streams.
+    #[serde(default)]
+    pub title: Option<String>,
+}
+
+/// A stream queued by URL (see [`PlayQueue::enqueue_streams`]).
+#[derive(Clone, Deserialize)]
+pub struct QueueStream {
+    pub url: String,
+    pub title: Option<String>,
+}
+
+impl QueueEntry {
+    /// Same underlying audio (file + segment), regardless of entry id.
+    fn same_source(&self, other: &QueueEntry) -> bool {
+        self.path == other.path && self.start_secs == other.start_secs
+    }
+}
+
+#[derive(Clone, Serialize)]
+pub struct HistoryEntry {
+    /// Queue entry id at the time it was played.
+    pub entry_id: u64,
+    pub path: String,
+    /// Unix timestamp (seconds) when playback of this entry started.
+    pub played_at: u64,
+}
+
+#[derive(Clone, Serialize)]
+pub struct QueueSnapshot {
+    pub entries: Vec<QueueEntry>,
+    pub current_index: Option<usize>,
+    pub shuffle: bool,
+    pub repeat: RepeatMode,
+    pub dedup: DedupMode,
+}
+
+/// Result of an enqueue operation.
+#[derive(Clone, Serialize)]
+pub struct EnqueueReport {
+    /// Newly created entries.
+    pub added: Vec<QueueEntry>,
+    /// Existing entries moved to the end (dedup mode `MoveToEnd`).
+    pub moved: Vec<QueueEntry>,
+    /// Paths not added because they were already queued (dedup mode `Ignore`).
+    pub skipped: Vec<String>,
+    /// Paths that could not be queued (e.g. unreadable cue sheets), with the reason.
+    pub errors: Vec<String>,
+}
+
+/// What the caller should do in response to a "previous" request.
+pub enum PreviousAction {
+    /// Seek the current track back to 0.
+    Restart,
+    /// Start playing this entry.
+    Play(QueueEntry),
+}
+
+pub struct PlayQueue {
+    entries: Vec<QueueEntry>,
+    current: Option<usize>,
+    /// Unix timestamp of when the current entry started playing.
+    current_started_at: u64,
+    shuffle: bool,
+    repeat: RepeatMode,
+    dedup: DedupMode,
+    /// Previously played entries, most recent last.
+    history: Vec<HistoryEntry>,
+    next_id: u64,
+}
+
+impl Default for PlayQueue {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PlayQueue {
+    pub fn new() -> Self {
+        Self {
+            entries: Vec::new(),
+            current: None,
+            current_started_at: 0,
+            shuffle: false,
+            repeat: RepeatMode::Off,
+            dedup: DedupMode::Off,
+            history: Vec::new(),
+            next_id: 1,
+        }
+    }
+
+    pub fn snapshot(&self) -> QueueSnapshot {
+        QueueSnapshot {
+            entries: self.entries.clone(),
+            current_index: self.current,
+            shuffle: self.shuffle,
+            repeat: self.repeat,
+            dedup: self.dedup,
+        }
+    }
+
+    pub fn current(&self) -> Option<&QueueEntry> {
+        self.current.and_then(|i| self.entries.get(i))
+    }
+
+    /// Append paths to the end of the queue, applying the dedup mode.
+    /// Cue sheets are expanded into their virtual tracks.
+    pub fn enqueue(&mut self, paths: Vec<String>) -> EnqueueReport {
+        let mut report = EnqueueReport {
+            added: Vec::new(),
+            moved: Vec::new(),
+            skipped: Vec::new(),
+            errors: Vec::new(),
+        };
+
+        let mut items = Vec::new();
+        for path in paths {
+            match expand_path(path) {
+                Ok(expanded) => items.extend(expanded),
+                Err(e) => report.errors.push(e),
+            }
+        }
+        self.append(items, &mut report);
+        report
+    }
+
+    /// Append streams from media servers, which carry their own titles
+    /// since there are no tags to read them from.
+    pub fn enqueue_streams(&mut self, streams: Vec<QueueStream>) -> EnqueueReport {
+        let mut report = EnqueueReport {
+            added: Vec::new(),
+            moved: Vec::new(),
+            skipped: Vec::new(),
+            errors: Vec::new(),
+        };
+        let items = streams
+            .into_iter()
+            .map(|stream| QueueEntry {
+                id: 0,
+                path: stream.url,
+                start_secs: 0.0,
+                end_secs: None,
+                title: stream.title,
+            })
+            .collect();
+        self.append(items, &mut report);
+        report
+    }
+
+    fn append(&mut self, items: Vec<QueueEntry>, report: &mut EnqueueReport) {
+        for item in items {
+            let existing = match self.dedup {
+                DedupMode::Off => None,
+                _ => self.entries.iter().position(|e| e.same_source(&item)),
+            };
+
+            match (existing, self.dedup) {
+                (Some(_), DedupMode::Ignore) => report.skipped.push(item.path),
+                (Some(idx), DedupMode::MoveToEnd) => {
+                    let entry = self.entries.remove(idx);
+                    // Keep `current` pointing at the same entry after the shift
+                    self.current = self.current.map(|c| {
+                        if c == idx {
+                            self.entries.len()
+                        } else if c > idx {
+                            c - 1
+                        } else {
+                            c
+                        }
+                    });
+                    self.entries.push(entry.clone());
+                    report.moved.push(entry);
+                }
+                _ => {
+                    let entry = QueueEntry {
+                        id: self.next_id,
+                        ..item
+                    };
+                    self.next_id += 1;
+                    self.entries.push(entry.clone());
+                    report.added.push(entry);
+                }
+            }
+        }
+    }
+
+    /// Remove all entries. History is kept — it records what was played, not what is queued.
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.current = None;
+    }
+
+    pub fn set_shuffle(&mut self, on: bool) {
+        self.shuffle = on;
+    }
+
+    pub fn set_repeat(&mut self, mode: RepeatMode) {
+        self.repeat = mode;
+    }
+
+    pub fn set_dedup(&mut self, mode: DedupMode) {
+        self.dedup = mode;
+    }
+
+    /// Move to the next entry according to shuffle/repeat.
+    /// Returns `None` at the end of the queue (repeat off).
+    pub fn advance(&mut self) -> Option<QueueEntry> {
+        if self.entries.is_empty() {
+            return None;
+        }
+
+        let len = self.entries.len();
+        let next = match self.current {
+            Some(i) if self.repeat == RepeatMode::One => i,
+            Some(i) if self.shuffle => {
+                if len == 1 {
+                    0
+                } else {
+                    // Pick a random index different from the current one
+                    let mut rng = rand::thread_rng();
+                    let r = rng.gen_range(0..len - 1);
+                    if r >= i {
+                        r + 1
+                    } else {
+                        r
+                    }
+                }
+            }
+            Some(i) if i + 1 < len => i + 1,
+            Some(_) if self.repeat == RepeatMode::All => 0,
+            Some(_) => return None,
+            None if self.shuffle => rand::thread_rng().gen_range(0..len),
+            None => 0,
+        };
+
+        // Repeat-one replays don't count as new history — "previous" should
+        // leave the looped track, not land on another copy of it.
+        if self.current != Some(next) {
+            self.push_current_to_history();
+        }
+        self.set_current(next);
+        self.current().cloned()
+    }
+
+    /// Make `index` the current entry (e.g. double-click in the queue view).
+    /// The entry that was playing goes onto the history stack like a normal advance.
+    pub fn jump_to(&mut self, index: usize) -> Option<QueueEntry> {
+        if index >= self.entries.len() {
+            return None;
+        }
+        if self.current != Some(index) {
+            self.push_current_to_history();
+        }
+        self.set_current(index);
+        self.current().cloned()
+    }
+
+    /// Go back to the previously played entry.
+    ///
+    /// Restarts the current track when more than [`PREVIOUS_RESTART_THRESHOLD_MS`]
+    /// in. Otherwise pops the history stack, skipping entries that have since been
+    /// removed from the queue. Without usable history, falls back to the prior queue
+    /// index (non-shuffle only), and finally to restarting the current track.
+    pub fn previous(&mut self, position_ms: u64) -> PreviousAction {
+        if position_ms > PREVIOUS_RESTART_THRESHOLD_MS && self.current.is_some() {
+            return PreviousAction::Restart;
+        }
+
+        while let Some(h) = self.history.pop() {
+            if let Some(idx) = self.index_of(h.entry_id) {
+                self.set_current(idx);
+                return PreviousAction::Play(self.entries[idx].clone());
+            }
+        }
+
+        match self.current {
+            Some(i) if !self.shuffle && i > 0 => {
+                self.set_current(i - 1);
+                PreviousAction::Play(self.entries[i - 1].clone())
+            }
+            _ => PreviousAction::Restart,
+        }
+    }
+
+    /// Played entries, most recent first.
+    pub fn history(&self) -> Vec<HistoryEntry> {
+        self.history.iter().rev().cloned().collect()
+    }
+
+    /// Current position of the entry with this id.
+    pub fn index_of(&self, entry_id: u64) -> Option<usize> {
+        self.entries.iter().position(|e| e.id == entry_id)
+    }
+
+    fn set_current(&mut self, index: usize) {
+        self.current = Some(index);
+        self.current_started_at = unix_now();
+    }
+
+    fn push_current_to_history(&mut self) {
+        if let Some(entry) = self.current() {
+            let h = HistoryEntry {
+                entry_id: entry.id,
+                path: entry.path.clone(),
+                played_at: self.current_started_at,
+            };
+            self.history.push(h);
+            if self.history.len() > MAX_HISTORY {
+                self.history.remove(0);
+            }
+        }
+    }
+}
+
+/// Turn an enqueued path into entries (ids unassigned): one per cue-sheet
+/// track for `.cue` files, the listed files for M3U playlists, otherwise the
+/// whole file.
+fn expand_path(path: String) -> Result<Vec<QueueEntry>, String> {
+    if m3u::is_m3u_file(&path) {
+        let mut entries = Vec::new();
+        for listed in m3u::read_m3u(&path).map_err(|e| format!("{}: {}", path, e))? {
+            // Nested playlists aren't followed — only cue sheets and plain files
+            if !m3u::is_m3u_file(&listed) {
+                entries.extend(expand_path(listed)?);
+            }
+        }
+        return Ok(entries);
+    }
+
+    if let Some((_, track)) =
+        cue::find_virtual_track(&path).map_err(|e| format!("{}: {}", path, e))?
+    {
+        return Ok(vec![QueueEntry {
+            id: 0,
+            path: track.file,
+            start_secs: track.start_secs,
+            end_secs: track.end_secs,
+            title: track.title,
+        }]);
+    }
+
+    if !cue::is_cue_file(&path) {
+        return Ok(vec![QueueEntry {
+            id: 0,
+            path,
+            start_secs: 0.0,
+            end_secs: None,
+            title: None,
+        }]);
+    }
+
+    let sheet = cue::parse_cue_file(&path).map_err(|e| format!("{}: {}", path, e))?;
+    Ok(sheet
+        .tracks
+        .into_iter()
+        .map(|t| QueueEntry {
+            id: 0,
+            path: t.file,
+            start_secs: t.start_secs,
+            end_secs: t.end_secs,
+            title: t.title,
+        })
+        .collect())
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
@@ -0,0 +1,159 @@
+//! The remote server's REST endpoints and the commands they share with the
+//! WebSocket.
+//!
+//! - `GET /api/state`: [`PlaybackState`]
+//! - `GET /api/queue`: the queue, as `get_queue` returns it
+//! - `GET /api/search?q=...&limit=...`: library search hits
+//! - `POST /api/<action>`: run a [`RemoteCommand`]; the JSON body holds its
+//!   fields, e.g. `POST /api/seek` with `{"position_secs": 30}`. Answers
+//!   `{"result": ...}`.
+//!
+//! Over the WebSocket a command is the same object with the action named:
+//! `{"action": "seek", "position_secs": 30}`.
+//!
+//! Commands go through the same functions as the app's own, and are emitted
+//! as `remote://command` (the action's name) so the UI can refresh.
+
+use serde::Deserialize;
+use serde_json::Value;
+use tauri::{AppHandle, Emitter, Manager};
+
+use super::request::{Request, Response};
+use crate::audio::engine::PlaybackState;
+use crate::commands::{self, AppState};
+use crate::library::search::DEFAULT_SEARCH_LIMIT;
+use crate::playlist::queue::RepeatMode;
+
+#[derive(Deserialize)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum RemoteCommand {
+    /// A file or cue-sheet track path; the queue is left alone.
+    Play {
+        path: String,
+    },
+    Pause,
+    Resume,
+    /// Pause when playing, resume otherwise.
+    Toggle,
+    Stop,
+    Next,
+    Previous,
+    Seek {
+        position_secs: f64,
+    },
+    SetVolume {
+        volume: f32,
+    },
+    Enqueue {
+        paths: Vec<String>,
+    },
+    ClearQueue,
+    PlayQueueIndex {
+        index: usize,
+    },
+    SetShuffle {
+        enabled: bool,
+    },
+    SetRepeatMode {
+        mode: RepeatMode,
+    },
+}
+
+pub fn handle(app: &AppHandle, request: &Request) -> Response {
+    let state = app.state::<AppState>();
+    let Some(endpoint) = request.path.strip_prefix("/api/") else {
+        return Response::error(404, "Not found");
+    };
+    match (request.method.as_str(), endpoint) {
+        ("GET", "state") => Response::json(&state.engine.get_state()),
+        ("GET", "queue") => Response::json(&commands::get_queue(state)),
+        ("GET", "search") => {
+            let Some(query) = request.query("q") else {
+                return Response::error(400, "Missing q");
+            };
+            let limit = request
+                .query("limit")
+                .and_then(|l| l.parse().ok())
+                .unwrap_or(DEFAULT_SEARCH_LIMIT);
+            match state.library.lock().search(query, limit, true) {
+                Ok(hits) => Response::json(&hits),
+                Err(e) => Response::error(500, &e),
+            }
+        }
+        ("POST", action) => {
+            let mut fields = if request.body.is_empty() {
+                Value::Object(Default::default())
+            } else {
+                match serde_json::from_slice(&request.body) {
+                    Ok(Value::Object(fields)) => Value::Object(fields),
+                    _ => return Response::error(400, "Body must be a JSON object"),
+                }
+            };
+            fields["action"] = Value::String(action.to_string());
+            match parse(fields).and_then(|command| run(app, command)) {
+                Ok(result) => Response::json(&serde_json::json!({ "result": result })),
+                Err(e) => Response::error(400, &e),
+            }
+        }
+        (_, "state" | "queue" | "search") => Response::error(405, "Method not allowed"),
+        _ => Response::error(404, "Not found"),
+    }
+}
+
+/// A command from its JSON object.
+pub fn parse(command: Value) -> Result<RemoteCommand, String> {
+    serde_json::from_value(command).map_err(|e| format!("Invalid command: {}", e))
+}
+
+/// Run a command. Returns what the matching app command returns, or null.
+pub fn run(app: &AppHandle, command: RemoteCommand) -> Result<Value, String> {
+    let state = || app.state::<AppState>();
+    let (action, result) = match command {
+        RemoteCommand::Play { path } => ("play", json(commands::play_file(path, state())?)),
+        RemoteCommand::Pause => ("pause", json(commands::pause(state())?)),
+        RemoteCommand::Resume => ("resume", json(commands::resume(state())?)),
+        RemoteCommand::Toggle => {
+            if state().engine.get_state().is_playing {
+                ("pause", json(commands::pause(state())?))
+            } else {
+                ("resume", json(commands::resume(state())?))
+            }
+        }
+        RemoteCommand::Stop => ("stop", json(commands::stop(state())?)),
+        RemoteCommand::Next => ("next", json(commands::next_track(state()))),
+        RemoteCommand::Previous => ("previous", json(commands::previous_track(state()))),
+        RemoteCommand::Seek { position_secs } => {
+            ("seek", json(commands::seek(position_secs, state())?))
+        }
+        RemoteCommand::SetVolume { volume } => {
+            ("set_volume", json(commands::set_volume(volume, state())?))
+        }
+        RemoteCommand::Enqueue { paths } => {
+            ("enqueue", json(commands::add_to_queue(paths, state())))
+        }
+        RemoteCommand::ClearQueue => ("clear_queue", json(commands::clear_queue(state())?)),
+        RemoteCommand::PlayQueueIndex { index } => (
+            "play_queue_index",
+            json(commands::play_queue_index(index, state())?),
+        ),
+        RemoteCommand::SetShuffle { enabled } => (
+            "set_shuffle",
+            json(commands::set_shuffle(enabled, state())?),
+        ),
+        RemoteCommand::SetRepeatMode { mode } => (
+            "set_repeat_mode",
+            json(commands::set_repeat_mode(mode, state())?),
+        ),
+    };
+    let _ = app.emit("remote://command", action);
+    Ok(result)
+}
+
+/// The playback state as the WebSocket pushes it.
+pub fn state_message(state: &PlaybackState) -> String {
+    serde_json::json!({ "type": "state", "state": state }).to_string()
+}
+
+fn json<T: serde::Serialize>(value: T) -> Value {
+    serde_json::to_value(value).unwrap_or(Value::Null)
+}
@@ -0,0 +1,303 @@
+//! Remote control server: an optional HTTP server on the LAN, so phones and
+//! scripts can control playback, manage the queue and search the library.
+//!
+//! REST endpoints under `/api` take and return JSON; `/ws` is a WebSocket
+//! that pushes the playback state as it changes and takes the same commands
+//! as the REST endpoints (see [`api`]). Every request needs the server's
+//! token, as `Authorization: Bearer <token>` or a `token` query parameter
+//! (browsers can't set headers on a WebSocket).
+//!
+//! Off by default. The settings and token are stored as JSON in the app
+//! data directory; the token is generated on first use and can be
+//! regenerated, which drops every connected client. Each connection gets
+//! its own thread, like the rest of the backend, up to
+//! [`MAX_CONNECTIONS`].
+
+pub mod api;
+mod request;
+mod websocket;
+
+use parking_lot::Mutex;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::net::{IpAddr, TcpListener, TcpStream, UdpSocket};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+use tauri::AppHandle;
+
+use request::Response;
+
+const SETTINGS_FILE: &str = "remote_server.json";
+
+pub const DEFAULT_PORT: u16 = 8765;
+
+/// Connections served at once; more are turned away.
+pub const MAX_CONNECTIONS: usize = 32;
+
+/// How often the listener checks whether it should stop.
+const ACCEPT_INTERVAL: Duration = Duration::from_millis(100);
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+struct RemoteSettings {
+    enabled: bool,
+    port: u16,
+    token: String,
+}
+
+impl Default for RemoteSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            port: DEFAULT_PORT,
+            token: String::new(),
+        }
+    }
+}
+
+#[derive(Clone, Serialize)]
+pub struct RemoteServerStatus {
+    pub enabled: bool,
+    pub port: u16,
+    /// Needed by every request.
+    pub token: String,
+    /// Listening right now.
+    pub running: bool,
+    /// Address to reach the server at from the LAN, when running.
+    pub url: Option<String>,
+    /// Why the server couldn't start, e.g. the port is taken.
+    pub error: Option<String>,
+}
+
+struct Running {
+    stop: Arc<AtomicBool>,
+    listener: JoinHandle<()>,
+}
+
+pub struct RemoteServer {
+    app_data_dir: PathBuf,
+    settings: Mutex<RemoteSettings>,
+    running: Mutex<Option<Running>>,
+    error: Mutex<Option<String>>,
+}
+
+impl RemoteServer {
+    /// Load the settings, generating a token the first time. Call
+    /// [`RemoteServer::start`] once the app is up.
+    pub fn load(app_data_dir: &Path) -> Self {
+        let mut settings: RemoteSettings =
+            std::fs::read_to_string(app_data_dir.join(SETTINGS_FILE))
+                .ok()
+                .and_then(|data| serde_json::from_str(&data).ok())
+                .unwrap_or_default();
+        let server = Self {
+            app_data_dir: app_data_dir.to_path_buf(),
+            settings: Mutex::new(RemoteSettings::default()),
+            running: Mutex::new(None),
+            error: Mutex::new(None),
+        };
+        if settings.token.is_empty() {
+            settings.token = new_token();
+            if let Err(e) = server.save(&settings) {
+                log::warn!("{}", e);
+            }
+        }
+        *server.settings.lock() = settings;
+        server
+    }
+
+    pub fn status(&self) -> RemoteServerStatus {
+        let settings = self.settings.lock().clone();
+        let running = self.running.lock().is_some();
+        RemoteServerStatus {
+            enabled: settings.enabled,
+            port: settings.port,
+            token: settings.token,
+            running,
+            url: running
+                .then(lan_address)
+                .flatten()
+                .map(|ip| format!("http://{}:{}", ip, settings.port)),
+            error: self.error.lock().clone(),
+        }
+    }
+
+    /// Switch the server on or off and choose its port, restarting it.
+    pub fn configure(
+        &self,
+        app: &AppHandle,
+        enabled: bool,
+        port: u16,
+    ) -> Result<RemoteServerStatus, String> {
+        if port == 0 {
+            return Err("Port must be between 1 and 65535".to_string());
+        }
+        {
+            let mut settings = self.settings.lock();
+            settings.enabled = enabled;
+            settings.port = port;
+            self.save(&settings)?;
+        }
+        self.start(app);
+        Ok(self.status())
+    }
+
+    /// Replace the token, disconnecting every client.
+    pub fn regenerate_token(&self, app: &AppHandle) -> Result<RemoteServerStatus, String> {
+        {
+            let mut settings = self.settings.lock();
+            settings.token = new_token();
+            self.save(&settings)?;
+        }
+        self.start(app);
+        Ok(self.status())
+    }
+
+    /// (Re)start the server as the settings say: stop it if running, and
+    /// listen again when enabled.
+    pub fn start(&self, app: &AppHandle) {
+        let mut running = self.running.lock();
+        if let Some(previous) = running.take() {
+            previous.stop.store(true, Ordering::Relaxed);
+            let _ = previous.listener.join();
+        }
+        *self.error.lock() = None;
+
+        let settings = self.settings.lock().clone();
+        if !settings.enabled {
+            return;
+        }
+        let listener = match TcpListener::bind(("0.0.0.0", settings.port))
+            .and_then(|l| l.set_nonblocking(true).map(|_| l))
+        {
+            Ok(listener) => listener,
+            Err(e) => {
+                let message = format!("Failed to listen on port {}: {}", settings.port, e);
+                log::warn!("{}", message);
+                *self.error.lock() = Some(message);
+                return;
+            }
+        };
+        log::info!("Remote control server listening on port {}", settings.port);
+
+        let stop = Arc::new(AtomicBool::new(false));
+        let app = app.clone();
+        let token: Arc<str> = settings.token.into();
+        let stopped = stop.clone();
+        let listener = thread::Builder::new()
+            .name("remote-server".into())
+            .spawn(move || accept_loop(listener, app, token, stopped))
+            .expect("Failed to spawn remote server thread");
+        *running = Some(Running { stop, listener });
+    }
+
+    fn save(&self, settings: &RemoteSettings) -> Result<(), String> {
+        std::fs::create_dir_all(&self.app_data_dir)
+            .map_err(|e| format!("Failed to create dir: {}", e))?;
+        let json = serde_json::to_string_pretty(settings)
+            .map_err(|e| format!("Serialize failed: {}", e))?;
+        std::fs::write(self.app_data_dir.join(SETTINGS_FILE), json)
+            .map_err(|e| format!("Write failed: {}", e))
+    }
+}
+
+fn accept_loop(listener: TcpListener, app: AppHandle, token: Arc<str>, stop: Arc<AtomicBool>) {
+    let connections = Arc::new(AtomicUsize::new(0));
+    while !stop.load(Ordering::Relaxed) {
+        let stream = match listener.accept() {
+            Ok((stream, _)) => stream,
+            Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => {
+                thread::sleep(ACCEPT_INTERVAL);
+                continue;
+            }
+            Err(e) => {
+                log::warn!("Remote server accept failed: {}", e);
+                thread::sleep(ACCEPT_INTERVAL);
+                continue;
+            }
+        };
+        if connections.fetch_add(1, Ordering::Relaxed) >= MAX_CONNECTIONS {
+            connections.fetch_sub(1, Ordering::Relaxed);
+            let _ = request::write_response(&stream, &Response::error(503, "Too many connections"));
+            continue;
+        }
+        let (client_app, client_token, client_stop, client_connections) = (
+            app.clone(),
+            token.clone(),
+            stop.clone(),
+            connections.clone(),
+        );
+        let spawned = thread::Builder::new()
+            .name("remote-client".into())
+            .spawn(move || {
+                serve(&client_app, stream, &client_token, &client_stop);
+                client_connections.fetch_sub(1, Ordering::Relaxed);
+            });
+        if let Err(e) = spawned {
+            connections.fetch_sub(1, Ordering::Relaxed);
+            log::warn!("Failed to spawn remote client thread: {}", e);
+        }
+    }
+}
+
+/// Answer one request, or run a WebSocket until it closes.
+fn serve(app: &AppHandle, stream: TcpStream, token: &str, stop: &AtomicBool) {
+    if stream.set_nonblocking(false).is_err() {
+        return;
+    }
+    let request = match request::read_request(&stream) {
+        Ok(request) => request,
+        Err(e) => {
+            let _ = request::write_response(&stream, &Response::error(400, &e));
+            return;
+        }
+    };
+
+    let response = if request.method == "OPTIONS" {
+        // CORS preflight, for web pages served from elsewhere
+        Response::empty(204)
+    } else if !authorized(&request, token) {
+        Response::error(401, "Missing or wrong token")
+    } else if request.path == "/ws" {
+        if let Err(e) = websocket::serve(app, stream, &request, stop) {
+            log::debug!("Remote WebSocket closed: {}", e);
+        }
+        return;
+    } else {
+        api::handle(app, &request)
+    };
+    let _ = request::write_response(&stream, &response);
+}
+
+fn authorized(request: &request::Request, token: &str) -> bool {
+    let given = request
+        .header("authorization")
+        .and_then(|h| h.strip_prefix("Bearer "))
+        .or_else(|| request.query("token"));
+    // Compared in full every time, so timing doesn't give the token away
+    given.is_some_and(|given| {
+        given.len() == token.len()
+            && given
+                .bytes()
+                .zip(token.bytes())
+                .fold(0, |diff, (a, b)| diff | (a ^ b))
+                == 0
+    })
+}
+
+/// 32 random hex digits.
+fn new_token() -> String {
+    let bytes: [u8; 16] = rand::thread_rng().gen();
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// This machine's address on the LAN: the one the OS would send from to
+/// reach the internet. Nothing is sent.
+fn lan_address() -> Option<IpAddr> {
+    let socket = UdpSocket::bind(("0.0.0.0", 0)).ok()?;
+    socket.connect(("8.8.8.8", 80)).ok()?;
+    socket.local_addr().ok().map(|a| a.ip())
+}
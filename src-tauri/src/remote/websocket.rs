@@ -0,0 +1,111 @@
+//! The remote server's WebSocket: pushes `{"type": "state", "state": ...}`
+//! on connecting, whenever playback starts, stops, pauses or changes track,
+//! and every second while playing; takes commands (see [`super::api`]) and
+//! answers each with `{"type": "result", "result": ...}` or
+//! `{"type": "result", "error": ...}`.
+//!
+//! `tungstenite` does the framing on the connection's own thread. Reads
+//! time out every [`POLL_INTERVAL`] so the state can be pushed in between.
+
+use serde_json::Value;
+use std::io::{ErrorKind, Write};
+use std::net::TcpStream;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Manager};
+use tungstenite::handshake::derive_accept_key;
+use tungstenite::protocol::Role;
+use tungstenite::{Message, WebSocket};
+
+use super::api;
+use super::request::Request;
+use crate::commands::AppState;
+
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// How often the position is pushed while playing.
+const POSITION_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Complete the upgrade `request` asked for and serve the socket until the
+/// client leaves or the server stops.
+pub fn serve(
+    app: &AppHandle,
+    mut stream: TcpStream,
+    request: &Request,
+    stop: &AtomicBool,
+) -> Result<(), String> {
+    let key = request
+        .header("sec-websocket-key")
+        .ok_or("Not a WebSocket request")?;
+    write!(
+        stream,
+        "HTTP/1.1 101 Switching Protocols\r\n\
+         Upgrade: websocket\r\n\
+         Connection: Upgrade\r\n\
+         Sec-WebSocket-Accept: {}\r\n\r\n",
+        derive_accept_key(key.as_bytes())
+    )
+    .map_err(|e| e.to_string())?;
+    stream
+        .set_read_timeout(Some(POLL_INTERVAL))
+        .map_err(|e| e.to_string())?;
+    let mut socket = WebSocket::from_raw_socket(stream, Role::Server, None);
+
+    let state = app.state::<AppState>();
+    // (playing, paused, file, segment start) last pushed, and when
+    let mut pushed = None;
+    let mut pushed_at = Instant::now();
+
+    loop {
+        if stop.load(Ordering::Relaxed) {
+            let _ = socket.close(None);
+            let _ = socket.flush();
+            return Ok(());
+        }
+
+        match socket.read() {
+            Ok(Message::Text(text)) => {
+                let reply = match serde_json::from_str::<Value>(&text)
+                    .map_err(|e| format!("Invalid JSON: {}", e))
+                    .and_then(api::parse)
+                    .and_then(|command| api::run(app, command))
+                {
+                    Ok(result) => serde_json::json!({ "type": "result", "result": result }),
+                    Err(e) => serde_json::json!({ "type": "result", "error": e }),
+                };
+                socket
+                    .send(Message::Text(reply.to_string()))
+                    .map_err(|e| e.to_string())?;
+            }
+            Ok(Message::Close(_)) => {
+                let _ = socket.flush();
+                return Ok(());
+            }
+            // Pings are answered by tungstenite
+            Ok(_) => {}
+            Err(tungstenite::Error::Io(e))
+                if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => {}
+            Err(tungstenite::Error::ConnectionClosed | tungstenite::Error::AlreadyClosed) => {
+                return Ok(());
+            }
+            Err(e) => return Err(e.to_string()),
+        }
+
+        let playback = state.engine.get_state();
+        let key = (
+            playback.is_playing,
+            playback.is_paused,
+            playback.current_file.clone(),
+            playback.start_secs,
+        );
+        let due = pushed.as_ref() != Some(&key)
+            || (playback.is_playing && pushed_at.elapsed() >= POSITION_INTERVAL);
+        if due {
+            socket
+                .send(Message::Text(api::state_message(&playback)))
+                .map_err(|e| e.to_string())?;
+            pushed = Some(key);
+            pushed_at = Instant::now();
+        }
+    }
+}
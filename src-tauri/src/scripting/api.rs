@@ -0,0 +1,130 @@
+//! What scripts can call, on top of Rhai's core language:
+//!
+//! - `log(text)`: an info line in the app's log
+//! - `append_line(file, text)`: add a line to `scripts/output/<file>`; the
+//!   name can't contain a path
+//! - `http_post(url, body)`: post text, returning the response body
+//! - `http_post_json(url, value)`: post a map or array as JSON
+//! - `now()`: Unix time in seconds
+//!
+//! Failures raise errors the script can `try`/`catch`.
+
+use rhai::{Dynamic, Engine, EvalAltResult, ImmutableString, INT};
+use serde_json::Value;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use crate::http;
+
+/// Operations one call may run before it's stopped.
+const MAX_OPERATIONS: u64 = 1_000_000;
+const MAX_CALL_LEVELS: usize = 32;
+const MAX_STRING_SIZE: usize = 1 << 20;
+const MAX_COLLECTION_SIZE: usize = 10_000;
+
+const OUTPUT_DIR: &str = "output";
+
+type Result<T> = std::result::Result<T, Box<EvalAltResult>>;
+
+/// An engine with the limits and API, for scripts in `scripts_dir`.
+pub fn engine(scripts_dir: &Path) -> Engine {
+    let mut engine = Engine::new();
+    engine
+        .set_max_operations(MAX_OPERATIONS)
+        .set_max_call_levels(MAX_CALL_LEVELS)
+        .set_max_string_size(MAX_STRING_SIZE)
+        .set_max_array_size(MAX_COLLECTION_SIZE)
+        .set_max_map_size(MAX_COLLECTION_SIZE)
+        .set_module_resolver(rhai::module_resolvers::DummyModuleResolver::new());
+    engine.disable_symbol("eval");
+
+    engine.register_fn("log", |text: ImmutableString| {
+        log::info!("script: {}", text);
+    });
+    let output_dir = scripts_dir.join(OUTPUT_DIR);
+    engine.register_fn(
+        "append_line",
+        move |file: ImmutableString, text: ImmutableString| -> Result<()> {
+            append_line(&output_dir, &file, &text).map_err(Into::into)
+        },
+    );
+    engine.register_fn(
+        "http_post",
+        |url: ImmutableString, body: ImmutableString| -> Result<String> {
+            post(&url, "text/plain; charset=utf-8", &body)
+        },
+    );
+    engine.register_fn(
+        "http_post_json",
+        |url: ImmutableString, value: Dynamic| -> Result<String> {
+            let body = to_json(&value)?.to_string();
+            post(&url, "application/json", &body)
+        },
+    );
+    engine.register_fn("now", || -> INT {
+        std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_secs() as INT)
+            .unwrap_or(0)
+    });
+    engine
+}
+
+fn append_line(output_dir: &Path, file: &str, text: &str) -> std::result::Result<(), String> {
+    let name = Path::new(file);
+    if file.is_empty() || name.file_name() != Some(name.as_os_str()) || file.starts_with('.') {
+        return Err(format!("Invalid output file name: {}", file));
+    }
+    std::fs::create_dir_all(output_dir)
+        .map_err(|e| format!("Failed to create {}: {}", output_dir.display(), e))?;
+    let path: PathBuf = output_dir.join(name);
+    let mut out = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .map_err(|e| format!("Failed to open {}: {}", path.display(), e))?;
+    writeln!(out, "{}", text).map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+}
+
+fn post(url: &str, content_type: &str, body: &str) -> Result<String> {
+    if !url.starts_with("http://") && !url.starts_with("https://") {
+        return Err(format!("Not an http(s) URL: {}", url).into());
+    }
+    http::post_text(url, &[("Content-Type", content_type)], body).map_err(Into::into)
+}
+
+/// A script value as JSON. Functions and other host types can't be sent.
+fn to_json(value: &Dynamic) -> Result<Value> {
+    if value.is_unit() {
+        return Ok(Value::Null);
+    }
+    if let Ok(b) = value.as_bool() {
+        return Ok(Value::Bool(b));
+    }
+    if let Ok(n) = value.as_int() {
+        return Ok(Value::from(n));
+    }
+    if let Ok(n) = value.as_float() {
+        return Ok(serde_json::Number::from_f64(n).map_or(Value::Null, Value::Number));
+    }
+    if value.is_string() {
+        return Ok(Value::String(value.clone().into_string()?));
+    }
+    if value.is_array() {
+        let array = value.read_lock::<rhai::Array>().ok_or("Unreadable array")?;
+        return array
+            .iter()
+            .map(to_json)
+            .collect::<Result<Vec<_>>>()
+            .map(Value::Array);
+    }
+    if value.is_map() {
+        let map = value.read_lock::<rhai::Map>().ok_or("Unreadable map")?;
+        return map
+            .iter()
+            .map(|(k, v)| Ok((k.to_string(), to_json(v)?)))
+            .collect::<Result<serde_json::Map<_, _>>>()
+            .map(Value::Object);
+    }
+    Err(format!("Can't send a {} as JSON", value.type_name()).into())
+}
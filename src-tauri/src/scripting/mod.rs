@@ -0,0 +1,287 @@
+//! User scripts run on playback events, written in Rhai.
+//!
+//! Scripts are the `.rhai` files in `scripts/` under the app data
+//! directory, loaded in name order. A script handles an event by defining
+//! its function:
+//!
+//! ```text
+//! fn on_track_change(track) { ... }      // a new track started
+//! fn on_stop(track) { ... }              // playback stopped
+//! fn on_rating_change(track, rating) { } // 0 when the rating was cleared
+//! ```
+//!
+//! `track` is a map: `path`, `title`, `artist`, `album`, `album_artist`,
+//! `year`, `genre`, `track_number`, `duration_secs` (missing tags are `()`).
+//! Scripts see only the API in [`api`]: logging, appending lines to files
+//! in `scripts/output/`, HTTP posts for webhooks and the time. They can't
+//! import modules or `eval`, and each call is cut off after a fixed number
+//! of operations, so a runaway script can't hang the player.
+//!
+//! Scripts run one event at a time on their own thread, so a slow webhook
+//! holds up other scripts but never playback. Top-level statements run
+//! once, when the script loads. Edits take effect on [`Scripts::reload`].
+
+mod api;
+
+use parking_lot::Mutex;
+use rhai::{CallFnOptions, Dynamic, Engine, Map, Scope, AST, INT};
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Sender};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use crate::audio::engine::AudioEngine;
+use crate::metadata::reader;
+
+const SCRIPTS_DIR: &str = "scripts";
+
+/// How often the engine is checked for track changes and stops.
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+#[derive(Clone, Serialize)]
+pub struct ScriptStatus {
+    /// File name, e.g. `history.rhai`.
+    pub name: String,
+    /// The event functions it defines.
+    pub hooks: Vec<String>,
+    /// Why it failed to load, or the last error it raised.
+    pub error: Option<String>,
+}
+
+#[derive(Clone, Serialize)]
+pub struct ScriptsStatus {
+    /// Where scripts go.
+    pub dir: String,
+    pub scripts: Vec<ScriptStatus>,
+}
+
+enum Message {
+    Reload,
+    TrackChange(String),
+    Stop(String),
+    RatingChange(String, Option<u8>),
+}
+
+pub struct Scripts {
+    dir: PathBuf,
+    tx: Sender<Message>,
+    status: Arc<Mutex<Vec<ScriptStatus>>>,
+}
+
+impl Scripts {
+    /// Load the scripts and start the threads that run them and watch the
+    /// engine for their events.
+    pub fn spawn(app_data_dir: &Path, engine: Arc<AudioEngine>) -> Arc<Self> {
+        let dir = app_data_dir.join(SCRIPTS_DIR);
+        if let Err(e) = std::fs::create_dir_all(&dir) {
+            log::warn!("Failed to create {}: {}", dir.display(), e);
+        }
+        let (tx, rx) = mpsc::channel();
+        let status = Arc::new(Mutex::new(Vec::new()));
+
+        let (worker_dir, worker_status) = (dir.clone(), status.clone());
+        thread::Builder::new()
+            .name("scripts".into())
+            .spawn(move || {
+                let mut runner = Runner::new(worker_dir, worker_status);
+                runner.load();
+                for message in rx {
+                    match message {
+                        Message::Reload => runner.load(),
+                        Message::TrackChange(path) => {
+                            runner.call("on_track_change", &path, |track| vec![track])
+                        }
+                        Message::Stop(path) => runner.call("on_stop", &path, |track| vec![track]),
+                        Message::RatingChange(path, rating) => {
+                            let rating = INT::from(rating.unwrap_or(0));
+                            runner.call("on_rating_change", &path, |track| {
+                                vec![track, rating.into()]
+                            })
+                        }
+                    }
+                }
+            })
+            .expect("Failed to spawn scripts thread");
+
+        let watch_tx = tx.clone();
+        thread::Builder::new()
+            .name("scripts-watch".into())
+            .spawn(move || watch(&engine, &watch_tx))
+            .expect("Failed to spawn scripts watcher thread");
+
+        Arc::new(Self { dir, tx, status })
+    }
+
+    pub fn status(&self) -> ScriptsStatus {
+        ScriptsStatus {
+            dir: self.dir.to_string_lossy().to_string(),
+            scripts: self.status.lock().clone(),
+        }
+    }
+
+    /// Load the scripts again, picking up new, changed and removed files.
+    pub fn reload(&self) {
+        let _ = self.tx.send(Message::Reload);
+    }
+
+    /// Tell scripts a track's rating changed.
+    pub fn rating_changed(&self, path: &str, rating: Option<u8>) {
+        let _ = self
+            .tx
+            .send(Message::RatingChange(path.to_string(), rating));
+    }
+}
+
+/// Send track changes and stops until the scripts thread is gone.
+fn watch(engine: &AudioEngine, tx: &Sender<Message>) {
+    let mut current: Option<String> = None;
+    loop {
+        thread::sleep(POLL_INTERVAL);
+        let state = engine.get_state();
+        let active = state.is_playing || state.is_paused;
+        let file = state.current_file.filter(|_| active);
+        if file == current {
+            continue;
+        }
+        let message = match (&file, current.take()) {
+            (Some(file), _) => Message::TrackChange(file.clone()),
+            (None, Some(stopped)) => Message::Stop(stopped),
+            (None, None) => continue,
+        };
+        if tx.send(message).is_err() {
+            return;
+        }
+        current = file;
+    }
+}
+
+struct Script {
+    name: String,
+    ast: AST,
+}
+
+/// The engine and loaded scripts; lives on the scripts thread.
+struct Runner {
+    engine: Engine,
+    dir: PathBuf,
+    scripts: Vec<Script>,
+    status: Arc<Mutex<Vec<ScriptStatus>>>,
+}
+
+impl Runner {
+    fn new(dir: PathBuf, status: Arc<Mutex<Vec<ScriptStatus>>>) -> Self {
+        Self {
+            engine: api::engine(&dir),
+            dir,
+            scripts: Vec::new(),
+            status,
+        }
+    }
+
+    fn load(&mut self) {
+        let mut files: Vec<PathBuf> = std::fs::read_dir(&self.dir)
+            .map(|entries| {
+                entries
+                    .filter_map(|e| e.ok().map(|e| e.path()))
+                    .filter(|p| p.extension().is_some_and(|e| e == "rhai"))
+                    .collect()
+            })
+            .unwrap_or_default();
+        files.sort();
+
+        self.scripts.clear();
+        let mut status = Vec::new();
+        for path in files {
+            let name = path
+                .file_name()
+                .map(|n| n.to_string_lossy().to_string())
+                .unwrap_or_default();
+            let loaded = self.engine.compile_file(path).and_then(|ast| {
+                self.engine.run_ast_with_scope(&mut Scope::new(), &ast)?;
+                Ok(ast)
+            });
+            match loaded {
+                Ok(ast) => {
+                    let hooks = ast
+                        .iter_functions()
+                        .map(|f| f.name.to_string())
+                        .filter(|n| n.starts_with("on_"))
+                        .collect();
+                    log::info!("Loaded script {}", name);
+                    status.push(ScriptStatus {
+                        name: name.clone(),
+                        hooks,
+                        error: None,
+                    });
+                    self.scripts.push(Script { name, ast });
+                }
+                Err(e) => {
+                    log::warn!("Script {} failed to load: {}", name, e);
+                    status.push(ScriptStatus {
+                        name,
+                        hooks: Vec::new(),
+                        error: Some(e.to_string()),
+                    });
+                }
+            }
+        }
+        *self.status.lock() = status;
+    }
+
+    /// Call `hook` in every script defining it, with the track at `path`
+    /// and whatever else `args` adds.
+    fn call(&mut self, hook: &str, path: &str, args: impl Fn(Dynamic) -> Vec<Dynamic>) {
+        let defines = |script: &Script| script.ast.iter_functions().any(|f| f.name == hook);
+        if !self.scripts.iter().any(defines) {
+            return;
+        }
+        let track = Dynamic::from_map(track_map(path));
+        for script in self.scripts.iter().filter(|s| defines(s)) {
+            let result = self.engine.call_fn_with_options::<Dynamic>(
+                CallFnOptions::new().eval_ast(false),
+                &mut Scope::new(),
+                &script.ast,
+                hook,
+                args(track.clone()),
+            );
+            if let Err(e) = result {
+                log::warn!("Script {} failed in {}: {}", script.name, hook, e);
+                if let Some(status) = self
+                    .status
+                    .lock()
+                    .iter_mut()
+                    .find(|s| s.name == script.name)
+                {
+                    status.error = Some(format!("{}: {}", hook, e));
+                }
+            }
+        }
+    }
+}
+
+/// What scripts get as `track`: the tags when they can be read, the path
+/// in any case.
+fn track_map(path: &str) -> Map {
+    let mut map = Map::new();
+    map.insert("path".into(), path.to_string().into());
+    let Ok(tags) = reader::read_metadata(path) else {
+        return map;
+    };
+    let text = |value: Option<String>| value.map(Dynamic::from).unwrap_or(Dynamic::UNIT);
+    let number = |value: Option<u32>| {
+        value
+            .map(|n| Dynamic::from(INT::from(n)))
+            .unwrap_or(Dynamic::UNIT)
+    };
+    map.insert("title".into(), text(tags.title));
+    map.insert("artist".into(), text(tags.artist));
+    map.insert("album".into(), text(tags.album));
+    map.insert("album_artist".into(), text(tags.album_artist));
+    map.insert("year".into(), number(tags.year));
+    map.insert("genre".into(), text(tags.genre));
+    map.insert("track_number".into(), number(tags.track_number));
+    map.insert("duration_secs".into(), tags.duration_secs.into());
+    map
+}
@@ -0,0 +1,198 @@
+//! App-wide settings, stored as `settings.json` in the app data directory.
+//!
+//! Per-device preferences stay in [`DeviceProfileStore`]; this is for
+//! everything that isn't tied to an output. Sections are separate structs
+//! with `#[serde(default)]`, so a file written by an older version loads
+//! with defaults for whatever it lacks.
+//!
+//! [`DeviceProfileStore`]: crate::audio::device_profiles::DeviceProfileStore
+
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+use crate::audio::engine::{AudioCommand, AudioEngine, ReplayGainMode};
+use crate::buffering::BufferMode;
+use crate::dsp_plugins::{self, ChainEntry};
+use crate::errors::{self, ErrorCode};
+use crate::logging::{self, LogLevel};
+
+const SETTINGS_FILE: &str = "settings.json";
+
+/// Longest pause/resume/stop fade. Stop waits for the fade to finish.
+pub const MAX_FADE_MS: u32 = 1000;
+
+/// ReplayGain preamp range, in dB.
+pub const MAX_PREAMP_DB: f32 = 15.0;
+
+#[derive(Clone, Serialize, Deserialize, Default)]
+#[serde(default)]
+pub struct Settings {
+    pub playback: PlaybackSettings,
+    pub library: LibrarySettings,
+    pub logging: LoggingSettings,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PlaybackSettings {
+    /// Pause, resume and stop fade length in ms (0 = the shortest pop-free ramp).
+    pub fade_ms: u32,
+    pub replaygain_mode: ReplayGainMode,
+    /// Added to tagged files' ReplayGain, in dB.
+    pub replaygain_preamp_db: f32,
+    pub clipping_prevention: bool,
+    /// Buffering by battery saver and the network, or fixed.
+    pub buffer_mode: BufferMode,
+    /// DSP plugins run after ReplayGain, in order.
+    pub dsp_chain: Vec<ChainEntry>,
+}
+
+impl Default for PlaybackSettings {
+    fn default() -> Self {
+        Self {
+            fade_ms: 0,
+            replaygain_mode: ReplayGainMode::Off,
+            replaygain_preamp_db: 0.0,
+            clipping_prevention: true,
+            buffer_mode: BufferMode::Auto,
+            dsp_chain: Vec::new(),
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize, Default)]
+#[serde(default)]
+pub struct LibrarySettings {
+    /// Rescan every enabled folder when the app starts.
+    pub scan_on_startup: bool,
+}
+
+#[derive(Clone, Serialize, Deserialize, Default)]
+#[serde(default)]
+pub struct LoggingSettings {
+    /// Lowest level written to the log file and kept for reports.
+    pub level: LogLevel,
+}
+
+impl Settings {
+    fn validate(&self) -> Result<(), String> {
+        if self.playback.fade_ms > MAX_FADE_MS {
+            return Err(format!("Fade length must be at most {} ms", MAX_FADE_MS));
+        }
+        let preamp = self.playback.replaygain_preamp_db;
+        if !preamp.is_finite() || preamp.abs() > MAX_PREAMP_DB {
+            return Err(format!(
+                "ReplayGain preamp must be between -{0} and {0} dB",
+                MAX_PREAMP_DB
+            ));
+        }
+        dsp_plugins::validate(&self.playback.dsp_chain)
+    }
+
+    /// Bring invalid values from the file back in range, keeping the rest.
+    /// Returns what was wrong.
+    fn repair(&mut self) -> Vec<String> {
+        let mut problems = Vec::new();
+        let playback = &mut self.playback;
+        if playback.fade_ms > MAX_FADE_MS {
+            problems.push(format!("fade length {} ms", playback.fade_ms));
+            playback.fade_ms = MAX_FADE_MS;
+        }
+        let preamp = playback.replaygain_preamp_db;
+        if !preamp.is_finite() || preamp.abs() > MAX_PREAMP_DB {
+            problems.push(format!("ReplayGain preamp {} dB", preamp));
+            playback.replaygain_preamp_db = if preamp.is_finite() {
+                preamp.clamp(-MAX_PREAMP_DB, MAX_PREAMP_DB)
+            } else {
+                0.0
+            };
+        }
+        if let Err(e) = dsp_plugins::validate(&playback.dsp_chain) {
+            problems.push(e);
+            dsp_plugins::repair(&mut playback.dsp_chain);
+        }
+        problems
+    }
+
+    /// Put the playback and logging settings into effect.
+    pub fn apply(&self, engine: &AudioEngine) {
+        logging::set_level(self.logging.level);
+        let playback = &self.playback;
+        engine.send_command(AudioCommand::SetFadeLength(playback.fade_ms));
+        engine.send_command(AudioCommand::SetReplayGainPreamp(
+            playback.replaygain_preamp_db,
+        ));
+        engine.send_command(AudioCommand::SetReplayGain(playback.replaygain_mode));
+        engine.send_command(AudioCommand::SetClippingPrevention(
+            playback.clipping_prevention,
+        ));
+    }
+}
+
+pub struct SettingsStore {
+    path: PathBuf,
+    settings: Mutex<Settings>,
+}
+
+impl SettingsStore {
+    /// Load settings, falling back to defaults if the file is missing or
+    /// unreadable. Out-of-range values are reset to the nearest valid ones.
+    pub fn load(app_data_dir: &Path) -> Self {
+        let path = app_data_dir.join(SETTINGS_FILE);
+        let settings = match std::fs::read_to_string(&path) {
+            Ok(data) => serde_json::from_str::<Settings>(&data)
+                .map_err(|e| {
+                    errors::report(
+                        ErrorCode::ConfigLoad,
+                        format!("Ignoring invalid {}: {}", path.display(), e),
+                    )
+                })
+                .map(|mut settings| {
+                    let problems = settings.repair();
+                    if !problems.is_empty() {
+                        errors::report(
+                            ErrorCode::ConfigLoad,
+                            format!(
+                                "Reset invalid values in {}: {}",
+                                path.display(),
+                                problems.join(", ")
+                            ),
+                        );
+                    }
+                    settings
+                })
+                .unwrap_or_default(),
+            Err(_) => Settings::default(),
+        };
+        Self {
+            path,
+            settings: Mutex::new(settings),
+        }
+    }
+
+    pub fn get(&self) -> Settings {
+        self.settings.lock().clone()
+    }
+
+    /// Change the settings and save them. Nothing changes if the result is
+    /// invalid or can't be written.
+    pub fn update(&self, change: impl FnOnce(&mut Settings)) -> Result<Settings, String> {
+        let mut settings = self.settings.lock();
+        let mut updated = settings.clone();
+        change(&mut updated);
+        updated.validate()?;
+        self.save(&updated)?;
+        *settings = updated.clone();
+        Ok(updated)
+    }
+
+    fn save(&self, settings: &Settings) -> Result<(), String> {
+        if let Some(dir) = self.path.parent() {
+            std::fs::create_dir_all(dir).map_err(|e| format!("Failed to create dir: {}", e))?;
+        }
+        let json = serde_json::to_string_pretty(settings)
+            .map_err(|e| format!("Serialize failed: {}", e))?;
+        std::fs::write(&self.path, json).map_err(|e| format!("Write failed: {}", e))
+    }
+}
@@ -0,0 +1,354 @@
+//! UPnP/DLNA media servers (MinimServer, Plex, Serviio, ...) as a library
+//! source, browsed without copying anything.
+//!
+//! Servers are found with an SSDP search on the LAN, or added by the URL of
+//! their device description where multicast doesn't get through. Their
+//! ContentDirectory service is browsed a container at a time with SOAP
+//! `Browse` calls; the DIDL-Lite listings that come back give containers to
+//! descend into and audio items with an HTTP URL, which plays (or queues)
+//! like a path and is streamed by [`crate::audio::http_source`].
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::net::UdpSocket;
+use std::str::FromStr;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use super::xml::{self, Element};
+use crate::http;
+
+const SSDP_ADDRESS: &str = "239.255.255.250:1900";
+
+const MEDIA_SERVER: &str = "urn:schemas-upnp-org:device:MediaServer:1";
+
+const CONTENT_DIRECTORY: &str = "urn:schemas-upnp-org:service:ContentDirectory:";
+
+/// How long servers get to answer a search.
+const DISCOVERY_TIME: Duration = Duration::from_secs(3);
+
+/// Object id of every server's top container.
+pub const ROOT_ID: &str = "0";
+
+/// Entries asked for per browse when the caller doesn't say.
+pub const DEFAULT_BROWSE_COUNT: u32 = 200;
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct DlnaServer {
+    pub name: String,
+    /// Unique device name, stable across restarts and addresses.
+    pub udn: String,
+    pub manufacturer: Option<String>,
+    pub model: Option<String>,
+    /// URL of the device description.
+    pub location: String,
+    /// ContentDirectory control URL, which browsing goes to.
+    pub control_url: String,
+    /// ContentDirectory service type, e.g. `...:ContentDirectory:1`.
+    pub service_type: String,
+}
+
+#[derive(Clone, Serialize)]
+pub struct DlnaContainer {
+    pub id: String,
+    pub title: String,
+    pub child_count: Option<u32>,
+    pub album_art_url: Option<String>,
+}
+
+#[derive(Clone, Serialize)]
+pub struct DlnaItem {
+    pub id: String,
+    pub title: String,
+    pub artist: Option<String>,
+    pub album: Option<String>,
+    pub track_number: Option<u32>,
+    pub duration_secs: Option<f64>,
+    pub album_art_url: Option<String>,
+    /// Stream URL; play or queue it like a path.
+    pub url: String,
+    /// e.g. `audio/x-flac`.
+    pub mime_type: Option<String>,
+    pub sample_rate: Option<u32>,
+    pub bit_depth: Option<u8>,
+    pub channels: Option<u8>,
+    pub size_bytes: Option<u64>,
+}
+
+#[derive(Clone, Serialize)]
+pub struct DlnaBrowsePage {
+    pub containers: Vec<DlnaContainer>,
+    /// Audio items only; pictures and videos are left out.
+    pub items: Vec<DlnaItem>,
+    /// Offset of this page among the container's children.
+    pub start: u32,
+    /// Entries the server returned, audio or not: the next page starts at
+    /// `start + returned`.
+    pub returned: u32,
+    /// Children of the container in all.
+    pub total: u32,
+}
+
+/// Media servers that answer an SSDP search within [`DISCOVERY_TIME`].
+/// Servers whose description can't be read are left out.
+pub fn discover() -> Result<Vec<DlnaServer>, String> {
+    let socket =
+        UdpSocket::bind(("0.0.0.0", 0)).map_err(|e| format!("Failed to open socket: {}", e))?;
+    socket
+        .set_read_timeout(Some(Duration::from_millis(200)))
+        .map_err(|e| e.to_string())?;
+    let _ = socket.set_multicast_ttl_v4(2);
+    let search = format!(
+        "M-SEARCH * HTTP/1.1\r\n\
+         HOST: {}\r\n\
+         MAN: \"ssdp:discover\"\r\n\
+         MX: 2\r\n\
+         ST: {}\r\n\r\n",
+        SSDP_ADDRESS, MEDIA_SERVER
+    );
+    // Sent twice, as UDP gets lost
+    for _ in 0..2 {
+        socket
+            .send_to(search.as_bytes(), SSDP_ADDRESS)
+            .map_err(|e| format!("SSDP search failed: {}", e))?;
+    }
+
+    let mut locations = Vec::new();
+    let mut buf = [0u8; 2048];
+    let deadline = Instant::now() + DISCOVERY_TIME;
+    while Instant::now() < deadline {
+        let Ok((len, _)) = socket.recv_from(&mut buf) else {
+            continue;
+        };
+        let reply = String::from_utf8_lossy(&buf[..len]);
+        let location = reply.lines().find_map(|line| {
+            let (name, value) = line.split_once(':')?;
+            name.trim()
+                .eq_ignore_ascii_case("location")
+                .then(|| value.trim().to_string())
+        });
+        if let Some(location) = location {
+            if !locations.contains(&location) {
+                locations.push(location);
+            }
+        }
+    }
+
+    // Descriptions fetched side by side, so one slow server doesn't hold up
+    // the rest
+    let described: Vec<Result<DlnaServer, String>> = thread::scope(|scope| {
+        let handles: Vec<_> = locations
+            .iter()
+            .map(|location| scope.spawn(move || describe(location)))
+            .collect();
+        handles
+            .into_iter()
+            .map(|h| h.join().unwrap_or_else(|_| Err("Panicked".to_string())))
+            .collect()
+    });
+    let mut seen = HashSet::new();
+    let mut servers = Vec::new();
+    for (location, server) in locations.iter().zip(described) {
+        match server {
+            // A server listening on several interfaces answers once for each
+            Ok(server) if seen.insert(server.udn.clone()) => servers.push(server),
+            Ok(_) => {}
+            Err(e) => log::warn!("Skipping media server at {}: {}", location, e),
+        }
+    }
+    servers.sort_by_key(|s| s.name.to_lowercase());
+    Ok(servers)
+}
+
+/// Read the device description at `location` (the URL an SSDP answer
+/// gives, e.g. `http://192.168.1.10:9790/desc.xml`).
+pub fn describe(location: &str) -> Result<DlnaServer, String> {
+    let root = xml::parse(&http::get_text(location)?)?;
+    let device = root.child("device").ok_or("No device in description")?;
+    let service = root
+        .descendants("service")
+        .into_iter()
+        .find(|s| {
+            s.child_text("serviceType")
+                .is_some_and(|t| t.starts_with(CONTENT_DIRECTORY))
+        })
+        .ok_or("Not a media server: no ContentDirectory service")?;
+    let control_url = service
+        .child_text("controlURL")
+        .ok_or("ContentDirectory has no control URL")?;
+    let base = root.child_text("URLBase").unwrap_or(location);
+
+    Ok(DlnaServer {
+        name: device
+            .child_text("friendlyName")
+            .unwrap_or("Media server")
+            .to_string(),
+        udn: device.child_text("UDN").unwrap_or(location).to_string(),
+        manufacturer: device.child_text("manufacturer").map(str::to_string),
+        model: device.child_text("modelName").map(str::to_string),
+        location: location.to_string(),
+        control_url: resolve_url(base, control_url),
+        service_type: service
+            .child_text("serviceType")
+            .unwrap_or_default()
+            .to_string(),
+    })
+}
+
+/// One page of a container's children: `count` entries from `start`.
+pub fn browse(
+    server: &DlnaServer,
+    object_id: &str,
+    start: u32,
+    count: u32,
+) -> Result<DlnaBrowsePage, String> {
+    let envelope = format!(
+        r#"<?xml version="1.0" encoding="utf-8"?>
+<s:Envelope xmlns:s="http://schemas.xmlsoap.org/soap/envelope/" s:encodingStyle="http://schemas.xmlsoap.org/soap/encoding/">
+<s:Body><u:Browse xmlns:u="{}">
+<ObjectID>{}</ObjectID>
+<BrowseFlag>BrowseDirectChildren</BrowseFlag>
+<Filter>*</Filter>
+<StartingIndex>{}</StartingIndex>
+<RequestedCount>{}</RequestedCount>
+<SortCriteria></SortCriteria>
+</u:Browse></s:Body>
+</s:Envelope>"#,
+        server.service_type,
+        quick_xml::escape::escape(object_id),
+        start,
+        count
+    );
+    let action = format!("\"{}#Browse\"", server.service_type);
+    let response = http::post_text(
+        &server.control_url,
+        &[
+            ("Content-Type", "text/xml; charset=\"utf-8\""),
+            ("SOAPACTION", &action),
+        ],
+        &envelope,
+    )?;
+
+    let envelope = xml::parse(&response)?;
+    let result = envelope
+        .descendants("BrowseResponse")
+        .into_iter()
+        .next()
+        .ok_or("Server sent no browse result")?;
+    let count_of = |name| {
+        result
+            .child_text(name)
+            .and_then(|n| n.parse().ok())
+            .unwrap_or(0)
+    };
+    let returned = count_of("NumberReturned");
+    let total = count_of("TotalMatches");
+    let didl = match result.child_text("Result") {
+        Some(didl) => xml::parse(didl)?,
+        None => Element::default(),
+    };
+
+    let containers = didl
+        .children_named("container")
+        .filter_map(|c| {
+            Some(DlnaContainer {
+                id: c.attribute("id")?.to_string(),
+                title: c.child_text("title").unwrap_or("Untitled").to_string(),
+                child_count: number(c, "childCount"),
+                album_art_url: c.child_text("albumArtURI").map(str::to_string),
+            })
+        })
+        .collect();
+    let items = didl.children_named("item").filter_map(audio_item).collect();
+
+    Ok(DlnaBrowsePage {
+        containers,
+        items,
+        start,
+        returned,
+        total,
+    })
+}
+
+/// A DIDL-Lite item, when it's audio with a stream the player can fetch.
+fn audio_item(item: &Element) -> Option<DlnaItem> {
+    let class = item.child_text("class").unwrap_or_default();
+    if !class.starts_with("object.item.audioItem") {
+        return None;
+    }
+    // Servers list the original first and transcodes after it
+    let res = item.children_named("res").find(|r| {
+        let protocol = r.attribute("protocolInfo").unwrap_or_default();
+        protocol.starts_with("http-get:")
+            && protocol
+                .split(':')
+                .nth(2)
+                .is_some_and(|mime| mime.starts_with("audio/"))
+    })?;
+    let url = res.text.trim();
+    if url.is_empty() {
+        return None;
+    }
+    let mime_type = res
+        .attribute("protocolInfo")
+        .and_then(|p| p.split(':').nth(2))
+        .map(str::to_string);
+
+    Some(DlnaItem {
+        id: item.attribute("id")?.to_string(),
+        title: item.child_text("title").unwrap_or("Untitled").to_string(),
+        artist: item
+            .child_text("artist")
+            .or_else(|| item.child_text("creator"))
+            .map(str::to_string),
+        album: item.child_text("album").map(str::to_string),
+        track_number: item
+            .child_text("originalTrackNumber")
+            .and_then(|n| n.parse().ok()),
+        duration_secs: res.attribute("duration").and_then(parse_duration),
+        album_art_url: item.child_text("albumArtURI").map(str::to_string),
+        url: url.to_string(),
+        mime_type,
+        sample_rate: number(res, "sampleFrequency"),
+        bit_depth: number(res, "bitsPerSample"),
+        channels: number(res, "nrAudioChannels"),
+        size_bytes: number(res, "size"),
+    })
+}
+
+/// `H:MM:SS` or `H:MM:SS.fff` in seconds.
+fn parse_duration(text: &str) -> Option<f64> {
+    let mut parts = text.trim().split(':');
+    let hours: f64 = parts.next()?.parse().ok()?;
+    let minutes: f64 = parts.next()?.parse().ok()?;
+    let seconds: f64 = parts.next()?.parse().ok()?;
+    Some(hours * 3600.0 + minutes * 60.0 + seconds)
+}
+
+/// `url` resolved against `base`, as a browser would.
+fn resolve_url(base: &str, url: &str) -> String {
+    if url.starts_with("http://") || url.starts_with("https://") {
+        return url.to_string();
+    }
+    // "http://host:port"
+    let authority_end = base
+        .find("://")
+        .map(|scheme| {
+            let rest = scheme + 3;
+            base[rest..].find('/').map_or(base.len(), |p| rest + p)
+        })
+        .unwrap_or(base.len());
+    if url.starts_with('/') {
+        format!("{}{}", &base[..authority_end], url)
+    } else {
+        let directory_end = base[authority_end..]
+            .rfind('/')
+            .map_or(base.len(), |p| authority_end + p);
+        format!("{}/{}", &base[..directory_end], url)
+    }
+}
+
+/// An attribute holding a number, e.g. a `res` element's `sampleFrequency`.
+fn number<T: FromStr>(element: &Element, name: &str) -> Option<T> {
+    element.attribute(name)?.trim().parse().ok()
+}
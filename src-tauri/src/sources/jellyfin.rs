@@ -0,0 +1,749 @@
+//! Jellyfin servers as a library source: their music libraries are browsed
+//! and searched on the server, and their tracks stream through the queue
+//! and engine like local files.
+//!
+//! A server is added with an API key (Dashboard → API Keys) and the user
+//! whose libraries to show; API keys aren't tied to a user, so the user is
+//! looked up by name once and its id stored with the key in the app data
+//! directory.
+//!
+//! Tracks stream as the original file when the decoder takes its codec,
+//! so lossless files arrive bit for bit; anything else is transcoded to FLAC
+//! on the server. Playback of a server's tracks is reported back to it
+//! (start, progress every few seconds, stop), and plays the play counter
+//! counts mark the track played, so the server's "now playing", play counts
+//! and "recently played" follow what's heard here.
+
+use crossbeam_channel::{Receiver, RecvTimeoutError, Sender};
+use parking_lot::Mutex;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use super::random_hex;
+use crate::audio::engine::{AudioEngine, PlaybackState};
+use crate::http;
+
+const SETTINGS_FILE: &str = "jellyfin.json";
+
+const CLIENT_NAME: &str = "Masukii";
+
+/// Codecs the decoder plays as they are (Jellyfin's names for them).
+const DIRECT_CODECS: &[&str] = &["flac", "mp3", "aac", "alac", "vorbis"];
+
+/// How often the reporter looks at the engine.
+const SAMPLE_INTERVAL: Duration = Duration::from_secs(1);
+
+/// How often progress is reported while a track plays.
+const PROGRESS_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Jellyfin times are in ticks of 100 ns.
+const TICKS_PER_SEC: f64 = 10_000_000.0;
+
+/// Entries per page when the caller doesn't say.
+pub const DEFAULT_PAGE_SIZE: u32 = 100;
+
+/// Entries returned per search category when the caller doesn't say.
+pub const DEFAULT_SEARCH_LIMIT: u32 = 50;
+
+#[derive(Default, Serialize, Deserialize)]
+#[serde(default)]
+struct Settings {
+    /// Identifies this install to the servers, for their session lists.
+    device_id: String,
+    servers: Vec<ServerConfig>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+struct ServerConfig {
+    id: String,
+    /// Server name, e.g. `Living room`.
+    name: String,
+    /// Base URL, without a trailing slash.
+    url: String,
+    api_key: String,
+    user_id: String,
+    user_name: String,
+    #[serde(default)]
+    version: Option<String>,
+}
+
+#[derive(Clone, Serialize)]
+pub struct JellyfinServer {
+    pub id: String,
+    pub name: String,
+    pub url: String,
+    /// User whose libraries are shown.
+    pub user_name: String,
+    pub version: Option<String>,
+}
+
+impl From<&ServerConfig> for JellyfinServer {
+    fn from(config: &ServerConfig) -> Self {
+        Self {
+            id: config.id.clone(),
+            name: config.name.clone(),
+            url: config.url.clone(),
+            user_name: config.user_name.clone(),
+            version: config.version.clone(),
+        }
+    }
+}
+
+/// A music library on the server.
+#[derive(Clone, Serialize)]
+pub struct JellyfinLibrary {
+    pub id: String,
+    pub name: String,
+}
+
+#[derive(Clone, Serialize)]
+pub struct JellyfinArtist {
+    pub id: String,
+    pub name: String,
+    pub image_url: Option<String>,
+}
+
+#[derive(Clone, Serialize)]
+pub struct JellyfinAlbum {
+    pub id: String,
+    pub name: String,
+    pub artist: Option<String>,
+    pub year: Option<u32>,
+    pub track_count: Option<u32>,
+    pub duration_secs: Option<f64>,
+    pub image_url: Option<String>,
+}
+
+#[derive(Clone, Serialize)]
+pub struct JellyfinTrack {
+    pub id: String,
+    pub title: String,
+    pub artists: Vec<String>,
+    pub album_artist: Option<String>,
+    pub album: Option<String>,
+    pub album_id: Option<String>,
+    pub track_number: Option<u32>,
+    pub disc_number: Option<u32>,
+    pub year: Option<u32>,
+    pub duration_secs: Option<f64>,
+    /// Original's codec, e.g. `flac`.
+    pub codec: Option<String>,
+    pub sample_rate: Option<u32>,
+    pub bit_depth: Option<u8>,
+    pub channels: Option<u8>,
+    pub bit_rate_kbps: Option<u32>,
+    pub image_url: Option<String>,
+    /// Stream URL; play or queue it like a path.
+    pub url: String,
+    /// The stream is a server transcode rather than the original.
+    pub transcoded: bool,
+}
+
+#[derive(Clone, Serialize)]
+pub struct JellyfinPage<T> {
+    pub items: Vec<T>,
+    /// Offset of this page.
+    pub start: u32,
+    /// Entries in all.
+    pub total: u32,
+}
+
+#[derive(Clone, Serialize, Default)]
+pub struct JellyfinSearchResults {
+    pub artists: Vec<JellyfinArtist>,
+    pub albums: Vec<JellyfinAlbum>,
+    pub tracks: Vec<JellyfinTrack>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct Items {
+    #[serde(default)]
+    items: Vec<Item>,
+    total_record_count: Option<u32>,
+}
+
+/// Any item the API returns; which fields are set depends on its type.
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct Item {
+    id: String,
+    name: Option<String>,
+    collection_type: Option<String>,
+    album: Option<String>,
+    album_id: Option<String>,
+    album_artist: Option<String>,
+    #[serde(default)]
+    artists: Vec<String>,
+    index_number: Option<u32>,
+    parent_index_number: Option<u32>,
+    production_year: Option<u32>,
+    run_time_ticks: Option<u64>,
+    child_count: Option<u32>,
+    #[serde(default)]
+    image_tags: HashMap<String, String>,
+    album_primary_image_tag: Option<String>,
+    #[serde(default)]
+    media_sources: Vec<MediaSource>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct MediaSource {
+    #[serde(default)]
+    media_streams: Vec<MediaStream>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct MediaStream {
+    #[serde(rename = "Type")]
+    kind: String,
+    codec: Option<String>,
+    sample_rate: Option<u32>,
+    bit_depth: Option<u8>,
+    channels: Option<u8>,
+    bit_rate: Option<u64>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct User {
+    id: String,
+    name: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct SystemInfo {
+    server_name: Option<String>,
+    version: Option<String>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "PascalCase")]
+struct PlaybackReport<'a> {
+    item_id: &'a str,
+    play_session_id: &'a str,
+    position_ticks: u64,
+    is_paused: bool,
+    can_seek: bool,
+    play_method: &'static str,
+}
+
+/// A server's track being played, as the reporter tracks it.
+struct Session {
+    server: ServerConfig,
+    item_id: String,
+    play_session_id: String,
+    transcoded: bool,
+    paused: bool,
+    position_secs: f64,
+    reported_at: Instant,
+}
+
+pub struct Jellyfin {
+    app_data_dir: PathBuf,
+    settings: Mutex<Settings>,
+    played: Sender<String>,
+}
+
+impl Jellyfin {
+    /// Load the configured servers and start the thread that reports
+    /// playback of their tracks.
+    pub fn spawn(app_data_dir: &Path, engine: Arc<AudioEngine>) -> Arc<Self> {
+        let mut settings: Settings = std::fs::read_to_string(app_data_dir.join(SETTINGS_FILE))
+            .ok()
+            .and_then(|data| serde_json::from_str(&data).ok())
+            .unwrap_or_default();
+        if settings.device_id.is_empty() {
+            settings.device_id = random_hex(16);
+        }
+        let (played, rx) = crossbeam_channel::unbounded();
+        let jellyfin = Arc::new(Self {
+            app_data_dir: app_data_dir.to_path_buf(),
+            settings: Mutex::new(settings),
+            played,
+        });
+        let reporter = jellyfin.clone();
+        thread::Builder::new()
+            .name("jellyfin-reporter".into())
+            .spawn(move || reporter.run(engine, rx))
+            .expect("Failed to spawn Jellyfin reporter thread");
+        jellyfin
+    }
+
+    pub fn servers(&self) -> Vec<JellyfinServer> {
+        self.settings
+            .lock()
+            .servers
+            .iter()
+            .map(JellyfinServer::from)
+            .collect()
+    }
+
+    /// Check the API key, find the user and store the server. `user_name`
+    /// may be left out when the server has a single user. Blocks on the
+    /// network.
+    pub fn add_server(
+        &self,
+        url: &str,
+        api_key: &str,
+        user_name: Option<&str>,
+    ) -> Result<JellyfinServer, String> {
+        let url = url.trim().trim_end_matches('/');
+        if !url.starts_with("http://") && !url.starts_with("https://") {
+            return Err("Server URL must start with http:// or https://".to_string());
+        }
+        let api_key = api_key.trim();
+        if api_key.is_empty() {
+            return Err("No API key given".to_string());
+        }
+        let mut config = ServerConfig {
+            id: random_hex(4),
+            name: String::new(),
+            url: url.to_string(),
+            api_key: api_key.to_string(),
+            user_id: String::new(),
+            user_name: String::new(),
+            version: None,
+        };
+
+        let info: SystemInfo = self.get(&config, "/System/Info", &[])?;
+        let users: Vec<User> = self.get(&config, "/Users", &[])?;
+        let user = match user_name.map(str::trim).filter(|n| !n.is_empty()) {
+            Some(name) => users
+                .into_iter()
+                .find(|u| u.name.eq_ignore_ascii_case(name))
+                .ok_or_else(|| format!("No user named {} on the server", name))?,
+            None if users.len() == 1 => users.into_iter().next().unwrap(),
+            None => return Err("The server has several users; give a username".to_string()),
+        };
+        config.name = info.server_name.unwrap_or_else(|| "Jellyfin".to_string());
+        config.version = info.version;
+        config.user_id = user.id;
+        config.user_name = user.name;
+
+        let server = JellyfinServer::from(&config);
+        let mut settings = self.settings.lock();
+        settings.servers.push(config);
+        self.save(&settings)?;
+        Ok(server)
+    }
+
+    pub fn remove_server(&self, server_id: &str) -> Result<(), String> {
+        let mut settings = self.settings.lock();
+        settings.servers.retain(|s| s.id != server_id);
+        self.save(&settings)
+    }
+
+    /// The user's music libraries.
+    pub fn libraries(&self, server_id: &str) -> Result<Vec<JellyfinLibrary>, String> {
+        let config = self.server(server_id)?;
+        let views: Items = self.get(&config, &format!("/Users/{}/Views", config.user_id), &[])?;
+        Ok(views
+            .items
+            .into_iter()
+            .filter(|v| v.collection_type.as_deref() == Some("music"))
+            .map(|v| JellyfinLibrary {
+                name: v.name.unwrap_or_default(),
+                id: v.id,
+            })
+            .collect())
+    }
+
+    /// One page of album artists, from one library or all of them.
+    pub fn artists(
+        &self,
+        server_id: &str,
+        library_id: Option<&str>,
+        start: u32,
+        limit: u32,
+    ) -> Result<JellyfinPage<JellyfinArtist>, String> {
+        let config = self.server(server_id)?;
+        let (start_index, limit) = (start.to_string(), limit.to_string());
+        let mut query = vec![
+            ("userId", config.user_id.as_str()),
+            ("startIndex", &start_index),
+            ("limit", &limit),
+        ];
+        if let Some(library_id) = library_id {
+            query.push(("parentId", library_id));
+        }
+        let found: Items = self.get(&config, "/Artists/AlbumArtists", &query)?;
+        Ok(JellyfinPage {
+            total: found.total_record_count.unwrap_or(0),
+            start,
+            items: found
+                .items
+                .into_iter()
+                .map(|i| artist(&config, i))
+                .collect(),
+        })
+    }
+
+    /// One page of albums by name, from one library or all of them.
+    pub fn albums(
+        &self,
+        server_id: &str,
+        library_id: Option<&str>,
+        start: u32,
+        limit: u32,
+    ) -> Result<JellyfinPage<JellyfinAlbum>, String> {
+        let config = self.server(server_id)?;
+        let (start_index, limit) = (start.to_string(), limit.to_string());
+        let mut query = vec![
+            ("includeItemTypes", "MusicAlbum"),
+            ("recursive", "true"),
+            ("sortBy", "SortName"),
+            ("startIndex", &start_index),
+            ("limit", &limit),
+        ];
+        if let Some(library_id) = library_id {
+            query.push(("parentId", library_id));
+        }
+        let found = self.items(&config, &query)?;
+        Ok(JellyfinPage {
+            total: found.total_record_count.unwrap_or(0),
+            start,
+            items: found.items.into_iter().map(|i| album(&config, i)).collect(),
+        })
+    }
+
+    pub fn artist_albums(
+        &self,
+        server_id: &str,
+        artist_id: &str,
+    ) -> Result<Vec<JellyfinAlbum>, String> {
+        let config = self.server(server_id)?;
+        let found = self.items(
+            &config,
+            &[
+                ("albumArtistIds", artist_id),
+                ("includeItemTypes", "MusicAlbum"),
+                ("recursive", "true"),
+                ("sortBy", "ProductionYear,SortName"),
+            ],
+        )?;
+        Ok(found.items.into_iter().map(|i| album(&config, i)).collect())
+    }
+
+    /// An album's tracks in disc and track order, with stream URLs.
+    pub fn album_tracks(
+        &self,
+        server_id: &str,
+        album_id: &str,
+    ) -> Result<Vec<JellyfinTrack>, String> {
+        let config = self.server(server_id)?;
+        let found = self.items(
+            &config,
+            &[
+                ("parentId", album_id),
+                ("includeItemTypes", "Audio"),
+                ("recursive", "true"),
+                ("sortBy", "ParentIndexNumber,IndexNumber,SortName"),
+                ("fields", "MediaSources"),
+            ],
+        )?;
+        Ok(found.items.into_iter().map(|i| track(&config, i)).collect())
+    }
+
+    /// Artists, albums and tracks matching `query`, up to `limit` of each.
+    pub fn search(
+        &self,
+        server_id: &str,
+        query: &str,
+        limit: u32,
+    ) -> Result<JellyfinSearchResults, String> {
+        let query = query.trim();
+        if query.is_empty() {
+            return Ok(JellyfinSearchResults::default());
+        }
+        let config = self.server(server_id)?;
+        let limit = limit.to_string();
+        let search = |kind| {
+            self.items(
+                &config,
+                &[
+                    ("searchTerm", query),
+                    ("includeItemTypes", kind),
+                    ("recursive", "true"),
+                    ("limit", &limit),
+                    ("fields", "MediaSources"),
+                ],
+            )
+            .map(|found| found.items)
+        };
+        Ok(JellyfinSearchResults {
+            artists: search("MusicArtist")?
+                .into_iter()
+                .map(|i| artist(&config, i))
+                .collect(),
+            albums: search("MusicAlbum")?
+                .into_iter()
+                .map(|i| album(&config, i))
+                .collect(),
+            tracks: search("Audio")?
+                .into_iter()
+                .map(|i| track(&config, i))
+                .collect(),
+        })
+    }
+
+    /// Mark the playing track played on its server, for a play counted by
+    /// the play counter.
+    pub fn listened(&self, state: &PlaybackState) {
+        if let Some(file) = &state.current_file {
+            let _ = self.played.send(file.clone());
+        }
+    }
+
+    fn run(&self, engine: Arc<AudioEngine>, played: Receiver<String>) {
+        let mut session: Option<Session> = None;
+        loop {
+            match played.recv_timeout(SAMPLE_INTERVAL) {
+                Ok(file) => {
+                    if let Some((server, item_id)) = self.stream_item(&file) {
+                        let path = format!("/Users/{}/PlayedItems/{}", server.user_id, item_id);
+                        if let Err(e) = self.post(&server, &path, &serde_json::json!({})) {
+                            log::warn!(
+                                "Failed to mark {} played on {}: {}",
+                                item_id,
+                                server.name,
+                                e
+                            );
+                        }
+                    }
+                    continue;
+                }
+                Err(RecvTimeoutError::Timeout) => {}
+                Err(RecvTimeoutError::Disconnected) => return,
+            }
+
+            let s = engine.get_state();
+            let playing = s
+                .current_file
+                .as_deref()
+                .filter(|_| s.is_playing)
+                .and_then(|file| self.stream_item(file));
+            let same = match (&session, &playing) {
+                (Some(current), Some((server, item_id))) => {
+                    current.server.id == server.id && current.item_id == *item_id
+                }
+                _ => false,
+            };
+
+            if !same {
+                if let Some(ended) = session.take() {
+                    self.report(&ended, "/Sessions/Playing/Stopped");
+                }
+                if let Some((server, item_id)) = playing {
+                    let started = Session {
+                        transcoded: s
+                            .current_file
+                            .as_deref()
+                            .is_some_and(|f| !f.contains("/stream?static=true")),
+                        server,
+                        item_id,
+                        play_session_id: random_hex(16),
+                        paused: s.is_paused,
+                        position_secs: s.position_secs,
+                        reported_at: Instant::now(),
+                    };
+                    self.report(&started, "/Sessions/Playing");
+                    session = Some(started);
+                }
+                continue;
+            }
+            if let Some(current) = session.as_mut() {
+                current.position_secs = s.position_secs;
+                let paused_changed = current.paused != s.is_paused;
+                current.paused = s.is_paused;
+                if paused_changed || current.reported_at.elapsed() >= PROGRESS_INTERVAL {
+                    current.reported_at = Instant::now();
+                    self.report(current, "/Sessions/Playing/Progress");
+                }
+            }
+        }
+    }
+
+    fn report(&self, session: &Session, path: &str) {
+        let report = PlaybackReport {
+            item_id: &session.item_id,
+            play_session_id: &session.play_session_id,
+            position_ticks: (session.position_secs.max(0.0) * TICKS_PER_SEC) as u64,
+            is_paused: session.paused,
+            can_seek: true,
+            play_method: if session.transcoded {
+                "Transcode"
+            } else {
+                "DirectStream"
+            },
+        };
+        if let Err(e) = self.post(&session.server, path, &report) {
+            log::warn!(
+                "Jellyfin playback report to {} failed: {}",
+                session.server.name,
+                e
+            );
+        }
+    }
+
+    /// The server and item id of one of the servers' stream URLs.
+    fn stream_item(&self, file: &str) -> Option<(ServerConfig, String)> {
+        let settings = self.settings.lock();
+        settings.servers.iter().find_map(|server| {
+            let rest = file.strip_prefix(&format!("{}/Audio/", server.url))?;
+            let (item_id, _) = rest.split_once('/')?;
+            Some((server.clone(), item_id.to_string()))
+        })
+    }
+
+    fn items(&self, config: &ServerConfig, query: &[(&str, &str)]) -> Result<Items, String> {
+        let mut query = query.to_vec();
+        query.push(("userId", &config.user_id));
+        self.get(config, "/Items", &query)
+    }
+
+    fn get<T: DeserializeOwned>(
+        &self,
+        config: &ServerConfig,
+        path: &str,
+        query: &[(&str, &str)],
+    ) -> Result<T, String> {
+        let url = http::url_with_query(&format!("{}{}", config.url, path), query)?;
+        http::get_json_with_headers(&url, &[("Authorization", &self.authorization(config))])
+    }
+
+    fn post<B: Serialize>(
+        &self,
+        config: &ServerConfig,
+        path: &str,
+        body: &B,
+    ) -> Result<(), String> {
+        http::send_json(
+            &format!("{}{}", config.url, path),
+            &[("Authorization", &self.authorization(config))],
+            body,
+        )
+    }
+
+    fn authorization(&self, config: &ServerConfig) -> String {
+        format!(
+            "MediaBrowser Client=\"{}\", Device=\"{}\", DeviceId=\"{}\", Version=\"{}\", Token=\"{}\"",
+            CLIENT_NAME,
+            CLIENT_NAME,
+            self.settings.lock().device_id,
+            env!("CARGO_PKG_VERSION"),
+            config.api_key
+        )
+    }
+
+    fn server(&self, server_id: &str) -> Result<ServerConfig, String> {
+        self.settings
+            .lock()
+            .servers
+            .iter()
+            .find(|s| s.id == server_id)
+            .cloned()
+            .ok_or_else(|| format!("No Jellyfin server {}", server_id))
+    }
+
+    fn save(&self, settings: &Settings) -> Result<(), String> {
+        std::fs::create_dir_all(&self.app_data_dir)
+            .map_err(|e| format!("Failed to create dir: {}", e))?;
+        let json = serde_json::to_string_pretty(settings)
+            .map_err(|e| format!("Serialize failed: {}", e))?;
+        std::fs::write(self.app_data_dir.join(SETTINGS_FILE), json)
+            .map_err(|e| format!("Write failed: {}", e))
+    }
+}
+
+fn artist(config: &ServerConfig, item: Item) -> JellyfinArtist {
+    JellyfinArtist {
+        image_url: item
+            .image_tags
+            .contains_key("Primary")
+            .then(|| image_url(config, &item.id)),
+        name: item.name.unwrap_or_default(),
+        id: item.id,
+    }
+}
+
+fn album(config: &ServerConfig, item: Item) -> JellyfinAlbum {
+    JellyfinAlbum {
+        image_url: item
+            .image_tags
+            .contains_key("Primary")
+            .then(|| image_url(config, &item.id)),
+        name: item.name.unwrap_or_default(),
+        artist: item.album_artist,
+        year: item.production_year,
+        track_count: item.child_count,
+        duration_secs: item.run_time_ticks.map(|t| t as f64 / TICKS_PER_SEC),
+        id: item.id,
+    }
+}
+
+/// A track with its stream URL: the original file when the decoder takes
+/// its codec, otherwise a FLAC transcode.
+fn track(config: &ServerConfig, item: Item) -> JellyfinTrack {
+    let stream = item
+        .media_sources
+        .first()
+        .and_then(|source| source.media_streams.iter().find(|s| s.kind == "Audio"));
+    let codec = stream.and_then(|s| s.codec.clone());
+    let direct = codec.as_deref().is_some_and(|c| {
+        let c = c.to_lowercase();
+        DIRECT_CODECS.contains(&c.as_str()) || c.starts_with("pcm_")
+    });
+    let url = if direct {
+        format!(
+            "{}/Audio/{}/stream?static=true&api_key={}",
+            config.url, item.id, config.api_key
+        )
+    } else {
+        format!(
+            "{}/Audio/{}/stream.flac?audioCodec=flac&api_key={}",
+            config.url, item.id, config.api_key
+        )
+    };
+    let image_url = match (&item.album_id, &item.album_primary_image_tag) {
+        (Some(album_id), Some(_)) => Some(image_url(config, album_id)),
+        _ if item.image_tags.contains_key("Primary") => Some(image_url(config, &item.id)),
+        _ => None,
+    };
+
+    JellyfinTrack {
+        title: item.name.unwrap_or_default(),
+        artists: item.artists,
+        album_artist: item.album_artist,
+        album: item.album,
+        album_id: item.album_id,
+        track_number: item.index_number,
+        disc_number: item.parent_index_number,
+        year: item.production_year,
+        duration_secs: item.run_time_ticks.map(|t| t as f64 / TICKS_PER_SEC),
+        codec,
+        sample_rate: stream.and_then(|s| s.sample_rate),
+        bit_depth: stream.and_then(|s| s.bit_depth),
+        channels: stream.and_then(|s| s.channels),
+        bit_rate_kbps: stream.and_then(|s| s.bit_rate).map(|b| (b / 1000) as u32),
+        image_url,
+        url,
+        transcoded: !direct,
+        id: item.id,
+    }
+}
+
+fn image_url(config: &ServerConfig, item_id: &str) -> String {
+    format!(
+        "{}/Items/{}/Images/Primary?maxHeight=600&api_key={}",
+        config.url, item_id, config.api_key
+    )
+}
@@ -0,0 +1,15 @@
+pub mod dlna;
+pub mod jellyfin;
+pub mod radio;
+pub mod subsonic;
+mod xml;
+
+use rand::Rng;
+
+/// `bytes` random bytes in hex, for ids and salts.
+fn random_hex(bytes: usize) -> String {
+    let mut rng = rand::thread_rng();
+    (0..bytes)
+        .map(|_| format!("{:02x}", rng.gen::<u8>()))
+        .collect()
+}
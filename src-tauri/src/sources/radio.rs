@@ -0,0 +1,383 @@
+//! Internet radio stations: saved stream URLs, organized into groups and
+//! favorites in the user's own order, with a history of what each station
+//! played.
+//!
+//! A station plays (or queues) by its stream URL like any path. Playlist
+//! URLs (`.pls`, `.m3u`) are resolved to the stream they list when a station
+//! is added, and station playlists can be imported whole. While a station
+//! plays, the titles it announces in its ICY metadata (see
+//! [`crate::audio::icy`]) are recorded, newest last, up to
+//! [`MAX_HISTORY`] per station. Stations and history are stored as JSON in
+//! the app data directory.
+
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use super::random_hex;
+use crate::audio::engine::AudioEngine;
+use crate::audio::{http_source, icy};
+use crate::errors::{self, ErrorCode};
+use crate::http;
+
+const STATIONS_FILE: &str = "radio_stations.json";
+const HISTORY_FILE: &str = "radio_history.json";
+
+/// Titles kept per station; the oldest are dropped.
+pub const MAX_HISTORY: usize = 500;
+
+/// How often the tracker looks for a new title.
+const SAMPLE_INTERVAL: Duration = Duration::from_secs(2);
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct RadioStation {
+    pub id: String,
+    pub name: String,
+    /// Stream URL; play or queue it like a path.
+    pub url: String,
+    /// Group the station is filed under, e.g. `Jazz`.
+    #[serde(default)]
+    pub group: Option<String>,
+    #[serde(default)]
+    pub favorite: bool,
+    /// Unix time.
+    pub added_at: i64,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct RadioTitle {
+    /// As the station announced it, usually `Artist - Title`.
+    pub title: String,
+    /// Unix time it was first heard.
+    pub at: i64,
+}
+
+/// A station announcing a new title, as the tracker reports it.
+#[derive(Clone, Serialize)]
+pub struct RadioNowPlaying {
+    pub station_id: String,
+    pub title: String,
+    pub at: i64,
+}
+
+pub struct Radio {
+    app_data_dir: PathBuf,
+    stations: Mutex<Vec<RadioStation>>,
+    history: Mutex<HashMap<String, Vec<RadioTitle>>>,
+}
+
+impl Radio {
+    pub fn load(app_data_dir: &Path) -> Self {
+        Self {
+            app_data_dir: app_data_dir.to_path_buf(),
+            stations: Mutex::new(load(&app_data_dir.join(STATIONS_FILE))),
+            history: Mutex::new(load(&app_data_dir.join(HISTORY_FILE))),
+        }
+    }
+
+    /// Stations in the user's order.
+    pub fn stations(&self) -> Vec<RadioStation> {
+        self.stations.lock().clone()
+    }
+
+    pub fn station(&self, station_id: &str) -> Result<RadioStation, String> {
+        self.stations
+            .lock()
+            .iter()
+            .find(|s| s.id == station_id)
+            .cloned()
+            .ok_or_else(|| format!("No radio station {}", station_id))
+    }
+
+    /// Save a station. A playlist URL is fetched and its first stream used,
+    /// which blocks on the network.
+    pub fn add_station(
+        &self,
+        name: &str,
+        url: &str,
+        group: Option<String>,
+    ) -> Result<RadioStation, String> {
+        let url = url.trim();
+        if !http_source::is_url(url) {
+            return Err("Station URL must start with http:// or https://".to_string());
+        }
+        let (url, listed_name) = if is_playlist(url) {
+            let entries = parse_playlist(&http::get_text(url)?)?;
+            entries
+                .into_iter()
+                .next()
+                .ok_or("The playlist lists no streams")?
+        } else {
+            (url.to_string(), None)
+        };
+        let name = Some(name.trim())
+            .filter(|n| !n.is_empty())
+            .map(str::to_string)
+            .or(listed_name)
+            .unwrap_or_else(|| url.clone());
+
+        let station = new_station(name, url, group);
+        let mut stations = self.stations.lock();
+        stations.push(station.clone());
+        self.save_stations(&stations)?;
+        Ok(station)
+    }
+
+    /// Save every stream a station playlist (`.pls` or `.m3u`, a file or a
+    /// URL) lists. Streams already saved are skipped.
+    pub fn import_playlist(
+        &self,
+        path: &str,
+        group: Option<String>,
+    ) -> Result<Vec<RadioStation>, String> {
+        let text = if http_source::is_url(path) {
+            http::get_text(path)?
+        } else {
+            let bytes =
+                std::fs::read(path).map_err(|e| format!("Failed to read playlist: {}", e))?;
+            String::from_utf8_lossy(&bytes).to_string()
+        };
+        let entries = parse_playlist(&text)?;
+
+        let mut stations = self.stations.lock();
+        let mut added = Vec::new();
+        for (url, name) in entries {
+            if stations.iter().chain(&added).any(|s| s.url == url) {
+                continue;
+            }
+            let name = name.unwrap_or_else(|| url.clone());
+            added.push(new_station(name, url, group.clone()));
+        }
+        stations.extend(added.iter().cloned());
+        self.save_stations(&stations)?;
+        Ok(added)
+    }
+
+    /// Rename, regroup or (un)favorite a station.
+    pub fn update_station(
+        &self,
+        station_id: &str,
+        name: &str,
+        group: Option<String>,
+        favorite: bool,
+    ) -> Result<RadioStation, String> {
+        let name = name.trim();
+        if name.is_empty() {
+            return Err("Station name can't be empty".to_string());
+        }
+        let mut stations = self.stations.lock();
+        let station = stations
+            .iter_mut()
+            .find(|s| s.id == station_id)
+            .ok_or_else(|| format!("No radio station {}", station_id))?;
+        station.name = name.to_string();
+        station.group = group.filter(|g| !g.trim().is_empty());
+        station.favorite = favorite;
+        let station = station.clone();
+        self.save_stations(&stations)?;
+        Ok(station)
+    }
+
+    /// Move a station to `index` in the list.
+    pub fn move_station(&self, station_id: &str, index: usize) -> Result<(), String> {
+        let mut stations = self.stations.lock();
+        let from = stations
+            .iter()
+            .position(|s| s.id == station_id)
+            .ok_or_else(|| format!("No radio station {}", station_id))?;
+        let station = stations.remove(from);
+        let index = index.min(stations.len());
+        stations.insert(index, station);
+        self.save_stations(&stations)
+    }
+
+    /// Forget a station and its history.
+    pub fn remove_station(&self, station_id: &str) -> Result<(), String> {
+        let mut stations = self.stations.lock();
+        stations.retain(|s| s.id != station_id);
+        self.save_stations(&stations)?;
+        self.clear_history(station_id)
+    }
+
+    /// Titles the station played, newest last.
+    pub fn history(&self, station_id: &str) -> Vec<RadioTitle> {
+        self.history
+            .lock()
+            .get(station_id)
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    pub fn clear_history(&self, station_id: &str) -> Result<(), String> {
+        let mut history = self.history.lock();
+        if history.remove(station_id).is_some() {
+            save(&self.app_data_dir, HISTORY_FILE, &*history)?;
+        }
+        Ok(())
+    }
+
+    /// Start the thread that records the titles stations announce while
+    /// they play. `on_title` is called from it for every new title.
+    pub fn spawn_tracker(
+        self: &Arc<Self>,
+        engine: Arc<AudioEngine>,
+        mut on_title: impl FnMut(&RadioNowPlaying) + Send + 'static,
+    ) {
+        let radio = self.clone();
+        thread::Builder::new()
+            .name("radio-tracker".into())
+            .spawn(move || loop {
+                thread::sleep(SAMPLE_INTERVAL);
+
+                let s = engine.get_state();
+                let Some(file) = s.current_file.filter(|_| s.is_playing) else {
+                    continue;
+                };
+                let Some(title) = icy::stream_title(&file) else {
+                    continue;
+                };
+                let Some(station) = radio
+                    .stations
+                    .lock()
+                    .iter()
+                    .find(|s| s.url == file)
+                    .cloned()
+                else {
+                    continue;
+                };
+                if let Some(now_playing) = radio.record(&station.id, title) {
+                    on_title(&now_playing);
+                }
+            })
+            .expect("Failed to spawn radio tracker thread");
+    }
+
+    /// Add `title` to the station's history unless it's the latest already.
+    fn record(&self, station_id: &str, title: String) -> Option<RadioNowPlaying> {
+        let mut history = self.history.lock();
+        let titles = history.entry(station_id.to_string()).or_default();
+        if titles.last().is_some_and(|t| t.title == title) {
+            return None;
+        }
+        let at = unix_now();
+        titles.push(RadioTitle {
+            title: title.clone(),
+            at,
+        });
+        if titles.len() > MAX_HISTORY {
+            titles.remove(0);
+        }
+        if let Err(e) = save(&self.app_data_dir, HISTORY_FILE, &*history) {
+            errors::report(
+                ErrorCode::SaveFailed,
+                format!("Failed to save radio history: {}", e),
+            );
+        }
+        Some(RadioNowPlaying {
+            station_id: station_id.to_string(),
+            title,
+            at,
+        })
+    }
+
+    fn save_stations(&self, stations: &[RadioStation]) -> Result<(), String> {
+        save(&self.app_data_dir, STATIONS_FILE, stations)
+    }
+}
+
+fn new_station(name: String, url: String, group: Option<String>) -> RadioStation {
+    RadioStation {
+        id: random_hex(4),
+        name,
+        url,
+        group: group.filter(|g| !g.trim().is_empty()),
+        favorite: false,
+        added_at: unix_now(),
+    }
+}
+
+fn is_playlist(url: &str) -> bool {
+    http_source::url_extension(url).is_some_and(|e| {
+        ["pls", "m3u", "m3u8"]
+            .iter()
+            .any(|p| e.eq_ignore_ascii_case(p))
+    })
+}
+
+/// The streams a `.pls` or `.m3u` playlist lists, with their titles.
+fn parse_playlist(text: &str) -> Result<Vec<(String, Option<String>)>, String> {
+    let text = text.trim_start_matches('\u{feff}');
+    if text.contains("#EXT-X-") {
+        return Err("HLS streams aren't supported".to_string());
+    }
+    let mut entries = Vec::new();
+
+    if text
+        .trim_start()
+        .to_ascii_lowercase()
+        .starts_with("[playlist]")
+    {
+        // File1=http://... / Title1=...
+        let mut files = Vec::new();
+        let mut titles = HashMap::new();
+        for line in text.lines() {
+            let Some((key, value)) = line.trim().split_once('=') else {
+                continue;
+            };
+            let key = key.trim().to_ascii_lowercase();
+            let value = value.trim();
+            if let Some(n) = key.strip_prefix("file") {
+                files.push((n.to_string(), value.to_string()));
+            } else if let Some(n) = key.strip_prefix("title") {
+                titles.insert(n.to_string(), value.to_string());
+            }
+        }
+        for (n, url) in files {
+            let title = titles.remove(&n).filter(|t| !t.is_empty());
+            entries.push((url, title));
+        }
+    } else {
+        // #EXTINF:-1,Station name / http://...
+        let mut title = None;
+        for line in text.lines().map(str::trim) {
+            if let Some(info) = line.strip_prefix("#EXTINF:") {
+                title = info
+                    .split_once(',')
+                    .map(|(_, t)| t.trim().to_string())
+                    .filter(|t| !t.is_empty());
+            } else if !line.is_empty() && !line.starts_with('#') {
+                entries.push((line.to_string(), title.take()));
+            }
+        }
+    }
+
+    entries.retain(|(url, _)| http_source::is_url(url));
+    if entries.is_empty() {
+        return Err("The playlist lists no streams".to_string());
+    }
+    Ok(entries)
+}
+
+fn load<T: Default + serde::de::DeserializeOwned>(path: &Path) -> T {
+    std::fs::read_to_string(path)
+        .ok()
+        .and_then(|data| serde_json::from_str(&data).ok())
+        .unwrap_or_default()
+}
+
+fn save<T: Serialize + ?Sized>(app_data_dir: &Path, file: &str, value: &T) -> Result<(), String> {
+    std::fs::create_dir_all(app_data_dir).map_err(|e| format!("Failed to create dir: {}", e))?;
+    let json = serde_json::to_string(value).map_err(|e| format!("Serialize failed: {}", e))?;
+    std::fs::write(app_data_dir.join(file), json).map_err(|e| format!("Write failed: {}", e))
+}
+
+fn unix_now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or(0)
+}
@@ -0,0 +1,659 @@
+//! Subsonic-API servers (Navidrome, Airsonic, Gonic, ...) as a library
+//! source: their artists and albums are browsed and searched on the server,
+//! and their songs stream through the queue and engine like local files.
+//!
+//! A server is added with a username and password. The password isn't
+//! kept: Subsonic's token auth sends `md5(password + salt)` with the salt,
+//! so one salt and token are stored per server in the app data directory
+//! and sent with every call.
+//!
+//! Songs stream in their original format when the decoder reads it and the
+//! server has no bit-rate cap; otherwise the server is asked to transcode to
+//! MP3. Plays the play counter counts of a server's songs are scrobbled back
+//! to it, so its play counts and "recently played" stay right.
+
+use crossbeam_channel::{Receiver, Sender};
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::thread;
+
+use super::random_hex;
+use crate::audio::engine::PlaybackState;
+use crate::http;
+
+const SERVERS_FILE: &str = "subsonic_servers.json";
+
+/// API version asked for; 1.16.1 is the last Subsonic release, and what
+/// Navidrome and the OpenSubsonic servers speak.
+const API_VERSION: &str = "1.16.1";
+
+const CLIENT_NAME: &str = "Masukii";
+
+/// Formats the decoder plays as they are, by file suffix.
+const DIRECT_SUFFIXES: &[&str] = &[
+    "flac", "mp3", "wav", "aif", "aiff", "ogg", "oga", "m4a", "mp4", "aac", "alac", "caf", "mka",
+];
+
+/// Bit rate of the MP3 transcode for songs the decoder can't play, when
+/// the server has no cap.
+const TRANSCODE_BIT_RATE: u32 = 320;
+
+/// Entries returned per search category when the caller doesn't say.
+pub const DEFAULT_SEARCH_LIMIT: u32 = 50;
+
+#[derive(Clone, Serialize, Deserialize)]
+struct ServerConfig {
+    id: String,
+    name: String,
+    /// Base URL, without a trailing slash, e.g. `https://music.example.com`.
+    url: String,
+    username: String,
+    salt: String,
+    /// `md5(password + salt)` in hex.
+    token: String,
+    /// Kbps streams are capped at; 0 streams originals.
+    #[serde(default)]
+    max_bit_rate: u32,
+    #[serde(default = "default_true")]
+    scrobble: bool,
+    /// e.g. `navidrome 0.53.3`.
+    #[serde(default)]
+    server: Option<String>,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+#[derive(Clone, Serialize)]
+pub struct SubsonicServer {
+    pub id: String,
+    pub name: String,
+    pub url: String,
+    pub username: String,
+    /// Kbps streams are capped at; 0 streams originals.
+    pub max_bit_rate: u32,
+    /// Counted plays are scrobbled to the server.
+    pub scrobble: bool,
+    /// Server software and version, when it says.
+    pub server: Option<String>,
+}
+
+impl From<&ServerConfig> for SubsonicServer {
+    fn from(config: &ServerConfig) -> Self {
+        Self {
+            id: config.id.clone(),
+            name: config.name.clone(),
+            url: config.url.clone(),
+            username: config.username.clone(),
+            max_bit_rate: config.max_bit_rate,
+            scrobble: config.scrobble,
+            server: config.server.clone(),
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(rename_all(deserialize = "camelCase"))]
+pub struct SubsonicArtist {
+    pub id: String,
+    pub name: String,
+    pub album_count: Option<u32>,
+    #[serde(skip_deserializing)]
+    pub cover_art_url: Option<String>,
+    #[serde(skip_serializing)]
+    cover_art: Option<String>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(rename_all(deserialize = "camelCase"))]
+pub struct SubsonicAlbum {
+    pub id: String,
+    pub name: String,
+    pub artist: Option<String>,
+    pub artist_id: Option<String>,
+    pub year: Option<u32>,
+    pub genre: Option<String>,
+    pub song_count: Option<u32>,
+    #[serde(rename(deserialize = "duration"))]
+    pub duration_secs: Option<u64>,
+    #[serde(skip_deserializing)]
+    pub cover_art_url: Option<String>,
+    #[serde(skip_serializing)]
+    cover_art: Option<String>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(rename_all(deserialize = "camelCase"))]
+pub struct SubsonicSong {
+    pub id: String,
+    pub title: String,
+    pub artist: Option<String>,
+    pub album: Option<String>,
+    pub album_id: Option<String>,
+    #[serde(rename(deserialize = "track"))]
+    pub track_number: Option<u32>,
+    pub disc_number: Option<u32>,
+    pub year: Option<u32>,
+    #[serde(rename(deserialize = "duration"))]
+    pub duration_secs: Option<u64>,
+    /// Kbps of the original.
+    pub bit_rate: Option<u32>,
+    /// Original's file extension, e.g. `flac`.
+    pub suffix: Option<String>,
+    pub content_type: Option<String>,
+    // OpenSubsonic extensions
+    #[serde(rename(deserialize = "samplingRate"))]
+    pub sample_rate: Option<u32>,
+    pub bit_depth: Option<u8>,
+    #[serde(rename(deserialize = "channelCount"))]
+    pub channels: Option<u8>,
+    #[serde(skip_deserializing)]
+    pub cover_art_url: Option<String>,
+    /// Stream URL; play or queue it like a path.
+    #[serde(skip_deserializing)]
+    pub url: String,
+    /// The stream is a server transcode rather than the original.
+    #[serde(skip_deserializing)]
+    pub transcoded: bool,
+    #[serde(skip_serializing)]
+    cover_art: Option<String>,
+}
+
+#[derive(Clone, Serialize, Default)]
+pub struct SubsonicSearchResults {
+    pub artists: Vec<SubsonicArtist>,
+    pub albums: Vec<SubsonicAlbum>,
+    pub songs: Vec<SubsonicSong>,
+}
+
+/// Album lists the server can give (`getAlbumList2`'s `type`).
+#[derive(Clone, Copy, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AlbumListType {
+    Newest,
+    Recent,
+    Frequent,
+    Random,
+    Starred,
+    AlphabeticalByName,
+    AlphabeticalByArtist,
+}
+
+impl AlbumListType {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Newest => "newest",
+            Self::Recent => "recent",
+            Self::Frequent => "frequent",
+            Self::Random => "random",
+            Self::Starred => "starred",
+            Self::AlphabeticalByName => "alphabeticalByName",
+            Self::AlphabeticalByArtist => "alphabeticalByArtist",
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct Envelope {
+    #[serde(rename = "subsonic-response")]
+    response: ApiResponse,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ApiResponse {
+    status: String,
+    error: Option<ApiError>,
+    #[serde(rename = "type")]
+    server_type: Option<String>,
+    server_version: Option<String>,
+    #[serde(flatten)]
+    body: Map<String, Value>,
+}
+
+#[derive(Deserialize)]
+struct ApiError {
+    code: Option<u32>,
+    message: Option<String>,
+}
+
+/// A play handed over by the play counter, to scrobble.
+struct Scrobble {
+    server: ServerConfig,
+    song_id: String,
+    listened_at: i64,
+}
+
+pub struct Subsonic {
+    app_data_dir: PathBuf,
+    servers: Mutex<Vec<ServerConfig>>,
+    scrobbles: Sender<Scrobble>,
+}
+
+impl Subsonic {
+    /// Load the configured servers and start the scrobbling thread.
+    pub fn spawn(app_data_dir: &Path) -> Arc<Self> {
+        let servers = std::fs::read_to_string(app_data_dir.join(SERVERS_FILE))
+            .ok()
+            .and_then(|data| serde_json::from_str(&data).ok())
+            .unwrap_or_default();
+        let (scrobbles, rx) = crossbeam_channel::unbounded();
+        thread::Builder::new()
+            .name("subsonic-scrobble".into())
+            .spawn(move || run_scrobbles(rx))
+            .expect("Failed to spawn Subsonic scrobble thread");
+        Arc::new(Self {
+            app_data_dir: app_data_dir.to_path_buf(),
+            servers: Mutex::new(servers),
+            scrobbles,
+        })
+    }
+
+    pub fn servers(&self) -> Vec<SubsonicServer> {
+        self.servers
+            .lock()
+            .iter()
+            .map(SubsonicServer::from)
+            .collect()
+    }
+
+    /// Sign in to a server and store it. Blocks on the network.
+    pub fn add_server(
+        &self,
+        name: Option<String>,
+        url: &str,
+        username: &str,
+        password: &str,
+    ) -> Result<SubsonicServer, String> {
+        let url = url.trim().trim_end_matches('/');
+        let url = url.strip_suffix("/rest").unwrap_or(url);
+        if !url.starts_with("http://") && !url.starts_with("https://") {
+            return Err("Server URL must start with http:// or https://".to_string());
+        }
+        if username.trim().is_empty() {
+            return Err("No username given".to_string());
+        }
+        let salt = random_hex(6);
+        let mut config = ServerConfig {
+            id: random_hex(4),
+            name: name
+                .map(|n| n.trim().to_string())
+                .filter(|n| !n.is_empty())
+                .unwrap_or_else(|| host(url).to_string()),
+            url: url.to_string(),
+            username: username.trim().to_string(),
+            token: format!("{:x}", md5::compute(format!("{}{}", password, salt))),
+            salt,
+            max_bit_rate: 0,
+            scrobble: true,
+            server: None,
+        };
+        let ping = call(&config, "ping", &[])?;
+        config.server = ping.server_type.map(|t| match ping.server_version {
+            Some(version) => format!("{} {}", t, version),
+            None => t,
+        });
+
+        let server = SubsonicServer::from(&config);
+        let mut servers = self.servers.lock();
+        servers.push(config);
+        self.save(&servers)?;
+        Ok(server)
+    }
+
+    pub fn remove_server(&self, server_id: &str) -> Result<(), String> {
+        let mut servers = self.servers.lock();
+        servers.retain(|s| s.id != server_id);
+        self.save(&servers)
+    }
+
+    /// Cap the bit rate of streams (`None` or 0 streams originals) and
+    /// switch scrobbling to the server on or off.
+    pub fn set_options(
+        &self,
+        server_id: &str,
+        max_bit_rate: Option<u32>,
+        scrobble: bool,
+    ) -> Result<SubsonicServer, String> {
+        let mut servers = self.servers.lock();
+        let config = servers
+            .iter_mut()
+            .find(|s| s.id == server_id)
+            .ok_or_else(|| format!("No Subsonic server {}", server_id))?;
+        config.max_bit_rate = max_bit_rate.unwrap_or(0);
+        config.scrobble = scrobble;
+        let server = SubsonicServer::from(&*config);
+        self.save(&servers)?;
+        Ok(server)
+    }
+
+    /// Every artist on the server, in its own order.
+    pub fn artists(&self, server_id: &str) -> Result<Vec<SubsonicArtist>, String> {
+        #[derive(Deserialize)]
+        struct Artists {
+            #[serde(default)]
+            index: Vec<Index>,
+        }
+        #[derive(Deserialize)]
+        struct Index {
+            #[serde(default)]
+            artist: Vec<SubsonicArtist>,
+        }
+
+        let config = self.server(server_id)?;
+        let artists: Artists = field(call(&config, "getArtists", &[])?, "artists")?;
+        Ok(artists
+            .index
+            .into_iter()
+            .flat_map(|i| i.artist)
+            .map(|a| with_artist_art(&config, a))
+            .collect())
+    }
+
+    pub fn artist_albums(
+        &self,
+        server_id: &str,
+        artist_id: &str,
+    ) -> Result<Vec<SubsonicAlbum>, String> {
+        #[derive(Deserialize)]
+        struct Artist {
+            #[serde(default)]
+            album: Vec<SubsonicAlbum>,
+        }
+
+        let config = self.server(server_id)?;
+        let artist: Artist = field(call(&config, "getArtist", &[("id", artist_id)])?, "artist")?;
+        Ok(artist
+            .album
+            .into_iter()
+            .map(|a| with_album_art(&config, a))
+            .collect())
+    }
+
+    /// One page of an album list, e.g. the newest albums.
+    pub fn album_list(
+        &self,
+        server_id: &str,
+        list: AlbumListType,
+        offset: u32,
+        size: u32,
+    ) -> Result<Vec<SubsonicAlbum>, String> {
+        #[derive(Deserialize)]
+        struct AlbumList {
+            #[serde(default)]
+            album: Vec<SubsonicAlbum>,
+        }
+
+        let config = self.server(server_id)?;
+        let (offset, size) = (offset.to_string(), size.min(500).to_string());
+        let response = call(
+            &config,
+            "getAlbumList2",
+            &[
+                ("type", list.as_str()),
+                ("offset", &offset),
+                ("size", &size),
+            ],
+        )?;
+        let list: AlbumList = field(response, "albumList2")?;
+        Ok(list
+            .album
+            .into_iter()
+            .map(|a| with_album_art(&config, a))
+            .collect())
+    }
+
+    pub fn album_songs(
+        &self,
+        server_id: &str,
+        album_id: &str,
+    ) -> Result<Vec<SubsonicSong>, String> {
+        #[derive(Deserialize)]
+        struct Album {
+            #[serde(default)]
+            song: Vec<SubsonicSong>,
+        }
+
+        let config = self.server(server_id)?;
+        let album: Album = field(call(&config, "getAlbum", &[("id", album_id)])?, "album")?;
+        album
+            .song
+            .into_iter()
+            .map(|s| with_stream(&config, s))
+            .collect()
+    }
+
+    /// Artists, albums and songs matching `query`, up to `limit` of each.
+    pub fn search(
+        &self,
+        server_id: &str,
+        query: &str,
+        limit: u32,
+    ) -> Result<SubsonicSearchResults, String> {
+        #[derive(Deserialize, Default)]
+        #[serde(default)]
+        struct Found {
+            artist: Vec<SubsonicArtist>,
+            album: Vec<SubsonicAlbum>,
+            song: Vec<SubsonicSong>,
+        }
+
+        let query = query.trim();
+        if query.is_empty() {
+            return Ok(SubsonicSearchResults::default());
+        }
+        let config = self.server(server_id)?;
+        let limit = limit.to_string();
+        let response = call(
+            &config,
+            "search3",
+            &[
+                ("query", query),
+                ("artistCount", &limit),
+                ("albumCount", &limit),
+                ("songCount", &limit),
+            ],
+        )?;
+        // Servers leave the field out when nothing matches
+        let found: Found = match response.body.get("searchResult3") {
+            Some(_) => field(response, "searchResult3")?,
+            None => Found::default(),
+        };
+        Ok(SubsonicSearchResults {
+            artists: found
+                .artist
+                .into_iter()
+                .map(|a| with_artist_art(&config, a))
+                .collect(),
+            albums: found
+                .album
+                .into_iter()
+                .map(|a| with_album_art(&config, a))
+                .collect(),
+            songs: found
+                .song
+                .into_iter()
+                .map(|s| with_stream(&config, s))
+                .collect::<Result<_, _>>()?,
+        })
+    }
+
+    /// Scrobble a play counted at `listened_at` (Unix time) to the server
+    /// the playing stream comes from, if any.
+    pub fn listened(&self, state: &PlaybackState, listened_at: i64) {
+        let Some(file) = state.current_file.as_deref() else {
+            return;
+        };
+        let server = self
+            .servers
+            .lock()
+            .iter()
+            .find(|s| file.starts_with(&format!("{}/rest/stream", s.url)))
+            .cloned();
+        let (Some(server), Some(song_id)) = (server, http::query_value(file, "id")) else {
+            return;
+        };
+        if server.scrobble {
+            let _ = self.scrobbles.send(Scrobble {
+                server,
+                song_id,
+                listened_at,
+            });
+        }
+    }
+
+    fn server(&self, server_id: &str) -> Result<ServerConfig, String> {
+        self.servers
+            .lock()
+            .iter()
+            .find(|s| s.id == server_id)
+            .cloned()
+            .ok_or_else(|| format!("No Subsonic server {}", server_id))
+    }
+
+    fn save(&self, servers: &[ServerConfig]) -> Result<(), String> {
+        std::fs::create_dir_all(&self.app_data_dir)
+            .map_err(|e| format!("Failed to create dir: {}", e))?;
+        let json = serde_json::to_string_pretty(servers)
+            .map_err(|e| format!("Serialize failed: {}", e))?;
+        std::fs::write(self.app_data_dir.join(SERVERS_FILE), json)
+            .map_err(|e| format!("Write failed: {}", e))
+    }
+}
+
+fn run_scrobbles(scrobbles: Receiver<Scrobble>) {
+    for scrobble in scrobbles {
+        let time = (scrobble.listened_at * 1000).to_string();
+        let result = call(
+            &scrobble.server,
+            "scrobble",
+            &[
+                ("id", &scrobble.song_id),
+                ("time", &time),
+                ("submission", "true"),
+            ],
+        );
+        if let Err(e) = result {
+            log::warn!("Scrobble to {} failed: {}", scrobble.server.name, e);
+        }
+    }
+}
+
+/// The credentials and format every call takes.
+fn auth(config: &ServerConfig) -> [(&'static str, &str); 6] {
+    [
+        ("u", &config.username),
+        ("t", &config.token),
+        ("s", &config.salt),
+        ("v", API_VERSION),
+        ("c", CLIENT_NAME),
+        ("f", "json"),
+    ]
+}
+
+/// Call an API method, failing on the server's own errors as well as on
+/// HTTP ones.
+fn call(
+    config: &ServerConfig,
+    method: &str,
+    params: &[(&str, &str)],
+) -> Result<ApiResponse, String> {
+    let mut query = auth(config).to_vec();
+    query.extend_from_slice(params);
+    let envelope: Envelope = http::get_json(&format!("{}/rest/{}", config.url, method), &query)?;
+    let response = envelope.response;
+    if response.status == "ok" {
+        return Ok(response);
+    }
+    let error = response.error;
+    let message = error
+        .as_ref()
+        .and_then(|e| e.message.clone())
+        .unwrap_or_else(|| "Unknown error".to_string());
+    Err(match error.and_then(|e| e.code) {
+        Some(40) => "Wrong username or password".to_string(),
+        Some(41) => "The server doesn't support token authentication".to_string(),
+        _ => format!("Server error: {}", message),
+    })
+}
+
+/// Parse one field of a response's body.
+fn field<T: serde::de::DeserializeOwned>(
+    mut response: ApiResponse,
+    name: &str,
+) -> Result<T, String> {
+    let value = response
+        .body
+        .remove(name)
+        .ok_or_else(|| format!("Server sent no {}", name))?;
+    serde_json::from_value(value).map_err(|e| format!("Invalid {}: {}", name, e))
+}
+
+fn with_artist_art(config: &ServerConfig, mut artist: SubsonicArtist) -> SubsonicArtist {
+    artist.cover_art_url = artist
+        .cover_art
+        .as_deref()
+        .and_then(|id| art_url(config, id));
+    artist
+}
+
+fn with_album_art(config: &ServerConfig, mut album: SubsonicAlbum) -> SubsonicAlbum {
+    album.cover_art_url = album
+        .cover_art
+        .as_deref()
+        .and_then(|id| art_url(config, id));
+    album
+}
+
+/// Fill in the song's stream URL, transcoded when the decoder can't play
+/// the original or the server's bit rate is capped.
+fn with_stream(config: &ServerConfig, mut song: SubsonicSong) -> Result<SubsonicSong, String> {
+    let direct = song
+        .suffix
+        .as_deref()
+        .is_some_and(|s| DIRECT_SUFFIXES.contains(&s.to_lowercase().as_str()));
+    let max_bit_rate = match (config.max_bit_rate, direct) {
+        (0, true) => None,
+        (0, false) => Some(TRANSCODE_BIT_RATE),
+        (cap, _) => Some(cap),
+    };
+    // A capped original already under the cap is sent as it is
+    let transcode = max_bit_rate
+        .is_some_and(|cap| !direct || song.bit_rate.is_none_or(|rate| rate == 0 || rate > cap));
+
+    let mut query = auth(config).to_vec();
+    query.retain(|(name, _)| *name != "f");
+    let max_bit_rate = max_bit_rate.unwrap_or(0).to_string();
+    query.push(("id", &song.id));
+    if transcode {
+        query.extend([
+            ("format", "mp3"),
+            ("maxBitRate", &max_bit_rate),
+            // Lets the length, and so seeking, be known up front
+            ("estimateContentLength", "true"),
+        ]);
+    } else {
+        query.push(("format", "raw"));
+    }
+    song.url = http::url_with_query(&format!("{}/rest/stream", config.url), &query)?;
+    song.transcoded = transcode;
+    song.cover_art_url = song.cover_art.as_deref().and_then(|id| art_url(config, id));
+    Ok(song)
+}
+
+fn art_url(config: &ServerConfig, cover_art_id: &str) -> Option<String> {
+    let mut query = auth(config).to_vec();
+    query.retain(|(name, _)| *name != "f");
+    query.push(("id", cover_art_id));
+    http::url_with_query(&format!("{}/rest/getCoverArt", config.url), &query).ok()
+}
+
+/// Host part of a URL, to name a server by.
+fn host(url: &str) -> &str {
+    let rest = url.split_once("://").map_or(url, |(_, rest)| rest);
+    rest.split(['/', ':']).next().unwrap_or(rest)
+}
@@ -0,0 +1,120 @@
+//! A small XML tree for the documents media servers send: elements by local
+//! name (namespace prefixes dropped, as servers disagree on them), their
+//! attributes and text with entities resolved.
+
+use quick_xml::escape::resolve_xml_entity;
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::{Reader, XmlVersion};
+
+#[derive(Default)]
+pub struct Element {
+    pub name: String,
+    attributes: Vec<(String, String)>,
+    /// Text directly inside the element, untrimmed.
+    pub text: String,
+    pub children: Vec<Element>,
+}
+
+impl Element {
+    pub fn attribute(&self, name: &str) -> Option<&str> {
+        self.attributes
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, v)| v.as_str())
+    }
+
+    pub fn child(&self, name: &str) -> Option<&Element> {
+        self.children.iter().find(|c| c.name == name)
+    }
+
+    /// Trimmed text of the first child named `name`; `None` when missing or
+    /// empty.
+    pub fn child_text(&self, name: &str) -> Option<&str> {
+        self.child(name)
+            .map(|c| c.text.trim())
+            .filter(|t| !t.is_empty())
+    }
+
+    pub fn children_named<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a Element> {
+        self.children.iter().filter(move |c| c.name == name)
+    }
+
+    /// Every element named `name` below this one, in document order.
+    pub fn descendants<'a>(&'a self, name: &str) -> Vec<&'a Element> {
+        let mut found = Vec::new();
+        let mut pending: Vec<&Element> = self.children.iter().rev().collect();
+        while let Some(element) = pending.pop() {
+            if element.name == name {
+                found.push(element);
+            }
+            pending.extend(element.children.iter().rev());
+        }
+        found
+    }
+}
+
+/// Parse a document into its root element.
+pub fn parse(xml: &str) -> Result<Element, String> {
+    let mut reader = Reader::from_str(xml);
+    // Elements still open; the first holds the document's root
+    let mut open = vec![Element::default()];
+    loop {
+        let event = reader
+            .read_event()
+            .map_err(|e| format!("Invalid XML: {}", e))?;
+        let current = open.last_mut().ok_or("Invalid XML: unbalanced tags")?;
+        match event {
+            Event::Start(start) => {
+                let element = element(&start)?;
+                open.push(element);
+            }
+            Event::Empty(start) => current.children.push(element(&start)?),
+            Event::End(_) => {
+                let element = open.pop().ok_or("Invalid XML: unbalanced tags")?;
+                open.last_mut()
+                    .ok_or("Invalid XML: unbalanced tags")?
+                    .children
+                    .push(element);
+            }
+            Event::Text(text) => current.text.push_str(&text.xml10_content()),
+            Event::CData(data) => current.text.push_str(&data.xml10_content()),
+            Event::GeneralRef(reference) => {
+                if let Ok(Some(c)) = reference.resolve_char_ref() {
+                    current.text.push(c);
+                } else if let Some(s) = resolve_xml_entity(&reference.xml10_content()) {
+                    current.text.push_str(s);
+                }
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+    match open.pop() {
+        Some(document) if open.is_empty() => document
+            .children
+            .into_iter()
+            .next()
+            .ok_or_else(|| "Empty XML document".to_string()),
+        _ => Err("Invalid XML: unclosed tags".to_string()),
+    }
+}
+
+fn element(start: &BytesStart) -> Result<Element, String> {
+    let mut attributes = Vec::new();
+    for attribute in start.attributes() {
+        let attribute = attribute.map_err(|e| format!("Invalid XML: {}", e))?;
+        let value = attribute
+            .normalized_value(XmlVersion::Implicit1_0)
+            .map_err(|e| format!("Invalid XML: {}", e))?;
+        attributes.push((
+            attribute.key.local_name().as_ref().to_string(),
+            value.into_owned(),
+        ));
+    }
+    Ok(Element {
+        name: start.local_name().as_ref().to_string(),
+        attributes,
+        text: String::new(),
+        children: Vec::new(),
+    })
+}
@@ -0,0 +1,366 @@
+//! Windows taskbar integration: previous, play/pause and next buttons in the
+//! thumbnail toolbar (under the preview shown when hovering the app's
+//! taskbar button), and the playing track's progress on the button itself.
+//!
+//! The toolbar goes through `ITaskbarList3`, called through its vtable by
+//! hand since `windows-sys` has no COM interfaces. Button clicks arrive at
+//! the main window as `WM_COMMAND`, so the window is subclassed to catch
+//! them, and to add the buttons again when Explorer restarts and announces
+//! `TaskbarButtonCreated`. The icons are drawn here rather than shipped.
+//! Progress uses Tauri's progress bar: green while playing, yellow while
+//! paused, hidden when stopped.
+//!
+//! A thread watches the engine, like [`crate::media_controls`], and updates
+//! both; the toolbar itself is only touched on the main thread, which owns
+//! the window. Clicks act like the media keys and are emitted as
+//! `media://control` too.
+
+use std::cell::{Cell, RefCell};
+use std::ffi::c_void;
+use std::thread;
+use std::time::Duration;
+use tauri::window::{ProgressBarState, ProgressBarStatus};
+use tauri::{AppHandle, Emitter, Manager};
+use windows_sys::core::GUID;
+use windows_sys::Win32::Foundation::{HWND, LPARAM, LRESULT, WPARAM};
+use windows_sys::Win32::Graphics::Gdi::{CreateBitmap, DeleteObject};
+use windows_sys::Win32::System::Com::{CoCreateInstance, CLSCTX_INPROC_SERVER};
+use windows_sys::Win32::UI::Shell::{
+    DefSubclassProc, SetWindowSubclass, THBF_ENABLED, THBN_CLICKED, THB_FLAGS, THB_ICON,
+    THB_TOOLTIP, THUMBBUTTON,
+};
+use windows_sys::Win32::UI::WindowsAndMessaging::{
+    CreateIconIndirect, RegisterWindowMessageW, HICON, ICONINFO, WM_COMMAND,
+};
+
+use crate::audio::engine::AudioCommand;
+use crate::commands::{self, AppState};
+use crate::media_controls::MediaAction;
+
+/// How often the engine is checked for changes.
+const SYNC_INTERVAL: Duration = Duration::from_millis(500);
+
+const CLSID_TASKBAR_LIST: GUID = GUID::from_u128(0x56fdf344_fd6d_11d0_958a_006097c9a090);
+const IID_TASKBAR_LIST3: GUID = GUID::from_u128(0xea1afb91_9e28_4b86_90e9_9e9f8a5eefaf);
+
+/// Identifies the subclass procedure on the window.
+const SUBCLASS_ID: usize = 1;
+
+/// Icon size in pixels; Windows scales it for the toolbar.
+const ICON_SIZE: usize = 16;
+
+#[derive(Clone, Copy, PartialEq)]
+enum Button {
+    Previous = 0,
+    PlayPause = 1,
+    Next = 2,
+}
+
+/// `ITaskbarList3`'s vtable, up to the toolbar methods.
+#[repr(C)]
+struct TaskbarListVtbl {
+    query_interface: usize,
+    add_ref: usize,
+    release: usize,
+    hr_init: unsafe extern "system" fn(*mut TaskbarList) -> i32,
+    // AddTab to SetTabActive
+    _other: [usize; 11],
+    thumb_bar_add_buttons:
+        unsafe extern "system" fn(*mut TaskbarList, HWND, u32, *const THUMBBUTTON) -> i32,
+    thumb_bar_update_buttons:
+        unsafe extern "system" fn(*mut TaskbarList, HWND, u32, *const THUMBBUTTON) -> i32,
+}
+
+#[repr(C)]
+struct TaskbarList {
+    vtbl: *const TaskbarListVtbl,
+}
+
+struct Toolbar {
+    app: AppHandle,
+    list: *mut TaskbarList,
+    hwnd: HWND,
+    icons: Icons,
+    playing: bool,
+}
+
+struct Icons {
+    previous: HICON,
+    play: HICON,
+    pause: HICON,
+    next: HICON,
+}
+
+thread_local! {
+    /// The main thread's toolbar, once installed. Only borrowed briefly,
+    /// never across a call that could send the window a message.
+    static TOOLBAR: RefCell<Option<Toolbar>> = const { RefCell::new(None) };
+    /// `TaskbarButtonCreated`'s message number.
+    static CREATED_MESSAGE: Cell<u32> = const { Cell::new(0) };
+}
+
+/// Add the toolbar to the main window and start the thread keeping it and
+/// the progress up to date. Must be called on the main thread; logs and
+/// gives up when the taskbar isn't available.
+pub fn spawn(app: &AppHandle) {
+    let Some(window) = app.get_webview_window("main") else {
+        return;
+    };
+    let hwnd = match window.hwnd() {
+        Ok(hwnd) => hwnd.0 as usize as HWND,
+        Err(e) => {
+            log::warn!("Taskbar controls unavailable: {}", e);
+            return;
+        }
+    };
+    if let Err(e) = install(app, hwnd) {
+        log::warn!("Taskbar controls unavailable: {}", e);
+        return;
+    }
+
+    let app = app.clone();
+    thread::Builder::new()
+        .name("taskbar".into())
+        .spawn(move || sync(&app))
+        .expect("Failed to spawn taskbar thread");
+}
+
+fn install(app: &AppHandle, hwnd: HWND) -> Result<(), String> {
+    let mut list: *mut c_void = std::ptr::null_mut();
+    // SAFETY: the GUIDs outlive the call and `list` receives an interface
+    // pointer of the IID asked for
+    let hr = unsafe {
+        CoCreateInstance(
+            &CLSID_TASKBAR_LIST,
+            std::ptr::null_mut(),
+            CLSCTX_INPROC_SERVER,
+            &IID_TASKBAR_LIST3,
+            &mut list,
+        )
+    };
+    if hr < 0 || list.is_null() {
+        return Err(format!("ITaskbarList3 unavailable ({:#x})", hr));
+    }
+    let list = list as *mut TaskbarList;
+    // SAFETY: `list` is a live ITaskbarList3
+    let hr = unsafe { ((*(*list).vtbl).hr_init)(list) };
+    if hr < 0 {
+        return Err(format!("ITaskbarList3 failed to start ({:#x})", hr));
+    }
+
+    let name: Vec<u16> = "TaskbarButtonCreated\0".encode_utf16().collect();
+    // SAFETY: `name` is NUL-terminated
+    let created_message = unsafe { RegisterWindowMessageW(name.as_ptr()) };
+    CREATED_MESSAGE.with(|m| m.set(created_message));
+    let toolbar = Toolbar {
+        app: app.clone(),
+        list,
+        hwnd,
+        icons: Icons {
+            previous: icon(|x, y| triangle(16.0 - x, y, 3.5, 10.5) || (3.5..5.5).contains(&x))?,
+            play: icon(|x, y| triangle(x, y, 4.5, 12.5))?,
+            pause: icon(|x, _| (3.5..6.5).contains(&x) || (9.5..12.5).contains(&x))?,
+            next: icon(|x, y| triangle(x, y, 3.5, 10.5) || (10.5..12.5).contains(&x))?,
+        },
+        playing: false,
+    };
+    TOOLBAR.with(|t| *t.borrow_mut() = Some(toolbar));
+
+    // SAFETY: `hwnd` is the main window, owned by this thread
+    if unsafe { SetWindowSubclass(hwnd, Some(subclass_proc), SUBCLASS_ID, 0) } == 0 {
+        return Err("Failed to subclass the main window".to_string());
+    }
+    // Fails if the taskbar button doesn't exist yet; TaskbarButtonCreated
+    // adds the buttons then
+    add_buttons();
+    Ok(())
+}
+
+/// Keep the toolbar and progress up to date with the engine. Never returns.
+fn sync(app: &AppHandle) {
+    let state = app.state::<AppState>();
+    let mut shown_playing = false;
+    // Playing, paused and percent last shown
+    let mut shown_progress: Option<(bool, bool, u64)> = None;
+    loop {
+        thread::sleep(SYNC_INTERVAL);
+        let playback = state.engine.get_state();
+
+        if playback.is_playing != shown_playing {
+            let playing = playback.is_playing;
+            let _ = app.run_on_main_thread(move || set_playing(playing));
+            shown_playing = playing;
+        }
+
+        let percent = match playback.duration_secs {
+            d if d > 0.0 => ((playback.position_secs / d) * 100.0).clamp(0.0, 100.0) as u64,
+            _ => 0,
+        };
+        let progress = (playback.is_playing, playback.is_paused, percent);
+        if shown_progress == Some(progress) {
+            continue;
+        }
+        let status = if playback.is_playing {
+            ProgressBarStatus::Normal
+        } else if playback.is_paused {
+            ProgressBarStatus::Paused
+        } else {
+            ProgressBarStatus::None
+        };
+        if let Some(window) = app.get_webview_window("main") {
+            if let Err(e) = window.set_progress_bar(ProgressBarState {
+                status: Some(status),
+                progress: Some(percent),
+            }) {
+                log::warn!("Failed to update taskbar progress: {}", e);
+            }
+        }
+        shown_progress = Some(progress);
+    }
+}
+
+unsafe extern "system" fn subclass_proc(
+    hwnd: HWND,
+    message: u32,
+    wparam: WPARAM,
+    lparam: LPARAM,
+    _id: usize,
+    _data: usize,
+) -> LRESULT {
+    if message == WM_COMMAND && ((wparam >> 16) & 0xffff) as u32 == THBN_CLICKED {
+        clicked(wparam & 0xffff);
+    } else if message != 0 && message == CREATED_MESSAGE.with(Cell::get) {
+        add_buttons();
+    }
+    // SAFETY: passing the message on, as received
+    unsafe { DefSubclassProc(hwnd, message, wparam, lparam) }
+}
+
+fn clicked(id: usize) {
+    let Some(app) = TOOLBAR.with(|t| t.try_borrow().ok()?.as_ref().map(|t| t.app.clone())) else {
+        return;
+    };
+    let state = app.state::<AppState>();
+    let action = if id == Button::Previous as usize {
+        commands::previous_track(app.state::<AppState>());
+        MediaAction::Previous
+    } else if id == Button::Next as usize {
+        commands::next_track(app.state::<AppState>());
+        MediaAction::Next
+    } else if state.engine.get_state().is_playing {
+        state.engine.send_command(AudioCommand::Pause);
+        MediaAction::Pause
+    } else {
+        state.engine.send_command(AudioCommand::Resume);
+        MediaAction::Play
+    };
+    let _ = app.emit("media://control", action);
+}
+
+fn add_buttons() {
+    let Some((list, hwnd, buttons)) = current_buttons() else {
+        return;
+    };
+    // SAFETY: `list` is a live ITaskbarList3 and `buttons` outlives the call
+    unsafe {
+        ((*(*list).vtbl).thumb_bar_add_buttons)(list, hwnd, buttons.len() as u32, buttons.as_ptr())
+    };
+}
+
+/// Show play or pause on the middle button.
+fn set_playing(playing: bool) {
+    TOOLBAR.with(|t| {
+        if let Some(toolbar) = t.borrow_mut().as_mut() {
+            toolbar.playing = playing;
+        }
+    });
+    let Some((list, hwnd, buttons)) = current_buttons() else {
+        return;
+    };
+    // SAFETY: as in `add_buttons`
+    unsafe {
+        ((*(*list).vtbl).thumb_bar_update_buttons)(
+            list,
+            hwnd,
+            buttons.len() as u32,
+            buttons.as_ptr(),
+        )
+    };
+}
+
+/// The toolbar's interface, window and buttons as they should be now.
+fn current_buttons() -> Option<(*mut TaskbarList, HWND, [THUMBBUTTON; 3])> {
+    TOOLBAR.with(|t| {
+        let toolbar = t.try_borrow().ok()?;
+        let toolbar = toolbar.as_ref()?;
+        Some((toolbar.list, toolbar.hwnd, buttons(toolbar)))
+    })
+}
+
+fn buttons(toolbar: &Toolbar) -> [THUMBBUTTON; 3] {
+    let icons = &toolbar.icons;
+    let (play_pause, tip) = if toolbar.playing {
+        (icons.pause, "Pause")
+    } else {
+        (icons.play, "Play")
+    };
+    [
+        button(Button::Previous, icons.previous, "Previous"),
+        button(Button::PlayPause, play_pause, tip),
+        button(Button::Next, icons.next, "Next"),
+    ]
+}
+
+fn button(id: Button, icon: HICON, tip: &str) -> THUMBBUTTON {
+    let mut sz_tip = [0u16; 260];
+    for (slot, unit) in sz_tip.iter_mut().zip(tip.encode_utf16()) {
+        *slot = unit;
+    }
+    THUMBBUTTON {
+        dwMask: THB_ICON | THB_TOOLTIP | THB_FLAGS,
+        iId: id as u32,
+        iBitmap: 0,
+        hIcon: icon,
+        szTip: sz_tip,
+        dwFlags: THBF_ENABLED,
+    }
+}
+
+/// A right-pointing triangle from `left` to `right`, as tall as the glyph.
+fn triangle(x: f32, y: f32, left: f32, right: f32) -> bool {
+    x >= left && (y - 8.0).abs() <= (right - x) * 5.0 / (right - left)
+}
+
+/// A white icon of the pixels `shape` takes, tested at their centres.
+fn icon(shape: impl Fn(f32, f32) -> bool) -> Result<HICON, String> {
+    let mut pixels = vec![0u32; ICON_SIZE * ICON_SIZE];
+    for (i, pixel) in pixels.iter_mut().enumerate() {
+        let (x, y) = ((i % ICON_SIZE) as f32 + 0.5, (i / ICON_SIZE) as f32 + 0.5);
+        // Only the glyph's rows, 3 px in from the top and bottom
+        if (3.0..13.0).contains(&y) && shape(x, y) {
+            *pixel = 0xffff_ffff;
+        }
+    }
+    let mask = vec![0u8; ICON_SIZE * ICON_SIZE / 8];
+    let size = ICON_SIZE as i32;
+    // SAFETY: both buffers hold a whole bitmap of the size given and outlive
+    // the calls; the bitmaps are copied into the icon and deleted after
+    unsafe {
+        let color = CreateBitmap(size, size, 1, 32, pixels.as_ptr() as *const c_void);
+        let mask = CreateBitmap(size, size, 1, 1, mask.as_ptr() as *const c_void);
+        let info = ICONINFO {
+            fIcon: 1,
+            xHotspot: 0,
+            yHotspot: 0,
+            hbmMask: mask,
+            hbmColor: color,
+        };
+        let icon = CreateIconIndirect(&info);
+        DeleteObject(color);
+        DeleteObject(mask);
+        if icon.is_null() {
+            return Err("Failed to create a toolbar icon".to_string());
+        }
+        Ok(icon)
+    }
+}
@@ -0,0 +1,144 @@
+use std::path::PathBuf;
+
+use masukii_lib::audio::wav_writer::WavWriter;
+use masukii_lib::library::bookmarks;
+use masukii_lib::library::database::LibraryDb;
+use masukii_lib::library::exclude::ExcludeRules;
+use masukii_lib::library::organize::{self, CollisionPolicy, MoveStatus};
+use masukii_lib::library::scanner::{self, ScanControl};
+use masukii_lib::library::{integrity, waveform};
+use masukii_lib::metadata::cue;
+use parking_lot::Mutex;
+
+const SAMPLE_RATE: u32 = 44_100;
+
+/// A library of one 3-second stereo WAV image split into three tracks by
+/// a cue sheet, scanned into an in-memory database.
+fn cue_library(name: &str) -> (PathBuf, Mutex<LibraryDb>) {
+    let dir = std::env::temp_dir().join(format!("masukii-cue-{}-{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+
+    let mut wav = WavWriter::create(&dir.join("Album.wav"), SAMPLE_RATE, 2, 16).unwrap();
+    let samples: Vec<i32> = (0..SAMPLE_RATE * 3)
+        .flat_map(|frame| {
+            let sample = (frame % 1000) as i32 * 16;
+            [sample, -sample]
+        })
+        .collect();
+    wav.write(&samples).unwrap();
+    wav.finish().unwrap();
+    std::fs::write(
+        dir.join("Album.cue"),
+        "PERFORMER \"Artist\"\n\
+         TITLE \"Album\"\n\
+         FILE \"Album.wav\" WAVE\n\
+         \x20 TRACK 01 AUDIO\n\
+         \x20   TITLE \"One\"\n\
+         \x20   INDEX 01 00:00:00\n\
+         \x20 TRACK 02 AUDIO\n\
+         \x20   TITLE \"Two\"\n\
+         \x20   INDEX 01 00:01:00\n\
+         \x20 TRACK 03 AUDIO\n\
+         \x20   TITLE \"Three\"\n\
+         \x20   INDEX 01 00:02:00\n",
+    )
+    .unwrap();
+
+    let db = Mutex::new(LibraryDb::open_in_memory().unwrap());
+    let root = dir.to_string_lossy().to_string();
+    db.lock().add_root(&root).unwrap();
+    let exclude = ExcludeRules::default().compile().unwrap();
+    scanner::scan_into_library(
+        &db,
+        &root,
+        &exclude,
+        false,
+        &ScanControl::new(),
+        &mut |_| {},
+    )
+    .unwrap();
+    (dir, db)
+}
+
+#[test]
+fn integrity_checks_a_cue_image_once() {
+    let (dir, db) = cue_library("integrity");
+    let image = dir.join("Album.wav").to_string_lossy().to_string();
+    let track = cue::virtual_track_path(&dir.join("Album.cue").to_string_lossy(), 2);
+    assert!(db.lock().track_by_path(&track).unwrap().is_some());
+
+    assert_eq!(db.lock().integrity_pending(false).unwrap(), vec![image]);
+    let summary = integrity::run(&db, &[], false, &ScanControl::new(), &mut |_| {}).unwrap();
+    assert_eq!(summary.processed, 1);
+    assert!(summary.errors.is_empty());
+    assert!(db.lock().integrity_pending(false).unwrap().is_empty());
+
+    // An explicit cue track checks its image
+    let summary = integrity::run(&db, &[track], false, &ScanControl::new(), &mut |_| {}).unwrap();
+    assert_eq!(summary.processed, 1);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn waveforms_cover_each_cue_segment() {
+    let (dir, db) = cue_library("waveform");
+    let sheet = dir.join("Album.cue").to_string_lossy().to_string();
+    let tracks: Vec<String> = (1..=3)
+        .map(|n| cue::virtual_track_path(&sheet, n))
+        .collect();
+
+    assert_eq!(db.lock().tracks_without_waveform().unwrap(), tracks);
+    let summary = waveform::generate_missing(&db, &ScanControl::new(), &mut |_| {}).unwrap();
+    assert_eq!(summary.processed, 3);
+    assert!(summary.errors.is_empty(), "{:?}", summary.errors);
+    assert!(db.lock().tracks_without_waveform().unwrap().is_empty());
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn organize_moves_a_cue_sheet_with_its_image() {
+    let (dir, db) = cue_library("organize");
+    let plan = organize::plan(
+        &db,
+        "%albumartist%/%album%/%title%",
+        &[],
+        None,
+        CollisionPolicy::Skip,
+    )
+    .unwrap();
+    assert_eq!(plan.len(), 1);
+    assert!(plan[0].status == MoveStatus::Move);
+    let folder = dir.join("Artist").join("Album");
+    assert_eq!(plan[0].to, folder.join("Album.cue").to_string_lossy());
+
+    let (summary, moved) = organize::run(&db, &plan, &ScanControl::new(), &mut |_| {}).unwrap();
+    assert!(summary.errors.is_empty(), "{:?}", summary.errors);
+    assert_eq!(moved.len(), 3);
+    assert!(folder.join("Album.wav").is_file());
+    let track = cue::virtual_track_path(&plan[0].to, 3);
+    let track = db.lock().track_by_path(&track).unwrap().unwrap();
+    assert_eq!(
+        track.source_path,
+        Some(folder.join("Album.wav").to_string_lossy().to_string())
+    );
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn cue_tracks_are_bookmarked_by_their_own_path() {
+    let (dir, db) = cue_library("bookmarks");
+    let image = dir.join("Album.wav").to_string_lossy().to_string();
+    let sheet = dir.join("Album.cue").to_string_lossy().to_string();
+
+    let second = bookmarks::bookmark_path(&db, &image, 1.0, Some(2.0));
+    assert_eq!(second, Some(cue::virtual_track_path(&sheet, 2)));
+    let third = bookmarks::bookmark_path(&db, &image, 2.0, None);
+    assert_eq!(third, Some(cue::virtual_track_path(&sheet, 3)));
+    assert_eq!(bookmarks::bookmark_path(&db, &image, 1.5, None), None);
+    assert_eq!(
+        bookmarks::bookmark_path(&db, &image, 0.0, None),
+        Some(image.clone())
+    );
+    std::fs::remove_dir_all(&dir).unwrap();
+}
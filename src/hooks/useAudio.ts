@@ -7,19 +7,22 @@ import * as cmd from "../lib/tauri-commands";
  * Mount this ONCE in App.tsx. It keeps the playerStore in sync with the
  * audio engine's actual state.
  *
- * Also auto-advances to next track when current track ends naturally.
+ * The backend moves on to the next queue entry when a track ends, as it
+ * does for media keys; the store's queue is synced whenever what's playing
+ * changes.
  */
 export function useAudio() {
   const intervalRef = useRef<number | null>(null);
-  const wasPlayingRef = useRef(false);
+  const playingRef = useRef<string | null>(null);
 
   const setPosition = usePlayerStore((s) => s.setPosition);
   const setDuration = usePlayerStore((s) => s.setDuration);
   const setPlaybackFlags = usePlayerStore((s) => s.setPlaybackFlags);
-  const nextTrack = usePlayerStore((s) => s.nextTrack);
-  const queue = usePlayerStore((s) => s.queue);
+  const syncQueue = usePlayerStore((s) => s.syncQueue);
 
   useEffect(() => {
+    syncQueue();
+
     const poll = async () => {
       try {
         const state = await cmd.getPlaybackState();
@@ -27,16 +30,15 @@ export function useAudio() {
         setDuration(state.duration_secs);
         setPlaybackFlags(state.is_playing, state.is_paused);
 
-        // Auto-advance: if was playing and now stopped (not paused), go to next
-        if (
-          wasPlayingRef.current &&
-          !state.is_playing &&
-          !state.is_paused &&
-          queue.length > 0
-        ) {
-          nextTrack();
+        // Cue-sheet tracks share a file, so the segment start counts too
+        const playing =
+          state.current_file === null
+            ? null
+            : `${state.current_file}#${state.start_secs}`;
+        if (playing !== null && playing !== playingRef.current) {
+          syncQueue();
         }
-        wasPlayingRef.current = state.is_playing;
+        playingRef.current = playing;
       } catch {
         // Backend not ready — ignore
       }
@@ -48,5 +50,5 @@ export function useAudio() {
         window.clearInterval(intervalRef.current);
       }
     };
-  }, [setPosition, setDuration, setPlaybackFlags, nextTrack, queue]);
+  }, [setPosition, setDuration, setPlaybackFlags, syncQueue]);
 }
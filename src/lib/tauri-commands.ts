@@ -1,12 +1,116 @@
-import { invoke } from "@tauri-apps/api/core";
+import { convertFileSrc, invoke } from "@tauri-apps/api/core";
 import type {
   PlaybackState,
   AudioDiagnostics,
+  DiagnosticsSample,
+  AudioLevels,
+  BitActivity,
+  DropoutLog,
+  LatencyMeasurement,
   NullTestResult,
+  SignalPath,
+  Spectrogram,
+  SpectrogramOptions,
+  RecordOptions,
+  RecordingStatus,
+  ConvertOptions,
+  ConvertJob,
+  SnapcastSettings,
+  SnapcastStatus,
+  TrackClipping,
+  TruePeakAnalysis,
   AudioDeviceInfo,
   DeviceProfile,
   ReplayGainMode,
+  ReplayGainInfo,
   TrackMetadata,
+  MetadataFields,
+  TagFields,
+  TagWriteSettings,
+  EncodingChoice,
+  EncodingRepair,
+  TagSuggestion,
+  CoverArtQuery,
+  CoverCandidate,
+  ArtResize,
+  EmbeddedPicture,
+  LyricLine,
+  OnlineLyrics,
+  JobSummary,
+  QueueEntry,
+  QueueSnapshot,
+  HistoryEntry,
+  Bookmark,
+  BookmarkRules,
+  LibraryTrack,
+  FolderListing,
+  LibraryAlbum,
+  AlbumDetail,
+  LibraryArtist,
+  ArtistDetail,
+  LibraryComposer,
+  ClassicalWork,
+  LibraryGenre,
+  GenreDetail,
+  PlayPeriod,
+  PlayedTrack,
+  LibraryStats,
+  Waveform,
+  IntegrityProblem,
+  IntegrityStatus,
+  AccurateRipDisc,
+  CdDriveInfo,
+  CdDisc,
+  RipRequest,
+  CollisionPolicy,
+  PlannedMove,
+  DuplicateGroup,
+  PathMapping,
+  RestoreReport,
+  LibraryRoot,
+  ExcludeRules,
+  SearchHit,
+  TrackFilter,
+  TrackSort,
+  TrackPage,
+  LibraryList,
+  RepeatMode,
+  DedupMode,
+  EnqueueReport,
+  Playlist,
+  PlaylistSummary,
+  PlaylistSortKey,
+  ListenBrainzStatus,
+  RemoteServerStatus,
+  MqttSettings,
+  MqttStatus,
+  ScriptsStatus,
+  Settings,
+  LogLevel,
+  LogEntry,
+  BufferMode,
+  BufferingStatus,
+  DspChainEntry,
+  DspPluginList,
+  AppError,
+  DlnaServer,
+  DlnaBrowsePage,
+  QueueStream,
+  SubsonicServer,
+  SubsonicArtist,
+  SubsonicAlbum,
+  SubsonicAlbumList,
+  SubsonicSong,
+  SubsonicSearchResults,
+  JellyfinServer,
+  JellyfinLibrary,
+  JellyfinArtist,
+  JellyfinAlbum,
+  JellyfinTrack,
+  JellyfinPage,
+  JellyfinSearchResults,
+  RadioStation,
+  RadioTitle,
 } from "./types";
 
 // ─── Playback ───
@@ -31,6 +135,317 @@ export const getPlaybackState = () =>
 
 export const getPosition = () => invoke<number>("get_position");
 
+// ─── Queue ───
+
+export const getQueue = () => invoke<QueueSnapshot>("get_queue");
+
+export const addToQueue = (paths: string[]) =>
+  invoke<EnqueueReport>("add_to_queue", { paths });
+
+export const addStreamsToQueue = (streams: QueueStream[]) =>
+  invoke<EnqueueReport>("add_streams_to_queue", { streams });
+
+export const clearQueue = () => invoke<void>("clear_queue");
+
+export const setShuffle = (enabled: boolean) =>
+  invoke<void>("set_shuffle", { enabled });
+
+export const setRepeatMode = (mode: RepeatMode) =>
+  invoke<void>("set_repeat_mode", { mode });
+
+export const setQueueDedup = (mode: DedupMode) =>
+  invoke<void>("set_queue_dedup", { mode });
+
+// The backend also advances when a track ends, emitting queue://advanced (the
+// QueueEntry now playing, or null at the end of the queue)
+export const nextTrack = () => invoke<QueueEntry | null>("next_track");
+
+export const previousTrack = () => invoke<QueueEntry | null>("previous_track");
+
+export const playQueueIndex = (index: number) =>
+  invoke<QueueEntry>("play_queue_index", { index });
+
+export const getHistory = () => invoke<HistoryEntry[]>("get_history");
+
+// ─── Playlists ───
+
+export const listPlaylists = () =>
+  invoke<PlaylistSummary[]>("list_playlists");
+
+export const getPlaylist = (id: number) =>
+  invoke<Playlist>("get_playlist", { id });
+
+export const createPlaylist = (name: string) =>
+  invoke<Playlist>("create_playlist", { name });
+
+export const renamePlaylist = (id: number, name: string) =>
+  invoke<void>("rename_playlist", { id, name });
+
+export const deletePlaylist = (id: number) =>
+  invoke<void>("delete_playlist", { id });
+
+export const addToPlaylist = (id: number, paths: string[]) =>
+  invoke<void>("add_to_playlist", { id, paths });
+
+export const removeFromPlaylist = (id: number, indices: number[]) =>
+  invoke<void>("remove_from_playlist", { id, indices });
+
+export const movePlaylistTrack = (id: number, from: number, to: number) =>
+  invoke<void>("move_playlist_track", { id, from, to });
+
+export const sortPlaylist = (
+  id: number,
+  key: PlaylistSortKey,
+  descending: boolean
+) => invoke<Playlist>("sort_playlist", { id, key, descending });
+
+export const lockPlaylistOrder = (id: number) =>
+  invoke<void>("lock_playlist_order", { id });
+
+export const importPlaylistFile = (path: string) =>
+  invoke<Playlist>("import_playlist_file", { path });
+
+// ─── Bookmarks ───
+
+export const setBookmark = (path: string, positionSecs: number) =>
+  invoke<void>("set_bookmark", { path, positionSecs });
+
+export const clearBookmark = (path: string) =>
+  invoke<void>("clear_bookmark", { path });
+
+export const listBookmarks = () => invoke<Bookmark[]>("list_bookmarks");
+
+export const getBookmarkRules = () =>
+  invoke<BookmarkRules>("get_bookmark_rules");
+
+export const setBookmarkRules = (rules: BookmarkRules) =>
+  invoke<void>("set_bookmark_rules", { rules });
+
+// ─── Library ───
+
+export const listLibraryRoots = () =>
+  invoke<LibraryRoot[]>("list_library_roots");
+
+export const addLibraryRoot = (path: string) =>
+  invoke<LibraryRoot>("add_library_root", { path });
+
+export const removeLibraryRoot = (id: number) =>
+  invoke<void>("remove_library_root", { id });
+
+export const setLibraryRootEnabled = (id: number, enabled: boolean) =>
+  invoke<void>("set_library_root_enabled", { id, enabled });
+
+// Runs in the background: listen for library://scan-progress / scan-complete.
+// Omit `id` to scan every enabled root. A dry run writes nothing and lists
+// the changes in ScanSummary.changes; only dry runs take unsaved `exclude`.
+export const scanLibrary = (
+  id?: number,
+  dryRun?: boolean,
+  exclude?: ExcludeRules,
+) => invoke<void>("scan_library", { id, dryRun, exclude });
+
+export const cancelScan = () => invoke<boolean>("cancel_scan");
+
+export const isScanRunning = () => invoke<boolean>("is_scan_running");
+
+export const getWaveform = (path: string) =>
+  invoke<Waveform>("get_waveform", { path });
+
+export const generateWaveforms = () => invoke<void>("generate_waveforms");
+
+export const cancelWaveforms = () => invoke<boolean>("cancel_waveforms");
+
+export const scanReplaygain = (paths?: string[], albumIds?: number[]) =>
+  invoke<void>("scan_replaygain", { paths, albumIds });
+
+export const cancelReplaygainScan = () =>
+  invoke<boolean>("cancel_replaygain_scan");
+
+export const scanDynamicRange = (
+  paths?: string[],
+  albumIds?: number[],
+  writeTags?: boolean,
+) => invoke<void>("scan_dynamic_range", { paths, albumIds, writeTags });
+
+export const cancelDynamicRangeScan = () =>
+  invoke<boolean>("cancel_dynamic_range_scan");
+
+export const scanTranscodes = (paths?: string[]) =>
+  invoke<void>("scan_transcodes", { paths });
+
+export const cancelTranscodeScan = () =>
+  invoke<boolean>("cancel_transcode_scan");
+
+export const scanIntegrity = (paths?: string[], full?: boolean) =>
+  invoke<void>("scan_integrity", { paths, full });
+
+export const cancelIntegrityScan = () =>
+  invoke<boolean>("cancel_integrity_scan");
+
+export const getIntegrityProblems = (limit?: number, offset?: number) =>
+  invoke<IntegrityProblem[]>("get_integrity_problems", { limit, offset });
+
+export const getIntegrityStatus = () =>
+  invoke<IntegrityStatus>("get_integrity_status");
+
+export const verifyAlbumAccurateRip = (albumId: number) =>
+  invoke<AccurateRipDisc[]>("verify_album_accuraterip", { albumId });
+
+export const getCdDrives = () => invoke<CdDriveInfo[]>("get_cd_drives");
+
+export const readCd = (drive: string) =>
+  invoke<CdDisc>("read_cd", { drive });
+
+// Runs in the background: listen for library://rip-progress (in sectors) /
+// rip-complete (a RipSummary).
+export const ripCd = (request: RipRequest) =>
+  invoke<void>("rip_cd", { request });
+
+export const cancelRip = () => invoke<boolean>("cancel_rip");
+
+// Placeholders: %title% %artist% %album% %albumartist% %year% %genre%
+// %track% %disc% %composer%. Omit trackIds for the whole library.
+export const previewOrganize = (
+  pattern: string,
+  trackIds?: number[],
+  destination?: string,
+  collision?: CollisionPolicy,
+) =>
+  invoke<PlannedMove[]>("preview_organize", {
+    pattern,
+    trackIds,
+    destination,
+    collision,
+  });
+
+// Runs in the background: listen for library://organize-progress / organize-complete.
+export const organizeFiles = (
+  pattern: string,
+  trackIds?: number[],
+  destination?: string,
+  collision?: CollisionPolicy,
+) =>
+  invoke<void>("organize_files", { pattern, trackIds, destination, collision });
+
+export const cancelOrganize = () => invoke<boolean>("cancel_organize");
+
+export const getScanExclusions = () =>
+  invoke<ExcludeRules>("get_scan_exclusions");
+
+export const setScanExclusions = (rules: ExcludeRules) =>
+  invoke<void>("set_scan_exclusions", { rules });
+
+// List commands return everything unless given a limit
+export const getLibraryTracks = (limit?: number, offset?: number) =>
+  invoke<LibraryTrack[]>("get_library_tracks", { limit, offset });
+
+// Results arrive as "library://list-chunk" events tagged with streamId
+export const streamLibraryList = (
+  list: LibraryList,
+  streamId: number,
+  chunkSize?: number,
+) => invoke<void>("stream_library_list", { list, streamId, chunkSize });
+
+export const browseFolder = (path: string) =>
+  invoke<FolderListing>("browse_folder", { path });
+
+export const searchLibrary = (
+  query: string,
+  limit?: number,
+  fuzzy?: boolean,
+) => invoke<SearchHit[]>("search_library", { query, limit, fuzzy });
+
+export const queryTracks = (
+  filter?: TrackFilter,
+  sort?: TrackSort,
+  limit?: number,
+  offset?: number,
+) => invoke<TrackPage>("query_tracks", { filter, sort, limit, offset });
+
+export const listAlbums = (limit?: number, offset?: number) =>
+  invoke<LibraryAlbum[]>("list_albums", { limit, offset });
+
+export const getAlbum = (id: number) =>
+  invoke<AlbumDetail>("get_album", { id });
+
+export const getAlbumsMissingArt = () =>
+  invoke<LibraryAlbum[]>("get_albums_missing_art");
+
+export const getLibraryArtists = (
+  albumArtists?: boolean,
+  limit?: number,
+  offset?: number,
+) => invoke<LibraryArtist[]>("get_library_artists", { albumArtists, limit, offset });
+
+export const getArtist = (id: number) =>
+  invoke<ArtistDetail>("get_artist", { id });
+
+export const listComposers = (limit?: number, offset?: number) =>
+  invoke<LibraryComposer[]>("list_composers", { limit, offset });
+
+export const getComposerWorks = (name: string) =>
+  invoke<ClassicalWork[]>("get_composer_works", { name });
+
+export const listGenres = (limit?: number, offset?: number) =>
+  invoke<LibraryGenre[]>("list_genres", { limit, offset });
+
+export const getGenre = (id: number) =>
+  invoke<GenreDetail>("get_genre", { id });
+
+export const getGenreTracks = (id: number, limit?: number, offset?: number) =>
+  invoke<LibraryTrack[]>("get_genre_tracks", { id, limit, offset });
+
+// rating: 1–5 stars, 0 clears. writeTags defaults to true.
+export const setRating = (id: number, rating: number, writeTags?: boolean) =>
+  invoke<void>("set_rating", { id, rating, writeTags });
+
+export const setRatings = (
+  ids: number[],
+  rating: number,
+  writeTags?: boolean,
+) => invoke<void>("set_ratings", { ids, rating, writeTags });
+
+export const getRecentlyAdded = (limit?: number, offset?: number) =>
+  invoke<LibraryAlbum[]>("get_recently_added", { limit, offset });
+
+export const getMostPlayed = (
+  period?: PlayPeriod,
+  limit?: number,
+  offset?: number,
+) => invoke<PlayedTrack[]>("get_most_played", { period, limit, offset });
+
+export const getNeverPlayed = (limit?: number, offset?: number) =>
+  invoke<TrackPage>("get_never_played", { limit, offset });
+
+export const getLibraryStats = () =>
+  invoke<LibraryStats>("get_library_stats");
+
+export const findDuplicates = (
+  toleranceSecs?: number,
+  limit?: number,
+  offset?: number,
+) => invoke<DuplicateGroup[]>("find_duplicates", { toleranceSecs, limit, offset });
+
+export const getSuspectedTranscodes = (
+  minConfidence?: number,
+  limit?: number,
+  offset?: number,
+) =>
+  invoke<LibraryTrack[]>("get_suspected_transcodes", {
+    minConfidence,
+    limit,
+    offset,
+  });
+
+export const exportLibraryBackup = (path: string) =>
+  invoke<void>("export_library_backup", { path });
+
+export const restoreLibraryBackup = (path: string, pathMappings?: PathMapping[]) =>
+  invoke<RestoreReport>("restore_library_backup", { path, pathMappings });
+
+export const importItunesLibrary = (path: string, pathMappings?: PathMapping[]) =>
+  invoke<RestoreReport>("import_itunes_library", { path, pathMappings });
+
 // ─── ReplayGain ───
 
 export const setReplaygainMode = (mode: ReplayGainMode) =>
@@ -39,21 +454,149 @@ export const setReplaygainMode = (mode: ReplayGainMode) =>
 export const setClippingPrevention = (enabled: boolean) =>
   invoke<void>("set_clipping_prevention", { enabled });
 
+// ─── Settings ───
+// Changes from anywhere arrive as settings://changed (the new Settings).
+
+export const getSettings = () =>
+  invoke<Settings>("get_settings");
+
+export const setSettings = (settings: Settings) =>
+  invoke<Settings>("set_settings", { settings });
+
+// ─── Errors ───
+
+// Reported before the UI was listening; later ones come as app://error
+export const getRecentErrors = () => invoke<AppError[]>("get_recent_errors");
+
+// ─── Logs ───
+
+export const getRecentLogs = (level: LogLevel, count: number) =>
+  invoke<LogEntry[]>("get_recent_logs", { level, count });
+
+export const setLogLevel = (level: LogLevel) =>
+  invoke<void>("set_log_level", { level });
+
+export const getLogDir = () =>
+  invoke<string | null>("get_log_dir");
+
+// ─── Buffering ───
+
+export const getBuffering = () => invoke<BufferingStatus>("get_buffering");
+
+export const setBufferMode = (mode: BufferMode) =>
+  invoke<BufferingStatus>("set_buffer_mode", { mode });
+
+// ─── DSP Plugins ───
+
+export const getDspPlugins = () => invoke<DspPluginList>("get_dsp_plugins");
+
+export const rescanDspPlugins = () =>
+  invoke<DspPluginList>("rescan_dsp_plugins");
+
+export const setDspChain = (chain: DspChainEntry[]) =>
+  invoke<Settings>("set_dsp_chain", { chain });
+
 // ─── Diagnostics ───
 
 export const getAudioDiagnostics = () =>
   invoke<AudioDiagnostics>("get_audio_diagnostics");
 
+// Null when nothing is loaded
+export const getSignalPath = () => invoke<SignalPath | null>("get_signal_path");
+
+// One point per second of playback over the last five minutes, oldest first
+export const getDiagnosticsHistory = () =>
+  invoke<DiagnosticsSample[]>("get_diagnostics_history");
+
+// Ballistics run between calls, so poll steadily (e.g. every animation frame)
+export const getLevels = () => invoke<AudioLevels>("get_levels");
+
+// "active" clears on each call, so poll steadily like getLevels
+export const getBitActivity = () => invoke<BitActivity>("get_bit_activity");
+
+// Loudness isn't polled: listen for audio://loudness (a LoudnessReading).
+
+export const getDropoutLog = () => invoke<DropoutLog>("get_dropout_log");
+
+export const getClippingReport = () =>
+  invoke<TrackClipping[]>("get_clipping_report");
+
+// Decodes the playing track, so takes a few seconds
+export const analyzeTruePeak = () =>
+  invoke<TruePeakAnalysis>("analyze_true_peak");
+
+// JSON bundle of devices, settings, diagnostics and recent logs for bug reports
+export const exportDiagnosticsReport = (path: string) =>
+  invoke<void>("export_diagnostics_report", { path });
+
+// Plays test sweeps (stopping playback) and times them back through an input
+// device wired or within earshot of the output
+export const measureRoundTripLatency = (inputDevice?: string) =>
+  invoke<LatencyMeasurement>("measure_round_trip_latency", { inputDevice });
+
 // ─── Null Test ───
 
 export const runNullTest = (path: string) =>
   invoke<NullTestResult>("run_null_test", { path });
 
+// Nulls the file against itself run through the current ReplayGain, volume
+// and limiter settings
+export const runDspNullTest = (path: string) =>
+  invoke<NullTestResult>("run_dsp_null_test", { path });
+
+// Plays the file (stopping current playback) and records the output device
+// via WASAPI loopback; needs volume 100% and ReplayGain off
+export const runLoopbackNullTest = (path: string, seconds?: number) =>
+  invoke<NullTestResult>("run_loopback_null_test", { path, seconds });
+
+// ─── Spectrogram ───
+
+export const generateSpectrogram = (path: string, options: SpectrogramOptions = {}) =>
+  invoke<Spectrogram>("generate_spectrogram", { path, options });
+
+// ─── Recording ───
+
+export const getRecordingStatus = () =>
+  invoke<RecordingStatus>("get_recording_status");
+
+// Emits "recorder://saved" with each RecordedFile finished
+export const startRecording = (options: RecordOptions) =>
+  invoke<RecordingStatus>("start_recording", { options });
+
+export const stopRecording = () =>
+  invoke<RecordingStatus>("stop_recording");
+
+// ─── Converter ───
+
+// Emits "convert://job" with a ConvertJob whenever one changes
+export const convertTracks = (trackIds: number[], options: ConvertOptions) =>
+  invoke<ConvertJob[]>("convert_tracks", { trackIds, options });
+
+export const getConvertJobs = () =>
+  invoke<ConvertJob[]>("get_convert_jobs");
+
+export const cancelConvertJob = (id: number) =>
+  invoke<boolean>("cancel_convert_job", { id });
+
+export const clearFinishedConvertJobs = () =>
+  invoke<ConvertJob[]>("clear_finished_convert_jobs");
+
+// ─── Snapcast ───
+
+export const getSnapcastStatus = () =>
+  invoke<SnapcastStatus>("get_snapcast_status");
+
+export const setSnapcastOutput = (settings: SnapcastSettings) =>
+  invoke<SnapcastStatus>("set_snapcast_output", { settings });
+
 // ─── Devices ───
 
 export const getAudioDevices = () =>
   invoke<AudioDeviceInfo[]>("get_audio_devices");
 
+export const getInputDevices = () =>
+  invoke<AudioDeviceInfo[]>("get_input_devices");
+
 // ─── Device Profiles ───
 
 export const getDeviceProfile = (deviceName: string) =>
@@ -73,9 +616,316 @@ export const deleteDeviceProfile = (deviceName: string) =>
 export const readFileMetadata = (path: string) =>
   invoke<TrackMetadata>("read_file_metadata", { path });
 
+// Returns the updated library track, or null for files outside the library
+export const writeMetadata = (path: string, fields: MetadataFields) =>
+  invoke<LibraryTrack | null>("write_metadata", { path, fields });
+
+export const writeReplaygainTags = (path: string, values: ReplayGainInfo) =>
+  invoke<void>("write_replaygain_tags", { path, values });
+
+export const getTagFields = (path: string) =>
+  invoke<TagFields>("get_tag_fields", { path });
+
+// An empty values array removes the field
+export const setTagField = (path: string, key: string, values: string[]) =>
+  invoke<void>("set_tag_field", { path, key, values });
+
+export const getTagWriteSettings = () =>
+  invoke<TagWriteSettings>("get_tag_write_settings");
+
+export const setTagWriteSettings = (settings: TagWriteSettings) =>
+  invoke<void>("set_tag_write_settings", { settings });
+
+export const getLegacyEncodedTracks = () =>
+  invoke<LibraryTrack[]>("get_legacy_encoded_tracks");
+
+// Nothing is written; pass the chosen encoding on to repairTagEncodings
+export const previewEncodingRepair = (path: string, encoding?: string) =>
+  invoke<EncodingRepair>("preview_encoding_repair", { path, encoding });
+
+export const repairTagEncodings = (files: EncodingChoice[]) =>
+  invoke<JobSummary>("repair_tag_encodings", { files });
+
+// apiKey: AcoustID client key. Pass a suggestion's fields to writeMetadata.
+export const identifyTrack = (path: string, apiKey: string) =>
+  invoke<TagSuggestion[]>("identify_track", { path, apiKey });
+
+export const searchCoverArt = (query: CoverArtQuery) =>
+  invoke<CoverCandidate[]>("search_cover_art", { query });
+
+// embed defaults to true, saveFolder (folder.jpg) to false
+export const applyCoverArt = (
+  albumId: number,
+  imageUrl: string,
+  embed?: boolean,
+  saveFolder?: boolean,
+) =>
+  invoke<JobSummary>("apply_cover_art", { albumId, imageUrl, embed, saveFolder });
+
+// Embeds into every track of the album, replacing the front cover
+export const setAlbumArt = (
+  albumId: number,
+  imagePath: string,
+  resize?: ArtResize,
+  saveFolder?: boolean,
+) => invoke<JobSummary>("set_album_art", { albumId, imagePath, resize, saveFolder });
+
+export const removeAlbumArt = (albumId: number) =>
+  invoke<JobSummary>("remove_album_art", { albumId });
+
+// Pictures over maxBytes (default 1 MB) are shrunk with resize, or removed without it
+export const shrinkAlbumArt = (albumId: number, maxBytes?: number, resize?: ArtResize) =>
+  invoke<JobSummary>("shrink_album_art", { albumId, maxBytes, resize });
+
+// Front cover as folder.jpg, other pictures named by type (back.jpg, booklet.png, …)
+export const extractAlbumArt = (albumId: number, overwrite?: boolean) =>
+  invoke<JobSummary>("extract_album_art", { albumId, overwrite });
+
 export const getAlbumArtBase64 = (path: string) =>
   invoke<string | null>("get_album_art_base64", { path });
 
+// art:// URLs for <img src>, loaded without going through IPC; 404 when
+// there's no art. convertFileSrc gives the scheme's form on this platform
+const artUrl = (path: string) => convertFileSrc("", "art") + path;
+
+// The album's thumbnail, from the cache
+export const albumArtUrl = (albumId: number) => artUrl(`album/${albumId}`);
+
+// A file's cover at full size: embedded, or a folder image next to it
+export const trackArtUrl = (path: string) =>
+  artUrl(`track?path=${encodeURIComponent(path)}`);
+
+// includeData adds each picture's data_url (off by default)
+export const getEmbeddedPictures = (path: string, includeData?: boolean) =>
+  invoke<EmbeddedPicture[]>("get_embedded_pictures", { path, includeData });
+
+export const getLyrics = (path: string) =>
+  invoke<string | null>("get_lyrics", { path });
+
+// While a file plays, listen for lyrics://line (a LyricsLineEvent) to follow along.
+export const getSyncedLyrics = (path: string) =>
+  invoke<LyricLine[] | null>("get_synced_lyrics", { path });
+
+// LRCLIB lookup (cached); save keeps what the file lacks as .lrc / embedded lyrics
+export const fetchLyrics = (path: string, save?: boolean) =>
+  invoke<OnlineLyrics | null>("fetch_lyrics", { path, save });
+
+// ─── ListenBrainz ───
+
+export const getListenBrainzStatus = () =>
+  invoke<ListenBrainzStatus>("get_listenbrainz_status");
+
+export const connectListenBrainz = (token: string) =>
+  invoke<ListenBrainzStatus>("connect_listenbrainz", { token });
+
+export const disconnectListenBrainz = () =>
+  invoke<void>("disconnect_listenbrainz");
+
+export const setListenBrainzEnabled = (enabled: boolean) =>
+  invoke<void>("set_listenbrainz_enabled", { enabled });
+
+// ─── Remote control server ───
+
+export const getRemoteServerStatus = () =>
+  invoke<RemoteServerStatus>("get_remote_server_status");
+
+export const setRemoteServer = (enabled: boolean, port: number) =>
+  invoke<RemoteServerStatus>("set_remote_server", { enabled, port });
+
+export const regenerateRemoteServerToken = () =>
+  invoke<RemoteServerStatus>("regenerate_remote_server_token");
+
+// ─── MQTT ───
+
+export const getMqttStatus = () =>
+  invoke<MqttStatus>("get_mqtt_status");
+
+export const setMqttBridge = (settings: MqttSettings) =>
+  invoke<MqttStatus>("set_mqtt_bridge", { settings });
+
+// ─── Scripts ───
+
+export const getScripts = () =>
+  invoke<ScriptsStatus>("get_scripts");
+
+// Loads in the background; call getScripts afterwards for the result.
+export const reloadScripts = () =>
+  invoke<void>("reload_scripts");
+
+// ─── DLNA media servers ───
+
+export const discoverDlnaServers = () =>
+  invoke<DlnaServer[]>("discover_dlna_servers");
+
+export const addDlnaServer = (location: string) =>
+  invoke<DlnaServer>("add_dlna_server", { location });
+
+export const browseDlnaServer = (
+  server: DlnaServer,
+  objectId?: string,
+  start?: number,
+  count?: number,
+) =>
+  invoke<DlnaBrowsePage>("browse_dlna_server", {
+    server,
+    objectId,
+    start,
+    count,
+  });
+
+// ─── Subsonic servers ───
+
+export const getSubsonicServers = () =>
+  invoke<SubsonicServer[]>("get_subsonic_servers");
+
+export const addSubsonicServer = (
+  url: string,
+  username: string,
+  password: string,
+  name?: string,
+) =>
+  invoke<SubsonicServer>("add_subsonic_server", {
+    name,
+    url,
+    username,
+    password,
+  });
+
+export const removeSubsonicServer = (serverId: string) =>
+  invoke<void>("remove_subsonic_server", { serverId });
+
+export const setSubsonicServerOptions = (
+  serverId: string,
+  maxBitRate: number | null,
+  scrobble: boolean,
+) =>
+  invoke<SubsonicServer>("set_subsonic_server_options", {
+    serverId,
+    maxBitRate,
+    scrobble,
+  });
+
+export const getSubsonicArtists = (serverId: string) =>
+  invoke<SubsonicArtist[]>("get_subsonic_artists", { serverId });
+
+export const getSubsonicArtistAlbums = (serverId: string, artistId: string) =>
+  invoke<SubsonicAlbum[]>("get_subsonic_artist_albums", { serverId, artistId });
+
+export const getSubsonicAlbumList = (
+  serverId: string,
+  list: SubsonicAlbumList,
+  offset?: number,
+  size?: number,
+) =>
+  invoke<SubsonicAlbum[]>("get_subsonic_album_list", {
+    serverId,
+    list,
+    offset,
+    size,
+  });
+
+export const getSubsonicAlbumSongs = (serverId: string, albumId: string) =>
+  invoke<SubsonicSong[]>("get_subsonic_album_songs", { serverId, albumId });
+
+export const searchSubsonic = (serverId: string, query: string, limit?: number) =>
+  invoke<SubsonicSearchResults>("search_subsonic", { serverId, query, limit });
+
+// ─── Jellyfin servers ───
+
+export const getJellyfinServers = () =>
+  invoke<JellyfinServer[]>("get_jellyfin_servers");
+
+export const addJellyfinServer = (
+  url: string,
+  apiKey: string,
+  userName?: string,
+) =>
+  invoke<JellyfinServer>("add_jellyfin_server", { url, apiKey, userName });
+
+export const removeJellyfinServer = (serverId: string) =>
+  invoke<void>("remove_jellyfin_server", { serverId });
+
+export const getJellyfinLibraries = (serverId: string) =>
+  invoke<JellyfinLibrary[]>("get_jellyfin_libraries", { serverId });
+
+export const getJellyfinArtists = (
+  serverId: string,
+  libraryId?: string,
+  start?: number,
+  limit?: number,
+) =>
+  invoke<JellyfinPage<JellyfinArtist>>("get_jellyfin_artists", {
+    serverId,
+    libraryId,
+    start,
+    limit,
+  });
+
+export const getJellyfinAlbums = (
+  serverId: string,
+  libraryId?: string,
+  start?: number,
+  limit?: number,
+) =>
+  invoke<JellyfinPage<JellyfinAlbum>>("get_jellyfin_albums", {
+    serverId,
+    libraryId,
+    start,
+    limit,
+  });
+
+export const getJellyfinArtistAlbums = (serverId: string, artistId: string) =>
+  invoke<JellyfinAlbum[]>("get_jellyfin_artist_albums", { serverId, artistId });
+
+export const getJellyfinAlbumTracks = (serverId: string, albumId: string) =>
+  invoke<JellyfinTrack[]>("get_jellyfin_album_tracks", { serverId, albumId });
+
+export const searchJellyfin = (serverId: string, query: string, limit?: number) =>
+  invoke<JellyfinSearchResults>("search_jellyfin", { serverId, query, limit });
+
+// ─── Internet radio ───
+// While a station plays, listen for radio://title (a RadioNowPlaying) for its titles.
+
+export const getRadioStations = () =>
+  invoke<RadioStation[]>("get_radio_stations");
+
+export const addRadioStation = (name: string, url: string, group?: string) =>
+  invoke<RadioStation>("add_radio_station", { name, url, group });
+
+export const importRadioPlaylist = (path: string, group?: string) =>
+  invoke<RadioStation[]>("import_radio_playlist", { path, group });
+
+export const updateRadioStation = (
+  stationId: string,
+  name: string,
+  group: string | null,
+  favorite: boolean,
+) =>
+  invoke<RadioStation>("update_radio_station", {
+    stationId,
+    name,
+    group,
+    favorite,
+  });
+
+export const moveRadioStation = (stationId: string, index: number) =>
+  invoke<void>("move_radio_station", { stationId, index });
+
+export const removeRadioStation = (stationId: string) =>
+  invoke<void>("remove_radio_station", { stationId });
+
+export const playRadioStation = (stationId: string) =>
+  invoke<void>("play_radio_station", { stationId });
+
+export const addRadioStationsToQueue = (stationIds: string[]) =>
+  invoke<EnqueueReport>("add_radio_stations_to_queue", { stationIds });
+
+export const getRadioHistory = (stationId: string) =>
+  invoke<RadioTitle[]>("get_radio_history", { stationId });
+
+export const clearRadioHistory = (stationId: string) =>
+  invoke<void>("clear_radio_history", { stationId });
+
 // ─── Dialogs ───
 
 export const openFilesDialog = () =>
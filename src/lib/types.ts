@@ -9,7 +9,20 @@ export interface PlaybackState {
   bit_depth: number | null;
   channels: number;
   current_file: string | null;
-  resampled: boolean;
+  start_secs: number; // played part of the file (cue-sheet tracks)
+  end_secs: number | null;
+  resampled: boolean; // the device runs at another rate than the file
+  device_format: DeviceFormat | null; // what the OS runs the device at
+  codec: string | null; // e.g. "flac"
+  output_device: string | null;
+}
+
+// WASAPI shared-mode mix format / CoreAudio stream format; null elsewhere
+export interface DeviceFormat {
+  sample_rate: number;
+  channels: number;
+  sample_format: string; // e.g. "f32"
+  bits_per_sample: number;
 }
 
 export interface AudioDiagnostics {
@@ -22,6 +35,144 @@ export interface AudioDiagnostics {
   output_channels: number;
   is_bit_perfect: boolean;
   shared_mode: boolean;
+  // Over the last second of playback; null when stopped
+  decoder_cpu_pct: number | null; // share of one core
+  callback_min_us: number | null;
+  callback_avg_us: number | null;
+  callback_max_us: number | null;
+  resident_memory_bytes: number | null;
+  // Current track; fades aren't counted
+  clipped_samples: number; // at or over full scale after volume
+  limited_samples: number; // cut by the hard limiter
+}
+
+// dBFS, -96 for silence. Peaks fall back at ~12 dB/s, RMS is averaged over
+// ~300 ms, and the hold marker stays for 2 s.
+export interface ChannelLevel {
+  peak_db: number;
+  rms_db: number;
+  peak_hold_db: number;
+  clipped: boolean;
+}
+
+export interface AudioLevels {
+  channels: ChannelLevel[];
+}
+
+export interface LatencyMeasurement {
+  output_device: string;
+  input_device: string;
+  output_sample_rate: number;
+  input_sample_rate: number;
+  round_trip_ms: number; // median of the sweeps found
+  sweeps_ms: (number | null)[]; // null where a sweep wasn't heard
+}
+
+export interface DropoutEvent {
+  timestamp_ms: number; // Unix time
+  file: string | null;
+  position_ms: number;
+  // Ring buffer fill (0–100) at the last history sample before the dropout
+  buffer_fill_pct: number;
+  requested_samples: number;
+  delivered_samples: number;
+}
+
+export interface BufferFillSample {
+  timestamp_ms: number;
+  fill_pct: number;
+}
+
+export interface SignalPathStage {
+  stage:
+    | "decoder"
+    | "replaygain"
+    | "plugins"
+    | "volume"
+    | "limiter"
+    | "output"
+    | "device";
+  summary: string; // e.g. "volume -6.0 dB"
+  processing: boolean; // changes the samples
+}
+
+export interface SignalPath {
+  stages: SignalPathStage[]; // source first
+  description: string; // summaries joined with arrows
+  bit_perfect: boolean;
+}
+
+// Bits of the output as 32-bit PCM words, most significant first (32 each)
+export interface ChannelBits {
+  active: boolean[]; // since the previous call
+  track_active: boolean[]; // since the track started
+  effective_bits: number; // 16 for 16-bit audio played untouched; 0 silent
+  below_word: boolean; // finer than 32-bit: the output was processed
+}
+
+export interface BitActivity {
+  channels: ChannelBits[];
+}
+
+export interface DiagnosticsSample {
+  timestamp_ms: number;
+  buffer_fill_pct: number; // lowest during the second
+  latency_ms: number;
+  callback_load_pct: number; // callback time / audio time, averaged
+  callback_peak_pct: number; // slowest callback
+  decoder_cpu_pct: number | null; // share of one core
+  dropouts: number; // during the second
+}
+
+export interface TrackClipping {
+  file: string;
+  started_at_ms: number;
+  samples: number; // over all channels
+  clipped_samples: number;
+  limited_samples: number; // always 0 in bit-perfect mode
+  current: boolean;
+}
+
+export interface TruePeakAnalysis {
+  file: string;
+  true_peak_dbtp: number;
+  channel_true_peaks_dbtp: number[];
+  sample_peak_dbfs: number;
+  over_zero: boolean;
+  // Null when ReplayGain is off or the file has no gain tag
+  replaygain_gain_db: number | null;
+  true_peak_after_gain_dbtp: number | null;
+  warning: string | null;
+}
+
+export interface DropoutLog {
+  events: DropoutEvent[]; // most recent last
+  buffer_history: BufferFillSample[]; // last minute of playback, 10 per second
+}
+
+// Payload of media://control (Windows, macOS): a media key, the OS
+// now-playing controls or a Bluetooth remote acted on playback, already
+// applied by the backend.
+export type MediaAction =
+  | "play"
+  | "pause"
+  | "stop"
+  | "next"
+  | "previous"
+  | "seek";
+
+// Payload of deep-link://open: a masukii://album/<id> or
+// masukii://track/<id> link asked to show it in the library.
+export type DeepLinkDestination =
+  | { kind: "album"; id: number }
+  | { kind: "track"; id: number; album_id: number | null };
+
+// Payload of audio://loudness, sent every 100 ms while playing.
+// Null while too little has been measured or during silence.
+export interface LoudnessReading {
+  momentary_lufs: number | null;
+  short_term_lufs: number | null;
+  integrated_lufs: number | null;
 }
 
 export interface NullTestResult {
@@ -30,9 +181,107 @@ export interface NullTestResult {
   diff_samples: number;
   max_diff: number;
   rms_diff: number;
+  // max_diff / rms_diff in dBFS; null when nothing differs
+  max_diff_dbfs: number | null;
+  rms_diff_dbfs: number | null;
   summary: string;
 }
 
+// Omitted fields use the defaults: 1600×512, 4096-point FFT, -120 dBFS floor
+export interface SpectrogramOptions {
+  width?: number;
+  height?: number;
+  fft_size?: number; // power of two, 256–32768
+  floor_db?: number;
+  log_frequency?: boolean;
+}
+
+export interface Spectrogram {
+  image_path: string;
+  width: number;
+  height: number;
+  sample_rate: number;
+  duration_secs: number;
+  // Frequencies at the bottom and top edges
+  min_freq_hz: number;
+  max_freq_hz: number;
+  floor_db: number;
+  log_frequency: boolean;
+}
+
+export interface RecordOptions {
+  folder: string;
+  format: "wav" | "flac";
+  bit_depth?: 16 | 24; // default 24
+  // A new file for every track and every radio title
+  split_on_track_change?: boolean;
+}
+
+export interface RecordedFile {
+  path: string;
+  duration_secs: number;
+  size_bytes: number;
+}
+
+export interface RecordingStatus {
+  recording: boolean;
+  current_file: string | null;
+  current_secs: number;
+  saved: RecordedFile[];
+  // Why recording stopped on its own
+  error: string | null;
+}
+
+export type ConvertFormat = "wav" | "flac" | "alac" | "opus" | "mp3";
+
+// ALAC, Opus and MP3 need ffmpeg on the PATH
+export interface ConvertOptions {
+  format: ConvertFormat;
+  destination: string;
+  pattern?: string; // default "%albumartist%/%album%/%track% - %title%"
+  collision?: CollisionPolicy; // default "skip"
+  bit_depth?: 16 | 24; // lossless formats; default the source's
+  bitrate_kbps?: number; // Opus 160, MP3 320 by default
+  keep_art?: boolean; // default true
+  art_resize?: ArtResize;
+}
+
+export type ConvertJobStatus =
+  | "queued"
+  | "running"
+  | "done"
+  | "failed"
+  | "cancelled"
+  | "skipped";
+
+export interface ConvertJob {
+  id: number;
+  track_id: number;
+  source: string;
+  destination: string;
+  format: ConvertFormat;
+  status: ConvertJobStatus;
+  progress: number; // 0–1
+  error: string | null;
+}
+
+// Streams to a tcp://…?mode=server source, or the server's pipe
+export interface SnapcastSettings {
+  enabled: boolean;
+  transport: "tcp" | "pipe";
+  host: string; // default "127.0.0.1"
+  port: number; // default 4953
+  pipe_path: string; // default "/tmp/snapfifo"
+  sample_rate: number; // the server's sampleformat rate, default 48000
+}
+
+export interface SnapcastStatus {
+  settings: SnapcastSettings;
+  connected: boolean;
+  // Why the last attempt to connect or send failed
+  error: string | null;
+}
+
 export interface AudioDeviceInfo {
   name: string;
   is_default: boolean;
@@ -40,6 +289,14 @@ export interface AudioDeviceInfo {
 
 export type ReplayGainMode = "Off" | "Track" | "Album";
 
+// Gains in dB, peaks linear (1.0 = full scale). null removes the tag.
+export interface ReplayGainInfo {
+  track_gain_db: number | null;
+  track_peak: number | null;
+  album_gain_db: number | null;
+  album_peak: number | null;
+}
+
 export interface DeviceProfile {
   device_name: string;
   exclusive_mode: boolean;
@@ -66,6 +323,1092 @@ export interface TrackMetadata {
   file_name: string;
   format: string;
   has_album_art: boolean;
+  composer: string | null;
+  work: string | null;
+  movement: string | null;
+  movement_number: number | null;
+  compilation: boolean;
+  // Sort tags, e.g. "Beatles, The"
+  artist_sort: string | null;
+  album_sort: string | null;
+  album_artist_sort: string | null;
+  // Split multi-value tags; artists has the main artist first, then guests
+  artists: string[];
+  genres: string[];
+  composers: string[];
+  rating: number | null;
+  chapters: Chapter[];
+  // Cue-sheet tracks: file_path is "Album.cue#03", the audio is this segment
+  // of source_path
+  source_path: string | null;
+  start_secs: number;
+  end_secs: number | null;
+  // Tagged encoder, else the one named in the stream (e.g. LAME header)
+  encoder: string | null;
+  bitrate_kbps: number | null;
+  bitrate_mode: "CBR" | "VBR" | "ABR" | null;
+  // e.g. "MPEG-1 Layer III", "AAC-LC", "HE-AACv2"
+  codec_profile: string | null;
+  replaygain: ReplayGainInfo;
+  file_size: number;
+}
+
+// Seconds from the start of the file
+export interface Chapter {
+  title: string;
+  start_secs: number;
+  end_secs: number;
+}
+
+// Tag edits: omitted fields are kept, "" or 0 removes the field
+export interface MetadataFields {
+  title?: string;
+  artist?: string;
+  album?: string;
+  album_artist?: string;
+  year?: number;
+  genre?: string;
+  track_number?: number;
+  disc_number?: number;
+}
+
+// Raw fields of a file's primary tag, named as the format names them
+// ("TPE1", "ARTIST", "©ART", a TXXX description, ...)
+export interface TagFields {
+  tag_type: string;
+  fields: TagField[];
+}
+
+export interface TagField {
+  key: string;
+  values: string[];
+}
+
+// V24 upgrades ID3v2.3 tags when writing; V23 keeps/writes v2.3 for old
+// players. The strip flags remove duplicate ID3v1/APEv2 tags on write.
+export interface TagWriteSettings {
+  id3v2_version: "V24" | "V23";
+  strip_id3v1: boolean;
+  strip_ape: boolean;
+}
+
+// Codepages are WHATWG labels: "windows-1251", "shift_jis", "gbk", ...
+// encoding null means detect it
+export interface EncodingChoice {
+  path: string;
+  encoding: string | null;
+}
+
+export interface EncodingRepair {
+  // Codepage used: the one asked for, else the detected one
+  encoding: string | null;
+  detected: string | null;
+  // Fields that change; empty when there is nothing to repair
+  fields: RepairedField[];
+}
+
+export interface RepairedField {
+  // "ID3v1" or "ID3v2"
+  tag_type: string;
+  key: string;
+  original: string;
+  repaired: string;
+}
+
+export interface TagSuggestion {
+  // Fingerprint match, 0–1
+  score: number;
+  // MusicBrainz ids
+  recording_id: string;
+  release_id: string | null;
+  fields: MetadataFields;
+}
+
+// Cover Art Archive lookup: ids win over artist/album text
+export interface CoverArtQuery {
+  release_id?: string | null;
+  release_group_id?: string | null;
+  artist?: string | null;
+  album?: string | null;
+}
+
+export interface CoverCandidate {
+  release_id: string | null;
+  // Release details, for text searches
+  title: string | null;
+  artist: string | null;
+  date: string | null;
+  country: string | null;
+  image_url: string;
+  thumbnail_url: string | null;
+  front: boolean;
+  // "Front", "Back", "Booklet", …
+  types: string[];
+}
+
+// Scale artwork down to max_dimension px and/or recompress it as JPEG
+export interface ArtResize {
+  max_dimension?: number | null;
+  jpeg_quality?: number | null;
+}
+
+export interface EmbeddedPicture {
+  // "front", "back", "booklet", "disc", "artist", ... or "other"
+  picture_type: string;
+  mime_type: string | null;
+  description: string | null;
+  // null when the image format can't be decoded
+  width: number | null;
+  height: number | null;
+  size_bytes: number;
+  data_url: string | null;
+}
+
+export interface LyricLine {
+  time_ms: number;
+  text: string;
+}
+
+// Payload of lyrics://line, sent when the playing file's current line changes
+export interface LyricsLineEvent {
+  path: string;
+  // null before the first line
+  index: number | null;
+  time_ms: number | null;
+  text: string | null;
+}
+
+// Lyrics found on LRCLIB; synced is LRC text
+export interface OnlineLyrics {
+  plain: string | null;
+  synced: string | null;
+  instrumental: boolean;
+}
+
+export interface QueueEntry {
+  id: number;
+  path: string;
+  start_secs: number;
+  end_secs: number | null;
+  title: string | null;
+}
+
+// A media server stream to queue by URL
+export interface QueueStream {
+  url: string;
+  title: string | null;
+}
+
+export type DedupMode = "off" | "ignore" | "move_to_end";
+
+export interface QueueSnapshot {
+  entries: QueueEntry[];
+  current_index: number | null;
+  shuffle: boolean;
+  repeat: RepeatMode;
+  dedup: DedupMode;
+}
+
+export interface EnqueueReport {
+  added: QueueEntry[];
+  moved: QueueEntry[];
+  skipped: string[];
+  errors: string[];
+}
+
+export interface HistoryEntry {
+  entry_id: number;
+  path: string;
+  played_at: number;
+}
+
+export type PlaylistSortKey = "Artist" | "Album" | "Title" | "Path";
+
+export interface Playlist {
+  id: number;
+  name: string;
+  tracks: string[];
+  sort_key: PlaylistSortKey | null;
+  sort_descending: boolean;
+}
+
+export interface PlaylistSummary {
+  id: number;
+  name: string;
+  track_count: number;
+  sort_key: PlaylistSortKey | null;
+}
+
+export interface BookmarkRules {
+  folders: string[];
+  genres: string[];
+}
+
+export interface Bookmark {
+  path: string;
+  position_secs: number;
+  updated_at: number;
+}
+
+export interface LibraryTrack {
+  id: number;
+  path: string;
+  title: string | null;
+  artist: string | null;
+  album: string | null;
+  album_artist: string | null;
+  year: number | null;
+  genre: string | null;
+  track_number: number | null;
+  disc_number: number | null;
+  duration_secs: number;
+  sample_rate: number | null;
+  bit_depth: number | null;
+  channels: number | null;
+  format: string;
+  has_album_art: boolean;
+  file_size: number;
+  modified_at: number;
+  artist_id: number | null;
+  album_id: number | null;
+  rating: number | null;
+  composer: string | null;
+  work: string | null;
+  movement: string | null;
+  movement_number: number | null;
+  compilation: boolean;
+  added_at: number | null;
+  // Measured DR, unrounded; null until analysed
+  dynamic_range: number | null;
+  // Highest frequency with content, from the transcode check
+  spectral_cutoff_hz: number | null;
+  // Likelihood (0–1) of a lossy source; null until checked
+  transcode_confidence: number | null;
+  artist_sort: string | null;
+  album_sort: string | null;
+  album_artist_sort: string | null;
+  // Cue-sheet tracks: path is "Album.cue#03", the audio is this segment of
+  // source_path
+  source_path: string | null;
+  start_secs: number | null;
+  end_secs: number | null;
+}
+
+export interface TrackFilter {
+  year_min?: number | null;
+  year_max?: number | null;
+  genres?: string[];
+  genre_id?: number | null;
+  formats?: string[];
+  sample_rate_min?: number | null;
+  sample_rate_max?: number | null;
+  bit_depth_min?: number | null;
+  bit_depth_max?: number | null;
+  duration_min_secs?: number | null;
+  duration_max_secs?: number | null;
+  rating_min?: number | null;
+  rating_max?: number | null;
+  unrated?: boolean;
+  never_played?: boolean;
+  artist_id?: number | null;
+  album_id?: number | null;
+}
+
+export type TrackSortField =
+  | "Title"
+  | "Artist"
+  | "Album"
+  | "Year"
+  | "Genre"
+  | "Format"
+  | "Duration"
+  | "SampleRate"
+  | "BitDepth"
+  | "Rating"
+  | "FileSize"
+  | "Modified"
+  | "Added"
+  | "Path";
+
+export interface TrackSort {
+  field: TrackSortField;
+  descending?: boolean;
+}
+
+export interface TrackPage {
+  tracks: LibraryTrack[];
+  total: number;
+}
+
+export interface SearchHit {
+  track: LibraryTrack;
+  score: number;
+}
+
+export interface LibraryAlbum {
+  id: number;
+  title: string;
+  artist: string | null;
+  year: number | null;
+  track_count: number;
+  disc_count: number;
+  duration_secs: number;
+  // Filed under "Various Artists"
+  compilation: boolean;
+  added_at: number | null;
+  // Average DR of its analysed tracks
+  dynamic_range: number | null;
+  // Cached thumbnail path, null until generated or if the album has no art;
+  // albumArtUrl(id) serves it to an <img>
+  thumbnail: string | null;
+}
+
+export interface AlbumDisc {
+  number: number | null;
+  tracks: LibraryTrack[];
+}
+
+export interface AlbumDetail {
+  album: LibraryAlbum;
+  discs: AlbumDisc[];
+}
+
+export interface LibraryArtist {
+  id: number;
+  name: string;
+  // From ARTISTSORT / ALBUMARTISTSORT, used for ordering
+  sort_name: string | null;
+  // Albums as album artist
+  album_count: number;
+  // Tracks as main artist
+  track_count: number;
+  // Tracks as a "feat." guest
+  featured_count: number;
+}
+
+export interface LibraryComposer {
+  name: string;
+  work_count: number;
+  track_count: number;
+}
+
+export interface ClassicalWork {
+  title: string;
+  composer: string;
+  album: string | null;
+  album_id: number | null;
+  duration_secs: number;
+  movements: LibraryTrack[];
+}
+
+export interface LibraryGenre {
+  id: number;
+  name: string;
+  album_count: number;
+  track_count: number;
+}
+
+export interface GenreDetail {
+  genre: LibraryGenre;
+  albums: LibraryAlbum[];
+}
+
+export type PlayPeriod = "Week" | "Month" | "Year" | "AllTime";
+
+export interface PlayedTrack {
+  track: LibraryTrack;
+  play_count: number;
+  last_played_at: number;
+}
+
+export interface ArtistDetail {
+  artist: LibraryArtist;
+  albums: LibraryAlbum[];
+  appears_on: LibraryAlbum[];
+  featured_on: LibraryTrack[];
+}
+
+export interface LibraryStats {
+  track_count: number;
+  album_count: number;
+  artist_count: number;
+  genre_count: number;
+  total_duration_secs: number;
+  total_size_bytes: number;
+  lossless_track_count: number;
+  // More than 16 bits or 48 kHz
+  hires_track_count: number;
+  average_bitrate_kbps: number;
+  formats: FormatStats[];
+}
+
+export interface FormatStats {
+  format: string;
+  track_count: number;
+  total_duration_secs: number;
+  total_size_bytes: number;
+  average_bitrate_kbps: number;
+}
+
+export interface DuplicateCandidate {
+  track: LibraryTrack;
+  lossless: boolean;
+  bitrate_kbps: number;
+  // Highest quality copy in its group
+  best: boolean;
+}
+
+export interface DuplicateGroup {
+  artist: string;
+  title: string;
+  // Best copy first
+  candidates: DuplicateCandidate[];
+}
+
+export interface FolderListing {
+  path: string;
+  // null at a filesystem root
+  parent: string | null;
+  folders: FolderEntry[];
+  files: FolderFile[];
+}
+
+export interface FolderEntry {
+  name: string;
+  path: string;
+  // Indexed tracks anywhere below the folder
+  track_count: number;
+}
+
+export interface FolderFile {
+  name: string;
+  path: string;
+  file_size: number;
+  // null if the file isn't in the library
+  track: LibraryTrack | null;
+}
+
+// Replaces the `from` folder prefix of backup paths with `to`
+export interface PathMapping {
+  from: string;
+  to: string;
+}
+
+export interface RestoreReport {
+  roots_added: number;
+  roots_skipped: string[];
+  tracks_restored: number;
+  // Applied when a scan finds the files
+  tracks_pending: number;
+  playlists: number;
+  bookmarks: number;
+}
+
+export interface LibraryRoot {
+  id: number;
+  path: string;
+  enabled: boolean;
+  online: boolean;
+  track_count: number;
+  last_scanned_at: number | null;
+}
+
+export interface ExcludeRules {
+  patterns: string[];
+  skip_hidden: boolean;
+  extensions: string[];
+  min_duration_secs: number;
+}
+
+export interface ScanSummary {
+  added: number;
+  updated: number;
+  removed: number;
+  unchanged: number;
+  excluded: number;
+  errors: string[];
+  cancelled: boolean;
+  // Nothing was written; `changes` lists what a scan would do
+  dry_run: boolean;
+  // Filled by dry runs only
+  changes: ScanChange[];
+}
+
+export type ChangeKind = "add" | "update" | "remove";
+
+export type ChangeReason =
+  | "new_file"
+  | "size_changed"
+  | "modified"
+  | "missing"
+  // Still on disk but matches an exclude rule
+  | "excluded"
+  | "too_short";
+
+export interface ScanChange {
+  path: string;
+  kind: ChangeKind;
+  reason: ChangeReason;
+}
+
+export type ScanPhase = "Discovering" | "Indexing" | "Artwork";
+
+export interface ScanProgress {
+  phase: ScanPhase;
+  files_found: number;
+  files_processed: number;
+  current_folder: string;
+  tracks_per_sec: number;
+}
+
+export interface Waveform {
+  // Lowest sample per bucket, -1–0
+  min: number[];
+  // Highest sample per bucket, 0–1
+  max: number[];
+}
+
+// Progress of a per-file library job (waveforms, ReplayGain analysis)
+export interface JobProgress {
+  total: number;
+  processed: number;
+  current_file: string;
+}
+
+export interface JobSummary {
+  processed: number;
+  errors: string[];
+  cancelled: boolean;
+}
+
+export interface IntegrityProblem {
+  track: LibraryTrack;
+  error: string;
+  checked_at: number;
+  // The file has changed since it was checked
+  stale: boolean;
+}
+
+export interface IntegrityStatus {
+  checked: number;
+  unchecked: number;
+  checksum_verified: number;
+  problems: number;
+  oldest_check: number | null;
+}
+
+export interface AccurateRipTrack {
+  path: string;
+  title: string | null;
+  number: number;
+  crc_v1: string;
+  crc_v2: string;
+  // Rips that agreed; null if none did
+  confidence: number | null;
+  matched: "v1" | "v2" | null;
+}
+
+export interface AccurateRipDisc {
+  disc_number: number | null;
+  disc_id: string;
+  found: boolean;
+  tracks: AccurateRipTrack[];
+  accurate: boolean;
+}
+
+export interface CdDriveInfo {
+  path: string;
+  name: string;
+}
+
+export interface TocTrack {
+  number: number;
+  // First sector
+  start: number;
+  audio: boolean;
+}
+
+export interface Toc {
+  tracks: TocTrack[];
+  lead_out: number;
+}
+
+export interface DiscRelease {
+  // MusicBrainz release id
+  id: string;
+  title: string;
+  artist: string;
+  year: number | null;
+  disc_number: number | null;
+  tracks: { title: string; artist: string }[];
+}
+
+export interface CdDisc {
+  toc: Toc;
+  releases: DiscRelease[];
+}
+
+export interface RipRequest {
+  drive: string;
+  // Drive read offset in samples, as AccurateRip lists it
+  read_offset?: number;
+  release_id?: string;
+  // Defaults to the first library folder
+  destination?: string;
+}
+
+export interface RippedTrack {
+  number: number;
+  path: string;
+  title: string;
+  rereads: number;
+  suspicious_sectors: number[];
+}
+
+export interface RipSummary {
+  folder: string;
+  release_id: string | null;
+  tracks: RippedTrack[];
+  // null for discs with a data track
+  accuraterip: AccurateRipDisc | null;
+  c2_supported: boolean;
+  imported: boolean;
+  cancelled: boolean;
+}
+
+// A library list to page or stream, see streamLibraryList
+export type LibraryList =
+  | { kind: "tracks" }
+  | { kind: "query"; filter?: TrackFilter; sort?: TrackSort }
+  | { kind: "albums" }
+  | { kind: "artists"; album_artists?: boolean }
+  | { kind: "composers" }
+  | { kind: "genres" }
+  | { kind: "genre_tracks"; id: number }
+  | { kind: "duplicates"; tolerance_secs?: number }
+  | { kind: "suspected_transcodes"; min_confidence?: number }
+  | { kind: "integrity_problems" };
+
+export interface ListChunk {
+  stream_id: number;
+  // Position of the first item in the whole list
+  offset: number;
+  items:
+    | LibraryTrack[]
+    | LibraryAlbum[]
+    | LibraryArtist[]
+    | LibraryComposer[]
+    | LibraryGenre[]
+    | DuplicateGroup[]
+    | IntegrityProblem[];
+  // Last chunk of the stream
+  done: boolean;
+}
+
+export interface ListStreamError {
+  stream_id: number;
+  error: string;
+}
+
+export type CollisionPolicy = "skip" | "number";
+
+export type MoveStatus = "move" | "unchanged" | "skipped";
+
+export interface PlannedMove {
+  track_id: number;
+  from: string;
+  to: string;
+  status: MoveStatus;
+  // Why a file is skipped
+  reason: string | null;
+}
+
+export interface ListenBrainzStatus {
+  enabled: boolean;
+  // A token is stored
+  connected: boolean;
+  username: string | null;
+  // Listens waiting to be sent
+  queued: number;
+  // Why the last submission failed, until one succeeds
+  last_error: string | null;
+}
+
+export interface RemoteServerStatus {
+  enabled: boolean;
+  port: number;
+  // Needed by every request, as "Authorization: Bearer <token>" or ?token=
+  token: string;
+  // Listening right now
+  running: boolean;
+  // Address to reach the server at from the LAN, when running
+  url: string | null;
+  // Why the server couldn't start, e.g. the port is taken
+  error: string | null;
+}
+
+// Publishes <base>/availability, state, track, volume and position
+// (retained); takes <base>/command and <base>/volume/set
+export interface MqttSettings {
+  enabled: boolean;
+  host: string;
+  port: number; // default 1883
+  username: string; // empty for no authentication
+  password: string;
+  client_id: string;
+  base_topic: string; // default "masukii"
+  accept_commands: boolean;
+}
+
+export interface MqttStatus {
+  settings: MqttSettings;
+  connected: boolean;
+  // Why the last connection failed or dropped
+  error: string | null;
+}
+
+// A .rhai file in the scripts folder
+export interface ScriptStatus {
+  name: string;
+  hooks: string[]; // e.g. "on_track_change", "on_stop", "on_rating_change"
+  // Why it failed to load, or the last error it raised
+  error: string | null;
+}
+
+export interface ScriptsStatus {
+  dir: string;
+  scripts: ScriptStatus[];
+}
+
+// App-wide settings; also the payload of settings://changed
+export interface Settings {
+  playback: PlaybackSettings;
+  library: LibrarySettings;
+  logging: LoggingSettings;
+}
+
+export interface PlaybackSettings {
+  fade_ms: number; // 0–1000; 0 = shortest pop-free ramp
+  replaygain_mode: ReplayGainMode;
+  replaygain_preamp_db: number; // -15 to 15
+  clipping_prevention: boolean;
+  buffer_mode: BufferMode;
+  dsp_chain: DspChainEntry[]; // run after ReplayGain, in order
+}
+
+export interface LibrarySettings {
+  scan_on_startup: boolean;
+}
+
+// "auto" follows battery saver and the network (mobile); the others override
+export type BufferMode = "auto" | "standard" | "saver";
+
+export interface BufferPolicy {
+  ahead_ms: number; // decoded ahead of the output
+  refill_ms: number; // once full, decoding waits until down to this
+  prefetch_bytes: number; // of HTTP streams, ahead of the decoder
+}
+
+// Payload of buffering://changed too
+export interface BufferingStatus {
+  mode: BufferMode;
+  conditions: { power_saving: boolean; metered: boolean };
+  policy: BufferPolicy;
+}
+
+// A DSP plugin in the chain, by id
+export interface DspChainEntry {
+  plugin: string;
+  enabled: boolean;
+  params: Record<string, number>; // by parameter id; missing = default
+}
+
+export interface DspParam {
+  id: string;
+  name: string;
+  unit: string | null; // e.g. "dB", "Hz"
+  min: number;
+  max: number;
+  default_value: number;
+  step: number; // 0 = continuous
+}
+
+export interface DspPluginInfo {
+  id: string;
+  name: string;
+  version: string;
+  path: string | null;
+  params: DspParam[];
+}
+
+export interface DspPluginList {
+  folder: string; // where to put plugin libraries
+  plugins: DspPluginInfo[];
+  failed: { path: string; error: string }[];
+}
+
+export type ErrorCode =
+  | "playback_open"
+  | "playback_seek"
+  | "playback_decode" // playback stopped there
+  | "output_device"
+  | "replaygain_tags" // playing without gain
+  | "config_load" // defaults in use
+  | "save_failed"
+  | "library_open" // library is in memory until restart
+  | "recording"
+  | "dsp_plugin"; // left out of the chain
+
+// Payload of app://error
+export interface AppError {
+  code: ErrorCode;
+  message: string;
+  path: string | null; // file or URL it's about
+  timestamp_ms: number;
+}
+
+export type LogLevel = "error" | "warn" | "info" | "debug" | "trace";
+
+export interface LoggingSettings {
+  level: LogLevel; // lowest level written to the log file
+}
+
+export interface LogEntry {
+  timestamp_ms: number;
+  level: string; // "ERROR", "WARN", "INFO", "DEBUG" or "TRACE"
+  target: string; // Rust module the record came from
+  message: string;
+}
+
+// Payload of remote://command: an action a remote client ran, already
+// applied by the backend.
+export type RemoteAction =
+  | "play"
+  | "pause"
+  | "resume"
+  | "stop"
+  | "next"
+  | "previous"
+  | "seek"
+  | "set_volume"
+  | "enqueue"
+  | "clear_queue"
+  | "play_queue_index"
+  | "set_shuffle"
+  | "set_repeat_mode";
+
+// ─── DLNA media servers ───
+
+export interface DlnaServer {
+  name: string;
+  // Unique device name, stable across restarts and addresses
+  udn: string;
+  manufacturer: string | null;
+  model: string | null;
+  // URL of the device description
+  location: string;
+  control_url: string;
+  service_type: string;
+}
+
+export interface DlnaContainer {
+  id: string;
+  title: string;
+  child_count: number | null;
+  album_art_url: string | null;
+}
+
+export interface DlnaItem {
+  id: string;
+  title: string;
+  artist: string | null;
+  album: string | null;
+  track_number: number | null;
+  duration_secs: number | null;
+  album_art_url: string | null;
+  // Stream URL; play or queue it like a path
+  url: string;
+  mime_type: string | null;
+  sample_rate: number | null;
+  bit_depth: number | null;
+  channels: number | null;
+  size_bytes: number | null;
+}
+
+export interface DlnaBrowsePage {
+  containers: DlnaContainer[];
+  // Audio items only
+  items: DlnaItem[];
+  start: number;
+  // Entries the server returned, audio or not: the next page starts at start + returned
+  returned: number;
+  total: number;
+}
+
+// ─── Subsonic servers ───
+
+export interface SubsonicServer {
+  id: string;
+  name: string;
+  url: string;
+  username: string;
+  // Kbps streams are capped at; 0 streams originals
+  max_bit_rate: number;
+  // Counted plays are scrobbled to the server
+  scrobble: boolean;
+  // Server software and version, e.g. "navidrome 0.53.3"
+  server: string | null;
+}
+
+export interface SubsonicArtist {
+  id: string;
+  name: string;
+  album_count: number | null;
+  cover_art_url: string | null;
+}
+
+export interface SubsonicAlbum {
+  id: string;
+  name: string;
+  artist: string | null;
+  artist_id: string | null;
+  year: number | null;
+  genre: string | null;
+  song_count: number | null;
+  duration_secs: number | null;
+  cover_art_url: string | null;
+}
+
+export interface SubsonicSong {
+  id: string;
+  title: string;
+  artist: string | null;
+  album: string | null;
+  album_id: string | null;
+  track_number: number | null;
+  disc_number: number | null;
+  year: number | null;
+  duration_secs: number | null;
+  // Kbps of the original
+  bit_rate: number | null;
+  suffix: string | null;
+  content_type: string | null;
+  sample_rate: number | null;
+  bit_depth: number | null;
+  channels: number | null;
+  cover_art_url: string | null;
+  // Stream URL; play or queue it like a path
+  url: string;
+  // The stream is a server transcode rather than the original
+  transcoded: boolean;
+}
+
+export interface SubsonicSearchResults {
+  artists: SubsonicArtist[];
+  albums: SubsonicAlbum[];
+  songs: SubsonicSong[];
+}
+
+export type SubsonicAlbumList =
+  | "newest"
+  | "recent"
+  | "frequent"
+  | "random"
+  | "starred"
+  | "alphabetical_by_name"
+  | "alphabetical_by_artist";
+
+// ─── Jellyfin servers ───
+
+export interface JellyfinServer {
+  id: string;
+  name: string;
+  url: string;
+  // User whose libraries are shown
+  user_name: string;
+  version: string | null;
+}
+
+export interface JellyfinLibrary {
+  id: string;
+  name: string;
+}
+
+export interface JellyfinArtist {
+  id: string;
+  name: string;
+  image_url: string | null;
+}
+
+export interface JellyfinAlbum {
+  id: string;
+  name: string;
+  artist: string | null;
+  year: number | null;
+  track_count: number | null;
+  duration_secs: number | null;
+  image_url: string | null;
+}
+
+export interface JellyfinTrack {
+  id: string;
+  title: string;
+  artists: string[];
+  album_artist: string | null;
+  album: string | null;
+  album_id: string | null;
+  track_number: number | null;
+  disc_number: number | null;
+  year: number | null;
+  duration_secs: number | null;
+  // Original's codec, e.g. "flac"
+  codec: string | null;
+  sample_rate: number | null;
+  bit_depth: number | null;
+  channels: number | null;
+  bit_rate_kbps: number | null;
+  image_url: string | null;
+  // Stream URL; play or queue it like a path
+  url: string;
+  // The stream is a FLAC transcode rather than the original
+  transcoded: boolean;
+}
+
+export interface JellyfinPage<T> {
+  items: T[];
+  start: number;
+  total: number;
+}
+
+export interface JellyfinSearchResults {
+  artists: JellyfinArtist[];
+  albums: JellyfinAlbum[];
+  tracks: JellyfinTrack[];
+}
+
+// ─── Internet radio ───
+
+export interface RadioStation {
+  id: string;
+  name: string;
+  // Stream URL; play or queue it like a path
+  url: string;
+  group: string | null;
+  favorite: boolean;
+  added_at: number;
+}
+
+export interface RadioTitle {
+  // As the station announced it, usually "Artist - Title"
+  title: string;
+  at: number;
+}
+
+// Payload of radio://title, sent when a playing station announces a new title
+export interface RadioNowPlaying {
+  station_id: string;
+  title: string;
+  at: number;
 }
 
 // ─── Frontend-only types ───
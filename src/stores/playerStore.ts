@@ -1,11 +1,16 @@
 import { create } from "zustand";
-import type { TrackMetadata, RepeatMode } from "../lib/types";
+import type {
+  TrackMetadata,
+  RepeatMode,
+  QueueEntry,
+  QueueSnapshot,
+} from "../lib/types";
 import * as cmd from "../lib/tauri-commands";
 
 interface PlayerState {
   // Current track
   currentTrack: TrackMetadata | null;
-  albumArt: string | null;
+  albumArt: string | null; // art:// URL of its cover
 
   // Playback
   isPlaying: boolean;
@@ -18,8 +23,8 @@ interface PlayerState {
   isMuted: boolean;
   previousVolume: number;
 
-  // Queue
-  queue: TrackMetadata[];
+  // Queue, as kept by the backend
+  queue: QueueEntry[];
   queueIndex: number;
   shuffle: boolean;
   repeat: RepeatMode;
@@ -33,10 +38,11 @@ interface PlayerState {
   toggleMute: () => Promise<void>;
   nextTrack: () => Promise<void>;
   previousTrack: () => Promise<void>;
-  addToQueue: (tracks: TrackMetadata[]) => void;
-  clearQueue: () => void;
-  toggleShuffle: () => void;
-  cycleRepeat: () => void;
+  addToQueue: (paths: string[]) => Promise<void>;
+  clearQueue: () => Promise<void>;
+  toggleShuffle: () => Promise<void>;
+  cycleRepeat: () => Promise<void>;
+  syncQueue: () => Promise<void>;
 
   // Internal setters (called by useAudio hook)
   setPosition: (secs: number) => void;
@@ -64,26 +70,10 @@ export const usePlayerStore = create<PlayerState>((set, get) => ({
     const { queue } = get();
     if (index < 0 || index >= queue.length) return;
 
-    const track = queue[index];
-    set({
-      currentTrack: track,
-      queueIndex: index,
-      isPlaying: true,
-      isPaused: false,
-      positionSecs: 0,
-      durationSecs: track.duration_secs,
-      albumArt: null, // Clear while loading
-    });
-
     try {
-      await cmd.playFile(track.file_path);
-
-      // Load album art (with race condition guard)
-      const art = await cmd.getAlbumArtBase64(track.file_path);
-      // Verify this track is still current before setting art
-      if (get().currentTrack?.file_path === track.file_path) {
-        set({ albumArt: art });
-      }
+      await cmd.playQueueIndex(index);
+      set({ isPlaying: true, isPaused: false, positionSecs: 0 });
+      await get().syncQueue();
     } catch (e) {
       console.error("Failed to play track:", e);
     }
@@ -152,84 +142,108 @@ export const usePlayerStore = create<PlayerState>((set, get) => ({
     }
   },
 
+  // The backend picks the entry by shuffle and repeat, and keeps history
   nextTrack: async () => {
-    const { queue, queueIndex, repeat, shuffle } = get();
-    if (queue.length === 0) return;
-
-    let nextIndex: number;
-
-    if (repeat === "one") {
-      nextIndex = queueIndex;
-    } else if (shuffle) {
-      // Pick random different index
-      if (queue.length === 1) {
-        nextIndex = 0;
-      } else {
-        do {
-          nextIndex = Math.floor(Math.random() * queue.length);
-        } while (nextIndex === queueIndex);
-      }
-    } else {
-      nextIndex = queueIndex + 1;
-      if (nextIndex >= queue.length) {
-        if (repeat === "all") {
-          nextIndex = 0;
-        } else {
-          // End of queue, stop
-          set({ isPlaying: false, isPaused: false });
-          return;
-        }
+    try {
+      const entry = await cmd.nextTrack();
+      if (entry === null) {
+        // End of the queue: playback is left alone
+        return;
       }
+      set({ isPlaying: true, isPaused: false, positionSecs: 0 });
+      await get().syncQueue();
+    } catch (e) {
+      console.error("Next track failed:", e);
     }
-
-    await get().playTrack(nextIndex);
   },
 
   previousTrack: async () => {
-    const { queue, queueIndex, positionSecs } = get();
-    if (queue.length === 0) return;
-
-    // If more than 3 seconds in, restart current track
-    if (positionSecs > 3) {
-      await get().seekTo(0);
-      return;
+    try {
+      await cmd.previousTrack();
+      await get().syncQueue();
+    } catch (e) {
+      console.error("Previous track failed:", e);
     }
+  },
 
-    const prevIndex = queueIndex - 1;
-    if (prevIndex >= 0) {
-      await get().playTrack(prevIndex);
-    } else {
-      await get().seekTo(0);
+  addToQueue: async (paths: string[]) => {
+    try {
+      const report = await cmd.addToQueue(paths);
+      for (const error of report.errors) {
+        console.error("Failed to queue:", error);
+      }
+      await get().syncQueue();
+    } catch (e) {
+      console.error("Add to queue failed:", e);
     }
   },
 
-  addToQueue: (tracks: TrackMetadata[]) => {
-    set((state) => ({
-      queue: [...state.queue, ...tracks],
-    }));
+  clearQueue: async () => {
+    try {
+      await cmd.clearQueue();
+      set({ queue: [], queueIndex: -1 });
+    } catch (e) {
+      console.error("Clear queue failed:", e);
+    }
   },
 
-  clearQueue: () => {
-    set({
-      queue: [],
-      queueIndex: -1,
-      currentTrack: null,
-      albumArt: null,
-      isPlaying: false,
-      isPaused: false,
-    });
+  toggleShuffle: async () => {
+    const shuffle = !get().shuffle;
+    try {
+      await cmd.setShuffle(shuffle);
+      set({ shuffle });
+    } catch (e) {
+      console.error("Set shuffle failed:", e);
+    }
   },
 
-  toggleShuffle: () => {
-    set((state) => ({ shuffle: !state.shuffle }));
+  cycleRepeat: async () => {
+    const modes: RepeatMode[] = ["off", "all", "one"];
+    const repeat = modes[(modes.indexOf(get().repeat) + 1) % modes.length];
+    try {
+      await cmd.setRepeatMode(repeat);
+      set({ repeat });
+    } catch (e) {
+      console.error("Set repeat failed:", e);
+    }
   },
 
-  cycleRepeat: () => {
-    set((state) => {
-      const modes: RepeatMode[] = ["off", "all", "one"];
-      const idx = modes.indexOf(state.repeat);
-      return { repeat: modes[(idx + 1) % modes.length] };
+  // Fetch the backend queue, and the tags of its current entry when that
+  // changed: it also moves on at the end of a track, and from media keys
+  syncQueue: async () => {
+    let snapshot: QueueSnapshot;
+    try {
+      snapshot = await cmd.getQueue();
+    } catch (e) {
+      console.error("Failed to get queue:", e);
+      return;
+    }
+    const queueIndex = snapshot.current_index ?? -1;
+    const entry = snapshot.entries[queueIndex] ?? null;
+    const previous = get().queue[get().queueIndex] ?? null;
+    set({
+      queue: snapshot.entries,
+      queueIndex,
+      shuffle: snapshot.shuffle,
+      repeat: snapshot.repeat,
     });
+    if (entry?.id === previous?.id && get().currentTrack !== null) return;
+
+    if (entry === null) {
+      set({ currentTrack: null, albumArt: null });
+      return;
+    }
+    try {
+      const track = await cmd.readFileMetadata(entry.path);
+      set({
+        currentTrack: track,
+        durationSecs: track.duration_secs,
+        albumArt: cmd.trackArtUrl(entry.path),
+      });
+    } catch {
+      // A stream, or a file gone since it was queued
+      set({ currentTrack: null, albumArt: null });
+    }
   },
 
   // Internal setters